@@ -1,7 +1,9 @@
 use criterion::{Criterion, criterion_group, criterion_main};
-use ethel::state::data::{Column, IndexArrayColumn, ParallelIndexArrayColumn, column::IterColumn};
+use ethel::state::data::{
+    Column, IndexArrayColumn, ParallelIndexArrayColumn, column::IterColumn, join,
+};
 
-criterion_group!(col_benches, col_iteration);
+criterion_group!(col_benches, col_iteration, col_locality);
 criterion_main!(col_benches);
 
 fn col_iteration(cr: &mut Criterion) {
@@ -81,6 +83,55 @@ fn col_iteration(cr: &mut Criterion) {
     });
 }
 
+fn col_locality(cr: &mut Criterion) {
+    const COUNT: usize = 100_000;
+
+    // two columns sharing their slots, with `a` churned so that its
+    // contiguous order no longer follows the order of `b`
+    let churned = || {
+        let mut a = ParallelIndexArrayColumn::with_capacity(COUNT);
+        let mut b = ParallelIndexArrayColumn::with_capacity(COUNT);
+        let slots = (1..=COUNT)
+            .map(|i| {
+                b.insert(Data::new(i));
+                a.insert(Data::new(i))
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = 0x9e37_79b9u32;
+        for _ in 0..COUNT / 2 {
+            rng ^= rng << 13;
+            rng ^= rng >> 17;
+            rng ^= rng << 5;
+            let slot = slots[rng as usize % COUNT];
+            if a.solve_indirect(slot).is_some() {
+                a.free(slot);
+                a.insert(Data::new(slot.as_index()));
+            }
+        }
+        (a, b)
+    };
+
+    let join_sum = |a: &ParallelIndexArrayColumn<Data>, b: &ParallelIndexArrayColumn<Data>| {
+        let mut sum = 0i128;
+        join::join(a, b).for_each(|(_, a, b)| {
+            sum += op(a) + op(b);
+        });
+        sum
+    };
+
+    cr.bench_function("join_after_churn", |bench| {
+        let (a, b) = churned();
+        bench.iter(|| std::hint::black_box(join_sum(&a, &b)))
+    });
+
+    cr.bench_function("join_after_sort_by_owner", |bench| {
+        let (mut a, b) = churned();
+        a.sort_by_owner();
+        bench.iter(|| std::hint::black_box(join_sum(&a, &b)))
+    });
+}
+
 #[repr(C)]
 #[derive(Clone, Debug, Default)]
 struct Data {
@@ -0,0 +1,53 @@
+//! Compares uploading whole columns with uploading only their dirty ranges.
+//!
+//! The blits go through a [`PartitionedTriBuffer::detached`] buffer, whose
+//! sections are in system memory, as the bandwidth of persistently mapped
+//! memory is proportional to the bytes copied.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use ethel::{
+    render::buffer::{Layout, PartitionedTriBuffer},
+    state::data::DirtyRanges,
+};
+use glam::Vec4;
+
+criterion_group!(dirty_benches, upload_positions);
+criterion_main!(dirty_benches);
+
+fn upload_positions(cr: &mut Criterion) {
+    const COUNT: usize = 1_000_000;
+
+    let positions = (0..COUNT)
+        .map(|i| Vec4::new(i as f32, (i / 2) as f32, -(i as f32), 1.0))
+        .collect::<Vec<_>>();
+    let buffer = PartitionedTriBuffer::detached(Layout::<1>::new().partition::<Vec4>(COUNT));
+
+    cr.bench_function("upload_full", |b| {
+        b.iter(|| {
+            let written = unsafe { buffer.blit_part_at(0, 0, 0, &positions) };
+            std::hint::black_box(written);
+        })
+    });
+
+    // 2% of the entities move, clustered after sorting by locality
+    cr.bench_function("upload_dirty_clustered", |b| {
+        let mut dirty = DirtyRanges::new();
+        unsafe { dirty.upload_part(&buffer, 0, 0, &positions) };
+        b.iter(|| {
+            (0..COUNT / 50).for_each(|i| dirty.mark(COUNT / 2 + i));
+            let written = unsafe { dirty.upload_part(&buffer, 0, 0, &positions) };
+            std::hint::black_box(written);
+        })
+    });
+
+    // 0.1% of the entities move, scattered across the column
+    cr.bench_function("upload_dirty_scattered", |b| {
+        let mut dirty = DirtyRanges::new();
+        unsafe { dirty.upload_part(&buffer, 0, 0, &positions) };
+        b.iter(|| {
+            (0..COUNT / 1000).for_each(|i| dirty.mark((i * 7919) % COUNT));
+            let written = unsafe { dirty.upload_part(&buffer, 0, 0, &positions) };
+            std::hint::black_box(written);
+        })
+    });
+}
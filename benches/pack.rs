@@ -0,0 +1,65 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use ethel::render::buffer::pack::{self, PackedTransform};
+use glam::{Quat, Vec3, Vec4};
+
+criterion_group!(pack_benches, pack_transforms);
+criterion_main!(pack_benches);
+
+fn pack_transforms(cr: &mut Criterion) {
+    const COUNT: usize = 100_000;
+
+    let positions = (0..COUNT)
+        .map(|i| Vec3::new(i as f32, (i / 2) as f32, -(i as f32)))
+        .collect::<Vec<_>>();
+    let rotations = (0..COUNT)
+        .map(|i| Quat::from_rotation_y(i as f32 * 0.001))
+        .collect::<Vec<_>>();
+
+    cr.bench_function("pack_vec3_scalar", |b| {
+        let mut dst = vec![[0.0f32; 4]; COUNT];
+        b.iter(|| {
+            positions.iter().zip(dst.iter_mut()).for_each(|(p, d)| {
+                *d = [p.x, p.y, p.z, 1.0];
+            });
+            std::hint::black_box(&dst);
+        })
+    });
+
+    cr.bench_function("pack_vec3_chunked", |b| {
+        let mut dst = vec![Vec4::ZERO; COUNT];
+        b.iter(|| {
+            pack::pack_vec3(&positions, 1.0, &mut dst);
+            std::hint::black_box(&dst);
+        })
+    });
+
+    cr.bench_function("pack_transforms_scalar", |b| {
+        let mut dst = vec![[[0.0f32; 4]; 2]; COUNT];
+        b.iter(|| {
+            positions
+                .iter()
+                .zip(rotations.iter())
+                .zip(dst.iter_mut())
+                .for_each(|((p, r), d)| {
+                    *d = [[p.x, p.y, p.z, 1.0], [r.x, r.y, r.z, r.w]];
+                });
+            std::hint::black_box(&dst);
+        })
+    });
+
+    cr.bench_function("pack_transforms_chunked", |b| {
+        let mut dst = vec![PackedTransform::default(); COUNT];
+        b.iter(|| {
+            pack::pack_transforms(&positions, &rotations, &mut dst);
+            std::hint::black_box(&dst);
+        })
+    });
+
+    cr.bench_function("pack_transform_matrices_chunked", |b| {
+        let mut dst = vec![glam::Mat4::IDENTITY; COUNT];
+        b.iter(|| {
+            pack::pack_transform_matrices(&positions, &rotations, &mut dst);
+            std::hint::black_box(&dst);
+        })
+    });
+}
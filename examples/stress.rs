@@ -0,0 +1,991 @@
+//! Stress test scene and performance regression harness.
+//!
+//! Spawns a large amount of entities (100k by default, or the amount given as
+//! the first argument) bouncing inside of a box, each using one of a few mesh
+//! families. Every upload, entities are culled against the view frustum on
+//! the CPU, assigned a level of detail depending on their distance from the
+//! camera, and packed into contiguous instance ranges: one indirect draw
+//! command is issued per mesh family and level of detail.
+//!
+//! Statistics are printed once per second: simulation, culling and upload
+//! times on the simulation thread, and frame times on the render thread.
+//!
+//! The optional second argument selects the [`UploadMode`] of the triple
+//! buffers, to compare persistent mapping, buffer orphaning and explicit
+//! flushing:
+//!
+//! ```sh
+//! cargo run --release --features stress --example stress -- 250000
+//! cargo run --release --features stress --example stress -- 250000 orphaning
+//! cargo run --release --features stress --example stress -- 250000 explicit_flush
+//! ```
+//!
+//! The optional third argument runs a benchmark of the given amount of
+//! seconds instead: the camera orbits the box along a fixed path, and a
+//! summary of the frame times, with the 1% and 0.1% lows, is printed and
+//! written to `stress-bench.txt` before exiting.
+//!
+//! ```sh
+//! cargo run --release --features stress --example stress -- 250000 persistent 30
+//! ```
+//!
+//! With the `--gpu-culling` flag, culling and level of detail selection run
+//! in a compute shader instead (see [`ethel::render::cull`]): entities are
+//! uploaded in their table order, and the vertex shader reads the id of the
+//! entity of each instance from the culling output.
+//!
+//! ```sh
+//! cargo run --release --features stress --example stress -- 250000 --gpu-culling
+//! ```
+//!
+//! The example is only built with the `stress` feature, since it has not
+//! been verified against the window and context API of the current janus
+//! revision.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use ethel::{
+    InputSystem, RenderHandler, StartupHandler, StateHandler,
+    mesh::{self, MeshStaging, Vertex},
+    render::{
+        Renderer, Resolution, ScreenSpace,
+        buffer::{
+            PartitionedTriBuffer, StorageSection, TriBuffer, UploadMode, overflow,
+            pack::{self, PackedTransform},
+            packed::Half4,
+            validate,
+        },
+        clear::ClearPolicy,
+        color::Color,
+        command::{DrawArraysIndirectCommand, DrawGroups, GpuCommandDispatch, GpuCommandQueue},
+        config::BufferConfig,
+        cull::{self, CullInput, CullParams, GpuCuller},
+        frame::{FrameHeaderBuffer, GLSL_SSBO_FRAME_HEADER, GLSL_UBO_FRAME_GLOBALS},
+        material::{self, Material, MaterialId, MaterialStorage},
+    },
+    shader::{GlslUniform, ShaderKind, ShaderProgram},
+    state::{
+        State,
+        arena::StagingArena,
+        bench::{BenchRecorder, CameraPath},
+        camera::ViewPoint,
+        cross::{Cross, Producer},
+        data::{DirtyRanges, IndirectIndex},
+        stats,
+    },
+};
+use glam::{Mat4, Quat, Vec3, Vec4, Vec4Swizzles};
+use janus::{
+    context::{DeltaTime, Setup},
+    sync::{Mirror, TriCell},
+};
+
+const DEFAULT_ENTITIES: usize = 100_000;
+
+/// Half extent of the box entities bounce in.
+const BOUNDS: Vec3 = Vec3::new(400.0, 150.0, 400.0);
+/// The camera sits in front of the box, looking down the negative Z axis.
+const CAMERA_POSITION: Vec3 = Vec3::new(0.0, 0.0, BOUNDS.z + 60.0);
+/// The file the benchmark report is written to.
+const BENCH_REPORT: &str = "stress-bench.txt";
+
+/// Radius of the bounding sphere of all unscaled meshes.
+const ENTITY_RADIUS: f32 = 1.0;
+
+const LODS: usize = 3;
+/// Maximum distance from the camera of each level of detail.
+const LOD_DISTANCES: [f32; LODS - 1] = [80.0, 250.0];
+
+const FAMILIES: usize = 2;
+const BUCKETS: usize = FAMILIES * LODS;
+
+const SHADER_BINDING_INSTANCES: u32 = 2;
+const SHADER_BINDING_SCALES: u32 = 3;
+
+ethel::layout_buffer! {
+    const InstanceData: 5, {
+        enum transforms: DEFAULT_ENTITIES => {
+            type PackedTransform;
+            bind 0;
+            shader SHADER_BINDING_INSTANCES;
+            glsl Transform => TransformGlslStruct::as_definition();
+        };
+        enum materials: FAMILIES + 1 => {
+            type Material;
+            bind 1;
+            shader material::SHADER_BINDING_MATERIALS;
+            glsl Material => material::MaterialGlslStruct::as_definition();
+        };
+        enum entity_materials: DEFAULT_ENTITIES => {
+            type MaterialId;
+            bind 2;
+            shader material::SHADER_BINDING_ENTITY_MATERIALS;
+            glsl uint;
+        };
+        enum scales: DEFAULT_ENTITIES => {
+            type Half4;
+            bind 3;
+            shader SHADER_BINDING_SCALES;
+            packed Vec4;
+        };
+        enum cull_inputs: DEFAULT_ENTITIES => {
+            type CullInput;
+            bind 4;
+            shader cull::SHADER_BINDING_CULL_INPUTS;
+            glsl CullInput => cull::CullInputGlslStruct::as_definition();
+        };
+    }
+}
+
+ethel::table_spec! {
+    struct Bodies {
+        position: Vec3;
+        velocity: Vec3;
+        rotation: Quat;
+        scale: Vec3;
+        spin: Quat;
+        family: u32;
+        material: MaterialId;
+    }
+}
+
+ethel::shader_glsl_struct! {
+    struct Transform for PackedTransform {
+        position: [f32; 4] => vec4;
+        rotation: [f32; 4] => vec4;
+    }
+}
+
+ethel::shader_glsl! {
+    struct Stress > [460] {
+        common {
+            uniform {
+                length 1, view_projection: mat4 => Mat4;
+            };
+
+            type {
+                mesh::VertexGlslStruct::as_definition()
+            };
+
+            ssbo {
+                { mesh::GLSL_SSBO_INTEGRATION[0].clone() }
+                { mesh::GLSL_SSBO_INTEGRATION[1].clone() }
+                { GLSL_SSBO_FRAME_HEADER }
+                { GLSL_UBO_FRAME_GLOBALS }
+                { LayoutInstanceData::glsl_include() }
+                { cull::GLSL_SSBO_CULL_VISIBLE }
+            };
+        };
+
+        unit ShaderKind::Vertex => [
+            attribs {
+                ethel::shader_glsl_attribs! {
+                    output v_normal: vec3;
+                    output v_tint: vec3;
+                }
+            };
+
+            uniform {
+                length 1, gpu_culling: uint => u32;
+            };
+
+            lib {
+                cull::GLSL_LIB_CULLED_ENTITY;
+                material::GLSL_LIB_ENTITY_MATERIAL;
+                pack::GLSL_LIB_TRS_MATRIX;
+                ethel::shader_glsl_lib! {
+                    vec3 rotate [ q: vec4, v: vec3 ] => "
+                        vec3 t = 2.0 * cross(q.xyz, v);
+                        return v + q.w * t + cross(q.xyz, t);
+                    "
+                };
+            };
+
+            src() "
+                uint instance = gl_BaseInstance + gl_InstanceID;
+                // entities are packed per draw, unless culled on the GPU
+                uint entity = gpu_culling != 0u ? culled_entity(instance) : instance;
+                if (entity >= entity_count) {
+                    gl_Position = vec4(0.0);
+                    return;
+                }
+
+                Vertex vertex = vertex_storage[gl_VertexID];
+                Transform transform = fetch_transforms(entity);
+                vec4 scale = fetch_scales(entity);
+
+                mat4 model = trs_matrix(transform.position, transform.rotation, scale);
+                gl_Position = view_projection * model * vertex.position;
+
+                // one draw per family and level of detail
+                const vec3 LOD_TINTS[3] = vec3[](
+                    vec3(0.9, 0.9, 0.9),
+                    vec3(0.4, 0.8, 0.4),
+                    vec3(0.8, 0.4, 0.4)
+                );
+                float pulse = 0.9 + 0.1 * sin(globals.time * 2.0 + float(entity));
+                vec3 albedo = entity_material(entity).albedo.rgb;
+                v_tint = LOD_TINTS[gl_DrawID % 3] * albedo * pulse;
+                v_normal = rotate(transform.rotation, vertex.normal.xyz / scale.xyz);
+            "
+        ];
+
+        unit ShaderKind::Pixel => [
+            attribs {
+                ethel::shader_glsl_attribs! {
+                    input v_normal: vec3;
+                    input v_tint: vec3;
+                    output out_color: vec4;
+                }
+            };
+
+            src() "
+                vec3 light = normalize(vec3(0.3, 1.0, 0.5));
+                float diffuse = max(dot(normalize(v_normal), light), 0.0);
+                out_color = vec4(v_tint * (0.2 + 0.8 * diffuse), 1.0);
+            "
+        ];
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Groups;
+
+impl std::fmt::Display for Groups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl DrawGroups for Groups {
+    fn as_str(&self) -> &'static str {
+        "entities"
+    }
+}
+
+/// The shared data is created with [`Default`] before the GL context exists,
+/// and replaced by [`SharedData::new`] during startup.
+#[derive(Debug, Default)]
+struct SharedData {
+    instances: PartitionedTriBuffer<5>,
+    draw_commands: TriBuffer<DrawArraysIndirectCommand>,
+    header: FrameHeaderBuffer,
+}
+
+impl SharedData {
+    fn new() -> Self {
+        let config = buffer_config();
+
+        let layout = LayoutInstanceData::create_with([
+            config.entity_capacity(),
+            FAMILIES + 1,
+            config.entity_capacity(),
+            config.entity_capacity(),
+            config.entity_capacity(),
+        ]);
+        config
+            .validate_layout(&layout)
+            .expect("instance buffer exceeds the GL limits");
+
+        // grown by the exclusive hook registered in `main` when spawning
+        // outgrows the configured capacity
+        let instances = PartitionedTriBuffer::new(layout).with_auto_grow();
+        LayoutInstanceData::initialise_partitions(&instances);
+
+        Self {
+            instances,
+            draw_commands: TriBuffer::zeroed(config.command_capacity()),
+            header: FrameHeaderBuffer::new(),
+        }
+    }
+}
+
+/// The amount of entities requested on the command line.
+static ENTITIES: AtomicUsize = AtomicUsize::new(DEFAULT_ENTITIES);
+/// Whether culling runs on the GPU, see [`cull`].
+static GPU_CULLING: AtomicBool = AtomicBool::new(false);
+/// The duration of the benchmark requested on the command line, in seconds,
+/// `0` if not benchmarking.
+static BENCH_SECONDS: AtomicU64 = AtomicU64::new(0);
+
+fn bench_duration() -> Option<Duration> {
+    match BENCH_SECONDS.load(Ordering::Relaxed) {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    }
+}
+
+/// The camera path of benchmarks: a full orbit around the box, slightly
+/// above it.
+fn bench_path(duration: Duration) -> CameraPath {
+    CameraPath::orbit(Vec3::ZERO, CAMERA_POSITION.z, BOUNDS.y, duration)
+}
+
+fn buffer_config() -> BufferConfig {
+    BufferConfig::new()
+        .entities(ENTITIES.load(Ordering::Relaxed))
+        .meshes(FAMILIES * LODS)
+        .vertices(4096)
+        .commands(BUCKETS)
+        .headroom(1.0)
+}
+
+/// A view frustum made of its side and near planes.
+///
+/// There is no far plane, as the projection has an infinite far plane.
+#[derive(Clone, Copy, Debug, Default)]
+struct Frustum {
+    planes: [Vec4; 5],
+}
+
+impl Frustum {
+    fn from_view_projection(m: Mat4) -> Self {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 - r2].map(|p| p / p.xyz().length());
+        Self { planes }
+    }
+
+    #[inline]
+    fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.xyz().dot(center) + p.w >= -radius)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    since: Option<Instant>,
+    uploads: u32,
+    step_time: Duration,
+    cull_time: Duration,
+    upload_time: Duration,
+    visible: [usize; LODS],
+    dropped: usize,
+}
+
+impl Stats {
+    fn report(&mut self, entities: usize) {
+        let since = *self.since.get_or_insert_with(Instant::now);
+        if since.elapsed() < Duration::from_secs(1) || self.uploads == 0 {
+            return;
+        }
+
+        let n = self.uploads;
+        println!(
+            "[sim] entities: {entities} | visible per lod: {:?} | step: {:.2?} | cull: {:.2?} | upload: {:.2?} | dropped: {} | uploads/s: {n}",
+            self.visible.map(|v| v / n as usize),
+            self.step_time / n,
+            self.cull_time / n,
+            self.upload_time / n,
+            self.dropped,
+        );
+        *self = Self {
+            since: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+}
+
+/// Deterministic xorshift generator, so that runs are comparable.
+struct Rng(u32);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+
+    /// A vector with all components in the range (-1, 1).
+    fn next_vec3(&mut self) -> Vec3 {
+        Vec3::new(self.next_f32(), self.next_f32(), self.next_f32()) * 2.0 - Vec3::ONE
+    }
+}
+
+#[derive(Debug, Default)]
+struct StressState {
+    bodies: BodiesRowTable,
+    meshes: [[mesh::Metadata; LODS]; FAMILIES],
+    materials: MaterialStorage,
+    frustum: Frustum,
+    /// The camera position of the current frame.
+    camera: Vec3,
+    /// The benchmark camera path, and when it started.
+    bench: Option<(CameraPath, Instant)>,
+    stats: Stats,
+    /// The transforms changed since each section was last uploaded in table
+    /// order, see [`Self::upload_for_gpu_culling`].
+    transforms: DirtyRanges,
+}
+
+impl StressState {
+    fn spawn(&mut self, count: usize) {
+        let mut rng = Rng(0x2545_f491);
+        let materials = [
+            Material::new(Color::linear(0.9, 0.6, 0.3, 1.0)).with_roughness(0.3),
+            Material::new(Color::linear(0.3, 0.6, 0.9, 1.0)).with_roughness(0.8),
+        ]
+        .map(|material| self.materials.insert(material));
+        let rows = (0..count).map(|i| {
+            let axis = rng.next_vec3().normalize_or(Vec3::Y);
+            // the first quarter of the bodies stays still, and is only
+            // uploaded when it changes
+            let moving = if i < count / 4 { 0.0 } else { 1.0 };
+            BodiesTableDef::builder()
+                .position(rng.next_vec3() * BOUNDS)
+                .velocity(rng.next_vec3() * 20.0 * moving)
+                .rotation(Quat::IDENTITY)
+                .scale(Vec3::splat(0.5) + rng.next_vec3().abs())
+                .spin(Quat::from_axis_angle(axis, rng.next_f32() * 0.05 * moving))
+                .family((i % FAMILIES) as u32)
+                .material(materials[i % FAMILIES])
+                .build()
+        });
+        self.bodies.insert_rows(rows);
+        self.transforms.mark_all();
+    }
+
+    fn lod_of(distance: f32) -> usize {
+        LOD_DISTANCES
+            .iter()
+            .position(|&max| distance < max)
+            .unwrap_or(LODS - 1)
+    }
+
+    /// Upload all entities in their table order, with the draw commands to
+    /// fill by the culling shader.
+    ///
+    /// Only the transforms changed since the section was last uploaded are
+    /// written.
+    fn upload_for_gpu_culling(
+        &mut self,
+        frame_boundary: &Cross<Producer, SharedData>,
+        arena: &StagingArena,
+    ) {
+        let start = Instant::now();
+
+        let positions = &self.bodies.position[1..];
+        let rotations = &self.bodies.rotation[1..];
+        let scales = &self.bodies.scale[1..];
+        let families = &self.bodies.family[1..];
+        let materials = &self.bodies.material[1..];
+
+        let transforms = arena.alloc_slice_from_iter(
+            positions
+                .iter()
+                .zip(rotations)
+                .map(|(&position, &rotation)| PackedTransform::new(position, rotation)),
+        );
+        let entity_scales = arena.alloc_slice_from_iter(scales.iter().map(|s| s.extend(0.0)));
+        let entities = positions.iter().zip(scales).zip(families);
+        let inputs = arena.alloc_slice_from_iter(entities.map(|((&position, &scale), &family)| {
+            let radius = ENTITY_RADIUS * scale.max_element();
+            CullInput::new(position, radius, family * LODS as u32, LODS as u32)
+        }));
+
+        // any entity of a family can select any of its levels of detail
+        let mut family_counts = [0u32; FAMILIES];
+        for &family in families {
+            family_counts[family as usize] += 1;
+        }
+        let mut commands = std::array::from_fn::<_, BUCKETS, _>(|bucket| {
+            let mesh = &self.meshes[bucket / LODS][bucket % LODS];
+            DrawArraysIndirectCommand::from_metadata(mesh, 0, 0)
+        });
+        let capacities =
+            std::array::from_fn::<_, BUCKETS, _>(|bucket| family_counts[bucket / LODS]);
+        cull::prepare_commands(&mut commands, &capacities);
+        let pack_time = start.elapsed();
+
+        stats::record_draws(BUCKETS as u32);
+
+        validate::validate("transforms", transforms);
+        validate::validate("scales", entity_scales);
+
+        let material_table = &self.materials;
+        let dirty_transforms = &mut self.transforms;
+        frame_boundary.cross(|section, storage| {
+            let index = section.as_index();
+            // SAFETY: the partitions are indexed through their layout enum.
+            unsafe {
+                dirty_transforms.upload_part(
+                    &storage.instances,
+                    index,
+                    LayoutInstanceData::Transforms as usize,
+                    transforms,
+                );
+                storage.instances.blit_part(
+                    index,
+                    LayoutInstanceData::EntityMaterials as usize,
+                    materials,
+                    0,
+                );
+                LayoutInstanceData::blit_scales(&storage.instances, index, entity_scales, 0);
+                storage.instances.blit_part(
+                    index,
+                    LayoutInstanceData::CullInputs as usize,
+                    inputs,
+                    0,
+                );
+                material_table.blit_to(
+                    &storage.instances,
+                    index,
+                    LayoutInstanceData::Materials as usize,
+                );
+            }
+            storage.draw_commands.blit_section(index, &commands, 0);
+            storage
+                .header
+                .upload(section, positions.len() as u32, BUCKETS as u32, self.camera);
+        });
+
+        let stats = &mut self.stats;
+        stats.uploads += 1;
+        stats.cull_time += pack_time;
+        stats.upload_time += start.elapsed() - pack_time;
+        stats.dropped += overflow::dropped_elements();
+        stats.report(positions.len());
+    }
+}
+
+impl StateHandler<SharedData, Groups> for StressState {
+    fn upload_gpu(
+        &mut self,
+        frame_boundary: &Cross<Producer, SharedData>,
+        _command_queue: &mut GpuCommandQueue<ethel::DrawCommand, Groups>,
+        arena: &StagingArena,
+    ) {
+        if GPU_CULLING.load(Ordering::Relaxed) {
+            self.upload_for_gpu_culling(frame_boundary, arena);
+            return;
+        }
+        let start = Instant::now();
+
+        let positions = &self.bodies.position[1..];
+        let rotations = &self.bodies.rotation[1..];
+        let scales = &self.bodies.scale[1..];
+        let families = &self.bodies.family[1..];
+        let materials = &self.bodies.material[1..];
+
+        // cull and assign each entity to its bucket
+        let mut counts = [0u32; BUCKETS];
+        let entities = positions.iter().zip(scales).zip(families);
+        let buckets =
+            arena.alloc_slice_from_iter(entities.map(|((&position, &scale), &family)| {
+                let radius = ENTITY_RADIUS * scale.max_element();
+                if !self.frustum.intersects_sphere(position, radius) {
+                    return u8::MAX;
+                }
+                let lod = Self::lod_of(position.distance(self.camera));
+                let bucket = family as usize * LODS + lod;
+                counts[bucket] += 1;
+                bucket as u8
+            }));
+
+        let mut offsets = [0u32; BUCKETS];
+        for i in 1..BUCKETS {
+            offsets[i] = offsets[i - 1] + counts[i - 1];
+        }
+        let visible = (offsets[BUCKETS - 1] + counts[BUCKETS - 1]) as usize;
+
+        // pack transforms, materials and scales in contiguous instance ranges per
+        // bucket
+        let instances = arena.alloc_slice_fill(visible, PackedTransform::default());
+        let instance_materials = arena.alloc_slice_fill(visible, MaterialId::DEFAULT);
+        let instance_scales = arena.alloc_slice_fill(visible, Vec4::ONE);
+        let mut heads = offsets;
+        for (i, &bucket) in buckets.iter().enumerate() {
+            if bucket == u8::MAX {
+                continue;
+            }
+            let head = &mut heads[bucket as usize];
+            instances[*head as usize] = PackedTransform::new(positions[i], rotations[i]);
+            instance_materials[*head as usize] = materials[i];
+            instance_scales[*head as usize] = scales[i].extend(0.0);
+            *head += 1;
+        }
+
+        let commands = std::array::from_fn::<_, BUCKETS, _>(|bucket| {
+            let mesh = &self.meshes[bucket / LODS][bucket % LODS];
+            DrawArraysIndirectCommand::from_metadata(mesh, counts[bucket], offsets[bucket])
+        });
+        let cull_time = start.elapsed();
+
+        stats::record_draws(BUCKETS as u32);
+        stats::record_culled((positions.len() - visible) as u32);
+
+        validate::validate("transforms", instances);
+        validate::validate("scales", instance_scales);
+        validate::validate("draw commands", &commands);
+
+        // the transforms are packed per bucket, and no longer in table order
+        self.transforms.mark_all();
+
+        let material_table = &self.materials;
+        frame_boundary.cross(|section, storage| {
+            let index = section.as_index();
+            // SAFETY: the partitions are indexed through their layout enum.
+            unsafe {
+                storage.instances.blit_part(
+                    index,
+                    LayoutInstanceData::Transforms as usize,
+                    instances,
+                    0,
+                );
+                storage.instances.blit_part(
+                    index,
+                    LayoutInstanceData::EntityMaterials as usize,
+                    instance_materials,
+                    0,
+                );
+                LayoutInstanceData::blit_scales(&storage.instances, index, instance_scales, 0);
+                material_table.blit_to(
+                    &storage.instances,
+                    index,
+                    LayoutInstanceData::Materials as usize,
+                );
+            }
+            storage.draw_commands.blit_section(index, &commands, 0);
+            storage
+                .header
+                .upload(section, visible as u32, BUCKETS as u32, self.camera);
+        });
+
+        let stats = &mut self.stats;
+        stats.uploads += 1;
+        stats.cull_time += cull_time;
+        stats.upload_time += start.elapsed() - cull_time;
+        stats.dropped += overflow::dropped_elements();
+        for (bucket, count) in counts.iter().enumerate() {
+            stats.visible[bucket % LODS] += *count as usize;
+        }
+        stats.report(positions.len());
+    }
+
+    fn fixed_step(
+        &mut self,
+        _input: &mut InputSystem,
+        _screen: &mut Mirror<ScreenSpace>,
+        _view_point: &TriCell<ViewPoint>,
+        _delta: DeltaTime,
+    ) {
+        let start = Instant::now();
+        let dt = self.step_duration().as_secs_f32();
+
+        let bodies = &mut self.bodies;
+        let dirty = &mut self.transforms;
+        let positions = bodies.position[1..].iter_mut();
+        let velocities = bodies.velocity[1..].iter_mut();
+        for (i, (position, velocity)) in positions.zip(velocities).enumerate() {
+            if *velocity == Vec3::ZERO {
+                continue;
+            }
+            dirty.mark(i);
+            *position += *velocity * dt;
+
+            let outside = position.abs().cmpgt(BOUNDS);
+            *velocity = Vec3::select(outside, -*velocity, *velocity);
+            *position = position.clamp(-BOUNDS, BOUNDS);
+        }
+
+        let rotations = bodies.rotation[1..].iter_mut();
+        for (i, (rotation, spin)) in rotations.zip(&bodies.spin[1..]).enumerate() {
+            if *spin == Quat::IDENTITY {
+                continue;
+            }
+            dirty.mark(i);
+            *rotation = (*spin * *rotation).normalize();
+        }
+
+        self.stats.step_time += start.elapsed();
+    }
+
+    fn destroy_entity(&mut self, entity: IndirectIndex) -> bool {
+        let removed = self.bodies.remove(entity).is_some();
+        if removed {
+            // the rows after it moved
+            self.transforms.mark_all();
+        }
+        removed
+    }
+
+    fn on_new_frame(
+        &mut self,
+        _input: &mut InputSystem,
+        screen: &mut Mirror<ScreenSpace>,
+        view_point: &TriCell<ViewPoint>,
+        _total_delta: DeltaTime,
+    ) {
+        if self.bench.is_none() {
+            self.bench = bench_duration().map(|duration| (bench_path(duration), Instant::now()));
+        }
+
+        let mut current = **view_point;
+        if let Some((path, start)) = &self.bench {
+            current = path.sample(start.elapsed());
+            view_point.publish(current);
+        }
+        self.camera = current.position;
+
+        let view = current.into_mat4().inverse();
+        self.frustum = Frustum::from_view_projection(*screen.projection() * view);
+    }
+}
+
+#[derive(Debug, Default)]
+struct StressRender {
+    shader: ShaderStress,
+    view_projection: Mat4,
+    camera: Vec3,
+    culler: Option<GpuCuller>,
+
+    since: Option<Instant>,
+    frames: u32,
+    longest_frame: Duration,
+    last_frame: Option<Instant>,
+    bench: Option<BenchRecorder>,
+}
+
+impl RenderHandler<SharedData> for StressRender {
+    fn init_resources(&mut self, _resolution: Resolution) {
+        self.shader = ShaderStress::new_compiled();
+        debug_assert_eq!(
+            LayoutInstanceData::verify_program(self.shader.handle()),
+            0,
+            "instance partitions do not match the shader storage blocks"
+        );
+        if GPU_CULLING.load(Ordering::Relaxed) {
+            self.culler = Some(GpuCuller::new(buffer_config().entity_capacity() * LODS));
+        }
+    }
+
+    fn pre_frame(
+        &mut self,
+        screen: &mut Mirror<ScreenSpace>,
+        view: &TriCell<ViewPoint>,
+        _delta: DeltaTime,
+    ) {
+        let now = Instant::now();
+        if let Some(duration) = bench_duration() {
+            let bench = self
+                .bench
+                .get_or_insert_with(|| BenchRecorder::new(duration));
+            bench.frame(now);
+            if bench.is_finished() {
+                let report = bench.report();
+                println!(
+                    "[bench] {} seconds, camera orbit\n{report}",
+                    duration.as_secs()
+                );
+                if let Err(err) = report.write_to(BENCH_REPORT) {
+                    eprintln!("[bench] cannot write {BENCH_REPORT}: {err}");
+                }
+                std::process::exit(0);
+            }
+        }
+        if let Some(last) = self.last_frame.replace(now) {
+            self.longest_frame = self.longest_frame.max(now - last);
+        }
+        self.frames += 1;
+
+        let since = *self.since.get_or_insert(now);
+        if now - since >= Duration::from_secs(1) {
+            println!(
+                "[render] fps: {} | longest frame: {:.2?}",
+                self.frames, self.longest_frame
+            );
+            self.since = Some(now);
+            self.frames = 0;
+            self.longest_frame = Duration::ZERO;
+        }
+
+        self.view_projection = *screen.projection() * view.into_mat4().inverse();
+        self.camera = view.position;
+
+        unsafe {
+            janus::gl::Clear(janus::gl::COLOR_BUFFER_BIT | janus::gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    fn render_frame(&self, frame_data: &SharedData, section: StorageSection) {
+        frame_data.instances.bind_shader_storage(section.as_index());
+        frame_data.header.bind_shader_storage(section);
+
+        if let Some(culler) = &self.culler {
+            let entities = ENTITIES.load(Ordering::Relaxed) as u32;
+            let params =
+                CullParams::new(self.view_projection, self.camera, &LOD_DISTANCES, entities);
+            culler.dispatch(&frame_data.draw_commands, section.as_index(), &params);
+        }
+
+        self.shader.bind();
+        self.shader
+            .uniform_view_projection_mat4v([self.view_projection]);
+        self.shader
+            .uniform_gpu_culling_uintv([self.culler.is_some() as u32]);
+
+        frame_data.draw_commands.flush_section(section.as_index());
+        let commands = frame_data.draw_commands.view_section(section.as_index());
+        GpuCommandDispatch::from_view(commands).dispatch();
+    }
+}
+
+/// Unit meshes of each family, from the most to the least detailed.
+fn build_meshes(staging: &mut MeshStaging) -> [[mesh::Id; LODS]; FAMILIES] {
+    let spheres = [uv_sphere(16, 12), uv_sphere(8, 6), uv_sphere(4, 3)];
+    let boxes = [cuboid(), cuboid(), tetrahedron()];
+    [
+        spheres.map(|vertices| staging.stage(&vertices)),
+        boxes.map(|vertices| staging.stage(&vertices)),
+    ]
+}
+
+fn vertex(position: Vec3, normal: Vec3) -> Vertex {
+    Vertex {
+        position: position.extend(1.0).to_array(),
+        normal: normal.extend(0.0).to_array(),
+    }
+}
+
+fn triangle(vertices: &mut Vec<Vertex>, [a, b, c]: [Vec3; 3]) {
+    let normal = (b - a).cross(c - a).normalize();
+    vertices.extend([a, b, c].map(|p| vertex(p, normal)));
+}
+
+fn uv_sphere(slices: u32, stacks: u32) -> Vec<Vertex> {
+    let point = |slice: u32, stack: u32| {
+        let theta = slice as f32 / slices as f32 * std::f32::consts::TAU;
+        let phi = stack as f32 / stacks as f32 * std::f32::consts::PI;
+        Vec3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin())
+    };
+
+    let mut vertices = Vec::new();
+    for stack in 0..stacks {
+        for slice in 0..slices {
+            let quad = [
+                point(slice, stack),
+                point(slice + 1, stack),
+                point(slice + 1, stack + 1),
+                point(slice, stack + 1),
+            ];
+            for [a, b, c] in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                vertices.extend([a, b, c].map(|p| vertex(p * ENTITY_RADIUS, p)));
+            }
+        }
+    }
+    vertices
+}
+
+fn cuboid() -> Vec<Vertex> {
+    let half = ENTITY_RADIUS / 3f32.sqrt();
+    let mut vertices = Vec::new();
+    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+        for sign in [1.0, -1.0] {
+            let normal = axis * sign;
+            let u = normal.any_orthonormal_vector();
+            let v = normal.cross(u);
+            let corner = |a: f32, b: f32| (normal + u * a + v * b) * half;
+            triangle(
+                &mut vertices,
+                [corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0)],
+            );
+            triangle(
+                &mut vertices,
+                [corner(-1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)],
+            );
+        }
+    }
+    vertices
+}
+
+fn tetrahedron() -> Vec<Vertex> {
+    let s = ENTITY_RADIUS / 3f32.sqrt();
+    let [a, b, c, d] = [
+        Vec3::new(s, s, s),
+        Vec3::new(-s, -s, s),
+        Vec3::new(-s, s, -s),
+        Vec3::new(s, -s, -s),
+    ];
+
+    let mut vertices = Vec::new();
+    for face in [[a, b, c], [a, d, b], [a, c, d], [b, d, c]] {
+        triangle(&mut vertices, face);
+    }
+    vertices
+}
+
+const DISPLAY_PARAMS: janus::window::DisplayParameters =
+    janus::window::DisplayParameters::windowed("ethel stress test", 1600, 900);
+
+fn main() {
+    let (flags, args): (Vec<_>, Vec<_>) = std::env::args().partition(|arg| arg.starts_with("--"));
+    GPU_CULLING.store(
+        flags.iter().any(|flag| flag == "--gpu-culling"),
+        Ordering::Relaxed,
+    );
+
+    if let Some(count) = args.get(1) {
+        let count = count.parse().expect("entity count must be an integer");
+        ENTITIES.store(count, Ordering::Relaxed);
+    }
+    let entities = ENTITIES.load(Ordering::Relaxed);
+
+    if let Some(mode) = args.get(2) {
+        let mode: UploadMode = mode.parse().unwrap_or_else(|err| panic!("{err}"));
+        mode.set_current();
+    }
+    println!("[sim] upload mode: {}", UploadMode::current());
+
+    if let Some(seconds) = args.get(3) {
+        let seconds = seconds.parse().expect("bench duration must be an integer");
+        BENCH_SECONDS.store(seconds, Ordering::Relaxed);
+    }
+
+    let (input_sys, input_dispatch) = janus::input::stream();
+
+    let mut staging = MeshStaging::new();
+    let mesh_ids = build_meshes(&mut staging);
+    let meshes = mesh_ids.map(|lods| lods.map(|id| *staging.metadata().get(id)));
+
+    let mut startup_handler = StartupHandler::new(input_sys, SharedData::new);
+    startup_handler.with_buffer_config(&buffer_config());
+    startup_handler.with_mesh_data(staging);
+    startup_handler.with_gl_state(|| unsafe {
+        // depth testing is set up by the renderer, see `DepthMode::ReverseZ`
+        janus::gl::Enable(janus::gl::CULL_FACE);
+    });
+
+    let ctx = janus::context::Context::new(
+        |state: &mut State<SharedData, StressState, Groups>,
+         renderer: &mut Renderer<SharedData, StressRender>| {
+            state
+                .viewpoint_shared()
+                .publish(ViewPoint::from_position(CAMERA_POSITION));
+            state.handler_init_callback(|inner| {
+                inner.meshes = meshes;
+                inner.spawn(entities);
+            });
+
+            startup_handler
+                .init(state, renderer)
+                .expect("failed to initialise the stress test");
+            renderer.set_clear_policy(
+                ClearPolicy::default().with_color(Color::srgb(0.05, 0.05, 0.08, 1.0)),
+            );
+            renderer
+                .cross_hooks_mut()
+                .add_exclusive(|shared: &mut SharedData| {
+                    shared.instances.grow_to_fit();
+                });
+        },
+        input_dispatch,
+        DISPLAY_PARAMS,
+    );
+
+    janus::run(ctx);
+}
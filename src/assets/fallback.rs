@@ -0,0 +1,42 @@
+//! Built-in fallback resources for assets that failed to load.
+//!
+//! Missing content should degrade visibly, but gracefully: rather than
+//! panicking or rendering nothing, a failed asset is replaced by a resource
+//! that stands out on screen.
+//!
+//! See also [`crate::mesh::unit_cube`] for meshes and
+//! [`crate::shader::ERROR_PIXEL_SHADER_SOURCE`] for shaders.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::assets::RawTexture;
+
+/// A resource with a built-in fallback, used in place of the resource when it
+/// fails to load.
+pub trait Fallback {
+    fn fallback() -> Self;
+}
+
+const CHECKER_SIZE: u32 = 64;
+const CHECKER_CELL: u32 = 8;
+
+const MAGENTA: Rgba<u8> = Rgba([255, 0, 255, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// A 64x64 magenta and black checker texture.
+pub fn checker_texture() -> RawTexture {
+    let image = RgbaImage::from_fn(CHECKER_SIZE, CHECKER_SIZE, |x, y| {
+        if (x / CHECKER_CELL + y / CHECKER_CELL) % 2 == 0 {
+            MAGENTA
+        } else {
+            BLACK
+        }
+    });
+    RawTexture::new(DynamicImage::ImageRgba8(image))
+}
+
+impl Fallback for RawTexture {
+    fn fallback() -> Self {
+        checker_texture()
+    }
+}
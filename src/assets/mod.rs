@@ -12,11 +12,15 @@ use janus::{
 use serde::{Deserialize, Serialize};
 use tracing::{Level, event};
 
-use crate::assets::pipe::{AssetMessage, AssetMessageRequestKind, AssetSyncMessage};
+use crate::assets::{
+    fallback::Fallback,
+    pipe::{AssetMessage, AssetMessageRequestKind, AssetSyncMessage},
+};
 
 #[allow(unused_imports)]
 pub use strings::{CachedStringHash, StringCache};
 
+pub mod fallback;
 pub mod pipe;
 pub mod strings;
 
@@ -73,6 +77,7 @@ where
     pipe_tx: crossbeam::channel::Sender<AssetMessage<T>>,
     pipe_rx: crossbeam::channel::Receiver<AssetMessage<T>>,
     sync_pipe_tx: Option<crossbeam::channel::Sender<AssetSyncMessage<M>>>,
+    fallback: Option<Handle<T, M>>,
 }
 impl<T, M> Default for AssetRegistry<T, M>
 where
@@ -87,6 +92,7 @@ where
             pipe_tx,
             pipe_rx,
             sync_pipe_tx: None,
+            fallback: None,
         }
     }
 }
@@ -198,6 +204,20 @@ where
     pub fn get_gpu_view(&self, id: impl Into<StringHash>) -> Option<<T::AsGpu as AsView>::View> {
         self.assets.get(&id.into()).map(Handle::gpu_view).flatten()
     }
+
+    /// Get the gpu view of an asset, or of the fallback resource if the asset
+    /// is missing or not present in video memory.
+    ///
+    /// # Returns
+    /// `None` only if the fallback has not been uploaded with
+    /// [`Self::upload_fallback`] either.
+    pub fn get_gpu_view_or_fallback(
+        &self,
+        id: impl Into<StringHash>,
+    ) -> Option<<T::AsGpu as AsView>::View> {
+        self.get_gpu_view(id)
+            .or_else(|| self.fallback.as_ref().and_then(Handle::gpu_view))
+    }
 }
 impl<T, M> AssetRegistry<T, M>
 where
@@ -213,6 +233,31 @@ where
     }
 }
 
+impl<T, M> AssetRegistry<T, M>
+where
+    T: Import + Upload + HasMetadata<M> + Fallback,
+    <T as Upload>::AsGpu: HasMetadata<M>,
+    M: Default + Clone + Copy,
+{
+    /// Upload the built-in [`Fallback`] resource, returned in place of assets
+    /// that failed to load.
+    ///
+    /// This operation must be called on the graphics/windowing thread, where
+    /// the GL context resides.
+    pub fn upload_fallback(&mut self, params: &<T as Upload>::AdditionalParams) -> AssetResult<()> {
+        let mut handle = Handle::from_resource(0, T::fallback(), self);
+        handle.upload_to_gpu(params)?;
+        handle.fallback = true;
+        self.fallback = Some(handle);
+        Ok(())
+    }
+
+    /// The built-in fallback resource, if uploaded.
+    pub fn fallback(&self) -> Option<&Handle<T, M>> {
+        self.fallback.as_ref()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AssetMetadataRegistry<M: Default + Clone + Copy> {
     mapping: janus::StringMap<M>,
@@ -337,6 +382,8 @@ where
     #[serde(skip)]
     root_pipe: crossbeam::channel::Sender<AssetMessage<T>>,
     #[serde(skip)]
+    fallback: bool,
+    #[serde(skip)]
     _marker_meta: std::marker::PhantomData<M>,
 }
 impl<T, M> Handle<T, M>
@@ -390,6 +437,7 @@ where
             raw_resource: Some(resource),
             gpu_resource: None,
             root_pipe: registry.command_pipe(),
+            fallback: false,
             _marker_meta: std::marker::PhantomData,
         }
     }
@@ -405,6 +453,7 @@ where
             raw_resource: None,
             gpu_resource: Some(resource),
             root_pipe: registry.command_pipe(),
+            fallback: false,
             _marker_meta: std::marker::PhantomData,
         }
     }
@@ -420,6 +469,7 @@ where
             raw_resource: None,
             gpu_resource: None,
             root_pipe: registry.command_pipe(),
+            fallback: false,
             _marker_meta: std::marker::PhantomData,
         }
     }
@@ -436,6 +486,12 @@ where
         self.gpu_resource.is_some()
     }
 
+    /// Whether the resource of this asset is a built-in [`Fallback`], in
+    /// place of one that failed to load.
+    pub const fn is_fallback(&self) -> bool {
+        self.fallback
+    }
+
     pub fn file_source(&self) -> &Path {
         &self.source
     }
@@ -495,6 +551,30 @@ where
         Ok(self.raw_resource.as_ref().unwrap())
     }
 
+    /// Load the raw resource from disk as [`Self::load_to_memory`], replacing
+    /// it with the built-in [`Fallback`] resource if the load fails.
+    ///
+    /// The failure is logged, and can be checked with [`Self::is_fallback`].
+    pub fn load_to_memory_or_fallback(&mut self, params: &<T as Import>::AdditionalParams) -> &T
+    where
+        T: Fallback,
+    {
+        match self.load_to_memory(params) {
+            Ok(_) | Err(AssetError::AlreadyInMemory) => {}
+            Err(err) => {
+                event!(
+                    name: "assets.fallback",
+                    Level::WARN,
+                    "Failed to load asset hash_id {}, using fallback: {err}",
+                    self.id
+                );
+                self.raw_resource = Some(T::fallback());
+                self.fallback = true;
+            }
+        }
+        self.raw_resource.as_ref().unwrap()
+    }
+
     /// Attempt to load the resource to the gpu.
     ///
     /// This operation must be called on the graphics/windowing thread, where
@@ -1,7 +1,10 @@
 pub mod mesh;
+pub mod platform;
+pub mod prelude;
 pub mod render;
 pub mod shader;
 pub mod state;
+pub mod zone;
 
 #[cfg(feature = "profile")]
 pub mod profile;
@@ -12,22 +15,37 @@ pub mod assets;
 #[allow(unused_imports)]
 pub use state::data;
 
+// the dependencies found in public signatures, see `prelude`
+pub use glam;
+pub use janus;
+
 use janus::{
     input::{InputState, KeyEvent},
     sync::{Mirror, TriCell},
 };
 
 use crate::{
-    mesh::MeshStaging,
+    mesh::{MeshPoolId, MeshStaging},
+    platform::{
+        ThreadHints,
+        config::{Config, Persist},
+    },
     render::{
         Renderer, Resolution, ScreenSpace,
-        buffer::{self, Layout, StorageSection},
-        command::{DrawGroups, GpuCommandQueue},
+        barrier::BarrierMode,
+        buffer::{Layout, StorageSection},
+        command::{DrawCmd, DrawGroups, GpuCommandQueue},
+        config::{BufferConfig, GlBufferLimits},
+        debug_output::{self, DebugOutput},
+        pool::MeshPool,
+        stage::RenderStage,
     },
     state::{
         State,
+        arena::StagingArena,
         camera::ViewPoint,
-        cross::{self, Cross, Producer},
+        cross::{self, Cross, FramesInFlight, Producer},
+        watchdog::Watchdog,
     },
 };
 
@@ -35,6 +53,10 @@ pub type InputSystem = InputState<{ janus::input::SLOT_COUNT }, { janus::input::
 
 pub type DrawCommand = render::command::DrawArraysIndirectCommand;
 
+/// The command of indexed meshes, drawn from the element partition of the
+/// mesh buffer, see [`StateHandler`].
+pub type IndexedDrawCommand = render::command::DrawElementsIndirectCommand;
+
 /// Manages the simulation side state of the program, which contains multiple
 /// responsabilities.
 ///
@@ -47,6 +69,9 @@ pub type DrawCommand = render::command::DrawArraysIndirectCommand;
 /// phase of the GPU synchronization routine. This must write to the provided
 /// `frame_boundary` any data that must be present on the gpu.
 ///
+/// Entities are destroyed through [`Self::destroy_entity`], which must free
+/// them from the columns of the handler.
+///
 /// An optional but noteworthy function is [`Self::step_duration`], this is a
 /// getter function for a [`std::time::Duration`] type. This is already
 /// implemented by default, returning [`state::DEFAULT_STEP`] (8ms).
@@ -61,17 +86,31 @@ pub type DrawCommand = render::command::DrawArraysIndirectCommand;
 /// down/release events. This is used to register the pressing of arbitrary
 /// keys (for example a text field) which cannot be done with the classic
 /// 'is_key_down' approach. The default implementation is blank.
-pub trait StateHandler<FrameData: Sized, RG: DrawGroups> {
+///
+/// The commands of the `command_queue` are [`DrawCommand`]s by default.
+/// Handlers of indexed meshes implement `StateHandler<FrameData, RG,
+/// IndexedDrawCommand>` instead, and are run by a [`State`] of the same
+/// command type, whose commands are dispatched with
+/// [`GpuCommandDispatch`](render::command::GpuCommandDispatch) as usual.
+pub trait StateHandler<FrameData: Sized, RG: DrawGroups, C: DrawCmd = DrawCommand> {
     /// The 'write' phase of the GPU synchronization routine.
     ///
     /// Write must occur to the passed `frame_boundary` and `command_queue`.
+    /// The commands of every group of the queue are uploaded to the frame
+    /// data and drawn in a single cross with a
+    /// [`ChunkedCommands`](render::command::ChunkedCommands) buffer.
+    ///
+    /// Transient data derived for this upload only (interpolation sets,
+    /// visibility lists, ...) can be allocated in the `arena`, which is reset
+    /// before every call.
     ///
     /// This is called after the [`Self::fixed_step`] has finished, even multiple
     /// times depending on delta accumulation.
     fn upload_gpu(
         &mut self,
         frame_boundary: &Cross<Producer, FrameData>,
-        command_queue: &mut GpuCommandQueue<crate::DrawCommand, RG>,
+        command_queue: &mut GpuCommandQueue<C, RG>,
+        arena: &StagingArena,
     );
 
     /// The simulation advance/step routine.
@@ -128,6 +167,38 @@ pub trait StateHandler<FrameData: Sized, RG: DrawGroups> {
         _total_delta: janus::context::DeltaTime,
     ) {
     }
+
+    /// Convert CPU-layout data to its GPU layout for the upload `frame`.
+    ///
+    /// This is called exactly once per upload, right before
+    /// [`Self::upload_gpu`], and is where
+    /// [`StagingColumn`](state::data::staging::StagingColumn)s should be
+    /// synchronised. The default implementation is blank.
+    fn sync_staging(&mut self, _frame: u64) {}
+
+    /// The columns holding the per-entity data written by
+    /// [`Self::upload_gpu`], e.g. positions and rotations.
+    ///
+    /// [`State::destroy_entity`](state::State::destroy_entity) frees the slot
+    /// of the destroyed entity in each of them, which keeps their data
+    /// contiguous, so that no draw is emitted for it from the next upload on.
+    /// The default implementation has no columns.
+    fn entity_columns(&mut self) -> Vec<&mut dyn state::data::EntityColumn> {
+        Vec::new()
+    }
+
+    /// Remove the `entity` from any storage not returned by
+    /// [`Self::entity_columns`], called by
+    /// [`State::destroy_entity`](state::State::destroy_entity) once its
+    /// columns are freed.
+    ///
+    /// The default implementation is blank.
+    ///
+    /// # Returns
+    /// Whether the entity existed in that storage.
+    fn destroy_entity(&mut self, _entity: state::data::IndirectIndex) -> bool {
+        false
+    }
 }
 
 pub trait RenderHandler<FrameData: Sized> {
@@ -148,9 +219,18 @@ pub struct StartupHandler<FrameData: Sized> {
 
     frame_data_init: fn() -> FrameData,
     gl_state_init: fn(),
+    debug_output: Option<DebugOutput>,
 
     mesh_data: MeshStaging,
-    mesh_buf_layout: Layout<2>,
+    mesh_buf_layout: Layout<3>,
+    dynamic_mesh_buf: bool,
+    mesh_pools: Vec<(Layout<3>, MeshStaging)>,
+    command_capacity: usize,
+    frames_in_flight: FramesInFlight,
+    thread_hints: Option<(ThreadHints, ThreadHints)>,
+    watchdog_timeout: Option<std::time::Duration>,
+    content_scale: f32,
+    config: Option<Config>,
 }
 
 impl<FrameData: Sized> StartupHandler<FrameData> {
@@ -159,12 +239,30 @@ impl<FrameData: Sized> StartupHandler<FrameData> {
             input_system,
             frame_data_init: init_fn,
             gl_state_init: || (),
+            debug_output: cfg!(debug_assertions).then(DebugOutput::from_env),
             mesh_data: MeshStaging::new(),
             mesh_buf_layout: Layout::new(),
+            dynamic_mesh_buf: false,
+            mesh_pools: Vec::new(),
+            command_capacity: 0,
+            frames_in_flight: FramesInFlight::Unbounded,
+            thread_hints: None,
+            watchdog_timeout: None,
+            content_scale: 1.0,
+            config: None,
         }
     }
 
-    pub fn with_mesh_layout(&mut self, mesh_buf_layout: Layout<2>) {
+    /// Derive the mesh buffer layout and command queue capacity from
+    /// `config`.
+    ///
+    /// This overrides any layout set with [`Self::with_mesh_layout`].
+    pub fn with_buffer_config(&mut self, config: &BufferConfig) {
+        self.mesh_buf_layout = config.mesh_layout();
+        self.command_capacity = config.command_capacity();
+    }
+
+    pub fn with_mesh_layout(&mut self, mesh_buf_layout: Layout<3>) {
         self.mesh_buf_layout = mesh_buf_layout;
     }
 
@@ -172,41 +270,127 @@ impl<FrameData: Sized> StartupHandler<FrameData> {
         self.mesh_data = mesh_data;
     }
 
+    /// Allow updates of the partitions of the mesh buffers after startup,
+    /// e.g. to tweak the metadata of a mesh, see
+    /// [`ImmutableBuffer::update_partition`](render::buffer::ImmutableBuffer::update_partition).
+    pub fn with_dynamic_mesh_buffer(&mut self) {
+        self.dynamic_mesh_buf = true;
+    }
+
+    /// Add the mesh pool of `staging`, with its own mesh buffer of `layout`,
+    /// see [`render::pool`].
+    ///
+    /// A pool added again replaces the previous one.
+    ///
+    /// # Panics
+    /// If `staging` is of the default pool, whose meshes are set with
+    /// [`Self::with_mesh_data`].
+    pub fn with_mesh_pool(&mut self, layout: Layout<3>, staging: MeshStaging) {
+        assert_ne!(
+            staging.pool(),
+            MeshPoolId::DEFAULT,
+            "the default pool is set with `with_mesh_data`"
+        );
+        self.mesh_pools
+            .retain(|(_, pool)| pool.pool() != staging.pool());
+        self.mesh_pools.push((layout, staging));
+    }
+
     pub fn with_gl_state(&mut self, init_fn: fn()) {
         self.gl_state_init = init_fn;
     }
+
+    /// Forward the GL debug messages selected by `output` to `tracing`, or
+    /// none without, see [`render::debug_output`].
+    ///
+    /// Defaults to [`DebugOutput::from_env`] in debug builds, and to none in
+    /// release builds.
+    pub fn with_debug_output(&mut self, output: Option<DebugOutput>) {
+        self.debug_output = output;
+    }
+
+    /// Limit how many frames the simulation may publish ahead of the
+    /// renderer, see [`FramesInFlight`].
+    pub fn with_frames_in_flight(&mut self, frames: FramesInFlight) {
+        self.frames_in_flight = frames;
+    }
+
+    /// Set the scheduling hints of the `update` and `render` threads, see
+    /// [`platform`].
+    pub fn with_thread_hints(&mut self, update: ThreadHints, render: ThreadHints) {
+        self.thread_hints = Some((update, render));
+    }
+
+    /// Log a diagnostic dump when the simulation or the renderer has not
+    /// crossed the boundary for longer than `timeout`, see
+    /// [`state::watchdog`].
+    pub fn with_watchdog(&mut self, timeout: std::time::Duration) {
+        self.watchdog_timeout = Some(timeout);
+    }
+
+    /// Set the initial content scale of the display, see
+    /// [`Resolution::scale`].
+    ///
+    /// Later changes, e.g. when the window moves to another display, are
+    /// forwarded with [`Renderer::set_content_scale`].
+    pub fn with_content_scale(&mut self, scale: f32) {
+        self.content_scale = scale;
+    }
+
+    /// Restore the render settings from the persisted `config`, and keep it
+    /// in the [`State`] for the handler, see [`platform::config`]. Frame
+    /// stats are exported if the config opts into it, see
+    /// [`StatsSettings`](state::stats::StatsSettings).
+    pub fn with_config(&mut self, config: Config) {
+        self.config = Some(config);
+    }
 }
 
-impl<Fd, Sh, Rh, RG> janus::context::Setup<State<Fd, Sh, RG>, Renderer<Fd, Rh>>
+impl<Fd, Sh, Rh, Rs, RG, C> janus::context::Setup<State<Fd, Sh, RG, C>, Renderer<Fd, Rh, Rs>>
     for StartupHandler<Fd>
 where
     Fd: Sized + Default,
-    Sh: StateHandler<Fd, RG> + Default,
+    Sh: StateHandler<Fd, RG, C> + Default,
     Rh: RenderHandler<Fd> + Default,
+    Rs: RenderStage<Fd, Rh> + Default,
     RG: DrawGroups,
+    C: DrawCmd,
 {
     fn init(
         self,
-        state: &mut State<Fd, Sh, RG>,
-        renderer: &mut Renderer<Fd, Rh>,
+        state: &mut State<Fd, Sh, RG, C>,
+        renderer: &mut Renderer<Fd, Rh, Rs>,
     ) -> Result<(), &'static str>
     where
         Self: Sized,
     {
         *state.input_mut() = self.input_system;
 
+        let layouts = std::iter::once(&self.mesh_buf_layout)
+            .chain(self.mesh_pools.iter().map(|(layout, _)| layout));
+        if let Some(limits) = GlBufferLimits::query()
+            && let Some(err) = layouts
+                .filter_map(|layout| limits.validate_layout(layout).err())
+                .next()
         {
-            let mut mesh_buf = buffer::immutable::uninit(self.mesh_buf_layout);
-
-            let vertices = self.mesh_data.vertex_storage();
-            let vbs = mesh::BUFFER_VERTEX_STORAGE_INDEX;
-            mesh_buf.fill_partition(vbs, vertices);
-
-            let metadata = self.mesh_data.close();
-            let mds = mesh::BUFFER_MESH_META_INDEX;
-            mesh_buf.fill_partition(mds, &metadata);
+            tracing::event!(
+                name: "startup.mesh_layout",
+                tracing::Level::ERROR,
+                "Invalid mesh buffer layout: {err}"
+            );
+            return Err("mesh buffer layout exceeds the GL implementation limits");
+        }
 
-            renderer.mesh_buffer = mesh_buf.finish();
+        let dynamic = self.dynamic_mesh_buf;
+        let default_pool = MeshPool::build(self.mesh_buf_layout, self.mesh_data, dynamic);
+        renderer
+            .mesh_pools
+            .insert(MeshPoolId::DEFAULT, default_pool);
+        for (layout, staging) in self.mesh_pools {
+            let id = staging.pool();
+            renderer
+                .mesh_pools
+                .insert(id, MeshPool::build(layout, staging, dynamic));
         }
 
         let m_vp = state.viewpoint_shared().clone();
@@ -214,13 +398,40 @@ where
 
         let frame_data = (self.frame_data_init)();
         let (producer, consumer) = cross::create(frame_data);
+        producer.set_frames_in_flight(self.frames_in_flight);
+
+        if let Some(timeout) = self.watchdog_timeout {
+            match Watchdog::spawn(producer.pulse().clone(), timeout) {
+                Ok(watchdog) => state.set_watchdog(Some(watchdog)),
+                Err(err) => tracing::event!(
+                    name: "startup.watchdog",
+                    tracing::Level::WARN,
+                    "Failed to spawn the boundary watchdog: {err}"
+                ),
+            }
+        }
+
+        if let Some((update, render)) = self.thread_hints {
+            state.set_thread_hints(Some(update));
+            renderer.set_thread_hints(Some(render));
+        }
 
         renderer.boundary = consumer;
         *state.boundary_mut() = producer;
-        *state.command_queue_mut() = GpuCommandQueue::new();
+        *state.command_queue_mut() = GpuCommandQueue::with_capacity(self.command_capacity);
 
+        if let Some(output) = &self.debug_output {
+            debug_output::install(output);
+        }
         (self.gl_state_init)();
+        renderer.barriers_mut().set_mode(BarrierMode::from_env());
+
+        if let Some(config) = self.config {
+            renderer.settings_mut().restore(&config);
+            state.set_config(config);
+        }
 
+        renderer.set_content_scale(self.content_scale);
         let screen = renderer.screen_space_mirror().clone();
         renderer.handler.init_resources(screen.resolution());
         *state.screen_space_mirror_mut() = screen;
@@ -1,24 +1,93 @@
-use std::ops::Deref;
+use std::{
+    ops::Deref,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+};
 
 use crate::shader::glsl::GlslStorage;
 
+pub mod obj;
+
+/// The pool of mesh buffers a mesh is stored in.
+///
+/// Each pool has its own mesh buffer and [`Meshadata`], e.g. to keep the
+/// static world apart from streamed characters. Pool `0` is the default pool,
+/// built from the staging of
+/// [`StartupHandler::with_mesh_data`](crate::StartupHandler::with_mesh_data).
+///
+/// See [`MeshPools`](crate::render::pool::MeshPools).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct MeshPoolId(pub(crate) u16);
+
+impl MeshPoolId {
+    pub const DEFAULT: Self = Self(0);
+
+    pub const fn new(index: u16) -> Self {
+        Self(index)
+    }
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
 /// The ID that represents a Mesh present on GPU memory, from the CPU.
 ///
-/// An ID of `0` represents a `null` mesh: this is currently an empty mesh, but
-/// it could be changed to a "debug" mesh in the future.
+/// An ID of index `0` represents a `null` mesh: this is currently an empty
+/// mesh, but it could be changed to a "debug" mesh in the future.
 ///
 /// It is used to link objects or "renderables" to a mesh that is present on
-/// the GPU through its [`Metadata`].
+/// the GPU through its [`Metadata`], in the mesh buffer of its
+/// [`pool`](Self::pool).
+///
+/// IDs are ordered by pool first, so that batches sorted by mesh bind each
+/// pool once.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
-pub struct Id(pub(crate) u32);
+pub struct Id {
+    pub(crate) pool: MeshPoolId,
+    pub(crate) index: u32,
+}
 
 impl Id {
+    /// The mesh at `index` of the default pool.
+    ///
+    /// # Safety
+    /// The ID is not checked against the meshes of the pool: an `index` out
+    /// of its metadata reads out of the mesh buffer on the GPU.
     pub const unsafe fn from_value(index: u32) -> Self {
-        Self(index)
+        unsafe { Self::from_pool_value(MeshPoolId::DEFAULT, index) }
+    }
+
+    /// The mesh at `index` of `pool`.
+    ///
+    /// # Safety
+    /// See [`Self::from_value`].
+    pub const unsafe fn from_pool_value(pool: MeshPoolId, index: u32) -> Self {
+        Self { pool, index }
     }
 
     pub const fn is_null(self) -> bool {
-        self.0 == 0
+        self.index == 0
+    }
+
+    /// The index of the metadata of the mesh in its pool.
+    pub const fn index(self) -> u32 {
+        self.index
+    }
+
+    pub const fn pool(self) -> MeshPoolId {
+        self.pool
+    }
+}
+
+impl std::fmt::Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pool {
+            MeshPoolId::DEFAULT => write!(f, "{}", self.index),
+            pool => write!(f, "{}:{}", pool.0, self.index),
+        }
     }
 }
 
@@ -32,16 +101,75 @@ impl Id {
 /// * Determine the offset of the next [`Mesh Metadata`](Metadata).
 /// * Specify the amount of vertices the GPU has to draw for the instance using
 ///   the mesh.
+///
+/// Indexed meshes additionally store the range of their indices in the index
+/// partition of the mesh buffer, see [`Self::first_index`].
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
 pub struct Metadata {
     pub(crate) offset: u32,
     pub(crate) length: u32,
+    pub(crate) first_index: u32,
+    pub(crate) index_count: u32,
 }
 
 impl Metadata {
     pub unsafe fn from_values(offset: u32, length: u32) -> Self {
-        Self { offset, length }
+        Self {
+            offset,
+            length,
+            ..Default::default()
+        }
+    }
+
+    pub unsafe fn from_indexed_values(
+        offset: u32,
+        length: u32,
+        first_index: u32,
+        index_count: u32,
+    ) -> Self {
+        Self {
+            offset,
+            length,
+            first_index,
+            index_count,
+        }
+    }
+
+    /// The index of the first vertex of the mesh in the vertex buffer.
+    pub const fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// The amount of vertices of the mesh.
+    pub const fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// The position of the first index of the mesh in the index partition.
+    ///
+    /// The index partition is the first partition of the mesh buffer, so this
+    /// is also the position in the element array buffer, as expected by the
+    /// `firstIndex` of a [`DrawElementsIndirectCommand`].
+    ///
+    /// [`DrawElementsIndirectCommand`]: crate::render::command::DrawElementsIndirectCommand
+    pub const fn first_index(&self) -> u32 {
+        self.first_index
+    }
+
+    /// The amount of indices of the mesh, `0` if the mesh is not indexed.
+    pub const fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// The value added to each index of the mesh: indices are local to the
+    /// vertices of the mesh.
+    pub const fn base_vertex(&self) -> i32 {
+        self.offset as i32
+    }
+
+    pub const fn is_indexed(&self) -> bool {
+        self.index_count > 0
     }
 }
 
@@ -51,37 +179,91 @@ const INITIAL_VERTEX_ALLOC: usize = INITIAL_MESH_ALLOC * 8;
 #[derive(Default, Clone, Debug)]
 pub struct Meshadata {
     metadata: Vec<Metadata>,
+    pool: MeshPoolId,
 
     /// Vertex offset
     head: u32,
+    /// Index offset
+    index_head: u32,
 }
 
 impl Meshadata {
     pub fn new() -> Self {
+        Self::for_pool(MeshPoolId::DEFAULT)
+    }
+
+    /// The metadata of the meshes of `pool`.
+    pub fn for_pool(pool: MeshPoolId) -> Self {
         let mut metadata = Vec::with_capacity(INITIAL_MESH_ALLOC + 1);
         metadata.push(Metadata::default());
 
-        Self { metadata, head: 0 }
+        Self {
+            metadata,
+            pool,
+            head: 0,
+            index_head: 0,
+        }
+    }
+
+    pub fn pool(&self) -> MeshPoolId {
+        self.pool
     }
 
     pub fn clear(&mut self) {
         self.metadata.clear();
         self.metadata.push(Metadata::default());
         self.head = 0;
+        self.index_head = 0;
     }
 
     pub fn add(&mut self, length: u32) -> Id {
-        let id = self.metadata.len() as u32;
-        self.metadata.push(Metadata {
+        self.add_indexed(length, 0)
+    }
+
+    /// Add a mesh of `length` vertices drawn through `index_count` indices.
+    pub fn add_indexed(&mut self, length: u32, index_count: u32) -> Id {
+        let id = Id {
+            pool: self.pool,
+            index: self.metadata.len() as u32,
+        };
+        self.insert_indexed(id, length, index_count);
+        id
+    }
+
+    /// Add a mesh of `length` vertices at the slot of `id`, which may have
+    /// been reserved out of order through [`MeshIds`].
+    ///
+    /// Slots skipped to reach `id` are filled with the `null` mesh.
+    pub fn insert(&mut self, id: Id, length: u32) {
+        self.insert_indexed(id, length, 0);
+    }
+
+    /// Add a mesh of `length` vertices drawn through `index_count` indices at
+    /// the slot of `id`.
+    ///
+    /// See [`Self::insert`].
+    ///
+    /// # Panics
+    /// If `id` belongs to another pool.
+    pub fn insert_indexed(&mut self, id: Id, length: u32, index_count: u32) {
+        assert_eq!(id.pool, self.pool, "mesh {id} belongs to another pool");
+        let index = id.index as usize;
+        if index >= self.metadata.len() {
+            self.metadata.resize(index + 1, Metadata::default());
+        }
+        self.metadata[index] = Metadata {
             offset: self.head,
             length,
-        });
+            first_index: self.index_head,
+            index_count,
+        };
         self.head += length;
-        Id(id)
+        self.index_head += index_count;
     }
 
     pub fn get(&self, id: Id) -> &Metadata {
-        &self.metadata[id.0 as usize]
+        debug_assert_eq!(id.pool, self.pool, "mesh {id} belongs to another pool");
+        &self.metadata[id.index as usize]
     }
 
     /// The current head (offset) of the vertex buffer.
@@ -89,6 +271,11 @@ impl Meshadata {
         self.head
     }
 
+    /// The current head (offset) of the index buffer.
+    pub fn index_head(&self) -> u32 {
+        self.index_head
+    }
+
     pub fn inner_metadata(&self) -> &[Metadata] {
         &self.metadata
     }
@@ -109,18 +296,131 @@ pub struct Vertex {
     pub normal: [f32; 4],
 }
 
-pub(crate) const BUFFER_VERTEX_STORAGE_INDEX: usize = 0;
-pub(crate) const BUFFER_MESH_META_INDEX: usize = 1;
+/// The vertices of a cube of size `1.0` centred on the origin, with
+/// counter-clockwise winding and flat normals.
+///
+/// Used as a fallback for missing meshes, see
+/// [`MeshStaging::stage_placeholder_cube`].
+pub fn unit_cube() -> [Vertex; 36] {
+    use glam::Vec3;
+
+    // (normal, u, v) of each face, with u × v = normal
+    const FACES: [(Vec3, Vec3, Vec3); 6] = [
+        (Vec3::X, Vec3::Y, Vec3::Z),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::Z, Vec3::X),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y, Vec3::X),
+    ];
+    const CORNERS: [(f32, f32); 6] = [
+        (-0.5, -0.5),
+        (0.5, -0.5),
+        (0.5, 0.5),
+        (-0.5, -0.5),
+        (0.5, 0.5),
+        (-0.5, 0.5),
+    ];
+
+    let mut vertices = [Vertex::default(); 36];
+    for (i, (normal, u, v)) in FACES.into_iter().enumerate() {
+        for (j, (a, b)) in CORNERS.into_iter().enumerate() {
+            let position = normal * 0.5 + u * a + v * b;
+            vertices[i * 6 + j] = Vertex {
+                position: position.extend(1.0).to_array(),
+                normal: normal.extend(0.0).to_array(),
+            };
+        }
+    }
+    vertices
+}
+
+/// The default distance under which vertex positions and normals are
+/// considered identical by [`weld`].
+pub const DEFAULT_WELD_EPSILON: f32 = 1e-5;
+
+/// A mesh deduplicated by [`weld`]: each index refers to a unique vertex.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Welded {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Welded {
+    /// Expand the mesh back into a plain vertex list, with the vertices
+    /// repeated per index.
+    pub fn unwelded(&self) -> Vec<Vertex> {
+        self.indices
+            .iter()
+            .map(|&i| self.vertices[i as usize])
+            .collect()
+    }
+}
+
+/// Deduplicate identical vertices, producing an index buffer.
+///
+/// Positions and normals are snapped to a grid of `epsilon` cells: vertices
+/// falling into the same cell are merged, keeping the first one. Vertices
+/// closer than `epsilon` may fall into adjacent cells and remain separate,
+/// which only costs memory.
+///
+/// Cells are indexed with 64 bit integers, so that world-space meshes far
+/// from the origin do not run out of cells.
+///
+/// Meshes imported from formats that repeat vertices per face (e.g. OBJ)
+/// typically shrink by a factor of 4 to 6.
+///
+/// # Panics
+/// If `epsilon` is not positive.
+pub fn weld(vertices: &[Vertex], epsilon: f32) -> Welded {
+    assert!(
+        epsilon > 0.0,
+        "weld epsilon must be positive, got {epsilon}"
+    );
+
+    let inv = 1.0 / epsilon as f64;
+    let key = |vertex: &Vertex| {
+        let mut key = [0i64; 7];
+        let position = &vertex.position[..3];
+        let components = position.iter().chain(&vertex.normal[..3]);
+        for (key, &c) in key.iter_mut().zip(components) {
+            *key = (c as f64 * inv).round() as i64;
+        }
+        key[6] = vertex.position[3].to_bits() as i64;
+        key
+    };
+
+    let mut unique: rustc_hash::FxHashMap<[i64; 7], u32> = Default::default();
+    let mut welded = Welded {
+        vertices: Vec::with_capacity(vertices.len() / 4),
+        indices: Vec::with_capacity(vertices.len()),
+    };
+
+    for vertex in vertices {
+        let index = *unique.entry(key(vertex)).or_insert_with(|| {
+            welded.vertices.push(*vertex);
+            welded.vertices.len() as u32 - 1
+        });
+        welded.indices.push(index);
+    }
+    welded
+}
+
+pub(crate) const BUFFER_INDEX_STORAGE_INDEX: usize = 0;
+pub(crate) const BUFFER_VERTEX_STORAGE_INDEX: usize = 1;
+pub(crate) const BUFFER_MESH_META_INDEX: usize = 2;
 
 crate::shader_glsl_struct! {
-    struct Metadata {
+    struct Metadata for Metadata {
         offset: u32 => uint;
         length: u32 => uint;
+        first_index: u32 => uint;
+        index_count: u32 => uint;
     }
 }
 
 crate::shader_glsl_struct! {
-    struct Vertex {
+    struct Vertex for Vertex {
         position: [f32; 4] => vec4;
         normal: [f32; 4] => vec4;
     }
@@ -146,31 +446,54 @@ pub const SHADER_BINDING_MESH_METADATA: u32 = ssbo_binding!(MeshMetadata);
 ///
 /// Note how the vertex count is *global* for all meshes, not for each.
 ///
+/// An optional `indices` value sets the *global* size of the index partition
+/// used by indexed meshes, see [`MeshStaging::stage_indexed`]. It defaults to
+/// `0`.
+///
 /// # Examples
 /// ```rust,ignore
 /// layout_mesh_buffer!(count: 32; vertices: 10_000);
+/// layout_mesh_buffer!(count: 32; vertices: 10_000; indices: 40_000);
 /// ```
 ///
-/// The above example will allocate two GPU buffers: the first for mesh
+/// The first example will allocate two GPU buffers: the first for mesh
 /// metadata for 32 unique meshes; the second for vertex data for a total
 /// of 10,000 vertices (and normals) *globally*.
+///
+/// The index partition is always the first partition of the buffer, and it
+/// is declared as its element partition (see [`Layout::with_elements`]), so
+/// that the first index of each mesh is also its position in the element
+/// array buffer.
+///
+/// [`Layout::with_elements`]: crate::render::buffer::Layout::with_elements
 #[macro_export]
 macro_rules! layout_mesh_buffer {
     (count: $mc:expr; vertices: $vc:expr) => {
-        layout_mesh_buffer!(MeshStorage; count: $mc; vertices: $vc);
+        layout_mesh_buffer!(MeshStorage; count: $mc; vertices: $vc; indices: 0);
+    };
+    (count: $mc:expr; vertices: $vc:expr; indices: $ic:expr) => {
+        layout_mesh_buffer!(MeshStorage; count: $mc; vertices: $vc; indices: $ic);
     };
     ($name:ident; count: $mc:expr; vertices: $vc:expr) => {
+        layout_mesh_buffer!($name; count: $mc; vertices: $vc; indices: 0);
+    };
+    ($name:ident; count: $mc:expr; vertices: $vc:expr; indices: $ic:expr) => {
         layout_buffer! {
-            const $name: 2, {
+            const $name: 3, {
+                enum index_storage: $ic => {
+                    type u32 as elements;
+                    bind 0;
+                };
+
                 enum vertex_storage: $vc => {
                     type $crate::mesh::Vertex;
-                    bind 0;
+                    bind 1;
                     shader 10;
                 };
 
                 enum metadata: $mc => {
                     type $crate::mesh::Metadata;
-                    bind 1;
+                    bind 2;
                     shader 11;
                 };
             }
@@ -206,23 +529,210 @@ pub const GLSL_SSBO_INTEGRATION: [GlslStorage; 2] = [
     },
 ];
 
+/// Thread-safe allocator of mesh [`Id`]s.
+///
+/// Asset loaders running on other threads can [`reserve`](Self::reserve) the
+/// ID of a mesh immediately, and hand it to the entities using the mesh, then
+/// [`submit`](Self::submit) its vertices once loaded. Submitted meshes are
+/// staged by [`MeshStaging::stage_submitted`]: until then, the ID resolves to
+/// the placeholder mesh of the staging.
+///
+/// Shared with the [`MeshStaging`] it was created by, see
+/// [`MeshStaging::ids`].
+#[derive(Debug)]
+pub struct MeshIds {
+    pool: MeshPoolId,
+    /// The next ID to reserve. `0` is always the `null` mesh.
+    next: AtomicU32,
+    submitted: Mutex<Vec<Submitted>>,
+}
+
+/// The vertices and indices of a submitted mesh.
+type Submitted = (Id, Vec<Vertex>, Vec<u32>);
+
+impl Default for MeshIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeshIds {
+    pub fn new() -> Self {
+        Self::for_pool(MeshPoolId::DEFAULT)
+    }
+
+    /// The allocator of the IDs of the meshes of `pool`.
+    pub fn for_pool(pool: MeshPoolId) -> Self {
+        Self {
+            pool,
+            next: AtomicU32::new(1),
+            submitted: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn pool(&self) -> MeshPoolId {
+        self.pool
+    }
+
+    /// Reserve the ID of a mesh to be submitted later.
+    pub fn reserve(&self) -> Id {
+        Id {
+            pool: self.pool,
+            index: self.next.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// The amount of mesh slots reserved so far, including the `null` mesh.
+    pub fn reserved(&self) -> u32 {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    /// Submit the `vertices` of the mesh reserved as `id`.
+    ///
+    /// # Panics
+    /// If `id` is the `null` mesh or was not reserved by this allocator.
+    pub fn submit(&self, id: Id, vertices: Vec<Vertex>) {
+        self.submit_indexed(id, vertices, Vec::new());
+    }
+
+    /// Submit the `vertices` and `indices` of the indexed mesh reserved as
+    /// `id`.
+    ///
+    /// An empty `indices` submits a non-indexed mesh.
+    ///
+    /// # Panics
+    /// If `id` is the `null` mesh or was not reserved by this allocator.
+    pub fn submit_indexed(&self, id: Id, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        assert!(
+            !id.is_null() && id.pool == self.pool && id.index < self.reserved(),
+            "mesh {id} was not reserved"
+        );
+        self.submitted.lock().unwrap().push((id, vertices, indices));
+    }
+
+    fn take_submitted(&self) -> Vec<Submitted> {
+        std::mem::take(&mut *self.submitted.lock().unwrap())
+    }
+}
+
 #[derive(Debug)]
 pub struct MeshStaging {
     metadata: Meshadata,
     vertex_storage: Vec<Vertex>,
+    index_storage: Vec<u32>,
+
+    ids: Arc<MeshIds>,
+    /// Whether the mesh of each slot has been staged.
+    staged: Vec<bool>,
+    placeholder: Id,
 }
 
 impl MeshStaging {
     pub fn new() -> Self {
+        Self::for_pool(MeshPoolId::DEFAULT)
+    }
+
+    /// The staging of the meshes of `pool`, see
+    /// [`StartupHandler::with_mesh_pool`](crate::StartupHandler::with_mesh_pool).
+    pub fn for_pool(pool: MeshPoolId) -> Self {
         Self {
-            metadata: Meshadata::new(),
+            metadata: Meshadata::for_pool(pool),
             vertex_storage: Vec::with_capacity(INITIAL_VERTEX_ALLOC),
+            index_storage: Vec::new(),
+            ids: Arc::new(MeshIds::for_pool(pool)),
+            staged: vec![true],
+            placeholder: Id { pool, index: 0 },
         }
     }
 
+    pub fn pool(&self) -> MeshPoolId {
+        self.metadata.pool
+    }
+
     pub fn stage(&mut self, vertices: &[Vertex]) -> Id {
+        let id = self.ids.reserve();
+        self.stage_at(id, vertices, &[]);
+        id
+    }
+
+    /// Stage an indexed mesh, drawn with `glMultiDrawElementsIndirect`.
+    ///
+    /// `indices` are local to `vertices`: the offset of the mesh in the vertex
+    /// buffer is applied as the base vertex of the draw command.
+    ///
+    /// # Panics
+    /// If any index is out of the bounds of `vertices`.
+    pub fn stage_indexed(&mut self, vertices: &[Vertex], indices: &[u32]) -> Id {
+        let id = self.ids.reserve();
+        self.stage_at(id, vertices, indices);
+        id
+    }
+
+    /// Stage a mesh deduplicated by [`weld`] as an indexed mesh.
+    pub fn stage_welded(&mut self, welded: &Welded) -> Id {
+        self.stage_indexed(&welded.vertices, &welded.indices)
+    }
+
+    fn stage_at(&mut self, id: Id, vertices: &[Vertex], indices: &[u32]) {
+        if let Some(&max) = indices.iter().max() {
+            assert!(
+                (max as usize) < vertices.len(),
+                "index {max} of mesh {id} is out of bounds of its {} vertices",
+                vertices.len()
+            );
+        }
+
         self.vertex_storage.extend_from_slice(vertices);
-        self.metadata.add(vertices.len() as u32)
+        self.index_storage.extend_from_slice(indices);
+        self.metadata
+            .insert_indexed(id, vertices.len() as u32, indices.len() as u32);
+
+        let index = id.index as usize;
+        if index >= self.staged.len() {
+            self.staged.resize(index + 1, false);
+        }
+        self.staged[index] = true;
+    }
+
+    /// Stage all meshes submitted to the [`MeshIds`] of this staging.
+    ///
+    /// # Returns
+    /// The amount of staged meshes.
+    pub fn stage_submitted(&mut self) -> usize {
+        let submitted = self.ids.take_submitted();
+        for (id, vertices, indices) in &submitted {
+            self.stage_at(*id, vertices, indices);
+        }
+        submitted.len()
+    }
+
+    /// The mesh ID allocator of this staging, to share with asset loaders.
+    pub fn ids(&self) -> &Arc<MeshIds> {
+        &self.ids
+    }
+
+    /// Set the mesh rendered in place of reserved meshes that have not been
+    /// staged yet.
+    ///
+    /// The `null` (empty) mesh is used by default.
+    pub fn set_placeholder(&mut self, id: Id) {
+        self.placeholder = id;
+    }
+
+    /// Stage a [`unit_cube`] and set it as the placeholder mesh, so missing
+    /// meshes remain visible.
+    ///
+    /// # Returns
+    /// The ID of the cube.
+    pub fn stage_placeholder_cube(&mut self) -> Id {
+        let id = self.stage(&unit_cube());
+        self.set_placeholder(id);
+        id
+    }
+
+    /// Whether the mesh reserved as `id` has been staged.
+    pub fn is_staged(&self, id: Id) -> bool {
+        self.staged.get(id.index as usize).copied().unwrap_or(false)
     }
 
     pub fn metadata(&self) -> &Meshadata {
@@ -233,7 +743,124 @@ impl MeshStaging {
         &self.vertex_storage
     }
 
-    pub fn close(self) -> Meshadata {
+    /// The indices of all indexed meshes, each local to the vertices of its
+    /// mesh.
+    pub fn index_storage(&self) -> &[u32] {
+        &self.index_storage
+    }
+
+    /// Finish the staging, resolving all reserved meshes that have not been
+    /// staged to the placeholder mesh.
+    ///
+    /// Meshes submitted after the last [`Self::stage_submitted`] are not
+    /// staged.
+    pub fn close(mut self) -> Meshadata {
+        let reserved = self.ids.reserved() as usize;
+        if reserved > self.metadata.metadata.len() {
+            self.metadata.metadata.resize(reserved, Metadata::default());
+        }
+        self.staged.resize(reserved, false);
+
+        let placeholder = *self.metadata.get(self.placeholder);
+        for (metadata, staged) in self.metadata.metadata.iter_mut().zip(&self.staged) {
+            if !staged {
+                *metadata = placeholder;
+            }
+        }
         self.metadata
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_meshes_resolve_to_placeholder() {
+        let mut staging = MeshStaging::new();
+        let cube = staging.stage_placeholder_cube();
+
+        let ids = Arc::clone(staging.ids());
+        let (late, ready, never) = std::thread::spawn(move || {
+            let late = ids.reserve();
+            let ready = ids.reserve();
+            let never = ids.reserve();
+            ids.submit(ready, vec![Vertex::default(); 3]);
+            (late, ready, never)
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(staging.stage_submitted(), 1);
+        assert!(staging.is_staged(ready));
+        assert!(!staging.is_staged(late));
+
+        let metadata = staging.close();
+        assert_eq!(metadata.len(), 5);
+        assert_eq!(*metadata.get(ready), unsafe {
+            Metadata::from_values(36, 3)
+        });
+        assert_eq!(metadata.get(late), metadata.get(cube));
+        assert_eq!(metadata.get(never), metadata.get(cube));
+        assert_eq!(metadata.head(), 39);
+    }
+
+    #[test]
+    fn stage_indexed_meshes() {
+        let mut staging = MeshStaging::new();
+        let plain = staging.stage(&[Vertex::default(); 3]);
+        let cube = staging.stage_welded(&weld(&unit_cube(), DEFAULT_WELD_EPSILON));
+        let quad = staging.stage_indexed(&[Vertex::default(); 4], &[0, 1, 2, 0, 2, 3]);
+
+        let metadata = staging.metadata();
+        assert!(!metadata.get(plain).is_indexed());
+        assert_eq!(*metadata.get(cube), unsafe {
+            Metadata::from_indexed_values(3, 24, 0, 36)
+        });
+        assert_eq!(*metadata.get(quad), unsafe {
+            Metadata::from_indexed_values(27, 4, 36, 6)
+        });
+        assert_eq!(metadata.get(quad).base_vertex(), 27);
+        assert_eq!(metadata.index_head(), 42);
+        assert_eq!(&staging.index_storage()[36..], [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn unit_cube_faces_outward() {
+        for triangle in unit_cube().chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| glam::Vec4::from(triangle[i].position).truncate());
+            let normal = glam::Vec4::from(triangle[0].normal).truncate();
+
+            assert_eq!((b - a).cross(c - a).normalize(), normal);
+            assert!(a.abs().max_element() == 0.5 && a.dot(normal) == 0.5);
+        }
+    }
+
+    #[test]
+    fn weld_unit_cube() {
+        let cube = unit_cube();
+        let welded = weld(&cube, DEFAULT_WELD_EPSILON);
+
+        // 4 corners per face, as normals differ between faces
+        assert_eq!(welded.vertices.len(), 24);
+        assert_eq!(welded.indices.len(), 36);
+        assert_eq!(welded.unwelded(), cube);
+
+        let mut jittered = cube;
+        jittered[3].position[0] += DEFAULT_WELD_EPSILON * 0.1;
+        assert_eq!(weld(&jittered, DEFAULT_WELD_EPSILON).vertices.len(), 24);
+    }
+
+    #[test]
+    fn weld_far_from_origin() {
+        // beyond the range of 32 bit cells at the default epsilon
+        let vertices = [30_000.0, 30_001.0, 2.5e6, 2.5e6 + 0.25, 30_000.0].map(|x| Vertex {
+            position: [x, -x, 1.0e5, 1.0],
+            ..Default::default()
+        });
+        let welded = weld(&vertices, DEFAULT_WELD_EPSILON);
+        assert_eq!(welded.vertices.len(), 4);
+        assert_eq!(welded.indices, [0, 1, 2, 3, 0]);
+        assert_eq!(welded.unwelded(), vertices);
+    }
+}
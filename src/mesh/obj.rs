@@ -0,0 +1,284 @@
+//! Wavefront OBJ import into [`Vertex`] lists, ready to be staged with
+//! [`MeshStaging::stage`](super::MeshStaging::stage).
+//!
+//! Only geometry is imported: positions, normals, faces and smoothing groups.
+//! Texture coordinates, materials and object/group names are ignored. Faces
+//! with more than 3 vertices are triangulated as fans.
+//!
+//! # Normals
+//! Normals are recomputed per vertex from the faces around it, unless the
+//! file provides them and [`ObjOptions::recompute_normals`] is disabled:
+//! * faces outside of any smoothing group (`s off` or `s 0`) are flat shaded;
+//! * faces within a smoothing group are smoothed with the adjacent faces of
+//!   the same group, as long as the angle between the faces does not exceed
+//!   [`ObjOptions::smoothing_angle`].
+//!
+//! This keeps hard edges hard, e.g. between the sides of a cube exported in a
+//! single smoothing group, while curved surfaces remain smooth.
+
+use glam::Vec3;
+
+use crate::mesh::Vertex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObjOptions {
+    /// The maximum angle in degrees between two adjacent faces of the same
+    /// smoothing group for their normals to be smoothed together.
+    pub smoothing_angle: f32,
+    /// Recompute normals even if the file provides them.
+    pub recompute_normals: bool,
+}
+
+impl Default for ObjOptions {
+    fn default() -> Self {
+        Self {
+            smoothing_angle: Self::DEFAULT_SMOOTHING_ANGLE,
+            recompute_normals: false,
+        }
+    }
+}
+
+impl ObjOptions {
+    pub const DEFAULT_SMOOTHING_ANGLE: f32 = 60.0;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjError {
+    /// The line of the error, starting from 1.
+    pub line: usize,
+    pub kind: ObjErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjErrorKind {
+    InvalidNumber(String),
+    MissingComponent,
+    IndexOutOfRange(i64),
+    DegenerateFace,
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "obj line {}: ", self.line)?;
+        match &self.kind {
+            ObjErrorKind::InvalidNumber(s) => write!(f, "invalid number '{s}'"),
+            ObjErrorKind::MissingComponent => write!(f, "missing vector component"),
+            ObjErrorKind::IndexOutOfRange(i) => write!(f, "index {i} out of range"),
+            ObjErrorKind::DegenerateFace => write!(f, "face with less than 3 vertices"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+#[derive(Clone, Copy, Debug)]
+struct Triangle {
+    positions: [usize; 3],
+    normals: [Option<usize>; 3],
+    /// `0` if the face is not in a smoothing group.
+    group: u32,
+    normal: Vec3,
+    /// The interior angle at each corner, weighting the contribution of the
+    /// triangle to the smoothed normal of the corner.
+    ///
+    /// Unlike area weighting, this does not depend on how faces are
+    /// triangulated.
+    angles: [f32; 3],
+}
+
+/// Import the triangles of an OBJ `source` as a flat [`Vertex`] list.
+pub fn import(source: &str, options: &ObjOptions) -> Result<Vec<Vertex>, ObjError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut triangles = Vec::new();
+    let mut group = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let error = |kind| ObjError { line: i + 1, kind };
+
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => positions.push(parse_vec3(&mut tokens).map_err(error)?),
+            Some("vn") => normals.push(parse_vec3(&mut tokens).map_err(error)?),
+            Some("s") => {
+                group = match tokens.next() {
+                    Some("off") | None => 0,
+                    Some(token) => parse(token).map_err(error)?,
+                };
+            }
+            Some("f") => {
+                let mut corners = Vec::with_capacity(4);
+                for token in tokens {
+                    let mut indices = token.split('/');
+                    let position = indices.next().unwrap_or_default();
+                    let position = resolve(position, positions.len()).map_err(error)?;
+                    let normal = match indices.nth(1) {
+                        Some(normal) if !normal.is_empty() => {
+                            Some(resolve(normal, normals.len()).map_err(error)?)
+                        }
+                        _ => None,
+                    };
+                    corners.push((position, normal));
+                }
+                if corners.len() < 3 {
+                    return Err(error(ObjErrorKind::DegenerateFace));
+                }
+
+                for k in 1..corners.len() - 1 {
+                    let [a, b, c] = [corners[0], corners[k], corners[k + 1]];
+                    let [pa, pb, pc] = [a.0, b.0, c.0].map(|p| positions[p]);
+                    triangles.push(Triangle {
+                        positions: [a.0, b.0, c.0],
+                        normals: [a.1, b.1, c.1],
+                        group,
+                        normal: (pb - pa).cross(pc - pa).normalize_or(Vec3::Y),
+                        angles: [
+                            (pb - pa).angle_between(pc - pa),
+                            (pc - pb).angle_between(pa - pb),
+                            (pa - pc).angle_between(pb - pc),
+                        ],
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let smoothed = smooth_normals(&triangles, positions.len(), options.smoothing_angle);
+
+    let vertices = triangles
+        .iter()
+        .zip(smoothed.chunks(3))
+        .flat_map(|(triangle, smoothed)| {
+            (0..3).map(|k| {
+                let normal = match triangle.normals[k] {
+                    Some(n) if !options.recompute_normals => normals[n],
+                    _ => smoothed[k],
+                };
+                Vertex {
+                    position: positions[triangle.positions[k]].extend(1.0).to_array(),
+                    normal: normal.extend(0.0).to_array(),
+                }
+            })
+        })
+        .collect();
+    Ok(vertices)
+}
+
+/// The normal of each corner of each triangle.
+fn smooth_normals(triangles: &[Triangle], position_count: usize, angle: f32) -> Vec<Vec3> {
+    let mut adjacent = vec![Vec::new(); position_count];
+    for (t, triangle) in triangles.iter().enumerate() {
+        for (k, &p) in triangle.positions.iter().enumerate() {
+            adjacent[p].push((t, k));
+        }
+    }
+
+    let min_cos = angle.to_radians().cos();
+    let mut normals = Vec::with_capacity(triangles.len() * 3);
+    for triangle in triangles {
+        let face = triangle.normal;
+        for &p in &triangle.positions {
+            if triangle.group == 0 {
+                normals.push(face);
+                continue;
+            }
+
+            let sum: Vec3 = adjacent[p]
+                .iter()
+                .map(|&(t, k)| (&triangles[t], k))
+                .filter(|(other, _)| other.group == triangle.group)
+                .filter(|(other, _)| other.normal.dot(face) >= min_cos - 1e-6)
+                .map(|(other, k)| other.normal * other.angles[k])
+                .sum();
+            normals.push(sum.normalize_or(face));
+        }
+    }
+    normals
+}
+
+fn parse<T: std::str::FromStr>(token: &str) -> Result<T, ObjErrorKind> {
+    token
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(token.to_string()))
+}
+
+fn parse_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec3, ObjErrorKind> {
+    let mut v = [0.0; 3];
+    for c in &mut v {
+        *c = parse(tokens.next().ok_or(ObjErrorKind::MissingComponent)?)?;
+    }
+    Ok(Vec3::from_array(v))
+}
+
+/// Resolve a 1-based, possibly negative (relative) OBJ index.
+fn resolve(token: &str, len: usize) -> Result<usize, ObjErrorKind> {
+    let index: i64 = parse(token)?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange(index));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "
+        v -1 -1 -1
+        v  1 -1 -1
+        v  1  1 -1
+        v -1  1 -1
+        v -1 -1  1
+        v  1 -1  1
+        v  1  1  1
+        v -1  1  1
+        s 1
+        f 1 4 3 2
+        f 5 6 7 8
+        f 1 2 6 5
+        f 3 4 8 7
+        f 2 3 7 6
+        f 4 1 5 8
+    ";
+
+    #[test]
+    fn smoothing_angle_keeps_hard_edges() {
+        let hard = import(CUBE, &ObjOptions::default()).unwrap();
+        assert_eq!(hard.len(), 36);
+        for vertex in &hard {
+            let normal = Vec3::from_slice(&vertex.normal[..3]);
+            assert_eq!(normal.abs().max_element(), 1.0);
+        }
+
+        let options = ObjOptions {
+            smoothing_angle: 180.0,
+            ..Default::default()
+        };
+        let soft = import(CUBE, &options).unwrap();
+        for vertex in &soft {
+            let normal = Vec3::from_slice(&vertex.normal[..3]);
+            let corner = Vec3::from_slice(&vertex.position[..3]).normalize();
+            assert!(normal.abs_diff_eq(corner, 1e-5));
+        }
+
+        let flat = import(&CUBE.replace("s 1", "s off"), &options).unwrap();
+        assert_eq!(flat, hard);
+    }
+
+    #[test]
+    fn errors() {
+        let err = import("v 0 0 0\nf 1 2 -1", &ObjOptions::default()).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.kind, ObjErrorKind::IndexOutOfRange(2));
+
+        let err = import("v 0 zero 0", &ObjOptions::default()).unwrap_err();
+        assert_eq!(err.kind, ObjErrorKind::InvalidNumber("zero".into()));
+    }
+}
@@ -0,0 +1,266 @@
+//! Scheduling hints for the update and render threads.
+//!
+//! The render thread busy-loops waiting on fences and the boundary cross, and
+//! on CPUs with few cores it competes with the update thread for the same
+//! core. [`ThreadHints`] lower or raise the priority of a thread, and pin it
+//! to a set of cores, through the scheduler of the operating system.
+//!
+//! Hints are configured with
+//! [`StartupHandler::with_thread_hints`](crate::StartupHandler::with_thread_hints)
+//! and applied by each thread at the start of its first frame. Hints the
+//! platform does not support, or is not allowed to apply (e.g. raising the
+//! priority without the required privileges), are logged and ignored.
+//!
+//! Only Linux and Windows are supported.
+//!
+//! Per-user directories and persisted settings are in [`config`].
+//!
+//! # Example
+//! ```rust,ignore
+//! startup.with_thread_hints(
+//!     ThreadHints::new().with_priority(ThreadPriority::High),
+//!     ThreadHints::new()
+//!         .with_priority(ThreadPriority::Low)
+//!         .with_affinity([1]),
+//! );
+//! ```
+
+use tracing::{Level, event};
+
+pub mod config;
+
+/// The scheduling priority of a thread, relative to the other threads of the
+/// process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ThreadPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// A scheduling hint which could not be applied.
+#[derive(Debug)]
+pub enum ThreadHintError {
+    /// The platform does not support thread hints.
+    Unsupported,
+    /// The core index exceeds the amount of cores an affinity mask can hold.
+    InvalidCore(usize),
+    /// The operating system refused the hint.
+    Os(std::io::Error),
+}
+
+impl std::fmt::Display for ThreadHintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "thread hints are not supported on this platform"),
+            Self::InvalidCore(core) => write!(f, "core {core} is out of the affinity mask range"),
+            Self::Os(err) => write!(f, "the operating system refused the hint: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ThreadHintError {}
+
+/// The priority and core affinity to apply to a thread.
+///
+/// Unset hints leave the scheduling of the thread untouched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadHints {
+    pub priority: Option<ThreadPriority>,
+    /// The indices of the cores the thread may run on.
+    pub affinity: Option<Vec<usize>>,
+}
+
+impl ThreadHints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_priority(mut self, priority: ThreadPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn with_affinity(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.affinity = Some(cores.into_iter().collect());
+        self
+    }
+
+    /// Whether no hint is set.
+    pub fn is_empty(&self) -> bool {
+        self.priority.is_none() && self.affinity.is_none()
+    }
+
+    /// Apply the hints to the calling thread.
+    ///
+    /// The priority is applied first, and the affinity is not applied if it
+    /// fails.
+    pub fn apply(&self) -> Result<(), ThreadHintError> {
+        if let Some(priority) = self.priority {
+            sys::set_priority(priority)?;
+        }
+        if let Some(cores) = &self.affinity {
+            sys::set_affinity(&affinity_mask(cores)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Apply `hints` to the calling thread, named `thread` in the logs, at most
+/// once.
+pub(crate) fn apply_once(hints: &mut Option<ThreadHints>, thread: &str) {
+    let Some(hints) = hints.take() else {
+        return;
+    };
+    match hints.apply() {
+        Ok(()) => event!(
+            name: "platform.thread_hints",
+            Level::DEBUG,
+            "Applied scheduling hints to the {thread} thread: {hints:?}"
+        ),
+        Err(err) => event!(
+            name: "platform.thread_hints",
+            Level::WARN,
+            "Failed to apply scheduling hints to the {thread} thread: {err}"
+        ),
+    }
+}
+
+/// The amount of words of an affinity mask, as large as the `cpu_set_t` of
+/// glibc on Linux, and a single word on Windows.
+#[cfg(not(windows))]
+const MASK_WORDS: usize = 1024 / usize::BITS as usize;
+#[cfg(windows)]
+const MASK_WORDS: usize = 1;
+
+/// The affinity mask selecting `cores`, with core `i` at bit `i % usize::BITS`
+/// of word `i / usize::BITS`.
+fn affinity_mask(cores: &[usize]) -> Result<[usize; MASK_WORDS], ThreadHintError> {
+    let bits = usize::BITS as usize;
+    let mut mask = [0; MASK_WORDS];
+    for &core in cores {
+        let word = mask
+            .get_mut(core / bits)
+            .ok_or(ThreadHintError::InvalidCore(core))?;
+        *word |= 1 << (core % bits);
+    }
+    Ok(mask)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::{MASK_WORDS, ThreadHintError, ThreadPriority};
+
+    const PRIO_PROCESS: i32 = 0;
+
+    unsafe extern "C" {
+        fn setpriority(which: i32, who: u32, prio: i32) -> i32;
+        fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const usize) -> i32;
+    }
+
+    fn check(ret: i32) -> Result<(), ThreadHintError> {
+        match ret {
+            0 => Ok(()),
+            _ => Err(ThreadHintError::Os(std::io::Error::last_os_error())),
+        }
+    }
+
+    pub(super) fn set_priority(priority: ThreadPriority) -> Result<(), ThreadHintError> {
+        let nice = match priority {
+            ThreadPriority::Low => 10,
+            ThreadPriority::Normal => 0,
+            ThreadPriority::High => -5,
+        };
+        // the nice value is per thread on Linux, and `who = 0` is the calling
+        // thread rather than the whole process
+        check(unsafe { setpriority(PRIO_PROCESS, 0, nice) })
+    }
+
+    pub(super) fn set_affinity(mask: &[usize; MASK_WORDS]) -> Result<(), ThreadHintError> {
+        let size = std::mem::size_of_val(mask);
+        check(unsafe { sched_setaffinity(0, size, mask.as_ptr()) })
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+
+    use super::{MASK_WORDS, ThreadHintError, ThreadPriority};
+
+    const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+    const THREAD_PRIORITY_NORMAL: i32 = 0;
+    const THREAD_PRIORITY_ABOVE_NORMAL: i32 = 1;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+        fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+    }
+
+    fn last_error() -> ThreadHintError {
+        ThreadHintError::Os(std::io::Error::last_os_error())
+    }
+
+    pub(super) fn set_priority(priority: ThreadPriority) -> Result<(), ThreadHintError> {
+        let priority = match priority {
+            ThreadPriority::Low => THREAD_PRIORITY_BELOW_NORMAL,
+            ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+            ThreadPriority::High => THREAD_PRIORITY_ABOVE_NORMAL,
+        };
+        match unsafe { SetThreadPriority(GetCurrentThread(), priority) } {
+            0 => Err(last_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn set_affinity(mask: &[usize; MASK_WORDS]) -> Result<(), ThreadHintError> {
+        match unsafe { SetThreadAffinityMask(GetCurrentThread(), mask[0]) } {
+            0 => Err(last_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod sys {
+    use super::{MASK_WORDS, ThreadHintError, ThreadPriority};
+
+    pub(super) fn set_priority(_priority: ThreadPriority) -> Result<(), ThreadHintError> {
+        Err(ThreadHintError::Unsupported)
+    }
+
+    pub(super) fn set_affinity(_mask: &[usize; MASK_WORDS]) -> Result<(), ThreadHintError> {
+        Err(ThreadHintError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_hint_affinity_mask() {
+        let bits = usize::BITS as usize;
+        let mask = affinity_mask(&[0, 3]).unwrap();
+        assert_eq!(mask[0], 0b1001);
+        assert!(mask[1..].iter().all(|&word| word == 0));
+
+        let last = MASK_WORDS * bits - 1;
+        assert_eq!(
+            affinity_mask(&[last]).unwrap()[MASK_WORDS - 1],
+            1 << (bits - 1)
+        );
+        assert!(matches!(
+            affinity_mask(&[last + 1]),
+            Err(ThreadHintError::InvalidCore(core)) if core == last + 1
+        ));
+
+        assert!(ThreadHints::new().is_empty());
+        let hints = ThreadHints::new().with_affinity([2, 1]);
+        assert_eq!(hints.affinity.as_deref(), Some(&[2, 1][..]));
+        assert!(!hints.is_empty());
+    }
+}
@@ -0,0 +1,357 @@
+//! Per-user configuration and save directories, and settings persisted
+//! across runs.
+//!
+//! [`AppDirs`] resolves the directories of an application following the
+//! conventions of each operating system:
+//!
+//! | OS      | Config                                  | Saves                                       |
+//! |---------|-----------------------------------------|---------------------------------------------|
+//! | Linux   | `$XDG_CONFIG_HOME/<app>`, `~/.config/<app>` | `$XDG_DATA_HOME/<app>/saves`, `~/.local/share/<app>/saves` |
+//! | macOS   | `~/Library/Application Support/<app>`   | `~/Library/Application Support/<app>/saves` |
+//! | Windows | `%APPDATA%\<app>`                       | `%APPDATA%\<app>\saves`                     |
+//!
+//! The `ETHEL_CONFIG_DIR` environment variable overrides the config
+//! directory on every platform, e.g. to keep portable installs self
+//! contained.
+//!
+//! Settings are persisted in a [`Config`] file of `key = value` lines. Types
+//! stored in it, such as the [`RenderSettings`] or the tweaks and key
+//! bindings of an application, implement [`Persist`] under their own key
+//! prefix. A config passed to
+//! [`StartupHandler::with_config`](crate::StartupHandler::with_config) is
+//! restored into the render settings during setup, and kept by the
+//! [`State`](crate::state::State) for the handler.
+//!
+//! # Example
+//! ```rust,ignore
+//! let dirs = AppDirs::resolve("my-game").expect("no home directory");
+//! let config = Config::open(dirs.config_file("settings.cfg")).unwrap_or_default();
+//! startup.with_config(config);
+//!
+//! // later, e.g. when leaving the settings menu
+//! bindings.store(state.config_mut());
+//! state.config().save()?;
+//! ```
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{render::settings::RenderSettings, state::stats::StatsSettings};
+
+/// The environment variable overriding the config directory.
+pub const ENV_CONFIG_DIR: &str = "ETHEL_CONFIG_DIR";
+
+/// The per-user directories of an application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppDirs {
+    pub config: PathBuf,
+    pub saves: PathBuf,
+}
+
+impl AppDirs {
+    /// The directories of the application named `app` on the current
+    /// platform.
+    ///
+    /// # Returns
+    /// `None` if the home directory of the user cannot be found.
+    pub fn resolve(app: &str) -> Option<Self> {
+        Self::resolve_with(app, |name| std::env::var_os(name))
+    }
+
+    /// The directories of `app`, reading environment variables through
+    /// `var`.
+    fn resolve_with(app: &str, var: impl Fn(&str) -> Option<OsString>) -> Option<Self> {
+        // only absolute paths are valid XDG directories
+        let dir = |name| var(name).map(PathBuf::from).filter(|p| p.is_absolute());
+
+        let (config, data) = if cfg!(windows) {
+            let app_data = dir("APPDATA")?;
+            (app_data.clone(), app_data)
+        } else if cfg!(target_os = "macos") {
+            let support = dir("HOME")?.join("Library").join("Application Support");
+            (support.clone(), support)
+        } else {
+            let home = dir("HOME");
+            let config = dir("XDG_CONFIG_HOME").or_else(|| Some(home.clone()?.join(".config")));
+            let data =
+                dir("XDG_DATA_HOME").or_else(|| Some(home.clone()?.join(".local").join("share")));
+            (config?, data?)
+        };
+
+        let config = dir(ENV_CONFIG_DIR).unwrap_or_else(|| config.join(app));
+        Some(Self {
+            config,
+            saves: data.join(app).join("saves"),
+        })
+    }
+
+    /// The path of the config file `name`.
+    pub fn config_file(&self, name: &str) -> PathBuf {
+        self.config.join(name)
+    }
+
+    /// The path of the save file `name`.
+    pub fn save_file(&self, name: &str) -> PathBuf {
+        self.saves.join(name)
+    }
+
+    /// Create the directories if they do not exist.
+    pub fn create(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.config)?;
+        std::fs::create_dir_all(&self.saves)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    /// A line is neither a `key = value` pair, a comment nor blank.
+    Syntax {
+        line: usize,
+    },
+    /// [`Config::save`] was called on a config without a file.
+    NoPath,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "cannot access the config file: {err}"),
+            Self::Syntax { line } => write!(f, "line {line} of the config is not `key = value`"),
+            Self::NoPath => write!(f, "the config has no file to save to"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Settings persisted as `key = value` lines, in order of insertion.
+///
+/// Keys are namespaced with dots, e.g. `render.extrapolate`. Lines starting
+/// with `#` are comments, which are not preserved when saving.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    entries: Vec<(String, String)>,
+    path: Option<PathBuf>,
+}
+
+impl Config {
+    /// An empty config, without a file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the config file at `path`, saved back to it by [`Self::save`].
+    ///
+    /// A missing file is an empty config.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::new(),
+            Err(err) => return Err(err.into()),
+        };
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .filter(|(key, _)| !key.trim().is_empty())
+                .ok_or(ConfigError::Syntax { line: i + 1 })?;
+            config.set(key.trim(), value.trim());
+        }
+        Ok(config)
+    }
+
+    /// The file of the config, if opened from one.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Write the config to its file, creating its directory if needed.
+    ///
+    /// The file is replaced at once, so that a crash while saving keeps the
+    /// previous settings.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = self.path.as_deref().ok_or(ConfigError::NoPath)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.to_string())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of `key` parsed as `T`, `None` if missing or invalid.
+    pub fn get_parsed<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// Set `key` to `value`, keeping its position if it exists.
+    pub fn set(&mut self, key: &str, value: impl std::fmt::Display) {
+        let value = value.to_string();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.entries.push((key.to_owned(), value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The entries whose key starts with `prefix` and a dot, with the prefix
+    /// stripped, e.g. the key bindings under `bindings`.
+    pub fn section<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.iter().filter_map(move |(key, value)| {
+            let key = key.strip_prefix(prefix)?.strip_prefix('.')?;
+            Some((key, value))
+        })
+    }
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in &self.entries {
+            writeln!(f, "{key} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings stored in a [`Config`].
+pub trait Persist {
+    /// Write the settings to `config`.
+    fn store(&self, config: &mut Config);
+
+    /// Read the settings from `config`, keeping the current value of the
+    /// settings which are missing or invalid.
+    fn restore(&mut self, config: &Config);
+}
+
+impl Persist for RenderSettings {
+    fn store(&self, config: &mut Config) {
+        config.set("render.extrapolate", self.extrapolate);
+        config.set("render.max_extrapolation", self.max_extrapolation);
+    }
+
+    fn restore(&mut self, config: &Config) {
+        if let Some(extrapolate) = config.get_parsed("render.extrapolate") {
+            self.extrapolate = extrapolate;
+        }
+        if let Some(max) = config.get_parsed("render.max_extrapolation") {
+            self.max_extrapolation = max;
+        }
+    }
+}
+
+impl Persist for StatsSettings {
+    fn store(&self, config: &mut Config) {
+        match &self.export {
+            Some(path) => config.set("stats.export", path.display()),
+            None => _ = config.remove("stats.export"),
+        }
+    }
+
+    fn restore(&mut self, config: &Config) {
+        if let Some(path) = config.get("stats.export") {
+            self.export = (!path.is_empty()).then(|| PathBuf::from(path));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trip() {
+        let text =
+            "# settings\nrender.extrapolate = true\n\nbindings.jump = Space\nbindings.jump=W\n";
+        let config = Config::parse(text).unwrap();
+        assert_eq!(config.get("bindings.jump"), Some("W"));
+        assert_eq!(
+            config.section("bindings").collect::<Vec<_>>(),
+            [("jump", "W")]
+        );
+        assert!(matches!(
+            Config::parse("a = 1\nnot a pair"),
+            Err(ConfigError::Syntax { line: 2 })
+        ));
+
+        let mut settings = RenderSettings::default();
+        settings.restore(&config);
+        assert!(settings.extrapolate);
+        assert_eq!(
+            settings.max_extrapolation,
+            RenderSettings::DEFAULT_MAX_EXTRAPOLATION
+        );
+
+        let mut stored = Config::new();
+        settings.store(&mut stored);
+        assert_eq!(Config::parse(&stored.to_string()).unwrap(), stored);
+
+        // exporting stats is opt-in
+        let mut stats = StatsSettings::default();
+        stats.restore(&config);
+        assert_eq!(stats.export, None);
+        stats.restore(&Config::parse("stats.export = frames.csv").unwrap());
+        assert_eq!(stats.export.as_deref(), Some(Path::new("frames.csv")));
+        stats.store(&mut stored);
+        assert_eq!(stored.get("stats.export"), Some("frames.csv"));
+
+        let var = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| OsString::from(v))
+            }
+        };
+        let dirs = AppDirs::resolve_with("game", var(&[(ENV_CONFIG_DIR, "/portable")]));
+        if cfg!(all(unix, not(target_os = "macos"))) {
+            let dirs = AppDirs::resolve_with("game", var(&[("HOME", "/home/u")])).unwrap();
+            assert_eq!(dirs.config, Path::new("/home/u/.config/game"));
+            assert_eq!(
+                dirs.save_file("1.sav"),
+                Path::new("/home/u/.local/share/game/saves/1.sav")
+            );
+            // relative XDG directories are ignored
+            let relative = var(&[("HOME", "/home/u"), ("XDG_CONFIG_HOME", "cfg")]);
+            assert_eq!(
+                AppDirs::resolve_with("game", relative).unwrap().config,
+                dirs.config
+            );
+            assert_eq!(dirs.config_file("a.cfg"), dirs.config.join("a.cfg"));
+        }
+        assert_eq!(dirs, None);
+    }
+}
@@ -0,0 +1,64 @@
+//! The commonly used types, traits and macros of Ethel.
+//!
+//! Also exports the [`glam`] and [`janus`] types found in the signatures of
+//! the handler traits, so that applications do not need to depend on the
+//! exact versions Ethel is built with.
+//!
+//! ```rust,ignore
+//! use ethel::prelude::*;
+//! ```
+
+pub use crate::{
+    DrawCommand, IndexedDrawCommand, InputSystem, RenderHandler, StartupHandler, StateHandler,
+    mesh::{self, MeshPoolId, MeshStaging, Meshadata, Vertex},
+    render::{
+        Renderer, Resolution, ScreenSpace,
+        batch::CommandBatcher,
+        buffer::{
+            ImmutableBuffer, Layout, PartitionedTriBuffer, StorageSection, TriBuffer, View, ViewMut,
+        },
+        color::{Color, ColorSpace},
+        command::{
+            DrawArraysIndirectCommand, DrawElementsIndirectCommand, DrawGroups, GpuCommandDispatch,
+            GpuCommandQueue,
+        },
+        config::BufferConfig,
+        material::{Material, MaterialId},
+        per_draw::DrawConstants,
+        pool::MeshPools,
+        stage::{RenderStage, StageContext},
+    },
+    shader::{ShaderError, ShaderProgram, ShaderProgramBuilder},
+    state::{
+        State,
+        arena::StagingArena,
+        camera::ViewPoint,
+        cross::{Consumer, Cross, FramesInFlight, Producer, SectionAge},
+        data::{
+            ArrayColumn, Column, DirectIndex, IndexArrayColumn, IndirectIndex,
+            ParallelIndexArrayColumn,
+        },
+    },
+};
+pub use crate::{
+    layout_buffer, layout_mesh_buffer, shader_glsl, shader_glsl_compute, shader_glsl_ssbo,
+    shader_glsl_struct,
+};
+
+#[cfg(feature = "camera")]
+pub use crate::state::camera::Orbital;
+#[cfg(feature = "tables")]
+pub use crate::{state::data::Table, table_spec};
+
+#[cfg(feature = "assets")]
+pub use crate::{
+    assets::{AssetId, AssetRegistry, Handle, Import, Upload},
+    lazy_hash_str,
+};
+
+pub use glam::{IVec2, Mat3, Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
+pub use janus::{
+    context::DeltaTime,
+    input::KeyEvent,
+    sync::{Mirror, TriCell},
+};
@@ -0,0 +1,158 @@
+//! Per-entity playback of shared animations.
+//!
+//! Entities sharing one animation, e.g. the members of a crowd, would move
+//! in lockstep when sampled at the same time. Each entity carries an
+//! [`AnimationPhase`] instead, stored per entity in a column and uploaded to
+//! the partition declared by [`GLSL_SSBO_ANIMATION_PHASES`] in the same
+//! order as the other per-entity partitions. Skinning and vertex animation
+//! shaders sample the animation at the local time of the entity, computed
+//! from the time of the [`FrameHeader`](super::frame::FrameHeader) with
+//! [`GLSL_LIB_ANIMATION_TIME`].
+//!
+//! Animations baked to vertex samples are played back from that local time
+//! too, see [`vat`].
+//!
+//! # Example
+//! ```rust,ignore
+//! layout_buffer! {
+//!     const EntityData: 2, {
+//!         enum transforms: 100_000 => {
+//!             type Mat4;
+//!             bind 0;
+//!             shader SHADER_BINDING_INSTANCES;
+//!         };
+//!         enum phases: 100_000 => {
+//!             type AnimationPhase;
+//!             bind 1;
+//!             shader SHADER_BINDING_ANIMATION_PHASES;
+//!         };
+//!     }
+//! }
+//!
+//! // at spawn
+//! props.phase.push(AnimationPhase::scattered(id, WALK_CYCLE).with_rate(speed / WALK_SPEED));
+//!
+//! // UPLOAD
+//! storage.blit_part(section, LayoutEntityData::Phases as usize, &props.phase, 0);
+//!
+//! // VERTEX SHADER
+//! float t = animation_time_looped(gl_InstanceID, time, WALK_CYCLE);
+//! ```
+
+use crate::shader::glsl::{GlslLib, GlslStorage};
+
+pub mod vat;
+
+macro_rules! ssbo_binding {
+    (AnimationPhases) => {
+        26
+    };
+}
+
+pub const SHADER_BINDING_ANIMATION_PHASES: u32 = ssbo_binding!(AnimationPhases);
+
+/// The time offset and playback rate of the animation of an entity.
+///
+/// Corresponds to a `vec2(offset, rate)` of [`GLSL_SSBO_ANIMATION_PHASES`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimationPhase {
+    /// Seconds added to the local time of the animation.
+    pub offset: f32,
+    /// The speed of playback, `1.0` for the authored speed.
+    pub rate: f32,
+}
+
+impl Default for AnimationPhase {
+    fn default() -> Self {
+        Self::new(0.0, 1.0)
+    }
+}
+
+impl AnimationPhase {
+    pub const fn new(offset: f32, rate: f32) -> Self {
+        Self { offset, rate }
+    }
+
+    /// An offset within a `duration` seconds long animation derived from the
+    /// `entity` index, spreading consecutive entities over the whole cycle.
+    pub fn scattered(entity: u32, duration: f32) -> Self {
+        // integer hash of the index, so that neighbours are uncorrelated
+        let mut x = entity.wrapping_add(0x9e37_79b9);
+        x = (x ^ (x >> 16)).wrapping_mul(0x21f0_aaad);
+        x = (x ^ (x >> 15)).wrapping_mul(0x735a_2d97);
+        x ^= x >> 15;
+        let unit = (x >> 8) as f32 / (1 << 24) as f32;
+        Self::new(unit * duration, 1.0)
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// The local time of the animation at the global `time`, in seconds.
+    pub fn local_time(&self, time: f32) -> f32 {
+        time * self.rate + self.offset
+    }
+
+    /// The local time of a looping animation of `duration` seconds at the
+    /// global `time`, in `[0, duration)`.
+    pub fn looped(&self, time: f32, duration: f32) -> f32 {
+        self.local_time(time).rem_euclid(duration)
+    }
+}
+
+/// Animation phases SSBO interface.
+///
+/// Contains the SSBO declaration of an [`AnimationPhase`] partition, on
+/// binding index 26, as `vec2(offset, rate)` per entity.
+pub const GLSL_SSBO_ANIMATION_PHASES: GlslStorage = crate::shader_glsl_ssbo! {
+    buf AnimationPhases => {
+        [dyn_array vec2: animation_phases]
+    }
+};
+
+/// The local animation time of the entity at `id` of the
+/// [`GLSL_SSBO_ANIMATION_PHASES`] partition at the global `time`, as
+/// computed by [`AnimationPhase::local_time`].
+pub const GLSL_LIB_ANIMATION_TIME: GlslLib = crate::shader_glsl_lib! {
+    float animation_time [ id: uint, time: float ] => "
+        vec2 phase = animation_phases[id];
+        return time * phase.y + phase.x;
+    "
+};
+
+/// The local time of a looping animation of `duration` seconds, as computed
+/// by [`AnimationPhase::looped`]. Requires [`GLSL_LIB_ANIMATION_TIME`].
+pub const GLSL_LIB_ANIMATION_TIME_LOOPED: GlslLib = crate::shader_glsl_lib! {
+    float animation_time_looped [ id: uint, time: float, duration: float ] => "
+        float t = animation_time(id, time);
+        return t - duration * floor(t / duration);
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animation_phases() {
+        let phase = AnimationPhase::new(0.5, 2.0);
+        assert_eq!(phase.local_time(1.0), 2.5);
+        assert_eq!(phase.looped(1.0, 2.0), 0.5);
+        // rewinding animations loop too
+        assert_eq!(phase.with_rate(-1.0).looped(1.0, 2.0), 1.5);
+        assert_eq!(AnimationPhase::default().local_time(3.0), 3.0);
+        assert_eq!(size_of::<AnimationPhase>(), 8);
+
+        let offsets: Vec<f32> = (0..64)
+            .map(|entity| AnimationPhase::scattered(entity, 4.0).offset)
+            .collect();
+        assert!(offsets.iter().all(|offset| (0.0..4.0).contains(offset)));
+        // consecutive entities are spread over the cycle
+        assert!(offsets.iter().any(|offset| *offset < 1.0));
+        assert!(offsets.iter().any(|offset| *offset >= 3.0));
+        assert_ne!(offsets[0], offsets[1]);
+    }
+}
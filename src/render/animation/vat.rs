@@ -0,0 +1,386 @@
+//! Vertex animation baked to storage buffers (VAT), a cheap alternative to
+//! GPU skinning for crowds.
+//!
+//! At build time or startup, animations are evaluated on the CPU and the
+//! position and normal of every vertex at every frame are stored in a
+//! [`VatBank`], either from any procedural function with
+//! [`VatBank::bake`], or from a skeleton with [`VatBank::bake_skinned`].
+//! Each baked animation is a [`VatClip`] of the bank.
+//!
+//! The bank is uploaded once with [`VatBank::upload`], and the vertex shader
+//! plays the clips back with [`GLSL_LIB_VAT_POSITION`] and
+//! [`GLSL_LIB_VAT_NORMAL`], interpolating between the two frames around the
+//! local time of the entity. The only per-entity data is its
+//! [`AnimationPhase`](super::AnimationPhase): no joint matrices are computed
+//! or uploaded.
+//!
+//! The meshes are still drawn from the mesh buffer, whose vertices provide
+//! everything but the position and normal, e.g. the texture coordinates.
+//! Their bounds should cover every frame, see [`VatBank::bounds`].
+//!
+//! # Example
+//! ```rust,ignore
+//! // STARTUP
+//! let mut bank = VatBank::new();
+//! let walk = bank.bake_skinned(&rest_pose, &skin, 30, 30.0, |time, joints| {
+//!     skeleton.pose(&walk_animation, time, joints)
+//! });
+//! mesh_bounds.set(soldier, bank.bounds(walk));
+//! let vat = bank.upload();
+//!
+//! // RENDER
+//! vat.bind_shader_storage();
+//!
+//! // VERTEX SHADER
+//! float t = animation_time(gl_InstanceID, time);
+//! vec3 position = vat_position(vat_clips[WALK], gl_VertexID - base_vertex, t);
+//! vec3 normal = vat_normal(vat_clips[WALK], gl_VertexID - base_vertex, t);
+//! ```
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{
+    render::{
+        bounds::Aabb,
+        buffer::{ImmutableBuffer, Layout, immutable},
+    },
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (VatSamples) => {
+        27
+    };
+    (VatClips) => {
+        28
+    };
+}
+
+pub const SHADER_BINDING_VAT_SAMPLES: u32 = ssbo_binding!(VatSamples);
+pub const SHADER_BINDING_VAT_CLIPS: u32 = ssbo_binding!(VatClips);
+
+/// The partition of the [`VatSample`]s in the buffer of a [`VatBank`].
+pub const BUFFER_VAT_SAMPLES_INDEX: usize = 0;
+/// The partition of the [`VatClip`]s in the buffer of a [`VatBank`].
+pub const BUFFER_VAT_CLIPS_INDEX: usize = 1;
+
+/// The position and normal of a vertex at a frame of a clip.
+///
+/// Corresponds to the `VatSample` struct of [`GLSL_SSBO_VAT_SAMPLES`] in a
+/// `std430` layout. The `w` components are unused and kept at `0.0`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VatSample {
+    pub position: Vec4,
+    pub normal: Vec4,
+}
+
+impl VatSample {
+    pub fn new(position: Vec3, normal: Vec3) -> Self {
+        Self {
+            position: position.extend(0.0),
+            normal: normal.extend(0.0),
+        }
+    }
+
+    /// The sample interpolated between `self` and `other` by `t`, with a
+    /// normalised normal.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self::new(
+            self.position.lerp(other.position, t).truncate(),
+            self.normal
+                .lerp(other.normal, t)
+                .truncate()
+                .normalize_or_zero(),
+        )
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct VatSample for VatSample {
+        position: Vec4 => vec4;
+        normal: Vec4 => vec4;
+    }
+}
+
+/// A looping animation baked into a [`VatBank`].
+///
+/// Corresponds to the `VatClip` struct of [`GLSL_SSBO_VAT_CLIPS`] in a
+/// `std430` layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VatClip {
+    /// The index of the first sample of the clip in the bank.
+    pub first: u32,
+    /// The amount of vertices of the animated mesh.
+    pub vertices: u32,
+    pub frames: u32,
+    /// The frames per second the clip was baked at.
+    pub frame_rate: f32,
+}
+
+impl VatClip {
+    /// The length of the loop, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames as f32 / self.frame_rate
+    }
+
+    /// The frames around the local `time`, and the interpolation factor
+    /// between them.
+    pub fn frames_at(&self, time: f32) -> (u32, u32, f32) {
+        let frame = (time * self.frame_rate).rem_euclid(self.frames as f32);
+        let current = (frame as u32).min(self.frames - 1);
+        (current, (current + 1) % self.frames, frame.fract())
+    }
+
+    /// The index in the bank of the sample of `vertex` at `frame`.
+    pub fn index(&self, frame: u32, vertex: u32) -> usize {
+        (self.first + frame * self.vertices + vertex) as usize
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct VatClip for VatClip {
+        first: u32 => uint;
+        vertices: u32 => uint;
+        frames: u32 => uint;
+        frame_rate: f32 => float;
+    }
+}
+
+/// The influence of up to four joints on a vertex, for
+/// [`VatBank::bake_skinned`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SkinWeights {
+    pub joints: [u32; 4],
+    /// The weights of the joints, summing to `1.0`.
+    pub weights: [f32; 4],
+}
+
+/// Animations baked to vertex samples, and uploaded to a storage buffer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VatBank {
+    samples: Vec<VatSample>,
+    clips: Vec<VatClip>,
+}
+
+impl VatBank {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bake a clip of `frames` frames at `frame_rate` frames per second, of
+    /// a mesh of `vertices` vertices, whose samples at each frame are written
+    /// by `sample` from the local time of the frame in seconds.
+    ///
+    /// # Returns
+    /// The index of the clip.
+    ///
+    /// # Panics
+    /// If `frames` or `vertices` is zero.
+    pub fn bake(
+        &mut self,
+        vertices: usize,
+        frames: u32,
+        frame_rate: f32,
+        mut sample: impl FnMut(f32, &mut [VatSample]),
+    ) -> usize {
+        assert!(
+            vertices > 0 && frames > 0,
+            "a baked clip requires vertices and frames"
+        );
+        let first = self.samples.len();
+        self.samples
+            .resize(first + vertices * frames as usize, VatSample::default());
+        for frame in 0..frames {
+            let start = first + frame as usize * vertices;
+            let time = frame as f32 / frame_rate;
+            sample(time, &mut self.samples[start..start + vertices]);
+        }
+        self.clips.push(VatClip {
+            first: first as u32,
+            vertices: vertices as u32,
+            frames,
+            frame_rate,
+        });
+        self.clips.len() - 1
+    }
+
+    /// Bake a clip of a skinned mesh in its `rest` pose, with linear blend
+    /// skinning of the joint matrices written by `pose` from the local time
+    /// of each frame.
+    ///
+    /// The joint matrices are the transforms from the rest pose to the
+    /// animated pose, i.e. with the inverse bind matrices applied.
+    ///
+    /// See [`Self::bake`].
+    ///
+    /// # Panics
+    /// If `skin` is not of the length of `rest`.
+    pub fn bake_skinned(
+        &mut self,
+        rest: &[VatSample],
+        skin: &[SkinWeights],
+        frames: u32,
+        frame_rate: f32,
+        mut pose: impl FnMut(f32, &mut Vec<Mat4>),
+    ) -> usize {
+        assert_eq!(rest.len(), skin.len(), "every vertex requires skin weights");
+        let mut joints = Vec::new();
+        self.bake(rest.len(), frames, frame_rate, |time, out| {
+            joints.clear();
+            pose(time, &mut joints);
+            for ((out, rest), skin) in out.iter_mut().zip(rest).zip(skin) {
+                let matrix = skin
+                    .joints
+                    .iter()
+                    .zip(skin.weights)
+                    .filter(|(_, weight)| *weight != 0.0)
+                    .fold(Mat4::ZERO, |matrix, (joint, weight)| {
+                        matrix + joints[*joint as usize] * weight
+                    });
+                *out = VatSample::new(
+                    matrix.transform_point3(rest.position.truncate()),
+                    matrix
+                        .transform_vector3(rest.normal.truncate())
+                        .normalize_or_zero(),
+                );
+            }
+        })
+    }
+
+    pub fn samples(&self) -> &[VatSample] {
+        &self.samples
+    }
+
+    pub fn clips(&self) -> &[VatClip] {
+        &self.clips
+    }
+
+    /// The sample of `vertex` of `clip` at the local `time`, interpolated as
+    /// by [`GLSL_LIB_VAT_POSITION`] and [`GLSL_LIB_VAT_NORMAL`].
+    pub fn sample(&self, clip: usize, vertex: u32, time: f32) -> VatSample {
+        let clip = &self.clips[clip];
+        let (current, next, t) = clip.frames_at(time);
+        self.samples[clip.index(current, vertex)].lerp(&self.samples[clip.index(next, vertex)], t)
+    }
+
+    /// The bounds of every frame of `clip`, to set as the bounds of its mesh.
+    pub fn bounds(&self, clip: usize) -> Aabb {
+        let clip = &self.clips[clip];
+        let samples = clip.first as usize..clip.index(clip.frames, 0);
+        Aabb::from_points(self.samples[samples].iter().map(|s| s.position.truncate()))
+    }
+
+    /// The layout of the buffer of the bank, with the samples and clips
+    /// partitions bound to [`SHADER_BINDING_VAT_SAMPLES`] and
+    /// [`SHADER_BINDING_VAT_CLIPS`].
+    pub fn layout(&self) -> Layout<2> {
+        Layout::new()
+            .partition::<VatSample>(self.samples.len())
+            .with_shader_storage(SHADER_BINDING_VAT_SAMPLES)
+            .partition::<VatClip>(self.clips.len())
+            .with_shader_storage(SHADER_BINDING_VAT_CLIPS)
+    }
+
+    /// Upload the bank to an immutable buffer, bound with
+    /// [`ImmutableBuffer::bind_shader_storage`].
+    pub fn upload(&self) -> ImmutableBuffer<2> {
+        let mut buffer = immutable::uninit(self.layout());
+        buffer.fill_partition(BUFFER_VAT_SAMPLES_INDEX, &self.samples);
+        buffer.fill_partition(BUFFER_VAT_CLIPS_INDEX, &self.clips);
+        buffer.finish()
+    }
+}
+
+/// VAT samples SSBO interface.
+///
+/// Contains the SSBO declaration of the samples of a [`VatBank`], on binding
+/// index 27. Requires the `VatSample` struct definition, see
+/// [`VatSampleGlslStruct::as_definition`].
+pub const GLSL_SSBO_VAT_SAMPLES: GlslStorage = crate::shader_glsl_ssbo! {
+    buf VatSamples => {
+        [dyn_array VatSample: vat_samples]
+    }
+};
+
+/// VAT clips SSBO interface.
+///
+/// Contains the SSBO declaration of the clips of a [`VatBank`], on binding
+/// index 28. Requires the `VatClip` struct definition, see
+/// [`VatClipGlslStruct::as_definition`].
+pub const GLSL_SSBO_VAT_CLIPS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf VatClips => {
+        [dyn_array VatClip: vat_clips]
+    }
+};
+
+/// The position of `vertex` of `clip` at the local time `t`, as computed by
+/// [`VatBank::sample`]. Requires [`GLSL_SSBO_VAT_SAMPLES`] and the `VatClip`
+/// struct definition.
+pub const GLSL_LIB_VAT_POSITION: GlslLib = crate::shader_glsl_lib! {
+    vec3 vat_position [ clip: VatClip, vertex: uint, t: float ] => "
+        float frame = mod(t * clip.frame_rate, float(clip.frames));
+        uint current = min(uint(frame), clip.frames - 1u);
+        uint next = (current + 1u) % clip.frames;
+        vec3 a = vat_samples[clip.first + current * clip.vertices + vertex].position.xyz;
+        vec3 b = vat_samples[clip.first + next * clip.vertices + vertex].position.xyz;
+        return mix(a, b, fract(frame));
+    "
+};
+
+/// The normal of `vertex` of `clip` at the local time `t`. Requires
+/// [`GLSL_SSBO_VAT_SAMPLES`] and the `VatClip` struct definition.
+pub const GLSL_LIB_VAT_NORMAL: GlslLib = crate::shader_glsl_lib! {
+    vec3 vat_normal [ clip: VatClip, vertex: uint, t: float ] => "
+        float frame = mod(t * clip.frame_rate, float(clip.frames));
+        uint current = min(uint(frame), clip.frames - 1u);
+        uint next = (current + 1u) % clip.frames;
+        vec3 a = vat_samples[clip.first + current * clip.vertices + vertex].normal.xyz;
+        vec3 b = vat_samples[clip.first + next * clip.vertices + vertex].normal.xyz;
+        return normalize(mix(a, b, fract(frame)));
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bake_and_sample_clips() {
+        let mut bank = VatBank::new();
+        // a vertex moving up by one unit per second, over two seconds
+        let rise = bank.bake(2, 4, 2.0, |time, out| {
+            out[0] = VatSample::new(Vec3::new(0.0, time, 0.0), Vec3::Y);
+            out[1] = VatSample::new(Vec3::new(1.0, time, 0.0), Vec3::Y);
+        });
+        assert_eq!(bank.clips()[rise].duration(), 2.0);
+        assert_eq!(bank.samples().len(), 8);
+        assert_eq!(
+            bank.sample(rise, 1, 0.75).position,
+            Vec4::new(1.0, 0.75, 0.0, 0.0)
+        );
+        // the last frame loops back to the first
+        assert_eq!(bank.sample(rise, 0, 1.75).position.y, 0.75);
+        assert_eq!(bank.sample(rise, 0, 2.25).position.y, 0.25);
+        assert_eq!(
+            bank.bounds(rise),
+            Aabb::new(Vec3::ZERO, Vec3::new(1.0, 1.5, 0.0))
+        );
+
+        // half of the vertex follows a joint translated along x
+        let rest = [VatSample::new(Vec3::ZERO, Vec3::Z)];
+        let skin = [SkinWeights {
+            joints: [0, 1, 0, 0],
+            weights: [0.5, 0.5, 0.0, 0.0],
+        }];
+        let slide = bank.bake_skinned(&rest, &skin, 2, 1.0, |time, joints| {
+            joints.push(Mat4::IDENTITY);
+            joints.push(Mat4::from_translation(Vec3::X * 2.0 * time));
+        });
+        assert_eq!(bank.clips()[slide].first, 8);
+        let moved = bank.sample(slide, 0, 1.0);
+        assert_eq!(moved.position, Vec4::new(1.0, 0.0, 0.0, 0.0));
+        assert_eq!(moved.normal, Vec4::Z);
+    }
+}
@@ -0,0 +1,291 @@
+//! Memory barriers between passes writing and reading GPU resources.
+//!
+//! Shader writes to storage buffers, images and atomic counters are
+//! incoherent: a compute pass filling a buffer that a later draw reads as
+//! indirect commands or vertex data requires a `glMemoryBarrier` in between,
+//! with the bits of how the buffer is read. Missing bits only show as
+//! flickering or stale data on some drivers, and extra bits stall the
+//! pipeline for nothing.
+//!
+//! Instead of issuing barriers by hand, passes declare the resources they
+//! access in a [`PassUsage`], and the [`Barriers`] of the renderer (see
+//! [`StageContext::barriers`](super::stage::StageContext::barriers)) issues
+//! the minimal barrier before each pass: the access bits of the resources
+//! it reads which were written by a shader since the last barrier covering
+//! that access.
+//!
+//! With [`BarrierMode::Full`], every pass is preceded by a barrier of all
+//! bits, whatever its usage, so that a bug disappearing with it is a missing
+//! declaration. It is enabled by the `ETHEL_FULL_BARRIERS` environment
+//! variable, see [`BarrierMode::from_env`].
+//!
+//! # Example
+//! ```rust,ignore
+//! let cull = PassUsage::new()
+//!     .read(inputs.id(), Access::SHADER_STORAGE)
+//!     .write(commands.id());
+//! let draw = PassUsage::new()
+//!     .read(commands.id(), Access::COMMAND)
+//!     .read(transforms.id(), Access::SHADER_STORAGE);
+//!
+//! ctx.barriers.begin_pass(&cull);
+//! culler.dispatch(..);
+//! ctx.barriers.end_pass(&cull);
+//!
+//! // issues COMMAND_BARRIER_BIT only
+//! ctx.barriers.begin_pass(&draw);
+//! dispatch.dispatch();
+//! ctx.barriers.end_pass(&draw);
+//! ```
+
+use rustc_hash::FxHashMap;
+
+/// The environment variable enabling [`BarrierMode::Full`].
+pub const ENV_FULL_BARRIERS: &str = "ETHEL_FULL_BARRIERS";
+
+/// How a resource written by shaders is accessed afterwards, as the bits of
+/// `glMemoryBarrier`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Access(u32);
+
+impl Access {
+    pub const NONE: Self = Self(0);
+    pub const VERTEX_ATTRIB: Self = Self(janus::gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: Self = Self(janus::gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: Self = Self(janus::gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: Self = Self(janus::gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE: Self = Self(janus::gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    /// Indirect draw and dispatch commands.
+    pub const COMMAND: Self = Self(janus::gl::COMMAND_BARRIER_BIT);
+    pub const PIXEL_BUFFER: Self = Self(janus::gl::PIXEL_BUFFER_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: Self = Self(janus::gl::TEXTURE_UPDATE_BARRIER_BIT);
+    /// Buffer reads and writes through the API, e.g. `glGetBufferSubData`
+    /// of a readback.
+    pub const BUFFER_UPDATE: Self = Self(janus::gl::BUFFER_UPDATE_BARRIER_BIT);
+    /// Reads of persistently mapped buffers by the CPU.
+    pub const CLIENT_MAPPED: Self = Self(janus::gl::CLIENT_MAPPED_BUFFER_BARRIER_BIT);
+    pub const FRAMEBUFFER: Self = Self(janus::gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const QUERY_BUFFER: Self = Self(janus::gl::QUERY_BUFFER_BARRIER_BIT);
+    pub const ATOMIC_COUNTER: Self = Self(janus::gl::ATOMIC_COUNTER_BARRIER_BIT);
+    pub const SHADER_STORAGE: Self = Self(janus::gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: Self = Self(janus::gl::ALL_BARRIER_BITS);
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// The bits of `self` not in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl std::ops::BitOr for Access {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl std::ops::BitOrAssign for Access {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+/// A GPU resource tracked by [`Barriers`], e.g. the name of a buffer or
+/// texture.
+pub type ResourceId = u32;
+
+/// The resources a pass reads, and those its shaders write.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PassUsage {
+    reads: Vec<(ResourceId, Access)>,
+    writes: Vec<ResourceId>,
+}
+
+impl PassUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pass reads `resource` through `access`.
+    pub fn read(mut self, resource: ResourceId, access: Access) -> Self {
+        self.reads.push((resource, access));
+        self
+    }
+
+    /// The shaders of the pass write `resource`, through storage buffer,
+    /// image or atomic counter writes.
+    pub fn write(mut self, resource: ResourceId) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    pub fn reads(&self) -> &[(ResourceId, Access)] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[ResourceId] {
+        &self.writes
+    }
+}
+
+/// Which barriers are issued before passes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BarrierMode {
+    /// The bits required by the declared usage of the pass, if any.
+    #[default]
+    Minimal,
+    /// All bits before every pass, to bisect missing barriers.
+    Full,
+}
+
+impl BarrierMode {
+    /// [`Self::Full`] if the `ETHEL_FULL_BARRIERS` environment variable is
+    /// set to anything but `0`, [`Self::Minimal`] otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var_os(ENV_FULL_BARRIERS) {
+            Some(value) if value != "0" => Self::Full,
+            _ => Self::Minimal,
+        }
+    }
+}
+
+/// Tracks the incoherent writes of passes, and issues the barriers their
+/// reads require.
+#[derive(Debug, Default)]
+pub struct Barriers {
+    mode: BarrierMode,
+    /// The resources written by shaders, with the accesses made visible by
+    /// the barriers issued since.
+    written: FxHashMap<ResourceId, Access>,
+    issued: u32,
+}
+
+impl Barriers {
+    pub fn new(mode: BarrierMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> BarrierMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: BarrierMode) {
+        self.mode = mode;
+    }
+
+    /// The bits of the barrier required before `usage`.
+    pub fn required(&self, usage: &PassUsage) -> Access {
+        usage
+            .reads()
+            .iter()
+            .filter_map(|(resource, access)| {
+                let visible = self.written.get(resource)?;
+                Some(access.difference(*visible))
+            })
+            .fold(Access::NONE, Access::union)
+    }
+
+    /// Issue the barrier required before a pass of `usage`, see
+    /// [`BarrierMode`].
+    ///
+    /// # Returns
+    /// The bits of the issued barrier, empty if none was.
+    pub fn begin_pass(&mut self, usage: &PassUsage) -> Access {
+        let bits = match self.mode {
+            BarrierMode::Minimal => self.required(usage),
+            BarrierMode::Full => Access::ALL,
+        };
+        self.barrier(bits);
+        bits
+    }
+
+    /// Record the writes of a pass of `usage`, once its commands have been
+    /// issued.
+    pub fn end_pass(&mut self, usage: &PassUsage) {
+        for &resource in usage.writes() {
+            self.written.insert(resource, Access::NONE);
+        }
+    }
+
+    /// Issue a barrier of `bits`, e.g. before a read not declared by a pass.
+    pub fn barrier(&mut self, bits: Access) {
+        if bits.is_empty() {
+            return;
+        }
+        unsafe {
+            janus::gl::MemoryBarrier(bits.bits());
+        }
+        self.issued += 1;
+        // the barrier covers the writes of every resource
+        for visible in self.written.values_mut() {
+            *visible |= bits;
+        }
+    }
+
+    /// Forget the writes of `resource`, e.g. once deleted.
+    pub fn forget(&mut self, resource: ResourceId) {
+        self.written.remove(&resource);
+    }
+
+    /// The amount of barriers issued, e.g. to report it in the frame stats.
+    pub fn issued(&self) -> u32 {
+        self.issued
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimal_barrier_bits() {
+        let (inputs, commands, transforms) = (1, 2, 3);
+        let cull = PassUsage::new()
+            .read(inputs, Access::SHADER_STORAGE)
+            .write(commands);
+        let draw = PassUsage::new()
+            .read(commands, Access::COMMAND)
+            .read(transforms, Access::SHADER_STORAGE);
+        let readback = PassUsage::new().read(commands, Access::COMMAND | Access::BUFFER_UPDATE);
+
+        let mut barriers = Barriers::new(BarrierMode::Minimal);
+        // nothing was written yet
+        assert_eq!(barriers.required(&cull), Access::NONE);
+        barriers.end_pass(&cull);
+        assert_eq!(barriers.required(&draw), Access::COMMAND);
+
+        // the visibility of each access is tracked separately
+        barriers.written.insert(commands, Access::COMMAND);
+        assert_eq!(barriers.required(&draw), Access::NONE);
+        assert_eq!(barriers.required(&readback), Access::BUFFER_UPDATE);
+        assert!(Access::ALL.contains(Access::BUFFER_UPDATE | Access::COMMAND));
+
+        barriers.end_pass(&cull);
+        assert_eq!(
+            barriers.required(&readback),
+            Access::COMMAND | Access::BUFFER_UPDATE
+        );
+        barriers.forget(commands);
+        assert!(barriers.required(&readback).is_empty());
+        assert_eq!(barriers.issued(), 0);
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    mesh,
+    render::{
+        buffer::TriBuffer,
+        command::DrawCmd,
+        per_draw::DrawConstants,
+        visibility::{RenderLayers, Visibility},
+    },
+};
+
+/// The identity of an [`IndirectBucket`]: all draw commands sharing the same
+/// material and mesh are batched into the same bucket.
+///
+/// Buckets are ordered by material first, then by mesh, so that all buckets
+/// of the same material are contiguous in the command buffer and only
+/// require a single shader/texture binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BatchKey<M> {
+    pub material: M,
+    pub mesh: mesh::Id,
+}
+
+/// A contiguous range of draw commands in an indirect command buffer.
+///
+/// The `offset` and `count` are both expressed in number of commands, not
+/// bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IndirectBucket<M> {
+    pub key: BatchKey<M>,
+    pub offset: u32,
+    pub count: u32,
+}
+
+/// Groups draw commands by (material, mesh) into indirect buckets.
+///
+/// Each bucket is written contiguously to the command buffer, allowing the
+/// render thread to dispatch heterogeneous scenes with a single multi-draw
+/// call per bucket through
+/// [`GpuCommandDispatch::dispatch_buckets`](super::command::GpuCommandDispatch::dispatch_buckets).
+///
+/// The allocations of each bucket are preserved across
+/// [`clear`](CommandBatcher::clear) calls, so a batcher can be reused every
+/// frame without allocator churn.
+#[derive(Debug)]
+pub struct CommandBatcher<C: DrawCmd, M: Clone + Copy + Ord> {
+    buckets: BTreeMap<BatchKey<M>, Vec<C>>,
+    /// The constants of the commands of each bucket, only for buckets with
+    /// commands pushed by [`CommandBatcher::push_with_constants`].
+    constants: BTreeMap<BatchKey<M>, Vec<DrawConstants>>,
+    len: usize,
+}
+
+impl<C: DrawCmd, M: Clone + Copy + Ord> Default for CommandBatcher<C, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: DrawCmd, M: Clone + Copy + Ord> CommandBatcher<C, M> {
+    pub fn new() -> Self {
+        Self {
+            buckets: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            len: 0,
+        }
+    }
+
+    /// Push a draw `command` in the bucket of `material` and `mesh`.
+    pub fn push(&mut self, material: M, mesh: mesh::Id, command: C) {
+        let key = BatchKey { material, mesh };
+        self.buckets.entry(key).or_default().push(command);
+        self.len += 1;
+    }
+
+    /// Push a draw `command` in the bucket of `material` and `mesh`, with
+    /// the `constants` of the draw, see [`per_draw`](super::per_draw).
+    ///
+    /// Commands of the same bucket pushed without constants, with
+    /// [`Self::push`], get the default constants.
+    pub fn push_with_constants(
+        &mut self,
+        material: M,
+        mesh: mesh::Id,
+        command: C,
+        constants: DrawConstants,
+    ) {
+        let key = BatchKey { material, mesh };
+        let commands = self.buckets.entry(key).or_default();
+        let bucket_constants = self.constants.entry(key).or_default();
+        bucket_constants.resize(commands.len(), DrawConstants::default());
+        bucket_constants.push(constants);
+        commands.push(command);
+        self.len += 1;
+    }
+
+    /// Push a draw `command` as [`Self::push`], only if the entity is
+    /// `visible` in any of the layers of `filter`.
+    ///
+    /// # Returns
+    /// Whether the command was pushed.
+    pub fn push_visible(
+        &mut self,
+        visibility: Visibility,
+        filter: RenderLayers,
+        material: M,
+        mesh: mesh::Id,
+        command: C,
+    ) -> bool {
+        let visible = visibility.is_visible_in(filter);
+        if visible {
+            self.push(material, mesh, command);
+        }
+        visible
+    }
+
+    /// Empty all buckets, preserving their allocations.
+    pub fn clear(&mut self) {
+        self.buckets.values_mut().for_each(Vec::clear);
+        self.constants.values_mut().for_each(Vec::clear);
+        self.len = 0;
+    }
+
+    /// Empty and deallocate all buckets.
+    pub fn reset(&mut self) {
+        self.buckets.clear();
+        self.constants.clear();
+        self.len = 0;
+    }
+
+    /// The total amount of draw commands across all buckets.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The amount of non-empty buckets.
+    pub fn bucket_count(&self) -> usize {
+        self.buckets
+            .values()
+            .filter(|cmds| !cmds.is_empty())
+            .count()
+    }
+
+    /// Write all buckets contiguously to `buffer`, recording the range of
+    /// each bucket in `buckets`.
+    ///
+    /// Any existing content of `buckets` is discarded. Empty buckets are
+    /// skipped.
+    ///
+    /// If the total amount of commands exceeds the length of `buffer`, the
+    /// exceeding commands are ignored and the last written bucket is
+    /// truncated.
+    ///
+    /// # Returns
+    /// The amount of commands written to `buffer`.
+    pub fn upload(&self, buffer: &mut [C], buckets: &mut Vec<IndirectBucket<M>>) -> usize {
+        buckets.clear();
+
+        let mut head = 0;
+        for (&key, commands) in self.buckets.iter() {
+            let avail = buffer.len() - head;
+            if avail == 0 {
+                break;
+            }
+            if commands.is_empty() {
+                continue;
+            }
+
+            let count = commands.len().min(avail);
+            buffer[head..head + count].copy_from_slice(&commands[..count]);
+            buckets.push(IndirectBucket {
+                key,
+                offset: head as u32,
+                count: count as u32,
+            });
+            head += count;
+        }
+
+        head
+    }
+
+    /// Write all buckets contiguously to `buffer` as [`Self::upload`], and
+    /// the constants of each command at the same index in `constants`,
+    /// setting the base instance of the commands to their index.
+    ///
+    /// The amount of written commands is limited by the shortest of `buffer`
+    /// and `constants`.
+    ///
+    /// # Returns
+    /// The amount of commands written to `buffer`.
+    pub fn upload_with_constants(
+        &self,
+        buffer: &mut [C],
+        constants: &mut [DrawConstants],
+        buckets: &mut Vec<IndirectBucket<M>>,
+    ) -> usize {
+        let len = buffer.len().min(constants.len());
+        let written = self.upload(&mut buffer[..len], buckets);
+
+        for bucket in buckets.iter() {
+            let offset = bucket.offset as usize;
+            let count = bucket.count as usize;
+            let bucket_constants = self.constants.get(&bucket.key);
+            for i in 0..count {
+                let index = offset + i;
+                buffer[index].set_base_instance(index as u32);
+                constants[index] = bucket_constants
+                    .and_then(|c| c.get(i))
+                    .copied()
+                    .unwrap_or_default();
+            }
+        }
+        written
+    }
+
+    /// Write all buckets contiguously to a `section` of the command
+    /// `buffer`, recording the range of each bucket in `buckets`.
+    ///
+    /// The length of the `section` is set to the total amount of written
+    /// commands.
+    ///
+    /// See [`CommandBatcher::upload`].
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn blit(
+        &self,
+        buffer: &TriBuffer<C>,
+        section: usize,
+        buckets: &mut Vec<IndirectBucket<M>>,
+    ) -> usize {
+        let mut view = buffer.view_section_mut(section);
+        let written = self.upload(&mut view, buckets);
+        buffer.set_length(section, written as u32);
+        written
+    }
+
+    /// Write all buckets and their constants to a `section` of the command
+    /// `buffer` and of the `constants` buffer, see
+    /// [`CommandBatcher::upload_with_constants`].
+    ///
+    /// The lengths of both sections are set to the total amount of written
+    /// commands.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn blit_with_constants(
+        &self,
+        buffer: &TriBuffer<C>,
+        constants: &TriBuffer<DrawConstants>,
+        section: usize,
+        buckets: &mut Vec<IndirectBucket<M>>,
+    ) -> usize {
+        let mut view = buffer.view_section_mut(section);
+        let mut constants_view = constants.view_section_mut(section);
+        let written = self.upload_with_constants(&mut view, &mut constants_view, buckets);
+        buffer.set_length(section, written as u32);
+        constants.set_length(section, written as u32);
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::command::DrawArraysIndirectCommand;
+
+    fn cmd(first_vertex: u32) -> DrawArraysIndirectCommand {
+        DrawArraysIndirectCommand {
+            count: 3,
+            instance_count: 1,
+            first_vertex,
+            base_instance: 0,
+        }
+    }
+
+    #[test]
+    fn batch_by_material_and_mesh() {
+        let mesh_a = unsafe { mesh::Id::from_value(1) };
+        let mesh_b = unsafe { mesh::Id::from_value(2) };
+
+        let mut batcher = CommandBatcher::<DrawArraysIndirectCommand, u32>::new();
+        batcher.push(1, mesh_b, cmd(0));
+        batcher.push(0, mesh_a, cmd(1));
+        batcher.push(1, mesh_a, cmd(2));
+        batcher.push(0, mesh_a, cmd(3));
+        batcher.push(1, mesh_b, cmd(4));
+
+        assert_eq!(batcher.len(), 5);
+        assert_eq!(batcher.bucket_count(), 3);
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 8];
+        let mut buckets = Vec::new();
+        let written = batcher.upload(&mut buf, &mut buckets);
+        assert_eq!(written, 5);
+
+        let ranges = buckets
+            .iter()
+            .map(|b| (b.key.material, b.key.mesh, b.offset, b.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [(0, mesh_a, 0, 2), (1, mesh_a, 2, 1), (1, mesh_b, 3, 2)]
+        );
+
+        let order = buf[..written]
+            .iter()
+            .map(|c| c.first_vertex)
+            .collect::<Vec<_>>();
+        assert_eq!(order, [1, 3, 2, 0, 4]);
+    }
+
+    #[test]
+    fn batch_draw_constants() {
+        let mesh_a = unsafe { mesh::Id::from_value(1) };
+        let mesh_b = unsafe { mesh::Id::from_value(2) };
+
+        let mut batcher = CommandBatcher::<DrawArraysIndirectCommand, u32>::new();
+        batcher.push_with_constants(1, mesh_a, cmd(0), DrawConstants::new(1, 10));
+        batcher.push(0, mesh_b, cmd(1));
+        batcher.push(1, mesh_a, cmd(2));
+        batcher.push_with_constants(1, mesh_a, cmd(3), DrawConstants::new(1, 30));
+        batcher.push_with_constants(0, mesh_a, cmd(4), DrawConstants::new(0, 40));
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 8];
+        let mut constants = vec![DrawConstants::new(9, 9); 4];
+        let mut buckets = Vec::new();
+        let written = batcher.upload_with_constants(&mut buf, &mut constants, &mut buckets);
+        assert_eq!(written, 4);
+
+        let commands = buf[..written]
+            .iter()
+            .map(|c| (c.first_vertex, c.base_instance))
+            .collect::<Vec<_>>();
+        assert_eq!(commands, [(4, 0), (1, 1), (0, 2), (2, 3)]);
+        let slots = constants.iter().map(|c| c.transform).collect::<Vec<_>>();
+        assert_eq!(slots, [40, 0, 10, 0]);
+    }
+
+    #[test]
+    fn batch_upload_truncates() {
+        let mesh = unsafe { mesh::Id::from_value(1) };
+
+        let mut batcher = CommandBatcher::<DrawArraysIndirectCommand, u32>::new();
+        (0..4).for_each(|i| batcher.push(0, mesh, cmd(i)));
+        (0..4).for_each(|i| batcher.push(1, mesh, cmd(i)));
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 6];
+        let mut buckets = Vec::new();
+        assert_eq!(batcher.upload(&mut buf, &mut buckets), 6);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[1].offset, 4);
+        assert_eq!(buckets[1].count, 2);
+
+        batcher.clear();
+        assert!(batcher.is_empty());
+        assert_eq!(batcher.upload(&mut buf, &mut buckets), 0);
+        assert!(buckets.is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+//! World-space bounding boxes of entities.
+//!
+//! The bounds of each mesh are computed once from its vertices into a
+//! [`MeshBounds`] table, and the world-space [`Aabb`] of each entity is
+//! derived from the bounds of its mesh and its model matrix every upload
+//! with [`world_bounds`]. The boxes are uploaded to a dedicated partition of
+//! the entity data buffer, declared by [`GLSL_SSBO_ENTITY_BOUNDS`], in the
+//! same order as the other per-entity partitions, as a shared input of GPU
+//! culling, picking, debug drawing and occlusion tests.
+//!
+//! Compute passes may transform the mesh bounds themselves instead, with
+//! [`GLSL_LIB_TRANSFORM_AABB`].
+//!
+//! # Example
+//! ```rust,ignore
+//! layout_buffer! {
+//!     const EntityData: 3, {
+//!         enum transforms: 100_000 => {
+//!             type Mat4;
+//!             bind 0;
+//!             shader SHADER_BINDING_INSTANCES;
+//!         };
+//!         enum bounds: 100_000 => {
+//!             type Aabb;
+//!             bind 1;
+//!             shader SHADER_BINDING_ENTITY_BOUNDS;
+//!         };
+//!     }
+//! }
+//!
+//! // at startup, before closing the staging
+//! let mesh_bounds = MeshBounds::compute(staging.metadata(), staging.vertex_storage());
+//!
+//! // UPLOAD
+//! pack_trs_matrices(&props.position, &props.rotation, &props.scale, models);
+//! world_bounds(&mesh_bounds, &props.mesh, models, &mut bounds);
+//! storage.blit_part(section, LayoutEntityData::Bounds as usize, &bounds, 0);
+//! ```
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{
+    mesh::{self, Meshadata, Vertex},
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (EntityBounds) => {
+        22
+    };
+}
+
+pub const SHADER_BINDING_ENTITY_BOUNDS: u32 = ssbo_binding!(EntityBounds);
+
+/// An axis-aligned bounding box.
+///
+/// Corresponds to the `Aabb` struct of [`GLSL_SSBO_ENTITY_BOUNDS`] in a
+/// `std430` layout. The `w` components are unused and kept at `0.0`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec4,
+    pub max: Vec4,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    /// The box containing nothing, e.g. the bounds of the `null` mesh.
+    ///
+    /// It is the identity of [`Self::union`], and is never intersected.
+    pub const EMPTY: Self = Self {
+        min: Vec4::new(f32::INFINITY, f32::INFINITY, f32::INFINITY, 0.0),
+        max: Vec4::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY, 0.0),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self {
+            min: min.extend(0.0),
+            max: max.extend(0.0),
+        }
+    }
+
+    /// The smallest box containing all `points`, empty without any.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, Self::extend)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn center(&self) -> Vec3 {
+        ((self.min + self.max) * 0.5).truncate()
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        ((self.max - self.min) * 0.5).truncate()
+    }
+
+    /// The box grown to contain `point`.
+    pub fn extend(self, point: Vec3) -> Self {
+        let point = point.extend(0.0);
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        let point = point.extend(0.0);
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    /// The smallest box containing this box transformed by the affine
+    /// `matrix`.
+    ///
+    /// Empty boxes remain empty.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        if self.is_empty() {
+            return Self::EMPTY;
+        }
+        let center = matrix.transform_point3(self.center());
+        let half = self.half_extents();
+        let half = matrix.x_axis.truncate().abs() * half.x
+            + matrix.y_axis.truncate().abs() * half.y
+            + matrix.z_axis.truncate().abs() * half.z;
+        Self::new(center - half, center + half)
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct Aabb for Aabb {
+        min: Vec4 => vec4;
+        max: Vec4 => vec4;
+    }
+}
+
+/// The local bounds of the meshes of a pool, indexed by mesh ID.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshBounds {
+    bounds: Vec<Aabb>,
+}
+
+impl MeshBounds {
+    /// The bounds of every mesh of `metadata`, from the `vertices` of its
+    /// mesh buffer, e.g. those of a
+    /// [`MeshStaging`](crate::mesh::MeshStaging).
+    ///
+    /// Meshes whose vertices are out of `vertices` are empty.
+    pub fn compute(metadata: &Meshadata, vertices: &[Vertex]) -> Self {
+        let bounds = metadata
+            .iter()
+            .map(|mesh| {
+                let (start, end) = (
+                    mesh.offset() as usize,
+                    (mesh.offset() + mesh.length()) as usize,
+                );
+                let points = vertices.get(start..end).unwrap_or_default();
+                Aabb::from_points(points.iter().map(|v| Vec4::from(v.position).truncate()))
+            })
+            .collect();
+        Self { bounds }
+    }
+
+    /// The bounds of the mesh `id`, empty if unknown.
+    pub fn get(&self, id: mesh::Id) -> Aabb {
+        self.bounds
+            .get(id.index as usize)
+            .copied()
+            .unwrap_or(Aabb::EMPTY)
+    }
+
+    /// Set the bounds of the mesh `id`, e.g. to include its animated poses.
+    pub fn set(&mut self, id: mesh::Id, bounds: Aabb) {
+        let index = id.index as usize;
+        if index >= self.bounds.len() {
+            self.bounds.resize(index + 1, Aabb::EMPTY);
+        }
+        self.bounds[index] = bounds;
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+}
+
+/// Compute the world-space bounds of parallel `meshes` and model matrix
+/// columns, e.g. packed with
+/// [`pack_trs_matrices`](super::buffer::pack::pack_trs_matrices), into `dst`.
+///
+/// # Returns
+/// The amount of computed bounds, i.e. the minimum length between `meshes`,
+/// `models` and `dst`.
+pub fn world_bounds(
+    bounds: &MeshBounds,
+    meshes: &[mesh::Id],
+    models: &[Mat4],
+    dst: &mut [Aabb],
+) -> usize {
+    let len = meshes.len().min(models.len()).min(dst.len());
+    for ((mesh, model), dst) in meshes.iter().zip(models).zip(&mut dst[..len]) {
+        *dst = bounds.get(*mesh).transformed(model);
+    }
+    len
+}
+
+/// Entity bounds SSBO interface.
+///
+/// Contains the SSBO declaration of an [`Aabb`] partition, on binding index
+/// 22. Requires the `Aabb` struct definition, see
+/// [`AabbGlslStruct::as_definition`].
+pub const GLSL_SSBO_ENTITY_BOUNDS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf EntityBounds => {
+        [dyn_array Aabb: entity_bounds]
+    }
+};
+
+/// The bounds of a box in mesh space transformed by the affine `model`
+/// matrix, as computed by [`Aabb::transformed`]. Requires the `Aabb` struct
+/// definition.
+pub const GLSL_LIB_TRANSFORM_AABB: GlslLib = crate::shader_glsl_lib! {
+    Aabb transform_aabb [ local: Aabb, model: mat4 ] => "
+        if (any(greaterThan(local.min.xyz, local.max.xyz))) {
+            return local;
+        }
+        vec3 center = (model * vec4((local.min.xyz + local.max.xyz) * 0.5, 1.0)).xyz;
+        vec3 half_extents = mat3(abs(model[0].xyz), abs(model[1].xyz), abs(model[2].xyz))
+            * ((local.max.xyz - local.min.xyz) * 0.5);
+        return Aabb(vec4(center - half_extents, 0.0), vec4(center + half_extents, 0.0));
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+
+    #[test]
+    fn transform_mesh_bounds() {
+        let mut staging = mesh::MeshStaging::new();
+        let cube = staging.stage(&mesh::unit_cube());
+        let null = mesh::Id::default();
+        let bounds = MeshBounds::compute(staging.metadata(), staging.vertex_storage());
+        assert_eq!(
+            bounds.get(cube),
+            Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5))
+        );
+        assert!(bounds.get(null).is_empty());
+
+        let models = [
+            Mat4::from_scale_rotation_translation(
+                Vec3::new(2.0, 1.0, 1.0),
+                Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                Vec3::new(0.0, 0.0, -5.0),
+            ),
+            Mat4::from_translation(Vec3::X),
+        ];
+        let mut world = [Aabb::EMPTY; 3];
+        assert_eq!(
+            world_bounds(&bounds, &[cube, null, cube], &models, &mut world),
+            2
+        );
+
+        let rotated = world[0];
+        assert!(
+            (rotated.half_extents() - Vec3::new(0.5, 1.0, 0.5))
+                .abs()
+                .max_element()
+                < 1e-5
+        );
+        assert!(
+            (rotated.center() - Vec3::new(0.0, 0.0, -5.0))
+                .abs()
+                .max_element()
+                < 1e-5
+        );
+        assert!(world[1].is_empty() && world[2].is_empty());
+
+        let moved = bounds
+            .get(cube)
+            .transformed(&Mat4::from_translation(Vec3::X));
+        assert!(moved.contains(Vec3::new(1.4, 0.0, 0.0)));
+        assert!(moved.intersects(&bounds.get(cube)));
+        assert!(!moved.intersects(&Aabb::EMPTY));
+        assert_eq!(Aabb::EMPTY.union(moved), moved);
+    }
+}
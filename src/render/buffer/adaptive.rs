@@ -0,0 +1,152 @@
+//! Capacity tracking for buffers whose usage varies per frame, such as
+//! indirect command buffers.
+
+/// The amount of frames kept in the usage history.
+const HISTORY: usize = 120;
+
+/// The default amount of consecutive overflowing frames before growing.
+pub const DEFAULT_GROW_AFTER: u32 = 30;
+
+/// The default capacity multiplier applied to the peak usage when growing.
+pub const DEFAULT_GROWTH: f32 = 1.5;
+
+/// Tracks the per-frame usage of a buffer against its capacity, and
+/// determines when the buffer should grow.
+///
+/// A single frame exceeding the capacity is not enough to grow: the overflow
+/// must be sustained for a number of consecutive frames, after which the new
+/// capacity is derived from the peak usage of the recent history. This
+/// hysteresis prevents reallocating the buffer on every spike.
+///
+/// The tracker does not own the buffer: buffers shared across the thread
+/// boundary cannot be reallocated while in use, so the suggested capacity
+/// must be applied by the owner of the buffer at a safe point, for example
+/// with [`TriBuffer::reallocate`](super::TriBuffer::reallocate).
+///
+/// # Example
+/// ```rust,ignore
+/// let written = command_buffer.blit_section(section, &commands, 0);
+/// if let Some(capacity) = command_usage.record(commands.len()) {
+///     pending_command_capacity = Some(capacity);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AdaptiveCapacity {
+    capacity: usize,
+    history: [usize; HISTORY],
+    head: usize,
+
+    overflow_streak: u32,
+    grow_after: u32,
+    growth: f32,
+}
+
+impl AdaptiveCapacity {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            history: [0; HISTORY],
+            head: 0,
+            overflow_streak: 0,
+            grow_after: DEFAULT_GROW_AFTER,
+            growth: DEFAULT_GROWTH,
+        }
+    }
+
+    /// The amount of consecutive overflowing frames before growing.
+    ///
+    /// # Panics
+    /// If `frames` is 0.
+    pub fn grow_after(mut self, frames: u32) -> Self {
+        assert_ne!(frames, 0, "cannot grow after 0 frames");
+        self.grow_after = frames;
+        self
+    }
+
+    /// The multiplier applied to the peak usage when growing.
+    ///
+    /// # Panics
+    /// If `growth` is lower than `1.0`.
+    pub fn growth(mut self, growth: f32) -> Self {
+        assert!(growth >= 1.0, "growth must be at least 1.0, got {growth}");
+        self.growth = growth;
+        self
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Set the tracked capacity, for example after the buffer has been
+    /// reallocated with a capacity other than the suggested one.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.overflow_streak = 0;
+    }
+
+    /// The highest usage recorded in the recent history.
+    pub fn peak(&self) -> usize {
+        self.history.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Record the usage of a frame, i.e. the amount of elements that were
+    /// requested to be written, including those that did not fit.
+    ///
+    /// # Returns
+    /// `Some` with the new capacity when the overflow has been sustained for
+    /// long enough. The tracker assumes the new capacity is applied and only
+    /// suggests growing again if the new capacity overflows as well.
+    pub fn record(&mut self, usage: usize) -> Option<usize> {
+        self.history[self.head] = usage;
+        self.head = (self.head + 1) % HISTORY;
+
+        if usage <= self.capacity {
+            self.overflow_streak = 0;
+            return None;
+        }
+
+        self.overflow_streak += 1;
+        if self.overflow_streak < self.grow_after {
+            return None;
+        }
+
+        let capacity = (self.peak() as f64 * self.growth as f64).ceil() as usize;
+        let previous = self.capacity;
+        self.set_capacity(capacity);
+
+        tracing::event!(
+            name: "buffer.adaptive.grow",
+            tracing::Level::INFO,
+            "buffer usage exceeded its capacity of {previous} for {} frames, growing to {capacity}",
+            self.grow_after
+        );
+        Some(capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_on_sustained_overflow() {
+        let mut usage = AdaptiveCapacity::new(100).grow_after(4).growth(2.0);
+
+        // isolated spikes never grow
+        for _ in 0..10 {
+            assert_eq!(usage.record(150), None);
+            assert_eq!(usage.record(80), None);
+        }
+
+        assert_eq!(usage.record(120), None);
+        assert_eq!(usage.record(110), None);
+        assert_eq!(usage.record(130), None);
+        // grows from the peak of the history, including previous spikes
+        assert_eq!(usage.record(120), Some(300));
+        assert_eq!(usage.capacity(), 300);
+
+        for _ in 0..10 {
+            assert_eq!(usage.record(200), None);
+        }
+    }
+}
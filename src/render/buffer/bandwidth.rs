@@ -0,0 +1,154 @@
+//! Accounting of the bytes blitted to GPU buffers.
+//!
+//! Every blit reports the amount of bytes written per buffer and partition
+//! to the [`Recorder`](crate::state::stats::Recorder) of the current thread,
+//! which [`State::upload`](crate::state::State::upload) collects into a
+//! [`Bandwidth`] every frame, see
+//! [`State::bandwidth`](crate::state::State::bandwidth). This shows where the
+//! upload bandwidth goes, and measures the effect of culling or of only
+//! uploading the data that changed.
+//!
+//! Buffers are identified by their GL object, and can be given readable names
+//! through [`TriBuffer::label`](super::TriBuffer::label) and
+//! [`PartitionedTriBuffer::label_partitions`](super::PartitionedTriBuffer::label_partitions).
+//!
+//! Data written directly through mapped views (e.g.
+//! [`ViewMut`](super::ViewMut)) is not blitted, and is not accounted for.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::state::stats;
+
+/// A buffer, or a partition of a partitioned buffer, written by blits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlitTarget {
+    /// The GL object of the buffer, or of its first section for buffers with
+    /// one GL object per section.
+    pub buffer: u32,
+    pub partition: Option<usize>,
+}
+
+/// The bytes blitted to a [`BlitTarget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetBandwidth {
+    pub target: BlitTarget,
+    pub name: Option<&'static str>,
+    pub bytes: usize,
+}
+
+/// The bytes blitted per buffer and partition over a frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bandwidth {
+    targets: Vec<TargetBandwidth>,
+}
+
+impl Bandwidth {
+    /// All targets blitted to, ordered by buffer and partition.
+    pub fn targets(&self) -> &[TargetBandwidth] {
+        &self.targets
+    }
+
+    /// The bytes blitted to the target labelled `name`.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.targets
+            .iter()
+            .find(|t| t.name == Some(name))
+            .map(|t| t.bytes)
+    }
+
+    /// The bytes blitted to all targets.
+    pub fn total(&self) -> usize {
+        self.targets.iter().map(|t| t.bytes).sum()
+    }
+}
+
+impl std::fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "uploaded {} bytes", self.total())?;
+        for t in &self.targets {
+            let BlitTarget { buffer, partition } = t.target;
+            write!(f, "\n  ")?;
+            match (t.name, partition) {
+                (Some(name), _) => write!(f, "{name:<24}")?,
+                (None, Some(partition)) => write!(f, "{:<24}", format!("{buffer}[{partition}]"))?,
+                (None, None) => write!(f, "{buffer:<24}")?,
+            }
+            write!(f, "{:>10} bytes", t.bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// The bytes blitted per target, collected by a
+/// [`Recorder`](crate::state::stats::Recorder).
+#[derive(Debug, Default)]
+pub(crate) struct BlitCounters {
+    blitted: RefCell<HashMap<BlitTarget, (Option<&'static str>, usize)>>,
+}
+
+impl BlitCounters {
+    fn add(&self, target: BlitTarget, name: Option<&'static str>, bytes: usize) {
+        let mut blitted = self.blitted.borrow_mut();
+        let (label, total) = blitted.entry(target).or_default();
+        *label = name;
+        *total += bytes;
+    }
+
+    /// Take the bytes blitted since the last call into `bandwidth`, resetting
+    /// the counters.
+    pub(crate) fn take(&self, bandwidth: &mut Bandwidth) {
+        bandwidth.targets.clear();
+        bandwidth
+            .targets
+            .extend(
+                self.blitted
+                    .borrow_mut()
+                    .drain()
+                    .map(|(target, (name, bytes))| TargetBandwidth {
+                        target,
+                        name,
+                        bytes,
+                    }),
+            );
+        bandwidth.targets.sort_unstable_by_key(|t| t.target);
+    }
+}
+
+/// Record a blit of `bytes` to the `partition` of `buffer`, named `name`, in
+/// the recorder of the current thread.
+#[inline]
+pub(crate) fn record(
+    buffer: u32,
+    partition: Option<usize>,
+    name: Option<&'static str>,
+    bytes: usize,
+) {
+    if bytes == 0 {
+        return;
+    }
+    let target = BlitTarget { buffer, partition };
+    stats::with_recorder(|recorder| recorder.blitted().add(target, name, bytes));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::stats::Recorder;
+
+    #[test]
+    fn accumulate_per_target() {
+        let recorder = Recorder::new();
+        recorder.record(|| {
+            record(7, Some(1), Some("rotations"), 32);
+            record(7, Some(0), None, 64);
+            record(7, Some(0), None, 64);
+        });
+
+        let mut bandwidth = Bandwidth::default();
+        recorder.blitted().take(&mut bandwidth);
+        assert_eq!(bandwidth.targets().len(), 2);
+        assert_eq!(bandwidth.targets()[0].bytes, 128);
+        assert_eq!(bandwidth.get("rotations"), Some(32));
+        assert_eq!(bandwidth.total(), 160);
+    }
+}
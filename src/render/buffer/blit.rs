@@ -0,0 +1,95 @@
+//! Checked blits, which write all of their data or nothing.
+//!
+//! The `blit_*` functions of the triple buffers clamp data exceeding their
+//! destination and report the dropped elements, see [`overflow`](super::overflow),
+//! which suits per-frame uploads that may momentarily outgrow a buffer. The
+//! `try_blit_*` variants instead refuse such blits with a [`BlitError`], for
+//! data whose truncation would be a bug.
+
+/// Why a checked blit was refused.
+///
+/// Offsets and capacities are in the unit of the blit: elements for
+/// [`TriBuffer`](super::TriBuffer) and partitions, bytes for the sections of a
+/// [`PartitionedTriBuffer`](super::PartitionedTriBuffer).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlitError {
+    /// The offset is at or past the end of the destination.
+    OutOfBounds { offset: usize, capacity: usize },
+    /// The data does not fit in the destination after the offset.
+    Overflow { requested: usize, available: usize },
+    /// The byte offset of a partition blit is not a multiple of the size of
+    /// its elements.
+    Misaligned { offset: usize, stride: usize },
+}
+
+impl std::fmt::Display for BlitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBounds { offset, capacity } => write!(
+                f,
+                "blit offset {offset} is out of bounds of a capacity of {capacity}"
+            ),
+            Self::Overflow {
+                requested,
+                available,
+            } => write!(
+                f,
+                "blit of {requested} elements exceeds the available capacity of {available}: {} would be dropped",
+                requested - available
+            ),
+            Self::Misaligned { offset, stride } => write!(
+                f,
+                "blit offset of {offset} bytes is not aligned to elements of {stride} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlitError {}
+
+/// Check a blit of `len` elements at `offset` in a destination of `capacity`
+/// elements.
+pub(crate) fn check(offset: usize, len: usize, capacity: usize) -> Result<(), BlitError> {
+    if offset >= capacity {
+        return Err(BlitError::OutOfBounds { offset, capacity });
+    }
+    let available = capacity - offset;
+    if len > available {
+        return Err(BlitError::Overflow {
+            requested: len,
+            available,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_blit_bounds() {
+        assert_eq!(check(0, 16, 16), Ok(()));
+        assert_eq!(check(12, 0, 16), Ok(()));
+        assert_eq!(
+            check(12, 6, 16),
+            Err(BlitError::Overflow {
+                requested: 6,
+                available: 4
+            })
+        );
+        assert_eq!(
+            check(16, 0, 16),
+            Err(BlitError::OutOfBounds {
+                offset: 16,
+                capacity: 16
+            })
+        );
+        assert!(
+            check(12, 6, 16)
+                .unwrap_err()
+                .to_string()
+                .ends_with("2 would be dropped")
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Explicitly flushed, non-coherent persistent mappings.
+//!
+//! Coherent mappings make every CPU write visible to the GPU without further
+//! calls, which forces write-combined memory semantics on the whole mapping.
+//! In [`UploadMode::ExplicitFlush`], buffers are mapped with
+//! `GL_MAP_FLUSH_EXPLICIT_BIT` instead: blits record the byte range they
+//! write in each section, and only that range is flushed with
+//! `glFlushMappedNamedBufferRange` when the section is flushed on the render
+//! thread, before it is read by the GPU. This is faster on some drivers.
+//!
+//! Writes through mutable views cannot be tracked, and mark the whole viewed
+//! range as written.
+//!
+//! Buffers pick their mapping when created, either from the process-wide
+//! [`UploadMode`], or per buffer with a [`MappingMode`], e.g. with
+//! [`PartitionedTriBuffer::with_mapping`](super::PartitionedTriBuffer::with_mapping).
+//!
+//! [`UploadMode::ExplicitFlush`]: super::UploadMode::ExplicitFlush
+
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::render::buffer::UploadMode;
+
+/// How the CPU writes to a persistent mapping are made visible to the GPU.
+///
+/// Selects between the two mapped [`UploadMode`]s for a single buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MappingMode {
+    /// Mapped with `GL_MAP_COHERENT_BIT`, see [`UploadMode::Persistent`].
+    #[default]
+    Coherent,
+    /// Mapped with `GL_MAP_FLUSH_EXPLICIT_BIT`, and flushed on the written
+    /// ranges, see [`UploadMode::ExplicitFlush`].
+    NonCoherent,
+}
+
+impl MappingMode {
+    /// The upload mode of buffers mapped in this mode.
+    pub const fn upload_mode(self) -> UploadMode {
+        match self {
+            Self::Coherent => UploadMode::Persistent,
+            Self::NonCoherent => UploadMode::ExplicitFlush,
+        }
+    }
+
+    /// The mapping of buffers in the upload `mode`.
+    ///
+    /// # Returns
+    /// `None` for [`UploadMode::Orphaning`], which does not map buffers.
+    pub const fn of(mode: UploadMode) -> Option<Self> {
+        match mode {
+            UploadMode::Persistent => Some(Self::Coherent),
+            UploadMode::ExplicitFlush => Some(Self::NonCoherent),
+            UploadMode::Orphaning => None,
+        }
+    }
+}
+
+impl From<MappingMode> for UploadMode {
+    fn from(mapping: MappingMode) -> Self {
+        mapping.upload_mode()
+    }
+}
+
+/// The byte range written to a section since it was last flushed.
+///
+/// Disjoint writes are merged into the smallest range covering all of them.
+#[derive(Debug)]
+pub(crate) struct WrittenRange {
+    start: AtomicUsize,
+    end: AtomicUsize,
+}
+
+impl Default for WrittenRange {
+    fn default() -> Self {
+        Self {
+            start: AtomicUsize::new(usize::MAX),
+            end: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl WrittenRange {
+    /// Record a write of the byte range `bytes`.
+    #[inline]
+    pub(crate) fn record(&self, bytes: Range<usize>) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.start.fetch_min(bytes.start, Ordering::Relaxed);
+        self.end.fetch_max(bytes.end, Ordering::Relaxed);
+    }
+
+    /// Take the range written since the last call, if any.
+    pub(crate) fn take(&self) -> Option<Range<usize>> {
+        let end = self.end.swap(0, Ordering::Relaxed);
+        let start = self.start.swap(usize::MAX, Ordering::Relaxed);
+        (start < end).then_some(start..end)
+    }
+}
+
+/// Flush the byte range `bytes` of the mapping of `gl_obj`.
+pub(crate) fn flush_range(gl_obj: u32, bytes: Range<usize>) {
+    unsafe {
+        janus::gl::FlushMappedNamedBufferRange(gl_obj, bytes.start as isize, bytes.len() as isize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_written_ranges() {
+        let written = WrittenRange::default();
+        assert_eq!(written.take(), None);
+
+        written.record(64..128);
+        written.record(16..32);
+        written.record(40..40);
+        assert_eq!(written.take(), Some(16..128));
+        assert_eq!(written.take(), None);
+
+        for mapping in [MappingMode::Coherent, MappingMode::NonCoherent] {
+            assert_eq!(MappingMode::of(mapping.into()), Some(mapping));
+        }
+        assert_eq!(MappingMode::of(UploadMode::Orphaning), None);
+    }
+}
@@ -1,36 +1,73 @@
 use std::rc::Rc;
 
+use tracing::{Level, event};
+
 use crate::render::buffer::Layout;
 
 pub fn uninit<const PARTS: usize>(layout: Layout<PARTS>) -> UninitImmutableBuffer<PARTS> {
     UninitImmutableBuffer::new(layout)
 }
 
-#[derive(Debug, Default)]
+pub fn uninit_dynamic<const PARTS: usize>(layout: Layout<PARTS>) -> UninitImmutableBuffer<PARTS> {
+    UninitImmutableBuffer::new_dynamic(layout)
+}
+
+#[derive(Debug)]
 pub struct UninitImmutableBuffer<const PARTS: usize> {
     gl_obj: u32,
     ptr: *mut u8,
     layout: Layout<PARTS>,
     mapped: bool,
+    dynamic: bool,
+    /// The amount of bytes written at the start of each partition, flushed
+    /// when the buffer is finished.
+    filled: [usize; PARTS],
 
     // Unitialised buffer must not be sent to other threads
     // Drop impl requires GL calls, as does its creation
     _marker: std::marker::PhantomData<Rc<()>>,
 }
 
+impl<const PARTS: usize> Default for UninitImmutableBuffer<PARTS> {
+    fn default() -> Self {
+        Self {
+            gl_obj: 0,
+            ptr: std::ptr::null_mut(),
+            layout: Layout::default(),
+            mapped: false,
+            dynamic: false,
+            filled: [0; PARTS],
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
 impl<const PARTS: usize> UninitImmutableBuffer<PARTS> {
     pub fn new(layout: Layout<PARTS>) -> Self {
+        Self::with_storage(layout, false)
+    }
+
+    /// Like [`Self::new`], but the finished buffer permits updates of its
+    /// partitions, see [`ImmutableBuffer::update_partition`].
+    ///
+    /// The storage of the buffer is created with `DYNAMIC_STORAGE_BIT`, which
+    /// may lead the driver to place it in slower memory: only opt in for
+    /// buffers which need small, occasional fixes.
+    pub fn new_dynamic(layout: Layout<PARTS>) -> Self {
+        Self::with_storage(layout, true)
+    }
+
+    fn with_storage(layout: Layout<PARTS>, dynamic: bool) -> Self {
         let mut gl_obj = 0;
         let total_length = layout.len() as isize;
+        let mut flags = janus::gl::MAP_WRITE_BIT | janus::gl::MAP_READ_BIT;
+        if dynamic {
+            flags |= janus::gl::DYNAMIC_STORAGE_BIT;
+        }
 
         let ptr = unsafe {
             janus::gl::CreateBuffers(1, &mut gl_obj);
-            janus::gl::NamedBufferStorage(
-                gl_obj,
-                total_length,
-                std::ptr::null(),
-                janus::gl::MAP_WRITE_BIT | janus::gl::MAP_READ_BIT,
-            );
+            janus::gl::NamedBufferStorage(gl_obj, total_length, std::ptr::null(), flags);
             janus::gl::ClearNamedBufferData(
                 gl_obj,
                 janus::gl::R32UI,
@@ -38,11 +75,13 @@ impl<const PARTS: usize> UninitImmutableBuffer<PARTS> {
                 janus::gl::UNSIGNED_INT,
                 0 as *const _,
             );
+            // writes are flushed explicitly by `finish`, only over the
+            // ranges which were filled
             janus::gl::MapNamedBufferRange(
                 gl_obj,
                 0,
                 total_length,
-                janus::gl::MAP_WRITE_BIT | janus::gl::MAP_READ_BIT,
+                janus::gl::MAP_WRITE_BIT | janus::gl::MAP_FLUSH_EXPLICIT_BIT,
             )
         } as *mut u8;
 
@@ -51,6 +90,8 @@ impl<const PARTS: usize> UninitImmutableBuffer<PARTS> {
             ptr,
             gl_obj,
             mapped: true,
+            dynamic,
+            filled: [0; PARTS],
             _marker: std::marker::PhantomData,
         }
     }
@@ -91,25 +132,80 @@ impl<const PARTS: usize> UninitImmutableBuffer<PARTS> {
                 len_bytes,
             );
         }
+        self.filled[partition] = self.filled[partition].max(len_bytes);
+    }
+
+    /// The amount of bytes written at the start of `partition`.
+    ///
+    /// # Panics
+    /// If `partition` is greater or equal to `PARTS`.
+    pub fn filled_len(&self, partition: usize) -> usize {
+        self.filled[partition]
+    }
+
+    /// The partitions with an allocated block which were never filled, and
+    /// hold zeroes.
+    pub fn unfilled_partitions(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..PARTS).filter(|&part| self.filled[part] == 0 && self.layout.length_at(part) > 0)
     }
 
-    /// Unmap the buffer and forbid any further changes to its contents.
+    /// Unmap the buffer and forbid any further changes to its contents,
+    /// unless it was created with [`Self::new_dynamic`].
     ///
     /// # Returns
     /// An [`ImmutableBuffer`] preserving the OpenGL buffer object.
     pub fn finish(mut self) -> ImmutableBuffer<PARTS> {
+        if cfg!(debug_assertions) {
+            self.report_fills();
+        }
+
         self.mapped = false;
 
         unsafe {
+            for part in 0..PARTS {
+                let filled = self.filled[part];
+                if filled > 0 {
+                    let offset = self.layout.offset_at(part);
+                    janus::gl::FlushMappedNamedBufferRange(
+                        self.gl_obj,
+                        offset as isize,
+                        filled as isize,
+                    );
+                }
+            }
             janus::gl::UnmapNamedBuffer(self.gl_obj);
         }
 
         ImmutableBuffer {
             gl_obj: self.gl_obj,
             layout: self.layout.clone(),
+            dynamic: self.dynamic,
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Log the partitions which were never filled, or only partially.
+    fn report_fills(&self) {
+        for part in self.unfilled_partitions() {
+            event!(
+                name: "buffer.immutable.unfilled",
+                Level::WARN,
+                "Finished immutable buffer (handle={}) with partition {part} never filled: it renders zeroes",
+                self.gl_obj
+            );
+        }
+        for part in 0..PARTS {
+            let (filled, length) = (self.filled[part], self.layout.length_at(part));
+            if filled > 0 && filled < length {
+                event!(
+                    name: "buffer.immutable.unfilled",
+                    Level::DEBUG,
+                    "Finished immutable buffer (handle={}) with {filled} of {length} bytes of partition {part} filled",
+                    self.gl_obj
+                );
+            }
+        }
+    }
 }
 
 impl<const PARTS: usize> Drop for UninitImmutableBuffer<PARTS> {
@@ -129,6 +225,7 @@ impl<const PARTS: usize> Drop for UninitImmutableBuffer<PARTS> {
 pub struct ImmutableBuffer<const PARTS: usize> {
     gl_obj: u32,
     layout: Layout<PARTS>,
+    dynamic: bool,
 
     // Immutable buffer must not be sent to other threads
     // All operations related to immutable buffers require GL calls, the logic
@@ -137,6 +234,69 @@ pub struct ImmutableBuffer<const PARTS: usize> {
 }
 
 impl<const PARTS: usize> ImmutableBuffer<PARTS> {
+    /// Whether the partitions of the buffer can be updated, i.e. it was
+    /// created with [`UninitImmutableBuffer::new_dynamic`].
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    /// Overwrite the start of `partition` with the given `data`.
+    ///
+    /// See [`Self::update_partition_at`].
+    pub fn update_partition<T: Sized>(&mut self, partition: usize, data: &[T]) {
+        self.update_partition_at(partition, 0, data);
+    }
+
+    /// Overwrite `partition` with the given `data`, starting at the element
+    /// `first` of the partition, counted in elements of `T`.
+    ///
+    /// The update is ordered by the driver after the draws already issued,
+    /// which keep reading the previous contents.
+    ///
+    /// # Panics
+    /// * If the buffer was not created with
+    ///   [`UninitImmutableBuffer::new_dynamic`].
+    /// * If `partition` is greater or equal to `PARTS`, i.e. it is not a
+    ///   valid partition.
+    /// * If the `data` written at `first` does not fit in the length
+    ///   allocated for the specified `partition` in the buffer's [`Layout`].
+    ///
+    /// # Safety
+    /// This operation does not ensure that the type `T` of `data` matches the
+    /// type and alignment of the buffer's [`Layout`] specification.
+    ///
+    /// Passing the wrong type `T` might lead to undefined behaviour, and will
+    /// cause VRAM corruption.
+    pub fn update_partition_at<T: Sized>(&mut self, partition: usize, first: usize, data: &[T]) {
+        assert!(
+            self.dynamic,
+            "attempted to update a partition of a buffer without dynamic storage"
+        );
+        assert!(
+            partition < PARTS,
+            "attempted to update partition {partition} of a buffer that contains only {PARTS} partitions"
+        );
+
+        let length = self.layout.length_at(partition);
+        let start = first * size_of::<T>();
+        let len_bytes = data.len() * size_of::<T>();
+        assert!(
+            start + len_bytes <= length,
+            "length of data cannot fit in the allocated block of this partition"
+        );
+
+        let offset = self.layout.offset_at(partition) + start;
+
+        unsafe {
+            janus::gl::NamedBufferSubData(
+                self.gl_obj,
+                offset as isize,
+                len_bytes as isize,
+                data.as_ptr() as *const _,
+            );
+        }
+    }
+
     pub fn bind_shader_storage(&self) {
         for part in 0..PARTS {
             if let Some(binding) = self.layout.ssbo_of(part) {
@@ -163,12 +323,35 @@ impl<const PARTS: usize> ImmutableBuffer<PARTS> {
             }
         }
     }
+
+    /// Bind the buffer as the element array buffer of `vao`.
+    ///
+    /// The whole buffer is bound: the first index of a draw command is counted
+    /// from the start of the buffer, not from the start of a partition.
+    pub fn bind_element_buffer(&self, vao: u32) {
+        unsafe {
+            janus::gl::VertexArrayElementBuffer(vao, self.gl_obj);
+        }
+    }
+
+    /// Bind the buffer as the element array buffer of the currently bound
+    /// vertex array.
+    ///
+    /// See [`Self::bind_element_buffer`].
+    pub fn bind_current_element_buffer(&self) {
+        unsafe {
+            janus::gl::BindBuffer(janus::gl::ELEMENT_ARRAY_BUFFER, self.gl_obj);
+        }
+    }
 }
 
 impl<const PARTS: usize> Drop for ImmutableBuffer<PARTS> {
     fn drop(&mut self) {
-        unsafe {
-            janus::gl::DeleteBuffers(1, &self.gl_obj);
+        // a default buffer holds no GL object
+        if self.gl_obj != 0 {
+            unsafe {
+                janus::gl::DeleteBuffers(1, &self.gl_obj);
+            }
         }
     }
 }
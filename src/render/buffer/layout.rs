@@ -1,10 +1,18 @@
+use std::{any::TypeId, marker::PhantomData};
+
+use crate::shader::ShaderProgram;
+
 #[derive(Clone, Debug)]
 pub struct Layout<const PARTS: usize> {
     head: usize,
     last: usize,
     offsets: [usize; PARTS],
     lengths: [usize; PARTS],
+    aligns: [usize; PARTS],
     shader: [u32; PARTS],
+    elements: Option<usize>,
+    /// The descriptor the layout was created from, see [`Self::with_tag`].
+    tag: Option<TypeId>,
 }
 
 impl<const PARTS: usize> Default for Layout<PARTS> {
@@ -21,7 +29,10 @@ impl<const PARTS: usize> Layout<PARTS> {
             last: 0,
             offsets: [0; PARTS],
             lengths: [0; PARTS],
+            aligns: [1; PARTS],
             shader: [u32::MAX; PARTS],
+            elements: None,
+            tag: None,
         }
     }
 
@@ -41,6 +52,7 @@ impl<const PARTS: usize> Layout<PARTS> {
 
         self.offsets[head] = offset;
         self.lengths[head] = length;
+        self.aligns[head] = partition_align;
 
         self.last = length + offset;
         self.head += 1;
@@ -48,6 +60,32 @@ impl<const PARTS: usize> Layout<PARTS> {
         self
     }
 
+    /// Change the length (in bytes) of the part at `index`, moving the
+    /// following parts while keeping their alignment.
+    ///
+    /// Used to grow a [`PartitionedTriBuffer`](super::PartitionedTriBuffer),
+    /// see [`PartitionedTriBuffer::grow`](super::PartitionedTriBuffer::grow).
+    ///
+    /// # Panics
+    /// If there is no part at `index`.
+    pub fn resize_partition(mut self, index: usize, length: usize) -> Self {
+        assert!(
+            index < self.head,
+            "attempted to resize partition {index} of a layout with {} partitions",
+            self.head
+        );
+        self.lengths[index] = length;
+
+        let mut last = self.offsets[index];
+        for part in index..self.head {
+            let align = self.aligns[part];
+            self.offsets[part] = (last + align - 1) & !(align - 1);
+            last = self.offsets[part] + self.lengths[part];
+        }
+        self.last = last;
+        self
+    }
+
     pub fn with_shader_storage(mut self, binding: u32) -> Self {
         self.shader[self.head - 1] = binding;
         self
@@ -63,6 +101,60 @@ impl<const PARTS: usize> Layout<PARTS> {
         self.lengths[index]
     }
 
+    /// Mark the last part as the element (index) partition of the buffer,
+    /// holding the `u32` indices of indexed draws.
+    ///
+    /// The whole buffer is bound as the element array buffer, so the first
+    /// index of a draw command is counted from the start of the buffer, see
+    /// [`element_base`](Self::element_base).
+    ///
+    /// # Panics
+    /// If there is no part yet, or another part is already the element
+    /// partition.
+    pub fn with_elements(mut self) -> Self {
+        assert!(
+            self.head > 0,
+            "cannot mark elements of a layout without parts"
+        );
+        if let Some(elements) = self.elements {
+            panic!("partition {elements} is already the element partition of the layout");
+        }
+        self.elements = Some(self.head - 1);
+        self
+    }
+
+    /// Tag the layout with the descriptor `L` it was created from, e.g. the
+    /// enum generated by [`layout_buffer!`](crate::layout_buffer), so that
+    /// its [`Partition`]s can be accessed safely.
+    ///
+    /// # Safety
+    /// The partitions of the layout must store the element types of the
+    /// [`Partition`]s of `L`.
+    pub unsafe fn with_tag<L: 'static>(mut self) -> Self {
+        self.tag = Some(TypeId::of::<L>());
+        self
+    }
+
+    /// Whether the layout was tagged with the descriptor `L`, see
+    /// [`Self::with_tag`].
+    pub fn is_tagged<L: 'static>(&self) -> bool {
+        self.tag == Some(TypeId::of::<L>())
+    }
+
+    /// The index of the element partition, if any.
+    pub fn elements(&self) -> Option<usize> {
+        self.elements
+    }
+
+    /// The first index of the element partition within the buffer, to add to
+    /// the first index of draw commands, if the layout has one.
+    ///
+    /// This is `0` when the element partition is the first part.
+    pub fn element_base(&self) -> Option<u32> {
+        self.elements
+            .map(|part| (self.offsets[part] / size_of::<u32>()) as u32)
+    }
+
     pub fn ssbo_of(&self, index: usize) -> Option<u32> {
         let binding = self.shader[index];
         if binding != u32::MAX {
@@ -87,6 +179,161 @@ impl<const PARTS: usize> Layout<PARTS> {
     }
 }
 
+impl<const PARTS: usize> Layout<PARTS> {
+    /// A displayable description of the byte layout of the partitions.
+    ///
+    /// Layouts created by [`layout_buffer!`] can be described with the names
+    /// and types of their partitions through the generated `describe`
+    /// function.
+    pub fn describe(&self) -> LayoutDescription<'_, PARTS> {
+        LayoutDescription {
+            layout: self,
+            names: None,
+            types: None,
+        }
+    }
+}
+
+/// The byte layout of a [`Layout`], one partition per line.
+///
+/// ```text
+/// layout of 3 partitions, 8704 bytes:
+///   [0] transforms: Transform   offset      0  length   6144  ssbo 2
+///   [1] meshes: u32             offset   6144  length    512
+///   [2] colors: Vec4            offset   6656  length   2048  ssbo 3
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutDescription<'a, const PARTS: usize> {
+    layout: &'a Layout<PARTS>,
+    names: Option<[&'static str; PARTS]>,
+    types: Option<[&'static str; PARTS]>,
+}
+
+impl<const PARTS: usize> LayoutDescription<'_, PARTS> {
+    pub fn names(mut self, names: [&'static str; PARTS]) -> Self {
+        self.names = Some(names);
+        self
+    }
+
+    pub fn types(mut self, types: [&'static str; PARTS]) -> Self {
+        self.types = Some(types);
+        self
+    }
+}
+
+impl<const PARTS: usize> std::fmt::Display for LayoutDescription<'_, PARTS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let layout = self.layout;
+        write!(f, "layout of {PARTS} partitions, {} bytes:", layout.last)?;
+        for part in 0..PARTS {
+            let mut label = self.names.map(|n| n[part]).unwrap_or_default().to_string();
+            if let Some(types) = self.types {
+                label = format!("{label}: {}", types[part]);
+            }
+            write!(
+                f,
+                "\n  [{part}] {label:<24}offset {:>6}  length {:>6}",
+                layout.offset_at(part),
+                layout.length_at(part)
+            )?;
+            if let Some(binding) = layout.ssbo_of(part) {
+                write!(f, "  ssbo {binding}")?;
+            }
+            if layout.elements() == Some(part) {
+                write!(f, "  elements")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The partition at `INDEX` of the layouts created from the descriptor `L`,
+/// storing elements of type `T`.
+///
+/// This zero-sized marker is generated for each partition by
+/// [`layout_buffer!`](crate::layout_buffer), e.g. `LayoutTest::HEALTHS`, and
+/// makes the typed accessors of
+/// [`PartitionedTriBuffer`](super::PartitionedTriBuffer) safe, such as
+/// [`view_part_typed`](super::PartitionedTriBuffer::view_part_typed).
+pub struct Partition<L, T, const INDEX: usize>(PhantomData<fn() -> (L, T)>);
+
+impl<L, T, const INDEX: usize> Partition<L, T, INDEX> {
+    pub const INDEX: usize = INDEX;
+
+    /// # Safety
+    /// The partition at `INDEX` of the layouts [tagged](Layout::with_tag)
+    /// with `L` must store elements of type `T`.
+    #[doc(hidden)]
+    pub const unsafe fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<L, T, const INDEX: usize> Clone for Partition<L, T, INDEX> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<L, T, const INDEX: usize> Copy for Partition<L, T, INDEX> {}
+
+impl<L, T, const INDEX: usize> std::fmt::Debug for Partition<L, T, INDEX> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Partition<{}, {}>({INDEX})",
+            std::any::type_name::<L>(),
+            std::any::type_name::<T>()
+        )
+    }
+}
+
+/// The error returned when converting an index or a name that does not refer
+/// to a partition into a [`layout_buffer!`] enum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnknownPartition {
+    Index(usize),
+    Name(String),
+}
+
+impl std::fmt::Display for UnknownPartition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "no partition at index {index}"),
+            Self::Name(name) => write!(f, "no partition named {name:?}"),
+        }
+    }
+}
+
+impl std::error::Error for UnknownPartition {}
+
+/// Compare the size of the storage block named `block` in `program` with the
+/// size of the Rust type uploaded to it, logging a mismatch.
+///
+/// Used by the `verify_program` function generated by [`layout_buffer!`].
+///
+/// # Returns
+/// Whether the sizes match, or the block is not active in `program`.
+pub fn verify_storage_block(
+    program: &impl ShaderProgram,
+    block: &str,
+    type_name: &str,
+    type_size: usize,
+) -> bool {
+    match program.storage_block_size(block) {
+        Some(size) if size != type_size => {
+            use tracing::Level;
+            tracing::event!(
+                name: "render.layout",
+                Level::ERROR,
+                "storage block {block} has a stride of {size} bytes in the shader, but {type_name} is {type_size} bytes"
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
 /// Convenience macro to create a [`Layout`] with a useful enum to access
 /// buffer partitions.
 ///
@@ -129,6 +376,10 @@ impl<const PARTS: usize> Layout<PARTS> {
 /// The created enum also contains an associated function `LayoutTest::create`,
 /// which will create a [`Layout`] with the defined parts.
 ///
+/// The partition counts defined in the descriptor can be overridden at
+/// runtime with `LayoutTest::create_with`, for example with capacities
+/// derived from a [`BufferConfig`].
+///
 /// The created enum has the `#[repr(usize)]` attribute, which means that the
 /// entries of the enum may be used in [`PartitionedTriBuffer`]'s view_part*
 /// methods:
@@ -174,6 +425,35 @@ impl<const PARTS: usize> Layout<PARTS> {
 /// };
 /// ```
 ///
+/// ## Data-driven Access
+///
+/// The partitions can be referenced from configuration or scene files by name
+/// or by index: the enum implements `FromStr`, parsing the partition names as
+/// written in the descriptor, and `TryFrom<usize>`, converting `bind` indices.
+/// Both fail with an [`UnknownPartition`]. `LayoutTest::ALL` lists every
+/// partition in `bind` order, and `Display` writes the name of a partition.
+///
+/// ```rust,ignore
+/// let part: LayoutTest = "healths".parse()?;
+/// assert_eq!(LayoutTest::try_from(1), Ok(part));
+/// assert_eq!(part.to_string(), "healths");
+/// ```
+///
+/// ## Typed Access
+///
+/// Each partition also has a zero-sized [`Partition`] marker, named after the
+/// partition in upper case, which carries its element type. The layouts
+/// returned by `create` and `create_with` are tagged with the enum, so the
+/// partitions of a buffer created from them can be viewed and blitted
+/// without `unsafe`, see [`PartitionedTriBuffer::view_part_typed`] and
+/// [`PartitionedTriBuffer::blit_part_typed`]:
+///
+/// ```rust,ignore
+/// let buffer = PartitionedTriBuffer::new(LayoutTest::create());
+/// buffer.blit_part_typed(section, LayoutTest::HEALTHS, &healths, 0);
+/// let positions: View<[f32; 4]> = buffer.view_part_typed(section, LayoutTest::POSITIONS);
+/// ```
+///
 /// ## Partitioned Buffer Initialisation
 ///
 /// To properly initialise a [`PartitionedTriBuffer`], the macro generates yet
@@ -192,19 +472,83 @@ impl<const PARTS: usize> Layout<PARTS> {
 /// [`InitStrategy::Zero`] initialisation strategies respectively, with the
 /// latter being the default.
 ///
+/// ## Element Partition
+///
+/// A partition of `u32` indices can be declared as the element partition of
+/// the buffer, see [`Layout::with_elements`], to hold the indices of indexed
+/// draws:
+///
+/// ```rust,ignore
+/// enum indices: 4096 => {
+///     type u32 as elements;
+///     bind 0;
+/// };
+/// ```
+///
+/// ## Shader Interface
+///
+/// Partitions with a `shader` binding can declare the GLSL type of their
+/// elements, and optionally its struct definition, after the binding:
+///
+/// ```rust,ignore
+/// enum transforms: 128 => {
+///     type PackedTransform;
+///     bind 0;
+///     shader 2;
+///     glsl Transform => TransformGlslStruct::as_definition();
+/// };
+/// ```
+///
+/// The generated `LayoutTest::glsl_include` function then emits the GLSL
+/// interface of these partitions as a [`GlslInclude`], to inject in the
+/// `ssbo` section of a shader instead of declaring the storages by hand, so
+/// that shaders and the layout cannot drift apart. For each partition, it
+/// contains the struct definition, a `BINDING_TRANSFORMS` constant, a
+/// `Transforms` storage block with a `transforms` array, and a
+/// `fetch_transforms(uint idx)` function.
+///
+/// Partitions storing a [`PackedFormat`] declare the type their elements
+/// are unpacked to instead, and get a `LayoutTest::blit_<partition>`
+/// function converting while blitting, and a fetch function returning the
+/// unpacked elements in the GLSL interface:
+///
+/// ```rust,ignore
+/// enum colors: 128 => {
+///     type Unorm8x4;
+///     bind 1;
+///     shader 3;
+///     packed Vec4;
+/// };
+/// ```
+///
+/// The size of the Rust type of these partitions is checked at compile time
+/// against the `std430` array stride of their GLSL type, when known, see
+/// [`std430`](crate::shader::std430). As a last resort against drifting
+/// layouts, the generated `LayoutTest::verify_program` function compares
+/// them with the storage blocks of a linked shader program, as laid out by
+/// the driver.
+///
+/// [`GlslInclude`]: crate::shader::glsl::GlslInclude
+/// [`PackedFormat`]: super::packed::PackedFormat
 /// [`InitStrategy::Zero`]: super::InitStrategy::Zero
 /// [`InitStrategy::FillWith`]: super::InitStrategy::FillWith
 /// [`PartitionedTriBuffer`]: super::partitioned::PartitionedTriBuffer
+/// [`PartitionedTriBuffer::view_part_typed`]: super::partitioned::PartitionedTriBuffer::view_part_typed
+/// [`PartitionedTriBuffer::blit_part_typed`]: super::partitioned::PartitionedTriBuffer::blit_part_typed
+/// [`BufferConfig`]: crate::render::config::BufferConfig
 #[macro_export]
 macro_rules! layout_buffer {
+    (@elements elements) => {};
     (
         const $name:ty: $len:expr, {
             $(
                 enum $part:ident: $part_len:expr => {
-                    type $part_ty:ty;
+                    type $part_ty:ty $(as $elements:ident)?;
                     bind $part_idx:expr;
                     $(init with $init:block;)?
                     $(shader $part_ssbo:expr;)?
+                    $(glsl $glsl_ty:ident $(=> $glsl_def:expr)?;)?
+                    $(packed $unpacked:ty;)?
                 };
             )+
         }
@@ -216,7 +560,81 @@ macro_rules! layout_buffer {
                 $([< $part:camel >] = [< $part_idx _usize>],)+
             }
 
+            const _: () = {
+                $(
+                    $(
+                        $crate::layout_buffer!(@elements $elements);
+                        assert!(
+                            ::core::mem::size_of::<$part_ty>() == ::core::mem::size_of::<u32>(),
+                            concat!("the element partition `", stringify!($part), "` must store `u32` indices")
+                        );
+                    )?
+                    $(
+                        #[allow(unused_variables)]
+                        {
+                            let layout = $crate::shader::std430::Std430::of(stringify!($glsl_ty));
+                            $(
+                                let layout = $glsl_def.std430();
+                            )?
+                            if let Some(layout) = layout {
+                                assert!(
+                                    ::core::mem::size_of::<$part_ty>() == layout.stride(),
+                                    concat!(
+                                        "the size of `", stringify!($part_ty),
+                                        "` does not match the std430 array stride of `",
+                                        stringify!($glsl_ty), "` in partition `", stringify!($part), "`"
+                                    )
+                                );
+                            }
+                        }
+                    )?
+                    $(
+                        {
+                            let glsl_ty = <$part_ty as $crate::render::buffer::packed::PackedFormat>::GLSL_TYPE;
+                            if let Some(layout) = $crate::shader::std430::Std430::of(glsl_ty) {
+                                assert!(
+                                    ::core::mem::size_of::<$part_ty>() == layout.stride(),
+                                    concat!(
+                                        "the size of `", stringify!($part_ty),
+                                        "` does not match the std430 array stride of its packed GLSL type in partition `",
+                                        stringify!($part), "`"
+                                    )
+                                );
+                            }
+                            let _ = ::core::marker::PhantomData::<$unpacked>;
+                        }
+                    )?
+                )+
+            };
+
             impl [< Layout$name >] {
+                /// Every partition, indexed by their `bind` index.
+                pub const ALL: [Self; $len] = {
+                    let parts = [$(Self::[< $part:camel >],)+];
+                    let mut all = parts;
+                    let mut i = 0;
+                    while i < parts.len() {
+                        all[parts[i] as usize] = parts[i];
+                        i += 1;
+                    }
+                    all
+                };
+
+                $(
+                    #[doc = concat!(
+                        "The `", stringify!($part), "` partition, of `", stringify!($part_ty),
+                        "` elements, for the typed accessors of `PartitionedTriBuffer`."
+                    )]
+                    pub const [< $part:upper >]: $crate::render::buffer::layout::Partition<Self, $part_ty, $part_idx> =
+                        // SAFETY: the partition is created from the descriptor
+                        unsafe { $crate::render::buffer::layout::Partition::new() };
+                )+
+
+                /// The name of the partition, as written in the descriptor.
+                pub const fn as_str(self) -> &'static str {
+                    Self::NAMES[self as usize]
+                }
+
                 pub fn create() -> $crate::render::buffer::layout::Layout<$len> {
                     let mut layout = $crate::render::buffer::layout::Layout::<$len>::new();
                     $(
@@ -224,10 +642,154 @@ macro_rules! layout_buffer {
                         $(
                             layout = layout.with_shader_storage($part_ssbo);
                         )?
+                        $(
+                            let _ = stringify!($elements);
+                            layout = layout.with_elements();
+                        )?
+                    )+
+                    // SAFETY: the partitions are created from the descriptor,
+                    // as are the markers of the enum
+                    unsafe { layout.with_tag::<Self>() }
+                }
+
+                /// Create the layout with the element `counts` of each
+                /// partition determined at runtime, indexed by their `bind`
+                /// index, rather than the counts defined in the descriptor.
+                pub fn create_with(counts: [usize; $len]) -> $crate::render::buffer::layout::Layout<$len> {
+                    let mut layout = $crate::render::buffer::layout::Layout::<$len>::new();
+                    $(
+                        layout = layout.partition::<$part_ty>(counts[$part_idx]);
+                        $(
+                            layout = layout.with_shader_storage($part_ssbo);
+                        )?
+                        $(
+                            let _ = stringify!($elements);
+                            layout = layout.with_elements();
+                        )?
+                    )+
+                    // SAFETY: the partitions are created from the descriptor,
+                    // as are the markers of the enum
+                    unsafe { layout.with_tag::<Self>() }
+                }
+
+                /// The names of the partitions, indexed by their `bind`
+                /// index.
+                pub const NAMES: [&'static str; $len] = {
+                    let mut names = [""; $len];
+                    $(names[$part_idx] = stringify!($part);)+
+                    names
+                };
+
+                /// The element types of the partitions, indexed by their
+                /// `bind` index.
+                pub const TYPES: [&'static str; $len] = {
+                    let mut types = [""; $len];
+                    $(types[$part_idx] = stringify!($part_ty);)+
+                    types
+                };
+
+                /// Describe the byte layout of `layout` with the names and
+                /// types of the partitions, e.g. to log it during development.
+                pub fn describe(
+                    layout: &$crate::render::buffer::layout::Layout<$len>,
+                ) -> $crate::render::buffer::layout::LayoutDescription<'_, $len> {
+                    layout.describe().names(Self::NAMES).types(Self::TYPES)
+                }
+
+                /// The GLSL interface of the partitions with both a `shader`
+                /// binding and a `glsl` element type: their struct
+                /// definitions, binding indices, storage blocks, and fetch
+                /// functions.
+                pub fn glsl_include() -> $crate::shader::glsl::GlslInclude {
+                    #[allow(unused_mut)]
+                    let mut include = $crate::shader::glsl::GlslInclude::new();
+                    $(
+                        #[allow(unused_mut, unused_variables)]
+                        {
+                            let mut binding: Option<u32> = None;
+                            $(binding = Some($part_ssbo);)?
+                            $(
+                                if let Some(binding) = binding {
+                                    $(include.add_struct($glsl_def);)?
+                                    include.add_storage(
+                                        binding,
+                                        stringify!([< $part:camel >]),
+                                        stringify!($glsl_ty),
+                                        stringify!($part),
+                                    );
+                                }
+                            )?
+                            $(
+                                if let Some(binding) = binding {
+                                    let _ = ::core::marker::PhantomData::<$unpacked>;
+                                    include.add_packed_storage::<$part_ty>(
+                                        binding,
+                                        stringify!([< $part:camel >]),
+                                        stringify!($part),
+                                    );
+                                }
+                            )?
+                        }
+                    )+
+                    include
+                }
+
+                /// Compare the size of the Rust type of each partition with a
+                /// `glsl` element type to the stride of its storage block in
+                /// `program`, logging mismatches.
+                ///
+                /// # Returns
+                /// The amount of mismatching partitions. Partitions whose
+                /// block is not active in `program` are skipped.
+                #[allow(unused_variables)]
+                pub fn verify_program(program: &impl $crate::shader::ShaderProgram) -> usize {
+                    #[allow(unused_mut)]
+                    let mut mismatches = 0;
+                    $(
+                        $(
+                            let matches = $crate::render::buffer::layout::verify_storage_block(
+                                program,
+                                stringify!([< $part:camel >]),
+                                stringify!($part_ty),
+                                ::core::mem::size_of::<$part_ty>(),
+                            );
+                            mismatches += (!matches) as usize;
+                            let _ = stringify!($glsl_ty);
+                        )?
+                        $(
+                            let matches = $crate::render::buffer::layout::verify_storage_block(
+                                program,
+                                stringify!([< $part:camel >]),
+                                stringify!($part_ty),
+                                ::core::mem::size_of::<$part_ty>(),
+                            );
+                            mismatches += (!matches) as usize;
+                            let _ = ::core::marker::PhantomData::<$unpacked>;
+                        )?
                     )+
-                    layout
+                    mismatches
                 }
 
+                $(
+                    $(
+                        #[doc = concat!(
+                            "Convert `data` to `", stringify!($part_ty), "` while blitting it to the `",
+                            stringify!($part), "` partition of `section`, see\n",
+                            "`PartitionedTriBuffer::blit_part_packed`.\n\n",
+                            "# Safety\n",
+                            "`buffer` must have been created with this layout."
+                        )]
+                        pub unsafe fn [< blit_ $part >]<const PARTS: usize>(
+                            buffer: &$crate::render::buffer::partitioned::PartitionedTriBuffer<PARTS>,
+                            section: usize,
+                            data: &[$unpacked],
+                            offset: usize,
+                        ) -> usize {
+                            unsafe { buffer.blit_part_packed::<$part_ty>(section, $part_idx, data, offset) }
+                        }
+                    )?
+                )+
+
                 pub fn initialise_partitions<const PARTS: usize>(buffer: &$crate::render::buffer::partitioned::PartitionedTriBuffer<PARTS>) {
                     $(
                         #[allow(unused_variables)]
@@ -241,6 +803,130 @@ macro_rules! layout_buffer {
                     )+
                 }
             }
+
+            impl ::core::fmt::Display for [< Layout$name >] {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(self.as_str())
+                }
+            }
+
+            impl ::core::convert::TryFrom<usize> for [< Layout$name >] {
+                type Error = $crate::render::buffer::layout::UnknownPartition;
+
+                fn try_from(index: usize) -> Result<Self, Self::Error> {
+                    Self::ALL
+                        .get(index)
+                        .copied()
+                        .ok_or($crate::render::buffer::layout::UnknownPartition::Index(index))
+                }
+            }
+
+            impl ::core::str::FromStr for [< Layout$name >] {
+                type Err = $crate::render::buffer::layout::UnknownPartition;
+
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    Self::NAMES
+                        .iter()
+                        .position(|name| *name == s)
+                        .map(|index| Self::ALL[index])
+                        .ok_or_else(|| $crate::render::buffer::layout::UnknownPartition::Name(s.to_owned()))
+                }
+            }
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::LayoutParse;
+
+    #[test]
+    fn resize_partition_keeps_alignment() {
+        let layout = Layout::<3>::new()
+            .partition::<u32>(10)
+            .partition::<[f32; 4]>(4)
+            .with_shader_storage(2)
+            .partition::<u8>(3);
+        let resized = layout.resize_partition(0, 250 * size_of::<u32>());
+        let expected = Layout::<3>::new()
+            .partition::<u32>(250)
+            .partition::<[f32; 4]>(4)
+            .partition::<u8>(3);
+
+        for part in 0..3 {
+            assert_eq!(resized.offset_at(part), expected.offset_at(part));
+            assert_eq!(resized.length_at(part), expected.length_at(part));
+        }
+        assert_eq!(resized.len(), expected.len());
+        assert_eq!(resized.ssbo_of(1), Some(2));
+    }
+
+    #[allow(dead_code)]
+    mod parse {
+        crate::layout_buffer! {
+            const Parse: 3, {
+                enum healths: 8 => {
+                    type f32;
+                    bind 0;
+                };
+                enum indices: 8 => {
+                    type u32 as elements;
+                    bind 1;
+                };
+                enum positions: 8 => {
+                    type [f32; 4];
+                    bind 2;
+                };
+            }
+        }
+    }
+
+    #[test]
+    fn parse_layout_partitions() {
+        assert_eq!(
+            LayoutParse::ALL,
+            [
+                LayoutParse::Healths,
+                LayoutParse::Indices,
+                LayoutParse::Positions
+            ]
+        );
+        assert_eq!("positions".parse(), Ok(LayoutParse::Positions));
+        assert_eq!(LayoutParse::try_from(1), Ok(LayoutParse::Indices));
+        assert_eq!(LayoutParse::Healths.to_string(), "healths");
+        assert_eq!(
+            "Positions".parse::<LayoutParse>(),
+            Err(UnknownPartition::Name("Positions".into()))
+        );
+        assert_eq!(LayoutParse::try_from(3), Err(UnknownPartition::Index(3)));
+    }
+
+    #[test]
+    fn typed_partitions() {
+        let _: Partition<LayoutParse, [f32; 4], 2> = LayoutParse::POSITIONS;
+        assert_eq!(size_of_val(&LayoutParse::HEALTHS), 0);
+        assert_eq!(
+            Partition::<LayoutParse, u32, 1>::INDEX,
+            LayoutParse::Indices as usize
+        );
+
+        assert!(LayoutParse::create().is_tagged::<LayoutParse>());
+        assert!(LayoutParse::create_with([1, 2, 3]).is_tagged::<LayoutParse>());
+        assert!(!LayoutParse::create().is_tagged::<UnknownPartition>());
+        assert!(
+            !Layout::<1>::new()
+                .partition::<u32>(4)
+                .is_tagged::<LayoutParse>()
+        );
+    }
+
+    #[test]
+    fn element_partition() {
+        let layout = LayoutParse::create();
+        assert_eq!(layout.elements(), Some(LayoutParse::Indices as usize));
+        assert_eq!(layout.element_base(), Some(layout.offset_at(1) as u32 / 4));
+        assert!(layout.describe().to_string().contains("elements"));
+        assert_eq!(Layout::<2>::new().partition::<u32>(4).element_base(), None);
+    }
+}
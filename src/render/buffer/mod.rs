@@ -1,12 +1,27 @@
+pub mod adaptive;
+pub mod bandwidth;
+pub mod blit;
+pub mod flush;
 pub mod immutable;
 pub mod layout;
+pub mod orphan;
+pub mod overflow;
+pub mod pack;
+pub mod packed;
 pub mod partitioned;
+pub mod statics;
+pub mod validate;
 
-use std::cell::UnsafeCell;
+use std::{cell::UnsafeCell, mem::MaybeUninit, ops::Range};
 
+pub use adaptive::AdaptiveCapacity;
+pub use blit::BlitError;
+pub use flush::MappingMode;
 pub use immutable::{ImmutableBuffer, UninitImmutableBuffer};
 pub use layout::Layout;
+pub use orphan::UploadMode;
 pub use partitioned::PartitionedTriBuffer;
+pub use statics::{ChunkBuffers, Mobility, StaticBuffer};
 
 #[derive(Clone, Copy, Debug)]
 pub enum InitStrategy<T: Sized + Clone, F: Fn() -> T> {
@@ -55,6 +70,11 @@ pub(crate) use assert_tb_section;
 ///
 /// </div>
 ///
+/// Buffers created in [`UploadMode::Orphaning`] or
+/// [`UploadMode::ExplicitFlush`] must be [flushed](Self::flush_section) on the
+/// render thread before a section is read by the GPU, see [`orphan`] and
+/// [`flush`].
+///
 /// [`PartitionedTriBuffer`]: partitioned::PartitionedTriBuffer
 #[derive(Default, Debug)]
 pub struct TriBuffer<T: Sized + Clone + Copy> {
@@ -65,6 +85,17 @@ pub struct TriBuffer<T: Sized + Clone + Copy> {
     /// Capacity per each section. This is number of elements.
     capacity: usize,
 
+    overflow: overflow::OverflowReport,
+    /// The name of the buffer in [`bandwidth`] reports.
+    label: Option<&'static str>,
+
+    mode: UploadMode,
+    /// Owns the system memory of all sections in [`UploadMode::Orphaning`],
+    /// which is only accessed through `ptr`.
+    _staging: Option<Box<[MaybeUninit<T>]>>,
+    /// The bytes written to each section since it was last flushed.
+    written: [flush::WrittenRange; 3],
+
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -79,28 +110,78 @@ where
         Self::new(capacity, InitStrategy::<T, fn() -> T>::Zero)
     }
 
+    /// Create a buffer of `capacity` elements per section, in the current
+    /// [`UploadMode`].
     pub fn new<F: Fn() -> T>(capacity: usize, init: InitStrategy<T, F>) -> Self {
+        Self::with_mode(capacity, init, UploadMode::current())
+    }
+
+    /// Create a buffer of `capacity` elements per section, in the given
+    /// `mode`, or in [`UploadMode::Orphaning`] if the GL context does not
+    /// support it, see [`UploadMode::supported`].
+    pub fn with_mode<F: Fn() -> T>(
+        capacity: usize,
+        init: InitStrategy<T, F>,
+        mode: UploadMode,
+    ) -> Self {
         let mut gl_obj = [0; 3];
         let mut ptr = [std::ptr::null_mut(); 3];
+        let mut staging = None;
         let total_size = (capacity * size_of::<T>()) as isize;
 
-        unsafe {
-            janus::gl::CreateBuffers(1, &mut gl_obj[0]);
-            janus::gl::CreateBuffers(1, &mut gl_obj[1]);
-            janus::gl::CreateBuffers(1, &mut gl_obj[2]);
-
-            let flags = janus::gl::MAP_WRITE_BIT
-                | janus::gl::MAP_READ_BIT
-                | janus::gl::MAP_COHERENT_BIT
-                | janus::gl::MAP_PERSISTENT_BIT;
-
-            for i in 0..3 {
-                janus::gl::NamedBufferStorage(gl_obj[i], total_size, std::ptr::null(), flags);
-                ptr[i] = janus::gl::MapNamedBufferRange(gl_obj[i], 0, total_size, flags) as *mut T;
+        let mode = mode.supported();
+
+        match mode {
+            UploadMode::Persistent | UploadMode::ExplicitFlush => unsafe {
+                janus::gl::CreateBuffers(3, gl_obj.as_mut_ptr());
+                let storage_flags = janus::gl::MAP_WRITE_BIT
+                    | janus::gl::MAP_READ_BIT
+                    | janus::gl::MAP_PERSISTENT_BIT;
+                let (storage_flags, map_flags) = match mode {
+                    UploadMode::ExplicitFlush => (
+                        storage_flags,
+                        storage_flags | janus::gl::MAP_FLUSH_EXPLICIT_BIT,
+                    ),
+                    _ => {
+                        let flags = storage_flags | janus::gl::MAP_COHERENT_BIT;
+                        (flags, flags)
+                    }
+                };
+
+                for i in 0..3 {
+                    janus::gl::NamedBufferStorage(
+                        gl_obj[i],
+                        total_size,
+                        std::ptr::null(),
+                        storage_flags,
+                    );
+                    ptr[i] = janus::gl::MapNamedBufferRange(gl_obj[i], 0, total_size, map_flags)
+                        as *mut T;
+                }
+            },
+            // without direct state access, which orphaning does not require
+            UploadMode::Orphaning => {
+                let memory = staging.insert(Box::new_zeroed_slice(capacity * 3));
+                unsafe { janus::gl::GenBuffers(3, gl_obj.as_mut_ptr()) };
+                for i in 0..3 {
+                    ptr[i] = unsafe { memory.as_mut_ptr().add(i * capacity) as *mut T };
+                    unsafe {
+                        janus::gl::BindBuffer(janus::gl::COPY_WRITE_BUFFER, gl_obj[i]);
+                        janus::gl::BufferData(
+                            janus::gl::COPY_WRITE_BUFFER,
+                            total_size,
+                            std::ptr::null(),
+                            janus::gl::STREAM_DRAW,
+                        );
+                    }
+                }
             }
         }
 
+        let filled = matches!(init, InitStrategy::FillWith(_));
         match init {
+            // the system memory of orphaning buffers is already zeroed
+            InitStrategy::Zero if mode == UploadMode::Orphaning => {}
             InitStrategy::Zero => {
                 for i in 0..3 {
                     unsafe {
@@ -128,12 +209,83 @@ where
 
         let lengths = [UnsafeCell::new(0), UnsafeCell::new(0), UnsafeCell::new(0)];
 
-        Self {
+        let buffer = Self {
             gl_obj,
             ptr,
             lengths,
             capacity,
+            overflow: Default::default(),
+            label: None,
+            mode,
+            _staging: staging,
+            written: Default::default(),
             _marker: std::marker::PhantomData,
+        };
+        if filled {
+            for section in 0..3 {
+                buffer.mark_written(section, 0..buffer.capacity * size_of::<T>());
+                buffer.flush_section(section);
+            }
+        }
+        buffer
+    }
+
+    pub fn mode(&self) -> UploadMode {
+        self.mode
+    }
+
+    /// Make the elements written to a `section` visible to the GPU.
+    ///
+    /// In [`UploadMode::Orphaning`], the elements of the section are uploaded
+    /// if it was written since it was last flushed. In
+    /// [`UploadMode::ExplicitFlush`], the range written since the last flush
+    /// is flushed. Coherent persistent mappings do not need to be flushed, and
+    /// this does nothing.
+    ///
+    /// This is called by [`Self::bind_shader_storage`], but must be called
+    /// explicitly before a section is otherwise read by the GPU, e.g. as an
+    /// indirect command buffer.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn flush_section(&self, section: usize) {
+        assert_tb_section!(section);
+        if self.mode == UploadMode::Persistent {
+            return;
+        }
+        let Some(written) = self.written[section].take() else {
+            return;
+        };
+
+        if self.mode == UploadMode::ExplicitFlush {
+            flush::flush_range(self.gl_obj[section], written);
+            return;
+        }
+
+        let total_size = (self.capacity * size_of::<T>()) as isize;
+        let length = self.length(section).min(self.capacity);
+        unsafe {
+            janus::gl::BindBuffer(janus::gl::COPY_WRITE_BUFFER, self.gl_obj[section]);
+            janus::gl::BufferData(
+                janus::gl::COPY_WRITE_BUFFER,
+                total_size,
+                std::ptr::null(),
+                janus::gl::STREAM_DRAW,
+            );
+            janus::gl::BufferSubData(
+                janus::gl::COPY_WRITE_BUFFER,
+                0,
+                (length * size_of::<T>()) as isize,
+                self.ptr[section] as *const _,
+            );
+        }
+    }
+
+    /// Record a write of the byte range `bytes` of a `section`.
+    #[inline]
+    fn mark_written(&self, section: usize, bytes: Range<usize>) {
+        if self.mode != UploadMode::Persistent {
+            self.written[section].record(bytes);
         }
     }
 
@@ -145,6 +297,7 @@ where
     /// Or if `offset` is greater or equal to the buffer's internal length.
     pub fn bind_shader_storage(&self, section: usize, ssbo_index: u32, offset: u32) {
         assert_tb_section!(section);
+        self.flush_section(section);
 
         #[cfg(debug_assertions)]
         {
@@ -191,6 +344,7 @@ where
 
     pub fn view_section_mut(&self, section: usize) -> ViewMut<'_, T> {
         assert_tb_section!(section);
+        self.mark_written(section, 0..self.capacity * size_of::<T>());
 
         let ptr = self.ptr[section];
         let slice = unsafe { std::slice::from_raw_parts_mut(ptr, self.capacity) };
@@ -205,6 +359,10 @@ where
     }
 
     pub fn set_length(&self, section: usize, length: u32) {
+        if self.mode == UploadMode::Orphaning {
+            // the whole length is uploaded, even if the contents did not change
+            self.mark_written(section, 0..length as usize * size_of::<T>());
+        }
         let p = self.lengths[section].get() as *mut u32;
         unsafe {
             *p = length;
@@ -220,6 +378,25 @@ where
         self.capacity
     }
 
+    /// Name the buffer in [`bandwidth`] reports.
+    pub fn label(&mut self, name: &'static str) {
+        self.label = Some(name);
+    }
+
+    /// Replace the storage of the buffer with a zeroed one of the given
+    /// `capacity`, for example as suggested by an [`AdaptiveCapacity`].
+    ///
+    /// The contents and lengths of all sections are discarded.
+    ///
+    /// Requires exclusive access to the buffer: the GPU must not be reading
+    /// from any of its sections, and buffers shared across threads must be
+    /// replaced as a whole instead.
+    pub fn reallocate(&mut self, capacity: usize) {
+        let label = self.label;
+        *self = Self::with_mode(capacity, InitStrategy::<T, fn() -> T>::Zero, self.mode);
+        self.label = label;
+    }
+
     /// Copy the given `data` into a `section` of the triple buffer at a given
     /// `offset`.
     ///
@@ -230,11 +407,15 @@ where
     ///
     /// If the length of `data` exceeds the capacity of the buffer, it will be
     /// automatically clamped and any exceeding elements will be ignored.
+    /// Ignored elements are reported, see [`overflow`].
+    ///
+    /// # Returns
+    /// The amount of elements written.
     ///
     /// # Panics
     /// * If `section` is not a value within the range (0, 2).
     /// * If `offset` is greater than the length of the section.
-    pub fn blit_section(&self, section: usize, data: &[T], offset: usize) {
+    pub fn blit_section(&self, section: usize, data: &[T], offset: usize) -> usize {
         assert_tb_section!(section);
         assert!(
             self.capacity > offset,
@@ -245,11 +426,60 @@ where
         let src = data.as_ptr();
         let avail = self.capacity - offset;
         let len = avail.min(data.len());
-        unsafe { *(self.lengths[section].get()) = len as u32 };
+        self.set_length(section, len as u32);
+        self.overflow
+            .record(self.gl_obj[section], None, data.len(), len);
+        bandwidth::record(self.gl_obj[0], None, self.label, len * size_of::<T>());
+        let start = offset * size_of::<T>();
+        self.mark_written(section, start..start + len * size_of::<T>());
 
         unsafe {
             std::ptr::copy_nonoverlapping(src, self.ptr[section].add(offset), len);
         }
+        len
+    }
+
+    /// Copy the given `data` into a `section` of the triple buffer at the
+    /// given element `offset`, updating only that range.
+    ///
+    /// Unlike [`blit_section`](Self::blit_section), the length of the section
+    /// only grows to cover the written range, so that the elements before and
+    /// after it keep their contents. These were last written to this section,
+    /// not to the previously published one: with a triple buffer, each
+    /// section must be brought up to date with the ranges changed since it
+    /// was last written.
+    ///
+    /// Exceeding elements are clamped and reported, as in `blit_section`.
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
+    /// # Panics
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If `offset` is greater than the length of the section.
+    pub fn blit_section_at(&self, section: usize, offset: usize, data: &[T]) -> usize {
+        let previous = self.length(section);
+        let written = self.blit_section(section, data, offset);
+        self.set_length(section, previous.max(offset + written) as u32);
+        written
+    }
+
+    /// Copy the given `data` into a `section` of the triple buffer at a given
+    /// `offset`, like [`blit_section`](Self::blit_section), but only if all
+    /// of it fits.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn try_blit_section(
+        &self,
+        section: usize,
+        data: &[T],
+        offset: usize,
+    ) -> Result<(), BlitError> {
+        assert_tb_section!(section);
+        blit::check(offset, data.len(), self.capacity)?;
+        self.blit_section(section, data, offset);
+        Ok(())
     }
 
     /// Copy the given `data` into a `section` of the triple buffer at a given
@@ -258,6 +488,7 @@ where
     ///
     /// If the length of `data` exceeds the capacity of the buffer, it will be
     /// automatically clamped and any exceeding elements will be ignored.
+    /// Ignored elements are reported, see [`overflow`].
     ///
     /// This function is intended for operations where the CPU and GPU data
     /// representations differ due to memory alignment requirements.
@@ -287,6 +518,9 @@ where
     /// of `data` with the given `pad_len` in bytes to satisfy SSBO alignment
     /// requirements, without the need of intermediary buffers on the CPU.
     ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
     /// # Panics
     /// * If `section` is not a value within the range (0, 2).
     /// * If `offset` is greater than the length of the section.
@@ -301,7 +535,7 @@ where
         data: &[S],
         offset: usize,
         pad_len: usize,
-    ) {
+    ) -> usize {
         assert_ne!(
             pad_len, 0,
             "cannot blit with padding: invalid padding value of 0"
@@ -314,7 +548,6 @@ where
             self.capacity
         );
 
-        let avail = self.capacity * size_of::<T>() - offset;
         let data_bytes_padded = size_of::<S>() + pad_len;
         assert_eq!(
             data_bytes_padded,
@@ -324,12 +557,17 @@ where
             size_of::<S>(),
         );
 
-        let avail_count = avail / data_bytes_padded;
+        let avail_count = self.capacity - offset;
         let data_count = data.len();
 
         // safe total length of data, element count
         let data_len = avail_count.min(data_count);
-        unsafe { *(self.lengths[section].get()) = data_len as u32 };
+        self.set_length(section, data_len as u32);
+        self.overflow
+            .record(self.gl_obj[section], None, data_count, data_len);
+        bandwidth::record(self.gl_obj[0], None, self.label, data_len * size_of::<T>());
+        let offset = offset * size_of::<T>();
+        self.mark_written(section, offset..offset + data_len * size_of::<T>());
 
         // SAFETY: we assert the section and partition are valid within this
         // buffer's layout. The buffer's layout, in turn, guarantees valid
@@ -346,6 +584,7 @@ where
                 dst = dst.add(pad_len);
             }
         }
+        data_len
     }
 }
 
@@ -355,8 +594,10 @@ where
 {
     fn drop(&mut self) {
         unsafe {
-            for i in 0..3 {
-                janus::gl::UnmapNamedBuffer(self.gl_obj[i]);
+            if self.mode == UploadMode::Persistent {
+                for i in 0..3 {
+                    janus::gl::UnmapNamedBuffer(self.gl_obj[i]);
+                }
             }
             janus::gl::DeleteBuffers(3, self.gl_obj.as_ptr());
         }
@@ -0,0 +1,252 @@
+//! Buffer orphaning fallback for drivers with slow persistent mappings.
+//!
+//! By default, [`TriBuffer`](super::TriBuffer) sections are persistently and
+//! coherently mapped: blits write straight into GPU-visible memory. Some
+//! drivers back such mappings with uncached or remote memory, where these
+//! writes are much slower than writes to system memory.
+//!
+//! In [`UploadMode::Orphaning`], buffers instead keep their sections in system
+//! memory, and each section is uploaded when it is flushed on the render
+//! thread: the storage of the GL buffer is orphaned with
+//! `glBufferData(NULL)`, so the driver can hand out fresh memory without
+//! waiting on in-flight draws, then filled with `glBufferSubData`.
+//!
+//! [`PartitionedTriBuffer`](super::PartitionedTriBuffer)s also keep their
+//! sections in system memory, but their three sections share a single GL
+//! buffer, whose orphaning would discard the sections still read by the GPU:
+//! only the range written to a section is uploaded with `glBufferSubData`.
+//!
+//! # Fallback
+//! Persistent mappings require immutable buffer storage, and are created and
+//! flushed with direct state access, i.e. GL 4.5 or both
+//! `GL_ARB_buffer_storage` and `GL_ARB_direct_state_access`. Orphaning only
+//! binds the buffers to edit them, and is used instead by the frame data
+//! buffers created on a context without either, see [`UploadMode::supported`].
+//! [`UploadMode::select_supported`] makes the fallback explicit, and logs it,
+//! once the context is created.
+//!
+//! The fallback only covers the frame data: mesh buffers
+//! ([`ImmutableBuffer`](super::immutable::ImmutableBuffer),
+//! [`StaticBuffer`](super::statics::StaticBuffer)) and the
+//! [`RingBuffer`](crate::render::ring::RingBuffer) still require GL 4.5.
+//!
+//! The mode is selected at runtime, per process, and applies to the buffers
+//! created afterwards. The `stress` example runs with either mode to compare
+//! them on the current machine:
+//!
+//! ```sh
+//! cargo run --release --example stress -- 250000 persistent
+//! cargo run --release --example stress -- 250000 orphaning
+//! cargo run --release --example stress -- 250000 explicit_flush
+//! ```
+
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicU8, Ordering},
+};
+
+static UPLOAD_MODE: AtomicU8 = AtomicU8::new(UploadMode::Persistent as u8);
+static BUFFER_STORAGE: OnceLock<bool> = OnceLock::new();
+
+/// Whether the GL context supports immutable buffer storage and direct state
+/// access, which the persistent mappings of [`UploadMode::Persistent`] and
+/// [`UploadMode::ExplicitFlush`] require.
+///
+/// The context is probed on the first call made with a current context: the
+/// result is cached for the whole process. Calls made before the context is
+/// created return `false` and are not cached.
+pub fn supports_buffer_storage() -> bool {
+    if let Some(&supported) = BUFFER_STORAGE.get() {
+        return supported;
+    }
+    match probe_buffer_storage() {
+        Some(supported) => *BUFFER_STORAGE.get_or_init(|| supported),
+        None => false,
+    }
+}
+
+/// Whether the current GL context is at least GL 4.5 or advertises both
+/// `GL_ARB_buffer_storage` and `GL_ARB_direct_state_access`.
+///
+/// # Returns
+/// `None` if no GL context is current.
+fn probe_buffer_storage() -> Option<bool> {
+    if !janus::gl::GetIntegerv::is_loaded() {
+        return None;
+    }
+
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        janus::gl::GetIntegerv(janus::gl::MAJOR_VERSION, &mut major);
+        janus::gl::GetIntegerv(janus::gl::MINOR_VERSION, &mut minor);
+    }
+    if major == 0 {
+        return None;
+    }
+
+    let loaded = janus::gl::BufferStorage::is_loaded()
+        && janus::gl::CreateBuffers::is_loaded()
+        && janus::gl::NamedBufferStorage::is_loaded()
+        && janus::gl::MapNamedBufferRange::is_loaded()
+        && janus::gl::FlushMappedNamedBufferRange::is_loaded();
+    if !loaded {
+        return Some(false);
+    }
+    if (major, minor) >= (4, 5) {
+        return Some(true);
+    }
+    if !janus::gl::GetStringi::is_loaded() {
+        return Some(false);
+    }
+
+    let mut count = 0;
+    unsafe {
+        janus::gl::GetIntegerv(janus::gl::NUM_EXTENSIONS, &mut count);
+    }
+    let has_extension = |extension: &[u8]| {
+        (0..count.max(0) as u32).any(|i| {
+            let name = unsafe { janus::gl::GetStringi(janus::gl::EXTENSIONS, i) };
+            !name.is_null()
+                && unsafe { std::ffi::CStr::from_ptr(name as *const _) }.to_bytes() == extension
+        })
+    };
+    Some(has_extension(b"GL_ARB_buffer_storage") && has_extension(b"GL_ARB_direct_state_access"))
+}
+
+/// How the CPU writes to a [`TriBuffer`](super::TriBuffer) reach the GPU.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum UploadMode {
+    /// Sections are persistently and coherently mapped.
+    #[default]
+    Persistent,
+    /// Sections are written in system memory, and uploaded by orphaning the
+    /// buffer storage when flushed.
+    Orphaning,
+    /// Sections are persistently mapped without coherency, and the written
+    /// ranges are flushed explicitly, see [`flush`](super::flush).
+    ExplicitFlush,
+}
+
+impl UploadMode {
+    /// The mode of the buffers created from now on.
+    pub fn current() -> Self {
+        match UPLOAD_MODE.load(Ordering::Relaxed) {
+            0 => Self::Persistent,
+            1 => Self::Orphaning,
+            _ => Self::ExplicitFlush,
+        }
+    }
+
+    /// Set the mode of the buffers created from now on.
+    ///
+    /// Existing buffers keep the mode they were created with.
+    pub fn set_current(self) {
+        UPLOAD_MODE.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// This mode if the GL context supports it, or [`UploadMode::Orphaning`]
+    /// otherwise, see [`supports_buffer_storage`].
+    pub fn supported(self) -> Self {
+        if self == Self::Orphaning || supports_buffer_storage() {
+            self
+        } else {
+            Self::Orphaning
+        }
+    }
+
+    /// Fall back to [`UploadMode::Orphaning`] for the buffers created from
+    /// now on if the GL context does not support the current mode.
+    ///
+    /// Buffers fall back on their own when created, this only logs the
+    /// fallback once; call it after the GL context is created.
+    ///
+    /// # Returns
+    /// The mode of the buffers created from now on.
+    pub fn select_supported() -> Self {
+        let current = Self::current();
+        let supported = current.supported();
+        if supported != current {
+            use tracing::Level;
+            tracing::event!(
+                name: "buffer.fallback",
+                Level::WARN,
+                "GL context lacks buffer storage or direct state access: falling back from {current} to {supported} uploads"
+            );
+            supported.set_current();
+        }
+        supported
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Persistent => "persistent",
+            Self::Orphaning => "orphaning",
+            Self::ExplicitFlush => "explicit_flush",
+        }
+    }
+}
+
+impl std::fmt::Display for UploadMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The error returned when parsing an unknown [`UploadMode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownUploadMode(pub String);
+
+impl std::fmt::Display for UnknownUploadMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown upload mode {:?}, expected \"persistent\", \"orphaning\" or \"explicit_flush\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownUploadMode {}
+
+impl std::str::FromStr for UploadMode {
+    type Err = UnknownUploadMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "persistent" => Ok(Self::Persistent),
+            "orphaning" | "orphan" => Ok(Self::Orphaning),
+            "explicit_flush" | "explicit" => Ok(Self::ExplicitFlush),
+            _ => Err(UnknownUploadMode(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_upload_mode() {
+        assert_eq!("orphan".parse(), Ok(UploadMode::Orphaning));
+        assert_eq!(
+            UploadMode::Persistent.to_string().parse(),
+            Ok(UploadMode::Persistent)
+        );
+        assert_eq!(
+            UploadMode::ExplicitFlush.to_string().parse(),
+            Ok(UploadMode::ExplicitFlush)
+        );
+        assert!("mapped".parse::<UploadMode>().is_err());
+    }
+
+    #[test]
+    fn fallback_without_buffer_storage() {
+        // no GL context is loaded in tests, which is not cached
+        assert!(!supports_buffer_storage());
+        assert!(BUFFER_STORAGE.get().is_none());
+        assert_eq!(UploadMode::Persistent.supported(), UploadMode::Orphaning);
+        assert_eq!(UploadMode::ExplicitFlush.supported(), UploadMode::Orphaning);
+        assert_eq!(UploadMode::Orphaning.supported(), UploadMode::Orphaning);
+    }
+}
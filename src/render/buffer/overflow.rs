@@ -0,0 +1,92 @@
+//! Tracking of elements dropped by blits exceeding the capacity of a buffer.
+//!
+//! Blits never write past the capacity of a section or partition: exceeding
+//! elements are deterministically discarded from the end of the source data.
+//! As this usually means that a buffer layout is too small for the scene, the
+//! first overflow of each buffer is logged, and the amount of dropped elements
+//! is reported to the [`Recorder`] of the current thread, which
+//! [`State::upload`](crate::state::State::upload) collects into its
+//! [`UploadStats`](crate::state::UploadStats) every frame.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::state::stats::{self, Recorder};
+
+/// The amount of elements dropped by blits on the current thread since its
+/// [`Recorder`] last took its counters, `0` without a recorder.
+pub fn dropped_elements() -> usize {
+    stats::with_recorder(Recorder::dropped_elements).unwrap_or(0)
+}
+
+/// Per-buffer overflow state, ensuring each overflow is only logged once.
+#[derive(Debug, Default)]
+pub(crate) struct OverflowReport {
+    reported: AtomicBool,
+}
+
+impl OverflowReport {
+    /// Record a blit of `requested` elements of which only `written` fit in
+    /// the destination.
+    ///
+    /// Does nothing if all elements were written.
+    #[inline]
+    pub(crate) fn record(
+        &self,
+        gl_obj: u32,
+        partition: Option<usize>,
+        requested: usize,
+        written: usize,
+    ) {
+        if written >= requested {
+            return;
+        }
+
+        let dropped = requested - written;
+        stats::record_dropped(dropped);
+
+        if self.reported.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        use tracing::Level;
+        match partition {
+            Some(partition) => tracing::event!(
+                name: "buffer.overflow",
+                Level::WARN,
+                "blit of {requested} elements exceeds the capacity of partition {partition} of buffer {gl_obj}: {dropped} elements were dropped, consider resizing the buffer layout"
+            ),
+            None => tracing::event!(
+                name: "buffer.overflow",
+                Level::WARN,
+                "blit of {requested} elements exceeds the capacity of buffer {gl_obj}: {dropped} elements were dropped, consider resizing the buffer"
+            ),
+        }
+    }
+
+    /// Whether an overflow has been reported for this buffer.
+    #[cfg(test)]
+    pub(crate) fn has_overflowed(&self) -> bool {
+        self.reported.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_once_count_always() {
+        let report = OverflowReport::default();
+        let recorder = Recorder::new();
+        recorder.record(|| {
+            report.record(1, None, 10, 10);
+            assert!(!report.has_overflowed());
+
+            report.record(1, Some(0), 10, 4);
+            report.record(1, Some(0), 8, 5);
+            assert!(report.has_overflowed());
+            assert_eq!(dropped_elements(), 9);
+        });
+        assert_eq!(dropped_elements(), 0);
+    }
+}
@@ -0,0 +1,278 @@
+//! Bulk conversion of simulation-side transform columns into their GPU-facing
+//! representation.
+//!
+//! Simulation data is usually stored as tightly packed `Vec3` and `Quat`
+//! columns, while SSBOs require 16-byte aligned `vec4`s. Packing these
+//! columns element by element quickly dominates upload time at high entity
+//! counts, so the functions in this module process their input in fixed-size
+//! chunks of SIMD `glam` types, which the compiler can unroll and vectorise.
+//!
+//! All functions write into a caller provided destination, which can be a
+//! mapped GPU buffer view (see [`ViewMut`](super::ViewMut)) to avoid any
+//! intermediate CPU buffer.
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use crate::shader::glsl::GlslLib;
+
+/// The amount of elements processed per iteration of the unrolled loops.
+const CHUNK: usize = 8;
+
+/// The GPU-facing representation of a rigid transform.
+///
+/// Corresponds to the following GLSL struct in a `std430` layout:
+/// ```glsl
+/// struct Transform {
+///     vec4 position;
+///     vec4 rotation;
+/// };
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PackedTransform {
+    pub position: Vec4,
+    pub rotation: Vec4,
+}
+
+impl PackedTransform {
+    #[inline(always)]
+    pub fn new(position: Vec3, rotation: Quat) -> Self {
+        Self {
+            position: position.extend(1.0),
+            rotation: Vec4::from(rotation),
+        }
+    }
+}
+
+#[inline(always)]
+fn pack_chunked<S: Copy, D, F: Fn(S) -> D>(src: &[S], dst: &mut [D], f: F) -> usize {
+    let len = src.len().min(dst.len());
+    let (src, dst) = (&src[..len], &mut dst[..len]);
+
+    let mut src_chunks = src.chunks_exact(CHUNK);
+    let mut dst_chunks = dst.chunks_exact_mut(CHUNK);
+    for (s, d) in (&mut src_chunks).zip(&mut dst_chunks) {
+        for i in 0..CHUNK {
+            d[i] = f(s[i]);
+        }
+    }
+
+    let src_rem = src_chunks.remainder();
+    let dst_rem = dst_chunks.into_remainder();
+    for (s, d) in src_rem.iter().zip(dst_rem) {
+        *d = f(*s);
+    }
+
+    len
+}
+
+/// Pack `src` into `dst`, extending each vector with the given `w`
+/// component.
+///
+/// Use a `w` of `1.0` for positions and `0.0` for directions.
+///
+/// # Returns
+/// The amount of packed elements, i.e. the minimum length between `src` and
+/// `dst`.
+pub fn pack_vec3(src: &[Vec3], w: f32, dst: &mut [Vec4]) -> usize {
+    pack_chunked(src, dst, |v| v.extend(w))
+}
+
+/// Pack the quaternions of `src` into `dst` as `(x, y, z, w)` vectors.
+///
+/// # Returns
+/// The amount of packed elements, i.e. the minimum length between `src` and
+/// `dst`.
+pub fn pack_quat(src: &[Quat], dst: &mut [Vec4]) -> usize {
+    pack_chunked(src, dst, Vec4::from)
+}
+
+/// Pack parallel `positions` and `rotations` columns into interleaved
+/// [`PackedTransform`]s.
+///
+/// # Returns
+/// The amount of packed elements, i.e. the minimum length between
+/// `positions`, `rotations` and `dst`.
+pub fn pack_transforms(
+    positions: &[Vec3],
+    rotations: &[Quat],
+    dst: &mut [PackedTransform],
+) -> usize {
+    let len = positions.len().min(rotations.len()).min(dst.len());
+    let (positions, rotations) = (&positions[..len], &rotations[..len]);
+    let dst = &mut dst[..len];
+
+    let mut pos_chunks = positions.chunks_exact(CHUNK);
+    let mut rot_chunks = rotations.chunks_exact(CHUNK);
+    let mut dst_chunks = dst.chunks_exact_mut(CHUNK);
+    for ((p, r), d) in (&mut pos_chunks).zip(&mut rot_chunks).zip(&mut dst_chunks) {
+        for i in 0..CHUNK {
+            d[i] = PackedTransform::new(p[i], r[i]);
+        }
+    }
+
+    let rem = pos_chunks.remainder().iter().zip(rot_chunks.remainder());
+    for ((p, r), d) in rem.zip(dst_chunks.into_remainder()) {
+        *d = PackedTransform::new(*p, *r);
+    }
+
+    len
+}
+
+/// Pack parallel `positions` and `rotations` columns into model matrices.
+///
+/// # Returns
+/// The amount of packed elements, i.e. the minimum length between
+/// `positions`, `rotations` and `dst`.
+pub fn pack_transform_matrices(positions: &[Vec3], rotations: &[Quat], dst: &mut [Mat4]) -> usize {
+    let len = positions.len().min(rotations.len()).min(dst.len());
+    let (positions, rotations) = (&positions[..len], &rotations[..len]);
+    let dst = &mut dst[..len];
+
+    let mut pos_chunks = positions.chunks_exact(CHUNK);
+    let mut rot_chunks = rotations.chunks_exact(CHUNK);
+    let mut dst_chunks = dst.chunks_exact_mut(CHUNK);
+    for ((p, r), d) in (&mut pos_chunks).zip(&mut rot_chunks).zip(&mut dst_chunks) {
+        for i in 0..CHUNK {
+            d[i] = Mat4::from_rotation_translation(r[i], p[i]);
+        }
+    }
+
+    let rem = pos_chunks.remainder().iter().zip(rot_chunks.remainder());
+    for ((p, r), d) in rem.zip(dst_chunks.into_remainder()) {
+        *d = Mat4::from_rotation_translation(*r, *p);
+    }
+
+    len
+}
+
+/// Pack parallel `positions`, `rotations` and `scales` columns into model
+/// matrices.
+///
+/// # Returns
+/// The amount of packed elements, i.e. the minimum length between
+/// `positions`, `rotations`, `scales` and `dst`.
+pub fn pack_trs_matrices(
+    positions: &[Vec3],
+    rotations: &[Quat],
+    scales: &[Vec3],
+    dst: &mut [Mat4],
+) -> usize {
+    let len = positions
+        .len()
+        .min(rotations.len())
+        .min(scales.len())
+        .min(dst.len());
+    let (positions, rotations, scales) = (&positions[..len], &rotations[..len], &scales[..len]);
+    let dst = &mut dst[..len];
+
+    let mut pos_chunks = positions.chunks_exact(CHUNK);
+    let mut rot_chunks = rotations.chunks_exact(CHUNK);
+    let mut scale_chunks = scales.chunks_exact(CHUNK);
+    let mut dst_chunks = dst.chunks_exact_mut(CHUNK);
+    let chunks = (&mut pos_chunks)
+        .zip(&mut rot_chunks)
+        .zip(&mut scale_chunks);
+    for (((p, r), s), d) in chunks.zip(&mut dst_chunks) {
+        for i in 0..CHUNK {
+            d[i] = Mat4::from_scale_rotation_translation(s[i], r[i], p[i]);
+        }
+    }
+
+    let rem = pos_chunks.remainder().iter().zip(rot_chunks.remainder());
+    let rem = rem.zip(scale_chunks.remainder());
+    for (((p, r), s), d) in rem.zip(dst_chunks.into_remainder()) {
+        *d = Mat4::from_scale_rotation_translation(*s, *r, *p);
+    }
+
+    len
+}
+
+/// The model matrix of a [`PackedTransform`] and a scale packed with
+/// [`pack_vec3`], stored in a separate partition.
+///
+/// Matches [`Mat4::from_scale_rotation_translation`].
+pub const GLSL_LIB_TRS_MATRIX: GlslLib = crate::shader_glsl_lib! {
+    mat4 trs_matrix [ position: vec4, rotation: vec4, scale: vec4 ] => "
+        vec3 q2 = rotation.xyz * 2.0;
+        float xx = rotation.x * q2.x;
+        float yy = rotation.y * q2.y;
+        float zz = rotation.z * q2.z;
+        float xy = rotation.x * q2.y;
+        float xz = rotation.x * q2.z;
+        float yz = rotation.y * q2.z;
+        float wx = rotation.w * q2.x;
+        float wy = rotation.w * q2.y;
+        float wz = rotation.w * q2.z;
+        return mat4(
+            vec4(1.0 - (yy + zz), xy + wz, xz - wy, 0.0) * scale.x,
+            vec4(xy - wz, 1.0 - (xx + zz), yz + wx, 0.0) * scale.y,
+            vec4(xz + wy, yz - wx, 1.0 - (xx + yy), 0.0) * scale.z,
+            vec4(position.xyz, 1.0)
+        );
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(n: usize) -> Vec<Vec3> {
+        (0..n)
+            .map(|i| Vec3::new(i as f32, i as f32 * 2.0, -(i as f32)))
+            .collect()
+    }
+
+    fn rotations(n: usize) -> Vec<Quat> {
+        (0..n)
+            .map(|i| Quat::from_rotation_y(i as f32 * 0.1))
+            .collect()
+    }
+
+    #[test]
+    fn pack_matches_scalar() {
+        // not a multiple of CHUNK, so that the remainder path is exercised
+        const N: usize = CHUNK * 3 + 5;
+        let pos = positions(N);
+        let rot = rotations(N);
+
+        let mut vec4s = vec![Vec4::ZERO; N];
+        assert_eq!(pack_vec3(&pos, 1.0, &mut vec4s), N);
+        assert!(pos.iter().zip(&vec4s).all(|(p, v)| p.extend(1.0) == *v));
+
+        assert_eq!(pack_quat(&rot, &mut vec4s), N);
+        assert!(rot.iter().zip(&vec4s).all(|(q, v)| Vec4::from(*q) == *v));
+
+        let mut transforms = vec![PackedTransform::default(); N];
+        assert_eq!(pack_transforms(&pos, &rot, &mut transforms), N);
+        for i in 0..N {
+            assert_eq!(transforms[i], PackedTransform::new(pos[i], rot[i]));
+        }
+
+        let mut matrices = vec![Mat4::IDENTITY; N];
+        assert_eq!(pack_transform_matrices(&pos, &rot, &mut matrices), N);
+        for i in 0..N {
+            assert_eq!(matrices[i], Mat4::from_rotation_translation(rot[i], pos[i]));
+        }
+
+        let scales: Vec<_> = pos.iter().map(|p| p.abs() + Vec3::ONE).collect();
+        assert_eq!(pack_trs_matrices(&pos, &rot, &scales, &mut matrices), N);
+        for i in 0..N {
+            let trs = Mat4::from_scale_rotation_translation(scales[i], rot[i], pos[i]);
+            assert_eq!(matrices[i], trs);
+        }
+    }
+
+    #[test]
+    fn pack_truncates_to_shortest() {
+        let pos = positions(20);
+        let rot = rotations(12);
+
+        let mut vec4s = vec![Vec4::ZERO; 10];
+        assert_eq!(pack_vec3(&pos, 0.0, &mut vec4s), 10);
+
+        let mut transforms = vec![PackedTransform::default(); 16];
+        assert_eq!(pack_transforms(&pos, &rot, &mut transforms), 12);
+        assert_eq!(transforms[12], PackedTransform::default());
+    }
+}
@@ -0,0 +1,244 @@
+//! Compact GPU formats for fat partitions.
+//!
+//! Most per-entity data does not need full `f32` precision on the GPU:
+//! scales and velocities fit in half precision floats, and colors in 8 bits
+//! per channel. Storing them in a [`PackedFormat`] halves (or quarters) the
+//! bytes uploaded every frame, at the cost of a conversion on the CPU while
+//! blitting, and of unpacking them in shaders with the GLSL built-ins
+//! `unpackHalf2x16` and `unpackUnorm4x8`.
+//!
+//! Partitions of a [`layout_buffer!`](crate::layout_buffer) declare the
+//! unpacked type of their packed elements with a `packed` clause, which
+//! generates a `blit_<partition>` function converting while blitting, and a
+//! `fetch_<partition>` GLSL function unpacking the elements:
+//!
+//! ```rust,ignore
+//! enum scales: 128 => {
+//!     type Half4;
+//!     bind 3;
+//!     shader 3;
+//!     packed Vec4;
+//! };
+//! ```
+
+use glam::{Vec2, Vec4};
+
+use crate::shader::glsl::GlslLib;
+
+/// A compact representation of `Unpacked` values in GPU buffers.
+pub trait PackedFormat: Copy {
+    type Unpacked: Copy;
+
+    /// The GLSL type the values are stored as.
+    const GLSL_TYPE: &'static str;
+    /// The GLSL type the values are unpacked to.
+    const GLSL_UNPACKED: &'static str;
+    /// The name of the GLSL function unpacking a value, defined by
+    /// [`GLSL_UNPACK`](Self::GLSL_UNPACK).
+    const GLSL_UNPACK_FN: &'static str;
+    const GLSL_UNPACK: GlslLib;
+
+    fn pack(value: Self::Unpacked) -> Self;
+
+    fn unpack(self) -> Self::Unpacked;
+}
+
+/// Convert `value` to the bits of the closest half precision float.
+///
+/// Values out of the half precision range become infinities, and NaNs stay
+/// NaNs.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    // round the dropped bits to the nearest, ties to even
+    let round = |value: u32, shift: u32| {
+        let rem = value & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let truncated = value >> shift;
+        truncated + (rem > halfway || (rem == halfway && truncated & 1 == 1)) as u32
+    };
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // subnormal, with the implicit leading bit made explicit
+        let shift = (14 - exponent) as u32;
+        sign | round(mantissa | 0x80_0000, shift) as u16
+    } else {
+        // a carry out of the mantissa correctly bumps the exponent
+        let rounded = round(((exponent as u32) << 23) | mantissa, 13);
+        sign | rounded as u16
+    }
+}
+
+/// Convert the bits of a half precision float to the equal `f32`.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+
+    match exponent {
+        0 => {
+            let value = mantissa as f32 / (1 << 24) as f32;
+            if sign != 0 { -value } else { value }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+fn pack_half2x16(x: f32, y: f32) -> u32 {
+    f32_to_f16(x) as u32 | ((f32_to_f16(y) as u32) << 16)
+}
+
+fn unpack_half2x16(v: u32) -> (f32, f32) {
+    (f16_to_f32(v as u16), f16_to_f32((v >> 16) as u16))
+}
+
+/// Two half precision floats, unpacked to a `vec2`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Half2(pub u32);
+
+impl PackedFormat for Half2 {
+    type Unpacked = Vec2;
+
+    const GLSL_TYPE: &'static str = "uint";
+    const GLSL_UNPACKED: &'static str = "vec2";
+    const GLSL_UNPACK_FN: &'static str = "unpack_half2";
+    const GLSL_UNPACK: GlslLib = crate::shader_glsl_lib! {
+        vec2 unpack_half2 [ v: uint ] => "
+            return unpackHalf2x16(v);
+        "
+    };
+
+    fn pack(value: Vec2) -> Self {
+        Self(pack_half2x16(value.x, value.y))
+    }
+
+    fn unpack(self) -> Vec2 {
+        let (x, y) = unpack_half2x16(self.0);
+        Vec2::new(x, y)
+    }
+}
+
+/// Four half precision floats, unpacked to a `vec4`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Half4(pub [u32; 2]);
+
+impl PackedFormat for Half4 {
+    type Unpacked = Vec4;
+
+    const GLSL_TYPE: &'static str = "uvec2";
+    const GLSL_UNPACKED: &'static str = "vec4";
+    const GLSL_UNPACK_FN: &'static str = "unpack_half4";
+    const GLSL_UNPACK: GlslLib = crate::shader_glsl_lib! {
+        vec4 unpack_half4 [ v: uvec2 ] => "
+            return vec4(unpackHalf2x16(v.x), unpackHalf2x16(v.y));
+        "
+    };
+
+    fn pack(value: Vec4) -> Self {
+        Self([
+            pack_half2x16(value.x, value.y),
+            pack_half2x16(value.z, value.w),
+        ])
+    }
+
+    fn unpack(self) -> Vec4 {
+        let (x, y) = unpack_half2x16(self.0[0]);
+        let (z, w) = unpack_half2x16(self.0[1]);
+        Vec4::new(x, y, z, w)
+    }
+}
+
+/// Four unsigned normalized 8 bit values, e.g. a color, unpacked to a `vec4`
+/// in the range (0, 1).
+///
+/// Packed values are clamped to the range (0, 1).
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Unorm8x4(pub u32);
+
+impl PackedFormat for Unorm8x4 {
+    type Unpacked = Vec4;
+
+    const GLSL_TYPE: &'static str = "uint";
+    const GLSL_UNPACKED: &'static str = "vec4";
+    const GLSL_UNPACK_FN: &'static str = "unpack_unorm8x4";
+    const GLSL_UNPACK: GlslLib = crate::shader_glsl_lib! {
+        vec4 unpack_unorm8x4 [ v: uint ] => "
+            return unpackUnorm4x8(v);
+        "
+    };
+
+    fn pack(value: Vec4) -> Self {
+        let bytes = (value.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+            .round()
+            .to_array()
+            .map(|c| c as u8);
+        Self(u32::from_le_bytes(bytes))
+    }
+
+    fn unpack(self) -> Vec4 {
+        Vec4::from_array(self.0.to_le_bytes().map(|c| c as f32 / 255.0))
+    }
+}
+
+/// Pack `src` into `dst`.
+///
+/// # Returns
+/// The amount of packed elements, i.e. the minimum length between `src` and
+/// `dst`.
+pub fn pack<P: PackedFormat>(src: &[P::Unpacked], dst: &mut [P]) -> usize {
+    let len = src.len().min(dst.len());
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = P::pack(*s);
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_and_unpack_formats() {
+        for value in [0.0, 1.0, -2.5, 0.1, 65504.0] {
+            let half = f32_to_f16(value);
+            assert!((f16_to_f32(half) - value).abs() <= value.abs() / 1024.0);
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 1);
+        assert_eq!(f16_to_f32(1), 2f32.powi(-24));
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        // 1 + 2^-11 is halfway between 1 and the next half, ties to even
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+
+        let scale = Vec4::new(0.5, 1.25, 3.0, 0.0);
+        assert_eq!(Half4::pack(scale).unpack(), scale);
+
+        let color = Unorm8x4::pack(Vec4::new(1.0, 0.0, 2.0, 0.5));
+        assert_eq!(color.0 & 0xff, 255);
+        assert_eq!(color.unpack().z, 1.0);
+
+        let mut dst = [Half2::default(); 2];
+        assert_eq!(pack(&[Vec2::ONE; 3], &mut dst), 2);
+        assert_eq!(dst[1].unpack(), Vec2::ONE);
+    }
+}
@@ -1,6 +1,17 @@
-use std::cell::UnsafeCell;
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::render::buffer::{InitStrategy, View, ViewMut, assert_tb_section, layout::Layout};
+use crate::render::buffer::{
+    InitStrategy, UploadMode, View, ViewMut, assert_tb_section, bandwidth,
+    blit::{self, BlitError},
+    flush::{self, MappingMode, WrittenRange},
+    layout::{Layout, Partition},
+    overflow::OverflowReport,
+    packed::{self, PackedFormat},
+};
 
 macro_rules! assert_partition {
     ($pt:expr, $pi:expr) => {
@@ -19,7 +30,10 @@ macro_rules! assert_partition {
 /// (contiguous memory blocks of data of the same type).
 ///
 /// # OpenGL Representation
-/// The GPU buffers are coherent persistent copy-write buffers. It includes
+/// The GPU buffers are coherent persistent copy-write buffers, explicitly
+/// flushed non-coherent ones in [`UploadMode::ExplicitFlush`], or written in
+/// system memory and uploaded in [`UploadMode::Orphaning`], see
+/// [`PartitionedTriBuffer::flush_section`]. It includes
 /// a convenience function to bind each partition of the buffer as an SSBO
 /// ([`PartitionedTriBuffer::bind_shader_storage`]).
 ///
@@ -61,6 +75,16 @@ macro_rules! assert_partition {
 /// to verify that the type in the given data corresponds to the same type of
 /// the data present on the GPU buffers.
 ///
+/// # Growing
+/// Partition sizes are fixed by the [`Layout`] of the buffer, and blits
+/// exceeding them are clamped. The buffer can be reallocated with a larger
+/// layout through [`grow`](PartitionedTriBuffer::grow), keeping its
+/// contents, or, with [`with_auto_grow`](PartitionedTriBuffer::with_auto_grow),
+/// it records the capacity required by overflowing blits and grows to fit
+/// them in [`grow_to_fit`](PartitionedTriBuffer::grow_to_fit), e.g. on
+/// every frame from an
+/// [exclusive hook](crate::render::stage::CrossHooks::add_exclusive).
+///
 /// # Synchronisation
 /// [`PartitionedTriBuffer`] can operate over cross-boundary synchronisation
 /// coordination of [`Boundary`] and [`Cross`] over its
@@ -77,6 +101,23 @@ pub struct PartitionedTriBuffer<const PARTS: usize> {
     layout: Layout<PARTS>,
     ptr: *mut u8,
     lengths: [[UnsafeCell<u32>; PARTS]; 3],
+    overflow: [OverflowReport; PARTS],
+    section_overflow: OverflowReport,
+    /// The names of the partitions in [`bandwidth`] reports.
+    labels: [Option<&'static str>; PARTS],
+
+    mode: UploadMode,
+    /// Owns the system memory of all sections in [`UploadMode::Orphaning`],
+    /// which is only accessed through `ptr`.
+    _staging: Option<Box<[MaybeUninit<u8>]>>,
+    /// The bytes written to each section since it was last flushed, from the
+    /// start of the buffer.
+    written: [WrittenRange; 3],
+
+    auto_grow: bool,
+    /// The bytes from the start of each partition required by the largest
+    /// overflowing blit, only tracked with `auto_grow`.
+    required: [AtomicUsize; PARTS],
 }
 
 impl<const PARTS: usize> Default for PartitionedTriBuffer<PARTS> {
@@ -87,6 +128,14 @@ impl<const PARTS: usize> Default for PartitionedTriBuffer<PARTS> {
             layout: Default::default(),
             ptr: Default::default(),
             lengths,
+            overflow: std::array::from_fn(|_| Default::default()),
+            section_overflow: Default::default(),
+            labels: [None; PARTS],
+            mode: Default::default(),
+            _staging: None,
+            written: Default::default(),
+            auto_grow: false,
+            required: std::array::from_fn(|_| AtomicUsize::new(0)),
         }
     }
 }
@@ -95,18 +144,117 @@ unsafe impl<const PARTS: usize> Sync for PartitionedTriBuffer<PARTS> {}
 unsafe impl<const PARTS: usize> Send for PartitionedTriBuffer<PARTS> {}
 
 impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
+    /// Create a buffer with the given `layout` per section, in the current
+    /// [`UploadMode`].
+    ///
+    /// See [`with_mapping`](Self::with_mapping) to choose the mapping of a
+    /// single buffer.
     pub fn new(layout: Layout<PARTS>) -> Self {
+        Self::with_mode(layout, UploadMode::current())
+    }
+
+    /// Create a buffer with the given `layout` per section, persistently
+    /// mapped in the given `mapping` mode.
+    ///
+    /// [`MappingMode::NonCoherent`] maps the buffer without
+    /// `MAP_COHERENT_BIT`, and flushes the ranges written to a section with
+    /// `glFlushMappedNamedBufferRange` in [`flush_section`](Self::flush_section),
+    /// which is faster on drivers with slow coherent mappings.
+    ///
+    /// Falls back to [`UploadMode::Orphaning`] like [`with_mode`](Self::with_mode).
+    pub fn with_mapping(layout: Layout<PARTS>, mapping: MappingMode) -> Self {
+        Self::with_mode(layout, mapping.upload_mode())
+    }
+
+    /// Create a buffer with the given `layout` per section, in the given
+    /// `mode`, or in [`UploadMode::Orphaning`] if the GL context does not
+    /// support it, see [`UploadMode::supported`].
+    ///
+    /// In [`UploadMode::Orphaning`], the sections are written in system
+    /// memory, and the range written to a section is uploaded with
+    /// `glBufferSubData` when it is flushed, see [`orphan`](super::orphan).
+    pub fn with_mode(layout: Layout<PARTS>, mode: UploadMode) -> Self {
+        let mode = mode.supported();
+
         let mut gl_obj = 0;
         let section_length = layout.len();
         let total_length = (section_length * 3) as isize;
+        let mut staging = None;
 
-        let ptr = unsafe {
+        unsafe {
             janus::gl::GenBuffers(1, &mut gl_obj);
             janus::gl::BindBuffer(janus::gl::COPY_WRITE_BUFFER, gl_obj);
+        }
+        let ptr = if mode == UploadMode::Orphaning {
+            let memory: &mut Box<[MaybeUninit<u8>]> =
+                staging.insert(Box::new_zeroed_slice(section_length * 3));
+            unsafe {
+                janus::gl::BufferData(
+                    janus::gl::COPY_WRITE_BUFFER,
+                    total_length,
+                    memory.as_ptr() as *const _,
+                    janus::gl::DYNAMIC_DRAW,
+                );
+            }
+            memory.as_mut_ptr() as *mut u8
+        } else {
+            Self::map_storage(total_length, mode)
+        };
+
+        let lengths = std::array::from_fn(|_| std::array::from_fn(|_| UnsafeCell::new(0)));
+        Self {
+            gl_obj,
+            layout,
+            ptr,
+            lengths,
+            overflow: std::array::from_fn(|_| Default::default()),
+            section_overflow: Default::default(),
+            labels: [None; PARTS],
+            mode,
+            _staging: staging,
+            written: Default::default(),
+            auto_grow: false,
+            required: std::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
 
-            let flags = janus::gl::MAP_WRITE_BIT
-                | janus::gl::MAP_COHERENT_BIT
-                | janus::gl::MAP_PERSISTENT_BIT;
+    /// Create a buffer with the given `layout` per section, written in
+    /// system memory without a GL buffer, e.g. to measure blits without a
+    /// GL context.
+    ///
+    /// The sections can be written and read back, but must be neither
+    /// flushed nor bound.
+    #[cfg(feature = "bench")]
+    pub fn detached(layout: Layout<PARTS>) -> Self {
+        let mut staging: Box<[MaybeUninit<u8>]> = Box::new_zeroed_slice(layout.len() * 3);
+        Self {
+            gl_obj: 0,
+            layout,
+            ptr: staging.as_mut_ptr() as *mut u8,
+            lengths: std::array::from_fn(|_| std::array::from_fn(|_| UnsafeCell::new(0))),
+            overflow: std::array::from_fn(|_| Default::default()),
+            section_overflow: Default::default(),
+            labels: [None; PARTS],
+            mode: UploadMode::Orphaning,
+            _staging: Some(staging),
+            written: Default::default(),
+            auto_grow: false,
+            required: std::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+
+    /// Allocate the immutable storage of the buffer bound to
+    /// `GL_COPY_WRITE_BUFFER`, and map it persistently.
+    fn map_storage(total_length: isize, mode: UploadMode) -> *mut u8 {
+        unsafe {
+            let flags = janus::gl::MAP_WRITE_BIT | janus::gl::MAP_PERSISTENT_BIT;
+            let (flags, map_flags) = match mode {
+                UploadMode::ExplicitFlush => (flags, flags | janus::gl::MAP_FLUSH_EXPLICIT_BIT),
+                _ => (
+                    flags | janus::gl::MAP_COHERENT_BIT,
+                    flags | janus::gl::MAP_COHERENT_BIT,
+                ),
+            };
             janus::gl::BufferStorage(
                 janus::gl::COPY_WRITE_BUFFER,
                 total_length,
@@ -114,15 +262,211 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
                 flags | janus::gl::DYNAMIC_STORAGE_BIT,
             );
 
-            janus::gl::MapBufferRange(janus::gl::COPY_WRITE_BUFFER, 0, total_length, flags)
-        } as *mut u8;
+            janus::gl::MapBufferRange(janus::gl::COPY_WRITE_BUFFER, 0, total_length, map_flags)
+                as *mut u8
+        }
+    }
 
-        let lengths = std::array::from_fn(|_| std::array::from_fn(|_| UnsafeCell::new(0)));
-        Self {
-            gl_obj,
-            layout,
-            ptr,
-            lengths,
+    /// Record the capacity required by blits exceeding a partition, so that
+    /// the buffer can grow to fit them with [`grow_to_fit`](Self::grow_to_fit).
+    ///
+    /// Exceeding elements are still dropped and reported until the buffer
+    /// grows.
+    pub fn with_auto_grow(mut self) -> Self {
+        self.auto_grow = true;
+        self
+    }
+
+    pub fn auto_grows(&self) -> bool {
+        self.auto_grow
+    }
+
+    /// Replace the storage of the buffer with a larger one of the given
+    /// `layout`, copying the contents and lengths of every partition of each
+    /// section with `glCopyNamedBufferSubData`.
+    ///
+    /// This waits for the copies to complete, stalling the pipeline, so it
+    /// should only be called rarely, e.g. when the amount of entities
+    /// outgrows the buffer.
+    ///
+    /// Requires exclusive access to the buffer and a GL context: frame data
+    /// shared across the boundary grows on the render thread, in an
+    /// [exclusive hook](crate::render::stage::CrossHooks::add_exclusive).
+    /// Draws still reading from the previous storage are unaffected, as it is
+    /// only deleted once they complete.
+    ///
+    /// # Panics
+    /// If any partition of `layout` is shorter than in the current layout.
+    pub fn grow(&mut self, layout: Layout<PARTS>) {
+        for part in 0..PARTS {
+            let (from, to) = (self.layout.length_at(part), layout.length_at(part));
+            assert!(
+                to >= from,
+                "cannot shrink partition {part} of buffer {} from {from} to {to} bytes",
+                self.gl_obj
+            );
+        }
+        for section in 0..3 {
+            self.flush_section(section);
+        }
+
+        let mut grown = Self::with_mode(layout, self.mode);
+        grown.auto_grow = self.auto_grow;
+        grown.labels = self.labels;
+
+        let (from, to) = (&self.layout, &grown.layout);
+        for section in 0..3 {
+            for part in 0..PARTS {
+                let length = from.length_at(part);
+                let src = section * from.len() + from.offset_at(part);
+                let dst = section * to.len() + to.offset_at(part);
+                if length > 0 && self.mode == UploadMode::Orphaning {
+                    // the system memory is the source of truth, uploaded below
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(self.ptr.add(src), grown.ptr.add(dst), length)
+                    };
+                    grown.mark_written(section, dst, length);
+                } else if length > 0 {
+                    unsafe {
+                        janus::gl::CopyNamedBufferSubData(
+                            self.gl_obj,
+                            grown.gl_obj,
+                            src as isize,
+                            dst as isize,
+                            length as isize,
+                        );
+                    }
+                }
+                *grown.lengths[section][part].get_mut() = *self.lengths[section][part].get_mut();
+            }
+        }
+        if self.mode == UploadMode::Orphaning {
+            for section in 0..3 {
+                grown.flush_section(section);
+            }
+        } else {
+            // the copies write to the new mapping asynchronously, they must be
+            // complete before the CPU writes to it
+            unsafe { janus::gl::Finish() };
+        }
+
+        use tracing::Level;
+        tracing::event!(
+            name: "buffer.grow",
+            Level::INFO,
+            "grew partitioned buffer {} of {} bytes into buffer {} of {} bytes",
+            self.gl_obj,
+            from.len() * 3,
+            grown.gl_obj,
+            to.len() * 3
+        );
+        *self = grown;
+    }
+
+    /// The layout fitting every blit recorded since the buffer was created or
+    /// last grown, with the length of each overflowing partition doubled
+    /// until it fits.
+    ///
+    /// # Returns
+    /// `None` if the buffer does not [auto-grow](Self::with_auto_grow), or no
+    /// blit exceeded its partition.
+    pub fn required_layout(&self) -> Option<Layout<PARTS>> {
+        if !self.auto_grow {
+            return None;
+        }
+
+        let mut layout = None;
+        for part in 0..PARTS {
+            let required = self.required[part].load(Ordering::Relaxed);
+            let mut length = self.layout.length_at(part);
+            if required <= length || length == 0 {
+                continue;
+            }
+            while length < required {
+                length *= 2;
+            }
+            let current = layout.unwrap_or_else(|| self.layout.clone());
+            layout = Some(current.resize_partition(part, length));
+        }
+        layout
+    }
+
+    /// [`grow`](Self::grow) the buffer to the [`required_layout`](Self::required_layout),
+    /// if any.
+    ///
+    /// Requires exclusive access to the buffer, see [`grow`](Self::grow).
+    ///
+    /// # Returns
+    /// Whether the buffer grew.
+    pub fn grow_to_fit(&mut self) -> bool {
+        match self.required_layout() {
+            Some(layout) => {
+                self.grow(layout);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record a blit requiring `bytes` from the start of `partition`.
+    #[inline]
+    fn require(&self, partition: usize, bytes: usize) {
+        if self.auto_grow && bytes > self.layout.length_at(partition) {
+            self.required[partition].fetch_max(bytes, Ordering::Relaxed);
+        }
+    }
+
+    pub fn mode(&self) -> UploadMode {
+        self.mode
+    }
+
+    /// The mapping of the buffer, `None` in [`UploadMode::Orphaning`].
+    pub fn mapping(&self) -> Option<MappingMode> {
+        MappingMode::of(self.mode)
+    }
+
+    /// Flush the range of a `section` written since it was last flushed, so
+    /// it is visible to the GPU.
+    ///
+    /// In [`UploadMode::Orphaning`], the range is uploaded from system memory.
+    /// In [`UploadMode::ExplicitFlush`], the range of the mapping is flushed.
+    /// Coherent persistent mappings do not need to be flushed, and this does
+    /// nothing. It is called by the `bind_shader_storage`
+    /// functions, but must be called explicitly before a section is otherwise
+    /// read by the GPU.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn flush_section(&self, section: usize) {
+        assert_tb_section!(section);
+        if self.mode == UploadMode::Persistent {
+            return;
+        }
+        let Some(written) = self.written[section].take() else {
+            return;
+        };
+
+        if self.mode == UploadMode::ExplicitFlush {
+            flush::flush_range(self.gl_obj, written);
+            return;
+        }
+        unsafe {
+            janus::gl::BindBuffer(janus::gl::COPY_WRITE_BUFFER, self.gl_obj);
+            janus::gl::BufferSubData(
+                janus::gl::COPY_WRITE_BUFFER,
+                written.start as isize,
+                written.len() as isize,
+                self.ptr.add(written.start) as *const _,
+            );
+        }
+    }
+
+    /// Record a write of `len` bytes at the byte `offset` from the start of
+    /// the buffer.
+    #[inline]
+    fn mark_written(&self, section: usize, offset: usize, len: usize) {
+        if self.mode != UploadMode::Persistent {
+            self.written[section].record(offset..offset + len);
         }
     }
 
@@ -137,6 +481,14 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         let offset = self.layout.offset_at(partition);
 
         match strategy {
+            InitStrategy::Zero if self.mode == UploadMode::Orphaning => {
+                for i in 0..3 {
+                    let section_offset = self.layout.len() * i + offset;
+                    unsafe { std::ptr::write_bytes(self.ptr.add(section_offset), 0, len) };
+                    self.mark_written(i, section_offset, len);
+                    self.flush_section(i);
+                }
+            }
             InitStrategy::Zero => {
                 for i in 0..3 {
                     let section_offset = (self.layout.len() * i) as isize;
@@ -164,6 +516,8 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
                             std::ptr::write(ptr.add(i), func());
                         }
                     }
+                    self.mark_written(i, section_offset, len * size_of::<T>());
+                    self.flush_section(i);
                 }
             }
         }
@@ -173,6 +527,12 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         &self.layout
     }
 
+    /// Name the partitions of the buffer in [`bandwidth`] reports, e.g. with
+    /// the `NAMES` generated by [`layout_buffer!`](crate::layout_buffer).
+    pub fn label_partitions(&mut self, names: [&'static str; PARTS]) {
+        self.labels = names.map(Some);
+    }
+
     /// Binds a single partition of buffered data of `section` to the GPU's SSBOs.
     ///
     /// The data will be bound to the SSBO specified by the given index
@@ -192,6 +552,7 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
     ) {
         assert_tb_section!(section);
         assert_partition!(PARTS, partition);
+        self.flush_section(section);
 
         let binding = ssbo_index
             .or_else(|| self.layout.ssbo_of(partition))
@@ -253,10 +614,17 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
     ///
     /// Also see [PartitionedTriBuffer::blit_part].
     ///
+    /// If the length of `data` exceeds the length of the section, it will be
+    /// automatically clamped and any exceeding bytes will be ignored.
+    /// Ignored bytes are reported, see [`overflow`](super::overflow).
+    ///
+    /// # Returns
+    /// The amount of bytes written.
+    ///
     /// # Panics
     /// * If `section` is not a value within the range (0, 2).
     /// * If `offset` is greater than the length of the section.
-    pub fn blit_section(&self, section: usize, data: &[u8], offset: usize) {
+    pub fn blit_section(&self, section: usize, data: &[u8], offset: usize) -> usize {
         assert_tb_section!(section);
 
         let src = data.as_ptr();
@@ -270,10 +638,50 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         let avail = section_len - offset;
         let data_len = avail.min(data.len());
         let offset = (section * section_len) + offset;
+        self.section_overflow
+            .record(self.gl_obj, None, data.len(), data_len);
+        bandwidth::record(self.gl_obj, None, None, data_len);
+        self.mark_written(section, offset, data_len);
 
         unsafe {
             std::ptr::copy_nonoverlapping(src, self.ptr.add(offset), data_len);
         }
+        data_len
+    }
+
+    /// Copy the given `data` in a `section` of the storage buffer at the
+    /// given byte `offset`, updating only that range.
+    ///
+    /// This is [`blit_section`](Self::blit_section) with the arguments in the
+    /// order of [`blit_part_at`](Self::blit_part_at): section blits never
+    /// change the lengths of the partitions.
+    ///
+    /// # Returns
+    /// The amount of bytes written.
+    ///
+    /// # Panics
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If `offset` is greater than the length of the section.
+    pub fn blit_section_at(&self, section: usize, offset: usize, data: &[u8]) -> usize {
+        self.blit_section(section, data, offset)
+    }
+
+    /// Copy the given `data` in a `section` of the storage buffer at a given
+    /// byte `offset`, like [`blit_section`](Self::blit_section), but only if
+    /// all of it fits.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn try_blit_section(
+        &self,
+        section: usize,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<(), BlitError> {
+        assert_tb_section!(section);
+        blit::check(offset, data.len(), self.layout.len())?;
+        self.blit_section(section, data, offset);
+        Ok(())
     }
 
     /// Get an immutable view to a `section` of the triple buffer.
@@ -335,6 +743,7 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
 
         let length = self.layout.len();
         let offset = section * length;
+        self.mark_written(section, offset, length);
         unsafe {
             let slice = std::slice::from_raw_parts_mut(self.ptr.add(offset), length);
             ViewMut {
@@ -429,6 +838,7 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         let offset = self.layout.offset_at(partition);
         let cap = self.layout.length_at(partition) / size_of::<T>();
         let len = self.length(section, partition);
+        self.mark_written(section, base_offset + offset, cap * size_of::<T>());
 
         unsafe {
             let ptr = self.ptr.add(base_offset + offset) as *mut T;
@@ -442,11 +852,69 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         }
     }
 
+    /// Get an immutable view to a `partition` of a `section` of the buffer,
+    /// typed by its [`Partition`] marker, see [`view_part`](Self::view_part).
+    ///
+    /// # Panic
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If the layout of the buffer was not created from the descriptor `L`
+    ///   of the marker, see [`Layout::with_tag`].
+    pub fn view_part_typed<L: 'static, T: Sized, const INDEX: usize>(
+        &self,
+        section: usize,
+        partition: Partition<L, T, INDEX>,
+    ) -> View<'_, T> {
+        self.assert_tagged(partition);
+        // SAFETY: the layout is tagged with `L`, whose partition `INDEX`
+        // stores elements of type `T`.
+        unsafe { self.view_part(section, INDEX) }
+    }
+
+    /// Copy the given `data` in a `partition` of a `section` of the buffer,
+    /// typed by its [`Partition`] marker, at the `offset` of the given amount
+    /// of elements, see [`blit_part`](Self::blit_part).
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
+    /// # Panic
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If the layout of the buffer was not created from the descriptor `L`
+    ///   of the marker, see [`Layout::with_tag`].
+    /// * If `offset` is greater than the capacity of the partition.
+    pub fn blit_part_typed<L: 'static, T: Sized + Clone + Copy, const INDEX: usize>(
+        &self,
+        section: usize,
+        partition: Partition<L, T, INDEX>,
+        data: &[T],
+        offset: usize,
+    ) -> usize {
+        self.assert_tagged(partition);
+        // SAFETY: the layout is tagged with `L`, whose partition `INDEX`
+        // stores elements of type `T`.
+        unsafe { self.blit_part(section, INDEX, data, offset * size_of::<T>()) }
+    }
+
+    fn assert_tagged<L: 'static, T, const INDEX: usize>(&self, partition: Partition<L, T, INDEX>) {
+        assert!(
+            self.layout.is_tagged::<L>(),
+            "attempted to access {partition:?} of buffer {}, whose layout was not created from its descriptor",
+            self.gl_obj
+        );
+    }
+
     /// Copy the given `data` in a `partition` of a `section` of the buffer at
     /// the given bytes `offset`.
     ///
     /// A `partition` represents a contiguous stream of data of the same type.
     ///
+    /// If the length of `data` exceeds the capacity of the partition, it will
+    /// be automatically clamped and any exceeding elements will be ignored.
+    /// Ignored elements are reported, see [`overflow`](super::overflow).
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
     /// # Safety
     /// The type parameter `T` cannot be verified to be the actual type of the
     /// data in this partition, the caller must ensure this is always the case.
@@ -462,7 +930,7 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         partition: usize,
         data: &[T],
         offset: usize,
-    ) {
+    ) -> usize {
         assert_tb_section!(section);
         assert_partition!(PARTS, partition);
 
@@ -475,15 +943,25 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
             "attempted to blit at offset {offset} with partition length {partition_len}"
         );
 
+        let data_bytes = data.len() * size_of::<T>();
+        self.require(partition, offset + data_bytes);
+
         let avail = partition_len - offset;
         let offset = self.layout.offset_at(partition) + offset;
-        let data_bytes = data.len() * size_of::<T>();
 
         // safe length of data, in bytes
         let data_len = avail.min(data_bytes);
 
         let total_len = data_len / size_of::<T>();
         self.set_length(section, partition, total_len as u32);
+        self.overflow[partition].record(self.gl_obj, Some(partition), data.len(), total_len);
+        bandwidth::record(
+            self.gl_obj,
+            Some(partition),
+            self.labels[partition],
+            total_len * size_of::<T>(),
+        );
+        self.mark_written(section, base_offset + offset, total_len * size_of::<T>());
 
         // SAFETY: we assert the section and partition are valid within this
         // buffer's layout. The buffer's layout, in turn, guarantees valid
@@ -493,8 +971,144 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         // corresponds to the same size of the type present on the GPU buffers.
         unsafe {
             let dst = self.ptr.add(base_offset + offset) as *mut T;
-            std::ptr::copy_nonoverlapping(src, dst, data_len / size_of::<T>());
+            std::ptr::copy_nonoverlapping(src, dst, total_len);
         }
+        total_len
+    }
+
+    /// Copy the given `data` in a `partition` of a `section` of the buffer at
+    /// the given element `offset`, updating only that range, e.g. the
+    /// positions of the entities which moved.
+    ///
+    /// Unlike [`blit_part`](Self::blit_part), the length of the partition
+    /// only grows to cover the written range, so that the elements before and
+    /// after it keep their contents. These were last written to this section,
+    /// not to the previously published one: with a triple buffer, each
+    /// section must be brought up to date with the ranges changed since it
+    /// was last written.
+    ///
+    /// Exceeding elements are clamped and reported, as in `blit_part`.
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
+    /// # Safety
+    /// The caller must ensure that `T` is the actual type of the data in this
+    /// partition, as with [`blit_part`](Self::blit_part).
+    ///
+    /// # Panic
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If `partition` is not a valid partition, i.e. it is greater than the
+    ///   `PARTS`constant type parameter.
+    /// * If `offset` is greater than the capacity of the partition.
+    pub unsafe fn blit_part_at<T: Sized + Clone + Copy>(
+        &self,
+        section: usize,
+        partition: usize,
+        offset: usize,
+        data: &[T],
+    ) -> usize {
+        let previous = self.length(section, partition);
+        // SAFETY: the caller guarantees the type of the partition.
+        let written = unsafe { self.blit_part(section, partition, data, offset * size_of::<T>()) };
+        self.set_length(section, partition, previous.max(offset + written) as u32);
+        written
+    }
+
+    /// Copy the given `data` in a `partition` of a `section` of the buffer at
+    /// the given byte `offset`, like [`blit_part`](Self::blit_part), but only
+    /// if all of it fits.
+    ///
+    /// The capacities of a [`BlitError`] are in elements of `T`.
+    ///
+    /// # Safety
+    /// The caller must ensure that `T` is the actual type of the data in this
+    /// partition, as with [`blit_part`](Self::blit_part).
+    ///
+    /// # Panic
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If `partition` is not a valid partition, i.e. it is greater than the
+    ///   `PARTS`constant type parameter.
+    pub unsafe fn try_blit_part<T: Sized + Clone + Copy>(
+        &self,
+        section: usize,
+        partition: usize,
+        data: &[T],
+        offset: usize,
+    ) -> Result<(), BlitError> {
+        assert_tb_section!(section);
+        assert_partition!(PARTS, partition);
+
+        let stride = size_of::<T>();
+        if !offset.is_multiple_of(stride) {
+            return Err(BlitError::Misaligned { offset, stride });
+        }
+        let capacity = self.layout.length_at(partition) / stride;
+        blit::check(offset / stride, data.len(), capacity)?;
+
+        // SAFETY: the caller guarantees the type of the partition.
+        unsafe { self.blit_part(section, partition, data, offset) };
+        Ok(())
+    }
+
+    /// Convert the given `data` to the packed format `P` while copying it in
+    /// a `partition` of a `section` of the buffer at the given bytes `offset`,
+    /// like [`blit_part`](Self::blit_part).
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
+    /// # Safety
+    /// The caller must ensure that `P` is the actual type of the data in this
+    /// partition, as with [`blit_part`](Self::blit_part).
+    ///
+    /// # Panic
+    /// * If `section` is not a value within the range (0, 2).
+    /// * If `partition` is not a valid partition, i.e. it is greater than the
+    ///   `PARTS`constant type parameter.
+    /// * If `offset` is greater than the length of the partition.
+    pub unsafe fn blit_part_packed<P: PackedFormat>(
+        &self,
+        section: usize,
+        partition: usize,
+        data: &[P::Unpacked],
+        offset: usize,
+    ) -> usize {
+        assert_tb_section!(section);
+        assert_partition!(PARTS, partition);
+
+        let base_offset = section * self.layout.len();
+
+        let partition_len = self.layout.length_at(partition);
+        assert!(
+            partition_len > offset,
+            "attempted to blit at offset {offset} with partition length {partition_len}"
+        );
+
+        self.require(partition, offset + data.len() * size_of::<P>());
+
+        let avail = (partition_len - offset) / size_of::<P>();
+        let offset = self.layout.offset_at(partition) + offset;
+        let total_len = avail.min(data.len());
+
+        self.set_length(section, partition, total_len as u32);
+        self.overflow[partition].record(self.gl_obj, Some(partition), data.len(), total_len);
+        bandwidth::record(
+            self.gl_obj,
+            Some(partition),
+            self.labels[partition],
+            total_len * size_of::<P>(),
+        );
+        self.mark_written(section, base_offset + offset, total_len * size_of::<P>());
+
+        // SAFETY: as in `blit_part`, the destination range lies within the
+        // partition, and the caller guarantees its type.
+        unsafe {
+            let dst = self.ptr.add(base_offset + offset) as *mut P;
+            let dst = std::slice::from_raw_parts_mut(dst, total_len);
+            packed::pack(&data[..total_len], dst);
+        }
+        total_len
     }
 
     /// Copy the given `data` in a `partition` of a `section` of the buffer at
@@ -512,6 +1126,10 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
     /// It is, in most cases, not recommended and [`blit_part`] should be
     /// preferred if possible.
     ///
+    /// If the length of `data` exceeds the capacity of the partition, it will
+    /// be automatically clamped and any exceeding elements will be ignored.
+    /// Ignored elements are reported, see [`overflow`](super::overflow).
+    ///
     /// **Note**: to ensure correct memory offsets and lengths, the type
     /// described in the [`Layout`] of this buffer must correspond to the type
     /// present on the GPU.
@@ -550,6 +1168,9 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
     ///   `PARTS`constant type parameter.
     /// * If `offset` is greater than the length of the partition.
     ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
     /// [`blit_part`]: PartitionedTriBuffer::blit_part
     pub unsafe fn blit_part_padded<T: Sized + Clone + Copy>(
         &self,
@@ -558,11 +1179,10 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
         data: &[T],
         offset: usize,
         pad_len: usize,
-    ) {
+    ) -> usize {
         if pad_len == 0 {
             // SAFETY: invariants correspond to those of this function.
-            unsafe { self.blit_part(section, partition, data, offset) };
-            return;
+            return unsafe { self.blit_part(section, partition, data, offset) };
         }
 
         assert_tb_section!(section);
@@ -576,17 +1196,25 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
             "attempted to blit at offset {offset} with partition length {partition_len}"
         );
 
+        let data_bytes_padded = size_of::<T>() + pad_len;
+        let data_count = data.len();
+        self.require(partition, offset + data_count * data_bytes_padded);
+
         let avail = partition_len - offset;
         let offset = self.layout.offset_at(partition) + offset;
-
-        let data_bytes_padded = size_of::<T>() + pad_len;
         let avail_count = avail / data_bytes_padded;
-        let data_count = data.len();
 
         // safe total length of data, element count
         let data_len = avail_count.min(data_count);
-        let total_len = data_len / size_of::<T>();
-        self.set_length(section, partition, total_len as u32);
+        self.set_length(section, partition, data_len as u32);
+        self.overflow[partition].record(self.gl_obj, Some(partition), data_count, data_len);
+        bandwidth::record(
+            self.gl_obj,
+            Some(partition),
+            self.labels[partition],
+            data_len * data_bytes_padded,
+        );
+        self.mark_written(section, base_offset + offset, data_len * data_bytes_padded);
 
         // SAFETY: we assert the section and partition are valid within this
         // buffer's layout. The buffer's layout, in turn, guarantees valid
@@ -603,15 +1231,20 @@ impl<const PARTS: usize> PartitionedTriBuffer<PARTS> {
                 dst = dst.add(pad_len);
             }
         }
+        data_len
     }
 }
 
 impl<const PARTS: usize> Drop for PartitionedTriBuffer<PARTS> {
     fn drop(&mut self) {
-        unsafe {
-            janus::gl::BindBuffer(janus::gl::COPY_WRITE_BUFFER, self.gl_obj);
-            janus::gl::UnmapBuffer(janus::gl::COPY_WRITE_BUFFER);
-            janus::gl::DeleteBuffers(1, &self.gl_obj);
+        if self.gl_obj != 0 {
+            unsafe {
+                if self.mode != UploadMode::Orphaning {
+                    janus::gl::BindBuffer(janus::gl::COPY_WRITE_BUFFER, self.gl_obj);
+                    janus::gl::UnmapBuffer(janus::gl::COPY_WRITE_BUFFER);
+                }
+                janus::gl::DeleteBuffers(1, &self.gl_obj);
+            }
         }
         self.ptr = std::ptr::null_mut();
     }
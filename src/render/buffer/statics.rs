@@ -0,0 +1,283 @@
+//! Single-buffered storage for static entities.
+//!
+//! Entities that never move do not need to be triple buffered: their data is
+//! uploaded once to a [`StaticBuffer`] and bound alongside the per-frame
+//! partitions, while only [`Mobility::Dynamic`] entities are blitted every
+//! frame. In mostly-static scenes, this cuts most of the upload bandwidth.
+
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{render::NotSend, state::data::hash::Cell};
+
+/// Whether an entity moves, stored per entity in a column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Mobility {
+    #[default]
+    Dynamic,
+    /// The entity never moves, and its render data is uploaded once to a
+    /// [`StaticBuffer`].
+    Static,
+}
+
+impl Mobility {
+    pub const fn is_static(self) -> bool {
+        matches!(self, Self::Static)
+    }
+
+    /// Split the elements of a column `data` into `statics` and `dynamics`,
+    /// according to the `mobility` column of the same entities.
+    ///
+    /// The destination vectors are cleared first. Elements keep their order.
+    ///
+    /// # Panics
+    /// If `mobility` and `data` differ in length.
+    pub fn split<T: Copy>(
+        mobility: &[Mobility],
+        data: &[T],
+        statics: &mut Vec<T>,
+        dynamics: &mut Vec<T>,
+    ) {
+        assert_eq!(
+            mobility.len(),
+            data.len(),
+            "mobility and data columns must be of the same length"
+        );
+        statics.clear();
+        dynamics.clear();
+        for (mobility, &element) in mobility.iter().zip(data) {
+            match mobility {
+                Mobility::Static => statics.push(element),
+                Mobility::Dynamic => dynamics.push(element),
+            }
+        }
+    }
+}
+
+/// A single OpenGL buffer for data that rarely, if ever, changes.
+///
+/// Unlike [`TriBuffer`](super::TriBuffer), the buffer is not mapped: updates
+/// go through `glNamedBufferSubData`, which the driver synchronises with
+/// in-flight draws. This makes updates expensive, but static data is
+/// expected to be uploaded once.
+#[derive(Debug, Default)]
+pub struct StaticBuffer<T: Sized + Clone + Copy> {
+    gl_obj: u32,
+    /// Capacity in number of elements.
+    capacity: usize,
+    length: usize,
+
+    _marker: std::marker::PhantomData<T>,
+    _not_send: NotSend,
+}
+
+impl<T> StaticBuffer<T>
+where
+    T: Sized + Clone + Copy,
+{
+    /// Create a zeroed buffer of `capacity` elements.
+    ///
+    /// # Panics
+    /// If `capacity` is `0`, as GL buffers cannot be empty.
+    pub fn zeroed(capacity: usize) -> Self {
+        assert!(capacity > 0, "static buffers must have a capacity");
+        let mut gl_obj = 0;
+        unsafe {
+            janus::gl::CreateBuffers(1, &mut gl_obj);
+            janus::gl::NamedBufferStorage(
+                gl_obj,
+                (capacity * size_of::<T>()) as isize,
+                std::ptr::null(),
+                janus::gl::DYNAMIC_STORAGE_BIT,
+            );
+            janus::gl::ClearNamedBufferData(
+                gl_obj,
+                janus::gl::R32UI,
+                janus::gl::RED_INTEGER,
+                janus::gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+        }
+
+        Self {
+            gl_obj,
+            capacity,
+            length: 0,
+            _marker: std::marker::PhantomData,
+            _not_send: std::marker::PhantomData,
+        }
+    }
+
+    /// Create a buffer holding exactly `data`.
+    ///
+    /// # Panics
+    /// If `data` is empty, see [`zeroed`](Self::zeroed).
+    pub fn from_slice(data: &[T]) -> Self {
+        let mut buffer = Self::zeroed(data.len());
+        buffer.upload(data);
+        buffer
+    }
+
+    /// Replace the contents of the buffer with `data`.
+    ///
+    /// If the length of `data` exceeds the capacity of the buffer, exceeding
+    /// elements are ignored.
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    pub fn upload(&mut self, data: &[T]) -> usize {
+        self.length = 0;
+        let written = self.update(0, data);
+        self.length = written;
+        written
+    }
+
+    /// Overwrite the elements starting at `offset` with `data`, e.g. when a
+    /// static entity is edited.
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
+    /// # Panics
+    /// If `offset` is greater than the capacity of the buffer.
+    pub fn update(&mut self, offset: usize, data: &[T]) -> usize {
+        assert!(
+            offset <= self.capacity,
+            "attempted to update at offset {offset} with capacity {}",
+            self.capacity
+        );
+
+        let len = (self.capacity - offset).min(data.len());
+        super::bandwidth::record(self.gl_obj, None, None, len * size_of::<T>());
+        if len > 0 {
+            unsafe {
+                janus::gl::NamedBufferSubData(
+                    self.gl_obj,
+                    (offset * size_of::<T>()) as isize,
+                    (len * size_of::<T>()) as isize,
+                    data.as_ptr() as *const _,
+                );
+            }
+        }
+        self.length = self.length.max(offset + len);
+        len
+    }
+
+    /// Bind the whole buffer to the given `ssbo_index`.
+    pub fn bind_shader_storage(&self, ssbo_index: u32) {
+        unsafe {
+            janus::gl::BindBufferBase(janus::gl::SHADER_STORAGE_BUFFER, ssbo_index, self.gl_obj);
+        }
+    }
+
+    /// The amount of elements uploaded.
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<T> Drop for StaticBuffer<T>
+where
+    T: Sized + Clone + Copy,
+{
+    fn drop(&mut self) {
+        if self.gl_obj != 0 {
+            unsafe {
+                janus::gl::DeleteBuffers(1, &self.gl_obj);
+            }
+        }
+    }
+}
+
+/// Per-chunk GPU residency of streamed static data.
+///
+/// Each resident chunk owns a [`StaticBuffer`], uploaded once when the chunk
+/// is streamed in and freed when it is streamed out, e.g. by the
+/// `state::stream::ChunkStream` of the `spatial-hash` feature.
+#[derive(Debug, Default)]
+pub struct ChunkBuffers<T: Sized + Clone + Copy> {
+    chunks: HashMap<Cell, StaticBuffer<T>>,
+}
+
+impl<T> ChunkBuffers<T>
+where
+    T: Sized + Clone + Copy,
+{
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::default(),
+        }
+    }
+
+    /// Upload the `data` of `chunk`, replacing its buffer if already resident.
+    ///
+    /// Chunks without data hold no buffer.
+    pub fn load(&mut self, chunk: Cell, data: &[T]) {
+        if data.is_empty() {
+            self.unload(chunk);
+            return;
+        }
+        match self.chunks.get_mut(&chunk) {
+            Some(buffer) if buffer.capacity() >= data.len() => {
+                buffer.upload(data);
+            }
+            _ => {
+                self.chunks.insert(chunk, StaticBuffer::from_slice(data));
+            }
+        }
+    }
+
+    /// Free the buffer of `chunk`.
+    ///
+    /// # Returns
+    /// Whether the chunk was resident.
+    pub fn unload(&mut self, chunk: Cell) -> bool {
+        self.chunks.remove(&chunk).is_some()
+    }
+
+    pub fn get(&self, chunk: Cell) -> Option<&StaticBuffer<T>> {
+        self.chunks.get(&chunk)
+    }
+
+    pub fn is_resident(&self, chunk: Cell) -> bool {
+        self.chunks.contains_key(&chunk)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Cell, &StaticBuffer<T>)> {
+        self.chunks.iter().map(|(&chunk, buffer)| (chunk, buffer))
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_by_mobility() {
+        let mobility = [
+            Mobility::Static,
+            Mobility::Dynamic,
+            Mobility::Static,
+            Mobility::Dynamic,
+        ];
+        let (mut statics, mut dynamics) = (vec![9], Vec::new());
+        Mobility::split(&mobility, &[0, 1, 2, 3], &mut statics, &mut dynamics);
+        assert_eq!(statics, [0, 2]);
+        assert_eq!(dynamics, [1, 3]);
+    }
+}
@@ -0,0 +1,196 @@
+//! Validation of data about to be uploaded to the GPU, in debug builds.
+//!
+//! A single NaN in a position or rotation is enough to corrupt the transform
+//! of an instance, and a malformed draw command may discard the whole draw,
+//! both silently. [`validate`] scans the data before it is blitted and logs
+//! the slot of the first offending element, so the entity can be traced back
+//! to the simulation.
+//!
+//! Validation is compiled out of release builds: [`validate`] returns
+//! immediately without reading `data`.
+//!
+//! # Example
+//! ```rust,ignore
+//! validate::validate("transforms", &transforms);
+//! validate::validate("draw commands", &commands);
+//! storage.transforms.blit_section(section.as_index(), &transforms, 0);
+//! ```
+
+use glam::{Quat, Vec2, Vec3, Vec4};
+
+use crate::render::{
+    buffer::pack::PackedTransform,
+    capture::{self, CaptureTrigger},
+    command::{DrawArraysIndirectCommand, DrawElementsIndirectCommand},
+};
+
+/// The largest absolute coordinate considered valid.
+///
+/// Past this value, `f32` positions cannot represent sub-unit offsets, and
+/// such values are usually the result of a diverging simulation.
+pub const MAX_COORDINATE: f32 = 1.0e7;
+
+/// The tolerance on the squared length of rotation quaternions.
+const ROTATION_EPSILON: f32 = 1.0e-2;
+
+/// Why a value was rejected by [`Validate::invalid`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Invalid {
+    NaN,
+    Infinite,
+    /// The value is finite, but outside of the range the GPU data expects,
+    /// e.g. a coordinate past [`MAX_COORDINATE`] or a non-unit rotation.
+    OutOfRange,
+}
+
+impl std::fmt::Display for Invalid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NaN => write!(f, "NaN"),
+            Self::Infinite => write!(f, "infinite"),
+            Self::OutOfRange => write!(f, "out of range"),
+        }
+    }
+}
+
+/// GPU data that can be checked for invalid values.
+pub trait Validate {
+    /// Why the value is invalid, or `None` if it is valid.
+    fn invalid(&self) -> Option<Invalid>;
+}
+
+impl Validate for f32 {
+    fn invalid(&self) -> Option<Invalid> {
+        if self.is_nan() {
+            Some(Invalid::NaN)
+        } else if self.is_infinite() {
+            Some(Invalid::Infinite)
+        } else if self.abs() > MAX_COORDINATE {
+            Some(Invalid::OutOfRange)
+        } else {
+            None
+        }
+    }
+}
+
+impl<const N: usize> Validate for [f32; N] {
+    fn invalid(&self) -> Option<Invalid> {
+        self.iter().find_map(Validate::invalid)
+    }
+}
+
+impl Validate for Vec2 {
+    fn invalid(&self) -> Option<Invalid> {
+        self.to_array().invalid()
+    }
+}
+
+impl Validate for Vec3 {
+    fn invalid(&self) -> Option<Invalid> {
+        self.to_array().invalid()
+    }
+}
+
+impl Validate for Vec4 {
+    fn invalid(&self) -> Option<Invalid> {
+        self.to_array().invalid()
+    }
+}
+
+impl Validate for Quat {
+    fn invalid(&self) -> Option<Invalid> {
+        self.to_array().invalid().or_else(|| {
+            let unit = (self.length_squared() - 1.0).abs() <= ROTATION_EPSILON;
+            (!unit).then_some(Invalid::OutOfRange)
+        })
+    }
+}
+
+impl Validate for PackedTransform {
+    fn invalid(&self) -> Option<Invalid> {
+        self.position
+            .invalid()
+            .or_else(|| Quat::from_vec4(self.rotation).invalid())
+    }
+}
+
+impl Validate for DrawArraysIndirectCommand {
+    fn invalid(&self) -> Option<Invalid> {
+        let vertices = self.first_vertex.checked_add(self.count);
+        let instances = self.base_instance.checked_add(self.instance_count);
+        (vertices.is_none() || instances.is_none()).then_some(Invalid::OutOfRange)
+    }
+}
+
+impl Validate for DrawElementsIndirectCommand {
+    fn invalid(&self) -> Option<Invalid> {
+        let indices = self.first_index.checked_add(self.count);
+        let instances = self.base_instance.checked_add(self.instance_count);
+        let invalid = indices.is_none() || instances.is_none() || self.base_vertex < 0;
+        invalid.then_some(Invalid::OutOfRange)
+    }
+}
+
+/// Check every element of `data`, logging the slot of the first invalid
+/// element under the given `name`.
+///
+/// Does nothing in release builds.
+///
+/// # Returns
+/// The amount of invalid elements.
+pub fn validate<T: Validate + std::fmt::Debug>(name: &str, data: &[T]) -> usize {
+    if !cfg!(debug_assertions) {
+        return 0;
+    }
+
+    let mut first = None;
+    let mut invalid = 0;
+    for (slot, element) in data.iter().enumerate() {
+        if let Some(reason) = element.invalid() {
+            first.get_or_insert((slot, reason));
+            invalid += 1;
+        }
+    }
+
+    if let Some((slot, reason)) = first {
+        crate::state::stats::record_invalid(invalid);
+        capture::report_failure(CaptureTrigger::Validation);
+
+        use tracing::Level;
+        tracing::event!(
+            name: "buffer.validate",
+            Level::ERROR,
+            "{name}[{slot}] is {reason}: {:?} ({invalid} invalid elements out of {})",
+            data[slot],
+            data.len()
+        );
+    }
+    invalid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_invalid_elements() {
+        let positions = [Vec3::ZERO, Vec3::new(0.0, f32::NAN, 0.0), Vec3::X];
+        assert_eq!(validate("positions", &positions), 1);
+        assert_eq!(Vec3::splat(2.0e7).invalid(), Some(Invalid::OutOfRange));
+
+        let rotations = [Quat::IDENTITY, Quat::from_xyzw(0.0, 0.0, 0.0, 2.0)];
+        assert_eq!(rotations[1].invalid(), Some(Invalid::OutOfRange));
+        assert_eq!(
+            PackedTransform::new(Vec3::INFINITY, Quat::IDENTITY).invalid(),
+            Some(Invalid::Infinite)
+        );
+
+        let command = DrawArraysIndirectCommand {
+            count: 36,
+            instance_count: 1,
+            first_vertex: u32::MAX,
+            base_instance: 0,
+        };
+        assert_eq!(command.invalid(), Some(Invalid::OutOfRange));
+    }
+}
@@ -0,0 +1,225 @@
+//! Programmatic frame captures through the RenderDoc in-application API.
+//!
+//! With the `renderdoc` feature enabled and the application launched from
+//! (or injected by) RenderDoc, frames can be captured without reaching for
+//! the capture key:
+//!
+//! * [`request_capture`] captures the next rendered frame, and can be called
+//!   from any thread, e.g. from a console command or an input binding.
+//! * With [`set_capture_on_failure`], the first frame showing a GL error or
+//!   invalid GPU data (see [`validate`](super::buffer::validate)) is captured
+//!   automatically.
+//!
+//! Failures are only known once they happened: a GL error is reported by the
+//! [debug output](super::debug_output) once the driver raises it, or at the
+//! end of the frame without a debug context, and invalid data is detected
+//! before it is uploaded, so a requested capture would begin with the
+//! following frame. To capture exactly the broken frame,
+//! [`CaptureMode::Watch`] captures every frame, and discards the captures of
+//! the frames without failures. This is expensive, and only meant for
+//! hunting down rare failures.
+//!
+//! Without the `renderdoc` feature, or when RenderDoc is not attached, all
+//! requests are ignored.
+//!
+//! # Example
+//! ```rust,ignore
+//! capture::set_mode(CaptureMode::Watch);
+//! capture::set_capture_on_failure(true);
+//!
+//! // in the console command handler
+//! "capture" => capture::request_capture(),
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static CAPTURE_ON_FAILURE: AtomicBool = AtomicBool::new(false);
+/// The [`CaptureTrigger`] of the failures reported during the current frame,
+/// `0` if there were none.
+static FAILURE: AtomicU8 = AtomicU8::new(0);
+static MODE: AtomicU8 = AtomicU8::new(CaptureMode::OnRequest as u8);
+
+/// Why a frame was captured.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CaptureTrigger {
+    /// Requested through [`request_capture`].
+    Request = 1,
+    /// A GL error was raised during the frame.
+    GlError,
+    /// Invalid data was uploaded for the frame.
+    Validation,
+}
+
+impl CaptureTrigger {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::Request),
+            2 => Some(Self::GlError),
+            3 => Some(Self::Validation),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CaptureTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request => write!(f, "request"),
+            Self::GlError => write!(f, "gl error"),
+            Self::Validation => write!(f, "invalid gpu data"),
+        }
+    }
+}
+
+/// When frames are captured.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CaptureMode {
+    /// Frames are captured after a request or a failure.
+    #[default]
+    OnRequest,
+    /// Every frame is captured, and only kept if a failure was reported
+    /// during it, or a capture was requested.
+    Watch,
+}
+
+/// Set when frames are captured.
+pub fn set_mode(mode: CaptureMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn mode() -> CaptureMode {
+    match MODE.load(Ordering::Relaxed) {
+        0 => CaptureMode::OnRequest,
+        _ => CaptureMode::Watch,
+    }
+}
+
+/// Capture the next rendered frame.
+pub fn request_capture() {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Capture the first frame with a failure, see [`report_failure`].
+///
+/// Only one frame is captured automatically: call again to capture the next
+/// failure.
+pub fn set_capture_on_failure(enabled: bool) {
+    CAPTURE_ON_FAILURE.store(enabled, Ordering::Relaxed);
+}
+
+/// Report a failure in the current frame, capturing it if enabled with
+/// [`set_capture_on_failure`].
+pub fn report_failure(trigger: CaptureTrigger) {
+    if CAPTURE_ON_FAILURE.swap(false, Ordering::Relaxed) {
+        FAILURE.store(trigger as u8, Ordering::Relaxed);
+        if mode() == CaptureMode::OnRequest {
+            request_capture();
+        }
+    }
+}
+
+/// Begin capturing the frame about to be rendered, if requested.
+///
+/// Called by the [`Renderer`](super::Renderer) before the frame.
+pub(crate) fn begin_frame() {
+    let capture = match mode() {
+        CaptureMode::OnRequest => REQUESTED.swap(false, Ordering::Relaxed),
+        CaptureMode::Watch => true,
+    };
+    if capture {
+        api::start();
+    }
+}
+
+/// End the capture of the frame just rendered, if any.
+///
+/// Called by the [`Renderer`](super::Renderer) after the frame has been
+/// presented.
+pub(crate) fn end_frame() {
+    if !api::is_capturing() {
+        return;
+    }
+
+    let failure = CaptureTrigger::from_u8(FAILURE.swap(0, Ordering::Relaxed));
+    let requested = mode() == CaptureMode::Watch && REQUESTED.swap(false, Ordering::Relaxed);
+    let trigger = failure.or(requested.then_some(CaptureTrigger::Request));
+
+    match (mode(), trigger) {
+        (CaptureMode::Watch, None) => api::discard(),
+        (_, trigger) => {
+            api::end();
+
+            use tracing::Level;
+            tracing::event!(
+                name: "render.capture",
+                Level::INFO,
+                "captured frame ({})",
+                trigger.unwrap_or(CaptureTrigger::Request)
+            );
+        }
+    }
+}
+
+#[cfg(feature = "renderdoc")]
+mod api {
+    use std::cell::RefCell;
+
+    use renderdoc::{RenderDoc, V141};
+
+    thread_local! {
+        /// The API is loaded on the render thread on first use, `None` if
+        /// RenderDoc is not attached.
+        static RENDERDOC: RefCell<Option<Option<RenderDoc<V141>>>> = const { RefCell::new(None) };
+    }
+
+    fn with<R>(f: impl FnOnce(&mut RenderDoc<V141>) -> R) -> Option<R> {
+        RENDERDOC.with_borrow_mut(|renderdoc| {
+            let renderdoc = renderdoc.get_or_insert_with(|| match RenderDoc::new() {
+                Ok(renderdoc) => Some(renderdoc),
+                Err(err) => {
+                    use tracing::Level;
+                    tracing::event!(
+                        name: "render.capture",
+                        Level::WARN,
+                        "renderdoc is not available, captures are ignored: {err}"
+                    );
+                    None
+                }
+            });
+            renderdoc.as_mut().map(f)
+        })
+    }
+
+    // null device and window handles select the current context and window
+    pub(super) fn start() {
+        with(|rd| rd.start_frame_capture(std::ptr::null(), std::ptr::null()));
+    }
+
+    pub(super) fn end() {
+        with(|rd| rd.end_frame_capture(std::ptr::null(), std::ptr::null()));
+    }
+
+    pub(super) fn discard() {
+        with(|rd| rd.discard_frame_capture(std::ptr::null(), std::ptr::null()));
+    }
+
+    pub(super) fn is_capturing() -> bool {
+        with(|rd| rd.is_frame_capturing()).unwrap_or(false)
+    }
+}
+
+#[cfg(not(feature = "renderdoc"))]
+mod api {
+    pub(super) fn start() {}
+
+    pub(super) fn end() {}
+
+    pub(super) fn discard() {}
+
+    pub(super) fn is_capturing() -> bool {
+        false
+    }
+}
@@ -0,0 +1,161 @@
+//! How the frame is cleared before its stages run.
+//!
+//! The [`ClearPolicy`] of the [`Renderer`](super::Renderer) selects the
+//! buffers cleared at the start of each frame and the colour of the
+//! background. Tools drawing over the previous frame, e.g. accumulating
+//! debug views, can disable the colour clear, and applications redrawing a
+//! part of the screen can restrict the clear to a scissor rectangle.
+//!
+//! The depth buffer is cleared to the far plane of the
+//! [`DepthMode`](super::depth::DepthMode) of the renderer.
+//!
+//! # Example
+//! ```rust,ignore
+//! renderer.set_clear_policy(
+//!     ClearPolicy::default()
+//!         .with_color(Color::srgb(0.05, 0.05, 0.08, 1.0))
+//!         .with_scissor(Some([0, 0, 320, 200])),
+//! );
+//! ```
+
+use crate::render::{
+    color::{self, Color, ColorSpace},
+    depth::DepthMode,
+};
+
+/// The buffers cleared at the start of a frame, and their clear values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClearPolicy {
+    /// The background colour, in linear space.
+    pub color: Color,
+    pub clear_color: bool,
+    /// Clear the depth buffer, if the depth mode has one.
+    pub clear_depth: bool,
+    pub clear_stencil: bool,
+    pub stencil: i32,
+    /// Only clear the rectangle at `[x, y, width, height]` pixels from the
+    /// bottom left corner of the framebuffer.
+    pub scissor: Option<[i32; 4]>,
+}
+
+impl Default for ClearPolicy {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            clear_color: true,
+            clear_depth: true,
+            clear_stencil: false,
+            stencil: 0,
+            scissor: None,
+        }
+    }
+}
+
+impl ClearPolicy {
+    /// Clear nothing: every frame is drawn over the previous one.
+    pub const NONE: Self = Self {
+        color: Color::BLACK,
+        clear_color: false,
+        clear_depth: false,
+        clear_stencil: false,
+        stencil: 0,
+        scissor: None,
+    };
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self.clear_color = true;
+        self
+    }
+
+    /// Clear the stencil buffer to `stencil`.
+    pub fn with_stencil(mut self, stencil: i32) -> Self {
+        self.stencil = stencil;
+        self.clear_stencil = true;
+        self
+    }
+
+    pub fn with_scissor(mut self, scissor: Option<[i32; 4]>) -> Self {
+        self.scissor = scissor;
+        self
+    }
+
+    /// The mask of the buffers to clear with `depth`.
+    pub fn mask(&self, depth: DepthMode) -> u32 {
+        let mut mask = 0;
+        if self.clear_color {
+            mask |= janus::gl::COLOR_BUFFER_BIT;
+        }
+        if self.clear_depth && depth.is_enabled() {
+            mask |= janus::gl::DEPTH_BUFFER_BIT;
+        }
+        if self.clear_stencil {
+            mask |= janus::gl::STENCIL_BUFFER_BIT;
+        }
+        mask
+    }
+
+    /// Clear the bound framebuffer, whose colour is displayed as is, with
+    /// `depth`.
+    ///
+    /// The clear values and write masks of the cleared buffers are left set.
+    pub fn apply(&self, depth: DepthMode) {
+        let mask = self.mask(depth);
+        if mask == 0 {
+            return;
+        }
+        unsafe {
+            if mask & janus::gl::COLOR_BUFFER_BIT != 0 {
+                color::clear_color(self.color, ColorSpace::Srgb);
+                janus::gl::ColorMask(
+                    janus::gl::TRUE,
+                    janus::gl::TRUE,
+                    janus::gl::TRUE,
+                    janus::gl::TRUE,
+                );
+            }
+            if mask & janus::gl::DEPTH_BUFFER_BIT != 0 {
+                janus::gl::ClearDepth(depth.clear_depth());
+                janus::gl::DepthMask(janus::gl::TRUE);
+            }
+            if mask & janus::gl::STENCIL_BUFFER_BIT != 0 {
+                janus::gl::ClearStencil(self.stencil);
+                janus::gl::StencilMask(!0);
+            }
+            if let Some([x, y, w, h]) = self.scissor {
+                janus::gl::Enable(janus::gl::SCISSOR_TEST);
+                janus::gl::Scissor(x, y, w, h);
+            }
+            janus::gl::Clear(mask);
+            if self.scissor.is_some() {
+                janus::gl::Disable(janus::gl::SCISSOR_TEST);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_masks() {
+        let policy = ClearPolicy::default();
+        assert_eq!(
+            policy.mask(DepthMode::ReverseZ),
+            janus::gl::COLOR_BUFFER_BIT | janus::gl::DEPTH_BUFFER_BIT
+        );
+        // there is no depth buffer to clear
+        assert_eq!(
+            policy.mask(DepthMode::Disabled),
+            janus::gl::COLOR_BUFFER_BIT
+        );
+        assert_eq!(
+            ClearPolicy::NONE.with_stencil(1).mask(DepthMode::Standard),
+            janus::gl::STENCIL_BUFFER_BIT
+        );
+        assert_eq!(ClearPolicy::NONE.mask(DepthMode::ReverseZ), 0);
+        // a mask of nothing issues no GL call
+        ClearPolicy::NONE.apply(DepthMode::ReverseZ);
+    }
+}
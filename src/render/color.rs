@@ -0,0 +1,237 @@
+//! Colors, and the color space they are encoded in.
+//!
+//! Lighting, blending and filtering must operate on linear values, while
+//! colors are usually authored in sRGB (hex codes, color pickers) and
+//! displayed on framebuffers which may or may not encode their writes to sRGB.
+//! Mixing the two up applies gamma twice or not at all, and the resulting
+//! washed out or overly dark colors are hard to trace back.
+//!
+//! [`Color`] always holds linear values with straight alpha: constructors
+//! state the space of their input, and conversions to another space are
+//! explicit, e.g. [`Color::encode`] for the clear color of a framebuffer.
+//!
+//! # Example
+//! ```rust,ignore
+//! let brick = Material::new(Color::from_hex("#9a4b33").unwrap());
+//!
+//! // the default framebuffer does not encode writes to sRGB
+//! color::clear_color(Color::srgb(0.05, 0.05, 0.08, 1.0), ColorSpace::Srgb);
+//! ```
+
+use crate::shader::{
+    UniformLocation,
+    glsl::{Glsl, GlslType},
+    uniform::UploadUniform,
+};
+
+/// The encoding of color values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Values proportional to the light intensity.
+    #[default]
+    Linear,
+    /// Values encoded with the sRGB transfer function, as displayed.
+    Srgb,
+}
+
+/// Decode a sRGB encoded channel to linear.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear channel to sRGB.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// A linear RGBA color, with straight (not premultiplied) alpha.
+///
+/// Laid out as a GLSL `vec4`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Self = Self::linear(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Self = Self::linear(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Self = Self::linear(1.0, 1.0, 1.0, 1.0);
+
+    /// A color from linear channels.
+    pub const fn linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// A color from sRGB encoded channels, with a linear alpha.
+    pub fn srgb(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::linear(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// A color from 8 bit sRGB encoded channels, with a linear alpha.
+    pub fn from_srgb8([r, g, b, a]: [u8; 4]) -> Self {
+        let unorm = |c: u8| c as f32 / 255.0;
+        Self::srgb(unorm(r), unorm(g), unorm(b), unorm(a))
+    }
+
+    /// Parse a sRGB hex code, `#rrggbb` or `#rrggbbaa`, with an optional
+    /// leading `#`.
+    ///
+    /// # Returns
+    /// `None` if `hex` is not a valid hex code.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok();
+        let alpha = match hex.len() {
+            8 => channel(3)?,
+            _ => u8::MAX,
+        };
+        Some(Self::from_srgb8([
+            channel(0)?,
+            channel(1)?,
+            channel(2)?,
+            alpha,
+        ]))
+    }
+
+    pub const fn with_alpha(mut self, a: f32) -> Self {
+        self.a = a;
+        self
+    }
+
+    /// The linear channels.
+    pub const fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// The linear color channels, without alpha.
+    pub const fn rgb(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    /// The sRGB encoded channels, with a linear alpha.
+    pub fn to_srgb(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    /// The 8 bit sRGB encoded channels, with a linear alpha.
+    pub fn to_srgb8(self) -> [u8; 4] {
+        self.to_srgb()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// The channels encoded in `space`.
+    pub fn encode(self, space: ColorSpace) -> [f32; 4] {
+        match space {
+            ColorSpace::Linear => self.to_array(),
+            ColorSpace::Srgb => self.to_srgb(),
+        }
+    }
+
+    /// The color channels multiplied by alpha, for premultiplied alpha
+    /// blending (`ONE, ONE_MINUS_SRC_ALPHA`).
+    pub fn premultiplied(self) -> Self {
+        Self::linear(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    /// The inverse of [`Self::premultiplied`].
+    ///
+    /// Fully transparent colors become transparent black.
+    pub fn unpremultiplied(self) -> Self {
+        if self.a == 0.0 {
+            return Self::TRANSPARENT;
+        }
+        Self::linear(self.r / self.a, self.g / self.a, self.b / self.a, self.a)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_array()
+    }
+}
+
+impl From<Color> for glam::Vec4 {
+    fn from(color: Color) -> Self {
+        glam::Vec4::from_array(color.to_array())
+    }
+}
+
+impl Glsl for Color {
+    fn to_glsl() -> &'static str {
+        "vec4"
+    }
+}
+
+impl GlslType for Color {
+    fn to_glsl_type() -> &'static str {
+        "vec4"
+    }
+}
+
+impl UploadUniform for Color {
+    fn upload(&self, location: UniformLocation) {
+        unsafe {
+            janus::gl::Uniform4f(*location, self.r, self.g, self.b, self.a);
+        }
+    }
+}
+
+/// Set the clear color of the bound framebuffer, whose writes are encoded in
+/// `framebuffer`.
+///
+/// The default framebuffer is usually [`ColorSpace::Srgb`]: it is displayed
+/// as is, and does not encode writes unless `GL_FRAMEBUFFER_SRGB` is enabled
+/// on a sRGB capable framebuffer.
+pub fn clear_color(color: Color, framebuffer: ColorSpace) {
+    let [r, g, b, a] = color.encode(framebuffer);
+    unsafe {
+        janus::gl::ClearColor(r, g, b, a);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_space_conversions() {
+        for c in [0.0, 0.002, 0.04, 0.2, 0.5, 1.0] {
+            assert!((srgb_to_linear(linear_to_srgb(c)) - c).abs() < 1e-5);
+        }
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+
+        let color = Color::from_hex("#ff800040").unwrap();
+        assert_eq!(color.to_srgb8(), [255, 128, 0, 64]);
+        assert_eq!(color.r, 1.0);
+        assert!(color.g < 0.5);
+        assert_eq!(Color::from_hex("ffffff"), Some(Color::WHITE));
+        assert_eq!(Color::from_hex("#fff"), None);
+        assert_eq!(Color::from_hex("#gg0000"), None);
+
+        let color = Color::linear(0.5, 1.0, 0.0, 0.5);
+        assert_eq!(color.premultiplied().to_array(), [0.25, 0.5, 0.0, 0.5]);
+        assert_eq!(color.premultiplied().unpremultiplied(), color);
+        assert_eq!(Color::WHITE.encode(ColorSpace::Linear), [1.0; 4]);
+        assert_eq!(Color::WHITE.to_srgb8(), [u8::MAX; 4]);
+    }
+}
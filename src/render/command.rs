@@ -1,6 +1,17 @@
 use std::sync::atomic::{AtomicU32, Ordering};
 
-use crate::render::buffer::View;
+use crate::{
+    mesh::{self, MeshPoolId},
+    render::{
+        batch::{BatchKey, IndirectBucket},
+        buffer::{ImmutableBuffer, View},
+        pool::MeshPools,
+    },
+};
+
+pub mod chunked;
+
+pub use chunked::ChunkedCommands;
 
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(C)]
@@ -11,40 +22,118 @@ pub struct DrawArraysIndirectCommand {
     pub base_instance: u32,
 }
 
+impl DrawArraysIndirectCommand {
+    /// Draw `instance_count` instances of the vertices of `mesh`, starting
+    /// from the instance `base_instance`.
+    pub const fn from_metadata(
+        mesh: &mesh::Metadata,
+        instance_count: u32,
+        base_instance: u32,
+    ) -> Self {
+        Self {
+            count: mesh.length(),
+            instance_count,
+            first_vertex: mesh.offset(),
+            base_instance,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(C)]
 pub struct DrawElementsIndirectCommand {
-    count: u32,
-    instance_count: u32,
-    first_vertex: u32,
-    base_vertex: i32,
-    base_instance: u32,
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+impl DrawElementsIndirectCommand {
+    /// Draw `instance_count` instances of the indexed `mesh`, starting from
+    /// the instance `base_instance`.
+    ///
+    /// The indices are read from the index partition of the mesh buffer,
+    /// which is bound as the element array buffer of the render VAO.
+    pub const fn from_metadata(
+        mesh: &mesh::Metadata,
+        instance_count: u32,
+        base_instance: u32,
+    ) -> Self {
+        Self {
+            count: mesh.index_count(),
+            instance_count,
+            first_index: mesh.first_index(),
+            base_vertex: mesh.base_vertex(),
+            base_instance,
+        }
+    }
 }
 
 pub trait DrawCmd: std::fmt::Debug + Clone + Copy {
-    fn call(draw_count: i32);
+    /// Whether the commands draw indexed meshes, reading their indices from
+    /// the element array buffer of the bound vertex array.
+    const INDEXED: bool = false;
+
+    fn call(draw_count: i32) {
+        Self::call_offset(0, draw_count);
+    }
+
+    /// Issue the multi-draw call starting from the command at index `first`
+    /// of the currently bound indirect buffer.
+    fn call_offset(first: usize, draw_count: i32) {
+        Self::call_offset_mode(janus::gl::TRIANGLES, first, draw_count);
+    }
+
+    /// Issue the multi-draw call of `mode` primitives, e.g. `gl::PATCHES`
+    /// for tessellated programs, starting from the command at index `first`
+    /// of the currently bound indirect buffer.
+    fn call_offset_mode(mode: u32, first: usize, draw_count: i32);
+
+    /// Set the first instance of the command, see
+    /// [`per_draw`](super::per_draw) for its use as a draw index.
+    fn set_base_instance(&mut self, base_instance: u32);
+
+    /// Draw `instance_count` instances of `mesh`, starting from the instance
+    /// `base_instance`.
+    fn from_mesh(mesh: &mesh::Metadata, instance_count: u32, base_instance: u32) -> Self;
 }
 
 impl DrawCmd for DrawArraysIndirectCommand {
-    fn call(draw_count: i32) {
+    fn from_mesh(mesh: &mesh::Metadata, instance_count: u32, base_instance: u32) -> Self {
+        Self::from_metadata(mesh, instance_count, base_instance)
+    }
+
+    fn set_base_instance(&mut self, base_instance: u32) {
+        self.base_instance = base_instance;
+    }
+
+    fn call_offset_mode(mode: u32, first: usize, draw_count: i32) {
+        let offset = first * size_of::<Self>();
         unsafe {
-            janus::gl::MultiDrawArraysIndirect(
-                janus::gl::TRIANGLES,
-                std::ptr::null(),
-                draw_count,
-                0,
-            );
+            janus::gl::MultiDrawArraysIndirect(mode, offset as *const _, draw_count, 0);
         }
     }
 }
 
 impl DrawCmd for DrawElementsIndirectCommand {
-    fn call(draw_count: i32) {
+    const INDEXED: bool = true;
+
+    fn from_mesh(mesh: &mesh::Metadata, instance_count: u32, base_instance: u32) -> Self {
+        Self::from_metadata(mesh, instance_count, base_instance)
+    }
+
+    fn set_base_instance(&mut self, base_instance: u32) {
+        self.base_instance = base_instance;
+    }
+
+    fn call_offset_mode(mode: u32, first: usize, draw_count: i32) {
+        let offset = first * size_of::<Self>();
         unsafe {
             janus::gl::MultiDrawElementsIndirect(
-                janus::gl::TRIANGLES,
+                mode,
                 janus::gl::UNSIGNED_INT,
-                std::ptr::null(),
+                offset as *const _,
                 draw_count,
                 0,
             );
@@ -76,9 +165,63 @@ impl<C: DrawCmd, G: DrawGroups> std::fmt::Display for Instruction<C, G> {
     }
 }
 
+/// The order of a draw command within its group, see
+/// [`GpuCommandQueue::sort`].
+///
+/// Opaque commands are ordered by material first, to minimise state
+/// changes, then front to back, so that early depth testing rejects hidden
+/// fragments, then by mesh. Transparent commands are ordered after all
+/// opaque ones, back to front, as required by blending.
+///
+/// The key of commands pushed without one is `0`: they are drawn first, in
+/// order of push.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey(pub u64);
+
+impl SortKey {
+    const TRANSPARENT: u64 = 1 << 63;
+    /// The bits of a depth.
+    const DEPTH_BITS: u32 = 24;
+    const DEPTH_MASK: u64 = (1 << Self::DEPTH_BITS) - 1;
+
+    /// The key of an opaque command of `material` and `mesh`, at `depth`
+    /// from the camera.
+    ///
+    /// Only the low 15 bits of the material and 24 bits of the mesh index
+    /// are kept.
+    pub fn opaque(material: u32, depth: f32, mesh: mesh::Id) -> Self {
+        let material = (material as u64 & 0x7FFF) << 48;
+        let depth = Self::depth_bits(depth) << 24;
+        Self(material | depth | (mesh.index as u64 & 0xFF_FFFF))
+    }
+
+    /// The key of a transparent command of `material` at `depth` from the
+    /// camera.
+    ///
+    /// Only the low 31 bits of the material are kept.
+    pub fn transparent(material: u32, depth: f32) -> Self {
+        let depth = (!Self::depth_bits(depth) & Self::DEPTH_MASK) << 31;
+        Self(Self::TRANSPARENT | depth | (material as u64 & 0x7FFF_FFFF))
+    }
+
+    pub const fn is_transparent(self) -> bool {
+        self.0 & Self::TRANSPARENT != 0
+    }
+
+    /// The `depth` quantised to 24 bits, preserving its order: the bits of
+    /// positive floats are ordered as the floats themselves.
+    fn depth_bits(depth: f32) -> u64 {
+        // NaN and negative depths are at the camera
+        let depth = if depth > 0.0 { depth } else { 0.0 };
+        (depth.to_bits() >> (31 - Self::DEPTH_BITS)) as u64
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct GpuCommandQueue<C: DrawCmd, G: DrawGroups> {
     queue: Vec<Instruction<C, G>>,
+    /// The sort key of each instruction, `0` for switches.
+    keys: Vec<SortKey>,
     head: AtomicU32,
     first_group: Option<G>,
 }
@@ -87,6 +230,7 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
     pub fn new() -> Self {
         Self {
             queue: Vec::new(),
+            keys: Vec::new(),
             head: AtomicU32::new(0),
             first_group: None,
         }
@@ -95,6 +239,7 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             queue: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
             head: AtomicU32::new(0),
             first_group: None,
         }
@@ -102,11 +247,13 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
 
     pub fn clear(&mut self) {
         self.queue.clear();
+        self.keys.clear();
         self.head.store(0, Ordering::Release);
         self.first_group = None;
     }
 
     pub fn pop(&mut self) -> Option<Instruction<C, G>> {
+        self.keys.pop();
         self.queue.pop()
     }
 
@@ -128,7 +275,78 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
     /// contiguous in the queue, to minimize both the amount of gpu draw
     /// dispatches and the possibility of a programmer error.
     pub fn push_command(&mut self, command: C) {
+        self.push_sorted(command, SortKey::default());
+    }
+
+    /// Push a new draw command, ordered by `key` within its group once the
+    /// queue is [sorted](Self::sort).
+    pub fn push_sorted(&mut self, command: C, key: SortKey) {
         self.queue.push(Instruction::Draw(command));
+        self.keys.push(key);
+    }
+
+    /// Sort the draw commands of each group by their [`SortKey`], keeping
+    /// the order of commands with the same key.
+    ///
+    /// Groups are not reordered. Sorting must happen before the queue is
+    /// uploaded.
+    pub fn sort(&mut self) {
+        let mut start = 0;
+        while start < self.queue.len() {
+            let len = self.queue[start..]
+                .iter()
+                .position(|instruction| matches!(instruction, Instruction::Switch(_)))
+                .unwrap_or(self.queue.len() - start);
+            let group = start..start + len;
+
+            let keys = &self.keys[group.clone()];
+            if !keys.is_sorted() {
+                let mut sorted = keys
+                    .iter()
+                    .copied()
+                    .zip(self.queue[group.clone()].iter().copied())
+                    .collect::<Vec<_>>();
+                sorted.sort_by_key(|(key, _)| *key);
+                for (i, (key, instruction)) in group.clone().zip(sorted) {
+                    self.keys[i] = key;
+                    self.queue[i] = instruction;
+                }
+            }
+            // skip the switch
+            start = group.end + 1;
+        }
+    }
+
+    /// Push a draw command for the mesh of each entity, as `(entity, mesh)`
+    /// pairs, with the metadata of their pool.
+    ///
+    /// Each command draws a single instance of the mesh, and its
+    /// `base_instance` is the index of the entity, so that the vertex shader
+    /// fetches the per-instance data of the entity through
+    /// `gl_BaseInstance + gl_InstanceID`.
+    ///
+    /// Entities using the `null` mesh are skipped.
+    ///
+    /// # Returns
+    /// The amount of commands pushed.
+    pub fn push_meshes(
+        &mut self,
+        metadata: &mesh::Meshadata,
+        entities: impl IntoIterator<Item = (u32, mesh::Id)>,
+    ) -> usize {
+        let len = self.queue.len();
+        for (entity, id) in entities {
+            let mesh = metadata.get(id);
+            let empty = if C::INDEXED {
+                !mesh.is_indexed()
+            } else {
+                mesh.length() == 0
+            };
+            if !empty {
+                self.push_command(C::from_mesh(mesh, 1, entity));
+            }
+        }
+        self.queue.len() - len
     }
 
     /// Push a new draw group.
@@ -144,6 +362,7 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
             self.first_group = Some(group);
         } else {
             self.queue.push(Instruction::Switch(group));
+            self.keys.push(SortKey::default());
         }
     }
 
@@ -157,6 +376,11 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
         self.head.load(Ordering::Relaxed)
     }
 
+    /// The amount of instructions not uploaded yet, including switches.
+    pub fn remaining(&self) -> usize {
+        self.queue.len().saturating_sub(self.index() as usize)
+    }
+
     fn get_head(&self) -> Option<Instruction<C, G>> {
         let head = self.head.load(Ordering::Acquire);
         let instr = self.queue.get(head as usize);
@@ -182,6 +406,9 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
     /// This will upload all [`Instruction::Draw`] entries until the queue is
     /// empty or an [`Instruction::Switch] entry is encountered.
     ///
+    /// The `buffer` must fit every command of the group, see
+    /// [`ChunkedCommands`] to upload every group at once.
+    ///
     /// # Returns
     /// `Some` with the group up next if there is one.
     pub fn upload_next_group(&self, buffer: &mut [C]) -> Option<G> {
@@ -201,20 +428,167 @@ impl<C: DrawCmd, G: DrawGroups> GpuCommandQueue<C, G> {
 
         None
     }
+
+    /// Drain the queue into `buffer` in chunks of contiguous commands of a
+    /// single group, each drawn with
+    /// [`GpuCommandDispatch::dispatch_chunk`].
+    ///
+    /// Each chunk is written after the previous one, until the `buffer` is
+    /// full: the commands which do not fit are left in the queue, see
+    /// [`Self::remaining`]. All chunks of a frame are thus uploaded to the same
+    /// section before it is drawn, and no chunk overwrites another one the
+    /// GPU did not read yet. [`ChunkedCommands`] uploads the chunks to a
+    /// section, pairs them with their dispatch on the render thread, and
+    /// grows to fit the commands left over.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let mut view = commands.view_section_mut(section);
+    /// let chunks = queue.upload_chunks(&mut view).collect::<Vec<_>>();
+    ///
+    /// // RENDER
+    /// GpuCommandDispatch::from_view(commands.view_section(section))
+    ///     .dispatch_chunks(&chunks, |group| bind_group(group));
+    /// ```
+    pub fn upload_chunks<'a>(&'a self, buffer: &'a mut [C]) -> UploadChunks<'a, C, G> {
+        let head = self.index() as usize;
+        let group = self.queue[..head.min(self.queue.len())]
+            .iter()
+            .rev()
+            .find_map(|instruction| match instruction {
+                Instruction::Switch(group) => Some(*group),
+                Instruction::Draw(_) => None,
+            })
+            .or(self.first_group);
+        UploadChunks {
+            queue: self,
+            buffer,
+            group,
+            offset: 0,
+        }
+    }
+}
+
+/// A range of contiguous commands of a single group in a command buffer,
+/// uploaded by [`GpuCommandQueue::upload_chunks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommandChunk<G: DrawGroups> {
+    /// The group of the commands, `None` for commands pushed before any
+    /// group.
+    pub group: Option<G>,
+    /// The index of the first command in the command buffer.
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl<G: DrawGroups> CommandChunk<G> {
+    /// The range of the commands in the command buffer.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// The chunks of a [`GpuCommandQueue`], see
+/// [`GpuCommandQueue::upload_chunks`].
+#[derive(Debug)]
+pub struct UploadChunks<'a, C: DrawCmd, G: DrawGroups> {
+    queue: &'a GpuCommandQueue<C, G>,
+    buffer: &'a mut [C],
+    group: Option<G>,
+    /// The position of the next chunk in the buffer.
+    offset: usize,
+}
+
+impl<C: DrawCmd, G: DrawGroups> Iterator for UploadChunks<'_, C, G> {
+    type Item = CommandChunk<G>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset == self.buffer.len() {
+            return None;
+        }
+
+        let start = self.offset;
+        while self.offset < self.buffer.len() {
+            let head = self.queue.head.load(Ordering::Acquire);
+            match self.queue.queue.get(head as usize) {
+                Some(Instruction::Draw(command)) => {
+                    self.buffer[self.offset] = *command;
+                    self.offset += 1;
+                }
+                // the next group starts a new chunk
+                Some(Instruction::Switch(_)) if self.offset > start => break,
+                Some(Instruction::Switch(group)) => self.group = Some(*group),
+                None => break,
+            }
+            self.queue.head.fetch_add(1, Ordering::Release);
+        }
+
+        (self.offset > start).then_some(CommandChunk {
+            group: self.group,
+            offset: start,
+            len: self.offset - start,
+        })
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct GpuCommandDispatch<'buf, C: DrawCmd + Clone + Copy> {
     command_buffer: View<'buf, C>,
+    primitive: u32,
+    elements: Option<&'buf ImmutableBuffer<3>>,
 }
 
 impl<'buf, C: DrawCmd + Clone + Copy> GpuCommandDispatch<'buf, C> {
     pub const fn from_view(view: View<'buf, C>) -> Self {
         Self {
             command_buffer: view,
+            primitive: janus::gl::TRIANGLES,
+            elements: None,
         }
     }
 
+    /// Bind the element partition of the mesh `buffer` as the element array
+    /// buffer of the bound vertex array before drawing indexed commands.
+    ///
+    /// The render VAO already reads the elements of the default mesh pool,
+    /// and [`Self::dispatch_pooled_buckets`] binds the buffer of each pool, so
+    /// this is only required when drawing another buffer, or with another
+    /// vertex array.
+    pub const fn with_element_buffer(mut self, buffer: &'buf ImmutableBuffer<3>) -> Self {
+        self.elements = Some(buffer);
+        self
+    }
+
+    /// Bind the element buffer, if any, of indexed commands.
+    fn bind_elements(&self) {
+        if !C::INDEXED {
+            return;
+        }
+        if let Some(elements) = self.elements {
+            elements.bind_current_element_buffer();
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            let mut bound = 0;
+            unsafe {
+                janus::gl::GetIntegerv(janus::gl::ELEMENT_ARRAY_BUFFER_BINDING, &mut bound);
+            }
+            debug_assert_ne!(
+                bound, 0,
+                "indexed draw commands dispatched without an element array buffer"
+            );
+        }
+    }
+
+    /// Draw `primitive`s instead of triangles, e.g. `gl::PATCHES` for
+    /// tessellated programs, see
+    /// [`ShaderProgramBuilder`](crate::shader::builder::ShaderProgramBuilder).
+    pub const fn with_primitive(mut self, primitive: u32) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
     pub fn dispatch(&self) {
         let len = self.command_buffer.length() as i32;
         let gl_obj = self.command_buffer.source();
@@ -222,7 +596,128 @@ impl<'buf, C: DrawCmd + Clone + Copy> GpuCommandDispatch<'buf, C> {
         unsafe {
             janus::gl::BindBuffer(janus::gl::DRAW_INDIRECT_BUFFER, gl_obj);
         }
-        C::call(len);
+        self.bind_elements();
+        C::call_offset_mode(self.primitive, 0, len);
+    }
+
+    /// Draw the commands of a `chunk` uploaded by
+    /// [`GpuCommandQueue::upload_chunks`] to the command buffer.
+    ///
+    /// Commands of the chunk beyond the command buffer are not drawn.
+    pub fn dispatch_chunk<G: DrawGroups>(&self, chunk: &CommandChunk<G>) {
+        let len = self.command_buffer.length() as usize;
+        if chunk.offset >= len {
+            return;
+        }
+        let count = chunk.len.min(len - chunk.offset);
+
+        unsafe {
+            janus::gl::BindBuffer(
+                janus::gl::DRAW_INDIRECT_BUFFER,
+                self.command_buffer.source(),
+            );
+        }
+        self.bind_elements();
+        C::call_offset_mode(self.primitive, chunk.offset, count as i32);
+    }
+
+    /// Draw the `chunks` uploaded by [`GpuCommandQueue::upload_chunks`] to the
+    /// command buffer in order, calling `bind` with the group of each chunk
+    /// before it is drawn.
+    pub fn dispatch_chunks<G, F>(&self, chunks: &[CommandChunk<G>], mut bind: F)
+    where
+        G: DrawGroups,
+        F: FnMut(Option<G>),
+    {
+        for chunk in chunks {
+            bind(chunk.group);
+            self.dispatch_chunk(chunk);
+        }
+    }
+
+    /// Dispatch each bucket of the command buffer with its own multi-draw
+    /// call.
+    ///
+    /// The `bind` function is called before a bucket is dispatched whenever
+    /// its material differs from the material of the previous bucket, and
+    /// it must bind any shader/texture state required by that material.
+    ///
+    /// The `buckets` must be the ones produced by
+    /// [`CommandBatcher::blit`](super::batch::CommandBatcher::blit) for the
+    /// same command buffer section.
+    pub fn dispatch_buckets<M, F>(&self, buckets: &[IndirectBucket<M>], bind: F)
+    where
+        M: Clone + Copy + PartialEq + std::fmt::Debug,
+        F: FnMut(&BatchKey<M>),
+    {
+        self.dispatch_buckets_in(buckets, None, bind);
+    }
+
+    /// Dispatch each bucket as [`Self::dispatch_buckets`], binding the
+    /// [`MeshPool`](super::pool::MeshPool) of its mesh whenever it differs
+    /// from the pool of the previous bucket.
+    ///
+    /// The default pool is bound again once all buckets are dispatched.
+    ///
+    /// # Panics
+    /// If the pool of a mesh is not in `pools`.
+    pub fn dispatch_pooled_buckets<M, F>(
+        &self,
+        buckets: &[IndirectBucket<M>],
+        pools: &MeshPools,
+        bind: F,
+    ) where
+        M: Clone + Copy + PartialEq + std::fmt::Debug,
+        F: FnMut(&BatchKey<M>),
+    {
+        self.dispatch_buckets_in(buckets, Some(pools), bind);
+    }
+
+    fn dispatch_buckets_in<M, F>(
+        &self,
+        buckets: &[IndirectBucket<M>],
+        pools: Option<&MeshPools>,
+        mut bind: F,
+    ) where
+        M: Clone + Copy + PartialEq + std::fmt::Debug,
+        F: FnMut(&BatchKey<M>),
+    {
+        let gl_obj = self.command_buffer.source();
+        let len = self.command_buffer.length();
+
+        unsafe {
+            janus::gl::BindBuffer(janus::gl::DRAW_INDIRECT_BUFFER, gl_obj);
+        }
+        self.bind_elements();
+
+        let mut bound = None;
+        let mut bound_pool = MeshPoolId::DEFAULT;
+        for bucket in buckets {
+            if bucket.offset >= len {
+                break;
+            }
+
+            if let Some(pools) = pools
+                && bound_pool != bucket.key.mesh.pool()
+            {
+                bound_pool = bucket.key.mesh.pool();
+                pools.bind(bound_pool);
+            }
+
+            if bound != Some(bucket.key.material) {
+                bind(&bucket.key);
+                bound = Some(bucket.key.material);
+            }
+
+            let count = bucket.count.min(len - bucket.offset);
+            C::call_offset_mode(self.primitive, bucket.offset as usize, count as i32);
+        }
+
+        if let Some(pools) = pools
+            && bound_pool != MeshPoolId::DEFAULT
+        {
+            pools.bind(MeshPoolId::DEFAULT);
+        }
     }
 }
 
@@ -292,4 +787,133 @@ mod tests {
             assert_eq!(next, None);
         }
     }
+    #[test]
+    fn indexed_cmd_queue() {
+        const { assert!(DrawElementsIndirectCommand::INDEXED && !DrawArraysIndirectCommand::INDEXED) };
+
+        let mut queue = GpuCommandQueue::<DrawElementsIndirectCommand, Groups>::new();
+        queue.push_group(Groups::A);
+        queue.push_command(DrawElementsIndirectCommand {
+            count: 36,
+            instance_count: 4,
+            first_index: 12,
+            base_vertex: 8,
+            base_instance: 0,
+        });
+        queue.push_group(Groups::B);
+
+        let mut buf = vec![DrawElementsIndirectCommand::default(); 1];
+        assert_eq!(queue.upload_next_group(&mut buf), Some(Groups::B));
+        assert_eq!((buf[0].first_index, buf[0].base_vertex), (12, 8));
+    }
+
+    #[test]
+    fn upload_in_chunks() {
+        let cmd = |first_vertex| DrawArraysIndirectCommand {
+            first_vertex,
+            ..Default::default()
+        };
+        let mut queue = GpuCommandQueue::new();
+        queue.push_group(Groups::A);
+        (0..5).for_each(|i| queue.push_command(cmd(i)));
+        queue.push_group(Groups::B);
+        queue.push_group(Groups::C);
+        (5..8).for_each(|i| queue.push_command(cmd(i)));
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 4];
+        let mut chunks = queue.upload_chunks(&mut buf);
+        let chunk = |group, offset, len| CommandChunk {
+            group: Some(group),
+            offset,
+            len,
+        };
+        assert_eq!(chunks.next(), Some(chunk(Groups::A, 0, 4)));
+        // the buffer is full, nothing is overwritten
+        assert_eq!(chunks.next(), None);
+        assert_eq!(
+            buf.iter().map(|c| c.first_vertex).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(queue.remaining(), 6);
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 8];
+        let chunks = queue.upload_chunks(&mut buf).collect::<Vec<_>>();
+        // empty groups are skipped
+        assert_eq!(chunks, [chunk(Groups::A, 0, 1), chunk(Groups::C, 1, 3)]);
+        assert_eq!(
+            buf[..4].iter().map(|c| c.first_vertex).collect::<Vec<_>>(),
+            [4, 5, 6, 7]
+        );
+        assert_eq!(queue.remaining(), 0);
+    }
+
+    #[test]
+    fn sort_commands_by_key() {
+        let cmd = |first_vertex| DrawArraysIndirectCommand {
+            first_vertex,
+            ..Default::default()
+        };
+        let mesh = |index| mesh::Id {
+            pool: MeshPoolId::DEFAULT,
+            index,
+        };
+        let mut queue = GpuCommandQueue::new();
+        queue.push_group(Groups::A);
+        queue.push_sorted(cmd(0), SortKey::transparent(0, 1.0));
+        queue.push_sorted(cmd(1), SortKey::opaque(1, 2.0, mesh(1)));
+        queue.push_sorted(cmd(2), SortKey::transparent(0, 8.0));
+        queue.push_sorted(cmd(3), SortKey::opaque(1, 0.5, mesh(2)));
+        queue.push_sorted(cmd(4), SortKey::opaque(0, 9.0, mesh(1)));
+        queue.push_command(cmd(5));
+        queue.push_group(Groups::B);
+        queue.push_sorted(cmd(6), SortKey::opaque(0, 1.0, mesh(1)));
+        queue.push_sorted(cmd(7), SortKey::opaque(0, -1.0, mesh(1)));
+        queue.sort();
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 6];
+        assert_eq!(queue.upload_next_group(&mut buf), Some(Groups::B));
+        // unkeyed, opaque by material then front to back, transparent back
+        // to front
+        assert_eq!(
+            buf.iter().map(|c| c.first_vertex).collect::<Vec<_>>(),
+            [5, 4, 3, 1, 2, 0]
+        );
+        queue.upload_next_group(&mut buf);
+        assert_eq!((buf[0].first_vertex, buf[1].first_vertex), (7, 6));
+        assert!(SortKey::transparent(0, 1.0).is_transparent());
+    }
+
+    #[test]
+    fn commands_from_metadata() {
+        let mut metadata = mesh::Meshadata::new();
+        let triangle = metadata.add(3);
+        let cube = metadata.add_indexed(24, 36);
+        let null = mesh::Id::default();
+
+        let entities = [(0, cube), (1, null), (2, triangle), (3, cube)];
+        let mut queue = GpuCommandQueue::<DrawArraysIndirectCommand, Groups>::new();
+        queue.push_group(Groups::A);
+        assert_eq!(queue.push_meshes(&metadata, entities), 3);
+
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 3];
+        assert_eq!(queue.upload_next_group(&mut buf), None);
+        assert_eq!(
+            buf.iter()
+                .map(|c| (c.count, c.first_vertex, c.base_instance))
+                .collect::<Vec<_>>(),
+            [(24, 3, 0), (3, 0, 2), (24, 3, 3)]
+        );
+
+        // non-indexed meshes draw nothing with indexed commands
+        let mut queue = GpuCommandQueue::<DrawElementsIndirectCommand, Groups>::new();
+        assert_eq!(queue.push_meshes(&metadata, entities), 2);
+        let mut buf = vec![DrawElementsIndirectCommand::default(); 2];
+        queue.upload_next_group(&mut buf);
+        assert_eq!(
+            buf.iter()
+                .map(|c| (c.count, c.first_index, c.base_vertex, c.base_instance))
+                .collect::<Vec<_>>(),
+            [(36, 0, 3, 0), (36, 0, 3, 3)]
+        );
+    }
 }
@@ -0,0 +1,201 @@
+//! Command buffers drawing every group of a [`GpuCommandQueue`] in one cross.
+//!
+//! [`GpuCommandQueue::upload_next_group`] uploads one group at a time, which
+//! must be drawn before the next one is uploaded over it. A
+//! [`ChunkedCommands`] buffer instead uploads all the chunks of the queue
+//! (see [`GpuCommandQueue::upload_chunks`]) one after the other into a
+//! section of its command buffer on the producer side, and records them with
+//! the section, so that the consumer draws each chunk after binding its group.
+//!
+//! Commands which do not fit in the section are dropped for the frame, and
+//! the capacity they required is recorded: the buffer grows to fit them on
+//! the render thread with [`ChunkedCommands::grow_to_fit`], e.g. from an
+//! [exclusive hook](crate::render::stage::CrossHooks::add_exclusive).
+//!
+//! # Example
+//! ```rust,ignore
+//! // UPLOAD
+//! frame_boundary.cross(|section, frame_data| {
+//!     frame_data.commands.upload(section.as_index(), command_queue);
+//! });
+//!
+//! // RENDER
+//! frame_data.commands.dispatch(section.as_index(), |group| match group {
+//!     Some(Groups::Opaque) | None => opaque.bind(),
+//!     Some(Groups::Transparent) => transparent.bind(),
+//! });
+//!
+//! // at startup
+//! renderer.cross_hooks_mut().add_exclusive(|frame_data: &mut FrameData| {
+//!     frame_data.commands.grow_to_fit();
+//! });
+//! ```
+
+use std::sync::{
+    Mutex, MutexGuard,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use crate::render::{
+    buffer::TriBuffer,
+    command::{CommandChunk, DrawCmd, DrawGroups, GpuCommandDispatch, GpuCommandQueue},
+};
+
+/// A triple buffered command buffer, and the chunks uploaded to each of its
+/// sections.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct ChunkedCommands<C: DrawCmd, G: DrawGroups> {
+    commands: TriBuffer<C>,
+    chunks: [Mutex<Vec<CommandChunk<G>>>; 3],
+    /// The most commands queued for a section since the buffer was created or
+    /// last grown.
+    required: AtomicUsize,
+}
+
+impl<C: DrawCmd, G: DrawGroups> ChunkedCommands<C, G> {
+    /// Create a command buffer of `capacity` commands per section.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            commands: TriBuffer::zeroed(capacity),
+            chunks: Default::default(),
+            required: AtomicUsize::new(0),
+        }
+    }
+
+    /// The command buffer.
+    pub fn commands(&self) -> &TriBuffer<C> {
+        &self.commands
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.commands.capacity()
+    }
+
+    /// The chunks uploaded to `section`, in the order they must be drawn.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn chunks(&self, section: usize) -> MutexGuard<'_, Vec<CommandChunk<G>>> {
+        lock(&self.chunks[section])
+    }
+
+    /// Upload the commands of the `queue` not uploaded yet to `section`.
+    ///
+    /// The commands which do not fit are dropped, and the queue is drained.
+    ///
+    /// # Returns
+    /// The amount of commands uploaded.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn upload(&self, section: usize, queue: &GpuCommandQueue<C, G>) -> usize {
+        let mut chunks = lock(&self.chunks[section]);
+        chunks.clear();
+
+        let mut view = self.commands.view_section_mut(section);
+        chunks.extend(queue.upload_chunks(&mut view));
+        let uploaded = chunks.last().map_or(0, |chunk| chunk.offset + chunk.len);
+        self.commands.set_length(section, uploaded as u32);
+
+        let dropped = queue.remaining();
+        if dropped > 0 {
+            queue.head.store(queue.len() as u32, Ordering::Release);
+            self.required
+                .fetch_max(uploaded + dropped, Ordering::Relaxed);
+
+            use tracing::Level;
+            tracing::event!(
+                name: "command.chunks.overflow",
+                Level::DEBUG,
+                "dropped up to {dropped} commands exceeding the capacity of {uploaded} commands"
+            );
+        }
+        uploaded
+    }
+
+    /// Draw the chunks uploaded to `section`, calling `bind` with the group
+    /// of each chunk before it is drawn.
+    ///
+    /// See [`GpuCommandDispatch::dispatch_chunks`] to draw them with another
+    /// primitive or element buffer.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn dispatch<F>(&self, section: usize, bind: F)
+    where
+        F: FnMut(Option<G>),
+    {
+        let chunks = lock(&self.chunks[section]);
+        GpuCommandDispatch::from_view(self.commands.view_section(section))
+            .dispatch_chunks(&chunks, bind);
+    }
+
+    /// The capacity fitting every command queued since the buffer was
+    /// created or last grown, doubled until it fits.
+    ///
+    /// # Returns
+    /// `None` if every command fit.
+    pub fn required_capacity(&self) -> Option<usize> {
+        required_capacity(self.capacity(), self.required.load(Ordering::Relaxed))
+    }
+
+    /// [`reallocate`](TriBuffer::reallocate) the command buffer to the
+    /// [`required_capacity`](Self::required_capacity), if any.
+    ///
+    /// The commands and chunks of every section are discarded: the sections
+    /// are drawn empty until the producer uploads them again.
+    ///
+    /// Requires exclusive access to the buffer and a GL context, see the
+    /// [module documentation](self).
+    ///
+    /// # Returns
+    /// Whether the buffer grew.
+    pub fn grow_to_fit(&mut self) -> bool {
+        let Some(capacity) = self.required_capacity() else {
+            return false;
+        };
+        self.commands.reallocate(capacity);
+        for chunks in &mut self.chunks {
+            chunks
+                .get_mut()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clear();
+        }
+        self.required.store(0, Ordering::Relaxed);
+        true
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// `capacity` doubled until it fits `required` commands, if it does not.
+fn required_capacity(capacity: usize, required: usize) -> Option<usize> {
+    if required <= capacity {
+        return None;
+    }
+    let mut grown = capacity.max(1);
+    while grown < required {
+        grown *= 2;
+    }
+    Some(grown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_to_required_capacity() {
+        assert_eq!(required_capacity(64, 0), None);
+        assert_eq!(required_capacity(64, 64), None);
+        assert_eq!(required_capacity(64, 65), Some(128));
+        assert_eq!(required_capacity(64, 300), Some(512));
+        assert_eq!(required_capacity(0, 3), Some(4));
+    }
+}
@@ -0,0 +1,309 @@
+//! Workgroup sizing and indirect dispatch of compute passes.
+//!
+//! The best workgroup size of a pass depends on the GPU: a multiple of the
+//! SIMD width (32 on most NVIDIA and recent AMD GPUs, 64 on older AMD ones),
+//! within the limits of the implementation. Compute shaders declared with
+//! [`shader_glsl_compute!`](crate::shader_glsl_compute) read their size from
+//! the `WORKGROUP_SIZE_X`, `_Y` and `_Z` preprocessor definitions, so that a
+//! [`WorkgroupSize`] picked at startup, from the [`ComputeLimits`] of the
+//! implementation or by benchmarking the candidates with [`Autotune`], is
+//! compiled in with `try_new_compiled_with_workgroup`.
+//!
+//! Passes whose amount of work is only known on the GPU, e.g. particles
+//! spawned by another pass, write a [`DispatchIndirectCommand`] instead, and
+//! are run with [`dispatch_indirect`].
+//!
+//! # Example
+//! ```rust,ignore
+//! let limits = ComputeLimits::query();
+//! let mut autotune = Autotune::new(WorkgroupSize::candidates(&limits));
+//! while let Some(size) = autotune.next_candidate() {
+//!     let shader = ComputeShaderParticles::try_new_compiled_with_workgroup(size.into())?;
+//!     shader.bind();
+//!     autotune.measure(size, 8, || shader.dispatch(size.groups_for(PARTICLES)));
+//! }
+//! let size = autotune.best().unwrap_or(WorkgroupSize::pick(&limits, 64));
+//! ```
+
+use std::time::Duration;
+
+/// The limits of compute workgroups of the GL implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputeLimits {
+    /// The maximum amount of invocations in a workgroup.
+    pub max_invocations: u32,
+    /// The maximum size of a workgroup along each axis.
+    pub max_size: [u32; 3],
+    /// The maximum amount of workgroups of a dispatch along each axis.
+    pub max_count: [u32; 3],
+}
+
+impl Default for ComputeLimits {
+    fn default() -> Self {
+        Self::GL_MINIMUM
+    }
+}
+
+impl ComputeLimits {
+    /// The minimum limits guaranteed by OpenGL 4.5.
+    pub const GL_MINIMUM: Self = Self {
+        max_invocations: 1024,
+        max_size: [1024, 1024, 64],
+        max_count: [65535, 65535, 65535],
+    };
+
+    /// Query the limits of the current context.
+    pub fn query() -> Self {
+        let mut limits = Self::GL_MINIMUM;
+        unsafe {
+            let mut value = 0;
+            janus::gl::GetIntegerv(janus::gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS, &mut value);
+            limits.max_invocations = value as u32;
+            for axis in 0..3 {
+                janus::gl::GetIntegeri_v(janus::gl::MAX_COMPUTE_WORK_GROUP_SIZE, axis, &mut value);
+                limits.max_size[axis as usize] = value as u32;
+                janus::gl::GetIntegeri_v(janus::gl::MAX_COMPUTE_WORK_GROUP_COUNT, axis, &mut value);
+                limits.max_count[axis as usize] = value as u32;
+            }
+        }
+        limits
+    }
+}
+
+/// The size of the workgroups of a compute shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WorkgroupSize {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl From<WorkgroupSize> for [u32; 3] {
+    fn from(size: WorkgroupSize) -> Self {
+        [size.x, size.y, size.z]
+    }
+}
+
+impl std::fmt::Display for WorkgroupSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}x{}", self.x, self.y, self.z)
+    }
+}
+
+impl WorkgroupSize {
+    /// A workgroup of `x` invocations along a single axis.
+    pub const fn linear(x: u32) -> Self {
+        Self { x, y: 1, z: 1 }
+    }
+
+    pub const fn invocations(self) -> u32 {
+        self.x * self.y * self.z
+    }
+
+    /// The linear size nearest to `preferred` within `limits`: the largest
+    /// power of two not above either of them, and at least 1.
+    pub fn pick(limits: &ComputeLimits, preferred: u32) -> Self {
+        let max = preferred
+            .min(limits.max_invocations)
+            .min(limits.max_size[0])
+            .max(1);
+        Self::linear(1 << max.ilog2())
+    }
+
+    /// The linear sizes worth benchmarking within `limits`: the powers of
+    /// two from 32 to 1024.
+    pub fn candidates(limits: &ComputeLimits) -> Vec<Self> {
+        let max = limits.max_invocations.min(limits.max_size[0]);
+        (5..=10)
+            .map(|exp| Self::linear(1 << exp))
+            .filter(|size| size.x <= max)
+            .collect()
+    }
+
+    /// Whether the size is within `limits`.
+    pub fn fits(self, limits: &ComputeLimits) -> bool {
+        self.invocations() <= limits.max_invocations
+            && self.x <= limits.max_size[0]
+            && self.y <= limits.max_size[1]
+            && self.z <= limits.max_size[2]
+    }
+
+    /// The amount of workgroups covering `items` invocations along the x
+    /// axis.
+    pub fn groups_for(self, items: u32) -> [u32; 3] {
+        [items.div_ceil(self.x), 1, 1]
+    }
+}
+
+/// The parameters of `glDispatchComputeIndirect`.
+///
+/// Corresponds to the `DispatchIndirectCommand` struct in a `std430` layout,
+/// e.g. written by a pass counting the work of the next one.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DispatchIndirectCommand {
+    pub num_groups_x: u32,
+    pub num_groups_y: u32,
+    pub num_groups_z: u32,
+}
+
+impl DispatchIndirectCommand {
+    /// The dispatch covering `items` invocations with workgroups of `size`.
+    pub fn for_items(items: u32, size: WorkgroupSize) -> Self {
+        let [x, y, z] = size.groups_for(items);
+        Self {
+            num_groups_x: x,
+            num_groups_y: y,
+            num_groups_z: z,
+        }
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct DispatchIndirectCommand for DispatchIndirectCommand {
+        num_groups_x: u32 => uint;
+        num_groups_y: u32 => uint;
+        num_groups_z: u32 => uint;
+    }
+}
+
+/// Dispatch the bound compute program with the [`DispatchIndirectCommand`]
+/// at the byte `offset` of `buffer`.
+///
+/// Commands written by shaders require a
+/// [`COMMAND`](super::barrier::Access::COMMAND) barrier first.
+pub fn dispatch_indirect(buffer: u32, offset: usize) {
+    unsafe {
+        janus::gl::BindBuffer(janus::gl::DISPATCH_INDIRECT_BUFFER, buffer);
+        janus::gl::DispatchComputeIndirect(offset as isize);
+    }
+}
+
+/// Benchmarks candidate workgroup sizes of a compute pass, keeping the
+/// fastest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Autotune {
+    pending: Vec<WorkgroupSize>,
+    results: Vec<(WorkgroupSize, Duration)>,
+}
+
+impl Autotune {
+    /// Benchmark the `candidates`, in order.
+    pub fn new(candidates: Vec<WorkgroupSize>) -> Self {
+        let mut pending = candidates;
+        pending.reverse();
+        Self {
+            pending,
+            results: Vec::new(),
+        }
+    }
+
+    /// The next size to measure, `None` once all were.
+    pub fn next_candidate(&mut self) -> Option<WorkgroupSize> {
+        self.pending.pop()
+    }
+
+    /// Record the GPU time of the pass with workgroups of `size`.
+    pub fn record(&mut self, size: WorkgroupSize, time: Duration) {
+        self.results.push((size, time));
+    }
+
+    /// Measure the GPU time of `dispatch`, issuing the pass with workgroups of
+    /// `size` with the compute program bound, as the best of `runs` runs.
+    ///
+    /// Blocks until the GPU has run every dispatch: meant for startup or a
+    /// benchmark mode.
+    pub fn measure(&mut self, size: WorkgroupSize, runs: u32, mut dispatch: impl FnMut()) {
+        use tracing::Level;
+
+        let mut query = 0;
+        let mut best = u64::MAX;
+        unsafe {
+            janus::gl::CreateQueries(janus::gl::TIME_ELAPSED, 1, &mut query);
+            // the first run warms up the caches and the driver
+            dispatch();
+            for _ in 0..runs.max(1) {
+                janus::gl::BeginQuery(janus::gl::TIME_ELAPSED, query);
+                dispatch();
+                janus::gl::EndQuery(janus::gl::TIME_ELAPSED);
+                let mut nanos = 0;
+                janus::gl::GetQueryObjectui64v(query, janus::gl::QUERY_RESULT, &mut nanos);
+                best = best.min(nanos);
+            }
+            janus::gl::DeleteQueries(1, &query);
+        }
+        let time = Duration::from_nanos(best);
+        tracing::event!(
+            name: "render.compute.autotune",
+            Level::DEBUG,
+            "Workgroup size {size} ran in {time:?}"
+        );
+        self.record(size, time);
+    }
+
+    /// The measured sizes, with their GPU time.
+    pub fn results(&self) -> &[(WorkgroupSize, Duration)] {
+        &self.results
+    }
+
+    /// The fastest measured size, the first one of equal times.
+    pub fn best(&self) -> Option<WorkgroupSize> {
+        self.results
+            .iter()
+            .min_by_key(|(_, time)| *time)
+            .map(|(size, _)| *size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shader::{ShaderComposer, ShadingVersion};
+
+    use super::*;
+
+    #[test]
+    fn pick_workgroup_sizes() {
+        let limits = ComputeLimits {
+            max_invocations: 256,
+            ..ComputeLimits::GL_MINIMUM
+        };
+        assert_eq!(WorkgroupSize::pick(&limits, 64), WorkgroupSize::linear(64));
+        assert_eq!(WorkgroupSize::pick(&limits, 100), WorkgroupSize::linear(64));
+        assert_eq!(
+            WorkgroupSize::pick(&limits, 4096),
+            WorkgroupSize::linear(256)
+        );
+        assert_eq!(WorkgroupSize::pick(&limits, 0), WorkgroupSize::linear(1));
+        assert_eq!(
+            WorkgroupSize::candidates(&limits)
+                .iter()
+                .map(|size| size.x)
+                .collect::<Vec<_>>(),
+            [32, 64, 128, 256]
+        );
+        assert!(!WorkgroupSize { x: 16, y: 32, z: 1 }.fits(&limits));
+        assert_eq!(
+            DispatchIndirectCommand::for_items(1000, WorkgroupSize::linear(64)).num_groups_x,
+            16
+        );
+
+        let mut autotune = Autotune::new(WorkgroupSize::candidates(&limits));
+        assert_eq!(autotune.best(), None);
+        while let Some(size) = autotune.next_candidate() {
+            // the fastest runs with 128 invocations
+            let time = Duration::from_micros(size.x.abs_diff(128) as u64 + 10);
+            autotune.record(size, time);
+        }
+        assert_eq!(autotune.results().len(), 4);
+        assert_eq!(autotune.best(), Some(WorkgroupSize::linear(128)));
+
+        let mut composer = ShaderComposer::new(ShadingVersion::core(450));
+        composer.set_workgroup_size(WorkgroupSize::linear(128).into());
+        assert!(composer.header().contains("#define WORKGROUP_SIZE_X 128\n"));
+        assert!(
+            composer
+                .header()
+                .contains("local_size_x = WORKGROUP_SIZE_X")
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{mesh, render::buffer::Layout};
+
+/// The default capacity multiplier applied to expected counts.
+pub const DEFAULT_HEADROOM: f32 = 1.25;
+
+/// Runtime configuration of GPU buffer capacities.
+///
+/// Rather than hard-coding the capacity of each buffer, capacities are derived
+/// from the expected amount of entities, meshes and vertices, with an
+/// additional `headroom` multiplier to absorb spikes.
+///
+/// The resulting layouts should be checked against the limits of the current
+/// GL implementation with [`BufferConfig::validate_layout`] once the GL
+/// context has been created.
+///
+/// # Example
+/// ```rust,ignore
+/// let config = BufferConfig::new()
+///     .entities(100_000)
+///     .meshes(64)
+///     .vertices(250_000);
+///
+/// let command_queue = GpuCommandQueue::with_capacity(config.command_capacity());
+/// let draw_commands = TriBuffer::zeroed(config.command_capacity());
+/// let entity_layout = LayoutEntityData::create_with([config.entity_capacity(); 3]);
+///
+/// config.validate_layout(&entity_layout)?;
+/// startup_handler.with_mesh_layout(config.mesh_layout());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BufferConfig {
+    entities: usize,
+    meshes: usize,
+    vertices: usize,
+    indices: usize,
+    commands: Option<usize>,
+    headroom: f32,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BufferConfig {
+    pub const fn new() -> Self {
+        Self {
+            entities: 0,
+            meshes: 0,
+            vertices: 0,
+            indices: 0,
+            commands: None,
+            headroom: DEFAULT_HEADROOM,
+        }
+    }
+
+    /// The expected amount of entities uploaded per frame.
+    pub const fn entities(mut self, count: usize) -> Self {
+        self.entities = count;
+        self
+    }
+
+    /// The expected amount of unique meshes.
+    pub const fn meshes(mut self, count: usize) -> Self {
+        self.meshes = count;
+        self
+    }
+
+    /// The expected amount of vertices across all meshes.
+    pub const fn vertices(mut self, count: usize) -> Self {
+        self.vertices = count;
+        self
+    }
+
+    /// The expected amount of indices across all indexed meshes.
+    pub const fn indices(mut self, count: usize) -> Self {
+        self.indices = count;
+        self
+    }
+
+    /// The expected amount of draw commands per frame.
+    ///
+    /// If this is not set, it is assumed that every entity may require its
+    /// own draw command.
+    pub const fn commands(mut self, count: usize) -> Self {
+        self.commands = Some(count);
+        self
+    }
+
+    /// The multiplier applied to all expected counts.
+    ///
+    /// # Panics
+    /// If `headroom` is lower than `1.0`.
+    pub fn headroom(mut self, headroom: f32) -> Self {
+        assert!(
+            headroom >= 1.0,
+            "buffer headroom must be at least 1.0, got {headroom}"
+        );
+        self.headroom = headroom;
+        self
+    }
+
+    fn scaled(&self, count: usize) -> usize {
+        (count as f64 * self.headroom as f64).ceil() as usize
+    }
+
+    pub fn entity_capacity(&self) -> usize {
+        self.scaled(self.entities)
+    }
+
+    /// The mesh metadata capacity.
+    ///
+    /// This includes the degenerate mesh at index `0`.
+    pub fn mesh_capacity(&self) -> usize {
+        self.scaled(self.meshes) + 1
+    }
+
+    pub fn vertex_capacity(&self) -> usize {
+        self.scaled(self.vertices)
+    }
+
+    pub fn index_capacity(&self) -> usize {
+        self.scaled(self.indices)
+    }
+
+    pub fn command_capacity(&self) -> usize {
+        self.scaled(self.commands.unwrap_or(self.entities))
+    }
+
+    /// Create the mesh storage layout with the configured mesh, vertex and
+    /// index capacities.
+    ///
+    /// This is equivalent to the layout created by
+    /// [`layout_mesh_buffer!`](crate::layout_mesh_buffer).
+    pub fn mesh_layout(&self) -> Layout<3> {
+        Layout::<3>::new()
+            .partition::<u32>(self.index_capacity())
+            .partition::<mesh::Vertex>(self.vertex_capacity())
+            .with_shader_storage(mesh::SHADER_BINDING_VERTEX_BUFFER)
+            .partition::<mesh::Metadata>(self.mesh_capacity())
+            .with_shader_storage(mesh::SHADER_BINDING_MESH_METADATA)
+    }
+
+    /// Validate all partitions of `layout` against the limits of the current
+    /// GL implementation.
+    ///
+    /// If the GL context has not been initialised yet, this always succeeds.
+    pub fn validate_layout<const PARTS: usize>(
+        &self,
+        layout: &Layout<PARTS>,
+    ) -> Result<(), BufferConfigError> {
+        let Some(limits) = GlBufferLimits::query() else {
+            return Ok(());
+        };
+        limits.validate_layout(layout)
+    }
+}
+
+/// The buffer size limits of the current GL implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlBufferLimits {
+    /// `GL_MAX_SHADER_STORAGE_BLOCK_SIZE`, in bytes.
+    pub max_shader_storage_block_size: usize,
+}
+
+impl GlBufferLimits {
+    /// Query the limits of the current GL context.
+    ///
+    /// Returns `None` if the GL context has not been initialised.
+    pub fn query() -> Option<Self> {
+        if !janus::gl::has_gl_init() {
+            return None;
+        }
+
+        let mut max_block_size = 0i64;
+        unsafe {
+            janus::gl::GetInteger64v(
+                janus::gl::MAX_SHADER_STORAGE_BLOCK_SIZE,
+                &mut max_block_size,
+            );
+        }
+
+        Some(Self {
+            max_shader_storage_block_size: max_block_size.max(0) as usize,
+        })
+    }
+
+    /// Validate the length of every partition of `layout` bound to an SSBO.
+    pub fn validate_layout<const PARTS: usize>(
+        &self,
+        layout: &Layout<PARTS>,
+    ) -> Result<(), BufferConfigError> {
+        for partition in 0..PARTS {
+            if layout.ssbo_of(partition).is_none() {
+                continue;
+            }
+
+            let length = layout.length_at(partition);
+            if length > self.max_shader_storage_block_size {
+                return Err(BufferConfigError::StorageBlockTooLarge {
+                    partition,
+                    length,
+                    limit: self.max_shader_storage_block_size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferConfigError {
+    /// A partition bound to an SSBO exceeds
+    /// `GL_MAX_SHADER_STORAGE_BLOCK_SIZE`.
+    StorageBlockTooLarge {
+        partition: usize,
+        length: usize,
+        limit: usize,
+    },
+}
+
+impl std::fmt::Display for BufferConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StorageBlockTooLarge {
+                partition,
+                length,
+                limit,
+            } => write!(
+                f,
+                "partition {partition} has a length of {length} bytes, exceeding the shader storage block limit of {limit} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BufferConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_capacities() {
+        let config = BufferConfig::new()
+            .entities(1000)
+            .meshes(10)
+            .vertices(4000)
+            .headroom(1.5);
+
+        assert_eq!(config.entity_capacity(), 1500);
+        assert_eq!(config.mesh_capacity(), 16);
+        assert_eq!(config.vertex_capacity(), 6000);
+        assert_eq!(config.command_capacity(), 1500);
+        assert_eq!(config.commands(10).command_capacity(), 15);
+
+        let layout = config.mesh_layout();
+        assert_eq!(layout.length_at(0), 0);
+        assert_eq!(layout.length_at(1), 6000 * size_of::<mesh::Vertex>());
+        assert_eq!(layout.length_at(2), 16 * size_of::<mesh::Metadata>());
+        assert_eq!(config.indices(100).mesh_layout().length_at(0), 600);
+
+        let limits = GlBufferLimits {
+            max_shader_storage_block_size: 6000 * size_of::<mesh::Vertex>(),
+        };
+        assert!(limits.validate_layout(&layout).is_ok());
+
+        let layout = config.vertices(5000).mesh_layout();
+        assert_eq!(
+            limits.validate_layout(&layout),
+            Err(BufferConfigError::StorageBlockTooLarge {
+                partition: 1,
+                length: 7500 * size_of::<mesh::Vertex>(),
+                limit: limits.max_shader_storage_block_size,
+            })
+        );
+    }
+}
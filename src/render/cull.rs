@@ -0,0 +1,473 @@
+//! Frustum culling and level of detail selection on the GPU.
+//!
+//! Instead of culling entities and packing the survivors into contiguous
+//! instance ranges on the CPU every upload, the CPU uploads one [`CullInput`]
+//! per entity, in the same order as the other per-entity partitions, and one
+//! draw command per bucket (e.g. per mesh and level of detail) prepared with
+//! [`prepare_commands`]. Every frame, [`GpuCuller::dispatch`] runs a compute
+//! shader which tests the bounding sphere of each entity against the view
+//! frustum, selects its level of detail from its distance to the camera,
+//! and appends its id to the instance range of its bucket, incrementing the
+//! `instance_count` of the draw command in place.
+//!
+//! Vertex shaders then map their instance to the id of the entity with
+//! [`GLSL_LIB_CULLED_ENTITY`], and fetch the per-entity data with it.
+//!
+//! The amount of draw commands is fixed: empty buckets are drawn with zero
+//! instances, as `glMultiDrawArraysIndirectCount` is not part of GL 4.5. The
+//! amount of surviving entities is still counted in the visible block, for
+//! debugging.
+//!
+//! Occlusion culling requires a depth pyramid of the previous frame, which
+//! the renderer does not build: only frustum culling is performed.
+//!
+//! # Example
+//! ```rust,ignore
+//! // UPLOAD
+//! let inputs = positions.iter().zip(families).map(|(&position, &family)| {
+//!     CullInput::new(position, radius, family * LODS, LODS)
+//! });
+//! let capacity = cull::prepare_commands(&mut commands, &capacities);
+//!
+//! // RENDER
+//! frame_data.instances.bind_shader_storage(section.as_index());
+//! culler.dispatch(&frame_data.draw_commands, section.as_index(), &params);
+//! shader.bind();
+//! GpuCommandDispatch::from_view(commands).dispatch();
+//! ```
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{
+    render::{
+        NotSend, buffer::TriBuffer, command::DrawArraysIndirectCommand, compute::WorkgroupSize,
+    },
+    shader::{
+        GlslUniform, ShaderProgram,
+        glsl::{GlslLib, GlslStorage},
+    },
+};
+
+macro_rules! ssbo_binding {
+    (CullInputs) => {
+        16
+    };
+    (CullCommands) => {
+        17
+    };
+    (CullVisible) => {
+        18
+    };
+}
+
+pub const SHADER_BINDING_CULL_INPUTS: u32 = ssbo_binding!(CullInputs);
+pub const SHADER_BINDING_CULL_COMMANDS: u32 = ssbo_binding!(CullCommands);
+pub const SHADER_BINDING_CULL_VISIBLE: u32 = ssbo_binding!(CullVisible);
+
+/// The culling data of an entity.
+///
+/// Corresponds to the `CullInput` struct of [`GLSL_SSBO_CULL_INPUTS`] in a
+/// `std430` layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CullInput {
+    /// The center of the bounding sphere in world space, and its radius.
+    pub sphere: [f32; 4],
+    /// The draw command of the most detailed level of the entity, or
+    /// `u32::MAX` if the entity is never drawn.
+    pub bucket: u32,
+    /// The amount of levels of detail, at consecutive draw commands from
+    /// `bucket`.
+    pub lods: u32,
+    _pad: [u32; 2],
+}
+
+impl CullInput {
+    /// An entity bounded by the sphere at `center` of `radius`, drawn by the
+    /// commands from `first_bucket` to `first_bucket + lods - 1`, from the
+    /// most to the least detailed.
+    pub fn new(center: Vec3, radius: f32, first_bucket: u32, lods: u32) -> Self {
+        Self {
+            sphere: center.extend(radius).to_array(),
+            bucket: first_bucket,
+            lods: lods.max(1),
+            _pad: [0; 2],
+        }
+    }
+
+    /// An entity which is never drawn, e.g. a hidden one.
+    pub const fn skipped() -> Self {
+        Self {
+            sphere: [0.0; 4],
+            bucket: u32::MAX,
+            lods: 1,
+            _pad: [0; 2],
+        }
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct CullInput for CullInput {
+        sphere: [f32; 4] => vec4;
+        bucket: u32 => uint;
+        lods: u32 => uint;
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct DrawArraysIndirectCommand for DrawArraysIndirectCommand {
+        count: u32 => uint;
+        instance_count: u32 => uint;
+        first_vertex: u32 => uint;
+        base_instance: u32 => uint;
+    }
+}
+
+/// Culling inputs SSBO interface.
+///
+/// Contains the SSBO declaration of a [`CullInput`] partition, on binding
+/// index 16. Requires the `CullInput` struct definition, see
+/// [`CullInputGlslStruct::as_definition`].
+pub const GLSL_SSBO_CULL_INPUTS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf CullInputs => {
+        [dyn_array CullInput: cull_inputs]
+    }
+};
+
+/// Contains the SSBO declaration of the draw commands written by the culling
+/// shader, on binding index 17.
+pub const GLSL_SSBO_CULL_COMMANDS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf CullCommands => {
+        [dyn_array DrawArraysIndirectCommand: cull_commands]
+    }
+};
+
+/// Contains the SSBO declaration of the ids of the surviving entities, on
+/// binding index 18, in the instance ranges of their draw command.
+pub const GLSL_SSBO_CULL_VISIBLE: GlslStorage = crate::shader_glsl_ssbo! {
+    buf CullVisible => {
+        uint: visible_count;
+        [dyn_array uint: visible_entities]
+    }
+};
+
+/// The id of the entity drawn as `instance`, i.e.
+/// `gl_BaseInstance + gl_InstanceID`, by commands written by the culling
+/// shader. Requires [`GLSL_SSBO_CULL_VISIBLE`].
+pub const GLSL_LIB_CULLED_ENTITY: GlslLib = crate::shader_glsl_lib! {
+    uint culled_entity [ instance: uint ] => "
+        return visible_entities[instance];
+    "
+};
+
+crate::shader_glsl_compute! {
+    struct Cull > [460] {
+        workgroup [64, 1, 1];
+
+        uniform {
+            view_projection: mat4 => Mat4;
+            camera: vec4 => Vec4;
+            lod_distances: vec4 => Vec4;
+            entity_count: uint => u32;
+        };
+        type {
+            CullInputGlslStruct::as_definition()
+            DrawArraysIndirectCommandGlslStruct::as_definition()
+        };
+        ssbo {
+            GLSL_SSBO_CULL_INPUTS
+            GLSL_SSBO_CULL_COMMANDS
+            GLSL_SSBO_CULL_VISIBLE
+        };
+
+        src() "
+            uint id = gl_GlobalInvocationID.x;
+            if (id >= entity_count) {
+                return;
+            }
+            CullInput entity = cull_inputs[id];
+            if (entity.bucket == 0xFFFFFFFFu) {
+                return;
+            }
+
+            // side and near planes, the projection has an infinite far plane
+            mat4 rows = transpose(view_projection);
+            vec4 planes[5] = vec4[](
+                rows[3] + rows[0],
+                rows[3] - rows[0],
+                rows[3] + rows[1],
+                rows[3] - rows[1],
+                rows[3] - rows[2]
+            );
+            for (int i = 0; i < 5; i++) {
+                vec4 plane = planes[i] / length(planes[i].xyz);
+                if (dot(plane.xyz, entity.sphere.xyz) + plane.w < -entity.sphere.w) {
+                    return;
+                }
+            }
+
+            float camera_distance = distance(entity.sphere.xyz, camera.xyz);
+            uint lod = uint(dot(step(lod_distances, vec4(camera_distance)), vec4(1.0)));
+            uint command = entity.bucket + min(lod, entity.lods - 1u);
+
+            uint slot = atomicAdd(cull_commands[command].instance_count, 1u);
+            uint instance = cull_commands[command].base_instance + slot;
+            if (instance < visible_entities.length()) {
+                visible_entities[instance] = id;
+            }
+            atomicAdd(visible_count, 1u);
+        "
+    }
+}
+
+crate::shader_glsl_compute! {
+    struct CullReset > [460] {
+        workgroup [64, 1, 1];
+
+        uniform {
+            command_count: uint => u32;
+        };
+        type {
+            DrawArraysIndirectCommandGlslStruct::as_definition()
+        };
+        ssbo {
+            GLSL_SSBO_CULL_COMMANDS
+        };
+
+        src() "
+            uint id = gl_GlobalInvocationID.x;
+            if (id < command_count) {
+                cull_commands[id].instance_count = 0u;
+            }
+        "
+    }
+}
+
+/// Reset the instance counts of `commands` and lay out their instance
+/// ranges, before they are filled by [`GpuCuller::dispatch`].
+///
+/// The dispatch resets the instance counts on the GPU too, so that a section
+/// culled again, e.g. when no newer section was uploaded, does not count the
+/// entities on top of the previous dispatch.
+///
+/// The command at index `i` can draw up to `capacities[i]` instances, e.g.
+/// the amount of entities which can select it. Commands without a capacity
+/// draw no instance.
+///
+/// # Returns
+/// The total amount of instances, i.e. the capacity required by the
+/// [`GpuCuller`].
+pub fn prepare_commands(commands: &mut [DrawArraysIndirectCommand], capacities: &[u32]) -> u32 {
+    let mut base_instance = 0;
+    for (i, command) in commands.iter_mut().enumerate() {
+        command.instance_count = 0;
+        command.base_instance = base_instance;
+        base_instance += capacities.get(i).copied().unwrap_or(0);
+    }
+    base_instance
+}
+
+/// The view and the entities to cull in a [`GpuCuller::dispatch`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CullParams {
+    pub view_projection: Mat4,
+    pub camera: Vec3,
+    /// The maximum distance from the camera of each level of detail but the
+    /// last one, in ascending order. Unused levels must be `f32::INFINITY`.
+    pub lod_distances: Vec4,
+    /// The amount of [`CullInput`]s to test.
+    pub entity_count: u32,
+}
+
+impl CullParams {
+    /// Cull `entity_count` entities seen through `view_projection` from
+    /// `camera`, with up to 4 levels of detail at `lod_distances`.
+    ///
+    /// # Panics
+    /// If more than 3 distances are given.
+    pub fn new(
+        view_projection: Mat4,
+        camera: Vec3,
+        lod_distances: &[f32],
+        entity_count: u32,
+    ) -> Self {
+        assert!(
+            lod_distances.len() < 4,
+            "GPU culling supports up to 4 levels of detail"
+        );
+        let mut distances = [f32::INFINITY; 4];
+        distances[..lod_distances.len()].copy_from_slice(lod_distances);
+        Self {
+            view_projection,
+            camera,
+            lod_distances: Vec4::from_array(distances),
+            entity_count,
+        }
+    }
+}
+
+/// The compute pipeline culling entities into draw commands.
+///
+/// The ids of the surviving entities are written to a buffer local to the
+/// GPU, bound to [`SHADER_BINDING_CULL_VISIBLE`] by
+/// [`GpuCuller::dispatch`], which is read by vertex shaders through
+/// [`GLSL_LIB_CULLED_ENTITY`].
+#[derive(Debug)]
+pub struct GpuCuller {
+    shader: ComputeShaderCull,
+    reset: ComputeShaderCullReset,
+    workgroup: WorkgroupSize,
+    visible: u32,
+    capacity: usize,
+
+    _marker: NotSend,
+}
+
+impl GpuCuller {
+    /// Compile the culling shader and allocate room for `capacity` visible
+    /// entities, as returned by [`prepare_commands`].
+    ///
+    /// Entities culled into an instance range beyond the capacity are
+    /// dropped.
+    pub fn new(capacity: usize) -> Self {
+        let [x, ..] = ComputeShaderCull::WORKGROUP;
+        Self::with_workgroup(capacity, WorkgroupSize::linear(x))
+    }
+
+    /// Like [`Self::new`], with the culling shader compiled for linear
+    /// workgroups of `workgroup`, e.g. picked with
+    /// [`WorkgroupSize::pick`].
+    ///
+    /// # Panics
+    /// If `workgroup` is not linear, or the shader fails to compile.
+    pub fn with_workgroup(capacity: usize, workgroup: WorkgroupSize) -> Self {
+        assert!(
+            workgroup.y == 1 && workgroup.z == 1,
+            "the culling shader requires linear workgroups, not {workgroup}"
+        );
+        let shader = ComputeShaderCull::try_new_compiled_with_workgroup(workgroup.into())
+            .unwrap_or_else(|err| panic!("{err}"));
+        let reset = ComputeShaderCullReset::try_new_compiled_with_workgroup(workgroup.into())
+            .unwrap_or_else(|err| panic!("{err}"));
+
+        let mut visible = 0;
+        let size = (capacity + 1) * size_of::<u32>();
+        unsafe {
+            janus::gl::CreateBuffers(1, &mut visible);
+            janus::gl::NamedBufferStorage(visible, size as isize, std::ptr::null(), 0);
+        }
+
+        Self {
+            shader,
+            reset,
+            workgroup,
+            visible,
+            capacity,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    pub fn workgroup(&self) -> WorkgroupSize {
+        self.workgroup
+    }
+
+    /// The maximum amount of visible entities.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Cull the entities of the bound [`GLSL_SSBO_CULL_INPUTS`] partition
+    /// into `section` of `commands`, prepared with [`prepare_commands`].
+    ///
+    /// The instance counts of the commands are reset before culling, the
+    /// same section can be dispatched again.
+    ///
+    /// Issues a memory barrier: the commands can be drawn right after, and
+    /// the visible entities read by vertex shaders.
+    pub fn dispatch(
+        &self,
+        commands: &TriBuffer<DrawArraysIndirectCommand>,
+        section: usize,
+        params: &CullParams,
+    ) {
+        let zero = 0u32;
+        unsafe {
+            janus::gl::ClearNamedBufferSubData(
+                self.visible,
+                janus::gl::R32UI,
+                0,
+                size_of::<u32>() as isize,
+                janus::gl::RED_INTEGER,
+                janus::gl::UNSIGNED_INT,
+                &zero as *const u32 as *const _,
+            );
+            janus::gl::BindBufferBase(
+                janus::gl::SHADER_STORAGE_BUFFER,
+                SHADER_BINDING_CULL_VISIBLE,
+                self.visible,
+            );
+        }
+        commands.bind_shader_storage(section, SHADER_BINDING_CULL_COMMANDS, 0);
+
+        let command_count = commands.length(section) as u32;
+        if command_count > 0 {
+            self.reset.bind();
+            self.reset.uniform_command_count_uint(command_count);
+            self.reset
+                .dispatch(self.workgroup.groups_for(command_count));
+            unsafe { janus::gl::MemoryBarrier(janus::gl::SHADER_STORAGE_BARRIER_BIT) };
+        }
+        if params.entity_count == 0 {
+            return;
+        }
+
+        self.shader.bind();
+        self.shader
+            .uniform_view_projection_mat4(params.view_projection);
+        self.shader.uniform_camera_vec4(params.camera.extend(1.0));
+        self.shader.uniform_lod_distances_vec4(params.lod_distances);
+        self.shader.uniform_entity_count_uint(params.entity_count);
+        self.shader
+            .dispatch(self.workgroup.groups_for(params.entity_count));
+
+        unsafe {
+            janus::gl::MemoryBarrier(
+                janus::gl::COMMAND_BARRIER_BIT | janus::gl::SHADER_STORAGE_BARRIER_BIT,
+            );
+        }
+    }
+}
+
+impl Drop for GpuCuller {
+    fn drop(&mut self) {
+        unsafe {
+            janus::gl::DeleteBuffers(1, &self.visible);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_cull_commands() {
+        let mut commands = [DrawArraysIndirectCommand {
+            count: 36,
+            instance_count: 7,
+            ..Default::default()
+        }; 4];
+        let total = prepare_commands(&mut commands, &[10, 0, 5]);
+        assert_eq!(total, 15);
+        assert_eq!(commands.map(|c| c.base_instance), [0, 10, 10, 15]);
+        assert!(commands.iter().all(|c| c.instance_count == 0));
+        assert_eq!(commands[0].count, 36);
+
+        assert_eq!(
+            CullInputGlslStruct::std430_layout().map(|layout| layout.stride()),
+            Some(size_of::<CullInput>())
+        );
+        let params = CullParams::new(Mat4::IDENTITY, Vec3::ZERO, &[80.0, 250.0], 1);
+        assert_eq!(params.lod_distances.z, f32::INFINITY);
+    }
+}
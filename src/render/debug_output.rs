@@ -0,0 +1,385 @@
+//! Driver diagnostics through the GL debug output (`KHR_debug`).
+//!
+//! [`install`] hooks a `glDebugMessageCallback` forwarding every message of
+//! the driver to `tracing`, as the `render.debug.gl` event, with a level
+//! mapped from its [`DebugSeverity`]. Unlike polling `glGetError`, messages
+//! describe the failing call and are raised as it happens, along with
+//! performance warnings some drivers emit.
+//!
+//! The [`DebugOutput`] selects the messages: those below a minimum severity
+//! are disabled in the driver, and known noisy message IDs (e.g. the buffer
+//! placement notifications of NVIDIA drivers) can be ignored. Errors are
+//! reported to [`capture`](super::capture) as failures.
+//!
+//! In debug builds, [`DebugOutput::with_break_on_error`] makes the output
+//! synchronous and aborts on the first error, so that a debugger stops in
+//! the offending call. It is enabled by the `ETHEL_GL_BREAK_ON_ERROR`
+//! environment variable, see [`DebugOutput::from_env`].
+//!
+//! Messages are only guaranteed from debug contexts, requested by the
+//! platform layer when the window is created. Without a debug context, or
+//! until the output is installed, the GL errors of each frame are drained
+//! with `glGetError` at its end instead, in debug builds, see
+//! [`drain_errors`].
+//!
+//! # Example
+//! ```rust,ignore
+//! startup_handler.with_debug_output(Some(
+//!     DebugOutput::from_env()
+//!         .with_min_severity(DebugSeverity::Medium)
+//!         .ignore(131185),
+//! ));
+//! ```
+
+use std::{
+    ffi::{c_char, c_void},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::render::capture::{self, CaptureTrigger};
+
+/// The environment variable enabling [`DebugOutput::with_break_on_error`].
+pub const ENV_GL_BREAK_ON_ERROR: &str = "ETHEL_GL_BREAK_ON_ERROR";
+
+static BREAK_ON_ERROR: AtomicBool = AtomicBool::new(false);
+/// Whether the output is installed on a debug context.
+static FORWARDING: AtomicBool = AtomicBool::new(false);
+
+const SOURCES: [u32; 6] = [
+    janus::gl::DEBUG_SOURCE_API,
+    janus::gl::DEBUG_SOURCE_WINDOW_SYSTEM,
+    janus::gl::DEBUG_SOURCE_SHADER_COMPILER,
+    janus::gl::DEBUG_SOURCE_THIRD_PARTY,
+    janus::gl::DEBUG_SOURCE_APPLICATION,
+    janus::gl::DEBUG_SOURCE_OTHER,
+];
+
+const KINDS: [u32; 9] = [
+    janus::gl::DEBUG_TYPE_ERROR,
+    janus::gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR,
+    janus::gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR,
+    janus::gl::DEBUG_TYPE_PORTABILITY,
+    janus::gl::DEBUG_TYPE_PERFORMANCE,
+    janus::gl::DEBUG_TYPE_MARKER,
+    janus::gl::DEBUG_TYPE_PUSH_GROUP,
+    janus::gl::DEBUG_TYPE_POP_GROUP,
+    janus::gl::DEBUG_TYPE_OTHER,
+];
+
+/// The severity of a debug message, from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DebugSeverity {
+    /// Mapped to [`Level::DEBUG`](tracing::Level::DEBUG).
+    Notification,
+    /// Mapped to [`Level::INFO`](tracing::Level::INFO).
+    Low,
+    /// Mapped to [`Level::WARN`](tracing::Level::WARN).
+    Medium,
+    /// Mapped to [`Level::ERROR`](tracing::Level::ERROR).
+    High,
+}
+
+impl DebugSeverity {
+    pub const ALL: [Self; 4] = [Self::Notification, Self::Low, Self::Medium, Self::High];
+
+    pub const fn from_gl(severity: u32) -> Option<Self> {
+        match severity {
+            janus::gl::DEBUG_SEVERITY_NOTIFICATION => Some(Self::Notification),
+            janus::gl::DEBUG_SEVERITY_LOW => Some(Self::Low),
+            janus::gl::DEBUG_SEVERITY_MEDIUM => Some(Self::Medium),
+            janus::gl::DEBUG_SEVERITY_HIGH => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    pub const fn to_gl(self) -> u32 {
+        match self {
+            Self::Notification => janus::gl::DEBUG_SEVERITY_NOTIFICATION,
+            Self::Low => janus::gl::DEBUG_SEVERITY_LOW,
+            Self::Medium => janus::gl::DEBUG_SEVERITY_MEDIUM,
+            Self::High => janus::gl::DEBUG_SEVERITY_HIGH,
+        }
+    }
+}
+
+/// Which debug messages are forwarded, and what happens on errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugOutput {
+    min_severity: DebugSeverity,
+    ignored: Vec<u32>,
+    break_on_error: bool,
+}
+
+impl Default for DebugOutput {
+    fn default() -> Self {
+        Self {
+            min_severity: DebugSeverity::Low,
+            ignored: Vec::new(),
+            break_on_error: false,
+        }
+    }
+}
+
+impl DebugOutput {
+    /// The default output, breaking on errors if the
+    /// `ETHEL_GL_BREAK_ON_ERROR` environment variable is set to anything but
+    /// `0`.
+    pub fn from_env() -> Self {
+        let break_on_error =
+            std::env::var_os(ENV_GL_BREAK_ON_ERROR).is_some_and(|value| value != "0");
+        Self::default().with_break_on_error(break_on_error)
+    }
+
+    /// Only forward the messages of `severity` and above.
+    pub fn with_min_severity(mut self, severity: DebugSeverity) -> Self {
+        self.min_severity = severity;
+        self
+    }
+
+    /// Ignore the messages of `id`, whatever their source.
+    pub fn ignore(mut self, id: u32) -> Self {
+        if !self.ignored.contains(&id) {
+            self.ignored.push(id);
+        }
+        self
+    }
+
+    /// Abort on the first error message, with a synchronous output so that
+    /// the stack is that of the failing call.
+    ///
+    /// Ignored in release builds.
+    pub fn with_break_on_error(mut self, enabled: bool) -> Self {
+        self.break_on_error = enabled;
+        self
+    }
+
+    pub fn min_severity(&self) -> DebugSeverity {
+        self.min_severity
+    }
+
+    pub fn ignored(&self) -> &[u32] {
+        &self.ignored
+    }
+
+    /// Whether errors abort, which is never the case in release builds.
+    pub fn breaks_on_error(&self) -> bool {
+        cfg!(debug_assertions) && self.break_on_error
+    }
+
+    /// Whether a message of `id` and `severity` is forwarded.
+    pub fn forwards(&self, id: u32, severity: DebugSeverity) -> bool {
+        severity >= self.min_severity && !self.ignored.contains(&id)
+    }
+}
+
+/// Enable the debug output of the current context, forwarding messages as
+/// selected by `output`.
+///
+/// Installing again replaces the previous selection.
+///
+/// # Returns
+/// Whether the context is a debug context, which guarantees messages. The
+/// errors of other contexts keep being drained with [`drain_errors`].
+pub fn install(output: &DebugOutput) -> bool {
+    BREAK_ON_ERROR.store(output.breaks_on_error(), Ordering::Relaxed);
+
+    let mut flags = 0;
+    unsafe {
+        janus::gl::GetIntegerv(janus::gl::CONTEXT_FLAGS, &mut flags);
+        janus::gl::Enable(janus::gl::DEBUG_OUTPUT);
+        if output.breaks_on_error() {
+            janus::gl::Enable(janus::gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        } else {
+            janus::gl::Disable(janus::gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        }
+        janus::gl::DebugMessageCallback(Some(forward), std::ptr::null());
+
+        // filter in the driver, which skips building the messages
+        janus::gl::DebugMessageControl(
+            janus::gl::DONT_CARE,
+            janus::gl::DONT_CARE,
+            janus::gl::DONT_CARE,
+            0,
+            std::ptr::null(),
+            janus::gl::TRUE,
+        );
+        for severity in DebugSeverity::ALL {
+            if severity < output.min_severity {
+                janus::gl::DebugMessageControl(
+                    janus::gl::DONT_CARE,
+                    janus::gl::DONT_CARE,
+                    severity.to_gl(),
+                    0,
+                    std::ptr::null(),
+                    janus::gl::FALSE,
+                );
+            }
+        }
+        // ids are only unique per source and type
+        if !output.ignored.is_empty() {
+            for source in SOURCES {
+                for kind in KINDS {
+                    janus::gl::DebugMessageControl(
+                        source,
+                        kind,
+                        janus::gl::DONT_CARE,
+                        output.ignored.len() as i32,
+                        output.ignored.as_ptr(),
+                        janus::gl::FALSE,
+                    );
+                }
+            }
+        }
+    }
+
+    let debug_context = flags as u32 & janus::gl::CONTEXT_FLAG_DEBUG_BIT != 0;
+    FORWARDING.store(debug_context, Ordering::Relaxed);
+    if !debug_context {
+        use tracing::Level;
+        tracing::event!(
+            name: "render.debug.no_debug_context",
+            Level::DEBUG,
+            "GL context is not a debug context, GL errors are drained at the end of each frame"
+        );
+    }
+    debug_context
+}
+
+/// Disable the debug output of the current context.
+pub fn uninstall() {
+    FORWARDING.store(false, Ordering::Relaxed);
+    unsafe {
+        janus::gl::DebugMessageCallback(None, std::ptr::null());
+        janus::gl::Disable(janus::gl::DEBUG_OUTPUT);
+    }
+}
+
+/// Whether GL errors are forwarded by the debug output as they are raised,
+/// i.e. it is [installed](install) on a debug context.
+pub fn is_forwarding() -> bool {
+    FORWARDING.load(Ordering::Relaxed)
+}
+
+/// Drain and log the pending GL errors, unless they are forwarded by the
+/// debug output, see [`is_forwarding`].
+///
+/// Called by the default [`RenderStage::present`](super::stage::RenderStage::present)
+/// in debug builds.
+pub fn drain_errors() {
+    if is_forwarding() {
+        return;
+    }
+    loop {
+        let err = unsafe { janus::gl::GetError() };
+        if err == janus::gl::NO_ERROR {
+            break;
+        }
+
+        use tracing::Level;
+        tracing::event!(
+            name: "render.debug.gl_err",
+            Level::DEBUG,
+            "gl error: {err}"
+        );
+        capture::report_failure(CaptureTrigger::GlError);
+    }
+}
+
+const fn source_str(source: u32) -> &'static str {
+    match source {
+        janus::gl::DEBUG_SOURCE_API => "api",
+        janus::gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        janus::gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        janus::gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        janus::gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+const fn kind_str(kind: u32) -> &'static str {
+    match kind {
+        janus::gl::DEBUG_TYPE_ERROR => "error",
+        janus::gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behaviour",
+        janus::gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behaviour",
+        janus::gl::DEBUG_TYPE_PORTABILITY => "portability",
+        janus::gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        janus::gl::DEBUG_TYPE_MARKER => "marker",
+        janus::gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        janus::gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+extern "system" fn forward(
+    source: u32,
+    kind: u32,
+    id: u32,
+    severity: u32,
+    length: i32,
+    message: *const c_char,
+    _user: *mut c_void,
+) {
+    use tracing::Level;
+
+    let message = if message.is_null() {
+        std::borrow::Cow::Borrowed("")
+    } else if length < 0 {
+        unsafe { std::ffi::CStr::from_ptr(message) }.to_string_lossy()
+    } else {
+        let bytes = unsafe { std::slice::from_raw_parts(message.cast::<u8>(), length as usize) };
+        String::from_utf8_lossy(bytes)
+    };
+    let source = source_str(source);
+    let kind_name = kind_str(kind);
+
+    macro_rules! forward_event {
+        ($level:expr) => {
+            tracing::event!(
+                name: "render.debug.gl",
+                $level,
+                "gl {kind_name} #{id} from {source}: {message}"
+            )
+        };
+    }
+    match DebugSeverity::from_gl(severity) {
+        Some(DebugSeverity::High) => forward_event!(Level::ERROR),
+        Some(DebugSeverity::Medium) => forward_event!(Level::WARN),
+        Some(DebugSeverity::Low) => forward_event!(Level::INFO),
+        Some(DebugSeverity::Notification) | None => forward_event!(Level::DEBUG),
+    }
+
+    if kind == janus::gl::DEBUG_TYPE_ERROR {
+        capture::report_failure(CaptureTrigger::GlError);
+        if BREAK_ON_ERROR.load(Ordering::Relaxed) {
+            // unwinding out of the callback is not allowed
+            std::process::abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_output_selection() {
+        assert!(DebugSeverity::Notification < DebugSeverity::High);
+        for severity in DebugSeverity::ALL {
+            assert_eq!(DebugSeverity::from_gl(severity.to_gl()), Some(severity));
+        }
+        assert_eq!(DebugSeverity::from_gl(janus::gl::DONT_CARE), None);
+
+        let output = DebugOutput::default()
+            .with_min_severity(DebugSeverity::Medium)
+            .ignore(131185)
+            .ignore(131185);
+        assert_eq!(output.ignored(), [131185]);
+        assert!(output.forwards(1282, DebugSeverity::High));
+        assert!(!output.forwards(131185, DebugSeverity::High));
+        assert!(!output.forwards(1282, DebugSeverity::Low));
+
+        let breaking = output.with_break_on_error(true);
+        assert_eq!(breaking.breaks_on_error(), cfg!(debug_assertions));
+        assert_eq!(source_str(janus::gl::DEBUG_SOURCE_API), "api");
+    }
+}
@@ -0,0 +1,237 @@
+//! Depth buffer and depth testing of the [`Renderer`](super::Renderer).
+//!
+//! Frames are rendered to an offscreen [`DepthTarget`], made of a colour and
+//! a 32 bit float depth and 8 bit stencil renderbuffer sized to the
+//! resolution, which is cleared at the start of the frame following the
+//! [`ClearPolicy`] of the renderer and resolved to the default framebuffer
+//! before the [`present`](super::stage::RenderStage::present) stage.
+//!
+//! The perspective projection of the [`ScreenSpace`](super::ScreenSpace) maps
+//! the near plane to a depth of `1.0` and infinity to `0.0`: with
+//! [`DepthMode::ReverseZ`], the clip space depth range is set to `[0, 1]`
+//! and nearer fragments pass with `GL_GREATER`, which spreads the precision
+//! of the float depth buffer evenly over the whole view distance.
+
+use crate::render::{NotSend, clear::ClearPolicy};
+
+/// How fragments are depth tested.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DepthMode {
+    /// No depth buffer: geometry is drawn in submission order, straight to
+    /// the default framebuffer.
+    Disabled,
+    /// Reversed depth in a `[0, 1]` clip space range, matching
+    /// [`projection_perspective`](super::projection_perspective).
+    #[default]
+    ReverseZ,
+    /// Conventional depth in the `[-1, 1]` clip space range of OpenGL, for
+    /// custom projections.
+    Standard,
+}
+
+impl DepthMode {
+    pub const fn is_enabled(self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    /// The depth function under which nearer fragments pass.
+    pub const fn func(self) -> u32 {
+        match self {
+            Self::ReverseZ => janus::gl::GREATER,
+            Self::Standard | Self::Disabled => janus::gl::LESS,
+        }
+    }
+
+    /// The depth of the far plane, which the depth buffer is cleared to.
+    pub const fn clear_depth(self) -> f64 {
+        match self {
+            Self::ReverseZ => 0.0,
+            Self::Standard | Self::Disabled => 1.0,
+        }
+    }
+
+    /// The clip space depth range, as expected by `glClipControl`.
+    pub const fn clip_depth(self) -> u32 {
+        match self {
+            Self::ReverseZ => janus::gl::ZERO_TO_ONE,
+            Self::Standard | Self::Disabled => janus::gl::NEGATIVE_ONE_TO_ONE,
+        }
+    }
+}
+
+/// The offscreen framebuffer the frame is rendered to, with a depth
+/// attachment.
+///
+/// Its renderbuffers are allocated on the first frame and reallocated on
+/// resolution changes.
+#[derive(Debug, Default)]
+pub struct DepthTarget {
+    mode: DepthMode,
+    framebuffer: u32,
+    color: u32,
+    depth: u32,
+    size: (i32, i32),
+
+    _marker: NotSend,
+}
+
+impl DepthTarget {
+    pub fn new(mode: DepthMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> DepthMode {
+        self.mode
+    }
+
+    /// Set the depth `mode` of the next frames.
+    ///
+    /// The renderbuffers are released when disabled.
+    pub fn set_mode(&mut self, mode: DepthMode) {
+        self.mode = mode;
+        if !mode.is_enabled() {
+            self.release();
+        }
+    }
+
+    /// The framebuffer the frame is rendered to, `0` for the default one.
+    pub fn framebuffer(&self) -> u32 {
+        self.framebuffer
+    }
+
+    /// Bind the target of a frame of `width` by `height` pixels, apply the
+    /// depth state and `clear` it.
+    ///
+    /// When disabled, the default framebuffer is kept and depth testing is
+    /// disabled.
+    pub fn begin(&mut self, width: i32, height: i32, clear: &ClearPolicy) {
+        if !self.mode.is_enabled() {
+            unsafe {
+                janus::gl::Disable(janus::gl::DEPTH_TEST);
+            }
+            clear.apply(self.mode);
+            return;
+        }
+        if self.size != (width, height) || self.framebuffer == 0 {
+            self.allocate(width, height);
+        }
+        unsafe {
+            janus::gl::BindFramebuffer(janus::gl::FRAMEBUFFER, self.framebuffer);
+            janus::gl::ClipControl(janus::gl::LOWER_LEFT, self.mode.clip_depth());
+            janus::gl::Enable(janus::gl::DEPTH_TEST);
+            janus::gl::DepthMask(janus::gl::TRUE);
+            janus::gl::DepthFunc(self.mode.func());
+        }
+        clear.apply(self.mode);
+    }
+
+    /// Copy the colour of the frame to the default framebuffer, and bind it.
+    pub fn resolve(&self) {
+        if self.framebuffer == 0 {
+            return;
+        }
+        let (w, h) = self.size;
+        unsafe {
+            janus::gl::BlitNamedFramebuffer(
+                self.framebuffer,
+                0,
+                0,
+                0,
+                w,
+                h,
+                0,
+                0,
+                w,
+                h,
+                janus::gl::COLOR_BUFFER_BIT,
+                janus::gl::NEAREST,
+            );
+            janus::gl::BindFramebuffer(janus::gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    fn allocate(&mut self, width: i32, height: i32) {
+        use tracing::Level;
+
+        self.release();
+        unsafe {
+            janus::gl::CreateFramebuffers(1, &mut self.framebuffer);
+            janus::gl::CreateRenderbuffers(1, &mut self.color);
+            janus::gl::CreateRenderbuffers(1, &mut self.depth);
+            janus::gl::NamedRenderbufferStorage(self.color, janus::gl::RGBA8, width, height);
+            janus::gl::NamedRenderbufferStorage(
+                self.depth,
+                janus::gl::DEPTH32F_STENCIL8,
+                width,
+                height,
+            );
+            janus::gl::NamedFramebufferRenderbuffer(
+                self.framebuffer,
+                janus::gl::COLOR_ATTACHMENT0,
+                janus::gl::RENDERBUFFER,
+                self.color,
+            );
+            janus::gl::NamedFramebufferRenderbuffer(
+                self.framebuffer,
+                janus::gl::DEPTH_STENCIL_ATTACHMENT,
+                janus::gl::RENDERBUFFER,
+                self.depth,
+            );
+            let status =
+                janus::gl::CheckNamedFramebufferStatus(self.framebuffer, janus::gl::FRAMEBUFFER);
+            if status != janus::gl::FRAMEBUFFER_COMPLETE {
+                tracing::event!(
+                    name: "render.depth.incomplete",
+                    Level::ERROR,
+                    "Depth target of {width}x{height} is incomplete (status {status:#x})"
+                );
+            }
+        }
+        self.size = (width, height);
+    }
+
+    fn release(&mut self) {
+        if self.framebuffer == 0 {
+            return;
+        }
+        unsafe {
+            janus::gl::DeleteFramebuffers(1, &self.framebuffer);
+            janus::gl::DeleteRenderbuffers(1, &self.color);
+            janus::gl::DeleteRenderbuffers(1, &self.depth);
+        }
+        self.framebuffer = 0;
+        self.color = 0;
+        self.depth = 0;
+        self.size = (0, 0);
+    }
+}
+
+impl Drop for DepthTarget {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_z_clears_to_far() {
+        let reverse = DepthMode::default();
+        assert!(reverse.is_enabled());
+        // the far plane is at depth 0, so nearer fragments are greater
+        assert_eq!(reverse.clear_depth(), 0.0);
+        assert_eq!(reverse.func(), janus::gl::GREATER);
+        assert_eq!(reverse.clip_depth(), janus::gl::ZERO_TO_ONE);
+        assert_eq!(DepthMode::Standard.func(), janus::gl::LESS);
+        assert!(!DepthMode::Disabled.is_enabled());
+
+        let mut target = DepthTarget::new(DepthMode::Standard);
+        target.set_mode(DepthMode::Disabled);
+        assert_eq!(target.framebuffer(), 0);
+    }
+}
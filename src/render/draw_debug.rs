@@ -0,0 +1,280 @@
+//! Debugging view of the draws and entities resolved by vertex shaders.
+//!
+//! Vertex shaders resolve the entity they draw from their draw and instance
+//! through index maps (e.g. the instance ranges of the
+//! [culling shader](super::cull), or the per-entity partitions indexed by
+//! `gl_BaseInstance + gl_InstanceID`), which silently draw the wrong entity
+//! when they drift from the CPU columns. This module helps checking them:
+//!
+//! * [`GLSL_LIB_DEBUG_VIEW_COLOR`] replaces the color of a vertex with a
+//!   color unique to its draw or entity, selected by a [`DrawDebugView`]
+//!   uniform, so that mismatches show on screen.
+//! * [`DrawReadback`] records the draw and entity resolved for each instance
+//!   through [`GLSL_LIB_RECORD_DRAW`], and reads the mapping table back to
+//!   the CPU, where it can be dumped or compared with the entities expected
+//!   by the CPU columns with [`DrawReadback::mismatches`].
+//!
+//! Both are meant for development: the readback stalls the pipeline.
+//!
+//! # Example
+//! ```rust,ignore
+//! // VERTEX SHADER, with `debug_view: uint` uniform
+//! uint instance = gl_BaseInstance + gl_InstanceID;
+//! uint entity = culled_entity(instance);
+//! record_draw(instance, uint(gl_DrawID), entity);
+//! v_tint = debug_view_color(debug_view, uint(gl_DrawID), entity, albedo);
+//!
+//! // RENDER
+//! shader.uniform_debug_view_uint(DrawDebugView::Entity as u32);
+//! readback.bind();
+//! dispatch.dispatch();
+//! let table = readback.read();
+//! for mismatch in DrawReadback::mismatches(&table, &expected_entities) {
+//!     tracing::warn!("{mismatch}");
+//! }
+//! ```
+
+use crate::{
+    render::NotSend,
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (DrawDebug) => {
+        20
+    };
+}
+
+pub const SHADER_BINDING_DRAW_DEBUG: u32 = ssbo_binding!(DrawDebug);
+
+/// What the color of [`GLSL_LIB_DEBUG_VIEW_COLOR`] identifies, passed to
+/// shaders as a `uint` uniform.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DrawDebugView {
+    /// The color of the vertex is unchanged.
+    #[default]
+    Off = 0,
+    /// A color per `gl_DrawID`.
+    DrawId = 1,
+    /// A color per entity.
+    Entity = 2,
+}
+
+/// Contains the SSBO declaration of the mapping table of [`DrawReadback`],
+/// on binding index 20: the draw and entity resolved by each instance, both
+/// offset by one so that `0` marks instances which were not drawn.
+pub const GLSL_SSBO_DRAW_DEBUG: GlslStorage = crate::shader_glsl_ssbo! {
+    buf DrawDebug => {
+        [dyn_array uvec2: debug_draws]
+    }
+};
+
+/// Record the `draw` and `entity` of `instance` in the mapping table of
+/// [`DrawReadback`]. Requires [`GLSL_SSBO_DRAW_DEBUG`].
+pub const GLSL_LIB_RECORD_DRAW: GlslLib = crate::shader_glsl_lib! {
+    void record_draw [ instance: uint, draw: uint, entity: uint ] => "
+        if (instance < uint(debug_draws.length())) {
+            debug_draws[instance] = uvec2(draw + 1u, entity + 1u);
+        }
+    "
+};
+
+/// A color unique to the `draw` or `entity`, depending on the
+/// [`DrawDebugView`] `view`, or `color` when the view is off.
+pub const GLSL_LIB_DEBUG_VIEW_COLOR: GlslLib = crate::shader_glsl_lib! {
+    vec3 debug_view_color [ view: uint, draw: uint, entity: uint, color: vec3 ] => "
+        if (view == 0u) {
+            return color;
+        }
+        uint id = view == 1u ? draw : entity;
+        // integer hash spreading consecutive ids over distinct hues
+        id = (id ^ 61u) ^ (id >> 16u);
+        id *= 9u;
+        id ^= id >> 4u;
+        id *= 0x27d4eb2du;
+        id ^= id >> 15u;
+        return vec3(id & 0xFFu, (id >> 8u) & 0xFFu, (id >> 16u) & 0xFFu) / 255.0;
+    "
+};
+
+/// An instance whose resolved entity differs from the expected one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DrawMismatch {
+    pub instance: usize,
+    /// The draw of the instance, if it was drawn.
+    pub draw: Option<u32>,
+    /// The entity resolved by the vertex shader, if it was drawn.
+    pub resolved: Option<u32>,
+    /// The entity expected by the CPU columns, if any.
+    pub expected: Option<u32>,
+}
+
+impl std::fmt::Display for DrawMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "instance {}", self.instance)?;
+        if let Some(draw) = self.draw {
+            write!(f, " of draw {draw}")?;
+        }
+        match (self.resolved, self.expected) {
+            (Some(resolved), Some(expected)) => {
+                write!(f, " resolved entity {resolved}, expected {expected}")
+            }
+            (Some(resolved), None) => write!(f, " resolved entity {resolved}, expected none"),
+            (None, Some(expected)) => write!(f, " was not drawn, expected entity {expected}"),
+            (None, None) => write!(f, " is consistent"),
+        }
+    }
+}
+
+/// A GPU buffer recording the draw and entity resolved by each instance, see
+/// the [module documentation](self).
+#[derive(Debug)]
+pub struct DrawReadback {
+    gl_obj: u32,
+    capacity: usize,
+
+    _marker: NotSend,
+}
+
+impl DrawReadback {
+    /// Allocate room for the mapping of `capacity` instances.
+    pub fn new(capacity: usize) -> Self {
+        let mut gl_obj = 0;
+        let size = capacity * size_of::<[u32; 2]>();
+        unsafe {
+            janus::gl::CreateBuffers(1, &mut gl_obj);
+            janus::gl::NamedBufferStorage(
+                gl_obj,
+                size as isize,
+                std::ptr::null(),
+                janus::gl::DYNAMIC_STORAGE_BIT,
+            );
+        }
+        Self {
+            gl_obj,
+            capacity,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Clear the mapping table and bind it to [`SHADER_BINDING_DRAW_DEBUG`]
+    /// for the following draws.
+    pub fn bind(&self) {
+        unsafe {
+            janus::gl::ClearNamedBufferData(
+                self.gl_obj,
+                janus::gl::R32UI,
+                janus::gl::RED_INTEGER,
+                janus::gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+            janus::gl::BindBufferBase(
+                janus::gl::SHADER_STORAGE_BUFFER,
+                SHADER_BINDING_DRAW_DEBUG,
+                self.gl_obj,
+            );
+        }
+    }
+
+    /// Read the mapping table back: the draw and entity of each instance,
+    /// `None` for instances which were not drawn.
+    ///
+    /// Waits for the draws recording the table to complete.
+    pub fn read(&self) -> Vec<Option<(u32, u32)>> {
+        let mut table = vec![[0u32; 2]; self.capacity];
+        unsafe {
+            janus::gl::MemoryBarrier(janus::gl::BUFFER_UPDATE_BARRIER_BIT);
+            janus::gl::GetNamedBufferSubData(
+                self.gl_obj,
+                0,
+                (table.len() * size_of::<[u32; 2]>()) as isize,
+                table.as_mut_ptr() as *mut _,
+            );
+        }
+        table
+            .into_iter()
+            .map(|[draw, entity]| (draw != 0).then(|| (draw - 1, entity - 1)))
+            .collect()
+    }
+
+    /// The instances of a `table` read with [`DrawReadback::read`] whose
+    /// entity differs from the one `expected` by the CPU columns, indexed by
+    /// instance.
+    pub fn mismatches<'a>(
+        table: &'a [Option<(u32, u32)>],
+        expected: &'a [Option<u32>],
+    ) -> impl Iterator<Item = DrawMismatch> + 'a {
+        let len = table.len().max(expected.len());
+        (0..len).filter_map(|instance| {
+            let drawn = table.get(instance).copied().flatten();
+            let expected = expected.get(instance).copied().flatten();
+            let resolved = drawn.map(|(_, entity)| entity);
+            (resolved != expected).then_some(DrawMismatch {
+                instance,
+                draw: drawn.map(|(draw, _)| draw),
+                resolved,
+                expected,
+            })
+        })
+    }
+
+    /// Format the drawn instances of a `table` read with
+    /// [`DrawReadback::read`], one `instance draw entity` row per line.
+    pub fn dump(table: &[Option<(u32, u32)>]) -> String {
+        let mut dump = String::from("instance     draw   entity");
+        for (instance, (draw, entity)) in table
+            .iter()
+            .enumerate()
+            .filter_map(|(i, row)| row.map(|row| (i, row)))
+        {
+            dump.push_str(&format!("\n{instance:>8} {draw:>8} {entity:>8}"));
+        }
+        dump
+    }
+}
+
+impl Drop for DrawReadback {
+    fn drop(&mut self) {
+        unsafe {
+            janus::gl::DeleteBuffers(1, &self.gl_obj);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_readback_mismatches() {
+        let table = [Some((0, 4)), Some((0, 5)), None, Some((1, 9))];
+        let expected = [Some(4), Some(6), Some(7), Some(9), Some(10)];
+
+        let mismatches = DrawReadback::mismatches(&table, &expected)
+            .map(|m| (m.instance, m.resolved, m.expected))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            mismatches,
+            [
+                (1, Some(5), Some(6)),
+                (2, None, Some(7)),
+                (4, None, Some(10))
+            ]
+        );
+
+        let first = DrawReadback::mismatches(&table, &expected).next();
+        assert_eq!(
+            first.map(|m| m.to_string()).as_deref(),
+            Some("instance 1 of draw 0 resolved entity 5, expected 6")
+        );
+        assert_eq!(
+            DrawReadback::dump(&table[..2]),
+            "instance     draw   entity\n       0        0        4\n       1        0        5"
+        );
+    }
+}
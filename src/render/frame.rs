@@ -0,0 +1,375 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Instant,
+};
+
+use crate::{
+    render::{
+        Resolution,
+        buffer::{Layout, PartitionedTriBuffer, StorageSection},
+    },
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (FrameHeader) => {
+        12
+    };
+}
+
+pub const SHADER_BINDING_FRAME_HEADER: u32 = ssbo_binding!(FrameHeader);
+
+/// Per-frame information shared with all shaders.
+///
+/// Shaders otherwise have no way to know how many entities or draw commands
+/// are valid in the current frame, as buffers are allocated with a fixed
+/// capacity: the header allows compute and vertex shaders to bound their
+/// loops correctly.
+///
+/// Corresponds to the [`GLSL_SSBO_FRAME_HEADER`] block in a `std430` layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameHeader {
+    /// The world position of the camera. `w` is always `1.0`.
+    pub camera_position: [f32; 4],
+    pub entity_count: u32,
+    pub command_count: u32,
+    pub frame_index: u32,
+    /// Seconds elapsed since the start of the simulation.
+    pub time: f32,
+    /// Seconds elapsed since the previous upload.
+    pub delta: f32,
+}
+
+impl FrameHeader {
+    pub fn new(entity_count: u32, command_count: u32) -> Self {
+        Self {
+            camera_position: [0.0, 0.0, 0.0, 1.0],
+            entity_count,
+            command_count,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_camera_position(mut self, position: glam::Vec3) -> Self {
+        self.camera_position = position.extend(1.0).to_array();
+        self
+    }
+
+    pub fn with_time(mut self, frame_index: u32, time: f32, delta: f32) -> Self {
+        self.frame_index = frame_index;
+        self.time = time;
+        self.delta = delta;
+        self
+    }
+}
+
+/// Frame header SSBO interface.
+///
+/// Contains the SSBO declaration of the [`FrameHeader`] for drop-in
+/// integration with shader declarations using the [`crate::shader_glsl`] and
+/// [`crate::shader_glsl_compute`] macros, on binding index 12.
+pub const GLSL_SSBO_FRAME_HEADER: GlslStorage = crate::shader_glsl_ssbo! {
+    buf FrameHeader => {
+        vec4: camera_position;
+        uint: entity_count;
+        uint: command_count;
+        uint: frame_index;
+        float: time;
+        float: delta;
+    }
+};
+
+/// A triple buffered [`FrameHeader`], bound to
+/// [`SHADER_BINDING_FRAME_HEADER`].
+///
+/// The header should be uploaded every tick alongside the rest of the frame
+/// data, and bound in the same section during rendering.
+///
+/// The buffer keeps track of the frame index and time itself, which are
+/// filled in by [`FrameHeaderBuffer::upload`].
+///
+/// # Example
+/// ```rust,ignore
+/// // UPLOAD
+/// boundary.cross(|section, storage| {
+///     storage
+///         .frame_header
+///         .upload(section, entity_count, command_count, camera_position);
+/// });
+///
+/// // RENDER
+/// frame_data.frame_header.bind_shader_storage(section);
+/// ```
+#[derive(Debug)]
+pub struct FrameHeaderBuffer {
+    buffer: PartitionedTriBuffer<1>,
+
+    epoch: Instant,
+    frame: AtomicU32,
+    /// Bits of the `f32` time of the last upload.
+    last_time: AtomicU32,
+}
+
+impl Default for FrameHeaderBuffer {
+    fn default() -> Self {
+        Self {
+            buffer: Default::default(),
+            epoch: Instant::now(),
+            frame: AtomicU32::new(0),
+            last_time: AtomicU32::new(0),
+        }
+    }
+}
+
+impl FrameHeaderBuffer {
+    pub fn new() -> Self {
+        let layout = Layout::<1>::new()
+            .partition::<FrameHeader>(1)
+            .with_shader_storage(SHADER_BINDING_FRAME_HEADER);
+        Self {
+            buffer: PartitionedTriBuffer::new(layout),
+            epoch: Instant::now(),
+            frame: AtomicU32::new(0),
+            last_time: AtomicU32::new(0),
+        }
+    }
+
+    /// Upload the header of a new frame to `section`, filling in the frame
+    /// index and time.
+    ///
+    /// # Returns
+    /// The uploaded header.
+    pub fn upload(
+        &self,
+        section: StorageSection,
+        entity_count: u32,
+        command_count: u32,
+        camera_position: glam::Vec3,
+    ) -> FrameHeader {
+        let time = self.epoch.elapsed().as_secs_f32();
+        let last_time = f32::from_bits(self.last_time.swap(time.to_bits(), Ordering::Relaxed));
+        let frame_index = self.frame.fetch_add(1, Ordering::Relaxed);
+
+        let header = FrameHeader::new(entity_count, command_count)
+            .with_camera_position(camera_position)
+            .with_time(frame_index, time, time - last_time);
+        self.upload_header(section, &header);
+        header
+    }
+
+    /// Upload a custom `header` to `section`.
+    ///
+    /// The frame index and time are uploaded as they are.
+    pub fn upload_header(&self, section: StorageSection, header: &FrameHeader) {
+        // SAFETY: the only partition of the buffer is laid out for a single
+        // FrameHeader.
+        unsafe {
+            self.buffer
+                .blit_part(section.as_index(), 0, std::slice::from_ref(header), 0);
+        }
+    }
+
+    /// The header last uploaded to `section`.
+    pub fn header(&self, section: StorageSection) -> FrameHeader {
+        // SAFETY: see Self::upload_header
+        let view = unsafe { self.buffer.view_part::<FrameHeader>(section.as_index(), 0) };
+        view.as_slice()[0]
+    }
+
+    pub fn bind_shader_storage(&self, section: StorageSection) {
+        self.buffer.bind_shader_storage(section.as_index());
+    }
+}
+
+pub const UNIFORM_BINDING_FRAME_GLOBALS: u32 = 0;
+
+/// Builtin per-frame uniforms, set automatically by the
+/// [`Renderer`](super::Renderer) before any user pass.
+///
+/// Unlike the [`FrameHeader`], which describes the simulation data of a
+/// frame, these describe the rendered frame itself.
+///
+/// Corresponds to the [`GLSL_UBO_FRAME_GLOBALS`] block in a `std140` layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameGlobals {
+    pub resolution: [f32; 2],
+    /// Seconds elapsed since the first rendered frame.
+    pub time: f32,
+    /// Seconds elapsed since the previous rendered frame.
+    pub delta: f32,
+    pub frame_index: u32,
+    /// Seconds to extrapolate stale frame data by, `0.0` when the frame data
+    /// is new or extrapolation is disabled.
+    ///
+    /// See [`RenderSettings`](super::settings::RenderSettings) and
+    /// [`GLSL_LIB_EXTRAPOLATE`].
+    pub extrapolation: f32,
+    /// The content scale of the display, see
+    /// [`Resolution::scale`](super::Resolution::scale).
+    pub content_scale: f32,
+    _pad: u32,
+}
+
+/// Frame globals UBO interface.
+///
+/// Contains the uniform block declaration of the [`FrameGlobals`], on binding
+/// index 0, for drop-in integration with shader declarations using the
+/// [`crate::shader_glsl`] and [`crate::shader_glsl_compute`] macros (in their
+/// `ssbo` block).
+///
+/// The block is declared with the `globals` instance name, so its fields do
+/// not clash with the [`FrameHeader`] ones:
+/// ```glsl
+/// float wave = sin(globals.time);
+/// ```
+pub const GLSL_UBO_FRAME_GLOBALS: GlslStorage = GlslStorage::new(concat!(
+    "layout(std140, binding = 0) uniform FrameGlobals\n{\n",
+    "    vec2 resolution;\n",
+    "    float time;\n",
+    "    float delta;\n",
+    "    uint frame_index;\n",
+    "    float extrapolation;\n",
+    "    float content_scale;\n",
+    "} globals;\n",
+));
+
+/// Extrapolate a `position` moving at `velocity` (in units per second) by the
+/// [`FrameGlobals::extrapolation`] time.
+///
+/// Requires the [`GLSL_UBO_FRAME_GLOBALS`] block, and the velocities of the
+/// entities to be uploaded alongside their positions, e.g. in a dedicated
+/// partition.
+///
+/// # Example
+/// ```glsl
+/// vec3 position = extrapolate(positions[id].xyz, velocities[id].xyz);
+/// ```
+pub const GLSL_LIB_EXTRAPOLATE: GlslLib = crate::shader_glsl_lib! {
+    vec3 extrapolate [ position: vec3, velocity: vec3 ] => "
+        return position + velocity * globals.extrapolation;
+    "
+};
+
+/// The uniform buffer of the [`FrameGlobals`], owned by the
+/// [`Renderer`](super::Renderer).
+///
+/// The GL buffer is created on the first [`FrameGlobalsBuffer::update`], as
+/// the renderer is created before the GL context.
+#[derive(Debug, Default)]
+pub struct FrameGlobalsBuffer {
+    gl_obj: u32,
+    epoch: Option<Instant>,
+    globals: FrameGlobals,
+}
+
+impl FrameGlobalsBuffer {
+    /// Advance the globals to a new frame with the given `resolution`,
+    /// upload and bind them to [`UNIFORM_BINDING_FRAME_GLOBALS`].
+    pub fn update(&mut self, resolution: Resolution) -> &FrameGlobals {
+        if self.gl_obj == 0 {
+            // bound to edit rather than through direct state access, so that
+            // the globals are available on the contexts the upload fallback
+            // supports, see `orphan`
+            unsafe {
+                janus::gl::GenBuffers(1, &mut self.gl_obj);
+                janus::gl::BindBuffer(janus::gl::UNIFORM_BUFFER, self.gl_obj);
+                janus::gl::BufferData(
+                    janus::gl::UNIFORM_BUFFER,
+                    size_of::<FrameGlobals>() as isize,
+                    std::ptr::null(),
+                    janus::gl::DYNAMIC_DRAW,
+                );
+            }
+        }
+
+        let now = Instant::now();
+        let (epoch, frame_index) = match self.epoch {
+            Some(epoch) => (epoch, self.globals.frame_index.wrapping_add(1)),
+            None => (*self.epoch.insert(now), 0),
+        };
+        let time = now.duration_since(epoch).as_secs_f32();
+
+        self.globals.resolution = [resolution.width, resolution.height];
+        self.globals.content_scale = resolution.scale;
+        self.globals.delta = time - self.globals.time;
+        self.globals.time = time;
+        self.globals.frame_index = frame_index;
+
+        unsafe {
+            janus::gl::BindBufferBase(
+                janus::gl::UNIFORM_BUFFER,
+                UNIFORM_BINDING_FRAME_GLOBALS,
+                self.gl_obj,
+            );
+            janus::gl::BufferSubData(
+                janus::gl::UNIFORM_BUFFER,
+                0,
+                size_of::<FrameGlobals>() as isize,
+                &self.globals as *const FrameGlobals as *const std::ffi::c_void,
+            );
+        }
+        &self.globals
+    }
+
+    /// Set and upload the extrapolation time of the current frame.
+    ///
+    /// Does nothing before the first [`FrameGlobalsBuffer::update`].
+    pub fn set_extrapolation(&mut self, seconds: f32) {
+        if self.gl_obj == 0 || self.globals.extrapolation == seconds {
+            return;
+        }
+        self.globals.extrapolation = seconds;
+
+        unsafe {
+            janus::gl::BindBuffer(janus::gl::UNIFORM_BUFFER, self.gl_obj);
+            janus::gl::BufferSubData(
+                janus::gl::UNIFORM_BUFFER,
+                std::mem::offset_of!(FrameGlobals, extrapolation) as isize,
+                size_of::<f32>() as isize,
+                &self.globals.extrapolation as *const f32 as *const std::ffi::c_void,
+            );
+        }
+    }
+
+    /// The globals of the last rendered frame.
+    pub fn globals(&self) -> &FrameGlobals {
+        &self.globals
+    }
+}
+
+impl Drop for FrameGlobalsBuffer {
+    fn drop(&mut self) {
+        if self.gl_obj != 0 {
+            unsafe {
+                janus::gl::DeleteBuffers(1, &self.gl_obj);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn std430_layout() {
+        assert_eq!(std::mem::offset_of!(FrameHeader, camera_position), 0);
+        assert_eq!(std::mem::offset_of!(FrameHeader, entity_count), 16);
+        assert_eq!(std::mem::offset_of!(FrameHeader, command_count), 20);
+        assert_eq!(std::mem::offset_of!(FrameHeader, frame_index), 24);
+        assert_eq!(std::mem::offset_of!(FrameHeader, time), 28);
+        assert_eq!(std::mem::offset_of!(FrameHeader, delta), 32);
+
+        assert_eq!(size_of::<FrameGlobals>(), 32);
+        assert_eq!(std::mem::offset_of!(FrameGlobals, time), 8);
+        assert_eq!(std::mem::offset_of!(FrameGlobals, frame_index), 16);
+        assert_eq!(std::mem::offset_of!(FrameGlobals, extrapolation), 20);
+        assert_eq!(std::mem::offset_of!(FrameGlobals, content_scale), 24);
+
+        let glsl = GLSL_SSBO_FRAME_HEADER.as_str();
+        assert!(glsl.starts_with("layout(std430, binding = 12) buffer FrameHeader"));
+        assert!(glsl.contains("uint entity_count;"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Debug checks of the GL state left by each stage of a frame.
+//!
+//! Stages and [`CrossHooks`](super::stage::CrossHooks) share a single GL
+//! context, so a hook which forgets to unbind its program or to restore the
+//! blend state silently changes how the following stages draw. In debug
+//! builds, the [`Renderer`](super::Renderer) snapshots the critical GL state
+//! after each [`Stage`] with declared expectations, and logs every
+//! difference as a `render.gl_state` warning naming the stage.
+//!
+//! Only the state declared in a [`GlStateExpectation`] is compared, and only
+//! the storage buffer slots it declares are queried. Release builds do not
+//! check anything.
+//!
+//! # Example
+//! ```rust,ignore
+//! // the hooks must leave the scene program and the opaque state in place
+//! renderer.gl_state_checker_mut().expect(
+//!     Stage::Hooks,
+//!     GlStateExpectation::new()
+//!         .program(scene_program.gl_obj())
+//!         .storage_buffer(SHADER_BINDING_FRAME_HEADER, header_buffer)
+//!         .depth_test(true)
+//!         .blend(false),
+//! );
+//! ```
+
+use super::stage::Stage;
+
+/// A piece of GL state compared by the [`GlStateChecker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GlStateKey {
+    /// `GL_CURRENT_PROGRAM`.
+    Program,
+    /// `GL_VERTEX_ARRAY_BINDING`.
+    VertexArray,
+    /// The buffer bound to a `GL_SHADER_STORAGE_BUFFER` binding.
+    StorageBuffer(u32),
+    DepthTest,
+    /// `GL_DEPTH_WRITEMASK`.
+    DepthWrite,
+    /// `GL_DEPTH_FUNC`.
+    DepthFunc,
+    Blend,
+    CullFace,
+}
+
+impl std::fmt::Display for GlStateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Program => write!(f, "program"),
+            Self::VertexArray => write!(f, "vertex array"),
+            Self::StorageBuffer(binding) => write!(f, "storage buffer {binding}"),
+            Self::DepthTest => write!(f, "depth test"),
+            Self::DepthWrite => write!(f, "depth write"),
+            Self::DepthFunc => write!(f, "depth function"),
+            Self::Blend => write!(f, "blend"),
+            Self::CullFace => write!(f, "face culling"),
+        }
+    }
+}
+
+/// The values of the GL state compared by the [`GlStateChecker`], either
+/// queried with [`GlStateSnapshot::capture`] or expected.
+///
+/// Flags are `0` or `1`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlStateSnapshot {
+    values: Vec<(GlStateKey, u32)>,
+}
+
+impl GlStateSnapshot {
+    /// Query the current value of each of the `keys`.
+    pub fn capture(keys: impl IntoIterator<Item = GlStateKey>) -> Self {
+        let values = keys.into_iter().map(|key| (key, query(key))).collect();
+        Self { values }
+    }
+
+    pub fn get(&self, key: GlStateKey) -> Option<u32> {
+        self.values
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GlStateKey, u32)> + '_ {
+        self.values.iter().copied()
+    }
+}
+
+fn query(key: GlStateKey) -> u32 {
+    let mut value = 0;
+    unsafe {
+        match key {
+            GlStateKey::Program => janus::gl::GetIntegerv(janus::gl::CURRENT_PROGRAM, &mut value),
+            GlStateKey::VertexArray => {
+                janus::gl::GetIntegerv(janus::gl::VERTEX_ARRAY_BINDING, &mut value)
+            }
+            GlStateKey::StorageBuffer(binding) => janus::gl::GetIntegeri_v(
+                janus::gl::SHADER_STORAGE_BUFFER_BINDING,
+                binding,
+                &mut value,
+            ),
+            GlStateKey::DepthTest => value = janus::gl::IsEnabled(janus::gl::DEPTH_TEST) as i32,
+            GlStateKey::DepthWrite => {
+                let mut mask = 0;
+                janus::gl::GetBooleanv(janus::gl::DEPTH_WRITEMASK, &mut mask);
+                value = mask as i32;
+            }
+            GlStateKey::DepthFunc => janus::gl::GetIntegerv(janus::gl::DEPTH_FUNC, &mut value),
+            GlStateKey::Blend => value = janus::gl::IsEnabled(janus::gl::BLEND) as i32,
+            GlStateKey::CullFace => value = janus::gl::IsEnabled(janus::gl::CULL_FACE) as i32,
+        }
+    }
+    value as u32
+}
+
+/// The GL state a [`Stage`] must leave behind.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlStateExpectation {
+    expected: GlStateSnapshot,
+}
+
+impl GlStateExpectation {
+    /// No expectation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect the `key` to hold `value`, replacing any previous expectation
+    /// of the key.
+    pub fn with(mut self, key: GlStateKey, value: u32) -> Self {
+        let values = &mut self.expected.values;
+        values.retain(|(k, _)| *k != key);
+        values.push((key, value));
+        self
+    }
+
+    pub fn program(self, program: u32) -> Self {
+        self.with(GlStateKey::Program, program)
+    }
+
+    pub fn vertex_array(self, vao: u32) -> Self {
+        self.with(GlStateKey::VertexArray, vao)
+    }
+
+    pub fn storage_buffer(self, binding: u32, buffer: u32) -> Self {
+        self.with(GlStateKey::StorageBuffer(binding), buffer)
+    }
+
+    pub fn depth_test(self, enabled: bool) -> Self {
+        self.with(GlStateKey::DepthTest, enabled as u32)
+    }
+
+    pub fn depth_write(self, enabled: bool) -> Self {
+        self.with(GlStateKey::DepthWrite, enabled as u32)
+    }
+
+    pub fn depth_func(self, func: u32) -> Self {
+        self.with(GlStateKey::DepthFunc, func)
+    }
+
+    pub fn blend(self, enabled: bool) -> Self {
+        self.with(GlStateKey::Blend, enabled as u32)
+    }
+
+    pub fn cull_face(self, enabled: bool) -> Self {
+        self.with(GlStateKey::CullFace, enabled as u32)
+    }
+
+    /// The keys of the expected state.
+    pub fn keys(&self) -> impl Iterator<Item = GlStateKey> + '_ {
+        self.expected.iter().map(|(key, _)| key)
+    }
+
+    /// The differences between the expected state and a `snapshot` of the
+    /// state left by the `stage`.
+    pub fn compare(&self, stage: Stage, snapshot: &GlStateSnapshot) -> Vec<GlStateMismatch> {
+        self.expected
+            .iter()
+            .filter_map(|(key, expected)| {
+                let found = snapshot.get(key)?;
+                (found != expected).then_some(GlStateMismatch {
+                    stage,
+                    key,
+                    expected,
+                    found,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A piece of GL state left by a stage which differs from its expectation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlStateMismatch {
+    pub stage: Stage,
+    pub key: GlStateKey,
+    pub expected: u32,
+    pub found: u32,
+}
+
+impl std::fmt::Display for GlStateMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (stage, key) = (self.stage, self.key);
+        match key {
+            GlStateKey::DepthTest
+            | GlStateKey::DepthWrite
+            | GlStateKey::Blend
+            | GlStateKey::CullFace => {
+                let state = |value| if value != 0 { "enabled" } else { "disabled" };
+                write!(
+                    f,
+                    "{stage} stage left {key} {}, expected {}",
+                    state(self.found),
+                    state(self.expected)
+                )
+            }
+            _ => write!(
+                f,
+                "{stage} stage left {key} {:#x}, expected {:#x}",
+                self.found, self.expected
+            ),
+        }
+    }
+}
+
+/// The GL state expected after each [`Stage`], checked by the
+/// [`Renderer`](super::Renderer) in debug builds, see the
+/// [module documentation](self).
+#[derive(Clone, Debug, Default)]
+pub struct GlStateChecker {
+    expectations: Vec<(Stage, GlStateExpectation)>,
+}
+
+impl GlStateChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the state the `stage` must leave behind, replacing its previous
+    /// expectation.
+    pub fn expect(&mut self, stage: Stage, expectation: GlStateExpectation) {
+        self.expectations.retain(|(s, _)| *s != stage);
+        self.expectations.push((stage, expectation));
+    }
+
+    /// Remove the expectation of the `stage`.
+    pub fn forget(&mut self, stage: Stage) {
+        self.expectations.retain(|(s, _)| *s != stage);
+    }
+
+    /// Snapshot the GL state expected after `stage`, if any, and log its
+    /// differences with the expectation.
+    ///
+    /// Does nothing in release builds.
+    ///
+    /// # Returns
+    /// The amount of mismatches.
+    pub fn check(&self, stage: Stage) -> usize {
+        if !cfg!(debug_assertions) {
+            return 0;
+        }
+        let Some((_, expectation)) = self.expectations.iter().find(|(s, _)| *s == stage) else {
+            return 0;
+        };
+
+        let snapshot = GlStateSnapshot::capture(expectation.keys());
+        let mismatches = expectation.compare(stage, &snapshot);
+        for mismatch in &mismatches {
+            use tracing::Level;
+            tracing::event!(name: "render.gl_state", Level::WARN, "{mismatch}");
+        }
+        mismatches.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_gl_state() {
+        let expectation = GlStateExpectation::new()
+            .program(3)
+            .storage_buffer(12, 7)
+            .blend(false)
+            .blend(true)
+            .depth_test(true);
+        assert_eq!(expectation.keys().count(), 4);
+
+        // keys missing from the snapshot are not compared
+        let snapshot = GlStateSnapshot {
+            values: vec![
+                (GlStateKey::Program, 3),
+                (GlStateKey::StorageBuffer(12), 9),
+                (GlStateKey::Blend, 0),
+            ],
+        };
+        let mismatches = expectation.compare(Stage::Hooks, &snapshot);
+        assert_eq!(
+            mismatches.iter().map(|m| m.key).collect::<Vec<_>>(),
+            [GlStateKey::StorageBuffer(12), GlStateKey::Blend]
+        );
+        assert_eq!(
+            mismatches[0].to_string(),
+            "hooks stage left storage buffer 12 0x9, expected 0x7"
+        );
+        assert_eq!(
+            mismatches[1].to_string(),
+            "hooks stage left blend disabled, expected enabled"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Point lights, and the flags controlling their cost.
+//!
+//! The cost of a light is in the entities it is evaluated for and, above
+//! all, in its shadow map. Each [`Light`] carries [`LightFlags`], where
+//! [`LightFlags::CASTS_SHADOWS`] opts it into the shadow pass, and the
+//! [`RenderLayers`] it affects: entities outside of them are neither lit by
+//! it nor drawn into its shadow map, e.g. to keep a flashlight off the UI
+//! or a fill light out of the shadows of the scene.
+//!
+//! Both are respected on the CPU, by [`cull_lights`] for the lights of a
+//! cluster and [`shadow_casters`] for the shadow pass, and on the GPU with
+//! [`GLSL_LIB_LIGHT_AFFECTS`] over the partition declared by
+//! [`GLSL_SSBO_LIGHTS`].
+//!
+//! # Example
+//! ```rust,ignore
+//! let lamp = Light::point(Vec3::new(0.0, 3.0, 0.0), 10.0)
+//!     .with_color(Color::srgb(1.0, 0.8, 0.6, 1.0))
+//!     .with_shadows(true);
+//! let rim = Light::point(Vec3::new(2.0, 1.0, -1.0), 4.0)
+//!     .with_layers(RenderLayers::layer(2));
+//!
+//! // SHADOW PASS
+//! shadow_casters(&lights, &mut casters);
+//! for &light in &casters {
+//!     for (visibility, mesh) in entities {
+//!         batcher.push_visible(visibility, lights[light as usize].layers, material, mesh, command);
+//!     }
+//! }
+//!
+//! // CLUSTERED CULLING
+//! for (cluster, bounds) in clusters.iter().enumerate() {
+//!     let count = cull_lights(&lights, bounds, RenderLayers::ALL, &mut cluster_lights);
+//!     offsets[cluster] = (cluster_lights.len() - count) as u32;
+//! }
+//! ```
+
+use glam::{Vec3, Vec4};
+
+use crate::{
+    render::{
+        bounds::Aabb,
+        color::Color,
+        visibility::{RenderLayers, Visibility},
+    },
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (Lights) => {
+        25
+    };
+}
+
+pub const SHADER_BINDING_LIGHTS: u32 = ssbo_binding!(Lights);
+
+/// The flags of a [`Light`].
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightFlags(pub u32);
+
+impl Default for LightFlags {
+    fn default() -> Self {
+        Self::ENABLED
+    }
+}
+
+impl LightFlags {
+    pub const NONE: Self = Self(0);
+    /// The light is evaluated at all.
+    pub const ENABLED: Self = Self(1);
+    /// The light renders a shadow map, and its contribution is shadowed.
+    pub const CASTS_SHADOWS: Self = Self(2);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, flags: Self, enabled: bool) {
+        if enabled {
+            self.0 |= flags.0;
+        } else {
+            self.0 &= !flags.0;
+        }
+    }
+}
+
+impl std::ops::BitOr for LightFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A point light.
+///
+/// Corresponds to the `Light` struct of [`GLSL_SSBO_LIGHTS`] in a `std430`
+/// layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    /// The position in world space, and the range beyond which the light
+    /// has no effect.
+    pub position: Vec4,
+    /// The linear colour, and the intensity it is multiplied by.
+    pub color: Vec4,
+    /// The layers of the entities lit, and drawn into the shadow map.
+    pub layers: RenderLayers,
+    pub flags: LightFlags,
+    _pad: [u32; 2],
+}
+
+impl Light {
+    /// An enabled white light at `position` reaching `range`, affecting all
+    /// layers and without shadows.
+    pub fn point(position: Vec3, range: f32) -> Self {
+        Self {
+            position: position.extend(range),
+            color: Vec4::ONE,
+            layers: RenderLayers::ALL,
+            flags: LightFlags::default(),
+            _pad: [0; 2],
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        let [r, g, b, _] = color.to_array();
+        self.color = Vec4::new(r, g, b, self.color.w);
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.color.w = intensity;
+        self
+    }
+
+    /// Only affect the entities in any of `layers`.
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn with_shadows(mut self, shadows: bool) -> Self {
+        self.flags.set(LightFlags::CASTS_SHADOWS, shadows);
+        self
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.position.truncate()
+    }
+
+    pub fn range(&self) -> f32 {
+        self.position.w
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.flags.contains(LightFlags::ENABLED)
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.flags.set(LightFlags::ENABLED, enabled);
+    }
+
+    /// Whether the light is enabled and renders a shadow map.
+    pub fn casts_shadows(&self) -> bool {
+        self.is_enabled() && self.flags.contains(LightFlags::CASTS_SHADOWS)
+    }
+
+    /// Whether the light is enabled and affects any of `layers`.
+    pub fn affects(&self, layers: RenderLayers) -> bool {
+        self.is_enabled() && self.layers.intersects(layers)
+    }
+
+    /// Whether the light illuminates an entity of `visibility`.
+    pub fn illuminates(&self, visibility: &Visibility) -> bool {
+        visibility.is_visible() && self.affects(visibility.layers)
+    }
+
+    /// Whether the range of the light reaches into `bounds`.
+    pub fn reaches(&self, bounds: &Aabb) -> bool {
+        if bounds.is_empty() {
+            return false;
+        }
+        let center = self.center();
+        let closest = center.clamp(bounds.min.truncate(), bounds.max.truncate());
+        closest.distance_squared(center) <= self.range() * self.range()
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct Light for Light {
+        position: Vec4 => vec4;
+        color: Vec4 => vec4;
+        layers: u32 => uint;
+        flags: u32 => uint;
+    }
+}
+
+/// Append the indices of the `lights` affecting a cluster of `bounds`,
+/// containing entities of `layers`, to `out`.
+///
+/// # Returns
+/// The amount of lights appended.
+pub fn cull_lights(
+    lights: &[Light],
+    bounds: &Aabb,
+    layers: RenderLayers,
+    out: &mut Vec<u32>,
+) -> usize {
+    let before = out.len();
+    out.extend(lights.iter().enumerate().filter_map(|(index, light)| {
+        (light.affects(layers) && light.reaches(bounds)).then_some(index as u32)
+    }));
+    out.len() - before
+}
+
+/// Append the indices of the `lights` rendering a shadow map to `out`.
+///
+/// The shadow map of each light is drawn with the entities in its layers,
+/// e.g. with [`Light::layers`] as the filter of
+/// [`CommandBatcher::push_visible`](super::batch::CommandBatcher::push_visible).
+///
+/// # Returns
+/// The amount of lights appended.
+pub fn shadow_casters(lights: &[Light], out: &mut Vec<u32>) -> usize {
+    let before = out.len();
+    out.extend(
+        lights
+            .iter()
+            .enumerate()
+            .filter_map(|(index, light)| light.casts_shadows().then_some(index as u32)),
+    );
+    out.len() - before
+}
+
+/// Lights SSBO interface.
+///
+/// Contains the SSBO declaration of a [`Light`] partition, on binding index
+/// 25. Requires the `Light` struct definition, see
+/// [`LightGlslStruct::as_definition`].
+pub const GLSL_SSBO_LIGHTS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf Lights => {
+        [dyn_array Light: lights]
+    }
+};
+
+/// Whether `light` is enabled and affects an entity in the layers of
+/// `layers`, e.g. read from [`GLSL_SSBO_VISIBILITY`](super::visibility::GLSL_SSBO_VISIBILITY).
+/// Requires the `Light` struct definition.
+pub const GLSL_LIB_LIGHT_AFFECTS: GlslLib = crate::shader_glsl_lib! {
+    bool light_affects [ light: Light, layers: uint ] => "
+        return (light.flags & 1u) != 0u && (light.layers & layers) != 0u;
+    "
+};
+
+/// Whether `light` is enabled and shadowed. Requires the `Light` struct
+/// definition.
+pub const GLSL_LIB_LIGHT_CASTS_SHADOWS: GlslLib = crate::shader_glsl_lib! {
+    bool light_casts_shadows [ light: Light ] => "
+        return (light.flags & 3u) == 3u;
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_flags_and_layers() {
+        let ui = RenderLayers::layer(3);
+        let mut lights = [
+            Light::point(Vec3::ZERO, 2.0).with_shadows(true),
+            Light::point(Vec3::new(10.0, 0.0, 0.0), 2.0).with_shadows(true),
+            Light::point(Vec3::ZERO, 2.0).with_layers(ui),
+            Light::point(Vec3::new(0.0, 2.0, 0.0), 1.5),
+        ];
+        assert_eq!(size_of::<Light>(), 48);
+        assert!(!lights[3].casts_shadows());
+
+        let cluster = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let mut culled = Vec::new();
+        assert_eq!(
+            cull_lights(&lights, &cluster, RenderLayers::DEFAULT, &mut culled),
+            2
+        );
+        assert_eq!(culled, [0, 3]);
+        assert!(!lights[2].illuminates(&Visibility::new(RenderLayers::DEFAULT)));
+        assert!(lights[2].illuminates(&Visibility::new(ui)));
+
+        lights[1].set_enabled(false);
+        let mut casters = Vec::new();
+        shadow_casters(&lights, &mut casters);
+        assert_eq!(casters, [0]);
+        assert!(!lights[0].reaches(&Aabb::EMPTY));
+    }
+}
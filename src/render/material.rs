@@ -0,0 +1,295 @@
+//! Surface materials, looked up per instance by shaders.
+//!
+//! Materials are registered once in a [`MaterialStorage`], and each entity
+//! refers to its material by [`MaterialId`], stored in a column next to the
+//! rest of its data. Both are uploaded as partitions of the entity data
+//! buffer: the material table to the partition declared by
+//! [`GLSL_SSBO_MATERIALS`], and the per-entity ids to the partition declared
+//! by [`GLSL_SSBO_ENTITY_MATERIALS`], in the same order as the transforms.
+//!
+//! # Example
+//! ```rust,ignore
+//! layout_buffer! {
+//!     const EntityData: 3, {
+//!         enum transforms: 100_000 => {
+//!             type PackedTransform;
+//!             bind 0;
+//!             shader SHADER_BINDING_INSTANCES;
+//!         };
+//!         enum materials: 256 => {
+//!             type Material;
+//!             bind 1;
+//!             shader SHADER_BINDING_MATERIALS;
+//!         };
+//!         enum entity_materials: 100_000 => {
+//!             type MaterialId;
+//!             bind 2;
+//!             shader SHADER_BINDING_ENTITY_MATERIALS;
+//!         };
+//!     }
+//! }
+//!
+//! let brick = materials.insert(Material::new(Color::from_hex("#9a4b33").unwrap()).with_roughness(0.9));
+//! props.insert_rows([PropsTableDef::builder().material(brick).build()]);
+//!
+//! // SAFETY: the partition is indexed through its layout enum.
+//! unsafe {
+//!     materials.blit_to(&storage, section, LayoutEntityData::Materials as usize);
+//!     storage.blit_part(section, LayoutEntityData::EntityMaterials as usize, &props.material[1..], 0);
+//! }
+//! ```
+
+use std::cell::Cell;
+
+use crate::{
+    render::{buffer::PartitionedTriBuffer, color::Color, texture::BindlessHandle},
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (Materials) => {
+        14
+    };
+    (EntityMaterials) => {
+        15
+    };
+}
+
+pub const SHADER_BINDING_MATERIALS: u32 = ssbo_binding!(Materials);
+pub const SHADER_BINDING_ENTITY_MATERIALS: u32 = ssbo_binding!(EntityMaterials);
+
+/// The index of a [`Material`] in a [`MaterialStorage`], stored per entity in
+/// a column.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(pub u32);
+
+impl MaterialId {
+    /// The default material, present in every [`MaterialStorage`].
+    pub const DEFAULT: Self = Self(0);
+
+    pub const fn as_index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The surface properties of an entity.
+///
+/// Corresponds to the `Material` GLSL struct in a `std430` layout, see
+/// [`MaterialGlslStruct`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    /// The base color, multiplied with the albedo texture if any.
+    pub albedo: Color,
+    /// The linear emitted color, added on top of the lit color.
+    pub emissive: [f32; 3],
+    /// The roughness, from `0.0` (mirror) to `1.0` (fully diffuse).
+    pub roughness: f32,
+    /// The bindless handle of the albedo texture, `0` if there is none.
+    pub albedo_texture: BindlessHandle,
+    /// The bindless handle of the normal map, `0` if there is none.
+    pub normal_texture: BindlessHandle,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new(Color::WHITE)
+    }
+}
+
+impl Material {
+    /// A non-emissive, untextured material of the given `albedo`.
+    pub const fn new(albedo: Color) -> Self {
+        Self {
+            albedo,
+            emissive: [0.0; 3],
+            roughness: 0.5,
+            albedo_texture: BindlessHandle(0),
+            normal_texture: BindlessHandle(0),
+        }
+    }
+
+    /// Emit `emissive`, whose alpha is ignored.
+    pub const fn with_emissive(mut self, emissive: Color) -> Self {
+        self.emissive = emissive.rgb();
+        self
+    }
+
+    pub const fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Sample the albedo from a texture, which must be made resident before
+    /// it is drawn.
+    pub const fn with_albedo_texture(mut self, handle: BindlessHandle) -> Self {
+        self.albedo_texture = handle;
+        self
+    }
+
+    /// Sample normals from a normal map, which must be made resident before
+    /// it is drawn.
+    pub const fn with_normal_texture(mut self, handle: BindlessHandle) -> Self {
+        self.normal_texture = handle;
+        self
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct Material for Material {
+        albedo: [f32; 4] => vec4;
+        emissive: [f32; 3] => vec3;
+        roughness: f32 => float;
+        albedo_texture: [u32; 2] => uvec2;
+        normal_texture: [u32; 2] => uvec2;
+    }
+}
+
+/// The table of all materials, indexed by [`MaterialId`].
+///
+/// Material edits are rare, so the table is only blitted to the sections of
+/// a triple buffer after a change, see [`MaterialStorage::blit_to`].
+#[derive(Clone, Debug)]
+pub struct MaterialStorage {
+    materials: Vec<Material>,
+    /// The amount of sections still holding a stale copy of the table.
+    stale_sections: Cell<u8>,
+}
+
+impl Default for MaterialStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MaterialStorage {
+    /// A storage holding the default material at [`MaterialId::DEFAULT`].
+    pub fn new() -> Self {
+        Self {
+            materials: vec![Material::default()],
+            stale_sections: Cell::new(3),
+        }
+    }
+
+    /// Register a new material.
+    pub fn insert(&mut self, material: Material) -> MaterialId {
+        let id = MaterialId(self.materials.len() as u32);
+        self.materials.push(material);
+        self.stale_sections.set(3);
+        id
+    }
+
+    pub fn get(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(id.as_index())
+    }
+
+    /// Replace the material at `id`.
+    ///
+    /// # Panics
+    /// If `id` is not a material of this storage.
+    pub fn set(&mut self, id: MaterialId, material: Material) {
+        self.materials[id.as_index()] = material;
+        self.stale_sections.set(3);
+    }
+
+    /// All materials, indexed by [`MaterialId`].
+    pub fn as_slice(&self) -> &[Material] {
+        &self.materials
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    /// Copy the table in a `partition` of a `section` of `buffer`, if it has
+    /// changed since that section was last written.
+    ///
+    /// # Returns
+    /// Whether the table was blitted.
+    ///
+    /// # Safety
+    /// `partition` must be a partition of [`Material`]s.
+    pub unsafe fn blit_to<const PARTS: usize>(
+        &self,
+        buffer: &PartitionedTriBuffer<PARTS>,
+        section: usize,
+        partition: usize,
+    ) -> bool {
+        let stale = self.stale_sections.get();
+        if stale == 0 {
+            return false;
+        }
+        unsafe {
+            buffer.blit_part(section, partition, &self.materials, 0);
+        }
+        self.stale_sections.set(stale - 1);
+        true
+    }
+}
+
+/// Material SSBO interfaces.
+///
+/// Contains the SSBO declaration of the [`MaterialStorage`] table, on binding
+/// index 14. Requires the `Material` struct definition, see
+/// [`MaterialGlslStruct::as_definition`].
+pub const GLSL_SSBO_MATERIALS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf Materials => {
+        [dyn_array Material: materials]
+    }
+};
+
+/// Contains the SSBO declaration of the per-entity [`MaterialId`]s, on
+/// binding index 15.
+pub const GLSL_SSBO_ENTITY_MATERIALS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf EntityMaterials => {
+        [dyn_array uint: entity_materials]
+    }
+};
+
+/// The material of the entity at `id` of the [`GLSL_SSBO_ENTITY_MATERIALS`]
+/// partition.
+pub const GLSL_LIB_ENTITY_MATERIAL: GlslLib = crate::shader_glsl_lib! {
+    Material entity_material [ id: uint ] => "
+        return materials[entity_materials[id]];
+    "
+};
+
+/// The albedo of material `m` at `uv`, sampling its albedo texture if any.
+///
+/// Requires `ARB_bindless_texture`, see
+/// [`GLSL_EXT_BINDLESS`](super::texture::bindless::GLSL_EXT_BINDLESS).
+pub const GLSL_LIB_MATERIAL_ALBEDO: GlslLib = crate::shader_glsl_lib! {
+    vec4 material_albedo [ m: Material, uv: vec2 ] => "
+        if (m.albedo_texture == uvec2(0u)) {
+            return m.albedo;
+        }
+        return m.albedo * texture(sampler2D(m.albedo_texture), uv);
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn material_table() {
+        assert_eq!(size_of::<Material>(), 48);
+        assert_eq!(std::mem::offset_of!(Material, roughness), 28);
+        assert_eq!(std::mem::offset_of!(Material, albedo_texture), 32);
+
+        let mut storage = MaterialStorage::new();
+        let red = storage.insert(Material::new(Color::linear(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(red, MaterialId(1));
+        assert_eq!(storage.get(MaterialId::DEFAULT), Some(&Material::default()));
+        assert_eq!(storage.get(MaterialId(2)), None);
+
+        let glsl = MaterialGlslStruct::as_definition_str();
+        assert!(glsl.contains("float roughness;"));
+        assert!(glsl.contains("uvec2 normal_texture;"));
+    }
+}
@@ -1,6 +1,35 @@
+pub mod animation;
+pub mod barrier;
+pub mod batch;
+pub mod bounds;
 pub mod buffer;
+pub mod capture;
+pub mod clear;
+pub mod color;
 pub mod command;
+pub mod compute;
+pub mod config;
+pub mod cull;
+pub mod debug_output;
+pub mod depth;
+pub mod draw_debug;
+pub mod frame;
+pub mod gl_state;
+pub mod light;
+pub mod material;
+pub mod per_draw;
+pub mod pool;
+pub mod ring;
+pub mod select;
+pub mod settings;
+pub mod sprite;
+pub mod stage;
 pub mod sync;
+pub mod text;
+pub mod texture;
+pub mod timer;
+pub mod verify;
+pub mod visibility;
 
 use std::sync::Arc;
 
@@ -9,13 +38,32 @@ use glam::Vec4Swizzles;
 use crate::{
     RenderHandler,
     mesh::Meshadata,
-    render::{buffer::ImmutableBuffer, sync::SyncBarrier},
+    platform::ThreadHints,
+    render::{
+        barrier::Barriers,
+        buffer::ImmutableBuffer,
+        clear::ClearPolicy,
+        depth::{DepthMode, DepthTarget},
+        frame::{FrameGlobals, FrameGlobalsBuffer},
+        gl_state::GlStateChecker,
+        pool::MeshPools,
+        settings::RenderSettings,
+        stage::{CrossHooks, DefaultStages, RenderStage, Stage, StageContext},
+        sync::{LaneFences, SyncBarrier},
+    },
     state::{
         camera::ViewPoint,
-        cross::{Consumer, Cross},
+        cross::{Consumer, Cross, SectionAge},
     },
+    zone::{self, GpuZones},
 };
 
+/// Marks a type owning GL objects as neither [`Send`] nor [`Sync`].
+///
+/// GL objects are created, used and deleted with GL calls, which must be
+/// made on the thread of the context.
+pub(crate) type NotSend = std::marker::PhantomData<std::rc::Rc<()>>;
+
 pub trait GlPropertyEnum {
     fn as_gl_enum(&self) -> u32;
 }
@@ -36,11 +84,30 @@ pub fn projection_perspective(width: f32, height: f32, fov_degrees: f32) -> glam
     )
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+/// The size of the framebuffer, in physical pixels.
+///
+/// On scaled (high-DPI) displays, the [`scale`](Self::scale) converts between
+/// physical pixels and the logical pixels UI layouts and input are expressed
+/// in.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct Resolution {
     dirty: bool,
     pub width: f32,
     pub height: f32,
+    /// The content scale of the display: the amount of physical pixels per
+    /// logical pixel, e.g. `2.0` on a display scaled to 200%.
+    pub scale: f32,
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self {
+            dirty: false,
+            width: 0.0,
+            height: 0.0,
+            scale: 1.0,
+        }
+    }
 }
 
 impl Resolution {
@@ -48,6 +115,12 @@ impl Resolution {
         self.dirty
     }
 
+    /// Whether the width or the height is zero, e.g. while the window is
+    /// minimised.
+    pub fn is_empty(&self) -> bool {
+        self.width < 1.0 || self.height < 1.0
+    }
+
     pub fn width(&self) -> f32 {
         self.width
     }
@@ -56,10 +129,35 @@ impl Resolution {
         self.height
     }
 
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// The width in logical pixels.
+    pub fn logical_width(&self) -> f32 {
+        self.width / self.scale
+    }
+
+    /// The height in logical pixels.
+    pub fn logical_height(&self) -> f32 {
+        self.height / self.scale
+    }
+
+    /// Convert a `point` in logical pixels to physical pixels.
+    pub fn to_physical(&self, point: (f32, f32)) -> (f32, f32) {
+        (point.0 * self.scale, point.1 * self.scale)
+    }
+
+    /// Convert a `point` in physical pixels to logical pixels.
+    pub fn to_logical(&self, point: (f32, f32)) -> (f32, f32) {
+        (point.0 / self.scale, point.1 / self.scale)
+    }
+
     pub fn to_half(&self) -> Resolution {
         Resolution {
             width: self.width / 2f32,
             height: self.height / 2f32,
+            scale: self.scale,
             dirty: true,
         }
     }
@@ -68,6 +166,7 @@ impl Resolution {
         Resolution {
             width: self.width * 2f32,
             height: self.height * 2f32,
+            scale: self.scale,
             dirty: true,
         }
     }
@@ -76,6 +175,7 @@ impl Resolution {
         Resolution {
             width: self.width / 4f32,
             height: self.height / 4f32,
+            scale: self.scale,
             dirty: true,
         }
     }
@@ -125,6 +225,23 @@ impl ScreenSpace {
         &mut self.resolution
     }
 
+    /// The content scale of the display, see [`Resolution::scale`].
+    pub fn content_scale(&self) -> f32 {
+        self.resolution.scale
+    }
+
+    /// Set the content `scale` of the display, see [`Resolution::scale`].
+    ///
+    /// # Panics
+    /// If `scale` is not positive.
+    pub fn set_content_scale(&mut self, scale: f32) {
+        assert!(scale > 0.0, "content scale must be positive, got {scale}");
+        if self.resolution.scale != scale {
+            self.resolution.scale = scale;
+            self.resolution.dirty = true;
+        }
+    }
+
     pub fn projection(&self) -> &glam::Mat4 {
         &self.projection
     }
@@ -141,6 +258,11 @@ impl ScreenSpace {
         &mut self.ortho_proj
     }
 
+    /// Convert a `screen` point, in physical pixels, to normalized device
+    /// coordinates.
+    ///
+    /// Points in logical pixels, e.g. from a UI layout, must be converted
+    /// with [`Resolution::to_physical`] first.
     #[inline]
     pub const fn to_ndc(&self, screen: (f32, f32)) -> glam::Vec3 {
         let x = (2.0 * screen.0) / self.resolution.width - 1.0;
@@ -171,31 +293,56 @@ impl ScreenSpace {
 }
 
 /// Render state for the Janus rendering Context
+///
+/// The frame is drawn by the [`RenderStage`]s `S`, see [`stage`].
+/// It is rendered with a depth buffer, see [`depth`].
 #[derive(Debug, Default)]
-pub struct Renderer<D: Sized, T: RenderHandler<D>> {
+pub struct Renderer<D: Sized, T: RenderHandler<D>, S: RenderStage<D, T> = DefaultStages> {
     // only used for rendering as sometimes opengl may refuse to draw anything
     // without a vao bound during draw calls
     render_vao: u32,
 
-    pub mesh_buffer: ImmutableBuffer<2>,
-    pub metadata: Meshadata,
+    pub mesh_pools: MeshPools,
 
     pub screen_space: janus::sync::Mirror<ScreenSpace>,
     pub viewpoint: Arc<janus::sync::TriCell<ViewPoint>>,
 
     pub(crate) handler: T,
+    stages: S,
+    cross_hooks: CrossHooks<D>,
+
+    globals: FrameGlobalsBuffer,
+    settings: RenderSettings,
+    gpu_timer: timer::GpuTimer,
+    gpu_zones: GpuZones,
+    gl_state: GlStateChecker,
+    depth: DepthTarget,
+    clear: ClearPolicy,
+    barriers: Barriers,
+    /// The frame globals time at which the frame data was last new.
+    fresh_time: f32,
 
     sync_barrier: SyncBarrier,
+    lane_fences: LaneFences,
+    section_age: SectionAge,
     pub boundary: Cross<Consumer, D>,
+    /// Applied to the render thread on the next frame.
+    thread_hints: Option<ThreadHints>,
 }
 
-impl<D: Sized, T: RenderHandler<D>> Renderer<D, T> {
+impl<D: Sized, T: RenderHandler<D>, S: RenderStage<D, T>> Renderer<D, T, S> {
     pub fn handler_init_callback<F: FnOnce(&mut T)>(&mut self, callback: F) {
         callback(&mut self.handler)
     }
 
-    pub fn mesh_buffer(&self) -> &ImmutableBuffer<2> {
-        &self.mesh_buffer
+    /// The mesh buffer of the default pool.
+    pub fn mesh_buffer(&self) -> &ImmutableBuffer<3> {
+        &self.mesh_pools.default_pool().buffer
+    }
+
+    /// The mesh buffers of every pool, see [`MeshPools`].
+    pub fn mesh_pools(&self) -> &MeshPools {
+        &self.mesh_pools
     }
 
     pub fn screen_space(&self) -> &ScreenSpace {
@@ -206,8 +353,9 @@ impl<D: Sized, T: RenderHandler<D>> Renderer<D, T> {
         &self.screen_space
     }
 
+    /// The mesh metadata of the default pool.
     pub fn metadata(&self) -> &Meshadata {
-        &self.metadata
+        &self.mesh_pools.default_pool().metadata
     }
 
     pub fn boundary(&self) -> &Cross<Consumer, D> {
@@ -221,21 +369,143 @@ impl<D: Sized, T: RenderHandler<D>> Renderer<D, T> {
     pub fn viewpoint_shared(&self) -> &Arc<janus::sync::TriCell<ViewPoint>> {
         &self.viewpoint
     }
+
+    /// The age of the frame data of the last rendered frame.
+    ///
+    /// See [`SectionAge`].
+    pub fn section_age(&self) -> SectionAge {
+        self.section_age
+    }
+
+    /// The builtin uniforms of the current frame.
+    ///
+    /// See [`FrameGlobals`].
+    pub fn frame_globals(&self) -> &FrameGlobals {
+        self.globals.globals()
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut RenderSettings {
+        &mut self.settings
+    }
+
+    pub fn stages(&self) -> &S {
+        &self.stages
+    }
+
+    pub fn stages_mut(&mut self) -> &mut S {
+        &mut self.stages
+    }
+
+    /// The user hooks run inside the boundary cross.
+    ///
+    /// See [`CrossHooks`].
+    pub fn cross_hooks_mut(&mut self) -> &mut CrossHooks<D> {
+        &mut self.cross_hooks
+    }
+
+    /// The GL state expected after each stage, checked in debug builds.
+    ///
+    /// See [`GlStateChecker`].
+    pub fn gl_state_checker(&self) -> &GlStateChecker {
+        &self.gl_state
+    }
+
+    pub fn gl_state_checker_mut(&mut self) -> &mut GlStateChecker {
+        &mut self.gl_state
+    }
+
+    /// The memory barriers issued between passes, see [`barrier`].
+    pub fn barriers(&self) -> &Barriers {
+        &self.barriers
+    }
+
+    pub fn barriers_mut(&mut self) -> &mut Barriers {
+        &mut self.barriers
+    }
+
+    /// How the frame is cleared, see [`ClearPolicy`].
+    pub fn clear_policy(&self) -> &ClearPolicy {
+        &self.clear
+    }
+
+    pub fn set_clear_policy(&mut self, policy: ClearPolicy) {
+        self.clear = policy;
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth.mode()
+    }
+
+    /// Enable or disable the depth buffer and depth testing of the next
+    /// frames.
+    ///
+    /// See [`DepthMode`].
+    pub fn set_depth_mode(&mut self, mode: DepthMode) {
+        self.depth.set_mode(mode);
+    }
+
+    /// Set the content `scale` of the display, as reported by the windowing
+    /// layer, see [`Resolution::scale`].
+    ///
+    /// # Panics
+    /// If `scale` is not positive.
+    pub fn set_content_scale(&mut self, scale: f32) {
+        self.screen_space
+            .publish_with(|screen| screen.set_content_scale(scale));
+    }
+
+    /// Whether the resolution is empty, e.g. while the window is minimised.
+    ///
+    /// Frames are not rendered while minimised, and no stage is run: the
+    /// sections published by the producer are crossed without being read.
+    /// Rendering resumes with the new resolution once it is restored.
+    pub fn is_minimised(&self) -> bool {
+        self.screen_space.resolution.is_empty()
+    }
+
+    /// Apply the scheduling `hints` to the render thread at the start of the
+    /// next frame, see [`platform`](crate::platform).
+    pub fn set_thread_hints(&mut self, hints: Option<ThreadHints>) {
+        self.thread_hints = hints;
+    }
 }
 
-impl<D: Sized, T: RenderHandler<D>> janus::context::Draw for Renderer<D, T> {
+impl<D: Sized, T: RenderHandler<D>, S: RenderStage<D, T>> janus::context::Draw
+    for Renderer<D, T, S>
+{
     fn draw(&mut self, dt: janus::context::DeltaTime) {
+        let _zone = zone::zone("draw");
+        crate::platform::apply_once(&mut self.thread_hints, "render");
         if self.render_vao == 0 {
             unsafe {
                 janus::gl::GenVertexArrays(1, &mut self.render_vao);
                 janus::gl::BindVertexArray(self.render_vao);
             }
+            self.mesh_pools
+                .default_pool()
+                .buffer
+                .bind_element_buffer(self.render_vao);
         }
         {
             if self.screen_space.check_sync_status() {
                 self.screen_space.sync().unwrap();
                 let resolution = self.screen_space.resolution;
-                if resolution.is_changed() {
+                if resolution.is_changed() && resolution.is_empty() {
+                    // keep the projections of the last valid resolution, as
+                    // they would be degenerate
+                    self.screen_space.publish_with(|screen| {
+                        screen.resolution.dirty = false;
+                    });
+                    tracing::event!(
+                        name: "render.minimised",
+                        tracing::Level::DEBUG,
+                        "Resolution is empty, pausing rendering"
+                    );
+                } else if resolution.is_changed() {
                     self.screen_space.publish_with(|screen| {
                         let fov = screen.fov();
                         let w = resolution.width;
@@ -255,33 +525,86 @@ impl<D: Sized, T: RenderHandler<D>> janus::context::Draw for Renderer<D, T> {
             }
         }
 
-        self.handler
-            .pre_frame(&mut self.screen_space, &self.viewpoint, dt);
-        self.boundary
-            .cross(&mut self.sync_barrier, |section, storage| {
-                self.mesh_buffer.bind_shader_storage();
-                self.handler.render_frame(&storage, section);
-            });
+        if self.is_minimised() {
+            // keep draining the boundary, so that the producer is not held
+            // back by the frames in flight limit nor reported as stalled
+            let noop = |_, _, _: &D| {};
+            self.section_age =
+                self.boundary
+                    .cross_lanes(&mut self.sync_barrier, &self.lane_fences, noop);
+            return;
+        }
 
-        #[cfg(debug_assertions)]
-        {
-            #[allow(unused_assignments)]
-            let mut err = 0;
-            loop {
-                use tracing::Level;
-
-                err = unsafe { janus::gl::GetError() };
-                if err == 0 {
-                    break;
+        capture::begin_frame();
+        self.gpu_timer.begin();
+        self.gpu_zones.begin("frame");
+        let resolution = self.screen_space.resolution;
+        self.depth.begin(
+            resolution.width as i32,
+            resolution.height as i32,
+            &self.clear,
+        );
+        let mut ctx = StageContext {
+            handler: &mut self.handler,
+            screen_space: &mut self.screen_space,
+            viewpoint: &self.viewpoint,
+            mesh_pools: &self.mesh_pools,
+            globals: &mut self.globals,
+            lanes: &self.lane_fences,
+            barriers: &mut self.barriers,
+            delta: dt,
+            age: self.section_age,
+        };
+        let stages = &mut self.stages;
+        let cross_hooks = &mut self.cross_hooks;
+        let settings = &self.settings;
+        let fresh_time = &mut self.fresh_time;
+        let gpu_zones = &mut self.gpu_zones;
+        let gl_state = &self.gl_state;
+
+        if cross_hooks.has_exclusive() {
+            self.boundary
+                .try_exclusive(|storage| cross_hooks.run_exclusive(storage));
+        }
+        stages.pre_frame(&mut ctx);
+        gl_state.check(Stage::PreFrame);
+        stages.bind_globals(&mut ctx);
+        gl_state.check(Stage::BindGlobals);
+        let cross_zone = zone::zone("cross");
+        self.section_age = self.boundary.cross_lanes(
+            &mut self.sync_barrier,
+            &self.lane_fences,
+            |section, age, storage| {
+                ctx.age = age;
+
+                let time = ctx.globals.globals().time;
+                if age.is_new() {
+                    *fresh_time = time;
                 }
-
-                tracing::event!(
-                    name: "render.debug.gl_err",
-                    Level::DEBUG,
-                    "gl error: {err}"
-                );
-            }
+                ctx.globals
+                    .set_extrapolation(settings.extrapolation(*fresh_time, time));
+
+                gpu_zones.begin("scene");
+                stages.scene(&mut ctx, storage, section);
+                gl_state.check(Stage::Scene);
+                cross_hooks.run(section, storage);
+                gl_state.check(Stage::Hooks);
+                stages.post(&mut ctx, storage, section);
+                gl_state.check(Stage::Post);
+                gpu_zones.end();
+            },
+        );
+        drop(cross_zone);
+        self.depth.resolve();
+        stages.present(&mut ctx);
+        gl_state.check(Stage::Present);
+        self.gpu_zones.end();
+        self.gpu_zones.collect();
+        if let Some(gpu_time) = self.gpu_timer.end() {
+            self.boundary.report_gpu_time(gpu_time);
         }
+        capture::end_frame();
+        zone::frame_mark();
     }
 
     fn set_resolution(&mut self, (w, h): (f32, f32)) {
@@ -290,12 +613,13 @@ impl<D: Sized, T: RenderHandler<D>> janus::context::Draw for Renderer<D, T> {
                 dirty: true,
                 width: w,
                 height: h,
+                scale: screen.resolution.scale,
             }
         });
     }
 }
 
-impl<D: Sized, T: RenderHandler<D>> Drop for Renderer<D, T> {
+impl<D: Sized, T: RenderHandler<D>, S: RenderStage<D, T>> Drop for Renderer<D, T, S> {
     fn drop(&mut self) {
         unsafe {
             janus::gl::DeleteVertexArrays(1, &self.render_vao);
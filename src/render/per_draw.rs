@@ -0,0 +1,86 @@
+//! Per-draw constants indexed by the base instance of draw commands.
+//!
+//! Multi-draw calls identify each draw with `gl_DrawID`, which some drivers
+//! implement slowly, or not as a dynamically uniform value, and which
+//! restarts from 0 for every multi-draw call of a batch. Instead, the
+//! `base_instance` of each indirect command is set to its index in the
+//! command buffer, and the constants of the draw (e.g. its material and the
+//! first slot of its transforms) are written at the same index in the
+//! partition of [`GLSL_SSBO_DRAW_CONSTANTS`]. Vertex shaders read them with
+//! `draw_constants_at(gl_BaseInstance)`, see [`GLSL_LIB_DRAW_CONSTANTS`].
+//!
+//! `gl_InstanceID` does not include the base instance, so instanced draws
+//! still count their instances from 0, e.g. from the transform slot of their
+//! constants. Commands indexed this way cannot use their base instance for
+//! anything else, such as the instance ranges of
+//! [`cull::prepare_commands`](super::cull::prepare_commands).
+//!
+//! # Example
+//! ```rust,ignore
+//! // UPLOAD
+//! batcher.push_with_constants(material, mesh, command, DrawConstants::new(material, slot));
+//! batcher.blit_with_constants(&commands, &constants, section, &mut buckets);
+//!
+//! // VERTEX SHADER
+//! DrawConstants draw = draw_constants_at(gl_BaseInstance);
+//! Transform transform = transforms[draw.transform + gl_InstanceID];
+//! ```
+
+use crate::shader::glsl::{GlslLib, GlslStorage};
+
+macro_rules! ssbo_binding {
+    (PerDraw) => {
+        19
+    };
+}
+
+pub const SHADER_BINDING_DRAW_CONSTANTS: u32 = ssbo_binding!(PerDraw);
+
+/// The constants of a single draw.
+///
+/// Corresponds to the `DrawConstants` struct of [`GLSL_SSBO_DRAW_CONSTANTS`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DrawConstants {
+    /// The material of the draw, e.g. a
+    /// [`MaterialId`](super::material::MaterialId).
+    pub material: u32,
+    /// The first slot of the per-instance data of the draw.
+    pub transform: u32,
+}
+
+impl DrawConstants {
+    pub const fn new(material: u32, transform: u32) -> Self {
+        Self {
+            material,
+            transform,
+        }
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct DrawConstants for DrawConstants {
+        material: u32 => uint;
+        transform: u32 => uint;
+    }
+}
+
+/// Per-draw constants SSBO interface.
+///
+/// Contains the SSBO declaration of a [`DrawConstants`] partition, on binding
+/// index 19. Requires the `DrawConstants` struct definition, see
+/// [`DrawConstantsGlslStruct::as_definition`].
+pub const GLSL_SSBO_DRAW_CONSTANTS: GlslStorage = crate::shader_glsl_ssbo! {
+    buf PerDraw => {
+        [dyn_array DrawConstants: draw_constants]
+    }
+};
+
+/// The constants of the draw with the base instance `draw`, i.e.
+/// `draw_constants_at(gl_BaseInstance)` in vertex shaders. Requires
+/// [`GLSL_SSBO_DRAW_CONSTANTS`].
+pub const GLSL_LIB_DRAW_CONSTANTS: GlslLib = crate::shader_glsl_lib! {
+    DrawConstants draw_constants_at [ draw: uint ] => "
+        return draw_constants[draw];
+    "
+};
@@ -0,0 +1,210 @@
+//! Separate mesh buffers, one per [`MeshPoolId`].
+//!
+//! A single mesh buffer holds every mesh of the application, and it is
+//! immutable once built. Pools split meshes with different lifetimes, e.g. the
+//! static world and the streamed characters, into their own buffers, each
+//! with its own [`Meshadata`].
+//!
+//! Every pool binds its partitions at the same shader storage bindings, so
+//! only one pool can be bound at a time: the default pool is bound at the
+//! start of the frame, and
+//! [`GpuCommandDispatch::dispatch_pooled_buckets`](super::command::GpuCommandDispatch::dispatch_pooled_buckets)
+//! binds the pool of each batch.
+//!
+//! # Example
+//! ```rust,ignore
+//! let characters = MeshPoolId::new(1);
+//! let mut staging = MeshStaging::for_pool(characters);
+//! let knight = staging.stage(&knight_vertices);
+//! startup.with_mesh_pool(layout_mesh_buffer!(count: 64; vertices: 200_000), staging);
+//! ```
+
+use crate::{
+    mesh::{self, MeshPoolId, MeshStaging, Meshadata},
+    render::buffer::{ImmutableBuffer, Layout, immutable},
+};
+
+/// The mesh buffer and metadata of a pool.
+#[derive(Debug, Default)]
+pub struct MeshPool {
+    pub buffer: ImmutableBuffer<3>,
+    pub metadata: Meshadata,
+}
+
+impl MeshPool {
+    /// Stage the submitted meshes of `staging` and upload all of them to a
+    /// new mesh buffer of `layout`.
+    ///
+    /// With `dynamic`, the partitions of the buffer can be updated later, see
+    /// [`ImmutableBuffer::update_partition`].
+    ///
+    /// # Panics
+    /// If the index partition of `layout` is not its first partition, as
+    /// declared by [`layout_mesh_buffer!`](crate::layout_mesh_buffer).
+    pub fn build(layout: Layout<3>, mut staging: MeshStaging, dynamic: bool) -> Self {
+        assert_eq!(
+            layout.element_base(),
+            Some(0),
+            "the index partition of a mesh layout must be its first partition"
+        );
+        staging.stage_submitted();
+
+        let mut buffer = if dynamic {
+            immutable::uninit_dynamic(layout)
+        } else {
+            immutable::uninit(layout)
+        };
+
+        let indices = staging.index_storage();
+        let ibs = mesh::BUFFER_INDEX_STORAGE_INDEX;
+        buffer.fill_partition(ibs, indices);
+
+        let vertices = staging.vertex_storage();
+        let vbs = mesh::BUFFER_VERTEX_STORAGE_INDEX;
+        buffer.fill_partition(vbs, vertices);
+
+        let metadata = staging.close();
+        let mds = mesh::BUFFER_MESH_META_INDEX;
+        buffer.fill_partition(mds, &metadata);
+
+        Self {
+            buffer: buffer.finish(),
+            metadata,
+        }
+    }
+
+    /// Bind the partitions of the pool to their shader storage bindings, and
+    /// its indices as the element array buffer of the bound vertex array.
+    pub fn bind(&self) {
+        self.buffer.bind_shader_storage();
+        self.buffer.bind_current_element_buffer();
+    }
+}
+
+/// The mesh pools of the renderer, indexed by [`MeshPoolId`].
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct MeshPools {
+    pools: Vec<MeshPool>,
+}
+
+impl Default for MeshPools {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeshPools {
+    /// Only the empty default pool.
+    pub fn new() -> Self {
+        Self {
+            pools: vec![MeshPool::default()],
+        }
+    }
+
+    /// Set the pool of `id`, replacing the previous one.
+    ///
+    /// Pools skipped to reach `id` are empty.
+    pub fn insert(&mut self, id: MeshPoolId, pool: MeshPool) {
+        let index = id.index();
+        if index >= self.pools.len() {
+            self.pools.resize_with(index + 1, MeshPool::default);
+        }
+        self.pools[index] = pool;
+    }
+
+    /// # Panics
+    /// If there is no pool of `id`.
+    pub fn get(&self, id: MeshPoolId) -> &MeshPool {
+        &self.pools[id.index()]
+    }
+
+    /// # Panics
+    /// If there is no pool of `id`.
+    pub fn get_mut(&mut self, id: MeshPoolId) -> &mut MeshPool {
+        &mut self.pools[id.index()]
+    }
+
+    pub fn default_pool(&self) -> &MeshPool {
+        self.get(MeshPoolId::DEFAULT)
+    }
+
+    /// The amount of pools, including the default pool.
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    /// Always `false`, as the default pool always exists.
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    /// The metadata of the mesh `id`, in its pool.
+    ///
+    /// # Panics
+    /// If there is no pool of the mesh.
+    pub fn metadata(&self, id: mesh::Id) -> &mesh::Metadata {
+        self.get(id.pool()).metadata.get(id)
+    }
+
+    /// Bind the pool of `id`, see [`MeshPool::bind`].
+    ///
+    /// # Panics
+    /// If there is no pool of `id`.
+    pub fn bind(&self, id: MeshPoolId) {
+        self.get(id).bind();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{batch::CommandBatcher, command::DrawArraysIndirectCommand};
+
+    #[test]
+    fn mesh_pool_ids() {
+        let characters = MeshPoolId::new(2);
+        let mut world = Meshadata::new();
+        let mut streamed = Meshadata::for_pool(characters);
+        let rock = world.add(3);
+        let knight = streamed.add(6);
+        assert_eq!((rock.pool(), rock.index()), (MeshPoolId::DEFAULT, 1));
+        assert_eq!((knight.pool(), knight.index()), (characters, 1));
+        assert_eq!(
+            (rock.to_string(), knight.to_string()),
+            ("1".into(), "2:1".into())
+        );
+
+        let mut pools = MeshPools::new();
+        pools.get_mut(MeshPoolId::DEFAULT).metadata = world;
+        pools.insert(
+            characters,
+            MeshPool {
+                buffer: ImmutableBuffer::default(),
+                metadata: streamed,
+            },
+        );
+        assert_eq!(pools.len(), 3);
+        assert_eq!(pools.metadata(knight).length(), 6);
+        assert_eq!(pools.metadata(rock).length(), 3);
+
+        // buckets of the same material are grouped by pool
+        let mut batcher = CommandBatcher::<DrawArraysIndirectCommand, u32>::new();
+        let tree = unsafe { mesh::Id::from_value(2) };
+        batcher.push(0, rock, Default::default());
+        batcher.push(0, knight, Default::default());
+        batcher.push(0, tree, Default::default());
+        let mut buf = vec![DrawArraysIndirectCommand::default(); 3];
+        let mut buckets = Vec::new();
+        batcher.upload(&mut buf, &mut buckets);
+        let pools = buckets
+            .iter()
+            .map(|b| b.key.mesh.pool())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pools,
+            [MeshPoolId::DEFAULT, MeshPoolId::DEFAULT, characters]
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//! A persistently mapped ring buffer for transient render data.
+//!
+//! Data that is only read by the draws of a single frame (debug draw
+//! vertices, text glyphs, per-frame uniforms...) does not need a dedicated,
+//! triple buffered storage: it is written once on the render thread, read by
+//! the GPU, and can be overwritten as soon as those draws complete.
+//!
+//! A [`RingBuffer`] hands out aligned allocations from a single coherent
+//! mapping, without any synchronisation between them. Once all draws reading
+//! the allocations of a frame have been issued, [`RingBuffer::fence`] closes
+//! the region of the buffer they were allocated from with a GL fence. When
+//! the ring wraps around, regions are only reused once their fence is
+//! signaled: if the GPU is still reading from the region an allocation needs,
+//! the allocation stalls until it is done, and the stall is reported.
+//!
+//! # Example
+//! ```rust,ignore
+//! let mut ring = RingBuffer::new(4 * 1024 * 1024);
+//!
+//! // in RenderHandler::render_frame
+//! let glyphs = ring.push(&glyph_quads, 16)?;
+//! ring.bind_range(janus::gl::SHADER_STORAGE_BUFFER, SHADER_BINDING_GLYPHS, glyphs);
+//! text_shader.dispatch(glyph_quads.len());
+//!
+//! ring.fence();
+//! ```
+
+use std::{collections::VecDeque, ops::Range};
+
+use janus::gl::types::__GLsync;
+
+use crate::render::NotSend;
+
+/// How long an allocation waits for the GPU to release a region, in
+/// nanoseconds, before failing.
+const STALL_TIMEOUT_NS: u64 = 100_000_000;
+
+/// The maximum alignment of allocations.
+pub const MAX_ALIGN: usize = 256;
+
+/// Why an allocation could not be made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RingError {
+    /// The allocation is larger than the whole ring.
+    TooLarge { size: usize, capacity: usize },
+    /// The allocations made since the last [`RingBuffer::fence`] already
+    /// fill the ring.
+    Full,
+    /// The GPU did not release the needed region in time.
+    Timeout,
+}
+
+impl std::fmt::Display for RingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge { size, capacity } => write!(
+                f,
+                "allocation of {size} bytes exceeds the ring capacity of {capacity} bytes"
+            ),
+            Self::Full => write!(f, "ring buffer is full of unfenced allocations"),
+            Self::Timeout => write!(f, "timed out waiting for the GPU to release the ring"),
+        }
+    }
+}
+
+impl std::error::Error for RingError {}
+
+/// A range of bytes of a [`RingBuffer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RingRange {
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl RingRange {
+    pub const fn as_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.size
+    }
+}
+
+/// The allocation bookkeeping of a ring, independent of the fences it waits
+/// on.
+///
+/// Offsets are virtual: they grow monotonically, and the physical offset of
+/// a virtual offset is its remainder by the capacity.
+#[derive(Debug)]
+struct Cursor<F> {
+    capacity: usize,
+    /// The virtual offset of the next allocation.
+    head: u64,
+    /// The virtual start of the oldest region still in use.
+    tail: u64,
+    /// The virtual start of the allocations not fenced yet.
+    unfenced: u64,
+    /// The fenced regions, from the oldest, with their virtual end.
+    regions: VecDeque<(u64, F)>,
+    /// The fences of the regions released since they were last taken.
+    retired: Vec<F>,
+}
+
+impl<F> Cursor<F> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            head: 0,
+            tail: 0,
+            unfenced: 0,
+            regions: VecDeque::new(),
+            retired: Vec::new(),
+        }
+    }
+
+    /// Reserve `size` bytes aligned to `align`, releasing the regions that
+    /// overlap them.
+    ///
+    /// `wait` blocks until the fence of a region is signaled, and returns
+    /// whether it was before the timeout.
+    ///
+    /// # Returns
+    /// The physical offset of the reservation.
+    fn reserve(
+        &mut self,
+        size: usize,
+        align: usize,
+        mut wait: impl FnMut(&F) -> bool,
+    ) -> Result<usize, RingError> {
+        if size > self.capacity {
+            return Err(RingError::TooLarge {
+                size,
+                capacity: self.capacity,
+            });
+        }
+
+        let capacity = self.capacity as u64;
+        let mut start = self.head.next_multiple_of(align as u64);
+        // allocations never straddle the end of the ring
+        if start % capacity + size as u64 > capacity {
+            start = start.next_multiple_of(capacity);
+        }
+        let end = start + size as u64;
+
+        while end > self.tail + capacity {
+            let Some((region_end, fence)) = self.regions.front() else {
+                return Err(RingError::Full);
+            };
+            if !wait(fence) {
+                return Err(RingError::Timeout);
+            }
+            self.tail = *region_end;
+            self.retired
+                .extend(self.regions.pop_front().map(|(_, f)| f));
+        }
+
+        self.head = end;
+        Ok((start % capacity) as usize)
+    }
+
+    /// Close the region of the allocations made since the last call.
+    ///
+    /// # Returns
+    /// Whether any allocation was made.
+    fn fence(&mut self, fence: impl FnOnce() -> F) -> bool {
+        if self.head == self.unfenced {
+            return false;
+        }
+        self.regions.push_back((self.head, fence()));
+        self.unfenced = self.head;
+        true
+    }
+
+    /// Release the oldest regions for which `is_signaled` holds.
+    fn release(&mut self, mut is_signaled: impl FnMut(&F) -> bool) {
+        while let Some((end, fence)) = self.regions.front() {
+            if !is_signaled(fence) {
+                break;
+            }
+            self.tail = *end;
+            self.retired
+                .extend(self.regions.pop_front().map(|(_, f)| f));
+        }
+    }
+
+    /// The bytes allocated and not yet released.
+    fn in_flight(&self) -> usize {
+        (self.head - self.tail.min(self.head)) as usize
+    }
+}
+
+/// A persistently and coherently mapped ring of transient allocations.
+///
+/// All operations require GL calls, and must be made on the render thread.
+#[derive(Debug)]
+pub struct RingBuffer {
+    gl_obj: u32,
+    ptr: *mut u8,
+    cursor: Cursor<*const __GLsync>,
+    stalls: usize,
+
+    _marker: NotSend,
+}
+
+impl RingBuffer {
+    /// Create a ring of `capacity` bytes, rounded up to [`MAX_ALIGN`].
+    ///
+    /// # Panics
+    /// If `capacity` is `0`.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "attempted to create an empty ring buffer");
+        let capacity = capacity.next_multiple_of(MAX_ALIGN);
+
+        let mut gl_obj = 0;
+        let flags =
+            janus::gl::MAP_WRITE_BIT | janus::gl::MAP_PERSISTENT_BIT | janus::gl::MAP_COHERENT_BIT;
+        let ptr = unsafe {
+            janus::gl::CreateBuffers(1, &mut gl_obj);
+            janus::gl::NamedBufferStorage(gl_obj, capacity as isize, std::ptr::null(), flags);
+            janus::gl::MapNamedBufferRange(gl_obj, 0, capacity as isize, flags) as *mut u8
+        };
+
+        Self {
+            gl_obj,
+            ptr,
+            cursor: Cursor::new(capacity),
+            stalls: 0,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Allocate `len` elements of `T`, aligned to `align` bytes.
+    ///
+    /// The alignment must satisfy the binding the allocation is used with,
+    /// e.g. `GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT` for uniform blocks.
+    ///
+    /// # Returns
+    /// The range of the allocation and its mapped memory, which must be fully
+    /// written before it is read by the GPU.
+    ///
+    /// # Panics
+    /// If `align` is not a power of two, lower than the alignment of `T` or
+    /// greater than [`MAX_ALIGN`].
+    pub fn alloc<T: Copy>(
+        &mut self,
+        len: usize,
+        align: usize,
+    ) -> Result<(RingRange, &mut [std::mem::MaybeUninit<T>]), RingError> {
+        assert!(
+            align.is_power_of_two() && align >= align_of::<T>() && align <= MAX_ALIGN,
+            "invalid ring allocation alignment {align}"
+        );
+
+        let size = len * size_of::<T>();
+        let stalls = &mut self.stalls;
+        let offset = self.cursor.reserve(size, align, |&fence| {
+            if is_signaled(fence) {
+                return true;
+            }
+            *stalls += 1;
+
+            use tracing::Level;
+            tracing::event!(
+                name: "ring.stall",
+                Level::WARN,
+                "ring buffer allocation of {size} bytes is waiting on the GPU, consider a larger capacity"
+            );
+            let status = unsafe {
+                janus::gl::ClientWaitSync(
+                    fence,
+                    janus::gl::SYNC_FLUSH_COMMANDS_BIT,
+                    STALL_TIMEOUT_NS,
+                )
+            };
+            status == janus::gl::CONDITION_SATISFIED || status == janus::gl::ALREADY_SIGNALED
+        })?;
+        self.release();
+
+        // SAFETY: the reservation lies within the mapping, and no region the
+        // GPU may still read overlaps it.
+        let memory = unsafe { std::slice::from_raw_parts_mut(self.ptr.add(offset) as *mut _, len) };
+        Ok((RingRange { offset, size }, memory))
+    }
+
+    /// Allocate and copy `data`, aligned to `align` bytes.
+    ///
+    /// # Panics
+    /// See [`RingBuffer::alloc`].
+    pub fn push<T: Copy>(&mut self, data: &[T], align: usize) -> Result<RingRange, RingError> {
+        let (range, memory) = self.alloc::<T>(data.len(), align)?;
+        for (dst, src) in memory.iter_mut().zip(data) {
+            dst.write(*src);
+        }
+        super::buffer::bandwidth::record(self.gl_obj, None, None, range.size);
+        Ok(range)
+    }
+
+    /// Fence the allocations made since the last call, after all the GL
+    /// commands reading them have been issued.
+    pub fn fence(&mut self) {
+        self.cursor
+            .fence(|| unsafe { janus::gl::FenceSync(janus::gl::SYNC_GPU_COMMANDS_COMPLETE, 0) });
+    }
+
+    /// Bind `range` to the indexed buffer `target` (e.g.
+    /// `GL_SHADER_STORAGE_BUFFER` or `GL_UNIFORM_BUFFER`) at `index`.
+    pub fn bind_range(&self, target: u32, index: u32, range: RingRange) {
+        unsafe {
+            janus::gl::BindBufferRange(
+                target,
+                index,
+                self.gl_obj,
+                range.offset as isize,
+                range.size as isize,
+            );
+        }
+    }
+
+    /// Delete the fences of the regions the GPU is done with.
+    fn release(&mut self) {
+        self.cursor.release(|&fence| is_signaled(fence));
+        for fence in self.cursor.retired.drain(..) {
+            unsafe {
+                janus::gl::DeleteSync(fence);
+            }
+        }
+    }
+
+    /// The amount of allocations that had to wait on the GPU since the last
+    /// call, resetting the counter.
+    pub fn take_stalls(&mut self) -> usize {
+        std::mem::take(&mut self.stalls)
+    }
+
+    /// The bytes allocated that the GPU may still read.
+    pub fn in_flight(&self) -> usize {
+        self.cursor.in_flight()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cursor.capacity
+    }
+
+    /// The OpenGL buffer object.
+    pub fn gl_obj(&self) -> u32 {
+        self.gl_obj
+    }
+}
+
+impl Drop for RingBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            let regions = self.cursor.regions.drain(..).map(|(_, fence)| fence);
+            for fence in regions.chain(self.cursor.retired.drain(..)) {
+                janus::gl::DeleteSync(fence);
+            }
+            janus::gl::UnmapNamedBuffer(self.gl_obj);
+            janus::gl::DeleteBuffers(1, &self.gl_obj);
+        }
+    }
+}
+
+fn is_signaled(fence: *const __GLsync) -> bool {
+    let status = unsafe { janus::gl::ClientWaitSync(fence, 0, 0) };
+    status == janus::gl::CONDITION_SATISFIED || status == janus::gl::ALREADY_SIGNALED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_and_reuse_regions() {
+        let mut cursor = Cursor::<bool>::new(1024);
+        let never = |_: &bool| panic!("no region should be waited on");
+
+        assert_eq!(cursor.reserve(100, 16, never), Ok(0));
+        assert_eq!(cursor.reserve(100, 64, never), Ok(128));
+        assert!(cursor.fence(|| true));
+        assert!(!cursor.fence(|| true));
+
+        // the rest of the ring is free
+        assert_eq!(cursor.reserve(700, 16, never), Ok(240));
+        assert!(cursor.fence(|| false));
+
+        // wrapping reuses the first region, which is signaled
+        let mut waited = 0;
+        assert_eq!(
+            cursor.reserve(200, 16, |&signaled| {
+                waited += 1;
+                signaled
+            }),
+            Ok(0)
+        );
+        assert_eq!(waited, 1);
+        assert_eq!(cursor.retired, [true]);
+
+        // the second region is still in use by the GPU
+        assert_eq!(cursor.reserve(300, 16, |&s| s), Err(RingError::Timeout));
+        assert_eq!(
+            cursor.reserve(2048, 16, never),
+            Err(RingError::TooLarge {
+                size: 2048,
+                capacity: 1024
+            })
+        );
+    }
+}
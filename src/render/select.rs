@@ -0,0 +1,206 @@
+//! Band selection of entities by a screen-space rectangle.
+//!
+//! Editor-style multi-select drags a rectangle over the view and selects
+//! every entity whose bounds fall in it. [`BandSelect`] projects the
+//! world-space [`Aabb`]s of the entities, e.g. computed by
+//! [`world_bounds`](super::bounds::world_bounds), to screen-space
+//! [`ScreenRect`]s and tests them against the dragged rectangle. Boxes
+//! crossing the camera plane are clipped to the part in front of it, and
+//! boxes entirely behind it are never selected.
+//!
+//! # Example
+//! ```rust,ignore
+//! let rect = ScreenRect::from_corners(drag_start, cursor);
+//! let band = BandSelect::from_view(renderer.screen_space(), renderer.view())
+//!     .with_mode(SelectMode::Contain);
+//! selection.clear();
+//! band.select(rect, &bounds, &mut selection);
+//! inspector.show(&selection);
+//! ```
+
+use glam::{BVec3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::{
+    render::{ScreenSpace, bounds::Aabb},
+    state::camera::ViewPoint,
+};
+
+/// The minimum clip space `w` of projected points, in front of the camera.
+const MIN_W: f32 = 1e-5;
+
+/// An axis-aligned rectangle in physical pixels, from the top left corner
+/// of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenRect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl ScreenRect {
+    /// The rectangle between two opposite corners, e.g. the start of a drag
+    /// and the cursor, in any order.
+    pub fn from_corners(a: Vec2, b: Vec2) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn contains_rect(&self, other: &Self) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+}
+
+/// Which entities a band selects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SelectMode {
+    /// Entities whose projected bounds touch the rectangle.
+    #[default]
+    Intersect,
+    /// Entities whose projected bounds are entirely inside the rectangle.
+    Contain,
+}
+
+/// Selects entities by projecting their bounds to the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BandSelect {
+    view_projection: Mat4,
+    resolution: Vec2,
+    mode: SelectMode,
+}
+
+impl BandSelect {
+    /// A selection through `view_projection` on a screen of `resolution`
+    /// physical pixels.
+    pub fn new(view_projection: Mat4, resolution: Vec2) -> Self {
+        Self {
+            view_projection,
+            resolution,
+            mode: SelectMode::default(),
+        }
+    }
+
+    /// A selection through the perspective projection of `screen`, from the
+    /// camera at `view`.
+    pub fn from_view(screen: &ScreenSpace, view: &ViewPoint) -> Self {
+        let resolution = screen.resolution();
+        Self::new(
+            *screen.projection() * view.into_mat4().inverse(),
+            Vec2::new(resolution.width(), resolution.height()),
+        )
+    }
+
+    pub fn with_mode(mut self, mode: SelectMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The screen-space bounds of the part of `bounds` in front of the
+    /// camera.
+    ///
+    /// # Returns
+    /// `None` if `bounds` is empty or entirely behind the camera.
+    pub fn project(&self, bounds: &Aabb) -> Option<ScreenRect> {
+        if bounds.is_empty() {
+            return None;
+        }
+        let corners: [Vec4; 8] = std::array::from_fn(|i| {
+            let mask = BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0);
+            let corner = Vec3::select(mask, bounds.max.truncate(), bounds.min.truncate());
+            self.view_projection * corner.extend(1.0)
+        });
+
+        let mut rect: Option<ScreenRect> = None;
+        let mut add = |clip: Vec4| {
+            let ndc = clip.truncate().truncate() / clip.w;
+            let point = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * self.resolution;
+            rect = Some(match rect {
+                Some(rect) => ScreenRect::from_corners(rect.min.min(point), rect.max.max(point)),
+                None => ScreenRect::from_corners(point, point),
+            });
+        };
+        for (i, &corner) in corners.iter().enumerate() {
+            if corner.w >= MIN_W {
+                add(corner);
+            }
+            // clip the edges crossing the camera plane, along each axis
+            for axis in [1, 2, 4] {
+                let other = corners[i ^ axis];
+                if i & axis == 0 && (corner.w < MIN_W) != (other.w < MIN_W) {
+                    let t = (MIN_W - corner.w) / (other.w - corner.w);
+                    add(corner.lerp(other, t));
+                }
+            }
+        }
+        rect
+    }
+
+    /// Append the indices of the entities of `bounds` selected by `rect` to
+    /// `out`.
+    ///
+    /// # Returns
+    /// The amount of selected entities.
+    pub fn select(&self, rect: ScreenRect, bounds: &[Aabb], out: &mut Vec<u32>) -> usize {
+        let before = out.len();
+        out.extend(bounds.iter().enumerate().filter_map(|(entity, bounds)| {
+            let projected = self.project(bounds)?;
+            let selected = match self.mode {
+                SelectMode::Intersect => rect.intersects(&projected),
+                SelectMode::Contain => rect.contains_rect(&projected),
+            };
+            selected.then_some(entity as u32)
+        }));
+        out.len() - before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn band_select_entities() {
+        let band = BandSelect::new(
+            crate::render::projection_perspective(800.0, 600.0, 90.0),
+            Vec2::new(800.0, 600.0),
+        );
+        let cube = |center: Vec3| Aabb::new(center - 0.5, center + 0.5);
+        let bounds = [
+            cube(Vec3::new(0.0, 0.0, -5.0)),
+            cube(Vec3::new(0.0, 0.0, 5.0)),
+            cube(Vec3::new(-3.0, 0.0, -5.0)),
+            // through the camera plane
+            Aabb::new(Vec3::new(-0.5, -0.5, -5.0), Vec3::new(0.5, 0.5, 5.0)),
+            Aabb::EMPTY,
+        ];
+
+        let centre = band.project(&bounds[0]).unwrap();
+        assert!(centre.contains(Vec2::new(400.0, 300.0)));
+        assert!(centre.size().x < 100.0);
+        assert_eq!(band.project(&bounds[1]), None);
+        assert!(band.project(&bounds[2]).unwrap().max.x < 400.0);
+
+        let rect = ScreenRect::from_corners(Vec2::new(500.0, 400.0), Vec2::new(300.0, 200.0));
+        let mut selected = Vec::new();
+        assert_eq!(band.select(rect, &bounds, &mut selected), 2);
+        assert_eq!(selected, [0, 3]);
+
+        // the clipped box covers the screen, so it is not contained
+        selected.clear();
+        band.with_mode(SelectMode::Contain)
+            .select(rect, &bounds, &mut selected);
+        assert_eq!(selected, [0]);
+    }
+}
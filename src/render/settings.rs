@@ -0,0 +1,44 @@
+/// Runtime settings of the [`Renderer`](super::Renderer).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    /// Extrapolate stale frame data on the GPU.
+    ///
+    /// When the producer misses a frame, the renderer crosses the same
+    /// section again (see [`SectionAge`](crate::state::cross::SectionAge)).
+    /// With extrapolation enabled, the time elapsed since the data was new is
+    /// exposed to shaders as
+    /// [`FrameGlobals::extrapolation`](super::frame::FrameGlobals::extrapolation),
+    /// so that vertex shaders can move entities along their last known
+    /// velocities with [`GLSL_LIB_EXTRAPOLATE`](super::frame::GLSL_LIB_EXTRAPOLATE),
+    /// hiding hitches of the logic thread.
+    ///
+    /// Disabled by default.
+    pub extrapolate: bool,
+    /// The maximum amount of seconds stale frame data is extrapolated by.
+    ///
+    /// Extrapolation error grows quickly: past this time, entities freeze
+    /// until new data is available.
+    pub max_extrapolation: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            extrapolate: false,
+            max_extrapolation: Self::DEFAULT_MAX_EXTRAPOLATION,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub const DEFAULT_MAX_EXTRAPOLATION: f32 = 0.1;
+
+    /// The extrapolation time of frame data rendered at `time`, which was
+    /// last new at `fresh_time`.
+    pub fn extrapolation(&self, fresh_time: f32, time: f32) -> f32 {
+        if !self.extrapolate {
+            return 0.0;
+        }
+        (time - fresh_time).clamp(0.0, self.max_extrapolation)
+    }
+}
@@ -0,0 +1,331 @@
+//! Batched 2D sprites, drawn with a single instanced quad draw.
+//!
+//! The sprites of a frame are collected in a [`SpriteBatch`] by the logic
+//! thread and blitted to a partition of a [`PartitionedTriBuffer`] inside the
+//! boundary cross, like any other per-frame data. The render thread then
+//! draws the whole partition with [`draw_sprites`]: one instance per sprite,
+//! whose four corners are generated by the vertex shader from `gl_VertexID`
+//! with [`GLSL_LIB_SPRITE_CORNER`], so no vertex buffer is needed.
+//!
+//! UI primitives are drawn as sprites too, see [`ui`].
+//!
+//! The amount of sprites to draw is the length of the partition written by
+//! the last blit to the section, so it does not need to be part of the frame
+//! data.
+//!
+//! # Example
+//! ```rust,ignore
+//! layout_buffer! {
+//!     const SpriteData: 1, {
+//!         enum sprites: 50_000 => {
+//!             type Sprite;
+//!             bind 0;
+//!             shader SHADER_BINDING_SPRITES;
+//!         };
+//!     }
+//! }
+//!
+//! // UPLOAD
+//! batch.clear();
+//! for (position, frame) in players {
+//!     batch.push(Sprite::new(position, SIZE).with_uv(atlas.uv(frame)));
+//! }
+//! batch.sort_by_depth();
+//! frame_boundary.cross(|section, storage| {
+//!     batch.blit(&storage.sprites, section.as_index(), LayoutSpriteData::SPRITES);
+//! });
+//!
+//! // RENDER, with the projection in a uniform
+//! sprite_shader.bind();
+//! draw_sprites(&frame_data.sprites, section.as_index(), LayoutSpriteData::SPRITES);
+//!
+//! // VERTEX SHADER
+//! Sprite sprite = sprites[gl_InstanceID];
+//! gl_Position = projection * vec4(sprite_corner(sprite, gl_VertexID), sprite.transform.w, 1.0);
+//! v_uv = sprite_uv(sprite, gl_VertexID);
+//! v_color = sprite.color;
+//! ```
+
+use glam::{Vec2, Vec4};
+
+use crate::{
+    render::{
+        buffer::{layout::Partition, partitioned::PartitionedTriBuffer},
+        color::Color,
+    },
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+pub mod ui;
+
+macro_rules! ssbo_binding {
+    (Sprites) => {
+        24
+    };
+}
+
+pub const SHADER_BINDING_SPRITES: u32 = ssbo_binding!(Sprites);
+
+/// A textured quad in screen space.
+///
+/// Corresponds to the `Sprite` struct of [`GLSL_SSBO_SPRITES`] in a `std430`
+/// layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    /// The position of the pivot and the size of the quad, in pixels.
+    pub rect: Vec4,
+    /// The top left corner and size of the sprite in its texture, in
+    /// normalised texture coordinates.
+    pub uv: Vec4,
+    /// The linear colour the texture is multiplied by.
+    pub color: Vec4,
+    /// The rotation in radians, the pivot relative to the size of the quad,
+    /// and the depth.
+    pub transform: Vec4,
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO, Vec2::ONE)
+    }
+}
+
+impl Sprite {
+    /// A white sprite of the whole texture, with its top left corner at
+    /// `position`.
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            rect: Vec4::new(position.x, position.y, size.x, size.y),
+            uv: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            color: Vec4::ONE,
+            transform: Vec4::ZERO,
+        }
+    }
+
+    /// Draw the region of the texture at `uv`, see [`Self::uv`].
+    pub fn with_uv(mut self, uv: Vec4) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    /// Rotate the sprite clockwise on screen by `radians` around its pivot.
+    pub fn with_rotation(mut self, radians: f32) -> Self {
+        self.transform.x = radians;
+        self
+    }
+
+    /// Place and rotate the sprite around `pivot`, relative to its size, e.g.
+    /// `(0.5, 0.5)` for its centre. The top left corner by default.
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.transform.y = pivot.x;
+        self.transform.z = pivot.y;
+        self
+    }
+
+    /// Set the depth of the sprite, by which [`SpriteBatch::sort_by_depth`]
+    /// orders sprites.
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.transform.w = depth;
+        self
+    }
+
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.rect.x, self.rect.y)
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.rect.z, self.rect.w)
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.transform.w
+    }
+
+    /// The corners of the sprite, in the order of [`GLSL_LIB_SPRITE_CORNER`]:
+    /// top left, top right, bottom left and bottom right before rotation.
+    pub fn corners(&self) -> [Vec2; 4] {
+        let pivot = Vec2::new(self.transform.y, self.transform.z);
+        let rotation = Vec2::from_angle(self.transform.x);
+        std::array::from_fn(|vertex| {
+            let corner = Vec2::new((vertex & 1) as f32, (vertex >> 1) as f32);
+            self.position() + rotation.rotate((corner - pivot) * self.size())
+        })
+    }
+}
+
+crate::shader_glsl_struct! {
+    struct Sprite for Sprite {
+        rect: Vec4 => vec4;
+        uv: Vec4 => vec4;
+        color: Vec4 => vec4;
+        transform: Vec4 => vec4;
+    }
+}
+
+/// The sprites of a frame, in draw order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            sprites: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Order the sprites from the deepest to the shallowest, keeping the
+    /// order of sprites at the same depth, so that blending composes them
+    /// correctly.
+    pub fn sort_by_depth(&mut self) {
+        self.sprites.sort_by(|a, b| b.depth().total_cmp(&a.depth()));
+    }
+
+    /// Copy the sprites to the `partition` of `section` of `buffer`.
+    ///
+    /// # Returns
+    /// The amount of sprites written, clamped to the capacity of the
+    /// partition.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2), or if the layout
+    /// of `buffer` was not created from the descriptor `L`.
+    pub fn blit<L: 'static, const INDEX: usize, const PARTS: usize>(
+        &self,
+        buffer: &PartitionedTriBuffer<PARTS>,
+        section: usize,
+        partition: Partition<L, Sprite, INDEX>,
+    ) -> usize {
+        buffer.blit_part_typed(section, partition, &self.sprites, 0)
+    }
+}
+
+/// Draw the sprites last blitted to the `partition` of `section` of `buffer`
+/// with the bound shader program, as a single instanced draw of
+/// triangle-strip quads.
+///
+/// The partition is bound to [`SHADER_BINDING_SPRITES`].
+///
+/// # Returns
+/// The amount of sprites drawn.
+pub fn draw_sprites<L: 'static, const INDEX: usize, const PARTS: usize>(
+    buffer: &PartitionedTriBuffer<PARTS>,
+    section: usize,
+    _partition: Partition<L, Sprite, INDEX>,
+) -> usize {
+    let count = buffer.length(section, INDEX);
+    if count == 0 {
+        return 0;
+    }
+    buffer.bind_shader_storage_single(section, INDEX, Some(SHADER_BINDING_SPRITES));
+    unsafe {
+        janus::gl::DrawArraysInstanced(janus::gl::TRIANGLE_STRIP, 0, 4, count as i32);
+    }
+    count
+}
+
+/// Sprites SSBO interface.
+///
+/// Contains the SSBO declaration of a [`Sprite`] partition, on binding index
+/// 24. Requires the `Sprite` struct definition, see
+/// [`SpriteGlslStruct::as_definition`].
+pub const GLSL_SSBO_SPRITES: GlslStorage = crate::shader_glsl_ssbo! {
+    buf Sprites => {
+        [dyn_array Sprite: sprites]
+    }
+};
+
+/// The position of the corner `vertex` of `sprite`, i.e. `gl_VertexID` in
+/// a triangle strip of four vertices, as computed by [`Sprite::corners`].
+/// Requires the `Sprite` struct definition.
+pub const GLSL_LIB_SPRITE_CORNER: GlslLib = crate::shader_glsl_lib! {
+    vec2 sprite_corner [ sprite: Sprite, vertex: int ] => "
+        vec2 corner = vec2(float(vertex & 1), float(vertex >> 1));
+        vec2 local = (corner - sprite.transform.yz) * sprite.rect.zw;
+        float c = cos(sprite.transform.x);
+        float s = sin(sprite.transform.x);
+        return sprite.rect.xy + vec2(c * local.x - s * local.y, s * local.x + c * local.y);
+    "
+};
+
+/// The texture coordinates of the corner `vertex` of `sprite`. Requires the
+/// `Sprite` struct definition.
+pub const GLSL_LIB_SPRITE_UV: GlslLib = crate::shader_glsl_lib! {
+    vec2 sprite_uv [ sprite: Sprite, vertex: int ] => "
+        vec2 corner = vec2(float(vertex & 1), float(vertex >> 1));
+        return sprite.uv.xy + corner * sprite.uv.zw;
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprite_corners() {
+        let sprite = Sprite::new(Vec2::new(10.0, 20.0), Vec2::new(4.0, 2.0));
+        assert_eq!(
+            sprite.corners(),
+            [
+                Vec2::new(10.0, 20.0),
+                Vec2::new(14.0, 20.0),
+                Vec2::new(10.0, 22.0),
+                Vec2::new(14.0, 22.0),
+            ]
+        );
+
+        let rotated = sprite
+            .with_pivot(Vec2::splat(0.5))
+            .with_rotation(std::f32::consts::FRAC_PI_2);
+        // a quarter turn around the centre swaps the extents
+        let [top_left, .., bottom_right] = rotated.corners();
+        assert!((top_left - Vec2::new(11.0, 18.0)).abs().max_element() < 1e-5);
+        assert!((bottom_right - Vec2::new(9.0, 22.0)).abs().max_element() < 1e-5);
+
+        let mut batch = SpriteBatch::new();
+        batch.push(sprite.with_depth(1.0));
+        batch.push(rotated.with_depth(3.0));
+        batch.push(sprite.with_color(Color::BLACK).with_depth(1.0));
+        batch.sort_by_depth();
+        assert_eq!(
+            batch
+                .sprites()
+                .iter()
+                .map(|s| s.depth())
+                .collect::<Vec<_>>(),
+            [3.0, 1.0, 1.0]
+        );
+        assert_eq!(batch.sprites()[2].color, Vec4::new(0.0, 0.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,283 @@
+//! UI primitives drawn as sprites: rectangles, outlines, lines, nine-slice
+//! panels and rounded rectangles.
+//!
+//! A [`UiPainter`] appends the sprites of each primitive to a
+//! [`SpriteBatch`], so overlays such as the console, inspectors and HUDs are
+//! drawn by the same single instanced draw as the other sprites. All the
+//! primitives sample a single atlas texture:
+//!
+//! * solid shapes sample a region of opaque white pixels, tinted by their
+//!   colour;
+//! * [`NineSlice`] panels stretch the centre and edges of a region while
+//!   keeping its corners at their size;
+//! * rounded rectangles are nine-slice panels of a region holding a filled
+//!   circle, whose quarters make the corners.
+//!
+//! # Example
+//! ```rust,ignore
+//! let mut ui = UiPainter::new(&mut batch, atlas.white_uv())
+//!     .with_circle(atlas.circle_uv())
+//!     .with_depth(-1.0);
+//! ui.nine_slice(&panel, Vec2::new(16.0, 16.0), Vec2::new(320.0, 200.0), Color::WHITE);
+//! ui.rounded_rect(Vec2::new(24.0, 180.0), Vec2::new(96.0, 24.0), 6.0, accent);
+//! ui.line(start, end, 2.0, Color::BLACK);
+//! ```
+
+use glam::{Vec2, Vec4};
+
+use crate::render::color::Color;
+
+use super::{Sprite, SpriteBatch};
+
+/// A region of a texture split in nine cells by its margins.
+///
+/// The corners are drawn at a fixed size, the edges are stretched along
+/// their side, and the centre is stretched in both directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NineSlice {
+    /// The top left corner and size of the region, in normalised texture
+    /// coordinates.
+    pub uv: Vec4,
+    /// The left, top, right and bottom margins of the region, in normalised
+    /// texture coordinates.
+    pub uv_margins: Vec4,
+    /// The left, top, right and bottom margins drawn on screen, in pixels.
+    pub margins: Vec4,
+}
+
+impl NineSlice {
+    /// The nine-slice of the region at `origin` of `size` pixels in an atlas
+    /// of `atlas_size` pixels, with the left, top, right and bottom
+    /// `margins` in pixels, drawn at their size.
+    pub fn from_pixels(origin: Vec2, size: Vec2, margins: Vec4, atlas_size: Vec2) -> Self {
+        let scale = Vec4::new(atlas_size.x, atlas_size.y, atlas_size.x, atlas_size.y);
+        Self {
+            uv: Vec4::new(origin.x, origin.y, size.x, size.y) / scale,
+            uv_margins: margins / scale,
+            margins,
+        }
+    }
+
+    /// Draw the margins at `margins` pixels, e.g. to scale the panel with the
+    /// content scale of the display.
+    pub fn with_margins(mut self, margins: Vec4) -> Self {
+        self.margins = margins;
+        self
+    }
+
+    /// Append the sprites of the panel at `position` of `size` pixels to
+    /// `batch`.
+    ///
+    /// The margins are shrunk proportionally when the panel is smaller than
+    /// their sum, and empty cells are skipped.
+    ///
+    /// # Returns
+    /// The amount of sprites pushed, at most 9.
+    pub fn push(
+        &self,
+        batch: &mut SpriteBatch,
+        position: Vec2,
+        size: Vec2,
+        color: Color,
+        depth: f32,
+    ) -> usize {
+        let fit = |near: f32, far: f32, len: f32| {
+            let sum = near + far;
+            if sum > len && sum > 0.0 {
+                (near * len / sum, far * len / sum)
+            } else {
+                (near, far)
+            }
+        };
+        let (left, right) = fit(self.margins.x, self.margins.z, size.x);
+        let (top, bottom) = fit(self.margins.y, self.margins.w, size.y);
+
+        let xs = [0.0, left, size.x - right, size.x];
+        let ys = [0.0, top, size.y - bottom, size.y];
+        let (u, v, uw, vh) = self.uv.into();
+        let m = self.uv_margins;
+        let us = [u, u + m.x, u + uw - m.z, u + uw];
+        let vs = [v, v + m.y, v + vh - m.w, v + vh];
+
+        let mut pushed = 0;
+        for row in 0..3 {
+            for col in 0..3 {
+                let cell = Vec2::new(xs[col + 1] - xs[col], ys[row + 1] - ys[row]);
+                if cell.x <= 0.0 || cell.y <= 0.0 {
+                    continue;
+                }
+                let uv = Vec4::new(
+                    us[col],
+                    vs[row],
+                    us[col + 1] - us[col],
+                    vs[row + 1] - vs[row],
+                );
+                batch.push(
+                    Sprite::new(position + Vec2::new(xs[col], ys[row]), cell)
+                        .with_uv(uv)
+                        .with_color(color)
+                        .with_depth(depth),
+                );
+                pushed += 1;
+            }
+        }
+        pushed
+    }
+}
+
+/// Appends UI primitives to a [`SpriteBatch`].
+#[derive(Debug)]
+pub struct UiPainter<'a> {
+    batch: &'a mut SpriteBatch,
+    white: Vec4,
+    circle: Option<Vec4>,
+    depth: f32,
+}
+
+impl<'a> UiPainter<'a> {
+    /// A painter of solid shapes sampling the region of white pixels at
+    /// `white`, in normalised texture coordinates.
+    pub fn new(batch: &'a mut SpriteBatch, white: Vec4) -> Self {
+        Self {
+            batch,
+            white,
+            circle: None,
+            depth: 0.0,
+        }
+    }
+
+    /// Draw rounded corners from the filled circle inscribed in the region
+    /// at `circle`, in normalised texture coordinates.
+    ///
+    /// Without it, rounded rectangles are drawn with square corners.
+    pub fn with_circle(mut self, circle: Vec4) -> Self {
+        self.circle = Some(circle);
+        self
+    }
+
+    /// Draw the next primitives at `depth`, see [`Sprite::with_depth`].
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth;
+    }
+
+    /// A solid rectangle at `position` of `size` pixels.
+    pub fn rect(&mut self, position: Vec2, size: Vec2, color: Color) {
+        self.batch.push(self.solid(position, size, color));
+    }
+
+    /// The outline of a rectangle at `position` of `size` pixels, drawn
+    /// inside it with `thickness` pixels.
+    pub fn rect_outline(&mut self, position: Vec2, size: Vec2, thickness: f32, color: Color) {
+        let t = thickness.min(size.x * 0.5).min(size.y * 0.5);
+        let side = Vec2::new(t, size.y - 2.0 * t);
+        self.rect(position, Vec2::new(size.x, t), color);
+        self.rect(
+            position + Vec2::new(0.0, size.y - t),
+            Vec2::new(size.x, t),
+            color,
+        );
+        self.rect(position + Vec2::new(0.0, t), side, color);
+        self.rect(position + Vec2::new(size.x - t, t), side, color);
+    }
+
+    /// A line from `start` to `end` of `thickness` pixels, centred on the
+    /// segment.
+    pub fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        let delta = end - start;
+        let length = delta.length();
+        if length == 0.0 {
+            return;
+        }
+        self.batch.push(
+            self.solid(start, Vec2::new(length, thickness), color)
+                .with_pivot(Vec2::new(0.0, 0.5))
+                .with_rotation(delta.to_angle()),
+        );
+    }
+
+    /// A panel at `position` of `size` pixels, see [`NineSlice`].
+    pub fn nine_slice(&mut self, slice: &NineSlice, position: Vec2, size: Vec2, color: Color) {
+        slice.push(self.batch, position, size, color, self.depth);
+    }
+
+    /// A solid rectangle at `position` of `size` pixels, whose corners are
+    /// rounded with `radius` pixels.
+    ///
+    /// The radius is clamped to half the smallest side of the rectangle.
+    pub fn rounded_rect(&mut self, position: Vec2, size: Vec2, radius: f32, color: Color) {
+        let Some(circle) = self.circle else {
+            self.rect(position, size, color);
+            return;
+        };
+        let half = Vec4::new(circle.z, circle.w, circle.z, circle.w) * 0.5;
+        let slice = NineSlice {
+            uv: circle,
+            uv_margins: half,
+            margins: Vec4::splat(radius.min(size.min_element() * 0.5)),
+        };
+        slice.push(self.batch, position, size, color, self.depth);
+    }
+
+    fn solid(&self, position: Vec2, size: Vec2, color: Color) -> Sprite {
+        Sprite::new(position, size)
+            .with_uv(self.white)
+            .with_color(color)
+            .with_depth(self.depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nine_slice_cells() {
+        let slice = NineSlice::from_pixels(
+            Vec2::new(32.0, 0.0),
+            Vec2::splat(32.0),
+            Vec4::splat(8.0),
+            Vec2::splat(64.0),
+        );
+        assert_eq!(slice.uv, Vec4::new(0.5, 0.0, 0.5, 0.5));
+        assert_eq!(slice.uv_margins, Vec4::splat(0.125));
+
+        let mut batch = SpriteBatch::new();
+        let pushed = slice.push(
+            &mut batch,
+            Vec2::ZERO,
+            Vec2::new(100.0, 40.0),
+            Color::WHITE,
+            0.0,
+        );
+        assert_eq!(pushed, 9);
+        let centre = batch.sprites()[4];
+        assert_eq!(centre.rect, Vec4::new(8.0, 8.0, 84.0, 24.0));
+        assert_eq!(centre.uv, Vec4::new(0.625, 0.125, 0.25, 0.25));
+        let corner = batch.sprites()[8];
+        assert_eq!(corner.rect, Vec4::new(92.0, 32.0, 8.0, 8.0));
+
+        // the margins fill a panel smaller than their sum, without a centre
+        batch.clear();
+        assert_eq!(
+            slice.push(&mut batch, Vec2::ZERO, Vec2::splat(10.0), Color::WHITE, 0.0),
+            4
+        );
+
+        let mut ui = UiPainter::new(&mut batch, Vec4::new(0.0, 0.0, 0.01, 0.01))
+            .with_circle(Vec4::new(0.0, 0.5, 0.5, 0.5));
+        ui.line(Vec2::ZERO, Vec2::new(0.0, 10.0), 2.0, Color::BLACK);
+        ui.rounded_rect(Vec2::ZERO, Vec2::new(40.0, 8.0), 6.0, Color::WHITE);
+        let line = batch.sprites()[4];
+        let [start, .., end] = line.corners();
+        assert!((start - Vec2::new(1.0, 0.0)).abs().max_element() < 1e-5);
+        assert!((end - Vec2::new(-1.0, 10.0)).abs().max_element() < 1e-5);
+        // the radius is clamped to half the height
+        assert_eq!(batch.sprites()[5].rect, Vec4::new(0.0, 0.0, 4.0, 4.0));
+        assert_eq!(batch.len(), 4 + 1 + 6);
+    }
+}
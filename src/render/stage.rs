@@ -0,0 +1,327 @@
+//! The stages of a rendered frame.
+//!
+//! Every frame, the [`Renderer`](super::Renderer) runs the following stages in
+//! order:
+//! 1. [`RenderStage::pre_frame`]
+//! 2. [`RenderStage::bind_globals`]
+//! 3. [`RenderStage::scene`], with the current frame data
+//! 4. the user [`CrossHooks`], with the current frame data
+//! 5. [`RenderStage::post`], with the current frame data
+//! 6. [`RenderStage::present`]
+//!
+//! The scene stage, hooks and post stage run while the renderer crosses the boundary, so
+//! the frame data is synchronised and the fence of its section is placed after
+//! all of them have been submitted. The renderer handles the viewport and the
+//! boundary itself: stages only issue their own GL commands. Stages may
+//! release the partitions of a synchronisation lane early with
+//! [`StageContext::lanes`], see [`LaneFences`].
+//!
+//! All stages have a default implementation, which matches the behaviour of
+//! the [`DefaultStages`]. Applications can override a single stage, e.g. to
+//! inject a custom pass, and keep the others.
+//!
+//! # Example
+//! ```rust,ignore
+//! #[derive(Default)]
+//! struct WithOutline;
+//!
+//! impl<D, T: RenderHandler<D>> RenderStage<D, T> for WithOutline {
+//!     fn post(&mut self, ctx: &mut StageContext<T>, frame_data: &D, section: StorageSection) {
+//!         draw_outlines(ctx.globals.globals(), section);
+//!     }
+//! }
+//!
+//! type AppRenderer = Renderer<FrameData, AppRender, WithOutline>;
+//! ```
+
+use janus::{
+    context::DeltaTime,
+    sync::{Mirror, TriCell},
+};
+
+use crate::{
+    RenderHandler,
+    render::{
+        ScreenSpace, barrier::Barriers, buffer::StorageSection, frame::FrameGlobalsBuffer,
+        pool::MeshPools, sync::LaneFences,
+    },
+    state::{camera::ViewPoint, cross::SectionAge},
+};
+
+/// The renderer state available to the [`RenderStage`]s of a frame.
+pub struct StageContext<'r, T> {
+    pub handler: &'r mut T,
+
+    pub screen_space: &'r mut Mirror<ScreenSpace>,
+    pub viewpoint: &'r TriCell<ViewPoint>,
+
+    /// The mesh buffers, see [`MeshPools`].
+    pub mesh_pools: &'r MeshPools,
+
+    pub globals: &'r mut FrameGlobalsBuffer,
+    /// Lane fences of the frame, see [`LaneFences`].
+    pub lanes: &'r LaneFences,
+    /// The memory barriers between the passes of the frame, see
+    /// [`Barriers`].
+    pub barriers: &'r mut Barriers,
+
+    /// The delta time of the frame.
+    pub delta: DeltaTime,
+    /// The age of the frame data.
+    ///
+    /// Only valid from the [`RenderStage::scene`] stage onwards, as the frame
+    /// data is crossed after [`RenderStage::bind_globals`]. Holds the age of
+    /// the previous frame in the earlier stages.
+    pub age: SectionAge,
+}
+
+/// A stage of a rendered frame, in order of execution, e.g. to
+/// [check the GL state](super::gl_state) it leaves behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PreFrame,
+    BindGlobals,
+    Scene,
+    /// The [`CrossHooks`], run between the scene and post stages.
+    Hooks,
+    Post,
+    Present,
+}
+
+impl Stage {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::PreFrame => "pre-frame",
+            Self::BindGlobals => "bind globals",
+            Self::Scene => "scene",
+            Self::Hooks => "hooks",
+            Self::Post => "post",
+            Self::Present => "present",
+        }
+    }
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The overridable stages of a rendered frame.
+///
+/// See the [module documentation](self) for the order of execution.
+pub trait RenderStage<D: Sized, T: RenderHandler<D>> {
+    /// Calls [`RenderHandler::pre_frame`] by default.
+    fn pre_frame(&mut self, ctx: &mut StageContext<T>) {
+        ctx.handler
+            .pre_frame(ctx.screen_space, ctx.viewpoint, ctx.delta);
+    }
+
+    /// Updates and binds the [`FrameGlobals`](super::frame::FrameGlobals) and
+    /// binds the mesh buffer of the default pool by default.
+    fn bind_globals(&mut self, ctx: &mut StageContext<T>) {
+        ctx.globals.update(ctx.screen_space.resolution());
+        ctx.mesh_pools.default_pool().bind();
+    }
+
+    /// Calls [`RenderHandler::render_frame`] by default.
+    fn scene(&mut self, ctx: &mut StageContext<T>, frame_data: &D, section: StorageSection) {
+        ctx.handler.render_frame(frame_data, section);
+    }
+
+    /// Does nothing by default.
+    #[allow(unused_variables)]
+    fn post(&mut self, ctx: &mut StageContext<T>, frame_data: &D, section: StorageSection) {}
+
+    /// Drains and logs the GL errors of the frame in debug builds by default,
+    /// unless they are reported as they are raised, see
+    /// [`debug_output`](super::debug_output).
+    #[allow(unused_variables)]
+    fn present(&mut self, ctx: &mut StageContext<T>) {
+        #[cfg(debug_assertions)]
+        super::debug_output::drain_errors();
+    }
+}
+
+/// The default stages of the [`Renderer`](super::Renderer).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultStages;
+
+impl<D: Sized, T: RenderHandler<D>> RenderStage<D, T> for DefaultStages {}
+
+/// Identifier of a hook registered in [`CrossHooks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CrossHookId(u32);
+
+type CrossHookFn<D> = Box<dyn FnMut(StorageSection, &D) + Send>;
+type ExclusiveHookFn<D> = Box<dyn FnMut(&mut D) + Send>;
+
+/// User callbacks run by the [`Renderer`](super::Renderer) inside the
+/// consumer boundary cross, between the [`RenderStage::scene`] and
+/// [`RenderStage::post`] stages.
+///
+/// Hooks receive the section and frame data of the current frame, so
+/// extensions (e.g. particles or UI) can dispatch their own draws against the
+/// correct section: their commands are covered by the fence placed at the end
+/// of the cross.
+///
+/// Exclusive hooks receive the frame data mutably instead, before the cross,
+/// while the producer is not crossing, see
+/// [`Cross::try_exclusive`](crate::state::cross::Cross::try_exclusive). They
+/// are skipped on the frames the producer is crossing.
+///
+/// Hooks run in the order they were registered.
+///
+/// # Example
+/// ```rust,ignore
+/// renderer.cross_hooks_mut().add(move |section, frame_data: &FrameData| {
+///     frame_data.particles.bind_shader_storage(section.as_index(), 6, 0);
+///     particles.draw();
+/// });
+///
+/// // grow the instances with the blits exceeding them
+/// renderer.cross_hooks_mut().add_exclusive(|frame_data: &mut FrameData| {
+///     frame_data.instances.grow_to_fit();
+/// });
+/// ```
+pub struct CrossHooks<D> {
+    hooks: Vec<(CrossHookId, CrossHookFn<D>)>,
+    exclusive: Vec<(CrossHookId, ExclusiveHookFn<D>)>,
+    next_id: u32,
+}
+
+impl<D> Default for CrossHooks<D> {
+    fn default() -> Self {
+        Self {
+            hooks: Vec::new(),
+            exclusive: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<D> std::fmt::Debug for CrossHooks<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CrossHooks")
+            .field("len", &self.hooks.len())
+            .field("exclusive", &self.exclusive.len())
+            .finish()
+    }
+}
+
+impl<D> CrossHooks<D> {
+    /// Register a `hook`.
+    ///
+    /// # Returns
+    /// The identifier to [`remove`](Self::remove) the hook with.
+    pub fn add<F>(&mut self, hook: F) -> CrossHookId
+    where
+        F: FnMut(StorageSection, &D) + Send + 'static,
+    {
+        let id = self.next_id();
+        self.hooks.push((id, Box::new(hook)));
+        id
+    }
+
+    /// Register a `hook` run with exclusive access to the frame data, before
+    /// the cross.
+    ///
+    /// # Returns
+    /// The identifier to [`remove`](Self::remove) the hook with.
+    pub fn add_exclusive<F>(&mut self, hook: F) -> CrossHookId
+    where
+        F: FnMut(&mut D) + Send + 'static,
+    {
+        let id = self.next_id();
+        self.exclusive.push((id, Box::new(hook)));
+        id
+    }
+
+    fn next_id(&mut self) -> CrossHookId {
+        let id = CrossHookId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Unregister the hook identified by `id`.
+    ///
+    /// # Returns
+    /// Whether the hook was registered.
+    pub fn remove(&mut self, id: CrossHookId) -> bool {
+        let len = self.len();
+        self.hooks.retain(|(hook_id, _)| *hook_id != id);
+        self.exclusive.retain(|(hook_id, _)| *hook_id != id);
+        self.len() != len
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len() + self.exclusive.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether any exclusive hook is registered.
+    pub fn has_exclusive(&self) -> bool {
+        !self.exclusive.is_empty()
+    }
+
+    /// Run all exclusive hooks with the given `frame_data`.
+    pub fn run_exclusive(&mut self, frame_data: &mut D) {
+        for (_, hook) in &mut self.exclusive {
+            hook(frame_data);
+        }
+    }
+
+    /// Run all hooks with the given `section` and `frame_data`.
+    pub fn run(&mut self, section: StorageSection, frame_data: &D) {
+        for (_, hook) in &mut self.hooks {
+            hook(section, frame_data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    #[test]
+    fn cross_hooks_in_order() {
+        let mut hooks = CrossHooks::<Vec<usize>>::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let first = {
+            let calls = calls.clone();
+            hooks.add(move |_, data: &Vec<usize>| {
+                assert_eq!(calls.fetch_add(data[0], Ordering::Relaxed), 0);
+            })
+        };
+        {
+            let calls = calls.clone();
+            hooks.add(move |section, data: &Vec<usize>| {
+                assert_eq!(section, StorageSection::Spare);
+                calls.fetch_add(data[1], Ordering::Relaxed);
+            });
+        }
+
+        hooks.run(StorageSection::Spare, &vec![1, 10]);
+        assert_eq!(calls.load(Ordering::Relaxed), 11);
+
+        assert!(hooks.remove(first));
+        assert!(!hooks.remove(first));
+        assert_eq!(hooks.len(), 1);
+
+        let grow = hooks.add_exclusive(|data: &mut Vec<usize>| data.push(100));
+        let mut data = vec![1, 10];
+        hooks.run_exclusive(&mut data);
+        assert_eq!(data, [1, 10, 100]);
+        assert!(hooks.remove(grow));
+        assert!(!hooks.has_exclusive());
+    }
+}
@@ -1,23 +1,118 @@
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU8, Ordering},
+};
 
 use janus::gl::types::__GLsync;
 
 use crate::render::buffer::StorageSection;
 
+/// The maximum amount of synchronisation lanes.
+///
+/// See [`LaneFences`].
+pub const MAX_SYNC_LANES: usize = 4;
+
+const SECTION_BITS: [u8; 3] = [
+    StorageSection::Front as u8,
+    StorageSection::Back as u8,
+    StorageSection::Spare as u8,
+];
+
+/// The fence state of a lane in a section.
+#[derive(Clone, Copy, Debug, Default)]
+enum LaneFence {
+    /// The lane was not fenced explicitly, and follows the section fence.
+    #[default]
+    Section,
+    Pending(*const __GLsync),
+    Signaled,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct SyncBarrier {
     fences: [Option<*const __GLsync>; 3],
+    lanes: [[LaneFence; 3]; MAX_SYNC_LANES],
 }
 
 #[derive(Default, Debug)]
 pub struct SyncState {
     locks: AtomicU8,
+    lane_locks: [AtomicU8; MAX_SYNC_LANES],
+}
+
+/// Fences placed by the consumer for individual synchronisation lanes.
+///
+/// By default, a section is locked from the end of the consumer cross until
+/// the GPU has finished executing all commands issued during it, so the
+/// producer waits for the slowest reader of the section before writing any of
+/// its partitions.
+///
+/// Lanes split a section into groups of partitions which are synchronised
+/// independently, with the lane assignment being a convention between the
+/// producer and the consumer. When a lane is fenced in the middle of the
+/// consumer cross, e.g. right after the draws reading the scene transforms,
+/// the producer can write the partitions of that lane as soon as those draws
+/// complete, even if later passes still read other partitions of the section.
+///
+/// Lanes that are not fenced during a cross follow the section fence.
+///
+/// See [`Cross::cross_lanes`](crate::state::cross::Cross::cross_lanes).
+#[derive(Default, Debug)]
+pub struct LaneFences {
+    fences: [Cell<Option<*const __GLsync>>; MAX_SYNC_LANES],
+}
+
+impl LaneFences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fence `lane` after all GL commands issued so far.
+    ///
+    /// Fencing the same lane again replaces the previous fence.
+    ///
+    /// # Panics
+    /// If `lane` is not lower than [`MAX_SYNC_LANES`].
+    pub fn fence(&self, lane: usize) {
+        assert!(
+            lane < MAX_SYNC_LANES,
+            "lane {lane} exceeds the maximum of {MAX_SYNC_LANES} lanes"
+        );
+        let fence = unsafe { janus::gl::FenceSync(janus::gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        if let Some(previous) = self.fences[lane].replace(Some(fence)) {
+            unsafe {
+                janus::gl::DeleteSync(previous);
+            }
+        }
+    }
+
+    fn take(&self, lane: usize) -> Option<*const __GLsync> {
+        self.fences[lane].take()
+    }
+}
+
+impl Drop for LaneFences {
+    fn drop(&mut self) {
+        for lane in 0..MAX_SYNC_LANES {
+            if let Some(fence) = self.take(lane) {
+                unsafe {
+                    janus::gl::DeleteSync(fence);
+                }
+            }
+        }
+    }
+}
+
+fn is_signaled(fence: *const __GLsync) -> bool {
+    let fence_query = unsafe { janus::gl::ClientWaitSync(fence, 0, 1) };
+    fence_query == janus::gl::CONDITION_SATISFIED || fence_query == janus::gl::ALREADY_SIGNALED
 }
 
 impl SyncBarrier {
     pub fn new() -> Self {
         Self {
             fences: [Option::None; 3],
+            lanes: Default::default(),
         }
     }
 
@@ -25,37 +120,75 @@ impl SyncBarrier {
         let mut bits = 0u8;
         for i in 0..3 {
             if let Some(fence) = self.fences[i].take() {
-                let fence_query = unsafe { janus::gl::ClientWaitSync(fence, 0, 1) };
-                if fence_query == janus::gl::CONDITION_SATISFIED
-                    || fence_query == janus::gl::ALREADY_SIGNALED
-                {
+                if is_signaled(fence) {
                     unsafe {
                         janus::gl::DeleteSync(fence);
                     }
                 } else {
-                    match i {
-                        0 => bits |= StorageSection::Front as u8,
-                        1 => bits |= StorageSection::Back as u8,
-                        2 => bits |= StorageSection::Spare as u8,
-                        _ => unreachable!(),
-                    }
+                    bits |= SECTION_BITS[i];
                     self.fences[i] = Some(fence);
                 }
             }
         }
         to.set(bits);
+
+        for lane in 0..MAX_SYNC_LANES {
+            let mut lane_bits = 0u8;
+            for i in 0..3 {
+                let state = &mut self.lanes[lane][i];
+                match *state {
+                    LaneFence::Section => lane_bits |= bits & SECTION_BITS[i],
+                    LaneFence::Pending(fence) => {
+                        if is_signaled(fence) {
+                            unsafe {
+                                janus::gl::DeleteSync(fence);
+                            }
+                            *state = LaneFence::Signaled;
+                        } else {
+                            lane_bits |= SECTION_BITS[i];
+                        }
+                    }
+                    LaneFence::Signaled => {}
+                }
+            }
+            to.set_lane(lane, lane_bits);
+        }
     }
 
     pub fn set(&mut self, index: usize, fence: *const __GLsync) {
         self.fences[index] = Some(fence);
     }
+
+    /// Move the fences placed in `lanes` to the section at `index`.
+    ///
+    /// Lanes without a fence follow the section fence.
+    pub fn set_lanes(&mut self, index: usize, lanes: &LaneFences) {
+        for lane in 0..MAX_SYNC_LANES {
+            let state = match lanes.take(lane) {
+                Some(fence) => LaneFence::Pending(fence),
+                None => LaneFence::Section,
+            };
+            if let LaneFence::Pending(previous) =
+                std::mem::replace(&mut self.lanes[lane][index], state)
+            {
+                unsafe {
+                    janus::gl::DeleteSync(previous);
+                }
+            }
+        }
+    }
 }
 
 impl Drop for SyncBarrier {
     fn drop(&mut self) {
+        let lane_fences = self.lanes.iter().flatten().filter_map(|state| match state {
+            LaneFence::Pending(fence) => Some(*fence),
+            _ => None,
+        });
         self.fences
             .into_iter()
-            .filter_map(|maybe_fence| maybe_fence)
+            .flatten()
+            .chain(lane_fences)
             .for_each(|fence| unsafe {
                 janus::gl::DeleteSync(fence);
             });
@@ -64,9 +197,7 @@ impl Drop for SyncBarrier {
 
 impl SyncState {
     pub fn new() -> Self {
-        Self {
-            locks: AtomicU8::new(0),
-        }
+        Self::default()
     }
 
     /// Performs an `OR` operation on the internal lock bit.
@@ -95,8 +226,34 @@ impl SyncState {
         self.locks.store(bits, Ordering::Release);
     }
 
+    fn set_lane(&self, lane: usize, bits: u8) {
+        self.lane_locks[lane].store(bits, Ordering::Release);
+    }
+
+    /// The bits of the locked sections.
+    pub fn lock_mask(&self) -> u8 {
+        self.locks.load(Ordering::Acquire)
+    }
+
+    /// The bits of the sections where the partitions of `lane` are locked.
+    ///
+    /// # Panics
+    /// If `lane` is not lower than [`MAX_SYNC_LANES`].
+    pub fn lane_lock_mask(&self, lane: usize) -> u8 {
+        self.lane_locks[lane].load(Ordering::Acquire)
+    }
+
     pub fn has_lock(&self, section: StorageSection) -> bool {
         let bit = section as u8;
         self.locks.load(Ordering::Acquire) & bit == bit
     }
+
+    /// Whether the partitions of `lane` are locked in `section`.
+    ///
+    /// # Panics
+    /// If `lane` is not lower than [`MAX_SYNC_LANES`].
+    pub fn has_lane_lock(&self, lane: usize, section: StorageSection) -> bool {
+        let bit = section as u8;
+        self.lane_locks[lane].load(Ordering::Acquire) & bit == bit
+    }
 }
@@ -0,0 +1,317 @@
+//! Text shaping and glyph layout for debug and UI text.
+//!
+//! Text is turned into glyphs by a [`Shaper`] before being laid out into
+//! [`GlyphQuad`]s. Every glyph keeps the byte offset of the UTF-8 cluster it
+//! was shaped from, so that cursors, selections and hit tests work on the
+//! source string whatever the amount of glyphs per character, see
+//! [`ShapedText`].
+//!
+//! The default shaper is the [`BitmapFont`] itself: one glyph per character
+//! from a grid atlas, with combining marks and joiners folded into the
+//! cluster of their base character. Characters missing from the atlas are
+//! drawn with its fallback glyph, and further scripts are covered by adding
+//! their ranges to the atlas with [`BitmapFont::with_range`].
+//!
+//! Scripts which need contextual shaping (e.g. Arabic or Devanagari) require
+//! the `shaping` feature, which provides a [`Shaper`] backed by `rustybuzz`
+//! in [`shaping`].
+//!
+//! # Example
+//! ```rust,ignore
+//! let font = BitmapFont::ascii(16, 8, 16).with_range('А', 'я', 96);
+//! let text = ShapedText::shape(&font, "Привет, world", 16.0);
+//! font.quads(&text, Vec2::new(8.0, 8.0), 16.0, &mut quads);
+//!
+//! // the cursor after the cluster under the mouse
+//! let cursor = text.byte_at(mouse.x - 8.0);
+//! ```
+
+use glam::{Vec2, Vec4};
+
+#[cfg(feature = "shaping")]
+pub mod shaping;
+
+/// The index of a glyph in the atlas of a font.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GlyphId(pub u32);
+
+/// A glyph positioned along a line by a [`Shaper`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph: GlyphId,
+    /// The byte offset of the first character of the cluster of the glyph
+    /// in the shaped text.
+    pub cluster: u32,
+    /// The horizontal advance to the next glyph, in pixels.
+    pub advance: f32,
+    /// The offset of the glyph from the pen position, in pixels, e.g. of a
+    /// diacritic placed over its base.
+    pub offset: Vec2,
+}
+
+/// Converts text into positioned glyphs.
+pub trait Shaper {
+    /// Append the glyphs of a single line of `text` at `size` pixels to
+    /// `out`, in visual order.
+    fn shape(&self, text: &str, size: f32, out: &mut Vec<ShapedGlyph>);
+}
+
+/// Whether `c` combines with the previous character, e.g. a diacritic, a
+/// variation selector or a joiner, rather than starting a new cluster.
+pub fn is_combining(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{200C}'..='\u{200D}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FE20}'..='\u{FE2F}'
+            | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+/// A shaped line of text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShapedText {
+    glyphs: Vec<ShapedGlyph>,
+    len: u32,
+    width: f32,
+}
+
+impl ShapedText {
+    /// Shape `text` at `size` pixels with `shaper`.
+    pub fn shape(shaper: &impl Shaper, text: &str, size: f32) -> Self {
+        let mut glyphs = Vec::new();
+        shaper.shape(text, size, &mut glyphs);
+        let width = glyphs.iter().map(|g| g.advance).sum();
+        Self {
+            glyphs,
+            len: text.len() as u32,
+            width,
+        }
+    }
+
+    pub fn glyphs(&self) -> &[ShapedGlyph] {
+        &self.glyphs
+    }
+
+    /// The total advance of the line, in pixels.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// The byte range of the source text shaped into the glyph at `index`.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds.
+    pub fn cluster_range(&self, index: usize) -> std::ops::Range<usize> {
+        let start = self.glyphs[index].cluster;
+        // clusters are ordered by visual order, which is reversed in
+        // right-to-left runs
+        let end = self
+            .glyphs
+            .iter()
+            .map(|g| g.cluster)
+            .filter(|&c| c > start)
+            .min()
+            .unwrap_or(self.len);
+        start as usize..end as usize
+    }
+
+    /// The indices of the glyphs drawing any of the `bytes` of the source
+    /// text, e.g. to highlight a selection.
+    pub fn glyphs_in(&self, bytes: std::ops::Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        (0..self.glyphs.len()).filter(move |&i| {
+            let cluster = self.cluster_range(i);
+            cluster.start < bytes.end && bytes.start < cluster.end
+        })
+    }
+
+    /// The byte offset of the cluster boundary nearest to `x` pixels from
+    /// the start of the line, e.g. to place a cursor under the mouse.
+    pub fn byte_at(&self, x: f32) -> usize {
+        let mut pen = 0.0;
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            if x < pen + glyph.advance * 0.5 {
+                return self.cluster_range(i).start;
+            }
+            pen += glyph.advance;
+        }
+        self.len as usize
+    }
+}
+
+/// A quad drawing a glyph, as instance data of a text shader.
+///
+/// Corresponds to the `GlyphQuad` struct in a `std430` layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GlyphQuad {
+    /// The top left corner and size of the quad, in pixels.
+    pub rect: Vec4,
+    /// The top left corner and size of the glyph in the atlas, in normalised
+    /// texture coordinates.
+    pub uv: Vec4,
+}
+
+crate::shader_glsl_struct! {
+    struct GlyphQuad for GlyphQuad {
+        rect: Vec4 => vec4;
+        uv: Vec4 => vec4;
+    }
+}
+
+/// A monospaced font drawn from a grid atlas of equally sized cells, filled
+/// row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitmapFont {
+    columns: u32,
+    cell: (u32, u32),
+    /// The characters of the atlas: the first character, the last character
+    /// and the glyph of the first character.
+    ranges: Vec<(char, char, u32)>,
+    fallback: GlyphId,
+}
+
+impl BitmapFont {
+    /// A font of the printable ASCII characters, from `' '` to `'~'`, laid
+    /// out in an atlas of `columns` cells of `width` by `height` pixels.
+    ///
+    /// Missing characters are drawn as `'?'`.
+    ///
+    /// # Panics
+    /// If `columns` is `0`.
+    pub fn ascii(columns: u32, width: u32, height: u32) -> Self {
+        assert!(columns > 0, "a bitmap font requires at least a column");
+        Self {
+            columns,
+            cell: (width, height),
+            ranges: vec![(' ', '~', 0)],
+            fallback: GlyphId('?' as u32 - ' ' as u32),
+        }
+    }
+
+    /// Add the characters from `first` to `last` to the font, drawn by the
+    /// cells starting from `first_glyph`.
+    ///
+    /// Ranges added later take precedence.
+    pub fn with_range(mut self, first: char, last: char, first_glyph: u32) -> Self {
+        self.ranges.push((first, last, first_glyph));
+        self
+    }
+
+    /// Draw missing characters with `glyph`.
+    pub fn with_fallback(mut self, glyph: GlyphId) -> Self {
+        self.fallback = glyph;
+        self
+    }
+
+    /// The glyph of `c`, if in the atlas.
+    pub fn glyph(&self, c: char) -> Option<GlyphId> {
+        self.ranges
+            .iter()
+            .rev()
+            .find(|(first, last, _)| (*first..=*last).contains(&c))
+            .map(|(first, _, glyph)| GlyphId(glyph + (c as u32 - *first as u32)))
+    }
+
+    /// The size of a cell scaled to `size` pixels high.
+    pub fn cell_size(&self, size: f32) -> Vec2 {
+        let (w, h) = self.cell;
+        Vec2::new(w as f32 * size / h as f32, size)
+    }
+
+    /// The top left corner and size of `glyph` in an atlas of `atlas_size`
+    /// pixels, in normalised texture coordinates.
+    pub fn uv(&self, glyph: GlyphId, atlas_size: (u32, u32)) -> Vec4 {
+        let (w, h) = self.cell;
+        let (col, row) = (glyph.0 % self.columns, glyph.0 / self.columns);
+        let (aw, ah) = (atlas_size.0 as f32, atlas_size.1 as f32);
+        Vec4::new(
+            (col * w) as f32 / aw,
+            (row * h) as f32 / ah,
+            w as f32 / aw,
+            h as f32 / ah,
+        )
+    }
+
+    /// Append the quads of `text`, with its first pen position at `origin`,
+    /// to `out`, sampling an atlas of `atlas_size` pixels.
+    ///
+    /// `text` must have been shaped with this font at `size` pixels.
+    pub fn quads(
+        &self,
+        text: &ShapedText,
+        origin: Vec2,
+        size: f32,
+        atlas_size: (u32, u32),
+        out: &mut Vec<GlyphQuad>,
+    ) {
+        let cell = self.cell_size(size);
+        let mut pen = origin;
+        for glyph in text.glyphs() {
+            let corner = pen + glyph.offset;
+            out.push(GlyphQuad {
+                rect: Vec4::new(corner.x, corner.y, cell.x, cell.y),
+                uv: self.uv(glyph.glyph, atlas_size),
+            });
+            pen.x += glyph.advance;
+        }
+    }
+}
+
+impl Shaper for BitmapFont {
+    /// One glyph per cluster: combining characters are not drawn, as a
+    /// bitmap cell cannot be composed with its base.
+    fn shape(&self, text: &str, size: f32, out: &mut Vec<ShapedGlyph>) {
+        let advance = self.cell_size(size).x;
+        for (i, c) in text.char_indices() {
+            if is_combining(c) && !out.is_empty() {
+                continue;
+            }
+            out.push(ShapedGlyph {
+                glyph: self.glyph(c).unwrap_or(self.fallback),
+                cluster: i as u32,
+                advance,
+                offset: Vec2::ZERO,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_clusters() {
+        let font = BitmapFont::ascii(16, 8, 16).with_range('А', 'я', 95);
+        // a decomposed 'é', a cyrillic letter and a missing CJK character
+        let text = "e\u{301}Жx中";
+        let shaped = ShapedText::shape(&font, text, 32.0);
+        let glyphs = shaped.glyphs();
+        assert_eq!(glyphs.len(), 4);
+        assert_eq!(
+            glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(),
+            [0, 3, 5, 6]
+        );
+        assert_eq!(glyphs[1].glyph, GlyphId(95 + ('Ж' as u32 - 'А' as u32)));
+        assert_eq!(glyphs[3].glyph, font.glyph('?').unwrap());
+        assert_eq!(shaped.width(), 64.0);
+
+        assert_eq!(shaped.cluster_range(0), 0..3);
+        assert_eq!(shaped.cluster_range(3), 6..text.len());
+        assert_eq!(shaped.glyphs_in(1..4).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(shaped.byte_at(7.0), 0);
+        assert_eq!(shaped.byte_at(9.0), 3);
+        assert_eq!(shaped.byte_at(1000.0), text.len());
+
+        let mut quads = Vec::new();
+        font.quads(&shaped, Vec2::ZERO, 32.0, (128, 128), &mut quads);
+        assert_eq!(quads[2].rect, Vec4::new(32.0, 0.0, 16.0, 32.0));
+        // 'x' is the glyph 88, at column 8 of row 5
+        assert_eq!(quads[2].uv, Vec4::new(0.5, 0.625, 0.0625, 0.125));
+    }
+}
@@ -0,0 +1,68 @@
+//! Contextual text shaping with `rustybuzz`.
+//!
+//! [`FontShaper`] shapes text with the OpenType tables of a font: ligatures,
+//! contextual forms, mark positioning and right-to-left runs are resolved as
+//! by HarfBuzz. Its glyphs are the glyph IDs of the font, so the atlas it is
+//! drawn with must be rasterised from the same font, indexed by glyph ID.
+
+use glam::Vec2;
+
+use super::{GlyphId, ShapedGlyph, Shaper};
+
+/// A [`Shaper`] backed by the OpenType tables of a font.
+pub struct FontShaper {
+    face: rustybuzz::Face<'static>,
+    features: Vec<rustybuzz::Feature>,
+}
+
+impl std::fmt::Debug for FontShaper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FontShaper")
+            .field("units_per_em", &self.face.units_per_em())
+            .field("features", &self.features.len())
+            .finish()
+    }
+}
+
+impl FontShaper {
+    /// The shaper of the face at `index` of the font file `data`, e.g. read
+    /// with `include_bytes!`.
+    ///
+    /// # Returns
+    /// `None` if `data` is not a valid font.
+    pub fn new(data: &'static [u8], index: u32) -> Option<Self> {
+        Some(Self {
+            face: rustybuzz::Face::from_slice(data, index)?,
+            features: Vec::new(),
+        })
+    }
+
+    /// Apply the OpenType `features` when shaping, e.g. `"liga"` or
+    /// `"-kern"`.
+    ///
+    /// Invalid features are ignored.
+    pub fn with_features(mut self, features: &[&str]) -> Self {
+        self.features = features.iter().filter_map(|f| f.parse().ok()).collect();
+        self
+    }
+}
+
+impl Shaper for FontShaper {
+    fn shape(&self, text: &str, size: f32, out: &mut Vec<ShapedGlyph>) {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        let glyphs = rustybuzz::shape(&self.face, &self.features, buffer);
+
+        let scale = size / self.face.units_per_em() as f32;
+        let infos = glyphs.glyph_infos();
+        let positions = glyphs.glyph_positions();
+        out.extend(infos.iter().zip(positions).map(|(info, pos)| ShapedGlyph {
+            glyph: GlyphId(info.glyph_id),
+            cluster: info.cluster,
+            advance: pos.x_advance as f32 * scale,
+            // font units point up, screen pixels down
+            offset: Vec2::new(pos.x_offset as f32, -pos.y_offset as f32) * scale,
+        }));
+    }
+}
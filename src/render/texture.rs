@@ -0,0 +1,720 @@
+//! 2D textures, samplers and bindless texture handles.
+//!
+//! [`Texture2D`]s are immutable-storage textures created from raw pixels,
+//! with an optional full mipmap chain. How they are sampled is described by
+//! [`SamplerParams`], applied either to the texture itself or to a separate
+//! [`Sampler`] object shared across textures.
+//!
+//! # Bindless textures
+//! With `ARB_bindless_texture`, a texture is referenced in shaders through a
+//! 64-bit [`BindlessHandle`] instead of a texture unit. Handles can be stored
+//! per entity in SSBO data, so entities using different textures can be drawn
+//! by the same indirect draw without rebinding anything in between.
+//!
+//! The extension functions are not part of the core bindings, and must be
+//! loaded once the GL context has been created with [`bindless::load_with`]:
+//!
+//! ```rust,ignore
+//! bindless::load_with(|name| window.get_proc_address(name));
+//!
+//! let texture = Texture2D::from_pixels(64, 64, TextureFormat::Srgba8, &pixels, true)?;
+//! if let Some(handle) = texture.bindless_handle(None) {
+//!     handle.make_resident();
+//!     materials.push(handle);
+//! }
+//! ```
+//!
+//! Shaders reading handles must enable the extension, see
+//! [`bindless::GLSL_EXT_BINDLESS`].
+
+use crate::render::{GlPropertyEnum, NotSend};
+
+pub mod residency;
+
+/// `GL_TEXTURE_MAX_ANISOTROPY`, core since OpenGL 4.6.
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+
+/// The internal format of a texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgba8,
+    /// 8-bit RGBA with sRGB-encoded color channels, for color textures
+    /// authored in sRGB.
+    Srgba8,
+    R16F,
+    Rg16F,
+    Rgba16F,
+    R32F,
+    Rgba32F,
+}
+
+impl TextureFormat {
+    /// The size of one pixel of the format, in bytes.
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::R8 => 1,
+            Self::Rg8 | Self::R16F => 2,
+            Self::Rgba8 | Self::Srgba8 | Self::Rg16F | Self::R32F => 4,
+            Self::Rgba16F => 8,
+            Self::Rgba32F => 16,
+        }
+    }
+
+    /// The pixel format and type of the client data uploaded to a texture of
+    /// this format.
+    pub const fn pixel_format(self) -> (u32, u32) {
+        use janus::gl;
+        match self {
+            Self::R8 => (gl::RED, gl::UNSIGNED_BYTE),
+            Self::Rg8 => (gl::RG, gl::UNSIGNED_BYTE),
+            Self::Rgba8 | Self::Srgba8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            Self::R16F => (gl::RED, gl::HALF_FLOAT),
+            Self::Rg16F => (gl::RG, gl::HALF_FLOAT),
+            Self::Rgba16F => (gl::RGBA, gl::HALF_FLOAT),
+            Self::R32F => (gl::RED, gl::FLOAT),
+            Self::Rgba32F => (gl::RGBA, gl::FLOAT),
+        }
+    }
+}
+
+impl GlPropertyEnum for TextureFormat {
+    fn as_gl_enum(&self) -> u32 {
+        use janus::gl;
+        match self {
+            Self::R8 => gl::R8,
+            Self::Rg8 => gl::RG8,
+            Self::Rgba8 => gl::RGBA8,
+            Self::Srgba8 => gl::SRGB8_ALPHA8,
+            Self::R16F => gl::R16F,
+            Self::Rg16F => gl::RG16F,
+            Self::Rgba16F => gl::RGBA16F,
+            Self::R32F => gl::R32F,
+            Self::Rgba32F => gl::RGBA32F,
+        }
+    }
+}
+
+/// The filter used when a texture is magnified.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MagFilter {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+impl GlPropertyEnum for MagFilter {
+    fn as_gl_enum(&self) -> u32 {
+        match self {
+            Self::Nearest => janus::gl::NEAREST,
+            Self::Linear => janus::gl::LINEAR,
+        }
+    }
+}
+
+/// The filter used when a texture is minified, within and across mip levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    /// Trilinear filtering.
+    #[default]
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    /// Whether the filter samples mip levels.
+    pub const fn uses_mipmaps(self) -> bool {
+        !matches!(self, Self::Nearest | Self::Linear)
+    }
+}
+
+impl GlPropertyEnum for MinFilter {
+    fn as_gl_enum(&self) -> u32 {
+        use janus::gl;
+        match self {
+            Self::Nearest => gl::NEAREST,
+            Self::Linear => gl::LINEAR,
+            Self::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            Self::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            Self::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            Self::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+/// How texture coordinates outside of `[0, 1]` are resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Coordinates outside of the texture sample the border color, see
+    /// [`SamplerParams::border_color`].
+    ClampToBorder,
+}
+
+impl GlPropertyEnum for Wrap {
+    fn as_gl_enum(&self) -> u32 {
+        use janus::gl;
+        match self {
+            Self::Repeat => gl::REPEAT,
+            Self::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Self::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Self::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+/// The parameters describing how a texture is sampled.
+///
+/// # Example
+/// ```rust,ignore
+/// let pixel_art = SamplerParams::new()
+///     .filter(MinFilter::Nearest, MagFilter::Nearest)
+///     .wrap(Wrap::ClampToEdge);
+///
+/// texture.set_sampler(&pixel_art);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerParams {
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    /// The maximum anisotropy, `1.0` disables anisotropic filtering.
+    pub anisotropy: f32,
+    pub border_color: [f32; 4],
+}
+
+impl Default for SamplerParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerParams {
+    /// Trilinear filtering, repeating in both directions.
+    pub const fn new() -> Self {
+        Self {
+            min_filter: MinFilter::LinearMipmapLinear,
+            mag_filter: MagFilter::Linear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            anisotropy: 1.0,
+            border_color: [0.0; 4],
+        }
+    }
+
+    pub const fn filter(mut self, min: MinFilter, mag: MagFilter) -> Self {
+        self.min_filter = min;
+        self.mag_filter = mag;
+        self
+    }
+
+    /// Set the wrap mode of both directions.
+    pub const fn wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self
+    }
+
+    pub const fn wrap_s(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self
+    }
+
+    pub const fn wrap_t(mut self, wrap: Wrap) -> Self {
+        self.wrap_t = wrap;
+        self
+    }
+
+    /// Set the maximum anisotropy of the anisotropic filtering.
+    ///
+    /// # Panics
+    /// If `anisotropy` is lower than `1.0`.
+    pub fn anisotropy(mut self, anisotropy: f32) -> Self {
+        assert!(
+            anisotropy >= 1.0,
+            "anisotropy must be at least 1.0, got {anisotropy}"
+        );
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub const fn border_color(mut self, color: [f32; 4]) -> Self {
+        self.border_color = color;
+        self
+    }
+
+    /// Apply the parameters through `parameter_i` and `parameter_f`, which
+    /// target either a texture or a sampler object.
+    fn apply(&self, parameter_i: impl Fn(u32, i32), parameter_f: impl Fn(u32, f32)) {
+        use janus::gl;
+        parameter_i(gl::TEXTURE_MIN_FILTER, self.min_filter.as_gl_enum() as i32);
+        parameter_i(gl::TEXTURE_MAG_FILTER, self.mag_filter.as_gl_enum() as i32);
+        parameter_i(gl::TEXTURE_WRAP_S, self.wrap_s.as_gl_enum() as i32);
+        parameter_i(gl::TEXTURE_WRAP_T, self.wrap_t.as_gl_enum() as i32);
+        if self.anisotropy > 1.0 {
+            parameter_f(TEXTURE_MAX_ANISOTROPY, self.anisotropy);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextureError {
+    /// The width or height of the texture is `0`.
+    ZeroSize,
+    /// The amount of pixel bytes does not match the size and format of the
+    /// texture.
+    PixelCount { expected: usize, got: usize },
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroSize => write!(f, "textures cannot have a width or height of 0"),
+            Self::PixelCount { expected, got } => write!(
+                f,
+                "expected {expected} bytes of pixel data for the texture, got {got} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/// The amount of levels of a full mipmap chain for a texture of the given
+/// size, down to a 1x1 level.
+pub const fn mip_level_count(width: u32, height: u32) -> u32 {
+    let max = if width > height { width } else { height };
+    if max == 0 { 1 } else { max.ilog2() + 1 }
+}
+
+/// An immutable-storage 2D texture.
+#[derive(Debug)]
+pub struct Texture2D {
+    gl_obj: u32,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    levels: u32,
+
+    _marker: NotSend,
+}
+
+impl Texture2D {
+    /// Allocate an uninitialised texture with the given amount of mip
+    /// `levels`.
+    ///
+    /// # Panics
+    /// If `width`, `height` or `levels` are `0`.
+    pub fn new(width: u32, height: u32, format: TextureFormat, levels: u32) -> Self {
+        assert!(
+            width > 0 && height > 0 && levels > 0,
+            "attempted to create a texture of size {width}x{height} with {levels} levels"
+        );
+
+        let mut gl_obj = 0;
+        unsafe {
+            janus::gl::CreateTextures(janus::gl::TEXTURE_2D, 1, &mut gl_obj);
+            janus::gl::TextureStorage2D(
+                gl_obj,
+                levels as i32,
+                format.as_gl_enum(),
+                width as i32,
+                height as i32,
+            );
+        }
+
+        let texture = Self {
+            gl_obj,
+            width,
+            height,
+            format,
+            levels,
+            _marker: std::marker::PhantomData,
+        };
+        // the default minification filter samples mip levels, which leaves
+        // textures without mipmaps incomplete
+        let min_filter = if levels > 1 {
+            MinFilter::LinearMipmapLinear
+        } else {
+            MinFilter::Linear
+        };
+        texture.set_sampler(&SamplerParams::new().filter(min_filter, MagFilter::Linear));
+        texture
+    }
+
+    /// Create a texture from tightly packed rows of `pixels`, starting from
+    /// the bottom row.
+    ///
+    /// If `mipmaps` is set, a full mipmap chain is allocated and generated
+    /// from the pixels.
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        pixels: &[u8],
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        validate_pixels(width, height, format, pixels)?;
+
+        let levels = if mipmaps {
+            mip_level_count(width, height)
+        } else {
+            1
+        };
+        let texture = Self::new(width, height, format, levels);
+        texture.upload_level(0, pixels)?;
+        if mipmaps {
+            texture.generate_mipmaps();
+        }
+        Ok(texture)
+    }
+
+    /// Replace the pixels of the mip `level`.
+    ///
+    /// # Panics
+    /// If `level` is not a level of the texture.
+    pub fn upload_level(&self, level: u32, pixels: &[u8]) -> Result<(), TextureError> {
+        assert!(
+            level < self.levels,
+            "attempted to upload level {level} of a texture with {} levels",
+            self.levels
+        );
+
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        validate_pixels(width, height, self.format, pixels)?;
+
+        let (format, ty) = self.format.pixel_format();
+        unsafe {
+            // rows are tightly packed, regardless of their alignment
+            janus::gl::PixelStorei(janus::gl::UNPACK_ALIGNMENT, 1);
+            janus::gl::TextureSubImage2D(
+                self.gl_obj,
+                level as i32,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                format,
+                ty,
+                pixels.as_ptr() as *const _,
+            );
+            janus::gl::PixelStorei(janus::gl::UNPACK_ALIGNMENT, 4);
+        }
+        Ok(())
+    }
+
+    /// Generate all mip levels from the first level.
+    pub fn generate_mipmaps(&self) {
+        if self.levels > 1 {
+            unsafe {
+                janus::gl::GenerateTextureMipmap(self.gl_obj);
+            }
+        }
+    }
+
+    /// Set how the texture is sampled when no [`Sampler`] is bound to its
+    /// unit.
+    pub fn set_sampler(&self, params: &SamplerParams) {
+        let gl_obj = self.gl_obj;
+        params.apply(
+            |pname, value| unsafe { janus::gl::TextureParameteri(gl_obj, pname, value) },
+            |pname, value| unsafe { janus::gl::TextureParameterf(gl_obj, pname, value) },
+        );
+        unsafe {
+            janus::gl::TextureParameterfv(
+                gl_obj,
+                janus::gl::TEXTURE_BORDER_COLOR,
+                params.border_color.as_ptr(),
+            );
+        }
+    }
+
+    /// Bind the texture to the texture `unit`.
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            janus::gl::BindTextureUnit(unit, self.gl_obj);
+        }
+    }
+
+    /// The bindless handle of the texture, sampled with its own parameters or
+    /// those of `sampler`.
+    ///
+    /// Once a handle is created, the sampling parameters of the texture and
+    /// the sampler can no longer be changed.
+    ///
+    /// # Returns
+    /// `None` if `ARB_bindless_texture` is not available, see
+    /// [`bindless::load_with`].
+    pub fn bindless_handle(&self, sampler: Option<&Sampler>) -> Option<BindlessHandle> {
+        let functions = bindless::functions()?;
+        let handle = unsafe {
+            match sampler {
+                Some(sampler) => (functions.texture_sampler_handle)(self.gl_obj, sampler.gl_obj),
+                None => (functions.texture_handle)(self.gl_obj),
+            }
+        };
+        (handle != 0).then_some(BindlessHandle(handle))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// The amount of mip levels of the texture.
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// The OpenGL texture object.
+    pub fn gl_obj(&self) -> u32 {
+        self.gl_obj
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe {
+            janus::gl::DeleteTextures(1, &self.gl_obj);
+        }
+    }
+}
+
+fn validate_pixels(
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    pixels: &[u8],
+) -> Result<(), TextureError> {
+    if width == 0 || height == 0 {
+        return Err(TextureError::ZeroSize);
+    }
+    let expected = width as usize * height as usize * format.bytes_per_pixel();
+    if pixels.len() != expected {
+        return Err(TextureError::PixelCount {
+            expected,
+            got: pixels.len(),
+        });
+    }
+    Ok(())
+}
+
+/// A sampler object, overriding the sampling parameters of the textures bound
+/// to the same unit.
+#[derive(Debug)]
+pub struct Sampler {
+    gl_obj: u32,
+    params: SamplerParams,
+
+    _marker: NotSend,
+}
+
+impl Sampler {
+    pub fn new(params: SamplerParams) -> Self {
+        let mut gl_obj = 0;
+        unsafe {
+            janus::gl::CreateSamplers(1, &mut gl_obj);
+        }
+        params.apply(
+            |pname, value| unsafe { janus::gl::SamplerParameteri(gl_obj, pname, value) },
+            |pname, value| unsafe { janus::gl::SamplerParameterf(gl_obj, pname, value) },
+        );
+        unsafe {
+            janus::gl::SamplerParameterfv(
+                gl_obj,
+                janus::gl::TEXTURE_BORDER_COLOR,
+                params.border_color.as_ptr(),
+            );
+        }
+
+        Self {
+            gl_obj,
+            params,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Bind the sampler to the texture `unit`.
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            janus::gl::BindSampler(unit, self.gl_obj);
+        }
+    }
+
+    pub fn params(&self) -> &SamplerParams {
+        &self.params
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            janus::gl::DeleteSamplers(1, &self.gl_obj);
+        }
+    }
+}
+
+/// A bindless texture handle, as stored in GPU data.
+///
+/// In GLSL, a handle is read as a `uvec2` and converted with
+/// `sampler2D(handle)`. The handle must be made resident before any draw
+/// sampling it is issued.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BindlessHandle(pub u64);
+
+impl BindlessHandle {
+    /// Make the texture accessible to shaders through the handle.
+    pub fn make_resident(self) {
+        if let Some(functions) = bindless::functions() {
+            unsafe { (functions.make_resident)(self.0) }
+        }
+    }
+
+    /// Release the residency of the texture, e.g. when it is no longer used
+    /// by any entity.
+    pub fn make_non_resident(self) {
+        if let Some(functions) = bindless::functions() {
+            unsafe { (functions.make_non_resident)(self.0) }
+        }
+    }
+
+    /// The handle as the `uvec2` read by shaders.
+    pub const fn to_uvec2(self) -> [u32; 2] {
+        [self.0 as u32, (self.0 >> 32) as u32]
+    }
+}
+
+/// Loading of the `ARB_bindless_texture` functions.
+pub mod bindless {
+    use std::{ffi::c_void, sync::OnceLock};
+
+    /// The GLSL directive enabling bindless handles in a shader.
+    pub const GLSL_EXT_BINDLESS: &str = "#extension GL_ARB_bindless_texture : require\n";
+
+    type GetTextureHandle = unsafe extern "system" fn(u32) -> u64;
+    type GetTextureSamplerHandle = unsafe extern "system" fn(u32, u32) -> u64;
+    type MakeResidency = unsafe extern "system" fn(u64);
+
+    pub(super) struct Functions {
+        pub(super) texture_handle: GetTextureHandle,
+        pub(super) texture_sampler_handle: GetTextureSamplerHandle,
+        pub(super) make_resident: MakeResidency,
+        pub(super) make_non_resident: MakeResidency,
+    }
+
+    static FUNCTIONS: OnceLock<Option<Functions>> = OnceLock::new();
+
+    /// Load the extension functions through the proc address `loader` of the
+    /// GL context, if the extension is supported.
+    ///
+    /// Only the first call has an effect.
+    ///
+    /// # Returns
+    /// Whether bindless textures are available.
+    pub fn load_with<F: FnMut(&str) -> *const c_void>(mut loader: F) -> bool {
+        FUNCTIONS
+            .get_or_init(|| {
+                if !is_supported() {
+                    return None;
+                }
+                let mut load = |name| {
+                    let ptr = loader(name);
+                    (!ptr.is_null()).then_some(ptr)
+                };
+                // SAFETY: the pointers are the entry points of the extension
+                // functions with these signatures.
+                use std::mem::transmute;
+                unsafe {
+                    Some(Functions {
+                        texture_handle: transmute::<*const c_void, GetTextureHandle>(load(
+                            "glGetTextureHandleARB",
+                        )?),
+                        texture_sampler_handle: transmute::<*const c_void, GetTextureSamplerHandle>(
+                            load("glGetTextureSamplerHandleARB")?,
+                        ),
+                        make_resident: transmute::<*const c_void, MakeResidency>(load(
+                            "glMakeTextureHandleResidentARB",
+                        )?),
+                        make_non_resident: transmute::<*const c_void, MakeResidency>(load(
+                            "glMakeTextureHandleNonResidentARB",
+                        )?),
+                    })
+                }
+            })
+            .is_some()
+    }
+
+    /// Whether the extension functions have been loaded.
+    pub fn is_available() -> bool {
+        functions().is_some()
+    }
+
+    pub(super) fn functions() -> Option<&'static Functions> {
+        FUNCTIONS.get()?.as_ref()
+    }
+
+    /// Whether the current GL context advertises `GL_ARB_bindless_texture`.
+    fn is_supported() -> bool {
+        if !janus::gl::GetStringi::is_loaded() {
+            return false;
+        }
+
+        let mut count = 0;
+        unsafe {
+            janus::gl::GetIntegerv(janus::gl::NUM_EXTENSIONS, &mut count);
+        }
+        (0..count.max(0) as u32).any(|i| {
+            let name = unsafe { janus::gl::GetStringi(janus::gl::EXTENSIONS, i) };
+            !name.is_null()
+                && unsafe { std::ffi::CStr::from_ptr(name as *const _) }.to_bytes()
+                    == b"GL_ARB_bindless_texture"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_sizes() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 64), 9);
+        assert_eq!(mip_level_count(300, 1), 9);
+
+        assert_eq!(
+            validate_pixels(2, 2, TextureFormat::Rgba8, &[0; 16]),
+            Ok(())
+        );
+        assert_eq!(
+            validate_pixels(2, 2, TextureFormat::Rgba16F, &[0; 16]),
+            Err(TextureError::PixelCount {
+                expected: 32,
+                got: 16
+            })
+        );
+        assert_eq!(
+            validate_pixels(0, 2, TextureFormat::R8, &[]),
+            Err(TextureError::ZeroSize)
+        );
+        assert_eq!(BindlessHandle(0x0000_0002_0000_0001).to_uvec2(), [1, 2]);
+    }
+}
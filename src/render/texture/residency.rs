@@ -0,0 +1,649 @@
+//! Mip-level texture streaming under a VRAM budget.
+//!
+//! Large texture sets do not fit in video memory at full resolution, and
+//! most of their textures are only seen from afar at any time. Streamed
+//! textures only allocate the mip levels which are resident, from the
+//! coarsest one up to their finest resident level:
+//!
+//! * a feedback pass records the finest mip level sampled from each texture
+//!   into a [`TextureFeedback`] buffer, through [`GLSL_LIB_TEXTURE_FEEDBACK`];
+//! * [`TextureResidency::update`] reads the feedback, and requests the next
+//!   finer level of each texture sampled finer than its resident levels, most
+//!   recently used first;
+//! * to stay within the budget, the finest level of the least recently used
+//!   textures, or of textures resident finer than they are sampled, is
+//!   evicted;
+//! * the pixels of each [`StreamRequest`] are loaded by the application, e.g.
+//!   by its asset loaders, and uploaded with [`StreamedTexture::stream_in`].
+//!
+//! The coarsest level of each texture is always resident, so that every
+//! texture can be sampled while its finer levels are streamed in.
+//!
+//! Streaming in or evicting a level reallocates the texture, so its object
+//! and bindless handle change: they must be refreshed after each
+//! [`ResidencyUpdate`] is applied.
+//!
+//! # Example
+//! ```rust,ignore
+//! // FRAGMENT SHADER
+//! texture_feedback(material.texture, feedback_lod(uv, textureSize(albedo, 0)));
+//!
+//! // RENDER, after the feedback pass
+//! let update = residency.update(&feedback.read());
+//! feedback.clear();
+//! for texture in update.evictions {
+//!     textures[texture.index()].evict();
+//! }
+//! for request in update.requests {
+//!     loader.load_mip(request.texture, request.level);
+//! }
+//!
+//! // once a level is loaded
+//! match textures[texture.index()].stream_in(level, &pixels) {
+//!     Ok(()) => residency.fulfil(texture, level),
+//!     Err(_) => residency.cancel(texture),
+//! }
+//! ```
+
+use crate::{
+    render::{
+        NotSend,
+        texture::{Texture2D, TextureError, TextureFormat},
+    },
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (TextureFeedback) => {
+        23
+    };
+}
+
+pub const SHADER_BINDING_TEXTURE_FEEDBACK: u32 = ssbo_binding!(TextureFeedback);
+
+/// The default amount of [`StreamRequest`]s per update.
+pub const DEFAULT_MAX_REQUESTS: usize = 4;
+
+/// The feedback of a texture which was not sampled.
+pub const NOT_SAMPLED: u32 = u32::MAX;
+
+/// The size of the mip `level` of a texture of `width` by `height` pixels.
+pub const fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    let width = width >> level;
+    let height = height >> level;
+    (
+        if width == 0 { 1 } else { width },
+        if height == 0 { 1 } else { height },
+    )
+}
+
+/// The size, format and amount of mip levels of a streamed texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub levels: u32,
+}
+
+impl TextureDesc {
+    /// # Panics
+    /// If `width`, `height` or `levels` are `0`.
+    pub fn new(width: u32, height: u32, format: TextureFormat, levels: u32) -> Self {
+        assert!(
+            width > 0 && height > 0 && levels > 0,
+            "attempted to stream a texture of size {width}x{height} with {levels} levels"
+        );
+        Self {
+            width,
+            height,
+            format,
+            levels,
+        }
+    }
+
+    /// The coarsest mip level, which is always resident.
+    pub const fn tail(&self) -> u32 {
+        self.levels - 1
+    }
+
+    /// The size of the mip `level`, in bytes.
+    pub const fn level_bytes(&self, level: u32) -> usize {
+        let (width, height) = mip_size(self.width, self.height, level);
+        width as usize * height as usize * self.format.bytes_per_pixel()
+    }
+
+    /// The size of the mip levels from `level` to the tail, in bytes.
+    pub fn resident_bytes(&self, level: u32) -> usize {
+        (level..self.levels).map(|l| self.level_bytes(l)).sum()
+    }
+}
+
+/// A texture managed by a [`TextureResidency`], also its index in the
+/// [`TextureFeedback`] buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamedTextureId(u32);
+
+impl StreamedTextureId {
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A request to load the pixels of a mip `level` of a `texture`, and upload
+/// them with [`StreamedTexture::stream_in`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StreamRequest {
+    pub texture: StreamedTextureId,
+    pub level: u32,
+}
+
+/// The result of a [`TextureResidency::update`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResidencyUpdate {
+    /// Textures whose finest level must be evicted, once per entry, with
+    /// [`StreamedTexture::evict`].
+    pub evictions: Vec<StreamedTextureId>,
+    pub requests: Vec<StreamRequest>,
+}
+
+#[derive(Debug)]
+struct Residency {
+    desc: TextureDesc,
+    /// The finest resident level, `levels` if none.
+    resident: u32,
+    /// The finest level sampled in the last feedback.
+    wanted: u32,
+    /// The update in which the texture was last sampled.
+    last_used: u64,
+    /// The level requested and not loaded yet.
+    pending: Option<u32>,
+}
+
+impl Residency {
+    fn is_evictable(&self) -> bool {
+        self.pending.is_none() && self.resident < self.desc.tail()
+    }
+}
+
+/// The residency of the mip levels of streamed textures under a VRAM
+/// budget, see the [module documentation](self).
+#[derive(Debug)]
+pub struct TextureResidency {
+    textures: Vec<Residency>,
+    budget: usize,
+    /// The bytes of the resident and pending levels.
+    used: usize,
+    max_requests: usize,
+    update: u64,
+}
+
+impl TextureResidency {
+    /// A budget of `budget` bytes for all streamed textures.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            textures: Vec::new(),
+            budget,
+            used: 0,
+            max_requests: DEFAULT_MAX_REQUESTS,
+            update: 0,
+        }
+    }
+
+    /// Request at most `max_requests` levels per update, at least one.
+    pub fn with_max_requests(mut self, max_requests: usize) -> Self {
+        self.max_requests = max_requests.max(1);
+        self
+    }
+
+    /// Stream the texture of `desc`, requesting its coarsest level.
+    ///
+    /// The coarsest level is always resident, and is counted in the budget
+    /// even if it exceeds it.
+    ///
+    /// # Returns
+    /// The ID of the texture, and the request of its coarsest level.
+    pub fn register(&mut self, desc: TextureDesc) -> (StreamedTextureId, StreamRequest) {
+        let texture = StreamedTextureId(self.textures.len() as u32);
+        self.used += desc.level_bytes(desc.tail());
+        self.textures.push(Residency {
+            desc,
+            resident: desc.levels,
+            wanted: desc.tail(),
+            last_used: self.update,
+            pending: Some(desc.tail()),
+        });
+        let request = StreamRequest {
+            texture,
+            level: desc.tail(),
+        };
+        (texture, request)
+    }
+
+    /// Read the `feedback` of the last frames, one finest sampled level per
+    /// texture or [`NOT_SAMPLED`], and decide which levels to stream in and
+    /// out.
+    pub fn update(&mut self, feedback: &[u32]) -> ResidencyUpdate {
+        self.update += 1;
+        for (texture, &level) in self.textures.iter_mut().zip(feedback) {
+            if level != NOT_SAMPLED {
+                texture.wanted = level.min(texture.desc.tail());
+                texture.last_used = self.update;
+            }
+        }
+
+        // most recently used first, then the most blurry
+        let mut candidates = (0..self.textures.len())
+            .filter(|&i| {
+                let texture = &self.textures[i];
+                texture.pending.is_none() && texture.wanted < texture.resident
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&i| {
+            let texture = &self.textures[i];
+            (
+                std::cmp::Reverse(texture.last_used),
+                std::cmp::Reverse(texture.resident - texture.wanted),
+            )
+        });
+
+        let mut update = ResidencyUpdate::default();
+        for i in candidates {
+            if update.requests.len() == self.max_requests {
+                break;
+            }
+            let level = self.textures[i].resident - 1;
+            let bytes = self.textures[i].desc.level_bytes(level);
+            while self.used + bytes > self.budget {
+                let Some(victim) = self.victim(i) else {
+                    break;
+                };
+                let texture = &mut self.textures[victim];
+                self.used -= texture.desc.level_bytes(texture.resident);
+                texture.resident += 1;
+                update.evictions.push(StreamedTextureId(victim as u32));
+            }
+            if self.used + bytes > self.budget {
+                continue;
+            }
+
+            self.used += bytes;
+            self.textures[i].pending = Some(level);
+            update.requests.push(StreamRequest {
+                texture: StreamedTextureId(i as u32),
+                level,
+            });
+        }
+        update
+    }
+
+    /// The texture whose finest level is evicted to make room for a level
+    /// of `requester`: a texture resident finer than it is sampled, or the
+    /// least recently used one not sampled in the last feedback.
+    fn victim(&self, requester: usize) -> Option<usize> {
+        self.textures
+            .iter()
+            .enumerate()
+            .filter(|&(i, texture)| {
+                i != requester
+                    && texture.is_evictable()
+                    && (texture.resident < texture.wanted || texture.last_used < self.update)
+            })
+            .min_by_key(|(_, texture)| {
+                (
+                    texture.resident >= texture.wanted,
+                    texture.last_used,
+                    std::cmp::Reverse(texture.desc.level_bytes(texture.resident)),
+                )
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Mark the requested `level` of `texture` as resident, once uploaded.
+    ///
+    /// Levels which were not requested are ignored.
+    pub fn fulfil(&mut self, texture: StreamedTextureId, level: u32) {
+        let Some(texture) = self.textures.get_mut(texture.index()) else {
+            return;
+        };
+        if texture.pending == Some(level) {
+            texture.pending = None;
+            texture.resident = level;
+        }
+    }
+
+    /// Release the request of `texture`, e.g. if its level failed to load.
+    ///
+    /// The level is requested again by a later update.
+    pub fn cancel(&mut self, texture: StreamedTextureId) {
+        let Some(texture) = self.textures.get_mut(texture.index()) else {
+            return;
+        };
+        if let Some(level) = texture.pending.take() {
+            self.used -= texture.desc.level_bytes(level);
+        }
+    }
+
+    /// The finest resident level of `texture`, or its amount of levels if
+    /// none is resident yet.
+    pub fn resident_level(&self, texture: StreamedTextureId) -> Option<u32> {
+        self.textures
+            .get(texture.index())
+            .map(|texture| texture.resident)
+    }
+
+    /// The bytes of the resident and requested levels of all textures.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Change the budget, which is enforced by the next updates.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+    }
+
+    /// The amount of streamed textures, i.e. the capacity required of the
+    /// [`TextureFeedback`] buffer.
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
+
+/// A texture storing only its resident mip levels.
+#[derive(Debug)]
+pub struct StreamedTexture {
+    desc: TextureDesc,
+    /// The finest resident level, `levels` if none.
+    resident: u32,
+    texture: Option<Texture2D>,
+}
+
+impl StreamedTexture {
+    /// A texture of `desc` without any resident level.
+    pub fn new(desc: TextureDesc) -> Self {
+        Self {
+            resident: desc.levels,
+            desc,
+            texture: None,
+        }
+    }
+
+    pub fn desc(&self) -> &TextureDesc {
+        &self.desc
+    }
+
+    /// The finest resident level, or the amount of levels if none is
+    /// resident yet.
+    pub fn resident_level(&self) -> u32 {
+        self.resident
+    }
+
+    /// The storage of the resident levels, whose level `0` is the finest
+    /// resident level.
+    ///
+    /// The texture is reallocated whenever a level is streamed in or
+    /// evicted.
+    pub fn texture(&self) -> Option<&Texture2D> {
+        self.texture.as_ref()
+    }
+
+    /// Upload the `pixels` of the mip `level`, the level finer than the
+    /// finest resident level.
+    ///
+    /// # Panics
+    /// If `level` is not the level finer than the finest resident level.
+    pub fn stream_in(&mut self, level: u32, pixels: &[u8]) -> Result<(), TextureError> {
+        assert!(
+            level + 1 == self.resident,
+            "attempted to stream in level {level} of a texture resident from level {}",
+            self.resident
+        );
+        let (width, height) = mip_size(self.desc.width, self.desc.height, level);
+        super::validate_pixels(width, height, self.desc.format, pixels)?;
+
+        self.reallocate(level);
+        if let Some(texture) = &self.texture {
+            texture.upload_level(0, pixels)?;
+        }
+        Ok(())
+    }
+
+    /// Evict the finest resident level, keeping the coarsest level.
+    ///
+    /// # Returns
+    /// Whether a level was evicted.
+    pub fn evict(&mut self) -> bool {
+        if self.resident >= self.desc.tail() {
+            return false;
+        }
+        self.reallocate(self.resident + 1);
+        true
+    }
+
+    /// Allocate the levels from `resident` to the tail, and copy the levels
+    /// resident in both storages.
+    fn reallocate(&mut self, resident: u32) {
+        let (width, height) = mip_size(self.desc.width, self.desc.height, resident);
+        let texture = Texture2D::new(width, height, self.desc.format, self.desc.levels - resident);
+
+        if let Some(previous) = &self.texture {
+            for level in resident.max(self.resident)..self.desc.levels {
+                let (width, height) = mip_size(self.desc.width, self.desc.height, level);
+                unsafe {
+                    janus::gl::CopyImageSubData(
+                        previous.gl_obj(),
+                        janus::gl::TEXTURE_2D,
+                        (level - self.resident) as i32,
+                        0,
+                        0,
+                        0,
+                        texture.gl_obj(),
+                        janus::gl::TEXTURE_2D,
+                        (level - resident) as i32,
+                        0,
+                        0,
+                        0,
+                        width as i32,
+                        height as i32,
+                        1,
+                    );
+                }
+            }
+        }
+        self.texture = Some(texture);
+        self.resident = resident;
+    }
+}
+
+/// Texture feedback SSBO interface.
+///
+/// Contains the SSBO declaration of the [`TextureFeedback`] buffer, on
+/// binding index 23, as the finest sampled level per streamed texture.
+pub const GLSL_SSBO_TEXTURE_FEEDBACK: GlslStorage = crate::shader_glsl_ssbo! {
+    buf TextureFeedback => {
+        [dyn_array uint: texture_feedback]
+    }
+};
+
+/// Record that the mip `lod` of the streamed `texture` was sampled, e.g.
+/// from [`GLSL_LIB_FEEDBACK_LOD`]. Requires [`GLSL_SSBO_TEXTURE_FEEDBACK`].
+pub const GLSL_LIB_TEXTURE_FEEDBACK: GlslLib = crate::shader_glsl_lib! {
+    void texture_feedback [ texture: uint, lod: float ] => "
+        if (texture < uint(texture_feedback.length())) {
+            atomicMin(texture_feedback[texture], uint(max(lod, 0.0)));
+        }
+    "
+};
+
+/// The mip level sampled at `uv` from a texture of `size` pixels, from the
+/// derivatives of the fragment.
+pub const GLSL_LIB_FEEDBACK_LOD: GlslLib = crate::shader_glsl_lib! {
+    float feedback_lod [ uv: vec2, size: vec2 ] => "
+        vec2 dx = dFdx(uv * size);
+        vec2 dy = dFdy(uv * size);
+        return 0.5 * log2(max(dot(dx, dx), dot(dy, dy)));
+    "
+};
+
+/// The feedback buffer of streamed textures, written by the feedback pass
+/// and read by [`TextureResidency::update`].
+pub struct TextureFeedback {
+    gl_obj: u32,
+    capacity: u32,
+
+    _marker: NotSend,
+}
+
+impl TextureFeedback {
+    /// Allocate the feedback of `capacity` textures, cleared.
+    pub fn new(capacity: u32) -> Self {
+        let mut gl_obj = 0;
+        unsafe {
+            janus::gl::CreateBuffers(1, &mut gl_obj);
+            janus::gl::NamedBufferStorage(
+                gl_obj,
+                (capacity.max(1) as usize * size_of::<u32>()) as isize,
+                std::ptr::null(),
+                janus::gl::DYNAMIC_STORAGE_BIT,
+            );
+        }
+        let feedback = Self {
+            gl_obj,
+            capacity,
+            _marker: std::marker::PhantomData,
+        };
+        feedback.clear();
+        feedback
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Reset the feedback of every texture to [`NOT_SAMPLED`].
+    pub fn clear(&self) {
+        unsafe {
+            janus::gl::ClearNamedBufferData(
+                self.gl_obj,
+                janus::gl::R32UI,
+                janus::gl::RED_INTEGER,
+                janus::gl::UNSIGNED_INT,
+                &NOT_SAMPLED as *const u32 as *const _,
+            );
+        }
+    }
+
+    /// Bind the feedback to [`SHADER_BINDING_TEXTURE_FEEDBACK`] for the
+    /// following draws.
+    pub fn bind(&self) {
+        unsafe {
+            janus::gl::BindBufferBase(
+                janus::gl::SHADER_STORAGE_BUFFER,
+                SHADER_BINDING_TEXTURE_FEEDBACK,
+                self.gl_obj,
+            );
+        }
+    }
+
+    /// Read the feedback back.
+    ///
+    /// Waits for the draws writing the feedback to complete, so the
+    /// feedback should be read every few frames rather than every frame.
+    pub fn read(&self) -> Vec<u32> {
+        let mut feedback = vec![NOT_SAMPLED; self.capacity as usize];
+        unsafe {
+            janus::gl::MemoryBarrier(janus::gl::BUFFER_UPDATE_BARRIER_BIT);
+            janus::gl::GetNamedBufferSubData(
+                self.gl_obj,
+                0,
+                (feedback.len() * size_of::<u32>()) as isize,
+                feedback.as_mut_ptr() as *mut _,
+            );
+        }
+        feedback
+    }
+}
+
+impl Drop for TextureFeedback {
+    fn drop(&mut self) {
+        unsafe {
+            janus::gl::DeleteBuffers(1, &self.gl_obj);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_within_budget() {
+        // 4x4 R8 textures: levels of 16, 4 and 1 bytes
+        let desc = TextureDesc::new(4, 4, TextureFormat::R8, 3);
+        assert_eq!(desc.resident_bytes(0), 21);
+
+        let mut residency = TextureResidency::new(24);
+        let (a, request) = residency.register(desc);
+        assert_eq!(request.level, 2);
+        residency.fulfil(a, 2);
+        let (b, _) = residency.register(desc);
+        residency.fulfil(b, 2);
+
+        // a is sampled at full resolution, streamed one level at a time
+        let update = residency.update(&[0, NOT_SAMPLED]);
+        assert_eq!(
+            update.requests,
+            [StreamRequest {
+                texture: a,
+                level: 1
+            }]
+        );
+        residency.fulfil(a, 1);
+        let update = residency.update(&[0, 1]);
+        assert_eq!(
+            update.requests,
+            [StreamRequest {
+                texture: a,
+                level: 0
+            }]
+        );
+        residency.fulfil(a, 0);
+        assert_eq!(residency.used(), 22);
+
+        // b needs room, taken from a once it is no longer sampled finely
+        let update = residency.update(&[0, 1]);
+        assert!(update.requests.is_empty() && update.evictions.is_empty());
+        let update = residency.update(&[2, 1]);
+        assert_eq!(update.evictions, [a]);
+        assert_eq!(
+            update.requests,
+            [StreamRequest {
+                texture: b,
+                level: 1
+            }]
+        );
+        assert_eq!(residency.resident_level(a), Some(1));
+        assert_eq!(residency.used(), 10);
+
+        // cancelled requests are requested again
+        residency.cancel(b);
+        assert_eq!(residency.used(), 6);
+        let update = residency.update(&[NOT_SAMPLED; 2]);
+        assert_eq!(
+            update.requests,
+            [StreamRequest {
+                texture: b,
+                level: 1
+            }]
+        );
+        assert_eq!(mip_size(5, 2, 2), (1, 1));
+    }
+}
@@ -0,0 +1,73 @@
+//! GPU frame timing with timer queries.
+
+use std::time::Duration;
+
+use crate::render::NotSend;
+
+/// Measures the GPU time of frames with `GL_TIME_ELAPSED` queries.
+///
+/// Results are only read once available, which takes a few frames: the
+/// timer cycles through one query per section of the triple buffer, so
+/// reading a result never stalls the render thread.
+#[derive(Debug, Default)]
+pub struct GpuTimer {
+    queries: [u32; 3],
+    pending: [bool; 3],
+    index: usize,
+
+    _marker: NotSend,
+}
+
+impl GpuTimer {
+    /// Start timing the GL commands issued from now on.
+    pub fn begin(&mut self) {
+        if self.queries[0] == 0 {
+            unsafe {
+                janus::gl::CreateQueries(janus::gl::TIME_ELAPSED, 3, self.queries.as_mut_ptr());
+            }
+        }
+        unsafe {
+            janus::gl::BeginQuery(janus::gl::TIME_ELAPSED, self.queries[self.index]);
+        }
+    }
+
+    /// Stop timing.
+    ///
+    /// # Returns
+    /// The GPU time of the oldest timed frame, if its result is available.
+    pub fn end(&mut self) -> Option<Duration> {
+        unsafe {
+            janus::gl::EndQuery(janus::gl::TIME_ELAPSED);
+        }
+        self.pending[self.index] = true;
+        self.index = (self.index + 1) % 3;
+
+        // the query about to be reused is the oldest one
+        let oldest = self.queries[self.index];
+        if !std::mem::take(&mut self.pending[self.index]) {
+            return None;
+        }
+        let mut available = 0;
+        unsafe {
+            janus::gl::GetQueryObjectuiv(oldest, janus::gl::QUERY_RESULT_AVAILABLE, &mut available);
+        }
+        if available == 0 {
+            return None;
+        }
+        let mut nanos = 0;
+        unsafe {
+            janus::gl::GetQueryObjectui64v(oldest, janus::gl::QUERY_RESULT, &mut nanos);
+        }
+        Some(Duration::from_nanos(nanos))
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        if self.queries[0] != 0 {
+            unsafe {
+                janus::gl::DeleteQueries(3, self.queries.as_ptr());
+            }
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! CPU verification of the clip-space positions computed by vertex shaders.
+//!
+//! Swapped matrix multiplications, transposed uploads and drifting buffer
+//! layouts all show as misplaced geometry, which is hard to tell apart by
+//! eye and impossible to check in CI. Instead, vertex shaders record the
+//! `gl_Position` of the first vertices of each entity with
+//! [`GLSL_LIB_CAPTURE_CLIP`], read back with [`ClipCapture`], and the
+//! [`TransformVerifier`] recomputes them on the CPU from the same data the GPU
+//! received, without rasterising anything, reporting the vertices which
+//! differ and the likely cause.
+//!
+//! # Example
+//! ```rust,ignore
+//! // VERTEX SHADER
+//! gl_Position = view_projection * model * vertex.position;
+//! capture_clip(entity, uint(gl_VertexID) - mesh.offset, gl_Position);
+//!
+//! // RENDER
+//! capture.bind();
+//! dispatch.dispatch();
+//! let table = capture.read();
+//!
+//! let samples = (0..entities).flat_map(|entity| {
+//!     let model = verify::model_matrix(&transforms[entity], scales[entity]);
+//!     (0..3).map(move |vertex| TransformSample::new(entity, vertex, model, vertices[vertex]))
+//! });
+//! for mismatch in TransformVerifier::new(view_projection).verify(samples, &table) {
+//!     tracing::error!("{mismatch}");
+//! }
+//! ```
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use crate::{
+    render::{NotSend, buffer::pack::PackedTransform},
+    shader::glsl::{GlslLib, GlslStorage},
+};
+
+macro_rules! ssbo_binding {
+    (ClipCapture) => {
+        21
+    };
+}
+
+pub const SHADER_BINDING_CLIP_CAPTURE: u32 = ssbo_binding!(ClipCapture);
+
+/// The default tolerance of [`TransformVerifier`], relative to the `w`
+/// component of the expected clip-space position.
+pub const DEFAULT_TOLERANCE: f32 = 1e-4;
+
+/// Contains the SSBO declaration of the table of [`ClipCapture`], on binding
+/// index 21.
+pub const GLSL_SSBO_CLIP_CAPTURE: GlslStorage = crate::shader_glsl_ssbo! {
+    buf ClipCapture => {
+        uint: capture_vertices;
+        [dyn_array vec4: captured_clips]
+    }
+};
+
+/// Record the `clip` position of the `vertex` of `entity`, counted from the
+/// first vertex of its mesh, if it is within the table of [`ClipCapture`].
+/// Requires [`GLSL_SSBO_CLIP_CAPTURE`].
+pub const GLSL_LIB_CAPTURE_CLIP: GlslLib = crate::shader_glsl_lib! {
+    void capture_clip [ entity: uint, vertex: uint, clip: vec4 ] => "
+        uint slot = entity * capture_vertices + vertex;
+        if (vertex < capture_vertices && slot < uint(captured_clips.length())) {
+            captured_clips[slot] = clip;
+        }
+    "
+};
+
+/// The model matrix of a [`PackedTransform`] and a `scale`, as computed by
+/// [`GLSL_LIB_TRS_MATRIX`](super::buffer::pack::GLSL_LIB_TRS_MATRIX).
+pub fn model_matrix(transform: &PackedTransform, scale: Vec3) -> Mat4 {
+    Mat4::from_scale_rotation_translation(
+        scale,
+        Quat::from_vec4(transform.rotation),
+        transform.position.truncate(),
+    )
+}
+
+/// The clip-space positions captured for the first vertices of each entity,
+/// read with [`ClipCapture::read`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClipTable {
+    vertices: u32,
+    clips: Vec<Vec4>,
+}
+
+impl ClipTable {
+    /// A table of the first `vertices` of each entity, from the `clips` of
+    /// every entity one after the other.
+    pub fn new(vertices: u32, clips: Vec<Vec4>) -> Self {
+        Self { vertices, clips }
+    }
+
+    /// The clip-space position captured for the `vertex` of `entity`, if it
+    /// was drawn.
+    ///
+    /// The table is cleared to zero before drawing, which is never the
+    /// position of a visible vertex.
+    pub fn get(&self, entity: u32, vertex: u32) -> Option<Vec4> {
+        if vertex >= self.vertices {
+            return None;
+        }
+        let slot = entity as usize * self.vertices as usize + vertex as usize;
+        self.clips
+            .get(slot)
+            .copied()
+            .filter(|clip| *clip != Vec4::ZERO)
+    }
+}
+
+/// A vertex of an entity, with the data the GPU received to position it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformSample {
+    pub entity: u32,
+    /// The vertex, counted from the first vertex of the mesh of the entity.
+    pub vertex: u32,
+    pub model: Mat4,
+    /// The object-space position of the vertex.
+    pub position: Vec4,
+}
+
+impl TransformSample {
+    pub fn new(entity: u32, vertex: u32, model: Mat4, position: Vec4) -> Self {
+        Self {
+            entity,
+            vertex,
+            model,
+            position,
+        }
+    }
+}
+
+/// The likely cause of a [`TransformMismatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MismatchCause {
+    /// The vertex was not drawn, or not captured.
+    NotCaptured,
+    /// The shader multiplies the model matrix before the view projection.
+    SwappedOrder,
+    /// The matrices reached the shader transposed, e.g. uploaded row-major.
+    Transposed,
+    /// The position matches none of the usual mistakes, e.g. the shader read
+    /// the data of another entity, or a drifting layout.
+    Unknown,
+}
+
+/// A vertex whose captured clip-space position differs from the one
+/// computed on the CPU.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformMismatch {
+    pub entity: u32,
+    pub vertex: u32,
+    pub expected: Vec4,
+    pub captured: Option<Vec4>,
+    pub cause: MismatchCause,
+}
+
+impl std::fmt::Display for TransformMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (entity, vertex, expected) = (self.entity, self.vertex, self.expected);
+        let Some(captured) = self.captured else {
+            return write!(
+                f,
+                "vertex {vertex} of entity {entity} was not captured, expected {expected}"
+            );
+        };
+        write!(
+            f,
+            "vertex {vertex} of entity {entity} is at {captured} in clip space, expected {expected}"
+        )?;
+        match self.cause {
+            MismatchCause::SwappedOrder => {
+                write!(f, ": the model and view projection matrices are swapped")
+            }
+            MismatchCause::Transposed => write!(f, ": the matrices are transposed"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Recomputes clip-space positions on the CPU, see the
+/// [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformVerifier {
+    pub view_projection: Mat4,
+    tolerance: f32,
+}
+
+impl TransformVerifier {
+    pub fn new(view_projection: Mat4) -> Self {
+        Self {
+            view_projection,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// The tolerance of the comparisons, relative to the `w` component of
+    /// the expected clip-space position.
+    pub fn tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The clip-space position of `sample`.
+    pub fn expected(&self, sample: &TransformSample) -> Vec4 {
+        self.view_projection * sample.model * sample.position
+    }
+
+    fn matches(&self, expected: Vec4, captured: Vec4) -> bool {
+        let tolerance = self.tolerance * expected.w.abs().max(1.0);
+        (expected - captured).abs().max_element() <= tolerance
+    }
+
+    /// Compare the captured clip-space positions of `samples` in `table`
+    /// with the ones computed on the CPU.
+    ///
+    /// # Returns
+    /// The mismatching samples, with their likely cause.
+    pub fn verify(
+        &self,
+        samples: impl IntoIterator<Item = TransformSample>,
+        table: &ClipTable,
+    ) -> Vec<TransformMismatch> {
+        samples
+            .into_iter()
+            .filter_map(|sample| {
+                let expected = self.expected(&sample);
+                let captured = table.get(sample.entity, sample.vertex);
+                let cause = match captured {
+                    None => MismatchCause::NotCaptured,
+                    Some(captured) if self.matches(expected, captured) => return None,
+                    Some(captured) => self.cause(&sample, captured),
+                };
+                Some(TransformMismatch {
+                    entity: sample.entity,
+                    vertex: sample.vertex,
+                    expected,
+                    captured,
+                    cause,
+                })
+            })
+            .collect()
+    }
+
+    fn cause(&self, sample: &TransformSample, captured: Vec4) -> MismatchCause {
+        let (view_projection, model) = (self.view_projection, sample.model);
+        let swapped = model * view_projection * sample.position;
+        let transposed = view_projection.transpose() * model.transpose() * sample.position;
+        if self.matches(swapped, captured) {
+            MismatchCause::SwappedOrder
+        } else if self.matches(transposed, captured) {
+            MismatchCause::Transposed
+        } else {
+            MismatchCause::Unknown
+        }
+    }
+}
+
+/// A GPU buffer capturing the clip-space positions of the first vertices of
+/// each entity, see the [module documentation](self).
+#[derive(Debug)]
+pub struct ClipCapture {
+    gl_obj: u32,
+    entities: u32,
+    vertices: u32,
+
+    _marker: NotSend,
+}
+
+impl ClipCapture {
+    /// Allocate room for the first `vertices` of `entities` entities.
+    pub fn new(entities: u32, vertices: u32) -> Self {
+        let mut gl_obj = 0;
+        unsafe {
+            janus::gl::CreateBuffers(1, &mut gl_obj);
+            janus::gl::NamedBufferStorage(
+                gl_obj,
+                Self::size(entities, vertices) as isize,
+                std::ptr::null(),
+                janus::gl::DYNAMIC_STORAGE_BIT,
+            );
+        }
+        Self {
+            gl_obj,
+            entities,
+            vertices,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// The size of the storage block: the vertex count, padded to the
+    /// alignment of the `vec4` array, then the array.
+    fn size(entities: u32, vertices: u32) -> usize {
+        size_of::<Vec4>() * (1 + entities as usize * vertices as usize)
+    }
+
+    /// Clear the table and bind it to [`SHADER_BINDING_CLIP_CAPTURE`] for the
+    /// following draws.
+    pub fn bind(&self) {
+        unsafe {
+            janus::gl::ClearNamedBufferData(
+                self.gl_obj,
+                janus::gl::R32UI,
+                janus::gl::RED_INTEGER,
+                janus::gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+            janus::gl::NamedBufferSubData(
+                self.gl_obj,
+                0,
+                size_of::<u32>() as isize,
+                &self.vertices as *const u32 as *const _,
+            );
+            janus::gl::BindBufferBase(
+                janus::gl::SHADER_STORAGE_BUFFER,
+                SHADER_BINDING_CLIP_CAPTURE,
+                self.gl_obj,
+            );
+        }
+    }
+
+    /// Read the captured table back.
+    ///
+    /// Waits for the draws capturing the table to complete.
+    pub fn read(&self) -> ClipTable {
+        let mut clips = vec![Vec4::ZERO; self.entities as usize * self.vertices as usize];
+        unsafe {
+            janus::gl::MemoryBarrier(janus::gl::BUFFER_UPDATE_BARRIER_BIT);
+            janus::gl::GetNamedBufferSubData(
+                self.gl_obj,
+                size_of::<Vec4>() as isize,
+                (clips.len() * size_of::<Vec4>()) as isize,
+                clips.as_mut_ptr() as *mut _,
+            );
+        }
+        ClipTable::new(self.vertices, clips)
+    }
+}
+
+impl Drop for ClipCapture {
+    fn drop(&mut self) {
+        unsafe {
+            janus::gl::DeleteBuffers(1, &self.gl_obj);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_clip_positions() {
+        let view_projection = Mat4::perspective_infinite_reverse_rh(1.2, 1.5, 0.1)
+            * Mat4::look_at_rh(Vec3::new(0.0, 2.0, 6.0), Vec3::ZERO, Vec3::Y);
+        let transform = PackedTransform::new(Vec3::new(1.0, 0.0, -2.0), Quat::from_rotation_y(0.7));
+        let model = model_matrix(&transform, Vec3::splat(2.0));
+        let position = Vec4::new(0.5, 1.0, -0.5, 1.0);
+
+        let samples = (0..4).map(|entity| TransformSample::new(entity, 0, model, position));
+        let correct = view_projection * model * position;
+        let table = ClipTable::new(
+            2,
+            vec![
+                correct,
+                Vec4::ZERO,
+                model * view_projection * position,
+                Vec4::ZERO,
+                view_projection.transpose() * model.transpose() * position,
+                Vec4::ZERO,
+            ],
+        );
+
+        let verifier = TransformVerifier::new(view_projection);
+        let causes = verifier
+            .verify(samples, &table)
+            .iter()
+            .map(|m| (m.entity, m.cause))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            causes,
+            [
+                (1, MismatchCause::SwappedOrder),
+                (2, MismatchCause::Transposed),
+                (3, MismatchCause::NotCaptured)
+            ]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::shader::glsl::{GlslLib, GlslStorage};
+
+macro_rules! ssbo_binding {
+    (EntityVisibility) => {
+        13
+    };
+}
+
+pub const SHADER_BINDING_VISIBILITY: u32 = ssbo_binding!(EntityVisibility);
+
+/// A mask of up to 32 render layers.
+///
+/// Entities belong to one or more layers, and each camera (or pass) renders
+/// the entities of the layers in its filter, e.g. to keep UI-only or
+/// editor-only objects out of the main pass.
+///
+/// Entities belong to [`RenderLayers::DEFAULT`] by default.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl RenderLayers {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self(u32::MAX);
+    /// Layer 0.
+    pub const DEFAULT: Self = Self::layer(0);
+
+    /// The mask of the single `layer`.
+    ///
+    /// # Panics
+    /// If `layer` is not lower than 32.
+    pub const fn layer(layer: u32) -> Self {
+        assert!(layer < 32, "render layers range from 0 to 31");
+        Self(1 << layer)
+    }
+
+    pub const fn with(self, layer: u32) -> Self {
+        Self(self.0 | Self::layer(layer).0)
+    }
+
+    pub const fn without(self, layer: u32) -> Self {
+        Self(self.0 & !Self::layer(layer).0)
+    }
+
+    pub const fn contains(self, layer: u32) -> bool {
+        self.intersects(Self::layer(layer))
+    }
+
+    /// Whether any layer is shared between the two masks.
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl std::ops::BitOr for RenderLayers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for RenderLayers {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// The visibility of an entity: whether it is rendered at all, and by which
+/// cameras through its [`RenderLayers`].
+///
+/// Stored per entity in a column, and respected by command generation with
+/// [`CommandBatcher::push_visible`](super::batch::CommandBatcher::push_visible).
+/// It can also be uploaded to the partition declared by
+/// [`GLSL_SSBO_VISIBILITY`] for GPU-side command generation.
+///
+/// # Example
+/// ```rust,ignore
+/// table_spec! {
+///     struct Props {
+///         position: Vec3;
+///         visibility: Visibility;
+///     }
+/// }
+///
+/// let filter = viewpoint.layers;
+/// for (id, visibility) in props.visibility.iter().enumerate().skip(1) {
+///     batcher.push_visible(*visibility, filter, material, mesh, command(id));
+/// }
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Visibility {
+    pub layers: RenderLayers,
+    flags: u32,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self::new(RenderLayers::DEFAULT)
+    }
+}
+
+impl Visibility {
+    const FLAG_VISIBLE: u32 = 1;
+
+    /// Visible in the given `layers`.
+    pub const fn new(layers: RenderLayers) -> Self {
+        Self {
+            layers,
+            flags: Self::FLAG_VISIBLE,
+        }
+    }
+
+    pub const fn hidden(layers: RenderLayers) -> Self {
+        Self { layers, flags: 0 }
+    }
+
+    pub const fn is_visible(&self) -> bool {
+        self.flags & Self::FLAG_VISIBLE != 0
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        if visible {
+            self.flags |= Self::FLAG_VISIBLE;
+        } else {
+            self.flags &= !Self::FLAG_VISIBLE;
+        }
+    }
+
+    /// Whether the entity is visible and in any of the layers of `filter`.
+    pub const fn is_visible_in(&self, filter: RenderLayers) -> bool {
+        self.is_visible() && self.layers.intersects(filter)
+    }
+}
+
+/// Entity visibility SSBO interface.
+///
+/// Contains the SSBO declaration of a [`Visibility`] partition, on binding
+/// index 13, as `uvec2(layers, flags)` per entity.
+pub const GLSL_SSBO_VISIBILITY: GlslStorage = crate::shader_glsl_ssbo! {
+    buf EntityVisibility => {
+        [dyn_array uvec2: visibility]
+    }
+};
+
+/// Whether the entity at `id` of the [`GLSL_SSBO_VISIBILITY`] partition is
+/// visible in the layers of `filter`.
+pub const GLSL_LIB_IS_VISIBLE: GlslLib = crate::shader_glsl_lib! {
+    bool is_visible [ id: uint, filter: uint ] => "
+        uvec2 v = visibility[id];
+        return (v.y & 1u) != 0u && (v.x & filter) != 0u;
+    "
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visibility_layers() {
+        let ui = RenderLayers::layer(3);
+        let mut visibility = Visibility::new(RenderLayers::DEFAULT | ui);
+
+        assert!(visibility.is_visible_in(RenderLayers::ALL));
+        assert!(visibility.is_visible_in(ui));
+        assert!(!visibility.is_visible_in(RenderLayers::layer(4)));
+
+        visibility.set_visible(false);
+        assert!(!visibility.is_visible_in(RenderLayers::ALL));
+
+        assert_eq!(RenderLayers::ALL.without(0).with(0), RenderLayers::ALL);
+        assert_eq!(size_of::<Visibility>(), 8);
+    }
+}
@@ -0,0 +1,190 @@
+//! Shader programs assembled from complete sources, stage by stage.
+//!
+//! The [`shader_glsl!`](crate::shader_glsl) macro composes programs whose
+//! sources are known at compile time. [`ShaderProgramBuilder`] instead takes
+//! the full source of each stage, e.g. loaded from disk or generated at
+//! runtime, including the geometry and tessellation stages, and reports the
+//! stage which failed to compile or the program which failed to link as a
+//! [`ShaderError`] rather than panicking.
+//!
+//! Tessellated programs must be drawn as patches: set the amount of vertices
+//! per patch with [`set_patch_vertices`], and dispatch the draw commands with
+//! [`GpuCommandDispatch::with_primitive`] and `gl::PATCHES`.
+//!
+//! # Example
+//! ```rust,ignore
+//! let program = ShaderProgramBuilder::new()
+//!     .vertex(include_str!("terrain.vsh"))
+//!     .tess_control(include_str!("terrain.tcs"))
+//!     .tess_eval(include_str!("terrain.tes"))
+//!     .pixel(include_str!("terrain.fsh"))
+//!     .build()?;
+//!
+//! shader::set_patch_vertices(3);
+//! GpuCommandDispatch::from_view(commands)
+//!     .with_primitive(janus::gl::PATCHES)
+//!     .dispatch();
+//! ```
+//!
+//! [`GpuCommandDispatch::with_primitive`]: crate::render::command::GpuCommandDispatch::with_primitive
+
+use crate::shader::{
+    ShaderError, ShaderHandle, ShaderKind, attach_shader_units, compile_shader_unit,
+    delete_shader_units, generate_blank, link_shader_program_checked,
+};
+
+/// Check that `stages` form a valid pipeline.
+fn validate_stages(stages: &[ShaderKind]) -> Result<(), ShaderError> {
+    for (i, kind) in stages.iter().enumerate() {
+        if stages[..i].contains(kind) {
+            return Err(ShaderError::DuplicateStage(*kind));
+        }
+    }
+
+    let has = |kind| stages.contains(&kind);
+    if stages.is_empty() {
+        return Err(ShaderError::InvalidStages("no stage"));
+    }
+    if has(ShaderKind::Compute) {
+        return match stages.len() {
+            1 => Ok(()),
+            _ => Err(ShaderError::InvalidStages(
+                "compute shaders cannot be linked with other stages",
+            )),
+        };
+    }
+    if !has(ShaderKind::Vertex) {
+        return Err(ShaderError::InvalidStages("no vertex stage"));
+    }
+    if has(ShaderKind::TesselationCtl) && !has(ShaderKind::TesselationEval) {
+        return Err(ShaderError::InvalidStages(
+            "tessellation control stage without evaluation stage",
+        ));
+    }
+    Ok(())
+}
+
+/// Builds a [`ShaderHandle`] from the complete source of each stage.
+///
+/// See the [module documentation](self).
+#[derive(Clone, Debug, Default)]
+pub struct ShaderProgramBuilder {
+    stages: Vec<(ShaderKind, String)>,
+}
+
+impl ShaderProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the stage of `kind`, from its complete `source`, including the
+    /// `#version` directive.
+    pub fn stage(mut self, kind: ShaderKind, source: impl Into<String>) -> Self {
+        self.stages.push((kind, source.into()));
+        self
+    }
+
+    pub fn vertex(self, source: impl Into<String>) -> Self {
+        self.stage(ShaderKind::Vertex, source)
+    }
+
+    pub fn tess_control(self, source: impl Into<String>) -> Self {
+        self.stage(ShaderKind::TesselationCtl, source)
+    }
+
+    pub fn tess_eval(self, source: impl Into<String>) -> Self {
+        self.stage(ShaderKind::TesselationEval, source)
+    }
+
+    pub fn geometry(self, source: impl Into<String>) -> Self {
+        self.stage(ShaderKind::Geometry, source)
+    }
+
+    pub fn pixel(self, source: impl Into<String>) -> Self {
+        self.stage(ShaderKind::Pixel, source)
+    }
+
+    pub fn compute(self, source: impl Into<String>) -> Self {
+        self.stage(ShaderKind::Compute, source)
+    }
+
+    /// The kinds of the added stages, in order.
+    pub fn stages(&self) -> impl Iterator<Item = ShaderKind> + '_ {
+        self.stages.iter().map(|(kind, _)| *kind)
+    }
+
+    /// Compile every stage and link them into a program.
+    ///
+    /// All stages are compiled, so that the diagnostics of every failing
+    /// stage are logged, but only the first failure is returned.
+    pub fn build(self) -> Result<ShaderHandle, ShaderError> {
+        let kinds: Vec<_> = self.stages().collect();
+        validate_stages(&kinds)?;
+
+        let mut units = Vec::with_capacity(self.stages.len());
+        let mut error = None;
+        for (kind, source) in &self.stages {
+            match compile_shader_unit(source, *kind) {
+                Ok(unit) => units.push(unit),
+                Err(log) => {
+                    error.get_or_insert_with(|| ShaderError::compile(*kind, log, source));
+                }
+            }
+        }
+        if let Some(error) = error {
+            delete_shader_units(&mut units);
+            return Err(error);
+        }
+
+        let handle = generate_blank();
+        attach_shader_units(&handle, &units);
+        let linked = link_shader_program_checked(&handle, &units);
+        delete_shader_units(&mut units);
+        linked.map(|()| handle)
+    }
+}
+
+/// Set the amount of vertices per patch of tessellated draws.
+pub fn set_patch_vertices(count: u32) {
+    unsafe {
+        janus::gl::PatchParameteri(janus::gl::PATCH_VERTICES, count as i32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_pipeline_stages() {
+        use ShaderKind::*;
+
+        assert_eq!(validate_stages(&[Vertex, Pixel]), Ok(()));
+        assert_eq!(
+            validate_stages(&[Vertex, TesselationCtl, TesselationEval, Geometry, Pixel]),
+            Ok(())
+        );
+        assert_eq!(validate_stages(&[Vertex, TesselationEval]), Ok(()));
+        assert_eq!(validate_stages(&[Compute]), Ok(()));
+
+        assert_eq!(
+            validate_stages(&[Vertex, Pixel, Vertex]),
+            Err(ShaderError::DuplicateStage(Vertex))
+        );
+        assert!(matches!(
+            validate_stages(&[Vertex, TesselationCtl]),
+            Err(ShaderError::InvalidStages(_))
+        ));
+        assert!(matches!(
+            validate_stages(&[Geometry, Pixel]),
+            Err(ShaderError::InvalidStages(_))
+        ));
+        assert!(matches!(
+            validate_stages(&[Compute, Vertex]),
+            Err(ShaderError::InvalidStages(_))
+        ));
+
+        let builder = ShaderProgramBuilder::new().vertex("").geometry("");
+        assert_eq!(builder.stages().collect::<Vec<_>>(), [Vertex, Geometry]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Errors of shader compilation and linking.
+
+use crate::shader::ShaderKind;
+
+/// The amount of source lines shown before and after the line of a compile
+/// error, see [`source_excerpt`].
+const EXCERPT_CONTEXT: usize = 2;
+
+/// A shader program which cannot be built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShaderError {
+    /// The stages do not form a valid pipeline, e.g. a tessellation control
+    /// stage without an evaluation stage.
+    InvalidStages(&'static str),
+    /// A stage was added more than once.
+    DuplicateStage(ShaderKind),
+    /// A stage failed to compile, with the compile log and the source lines
+    /// around the first error, see [`source_excerpt`].
+    Compile {
+        kind: ShaderKind,
+        log: String,
+        excerpt: String,
+    },
+    /// The program failed to link, with the link log.
+    Link { log: String },
+}
+
+impl ShaderError {
+    /// The error of the stage of `kind` which failed to compile `source`
+    /// with the compile `log`.
+    pub fn compile(kind: ShaderKind, log: impl Into<String>, source: &str) -> Self {
+        let log = log.into();
+        let excerpt = source_excerpt(source, &log).unwrap_or_default();
+        Self::Compile { kind, log, excerpt }
+    }
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidStages(reason) => write!(f, "invalid shader stages: {reason}"),
+            Self::DuplicateStage(kind) => write!(f, "duplicate {kind} shader stage"),
+            Self::Compile { kind, log, excerpt } => {
+                write!(f, "failed to compile {kind} shader: {log}")?;
+                if !excerpt.is_empty() {
+                    write!(f, "\n{excerpt}")?;
+                }
+                Ok(())
+            }
+            Self::Link { log } => write!(f, "failed to link shader program: {log}"),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// The line of the first error of a compile `log`, counted from 1.
+///
+/// Understands the `0(12)` (NVIDIA) and `0:12` (Mesa, AMD, Intel) formats of
+/// source locations.
+fn error_line(log: &str) -> Option<usize> {
+    log.lines().find_map(|line| {
+        let at = line.find("0(").or_else(|| line.find("0:"))?;
+        let digits: String = line[at + 2..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.parse().ok()
+    })
+}
+
+/// The lines of `source` around the first error of the compile `log`, with
+/// their line numbers, and the line of the error marked with `>`.
+///
+/// # Returns
+/// `None` if the log does not refer to a line of `source`.
+pub fn source_excerpt(source: &str, log: &str) -> Option<String> {
+    let line = error_line(log)?;
+    let lines: Vec<_> = source.lines().collect();
+    if line == 0 || line > lines.len() {
+        return None;
+    }
+
+    let first = line.saturating_sub(EXCERPT_CONTEXT).max(1);
+    let last = (line + EXCERPT_CONTEXT).min(lines.len());
+    let excerpt = (first..=last)
+        .map(|n| {
+            let marker = if n == line { '>' } else { ' ' };
+            format!("{marker}{n:>5} | {}", lines[n - 1])
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(excerpt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_error_excerpt() {
+        assert_eq!(
+            error_line("0(3) : error C1008: undefined variable"),
+            Some(3)
+        );
+        assert_eq!(error_line("0:12(5): error: `foo' undeclared"), Some(12));
+        assert_eq!(
+            error_line("ERROR: 0:7: 'x' : undeclared identifier"),
+            Some(7)
+        );
+        assert_eq!(error_line("link failed"), None);
+
+        let source = "#version 460\nvoid main() {\n    foo = 1;\n}\n";
+        let error = ShaderError::compile(ShaderKind::Vertex, "0:3(5): error", source);
+        let ShaderError::Compile { excerpt, .. } = &error else {
+            panic!("expected a compile error");
+        };
+        assert_eq!(
+            excerpt,
+            "     1 | #version 460\n     2 | void main() {\n>    3 |     foo = 1;\n     4 | }"
+        );
+        assert!(error.to_string().contains(">    3 |     foo = 1;"));
+
+        assert_eq!(source_excerpt(source, "0:9(1): error"), None);
+    }
+}
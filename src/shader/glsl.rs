@@ -1,4 +1,7 @@
-use crate::shader::WriteValue;
+use crate::{
+    render::buffer::packed::PackedFormat,
+    shader::{WriteValue, std430::Std430},
+};
 
 pub trait GlslAlloc {
     fn to_glsl_alloc(&self) -> String;
@@ -143,6 +146,12 @@ copy_type_name_glsl!(glam::Mat3 => "mat3");
 copy_type_name_glsl!([(f32, f32, f32); 3] => "mat3");
 copy_type_name_glsl!(glam::Mat4 => "mat4");
 copy_type_name_glsl!([(f32, f32, f32, f32); 4] => "mat4");
+copy_type_name_glsl!(glam::IVec2 => "ivec2");
+copy_type_name_glsl!(glam::IVec3 => "ivec3");
+copy_type_name_glsl!(glam::IVec4 => "ivec4");
+copy_type_name_glsl!(glam::UVec2 => "uvec2");
+copy_type_name_glsl!(glam::UVec3 => "uvec3");
+copy_type_name_glsl!(glam::UVec4 => "uvec4");
 
 impl super::WriteValue for glam::Vec2 {
     fn write_value(&self, to: &mut impl std::fmt::Write) -> std::fmt::Result {
@@ -306,7 +315,7 @@ impl super::Inject for GlslAttribute {
 impl super::ShaderHeader for GlslAttribute {}
 
 #[derive(Clone, Debug)]
-pub struct GlslStruct(&'static str);
+pub struct GlslStruct(&'static str, Option<Std430>);
 
 impl std::fmt::Display for GlslStruct {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -316,12 +325,25 @@ impl std::fmt::Display for GlslStruct {
 
 impl GlslStruct {
     pub const fn new(value: &'static str) -> Self {
-        Self(value)
+        Self(value, None)
+    }
+
+    /// Attach the `std430` layout of the struct, checked against the Rust
+    /// types of the partitions using it, see
+    /// [`layout_buffer!`](crate::layout_buffer).
+    pub const fn with_std430(mut self, layout: Option<Std430>) -> Self {
+        self.1 = layout;
+        self
     }
 
     pub const fn as_str(&self) -> &str {
         self.0
     }
+
+    /// The `std430` layout of the struct, if known.
+    pub const fn std430(&self) -> Option<Std430> {
+        self.1
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -362,6 +384,87 @@ impl GlslLib {
     }
 }
 
+/// GLSL declarations generated at runtime, such as the interface of a buffer
+/// layout, see [`layout_buffer!`](crate::layout_buffer).
+///
+/// Struct definitions are emitted first, once each, followed by the library
+/// functions, once each, and the storage declarations in order.
+#[derive(Clone, Debug, Default)]
+pub struct GlslInclude {
+    structs: Vec<&'static str>,
+    libs: Vec<&'static str>,
+    source: String,
+}
+
+impl std::fmt::Display for GlslInclude {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for def in &self.structs {
+            write!(f, "{def}")?;
+        }
+        for lib in &self.libs {
+            write!(f, "{lib}")?;
+        }
+        write!(f, "{}", self.source)
+    }
+}
+
+impl GlslInclude {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define a struct, unless already defined by this include.
+    pub fn add_struct(&mut self, def: GlslStruct) {
+        if !self.structs.contains(&def.0) {
+            self.structs.push(def.0);
+        }
+    }
+
+    /// Define a function, unless already defined by this include.
+    pub fn add_lib(&mut self, lib: GlslLib) {
+        if !self.libs.contains(&lib.0) {
+            self.libs.push(lib.0);
+        }
+    }
+
+    /// Declare a storage block of an unsized array of `ty`, along with its
+    /// binding index as a `BINDING_<NAME>` constant, and a
+    /// `fetch_<name>(uint idx)` function returning the element at `idx`.
+    pub fn add_storage(&mut self, binding: u32, block: &str, ty: &str, name: &str) {
+        let fetch = format!("{name}[idx]");
+        self.write_storage(binding, block, ty, name, ty, &fetch);
+    }
+
+    /// Declare a storage block of an unsized array of packed `P` values, like
+    /// [`add_storage`](Self::add_storage), whose `fetch_<name>(uint idx)`
+    /// function returns the unpacked element at `idx`.
+    pub fn add_packed_storage<P: PackedFormat>(&mut self, binding: u32, block: &str, name: &str) {
+        self.add_lib(P::GLSL_UNPACK);
+        let fetch = format!("{}({name}[idx])", P::GLSL_UNPACK_FN);
+        self.write_storage(binding, block, P::GLSL_TYPE, name, P::GLSL_UNPACKED, &fetch);
+    }
+
+    fn write_storage(
+        &mut self,
+        binding: u32,
+        block: &str,
+        ty: &str,
+        name: &str,
+        fetch_ty: &str,
+        fetch: &str,
+    ) {
+        use std::fmt::Write;
+
+        let _ = write!(
+            self.source,
+            "const uint BINDING_{upper} = {binding}u;\n\
+             layout(std430, binding = {binding}) buffer {block}\n{{\n    {ty} {name}[];\n}};\n\
+             {fetch_ty} fetch_{name}(uint idx) {{\n    return {fetch};\n}}\n",
+            upper = name.to_uppercase(),
+        );
+    }
+}
+
 impl super::Inject for GlslStorage {
     fn inject_shader(&self, to: &mut impl std::fmt::Write) -> std::fmt::Result {
         writeln!(to, "{}", self.0)
@@ -384,6 +487,14 @@ impl super::ShaderHeader for GlslStorage {}
 
 impl super::ShaderHeader for GlslStruct {}
 
+impl super::Inject for GlslInclude {
+    fn inject_shader(&self, to: &mut impl std::fmt::Write) -> std::fmt::Result {
+        writeln!(to, "{self}")
+    }
+}
+
+impl super::ShaderHeader for GlslInclude {}
+
 impl super::ShaderBody for GlslLib {}
 
 /// Generate a Glsl struct from the given data structure.
@@ -395,8 +506,66 @@ impl super::ShaderBody for GlslLib {}
 /// and `GlslAlloc` traits:
 /// * `Glsl` will return a static Glsl struct declaration.
 /// * `GlslAlloc` requires a `String` allocation on the heap, and will
+///
+/// The `std430` layout of the struct is computed at compile time, see
+/// [`std430`](crate::shader::std430). With a `for` clause naming the
+/// `#[repr(C)]` Rust type uploaded as this struct, compilation fails if the
+/// offset of any field or the size of the type do not match that layout:
+///
+/// ```rust,ignore
+/// shader_glsl_struct! {
+///     struct Transform for PackedTransform {
+///         position: [f32; 4] => vec4;
+///         rotation: [f32; 4] => vec4;
+///     }
+/// }
+/// ```
 #[macro_export]
 macro_rules! shader_glsl_struct {
+    (
+        struct $name:ident for $repr:ty {
+            $(
+                $f_name:ident$([$rn:literal])?: $f_typ:ty => $f_lit:ident;
+            )+
+        }
+    ) => {
+        $crate::shader_glsl_struct! {
+            struct $name {
+                $(
+                    $f_name$([$rn])?: $f_typ => $f_lit;
+                )+
+            }
+        }
+
+        paste::paste! {
+            const _: () = {
+                let mut field = 0;
+                $(
+                    if let Some(offset) = [< $name GlslStruct >]::std430_offset(field) {
+                        assert!(
+                            offset == ::core::mem::offset_of!($repr, $f_name),
+                            concat!(
+                                "the offset of `", stringify!($repr), "::", stringify!($f_name),
+                                "` does not match the std430 layout of `", stringify!($name), "`"
+                            )
+                        );
+                    }
+                    field += 1;
+                )+
+                let _ = field;
+
+                if let Some(layout) = [< $name GlslStruct >]::std430_layout() {
+                    assert!(
+                        ::core::mem::size_of::<$repr>() == layout.stride(),
+                        concat!(
+                            "the size of `", stringify!($repr),
+                            "` does not match the std430 array stride of `", stringify!($name), "`"
+                        )
+                    );
+                }
+            };
+        }
+    };
     (
         struct $name:ident {
             $(
@@ -431,6 +600,32 @@ macro_rules! shader_glsl_struct {
                     $crate::shader::glsl::GlslStruct::new(
                         Self::as_definition_str()
                     )
+                    .with_std430(Self::std430_layout())
+                }
+
+                /// The `std430` layout of each field, in order.
+                const STD430_FIELDS: &'static [Option<$crate::shader::std430::Std430>] = &[
+                    $(
+                        {
+                            let layout = $crate::shader::std430::Std430::of(stringify!($f_lit));
+                            $(
+                                let layout = $crate::shader::std430::Std430::array(layout, $rn);
+                            )?
+                            layout
+                        },
+                    )+
+                ];
+
+                /// The `std430` layout of the struct, `None` if it contains
+                /// fields of unknown layout, such as nested structs.
+                pub const fn std430_layout() -> Option<$crate::shader::std430::Std430> {
+                    $crate::shader::std430::Std430::of_struct(Self::STD430_FIELDS)
+                }
+
+                /// The `std430` offset of the field at `index`, in declaration
+                /// order.
+                pub const fn std430_offset(index: usize) -> Option<usize> {
+                    $crate::shader::std430::Std430::offset_in(Self::STD430_FIELDS, index)
                 }
             }
 
@@ -627,4 +822,28 @@ mod tests {
 
         assert_eq!(TEST, generated.as_str());
     }
+
+    #[test]
+    fn shader_compose_glsl_include() {
+        const TEST: &str = "struct Pod {\n  vec4 pose;\n};\n\
+            const uint BINDING_PODS = 4u;\n\
+            layout(std430, binding = 4) buffer Pods\n{\n    Pod pods[];\n};\n\
+            Pod fetch_pods(uint idx) {\n    return pods[idx];\n}\n";
+
+        let mut include = GlslInclude::new();
+        include.add_struct(GlslStruct::new("struct Pod {\n  vec4 pose;\n};\n"));
+        include.add_storage(4, "Pods", "Pod", "pods");
+        include.add_struct(GlslStruct::new("struct Pod {\n  vec4 pose;\n};\n"));
+
+        assert_eq!(TEST, include.to_string());
+
+        use crate::render::buffer::packed::Half4;
+        include.add_packed_storage::<Half4>(5, "Scales", "scales");
+        let glsl = include.to_string();
+        assert!(glsl.contains("vec4 unpack_half4(uvec2 v)"));
+        assert!(glsl.contains("uvec2 scales[];"));
+        assert!(
+            glsl.contains("vec4 fetch_scales(uint idx) {\n    return unpack_half4(scales[idx]);")
+        );
+    }
 }
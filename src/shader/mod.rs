@@ -1,18 +1,23 @@
+pub mod builder;
+pub mod error;
 pub mod glsl;
+pub mod std430;
 pub mod uniform;
 
 pub use crate::shader_glsl_ssbo;
 use crate::state::data;
 
-use std::{hash::Hash, str::FromStr};
+use std::{cell::RefCell, hash::Hash, str::FromStr};
 
 use janus::{GlProperty, gl};
 use tracing::{Level, event};
 
+pub use builder::ShaderProgramBuilder;
+pub use error::ShaderError;
 pub use glsl::{
     Glsl, GlslAlloc, GlslAttribute, GlslLib, GlslStorage, GlslStruct, GlslType, ShadingVersion,
 };
-pub use uniform::GlslUniform;
+pub use uniform::{GlslUniform, UploadUniform, UploadUniformSlice};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ShaderKind {
@@ -58,7 +63,10 @@ impl janus::GlProperty for ShaderKind {
 
 pub fn generate_blank() -> ShaderHandle {
     let program = unsafe { janus::gl::CreateProgram() };
-    ShaderHandle { program }
+    ShaderHandle {
+        program,
+        uniforms: UniformCache::default(),
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -120,6 +128,42 @@ pub fn compile_shader_unit(
     })
 }
 
+/// Source of the fragment shader used in place of pixel shaders that fail to
+/// compile: a magenta and black checker pattern in screen space.
+pub const ERROR_PIXEL_SHADER_SOURCE: &str = r#"#version 450 core
+layout(location = 0) out vec4 error_color;
+
+void main() {
+    ivec2 cell = ivec2(gl_FragCoord.xy) / 8;
+    bool odd = ((cell.x + cell.y) & 1) == 1;
+    error_color = odd ? vec4(1.0, 0.0, 1.0, 1.0) : vec4(0.0, 0.0, 0.0, 1.0);
+}
+"#;
+
+/// Compile a shader unit, falling back to the [`ERROR_PIXEL_SHADER_SOURCE`]
+/// if a pixel shader fails to compile, so that the geometry of the program
+/// remains visible.
+///
+/// # Returns
+/// The compile log as error if a shader of any other kind fails to compile.
+pub fn compile_shader_unit_or_fallback(
+    source: &str,
+    shader_kind: ShaderKind,
+) -> Result<ShaderUnit, std::borrow::Cow<'_, str>> {
+    match compile_shader_unit(source, shader_kind) {
+        Err(_) if shader_kind == ShaderKind::Pixel => {
+            event!(
+                name: "shader.unit.fallback",
+                Level::WARN,
+                "Using the error pixel shader in place of the failed one"
+            );
+            compile_shader_unit(ERROR_PIXEL_SHADER_SOURCE, ShaderKind::Pixel)
+                .map_err(|log| std::borrow::Cow::Owned(log.into_owned()))
+        }
+        result => result,
+    }
+}
+
 pub fn attach_shader_units(shader: &impl ShaderProgram, units: &[ShaderUnit]) {
     let program = shader.shader_program();
     units
@@ -131,12 +175,53 @@ pub fn attach_shader_units(shader: &impl ShaderProgram, units: &[ShaderUnit]) {
 
 pub fn link_shader_program(shader: &impl ShaderProgram) {
     let program = shader.shader_program();
+    shader.invalidate_uniform_locations();
     unsafe {
         janus::gl::LinkProgram(program);
         janus::gl::ValidateProgram(program);
     }
 }
 
+/// Link the program of `shader`, made of `units`.
+///
+/// # Returns
+/// The link log as error if the program fails to link.
+pub fn link_shader_program_checked(
+    shader: &impl ShaderProgram,
+    units: &[ShaderUnit],
+) -> Result<(), ShaderError> {
+    let program = shader.shader_program();
+    shader.invalidate_uniform_locations();
+    let mut status = 0;
+    unsafe {
+        janus::gl::LinkProgram(program);
+        janus::gl::GetProgramiv(program, janus::gl::LINK_STATUS, &mut status);
+    }
+    if status as u8 == janus::gl::TRUE {
+        return Ok(());
+    }
+
+    let mut len = 0;
+    unsafe {
+        janus::gl::GetProgramiv(program, janus::gl::INFO_LOG_LENGTH, &mut len);
+    }
+    let mut log = vec![0u8; len.max(1) as usize];
+    let mut written = 0;
+    unsafe {
+        janus::gl::GetProgramInfoLog(program, len, &mut written, log.as_mut_ptr() as *mut _);
+    }
+    log.truncate(written.max(0) as usize);
+    let log = String::from_utf8_lossy(&log).into_owned();
+
+    let stages: Vec<_> = units.iter().map(|unit| unit.kind.as_str()).collect();
+    event!(
+        name: "shader.program.link",
+        Level::ERROR,
+        "Failed to link shader program (handle={program}, stages={stages:?}):\n{log}"
+    );
+    Err(ShaderError::Link { log })
+}
+
 pub fn delete_shader_units(units: &mut [ShaderUnit]) {
     units.iter_mut().for_each(|ShaderUnit { shader_obj, .. }| {
         unsafe {
@@ -228,6 +313,58 @@ impl std::ops::Deref for UniformLocation {
     }
 }
 
+/// The uniform locations of a program, by name, looked up on first use.
+///
+/// The cache takes no part in comparisons and hashing, so that handles stay
+/// equal to the program they refer to.
+#[derive(Debug, Default)]
+struct UniformCache(RefCell<rustc_hash::FxHashMap<Box<str>, UniformLocation>>);
+
+impl UniformCache {
+    /// The location of the uniform `name`, or the one returned by `lookup`
+    /// if it is not cached yet.
+    fn get_or_insert_with(
+        &self,
+        name: &str,
+        lookup: impl FnOnce() -> UniformLocation,
+    ) -> UniformLocation {
+        if let Some(&location) = self.0.borrow().get(name) {
+            return location;
+        }
+        let location = lookup();
+        self.0.borrow_mut().insert(name.into(), location);
+        location
+    }
+
+    fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+impl PartialEq for UniformCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for UniformCache {}
+
+impl PartialOrd for UniformCache {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for UniformCache {
+    fn cmp(&self, _other: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl Hash for UniformCache {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
 #[derive(Debug)]
 pub struct ShaderComposer {
     version: ShadingVersion,
@@ -299,6 +436,26 @@ impl ShaderComposer {
         self.header += "\n";
     }
 
+    /// Add a preprocessor definition to the shader's header.
+    pub fn define(&mut self, name: &str, value: impl std::fmt::Display) {
+        use std::fmt::Write;
+
+        let _ = writeln!(self.header, "#define {name} {value}");
+    }
+
+    /// Declare the workgroup size of a compute shader, which its source can
+    /// read from the `WORKGROUP_SIZE_X`, `WORKGROUP_SIZE_Y` and
+    /// `WORKGROUP_SIZE_Z` definitions.
+    pub fn set_workgroup_size(&mut self, size: [u32; 3]) {
+        self.define("WORKGROUP_SIZE_X", size[0]);
+        self.define("WORKGROUP_SIZE_Y", size[1]);
+        self.define("WORKGROUP_SIZE_Z", size[2]);
+        let _ = self.inject_header(&glsl::GlslWorkGroupSize::new(
+            "layout(local_size_x = WORKGROUP_SIZE_X, local_size_y = WORKGROUP_SIZE_Y, \
+             local_size_z = WORKGROUP_SIZE_Z) in;\n",
+        ));
+    }
+
     /// Add a uniform declaration to the shader's body.
     pub fn add_uniform(&mut self, uniform: GlslUniform) -> std::fmt::Result {
         uniform.inject_shader(&mut self.uniforms_section)
@@ -365,24 +522,218 @@ pub trait ShaderProgram: janus::GpuResource {
         self::unbind();
     }
 
+    /// The location of the uniform named `uniform_name`.
+    ///
+    /// Owned handles cache the locations, so that only the first lookup of
+    /// each uniform queries the GL implementation. Views query it every time.
     fn find_uniform_location(&self, uniform_name: &str) -> UniformLocation {
+        query_uniform_location(self.shader_program(), uniform_name)
+    }
+
+    /// Forget the cached uniform locations, which change when the program is
+    /// linked again.
+    fn invalidate_uniform_locations(&self) {}
+
+    /// Set the uniform named `name` of this program to `value`.
+    ///
+    /// The program must be bound. Uniforms which are not active in the
+    /// program are ignored by the GL implementation.
+    ///
+    /// The typed setters below, e.g. [`Self::uniform_vec3`], are shorthands
+    /// for this function.
+    fn uniform<U: UploadUniform>(&self, name: &str, value: U) {
+        value.upload(self.find_uniform_location(name));
+    }
+
+    /// Set the elements of the uniform array named `name` of this program,
+    /// starting from the first, to `values`.
+    ///
+    /// The program must be bound, see [`Self::uniform`].
+    fn uniform_slice<U: UploadUniformSlice>(&self, name: &str, values: &[U]) {
+        U::upload_slice(values, self.find_uniform_location(name));
+    }
+
+    fn uniform_f32(&self, name: &str, value: f32) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_i32(&self, name: &str, value: i32) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_u32(&self, name: &str, value: u32) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_bool(&self, name: &str, value: bool) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_vec2(&self, name: &str, value: glam::Vec2) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_vec3(&self, name: &str, value: glam::Vec3) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_vec4(&self, name: &str, value: glam::Vec4) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_ivec2(&self, name: &str, value: glam::IVec2) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_ivec3(&self, name: &str, value: glam::IVec3) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_ivec4(&self, name: &str, value: glam::IVec4) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_uvec2(&self, name: &str, value: glam::UVec2) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_uvec3(&self, name: &str, value: glam::UVec3) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_uvec4(&self, name: &str, value: glam::UVec4) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_mat2(&self, name: &str, value: glam::Mat2) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_mat3(&self, name: &str, value: glam::Mat3) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_mat4(&self, name: &str, value: glam::Mat4) {
+        self.uniform(name, value);
+    }
+
+    fn uniform_f32_slice(&self, name: &str, values: &[f32]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_i32_slice(&self, name: &str, values: &[i32]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_u32_slice(&self, name: &str, values: &[u32]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_vec2_slice(&self, name: &str, values: &[glam::Vec2]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_vec3_slice(&self, name: &str, values: &[glam::Vec3]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_vec4_slice(&self, name: &str, values: &[glam::Vec4]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_ivec2_slice(&self, name: &str, values: &[glam::IVec2]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_ivec3_slice(&self, name: &str, values: &[glam::IVec3]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_ivec4_slice(&self, name: &str, values: &[glam::IVec4]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_uvec2_slice(&self, name: &str, values: &[glam::UVec2]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_uvec3_slice(&self, name: &str, values: &[glam::UVec3]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_uvec4_slice(&self, name: &str, values: &[glam::UVec4]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_mat3_slice(&self, name: &str, values: &[glam::Mat3]) {
+        self.uniform_slice(name, values);
+    }
+
+    fn uniform_mat4_slice(&self, name: &str, values: &[glam::Mat4]) {
+        self.uniform_slice(name, values);
+    }
+
+    /// The minimum buffer size of the shader storage block named
+    /// `block_name`, as laid out by the GL implementation.
+    ///
+    /// For a block made of an unsized array, this is the array stride, to
+    /// compare against the size of the Rust type uploaded to it.
+    ///
+    /// # Returns
+    /// `None` if the program has no such active block.
+    fn storage_block_size(&self, block_name: &str) -> Option<usize> {
         let program = self.shader_program();
-        let c_string = std::ffi::CString::from_str(uniform_name).unwrap();
-        let location = unsafe { janus::gl::GetUniformLocation(program, c_string.as_ptr()) };
-        UniformLocation(location)
+        let c_string = std::ffi::CString::from_str(block_name).unwrap();
+        unsafe {
+            let index =
+                gl::GetProgramResourceIndex(program, gl::SHADER_STORAGE_BLOCK, c_string.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return None;
+            }
+
+            let mut size = 0;
+            gl::GetProgramResourceiv(
+                program,
+                gl::SHADER_STORAGE_BLOCK,
+                index,
+                1,
+                &gl::BUFFER_DATA_SIZE,
+                1,
+                std::ptr::null_mut(),
+                &mut size,
+            );
+            Some(size as usize)
+        }
     }
 }
 
+fn query_uniform_location(program: u32, uniform_name: &str) -> UniformLocation {
+    let c_string = std::ffi::CString::from_str(uniform_name).unwrap();
+    let location = unsafe { janus::gl::GetUniformLocation(program, c_string.as_ptr()) };
+    UniformLocation(location)
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct ShaderHandle {
     program: u32,
+    uniforms: UniformCache,
 }
 impl janus::GpuResource for ShaderHandle {
     fn resource_id(&self) -> u32 {
         self.program
     }
 }
-impl ShaderProgram for ShaderHandle {}
+impl ShaderProgram for ShaderHandle {
+    fn find_uniform_location(&self, uniform_name: &str) -> UniformLocation {
+        self.uniforms.get_or_insert_with(uniform_name, || {
+            query_uniform_location(self.program, uniform_name)
+        })
+    }
+
+    fn invalidate_uniform_locations(&self) {
+        self.uniforms.clear();
+    }
+}
 impl ShaderHandle {
     pub const fn view(&self) -> ShaderHandleView {
         ShaderHandleView {
@@ -429,7 +780,15 @@ impl janus::GpuResource for ComputeShaderHandle {
         self.inner.program
     }
 }
-impl ShaderProgram for ComputeShaderHandle {}
+impl ShaderProgram for ComputeShaderHandle {
+    fn find_uniform_location(&self, uniform_name: &str) -> UniformLocation {
+        self.inner.find_uniform_location(uniform_name)
+    }
+
+    fn invalidate_uniform_locations(&self) {
+        self.inner.invalidate_uniform_locations();
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ShaderHandleView {
@@ -701,7 +1060,35 @@ macro_rules! shader_glsl {
                     )?
                 )+
 
+                /// Compile and link the program.
+                ///
+                /// Pixel shaders which fail to compile are replaced by the
+                /// [`ERROR_PIXEL_SHADER_SOURCE`]($crate::shader::ERROR_PIXEL_SHADER_SOURCE).
+                ///
+                /// # Panics
+                /// If any other stage fails to compile, or the program fails to link.
                 pub fn new_compiled() -> Self {
+                    Self::compile_program_with($crate::shader::compile_shader_unit_or_fallback)
+                        .unwrap_or_else(|err| panic!("{err}"))
+                }
+
+                /// Compile and link the program, without falling back to the
+                /// error pixel shader, e.g. to keep the previous program when a
+                /// hot-reloaded source is invalid.
+                ///
+                /// # Returns
+                /// The error of the first stage which fails to compile, or of
+                /// the link.
+                pub fn try_new_compiled() -> Result<Self, $crate::shader::ShaderError> {
+                    Self::compile_program_with($crate::shader::compile_shader_unit)
+                }
+
+                fn compile_program_with(
+                    compile: for<'s> fn(
+                        &'s str,
+                        $crate::shader::ShaderKind,
+                    ) -> Result<$crate::shader::ShaderUnit, std::borrow::Cow<'s, str>>,
+                ) -> Result<Self, $crate::shader::ShaderError> {
                     let mut units = Vec::new();
 
                     {
@@ -797,17 +1184,21 @@ macro_rules! shader_glsl {
                             };
 
                             let full_source = composer.build();
-                            let shader_unit = $crate::shader::compile_shader_unit(&full_source, $kind)
-                                .expect(concat!("failed to compile ", stringify!($kind), " shader: see logs for details."));
-
-                            units.push(shader_unit);
+                            match compile(&full_source, $kind) {
+                                Ok(shader_unit) => units.push(shader_unit),
+                                Err(log) => {
+                                    $crate::shader::delete_shader_units(&mut units);
+                                    return Err($crate::shader::ShaderError::compile($kind, log, &full_source));
+                                }
+                            }
                         )+
                     }
 
                     let handle = $crate::shader::generate_blank();
                     $crate::shader::attach_shader_units(&handle, &units);
-                    $crate::shader::link_shader_program(&handle);
+                    let linked = $crate::shader::link_shader_program_checked(&handle, &units);
                     $crate::shader::delete_shader_units(&mut units);
+                    linked?;
 
                     $(
                         $(
@@ -822,7 +1213,7 @@ macro_rules! shader_glsl {
                         )?
                     )+
 
-                    Self {
+                    Ok(Self {
                         handle,
 
                         $(
@@ -837,7 +1228,7 @@ macro_rules! shader_glsl {
                                 )+
                             )?
                         )+
-                    }
+                    })
                 }
             }
         }
@@ -903,6 +1294,9 @@ macro_rules! shader_glsl_compute {
             }
 
             impl [< ComputeShader $name >] {
+                /// The workgroup size declared by the shader.
+                pub const WORKGROUP: [u32; 3] = [$wg_x, $wg_y, $wg_z];
+
                 pub fn bind(&self) {
                     self.handle.bind();
                 }
@@ -926,16 +1320,10 @@ macro_rules! shader_glsl_compute {
                 #[cfg(debug_assertions)]
                 pub fn build_sources() -> String {
                     let version = $crate::shader::ShadingVersion::core($ver);
+                    let workgroup = Self::WORKGROUP;
 
                     let mut composer = $crate::shader::ShaderComposer::new(version);
-                    {
-                        const WORK_GROUP_GLSL: &str = concat!(
-                            "layout(local_size_x = ", $wg_x,
-                            ", local_size_y = ", $wg_y,
-                            ", local_size_z = ", $wg_z, ") in;\n"
-                        );
-                        let _ = composer.inject_header(&$crate::shader::glsl::GlslWorkGroupSize::new(WORK_GROUP_GLSL));
-                    }
+                    composer.set_workgroup_size(workgroup);
 
                     $(
                         $(
@@ -976,19 +1364,37 @@ macro_rules! shader_glsl_compute {
                     )+
                 )?
 
+                /// Compile and link the program.
+                ///
+                /// # Panics
+                /// If the shader fails to compile or link.
                 pub fn new_compiled() -> Self {
+                    Self::try_new_compiled().unwrap_or_else(|err| panic!("{err}"))
+                }
+
+                /// Compile and link the program, e.g. to keep the previous
+                /// program when a hot-reloaded source is invalid.
+                ///
+                /// # Returns
+                /// The error of the compile or of the link.
+                pub fn try_new_compiled() -> Result<Self, $crate::shader::ShaderError> {
+                    Self::try_new_compiled_with_workgroup(Self::WORKGROUP)
+                }
+
+                /// Compile and link the program with a `workgroup` size other
+                /// than [`Self::WORKGROUP`], e.g. picked for the GL
+                /// implementation, see [`compute`](crate::render::compute).
+                ///
+                /// # Returns
+                /// The error of the compile or of the link.
+                pub fn try_new_compiled_with_workgroup(
+                    workgroup: [u32; 3],
+                ) -> Result<Self, $crate::shader::ShaderError> {
                     let version = $crate::shader::ShadingVersion::core($ver);
 
                     let mut composer = $crate::shader::ShaderComposer::new(version);
 
-                    {
-                        const WORK_GROUP_GLSL: &str = concat!(
-                            "layout(local_size_x = ", $wg_x,
-                            ", local_size_y = ", $wg_y,
-                            ", local_size_z = ", $wg_z, ") in;\n"
-                        );
-                        let _ = composer.inject_header(&$crate::shader::glsl::GlslWorkGroupSize::new(WORK_GROUP_GLSL));
-                    }
+                    composer.set_workgroup_size(workgroup);
 
                     $(
                         $(
@@ -1019,13 +1425,15 @@ macro_rules! shader_glsl_compute {
                     composer.set_source(indoc::indoc! { $src });
 
                     let full_source = composer.build();
-                    let shader_unit = $crate::shader::compile_shader_unit(&full_source, $crate::shader::ShaderKind::Compute)
-                        .expect(concat!("failed to compile Compute shader: see logs for details."));
+                    let kind = $crate::shader::ShaderKind::Compute;
+                    let shader_unit = $crate::shader::compile_shader_unit(&full_source, kind)
+                        .map_err(|log| $crate::shader::ShaderError::compile(kind, log, &full_source))?;
 
                     let handle = $crate::shader::ComputeShaderHandle::new($crate::shader::generate_blank());
                     $crate::shader::attach_shader_units(&handle, &[shader_unit]);
-                    $crate::shader::link_shader_program(&handle);
+                    let linked = $crate::shader::link_shader_program_checked(&handle, &[shader_unit]);
                     $crate::shader::delete_shader_units(&mut [shader_unit]);
+                    linked?;
 
                     $(
                         $(
@@ -1033,7 +1441,7 @@ macro_rules! shader_glsl_compute {
                         )+
                     )?
 
-                    Self {
+                    Ok(Self {
                         handle,
 
                         $(
@@ -1041,7 +1449,7 @@ macro_rules! shader_glsl_compute {
                                 [< location_ $u_gl_name _ $u_gl_type >],
                             )+
                         )?
-                    }
+                    })
                 }
             }
         }
@@ -1204,4 +1612,26 @@ mod tests {
 
         assert_eq!(sources[1].trim_end(), S1);
     }
+
+    #[test]
+    fn uniform_location_cache() {
+        let cache = UniformCache::default();
+        let lookups = std::cell::Cell::new(0);
+        let lookup = |name, location| {
+            cache.get_or_insert_with(name, || {
+                lookups.set(lookups.get() + 1);
+                UniformLocation(location)
+            })
+        };
+
+        assert_eq!(lookup("u_view", 2), UniformLocation(2));
+        assert_eq!(lookup("u_view", 5), UniformLocation(2));
+        assert_eq!(lookup("u_projection", -1), UniformLocation(-1));
+        assert_eq!(lookup("u_projection", 3), UniformLocation(-1));
+        assert_eq!(lookups.get(), 2);
+
+        cache.clear();
+        assert_eq!(lookup("u_view", 5), UniformLocation(5));
+        assert_eq!(lookups.get(), 3);
+    }
 }
@@ -0,0 +1,169 @@
+//! The `std430` memory layout of GLSL types, evaluated at compile time.
+//!
+//! Rust `#[repr(C)]` types uploaded to shader storage blocks must match the
+//! `std430` layout of their GLSL counterpart. The classic mistake is a `vec3`,
+//! which is aligned to 16 bytes in GLSL but to 4 bytes as a `[f32; 3]`.
+//!
+//! [`shader_glsl_struct!`](crate::shader_glsl_struct) computes the layout of
+//! the structs it declares, and asserts at compile time that the field
+//! offsets and size of a Rust type match it, with the `for` clause:
+//!
+//! ```rust,ignore
+//! shader_glsl_struct! {
+//!     struct Transform for PackedTransform {
+//!         position: [f32; 4] => vec4;
+//!         rotation: [f32; 4] => vec4;
+//!     }
+//! }
+//! ```
+//!
+//! Partitions of a [`layout_buffer!`](crate::layout_buffer) declaring their
+//! GLSL type are checked in the same way, and can be verified against a
+//! linked shader program with the generated `verify_program` function, see
+//! [`ShaderProgram::storage_block_size`](super::ShaderProgram::storage_block_size).
+
+/// The size and base alignment of a GLSL type in the `std430` layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Std430 {
+    pub size: usize,
+    pub align: usize,
+}
+
+/// The layout of the scalar, vector and matrix types, with matrices stored
+/// in column-major order.
+const BASIC_TYPES: &[(&str, Std430)] = &[
+    ("float", Std430::new(4, 4)),
+    ("int", Std430::new(4, 4)),
+    ("uint", Std430::new(4, 4)),
+    ("bool", Std430::new(4, 4)),
+    ("vec2", Std430::new(8, 8)),
+    ("ivec2", Std430::new(8, 8)),
+    ("uvec2", Std430::new(8, 8)),
+    ("bvec2", Std430::new(8, 8)),
+    ("vec3", Std430::new(12, 16)),
+    ("ivec3", Std430::new(12, 16)),
+    ("uvec3", Std430::new(12, 16)),
+    ("bvec3", Std430::new(12, 16)),
+    ("vec4", Std430::new(16, 16)),
+    ("ivec4", Std430::new(16, 16)),
+    ("uvec4", Std430::new(16, 16)),
+    ("bvec4", Std430::new(16, 16)),
+    ("mat2", Std430::new(16, 8)),
+    ("mat3", Std430::new(48, 16)),
+    ("mat4", Std430::new(64, 16)),
+];
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+impl Std430 {
+    pub const fn new(size: usize, align: usize) -> Self {
+        Self { size, align }
+    }
+
+    /// The layout of the GLSL scalar, vector or matrix type named
+    /// `glsl_type`.
+    ///
+    /// # Returns
+    /// `None` for other types, such as structs and double precision types.
+    pub const fn of(glsl_type: &str) -> Option<Self> {
+        let mut i = 0;
+        while i < BASIC_TYPES.len() {
+            if str_eq(BASIC_TYPES[i].0, glsl_type) {
+                return Some(BASIC_TYPES[i].1);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// The distance between consecutive elements of an array of this type.
+    pub const fn stride(self) -> usize {
+        self.size.next_multiple_of(self.align)
+    }
+
+    /// The layout of an array of `len` elements of `element`.
+    pub const fn array(element: Option<Self>, len: usize) -> Option<Self> {
+        match element {
+            Some(element) => Some(Self::new(element.stride() * len, element.align)),
+            None => None,
+        }
+    }
+
+    /// The layout of a struct made of `fields`, in order.
+    ///
+    /// # Returns
+    /// `None` if the layout of any field is unknown.
+    pub const fn of_struct(fields: &[Option<Self>]) -> Option<Self> {
+        let mut size = 0usize;
+        let mut align = 1;
+        let mut i = 0;
+        while i < fields.len() {
+            let Some(field) = fields[i] else {
+                return None;
+            };
+            size = size.next_multiple_of(field.align) + field.size;
+            if field.align > align {
+                align = field.align;
+            }
+            i += 1;
+        }
+        Some(Self::new(size.next_multiple_of(align), align))
+    }
+
+    /// The offset of the field at `index` in a struct made of `fields`.
+    ///
+    /// # Returns
+    /// `None` if the layout of the field or of any previous one is unknown.
+    pub const fn offset_in(fields: &[Option<Self>], index: usize) -> Option<usize> {
+        let mut offset = 0usize;
+        let mut i = 0;
+        while i <= index && i < fields.len() {
+            let Some(field) = fields[i] else {
+                return None;
+            };
+            offset = offset.next_multiple_of(field.align);
+            if i == index {
+                return Some(offset);
+            }
+            offset += field.size;
+            i += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn std430_struct_layout() {
+        // struct { vec3 a; float b; vec3 c; vec2 d[2]; }
+        let fields = [
+            Std430::of("vec3"),
+            Std430::of("float"),
+            Std430::of("vec3"),
+            Std430::array(Std430::of("vec2"), 2),
+        ];
+        let offsets = [0, 1, 2, 3].map(|i| Std430::offset_in(&fields, i));
+        assert_eq!(offsets, [Some(0), Some(12), Some(16), Some(32)]);
+        assert_eq!(Std430::of_struct(&fields), Some(Std430::new(48, 16)));
+
+        assert_eq!(Std430::of("vec3").map(Std430::stride), Some(16));
+        assert_eq!(Std430::of("Transform"), None);
+        assert_eq!(Std430::of_struct(&[Std430::of("dvec4")]), None);
+    }
+}
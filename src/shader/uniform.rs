@@ -1,9 +1,112 @@
 use crate::shader::{UniformLocation, glsl::Glsl};
 
+/// A value which can be set as a uniform of the bound program.
+///
+/// See [`ShaderProgram::uniform`](super::ShaderProgram::uniform).
 pub trait UploadUniform: Glsl {
     fn upload(&self, location: UniformLocation);
 }
 
+/// A value which can be set as an element of a uniform array of the bound
+/// program.
+///
+/// See [`ShaderProgram::uniform_slice`](super::ShaderProgram::uniform_slice).
+pub trait UploadUniformSlice: UploadUniform + Sized {
+    /// Upload `values` to the array starting at `location`.
+    fn upload_slice(values: &[Self], location: UniformLocation);
+}
+
+impl UploadUniform for f32 {
+    fn upload(&self, location: UniformLocation) {
+        unsafe {
+            janus::gl::Uniform1f(*location, *self);
+        }
+    }
+}
+
+macro_rules! upload_uniform_slice {
+    ($($ty:ty => $uniform_v:ident($scalar:ty; $n:literal);)+) => {
+        $(
+            impl UploadUniformSlice for $ty {
+                fn upload_slice(values: &[Self], location: UniformLocation) {
+                    const {
+                        assert!(std::mem::size_of::<$ty>() == $n * std::mem::size_of::<$scalar>());
+                    }
+                    unsafe {
+                        janus::gl::$uniform_v(
+                            *location,
+                            values.len() as i32,
+                            values.as_ptr() as *const $scalar,
+                        );
+                    }
+                }
+            }
+        )+
+    };
+    ($($ty:ty => matrix $uniform_v:ident($n:literal);)+) => {
+        $(
+            impl UploadUniformSlice for $ty {
+                fn upload_slice(values: &[Self], location: UniformLocation) {
+                    const {
+                        assert!(std::mem::size_of::<$ty>() == $n * std::mem::size_of::<f32>());
+                    }
+                    unsafe {
+                        janus::gl::$uniform_v(
+                            *location,
+                            values.len() as i32,
+                            janus::gl::FALSE,
+                            values.as_ptr() as *const f32,
+                        );
+                    }
+                }
+            }
+        )+
+    };
+}
+
+upload_uniform_slice! {
+    f32 => Uniform1fv(f32; 1);
+    i32 => Uniform1iv(i32; 1);
+    u32 => Uniform1uiv(u32; 1);
+    glam::Vec2 => Uniform2fv(f32; 2);
+    glam::Vec3 => Uniform3fv(f32; 3);
+    glam::Vec4 => Uniform4fv(f32; 4);
+    glam::IVec2 => Uniform2iv(i32; 2);
+    glam::IVec3 => Uniform3iv(i32; 3);
+    glam::IVec4 => Uniform4iv(i32; 4);
+    glam::UVec2 => Uniform2uiv(u32; 2);
+    glam::UVec3 => Uniform3uiv(u32; 3);
+    glam::UVec4 => Uniform4uiv(u32; 4);
+}
+
+upload_uniform_slice! {
+    glam::Mat2 => matrix UniformMatrix2fv(4);
+    glam::Mat3 => matrix UniformMatrix3fv(9);
+    glam::Mat4 => matrix UniformMatrix4fv(16);
+}
+
+/// Integer vectors are uploaded as single element arrays.
+macro_rules! upload_uniform_from_slice {
+    ($($ty:ty),+) => {
+        $(
+            impl UploadUniform for $ty {
+                fn upload(&self, location: UniformLocation) {
+                    UploadUniformSlice::upload_slice(std::slice::from_ref(self), location);
+                }
+            }
+        )+
+    };
+}
+
+upload_uniform_from_slice!(
+    glam::IVec2,
+    glam::IVec3,
+    glam::IVec4,
+    glam::UVec2,
+    glam::UVec3,
+    glam::UVec4
+);
+
 impl UploadUniform for glam::Vec2 {
     fn upload(&self, location: UniformLocation) {
         unsafe {
@@ -185,4 +288,11 @@ mod tests {
         let uniform = shader_glsl_uniform!(projection: mat4);
         assert_eq!(TEST, uniform.as_str());
     }
+
+    #[test]
+    fn uniform_vector_types() {
+        assert_eq!(<glam::IVec3 as Glsl>::to_glsl(), "ivec3");
+        assert_eq!(<glam::UVec2 as Glsl>::to_glsl(), "uvec2");
+        assert_eq!(<f32 as Glsl>::to_glsl(), "float");
+    }
 }
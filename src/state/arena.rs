@@ -0,0 +1,247 @@
+use std::{
+    alloc::Layout,
+    cell::{Cell, UnsafeCell},
+    ptr::NonNull,
+};
+
+/// The alignment of every chunk of a [`StagingArena`].
+///
+/// This is the maximum alignment supported for types allocated in the arena,
+/// which is sufficient for all `glam` SIMD types.
+const CHUNK_ALIGN: usize = 16;
+
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+struct Chunk {
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+impl Chunk {
+    fn new(size: usize) -> Self {
+        let layout = Layout::from_size_align(size, CHUNK_ALIGN).expect("invalid arena chunk size");
+        // SAFETY: the chunk size is never zero, see StagingArena::with_chunk_size
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        Self { ptr, size }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        let layout = Layout::from_size_align(self.size, CHUNK_ALIGN).unwrap();
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), layout) };
+    }
+}
+
+/// A bump allocator for transient, per-frame upload data.
+///
+/// Derived data that only lives for a single upload (such as interpolation
+/// sets or visibility lists) can be allocated here instead of in dedicated
+/// `Vec`s, avoiding allocator churn at high entity counts.
+///
+/// Allocations are made through a shared reference, so multiple slices can
+/// be alive at the same time. All allocations are invalidated at once with
+/// [`StagingArena::reset`], which the [`State`] calls before every
+/// [`StateHandler::upload_gpu`].
+///
+/// The arena grows by allocating new chunks, existing allocations are never
+/// moved. On reset, if more than one chunk was in use, all chunks are
+/// coalesced into a single chunk large enough to fit the whole previous
+/// frame, so steady-state frames never allocate.
+///
+/// Only `Copy` types can be allocated, as the arena never runs destructors.
+///
+/// [`State`]: crate::state::State
+/// [`StateHandler::upload_gpu`]: crate::StateHandler::upload_gpu
+#[derive(Debug)]
+pub struct StagingArena {
+    chunks: UnsafeCell<Vec<Chunk>>,
+    head: Cell<usize>,
+    used: Cell<usize>,
+    chunk_size: usize,
+}
+
+// The arena is only ever accessed through the simulation thread, but it may
+// be moved along with its owning State.
+unsafe impl Send for StagingArena {}
+
+impl Default for StagingArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StagingArena {
+    pub fn new() -> Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Create an arena whose chunks are at least `chunk_size` bytes long.
+    ///
+    /// No memory is allocated until the first allocation.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        Self {
+            chunks: UnsafeCell::new(Vec::new()),
+            head: Cell::new(0),
+            used: Cell::new(0),
+            chunk_size: chunk_size.max(CHUNK_ALIGN),
+        }
+    }
+
+    /// The total amount of bytes allocated since the last reset, including
+    /// alignment padding.
+    pub fn allocated_bytes(&self) -> usize {
+        self.used.get()
+    }
+
+    /// The total amount of bytes reserved by the arena across all chunks.
+    pub fn capacity(&self) -> usize {
+        let chunks = unsafe { &*self.chunks.get() };
+        chunks.iter().map(|chunk| chunk.size).sum()
+    }
+
+    /// Invalidate all allocations.
+    ///
+    /// If the previous frame required more than one chunk, all chunks are
+    /// replaced by a single chunk that fits all of them.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let total = chunks.iter().map(|chunk| chunk.size).sum();
+            chunks.clear();
+            chunks.push(Chunk::new(total));
+        }
+        self.head.set(0);
+        self.used.set(0);
+    }
+
+    /// Allocate a slice of `len` elements, each initialised to `value`.
+    ///
+    /// # Panics
+    /// If the alignment of `T` is greater than 16 bytes.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_fill<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        let ptr = self.alloc_raw(Layout::array::<T>(len).expect("arena allocation overflow"));
+        unsafe {
+            let ptr = ptr.as_ptr() as *mut T;
+            for i in 0..len {
+                ptr.add(i).write(value);
+            }
+            std::slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// Allocate a copy of `src`.
+    ///
+    /// # Panics
+    /// If the alignment of `T` is greater than 16 bytes.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let len = src.len();
+        let ptr = self.alloc_raw(Layout::array::<T>(len).expect("arena allocation overflow"));
+        unsafe {
+            let ptr = ptr.as_ptr() as *mut T;
+            std::ptr::copy_nonoverlapping(src.as_ptr(), ptr, len);
+            std::slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// Allocate a slice from the elements yielded by `iter`.
+    ///
+    /// If `iter` yields less elements than its reported length, the returned
+    /// slice is shortened accordingly.
+    ///
+    /// # Panics
+    /// If the alignment of `T` is greater than 16 bytes.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_from_iter<T, I>(&self, iter: I) -> &mut [T]
+    where
+        T: Copy,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        let cap = iter.len();
+        let ptr = self.alloc_raw(Layout::array::<T>(cap).expect("arena allocation overflow"));
+        unsafe {
+            let ptr = ptr.as_ptr() as *mut T;
+            let mut len = 0;
+            for value in iter.take(cap) {
+                ptr.add(len).write(value);
+                len += 1;
+            }
+            std::slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    fn alloc_raw(&self, layout: Layout) -> NonNull<u8> {
+        assert!(
+            layout.align() <= CHUNK_ALIGN,
+            "staging arena only supports alignments up to {CHUNK_ALIGN} bytes, got {}",
+            layout.align()
+        );
+
+        if layout.size() == 0 {
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+
+        // SAFETY: chunks are never removed or moved while allocations are
+        // alive, as that requires exclusive access through reset.
+        let chunks = unsafe { &mut *self.chunks.get() };
+
+        let head = self.head.get();
+        let aligned = (head + layout.align() - 1) & !(layout.align() - 1);
+        let fits = chunks
+            .last()
+            .is_some_and(|chunk| aligned + layout.size() <= chunk.size);
+
+        let (chunk, offset) = if fits {
+            (chunks.last().unwrap(), aligned)
+        } else {
+            let size = self.chunk_size.max(layout.size());
+            chunks.push(Chunk::new(size));
+            (chunks.last().unwrap(), 0)
+        };
+
+        let end = offset + layout.size();
+        self.used.set(self.used.get() + (end - head.min(offset)));
+        self.head.set(end);
+
+        unsafe { NonNull::new_unchecked(chunk.ptr.as_ptr().add(offset)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_alloc_and_reset() {
+        let mut arena = StagingArena::with_chunk_size(64);
+
+        let a = arena.alloc_slice_fill(4, 1u32);
+        let b = arena.alloc_slice_copy(&[glam::Vec4::ONE; 8]);
+        let c = arena.alloc_slice_from_iter((0..5u8).map(|i| i * 2));
+
+        assert_eq!(a, &[1, 1, 1, 1]);
+        assert!(b.iter().all(|v| *v == glam::Vec4::ONE));
+        assert_eq!(b.as_ptr() as usize % align_of::<glam::Vec4>(), 0);
+        assert_eq!(c, &[0, 2, 4, 6, 8]);
+
+        a[0] = 7;
+        assert_eq!(a[0], 7);
+        assert!(arena.capacity() > 64);
+
+        let previous = arena.capacity();
+        arena.reset();
+        assert_eq!(arena.allocated_bytes(), 0);
+        assert_eq!(arena.capacity(), previous);
+
+        // the coalesced chunk fits the whole previous frame
+        let _ = arena.alloc_slice_fill(4, 1u32);
+        let _ = arena.alloc_slice_copy(&[glam::Vec4::ONE; 8]);
+        assert_eq!(arena.capacity(), previous);
+    }
+}
@@ -0,0 +1,292 @@
+//! Benchmark runs over a scripted camera path.
+//!
+//! Performance changes are only comparable when measured over the same
+//! frames: a benchmark plays a [`CameraPath`] for a fixed duration, records
+//! every frame time with a [`BenchRecorder`], and summarises them in a
+//! [`BenchReport`] with the average and the 1% and 0.1% lows, which expose
+//! hitches that averages hide.
+//!
+//! # Example
+//! ```rust,ignore
+//! let path = CameraPath::orbit(Vec3::ZERO, 200.0, 50.0, Duration::from_secs(30));
+//! let mut recorder = BenchRecorder::new(path.duration());
+//!
+//! // on the simulation thread, every frame
+//! viewpoint.publish(path.sample(recorder.elapsed()));
+//!
+//! // on the render thread, every frame
+//! recorder.frame(Instant::now());
+//! if recorder.is_finished() {
+//!     recorder.report().write_to("bench.txt")?;
+//! }
+//! ```
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use glam::{Quat, Vec3};
+
+use crate::state::camera::ViewPoint;
+
+/// A keyframe of a [`CameraPath`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraKey {
+    /// The time of the keyframe since the start of the path.
+    pub time: Duration,
+    pub position: Vec3,
+    pub orientation: Quat,
+}
+
+impl CameraKey {
+    /// A keyframe at `position`, looking at `target`.
+    pub fn looking_at(time: Duration, position: Vec3, target: Vec3) -> Self {
+        let forward = (target - position).normalize_or(Vec3::NEG_Z);
+        let orientation = Quat::from_rotation_arc(Vec3::NEG_Z, forward);
+        Self {
+            time,
+            position,
+            orientation,
+        }
+    }
+}
+
+/// A predetermined camera motion, interpolated between keyframes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+}
+
+impl CameraPath {
+    /// A path through `keys`, sorted by time.
+    ///
+    /// # Panics
+    /// If `keys` is empty.
+    pub fn new(mut keys: Vec<CameraKey>) -> Self {
+        assert!(!keys.is_empty(), "camera paths need at least one keyframe");
+        keys.sort_by_key(|key| key.time);
+        Self { keys }
+    }
+
+    /// A full circle of `radius` around `center`, `height` units above it,
+    /// looking at the center, over `duration`.
+    pub fn orbit(center: Vec3, radius: f32, height: f32, duration: Duration) -> Self {
+        const STEPS: u32 = 64;
+        let keys = (0..=STEPS)
+            .map(|i| {
+                let t = i as f32 / STEPS as f32;
+                let angle = t * std::f32::consts::TAU;
+                let offset = Vec3::new(angle.sin() * radius, height, angle.cos() * radius);
+                CameraKey::looking_at(duration.mul_f32(t), center + offset, center)
+            })
+            .collect();
+        Self { keys }
+    }
+
+    /// The time of the last keyframe.
+    pub fn duration(&self) -> Duration {
+        self.keys[self.keys.len() - 1].time
+    }
+
+    pub fn keys(&self) -> &[CameraKey] {
+        &self.keys
+    }
+
+    /// The viewpoint at `time`, clamped to the duration of the path.
+    pub fn sample(&self, time: Duration) -> ViewPoint {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        let (position, orientation) =
+            match (self.keys.get(next.wrapping_sub(1)), self.keys.get(next)) {
+                (Some(a), Some(b)) => {
+                    let span = (b.time - a.time).as_secs_f32();
+                    let t = (time - a.time).as_secs_f32() / span;
+                    (
+                        a.position.lerp(b.position, t),
+                        a.orientation.slerp(b.orientation, t),
+                    )
+                }
+                (Some(key), None) | (None, Some(key)) => (key.position, key.orientation),
+                (None, None) => unreachable!("camera paths are never empty"),
+            };
+
+        ViewPoint {
+            orientation,
+            position,
+            ..Default::default()
+        }
+    }
+}
+
+/// Records frame times for the duration of a benchmark.
+///
+/// The first frame only starts the run.
+#[derive(Clone, Debug)]
+pub struct BenchRecorder {
+    duration: Duration,
+    start: Option<Instant>,
+    last: Option<Instant>,
+    frame_times: Vec<Duration>,
+}
+
+impl BenchRecorder {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            start: None,
+            last: None,
+            frame_times: Vec::new(),
+        }
+    }
+
+    /// Record a frame ending at `now`.
+    ///
+    /// Frames are ignored once the run is finished.
+    pub fn frame(&mut self, now: Instant) {
+        if self.is_finished() {
+            return;
+        }
+        self.start.get_or_insert(now);
+        if let Some(last) = self.last.replace(now) {
+            self.frame_times.push(now - last);
+        }
+    }
+
+    /// The time since the first frame.
+    pub fn elapsed(&self) -> Duration {
+        self.last
+            .zip(self.start)
+            .map(|(last, start)| last - start)
+            .unwrap_or_default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed() >= self.duration
+    }
+
+    pub fn frame_times(&self) -> &[Duration] {
+        &self.frame_times
+    }
+
+    /// Summarise the frames recorded so far.
+    pub fn report(&self) -> BenchReport {
+        BenchReport::from_frame_times(&self.frame_times)
+    }
+}
+
+/// A summary of the frame times of a benchmark run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BenchReport {
+    pub frames: usize,
+    pub duration: Duration,
+    pub average: Duration,
+    pub median: Duration,
+    /// The average of the slowest 1% of frames.
+    pub low_1: Duration,
+    /// The average of the slowest 0.1% of frames.
+    pub low_0_1: Duration,
+    pub best: Duration,
+    pub worst: Duration,
+}
+
+impl BenchReport {
+    pub fn from_frame_times(frame_times: &[Duration]) -> Self {
+        if frame_times.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted = frame_times.to_vec();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+
+        let duration: Duration = sorted.iter().sum();
+        let slowest = |fraction: f64| {
+            let count = ((sorted.len() as f64 * fraction).ceil() as usize).max(1);
+            sorted[..count].iter().sum::<Duration>() / count as u32
+        };
+
+        Self {
+            frames: sorted.len(),
+            duration,
+            average: duration / sorted.len() as u32,
+            median: sorted[sorted.len() / 2],
+            low_1: slowest(0.01),
+            low_0_1: slowest(0.001),
+            best: sorted[sorted.len() - 1],
+            worst: sorted[0],
+        }
+    }
+
+    /// The average frame rate.
+    pub fn fps(&self) -> f64 {
+        if self.average.is_zero() {
+            return 0.0;
+        }
+        1.0 / self.average.as_secs_f64()
+    }
+
+    /// Write the report, as displayed, to the file at `path`.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fps = |time: Duration| {
+            if time.is_zero() {
+                0.0
+            } else {
+                1.0 / time.as_secs_f64()
+            }
+        };
+        writeln!(
+            f,
+            "frames: {} over {:.2?} ({:.1} fps)",
+            self.frames,
+            self.duration,
+            self.fps()
+        )?;
+        writeln!(f, "average:  {:.2?}", self.average)?;
+        writeln!(f, "median:   {:.2?}", self.median)?;
+        writeln!(
+            f,
+            "1% low:   {:.2?} ({:.1} fps)",
+            self.low_1,
+            fps(self.low_1)
+        )?;
+        writeln!(
+            f,
+            "0.1% low: {:.2?} ({:.1} fps)",
+            self.low_0_1,
+            fps(self.low_0_1)
+        )?;
+        write!(f, "best: {:.2?} | worst: {:.2?}", self.best, self.worst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_and_path() {
+        let ms = Duration::from_millis;
+        let mut times = vec![ms(10); 198];
+        times.extend([ms(40), ms(50)]);
+
+        let report = BenchReport::from_frame_times(&times);
+        assert_eq!(report.frames, 200);
+        assert_eq!(report.median, ms(10));
+        assert_eq!(report.low_1, ms(45));
+        assert_eq!(report.low_0_1, ms(50));
+        assert_eq!(report.worst, ms(50));
+
+        let path = CameraPath::new(vec![
+            CameraKey::looking_at(ms(1000), Vec3::X * 10.0, Vec3::ZERO),
+            CameraKey::looking_at(ms(0), Vec3::ZERO, Vec3::NEG_Z),
+        ]);
+        assert_eq!(path.duration(), ms(1000));
+        assert_eq!(path.sample(ms(500)).position, Vec3::X * 5.0);
+        assert_eq!(path.sample(ms(2000)).position, Vec3::X * 10.0);
+    }
+}
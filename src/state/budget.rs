@@ -0,0 +1,230 @@
+//! CPU time budgets of the systems of the fixed step.
+//!
+//! The library runs a single [`fixed_step`](crate::StateHandler::fixed_step)
+//! per tick, and does not schedule systems itself: handlers call their own
+//! systems (physics, AI, animation, ...) from it. [`SystemBudgets`] times each
+//! of them, by name, and emits a `state.budget` warning when a system exceeds
+//! its budget for several consecutive runs, so that the system blowing the
+//! tick can be found without a profiler. Single spikes, e.g. from the OS
+//! descheduling the thread, are ignored.
+//!
+//! Each run is also a profiler zone, see [`zone`](crate::zone).
+//!
+//! # Example
+//! ```rust,ignore
+//! // at startup
+//! let mut budgets = SystemBudgets::new().with_default_budget(Duration::from_millis(2));
+//! budgets.set_budget("physics", Duration::from_millis(4));
+//!
+//! // in StateHandler::fixed_step
+//! self.budgets.run("physics", || self.physics.step(delta));
+//! self.budgets.run("ai", || self.ai.think(&self.world));
+//! ```
+
+use std::time::{Duration, Instant};
+
+/// The default amount of consecutive runs over budget before warning.
+pub const DEFAULT_STRIKES: u32 = 3;
+
+/// The timing of a system, see [`SystemBudgets::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemStats {
+    pub name: &'static str,
+    pub budget: Option<Duration>,
+    /// The CPU time of the last run.
+    pub last: Duration,
+    /// The longest CPU time of a run.
+    pub max: Duration,
+    pub total: Duration,
+    pub runs: u64,
+    /// The runs exceeding the budget.
+    pub overruns: u64,
+}
+
+impl SystemStats {
+    /// The average CPU time of a run.
+    pub fn average(&self) -> Duration {
+        match self.runs {
+            0 => Duration::ZERO,
+            runs => Duration::from_nanos((self.total.as_nanos() / runs as u128) as u64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct System {
+    stats: SystemStats,
+    /// The consecutive runs exceeding the budget.
+    streak: u32,
+}
+
+/// Per-system CPU time tracking with budget warnings, see the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct SystemBudgets {
+    systems: Vec<System>,
+    default_budget: Option<Duration>,
+    strikes: u32,
+}
+
+impl Default for SystemBudgets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemBudgets {
+    /// No budget, and warnings after [`DEFAULT_STRIKES`] consecutive
+    /// overruns.
+    pub fn new() -> Self {
+        Self {
+            systems: Vec::new(),
+            default_budget: None,
+            strikes: DEFAULT_STRIKES,
+        }
+    }
+
+    /// The budget of the systems without their own, see
+    /// [`Self::set_budget`].
+    pub fn with_default_budget(mut self, budget: Duration) -> Self {
+        self.default_budget = Some(budget);
+        self
+    }
+
+    /// Warn after `strikes` consecutive runs over budget, at least one.
+    pub fn with_strikes(mut self, strikes: u32) -> Self {
+        self.strikes = strikes.max(1);
+        self
+    }
+
+    /// Set the budget of the system `name`.
+    pub fn set_budget(&mut self, name: &'static str, budget: Duration) {
+        self.system(name).stats.budget = Some(budget);
+    }
+
+    /// Run the system `name`, recording its CPU time.
+    pub fn run<R>(&mut self, name: &'static str, system: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = {
+            let _zone = crate::zone::zone(name);
+            system()
+        };
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Record a run of the system `name` which took `elapsed` CPU time, for
+    /// systems timed by the handler itself.
+    ///
+    /// # Returns
+    /// Whether the run completed a streak of overruns, and a warning was
+    /// emitted.
+    pub fn record(&mut self, name: &'static str, elapsed: Duration) -> bool {
+        let (default_budget, strikes) = (self.default_budget, self.strikes);
+        let system = self.system(name);
+        let stats = &mut system.stats;
+        stats.last = elapsed;
+        stats.max = stats.max.max(elapsed);
+        stats.total += elapsed;
+        stats.runs += 1;
+
+        let Some(budget) = stats.budget.or(default_budget) else {
+            return false;
+        };
+        if elapsed <= budget {
+            system.streak = 0;
+            return false;
+        }
+        stats.overruns += 1;
+        system.streak += 1;
+        // warn once per streak
+        if system.streak != strikes {
+            return false;
+        }
+
+        use tracing::Level;
+        tracing::event!(
+            name: "state.budget",
+            Level::WARN,
+            "system {name} exceeded its budget of {budget:?} for {strikes} consecutive runs, last run took {elapsed:?} (average {:?})",
+            stats.average()
+        );
+        true
+    }
+
+    /// The timing of the system `name`, if it ran.
+    pub fn stats(&self, name: &str) -> Option<&SystemStats> {
+        self.systems
+            .iter()
+            .find(|system| system.stats.name == name)
+            .map(|system| &system.stats)
+    }
+
+    /// The timing of every system, in order of their first run.
+    pub fn iter(&self) -> impl Iterator<Item = &SystemStats> {
+        self.systems.iter().map(|system| &system.stats)
+    }
+
+    /// Clear the timings of every system, keeping their budgets.
+    pub fn reset(&mut self) {
+        for system in &mut self.systems {
+            system.stats = SystemStats {
+                name: system.stats.name,
+                budget: system.stats.budget,
+                ..Default::default()
+            };
+            system.streak = 0;
+        }
+    }
+
+    fn system(&mut self, name: &'static str) -> &mut System {
+        let index = match self.systems.iter().position(|s| s.stats.name == name) {
+            Some(index) => index,
+            None => {
+                self.systems.push(System {
+                    stats: SystemStats {
+                        name,
+                        ..Default::default()
+                    },
+                    streak: 0,
+                });
+                self.systems.len() - 1
+            }
+        };
+        &mut self.systems[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_streaks() {
+        let ms = Duration::from_millis;
+        let mut budgets = SystemBudgets::new()
+            .with_default_budget(ms(2))
+            .with_strikes(2);
+        budgets.set_budget("physics", ms(4));
+
+        // single spikes are ignored, streaks warn once
+        let warnings = [1, 3, 1, 3, 3, 3]
+            .map(|elapsed| budgets.record("ai", ms(elapsed)))
+            .map(u8::from);
+        assert_eq!(warnings, [0, 0, 0, 0, 1, 0]);
+        assert!(!budgets.record("physics", ms(3)));
+
+        let ai = budgets.stats("ai").copied().unwrap_or_default();
+        assert_eq!((ai.runs, ai.overruns, ai.max), (6, 4, ms(3)));
+        assert_eq!(ai.average(), ms(14) / 6);
+        assert_eq!(
+            budgets.iter().map(|s| s.name).collect::<Vec<_>>(),
+            ["physics", "ai"]
+        );
+
+        budgets.reset();
+        assert_eq!(budgets.stats("ai").map(|s| s.runs), Some(0));
+        assert_eq!(budgets.stats("physics").and_then(|s| s.budget), Some(ms(4)));
+        assert_eq!(budgets.run("ai", || 7), 7);
+    }
+}
@@ -1,10 +1,35 @@
-use core::f32;
-use std::ops::Range;
+//! The [`ViewPoint`] shared by the simulation and the renderer, and the camera
+//! controllers driving it.
+//!
+//! The `Orbital` controller is only available with the `camera` feature.
 
-#[derive(Clone, Copy, Debug, Default)]
+use crate::render::visibility::RenderLayers;
+
+#[cfg(feature = "camera")]
+mod orbital;
+
+#[cfg(feature = "camera")]
+pub use orbital::{Orbital, OrbitalDistance, RotationLimits};
+
+#[derive(Clone, Copy, Debug)]
 pub struct ViewPoint {
     pub orientation: glam::Quat,
     pub position: glam::Vec3,
+    /// The layers rendered from this viewpoint, [`RenderLayers::ALL`] by
+    /// default.
+    ///
+    /// See [`Visibility`](crate::render::visibility::Visibility).
+    pub layers: RenderLayers,
+}
+
+impl Default for ViewPoint {
+    fn default() -> Self {
+        Self {
+            orientation: Default::default(),
+            position: Default::default(),
+            layers: RenderLayers::ALL,
+        }
+    }
 }
 
 impl std::ops::Mul<glam::Quat> for ViewPoint {
@@ -13,7 +38,7 @@ impl std::ops::Mul<glam::Quat> for ViewPoint {
     fn mul(self, rhs: glam::Quat) -> Self::Output {
         Self::Output {
             orientation: self.orientation * rhs,
-            position: self.position,
+            ..self
         }
     }
 }
@@ -23,8 +48,8 @@ impl std::ops::Mul<glam::Vec3> for ViewPoint {
 
     fn mul(self, rhs: glam::Vec3) -> Self::Output {
         Self::Output {
-            orientation: self.orientation,
             position: self.position * rhs,
+            ..self
         }
     }
 }
@@ -34,8 +59,8 @@ impl std::ops::Add<glam::Vec3> for ViewPoint {
 
     fn add(self, rhs: glam::Vec3) -> Self::Output {
         Self::Output {
-            orientation: self.orientation,
             position: self.position + rhs,
+            ..self
         }
     }
 }
@@ -45,8 +70,8 @@ impl std::ops::Sub<glam::Vec3> for ViewPoint {
 
     fn sub(self, rhs: glam::Vec3) -> Self::Output {
         Self::Output {
-            orientation: self.orientation,
             position: self.position - rhs,
+            ..self
         }
     }
 }
@@ -62,6 +87,7 @@ impl ViewPoint {
         Self {
             orientation: glam::Quat::IDENTITY,
             position: pos.into(),
+            ..Default::default()
         }
     }
 
@@ -141,173 +167,3 @@ impl ViewPoint {
         glam::Mat4::from_rotation_translation(self.orientation, self.position)
     }
 }
-
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-pub struct OrbitalDistance(f32);
-
-impl Default for OrbitalDistance {
-    fn default() -> Self {
-        Self(Self::DEFAULT_BASE_DISTANCE)
-    }
-}
-
-impl OrbitalDistance {
-    pub const DEFAULT_BASE_DISTANCE: f32 = 5.0;
-
-    pub fn new(distance: f32) -> Self {
-        Self(distance)
-    }
-
-    pub fn set(&mut self, distance: f32) {
-        self.0 = distance.min(0.0);
-    }
-
-    pub fn into_inner(&self) -> f32 {
-        self.0
-    }
-}
-
-impl std::ops::Add<f32> for OrbitalDistance {
-    type Output = Self;
-
-    fn add(self, rhs: f32) -> Self::Output {
-        Self((self.0 + rhs).max(0.0))
-    }
-}
-
-impl std::ops::AddAssign<f32> for OrbitalDistance {
-    fn add_assign(&mut self, rhs: f32) {
-        self.0 = (self.0 + rhs).max(0.0);
-    }
-}
-
-impl std::ops::Sub<f32> for OrbitalDistance {
-    type Output = Self;
-
-    fn sub(self, rhs: f32) -> Self::Output {
-        Self((self.0 - rhs).max(0.0))
-    }
-}
-
-impl std::ops::SubAssign<f32> for OrbitalDistance {
-    fn sub_assign(&mut self, rhs: f32) {
-        self.0 = (self.0 - rhs).max(0.0);
-    }
-}
-impl std::ops::Deref for OrbitalDistance {
-    type Target = f32;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-#[derive(Clone, Debug)]
-pub struct RotationLimits {
-    pub yaw: Range<f32>,
-    pub pitch: Range<f32>,
-}
-
-impl Default for RotationLimits {
-    fn default() -> Self {
-        Self {
-            yaw: Self::DEFAULT_YAW_LIMIT,
-            pitch: Self::DEFAULT_PITCH_LIMIT,
-        }
-    }
-}
-
-impl RotationLimits {
-    pub const DEFAULT_YAW_LIMIT: Range<f32> = f32::NEG_INFINITY..f32::INFINITY;
-    pub const DEFAULT_PITCH_LIMIT: Range<f32> = -Self::PITCH_LIMIT_90_DEG..Self::PITCH_LIMIT_90_DEG;
-
-    const PITCH_LIMIT_90_DEG: f32 = f32::consts::FRAC_PI_2 - 0.5;
-
-    pub fn new(yaw: Range<f32>, pitch: Range<f32>) -> Self {
-        Self { yaw, pitch }
-    }
-
-    #[inline(always)]
-    pub fn clamp_yaw(&self, v: f32) -> f32 {
-        v.clamp(self.yaw.start, self.yaw.end)
-    }
-
-    #[inline(always)]
-    pub fn clamp_pitch(&self, v: f32) -> f32 {
-        v.clamp(self.pitch.start, self.pitch.end)
-    }
-}
-
-#[derive(Clone, Debug, Default)]
-pub struct Orbital {
-    viewpoint: ViewPoint,
-    orbit_distance: OrbitalDistance,
-    limits: RotationLimits,
-    anchor: glam::Vec3,
-}
-
-impl Orbital {
-    pub fn new(viewpoint: ViewPoint, distance: OrbitalDistance, limits: RotationLimits) -> Self {
-        Self {
-            viewpoint,
-            orbit_distance: distance,
-            limits,
-            anchor: glam::Vec3::ZERO,
-        }
-    }
-
-    pub fn with_anchor(
-        viewpoint: ViewPoint,
-        orbit_distance: OrbitalDistance,
-        anchor: glam::Vec3,
-        limits: RotationLimits,
-    ) -> Self {
-        Self {
-            viewpoint,
-            orbit_distance,
-            limits,
-            anchor,
-        }
-    }
-
-    pub fn update(&mut self, d_yaw: f32, d_pitch: f32) {
-        let (yaw, pitch) = self.viewpoint.yaw_pitch();
-        let yaw = self.limits.clamp_yaw(yaw - d_yaw);
-        let pitch = self.limits.clamp_pitch(pitch - d_pitch);
-
-        self.viewpoint.orientation = glam::Quat::from_euler(glam::EulerRot::YXZ, yaw, pitch, 0.0);
-        self.viewpoint.position = self.anchor - (self.viewpoint.forward() * *self.orbit_distance);
-    }
-
-    pub fn viewpoint(&self) -> &ViewPoint {
-        &self.viewpoint
-    }
-
-    pub fn viewpoint_mut(&mut self) -> &mut ViewPoint {
-        &mut self.viewpoint
-    }
-
-    pub fn distance(&self) -> OrbitalDistance {
-        self.orbit_distance
-    }
-
-    pub fn distance_mut(&mut self) -> &mut OrbitalDistance {
-        &mut self.orbit_distance
-    }
-
-    pub fn rotation_limits(&self) -> &RotationLimits {
-        &self.limits
-    }
-
-    pub fn rotation_limits_mut(&mut self) -> &mut RotationLimits {
-        &mut self.limits
-    }
-
-    pub fn anchor(&self) -> glam::Vec3 {
-        self.anchor
-    }
-
-    pub fn set_anchor(&mut self, anchor: glam::Vec3) {
-        self.anchor = anchor;
-    }
-}
@@ -0,0 +1,184 @@
+use crate::state::data::{Column, IndirectIndex};
+
+type Command<W> = Box<dyn FnOnce(&mut W) + Send>;
+
+/// A buffer of deferred structural changes to a world `W`, and events `E`
+/// emitted alongside them.
+///
+/// Spawning or despawning elements of a [`Column`] while iterating it is not
+/// possible without aliasing. Instead, systems record their changes in a
+/// `Commands` buffer, which is then applied to the world at a well defined
+/// point of the frame, such as between two simulation phases or at the end of
+/// a fixed step.
+///
+/// Commands are applied in the order they were recorded. Events are
+/// dispatched after all commands have been applied, so event handlers always
+/// observe the final state of the world.
+///
+/// The buffer keeps its allocations across [`Commands::apply`] calls.
+pub struct Commands<W, E = ()> {
+    queue: Vec<Command<W>>,
+    events: Vec<E>,
+}
+
+impl<W, E> std::fmt::Debug for Commands<W, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Commands")
+            .field("queue", &self.queue.len())
+            .field("events", &self.events.len())
+            .finish()
+    }
+}
+
+impl<W, E> Default for Commands<W, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W, E> Commands<W, E> {
+    pub fn new() -> Self {
+        Self {
+            queue: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// The amount of recorded commands, excluding events.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty() && self.events.is_empty()
+    }
+
+    /// Record an arbitrary change to the world.
+    pub fn push<F: FnOnce(&mut W) + Send + 'static>(&mut self, command: F) {
+        self.queue.push(Box::new(command));
+    }
+
+    /// Record the insertion of `value` in the column returned by `column`.
+    ///
+    /// See [`Column::insert`].
+    pub fn spawn<T, C, F>(&mut self, column: F, value: T)
+    where
+        T: Default + Send + 'static,
+        C: Column<T>,
+        F: FnOnce(&mut W) -> &mut C + Send + 'static,
+    {
+        self.push(move |world| {
+            column(world).insert(value);
+        });
+    }
+
+    /// Record the insertion of `value` in the column returned by `column`,
+    /// calling `then` with the index of the inserted element once applied.
+    ///
+    /// This allows to link further changes to the spawned element, such as
+    /// adding components to other columns.
+    pub fn spawn_then<T, C, F, G>(&mut self, column: F, value: T, then: G)
+    where
+        T: Default + Send + 'static,
+        C: Column<T>,
+        F: FnOnce(&mut W) -> &mut C + Send + 'static,
+        G: FnOnce(&mut W, IndirectIndex) + Send + 'static,
+    {
+        self.push(move |world| {
+            let slot = column(world).insert(value);
+            then(world, slot);
+        });
+    }
+
+    /// Record the removal of `slot` from the column returned by `column`.
+    ///
+    /// Despawning an element that has already been removed is a no-op.
+    ///
+    /// See [`Column::free`].
+    pub fn despawn<T, C, F>(&mut self, column: F, slot: IndirectIndex)
+    where
+        T: Default,
+        C: Column<T>,
+        F: FnOnce(&mut W) -> &mut C + Send + 'static,
+    {
+        self.push(move |world| column(world).free(slot));
+    }
+
+    /// Emit an `event`, dispatched after all commands have been applied.
+    pub fn emit(&mut self, event: E) {
+        self.events.push(event);
+    }
+
+    /// Apply all recorded commands to `world` in order, then pass each
+    /// emitted event to `on_event`.
+    ///
+    /// The buffer is empty afterwards.
+    pub fn apply<F: FnMut(&mut W, E)>(&mut self, world: &mut W, mut on_event: F) {
+        self.queue.drain(..).for_each(|command| command(world));
+        self.events
+            .drain(..)
+            .for_each(|event| on_event(world, event));
+    }
+
+    /// Discard all recorded commands and events.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::data::{ParallelIndexArrayColumn, column::IterColumn};
+
+    #[derive(Default)]
+    struct World {
+        health: ParallelIndexArrayColumn<f32>,
+        names: ParallelIndexArrayColumn<&'static str>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Died(IndirectIndex),
+    }
+
+    #[test]
+    fn deferred_structural_changes() {
+        let mut world = World::default();
+        let mut commands = Commands::<World, Event>::new();
+
+        let a = world.health.insert(0.0);
+        world.health.insert(10.0);
+
+        // despawning while iterating
+        for (slot, &health) in world.health.iter_slots() {
+            if health <= 0.0 {
+                commands.despawn(|w: &mut World| &mut w.health, slot);
+                commands.emit(Event::Died(slot));
+            }
+        }
+        commands.spawn_then(
+            |w: &mut World| &mut w.health,
+            5.0,
+            |w, _| {
+                w.names.insert("spawned");
+            },
+        );
+        assert_eq!(commands.len(), 2);
+
+        let mut events = Vec::new();
+        commands.apply(&mut world, |w, event| {
+            assert!(w.health.solve_indirect(a).is_none());
+            events.push(event);
+        });
+
+        assert!(commands.is_empty());
+        assert_eq!(events, [Event::Died(a)]);
+        assert_eq!(
+            world.health.iter().copied().collect::<Vec<_>>(),
+            [10.0, 5.0]
+        );
+        assert_eq!(world.names.iter().copied().collect::<Vec<_>>(), ["spawned"]);
+    }
+}
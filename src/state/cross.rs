@@ -1,11 +1,17 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicU8, Ordering},
+use std::{
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard,
+        atomic::{AtomicU8, AtomicU32, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-use crate::render::{
-    buffer::StorageSection,
-    sync::{SyncBarrier, SyncState},
+use crate::{
+    render::{
+        buffer::StorageSection,
+        sync::{LaneFences, SyncBarrier, SyncState},
+    },
+    state::watchdog::Pulse,
 };
 
 /// Common shader storage and metadata to synchronise [`cross`](Cross)
@@ -18,14 +24,110 @@ use crate::render::{
 /// the current working section of the buffer.
 ///
 /// It also contains the actual `Storage`, such as the ones provided by the
-/// [`render::buffer`] module.
+/// [`render::buffer`] module. Both sides share the storage while crossing;
+/// the [`Consumer`] can only modify it between crosses of the [`Producer`],
+/// see [`Cross::try_exclusive`].
 ///
 /// [`render::buffer`]: crate::render::buffer
 #[derive(Debug, Default)]
 pub struct Boundary<Storage> {
-    storage: Storage,
+    storage: RwLock<Storage>,
     working_section: AtomicU8,
     sync_cache: SyncState,
+
+    /// The amount of sections published by the producer.
+    published: AtomicU64,
+    /// The stamp of the section last crossed by the consumer.
+    consumed: AtomicU64,
+    stale_frames: AtomicU32,
+    frames_in_flight: AtomicU8,
+    /// Notified when the consumer crosses, or the limit of frames in flight
+    /// changes, to wake a producer waiting in `wait_in_flight`.
+    crossed: Condvar,
+    crossed_lock: Mutex<()>,
+    pulse: Arc<Pulse>,
+    /// The GPU time of the last frame completed by the consumer, in
+    /// nanoseconds, `u64::MAX` if unknown.
+    gpu_time: AtomicU64,
+}
+
+/// How many sections the [`Producer`] may publish ahead of the
+/// [`Consumer`].
+///
+/// Without a limit, the producer publishes sections at its own pace, and the
+/// consumer always renders the latest one, skipping the others: this is the
+/// incidental behaviour of the triple buffer. With a limit, the producer
+/// waits before writing a section while the given amount of published
+/// sections have not been crossed by the consumer yet, which paces the
+/// producer to the consumer.
+///
+/// One frame in flight minimises latency, as every section is rendered right
+/// after being published, while two frames in flight let the producer work
+/// on the next frame while the consumer is late, trading a frame of latency
+/// for throughput.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FramesInFlight {
+    #[default]
+    Unbounded = 0,
+    One = 1,
+    Two = 2,
+}
+
+impl FramesInFlight {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::One,
+            2 => Self::Two,
+            _ => Self::Unbounded,
+        }
+    }
+
+    /// The maximum amount of published sections not crossed by the consumer
+    /// yet, if bounded.
+    pub fn max_ahead(self) -> Option<u64> {
+        match self {
+            Self::Unbounded => None,
+            limit => Some(limit as u64),
+        }
+    }
+}
+
+/// The longest time the [`Producer`] waits for the [`Consumer`] to catch up,
+/// so that it cannot hang if the consumer stops crossing, e.g. while the
+/// window is minimised or on shutdown.
+const MAX_IN_FLIGHT_WAIT: Duration = Duration::from_millis(250);
+
+/// The age of the section crossed by the [`Consumer`].
+///
+/// The consumer renders at its own pace: when the producer stalls, the same
+/// section is crossed on consecutive frames, and when the producer outpaces
+/// the consumer, some sections are never rendered. The age allows the
+/// consumer to tell them apart, e.g. to skip redundant work on stale frames
+/// or extrapolate the scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SectionAge {
+    /// The amount of sections published by the [`Producer`] when the section
+    /// was crossed, identifying the data of the section.
+    pub stamp: u64,
+    /// The amount of consecutive crosses over the same data before this one.
+    ///
+    /// `0` if the data is new.
+    pub stale_frames: u32,
+    /// The amount of sections published since the previous cross that were
+    /// never crossed by the consumer.
+    pub skipped: u64,
+}
+
+impl SectionAge {
+    /// Whether the data had not been crossed by the consumer before.
+    pub fn is_new(&self) -> bool {
+        self.stale_frames == 0
+    }
+
+    pub fn is_stale(&self) -> bool {
+        !self.is_new()
+    }
 }
 
 impl<Storage> Boundary<Storage> {
@@ -33,14 +135,26 @@ impl<Storage> Boundary<Storage> {
         let working_section = AtomicU8::new(StorageSection::Spare as u8);
         let sync_cache = SyncState::new();
         Self {
-            storage,
+            storage: RwLock::new(storage),
             working_section,
             sync_cache,
+            published: AtomicU64::new(0),
+            consumed: AtomicU64::new(0),
+            stale_frames: AtomicU32::new(0),
+            frames_in_flight: AtomicU8::new(FramesInFlight::Unbounded as u8),
+            crossed: Condvar::new(),
+            crossed_lock: Mutex::new(()),
+            pulse: Arc::new(Pulse::new()),
+            gpu_time: AtomicU64::new(u64::MAX),
         }
     }
 
-    pub fn storage(&self) -> &Storage {
-        &self.storage
+    /// Share the storage, until the guard is dropped.
+    pub fn storage(&self) -> RwLockReadGuard<'_, Storage> {
+        // a panic while crossing does not leave the storage inconsistent
+        self.storage
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
     }
 
     pub fn current_section(&self) -> StorageSection {
@@ -59,6 +173,135 @@ impl<Storage> Boundary<Storage> {
         &self.sync_cache
     }
 
+    /// The amount of sections published by the [`Producer`].
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Acquire)
+    }
+
+    fn publish(&self) {
+        let published = self.published.fetch_add(1, Ordering::AcqRel) + 1;
+        self.pulse.produced(published, self.current_section());
+    }
+
+    /// The crosses of both sides, see [`Watchdog`](super::watchdog::Watchdog).
+    pub fn pulse(&self) -> &Arc<Pulse> {
+        &self.pulse
+    }
+
+    pub fn frames_in_flight(&self) -> FramesInFlight {
+        FramesInFlight::from_byte(self.frames_in_flight.load(Ordering::Relaxed))
+    }
+
+    pub fn set_frames_in_flight(&self, frames: FramesInFlight) {
+        self.frames_in_flight.store(frames as u8, Ordering::Relaxed);
+        self.notify_crossed();
+    }
+
+    /// The GPU time of the last frame completed by the consumer, if
+    /// measured.
+    pub fn gpu_time(&self) -> Option<Duration> {
+        match self.gpu_time.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    pub fn set_gpu_time(&self, time: Duration) {
+        self.gpu_time.store(
+            time.as_nanos().min(u64::MAX as u128 - 1) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// The amount of published sections not crossed by the [`Consumer`] yet.
+    pub fn in_flight(&self) -> u64 {
+        let consumed = self.consumed.load(Ordering::Acquire);
+        self.published().saturating_sub(consumed)
+    }
+
+    /// Whether the [`Producer`] must wait before writing a new section, see
+    /// [`FramesInFlight`].
+    pub fn is_ahead(&self) -> bool {
+        self.frames_in_flight()
+            .max_ahead()
+            .is_some_and(|max| self.in_flight() >= max)
+    }
+
+    /// Wait until the [`Producer`] may write a new section, or until
+    /// [`MAX_IN_FLIGHT_WAIT`] elapsed.
+    ///
+    /// The producer sleeps until the [`Consumer`] crosses, see
+    /// [`Self::notify_crossed`].
+    fn wait_in_flight(&self) {
+        if !self.is_ahead() {
+            return;
+        }
+        let deadline = Instant::now() + MAX_IN_FLIGHT_WAIT;
+        let mut guard = self.lock_crossed();
+        // checked under the lock, so that a cross cannot be missed between
+        // the check and the wait
+        while self.is_ahead() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                use tracing::Level;
+                tracing::event!(
+                    name: "state.cross.in_flight",
+                    Level::DEBUG,
+                    "consumer did not cross for {MAX_IN_FLIGHT_WAIT:?}, publishing anyway"
+                );
+                return;
+            }
+            guard = self
+                .crossed
+                .wait_timeout(guard, timeout)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    /// Wake the [`Producer`] if it is waiting in [`Self::wait_in_flight`].
+    fn notify_crossed(&self) {
+        let _guard = self.lock_crossed();
+        self.crossed.notify_all();
+    }
+
+    fn lock_crossed(&self) -> MutexGuard<'_, ()> {
+        self.crossed_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Determine the age of the current section for the [`Consumer`], and
+    /// mark it as consumed.
+    ///
+    /// Must be called before reading the current section: the stamp is only
+    /// incremented after the section has been advanced.
+    fn consume(&self) -> SectionAge {
+        let stamp = self.published();
+        let previous = self.consumed.swap(stamp, Ordering::AcqRel);
+        // the producer only waits with a limit, and a limit set while it
+        // waits is notified by `set_frames_in_flight`
+        if self.frames_in_flight() != FramesInFlight::Unbounded {
+            self.notify_crossed();
+        }
+
+        if stamp == previous {
+            let stale_frames = self.stale_frames.fetch_add(1, Ordering::AcqRel) + 1;
+            SectionAge {
+                stamp,
+                stale_frames,
+                skipped: 0,
+            }
+        } else {
+            self.stale_frames.store(0, Ordering::Release);
+            SectionAge {
+                stamp,
+                stale_frames: 0,
+                skipped: stamp - previous - 1,
+            }
+        }
+    }
+
     fn sync(&self, barrier: &mut SyncBarrier) {
         barrier.fetch(&self.sync_cache);
     }
@@ -107,9 +350,39 @@ impl<Role, Storage> Cross<Role, Storage> {
             _storage: std::marker::PhantomData,
         }
     }
+
+    pub fn frames_in_flight(&self) -> FramesInFlight {
+        self.boundary.frames_in_flight()
+    }
+
+    /// Limit how many sections the [`Producer`] may publish ahead of the
+    /// [`Consumer`], for both sides of the boundary.
+    ///
+    /// See [`FramesInFlight`].
+    pub fn set_frames_in_flight(&self, frames: FramesInFlight) {
+        self.boundary.set_frames_in_flight(frames);
+    }
+
+    /// The crosses of both sides of the boundary, to watch with a
+    /// [`Watchdog`](super::watchdog::Watchdog).
+    pub fn pulse(&self) -> &Arc<Pulse> {
+        self.boundary.pulse()
+    }
+
+    /// The GPU time of the last frame completed by the [`Consumer`], as
+    /// reported by the renderer.
+    pub fn gpu_time(&self) -> Option<Duration> {
+        self.boundary.gpu_time()
+    }
 }
 
 impl<Storage> Cross<Consumer, Storage> {
+    /// Report the GPU time of the last completed frame to the [`Producer`],
+    /// see [`Cross::gpu_time`].
+    pub fn report_gpu_time(&self, time: Duration) {
+        self.boundary.set_gpu_time(time);
+    }
+
     /// Let the [`Consumer`] cross the [`Boundary`], as a "read" operation.
     ///
     /// This will operate under the current buffer section.
@@ -120,20 +393,78 @@ impl<Storage> Cross<Consumer, Storage> {
     ///
     /// This means that the GPU fence synchronisation of `barrier` must be
     /// handled by the caller.
-    pub fn cross<F>(&self, barrier: &mut SyncBarrier, op: F)
+    ///
+    /// # Returns
+    /// The [`SectionAge`] of the crossed section.
+    pub fn cross<F>(&self, barrier: &mut SyncBarrier, op: F) -> SectionAge
+    where
+        F: FnOnce(StorageSection, &Storage),
+    {
+        self.cross_aged(barrier, |section, _, storage| op(section, storage))
+    }
+
+    /// Let the [`Consumer`] cross the [`Boundary`], as a "read" operation,
+    /// passing the [`SectionAge`] of the section to `op`.
+    ///
+    /// See [`Self::cross`].
+    pub fn cross_aged<F>(&self, barrier: &mut SyncBarrier, op: F) -> SectionAge
     where
-        F: Fn(StorageSection, &Storage),
+        F: FnOnce(StorageSection, SectionAge, &Storage),
     {
+        self.cross_lanes(barrier, &LaneFences::new(), op)
+    }
+
+    /// Let the [`Consumer`] cross the [`Boundary`], as a "read" operation,
+    /// moving the fences placed by `op` in `lanes` to the crossed section.
+    ///
+    /// See [`Self::cross`] and [`LaneFences`].
+    pub fn cross_lanes<F>(&self, barrier: &mut SyncBarrier, lanes: &LaneFences, op: F) -> SectionAge
+    where
+        F: FnOnce(StorageSection, SectionAge, &Storage),
+    {
+        let age = self.boundary.consume();
         let section = self.boundary.current_section();
         self.boundary.sync(barrier);
-        op(section, self.boundary.storage());
+        op(section, age, &self.boundary.storage());
 
         {
             let fence = unsafe { janus::gl::FenceSync(janus::gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
             barrier.set(section.as_index(), fence);
+            barrier.set_lanes(section.as_index(), lanes);
         }
 
         self.boundary.sync(barrier);
+        self.boundary
+            .pulse
+            .consumed(age.stamp, self.boundary.sync_cache());
+        age
+    }
+
+    /// Modify the storage between two crosses, e.g. to
+    /// [`grow`](crate::render::buffer::PartitionedTriBuffer::grow) a buffer
+    /// on the render thread.
+    ///
+    /// The [`Producer`] cannot cross while `op` executes. The consumer does
+    /// not wait for the producer to finish a cross in progress, as the
+    /// producer may itself be waiting on the consumer to release a section:
+    /// the operation is skipped instead, and must be retried later, e.g. on
+    /// the next frame.
+    ///
+    /// Sections crossed before may still be read by the GPU: `op` must not
+    /// modify their GL storage in place, but may replace it.
+    ///
+    /// # Returns
+    /// The result of `op`, or `None` if the producer was crossing.
+    pub fn try_exclusive<F, R>(&self, op: F) -> Option<R>
+    where
+        F: FnOnce(&mut Storage) -> R,
+    {
+        let mut storage = match self.boundary.storage.try_write() {
+            Ok(storage) => storage,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => return None,
+        };
+        Some(op(&mut storage))
     }
 }
 
@@ -148,17 +479,99 @@ impl<Storage> Cross<Producer, Storage> {
     /// After the operation is executed (no lock was present on the section),
     /// the current tracked section of the [`Boundary`] is advanced to the
     /// next section (the one the CPU has just finished writing to).
+    ///
+    /// With a limit of [`FramesInFlight`], first waits for the [`Consumer`]
+    /// to cross enough of the published sections.
     pub fn cross<F>(&self, op: F)
     where
-        F: Fn(StorageSection, &Storage),
+        F: FnOnce(StorageSection, &Storage),
     {
+        self.boundary.wait_in_flight();
         let section = self.boundary.current_section().next();
 
-        while self.boundary.sync_cache().has_lock(section) {
-            std::hint::spin_loop();
+        if self.boundary.sync_cache().has_lock(section) {
+            self.boundary.pulse.set_waiting(Some(section));
+            while self.boundary.sync_cache().has_lock(section) {
+                std::hint::spin_loop();
+            }
+            self.boundary.pulse.set_waiting(None);
         }
-        op(section, self.boundary.storage());
+        op(section, &self.boundary.storage());
+        self.boundary.advance_section();
+        self.boundary.publish();
+    }
+
+    /// Let the [`Producer`] cross the [`Boundary`], as a "write" operation,
+    /// synchronising each lane separately.
+    ///
+    /// Unlike [`Self::cross`], `op` is executed right away: it must wait on
+    /// the [`LaneGate`] for each lane before writing to its partitions, and
+    /// for the whole section before writing to partitions outside of any
+    /// lane. This allows the producer to write the partitions of a lane
+    /// released early by the consumer while the GPU is still reading others.
+    ///
+    /// See [`LaneFences`].
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// boundary.cross_lanes(|section, storage, gate| {
+    ///     gate.wait(LANE_TRANSFORMS);
+    ///     storage.transforms.blit_section(section.as_index(), &transforms, 0);
+    ///
+    ///     gate.wait(LANE_COMMANDS);
+    ///     storage.commands.blit_section(section.as_index(), &commands, 0);
+    /// });
+    /// ```
+    pub fn cross_lanes<F>(&self, op: F)
+    where
+        F: FnOnce(StorageSection, &Storage, &LaneGate),
+    {
+        self.boundary.wait_in_flight();
+        let section = self.boundary.current_section().next();
+        let gate = LaneGate {
+            sync: self.boundary.sync_cache(),
+            pulse: &self.boundary.pulse,
+            section,
+        };
+        op(section, &self.boundary.storage(), &gate);
         self.boundary.advance_section();
+        self.boundary.publish();
+    }
+}
+
+/// Waits for the locks of a section written by the [`Producer`] in
+/// [`Cross::cross_lanes`].
+#[derive(Debug)]
+pub struct LaneGate<'b> {
+    sync: &'b SyncState,
+    pulse: &'b Pulse,
+    section: StorageSection,
+}
+
+impl LaneGate<'_> {
+    /// Wait until the partitions of `lane` are no longer read by the GPU.
+    ///
+    /// # Panics
+    /// If `lane` is not lower than
+    /// [`MAX_SYNC_LANES`](crate::render::sync::MAX_SYNC_LANES).
+    pub fn wait(&self, lane: usize) {
+        self.wait_while(|| self.sync.has_lane_lock(lane, self.section));
+    }
+
+    /// Wait until the whole section is no longer read by the GPU.
+    pub fn wait_section(&self) {
+        self.wait_while(|| self.sync.has_lock(self.section));
+    }
+
+    fn wait_while(&self, locked: impl Fn() -> bool) {
+        if !locked() {
+            return;
+        }
+        self.pulse.set_waiting(Some(self.section));
+        while locked() {
+            std::hint::spin_loop();
+        }
+        self.pulse.set_waiting(None);
     }
 }
 
@@ -180,3 +593,86 @@ pub fn create<Storage>(storage: Storage) -> (Cross<Producer, Storage>, Cross<Con
     let consumer = Cross::new(Arc::clone(&boundary));
     (producer, consumer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn section_age() {
+        let boundary = Boundary::new(());
+
+        // nothing published yet
+        assert_eq!(boundary.consume().stale_frames, 1);
+
+        boundary.publish();
+        let age = boundary.consume();
+        assert!(age.is_new());
+        assert_eq!((age.stamp, age.skipped), (1, 0));
+
+        assert_eq!(boundary.consume().stale_frames, 1);
+        assert_eq!(boundary.consume().stale_frames, 2);
+
+        boundary.publish();
+        boundary.publish();
+        boundary.publish();
+        let age = boundary.consume();
+        assert!(age.is_new());
+        assert_eq!((age.stamp, age.skipped), (4, 2));
+    }
+
+    #[test]
+    fn frames_in_flight() {
+        let boundary = Boundary::new(());
+        assert!(!boundary.is_ahead());
+
+        boundary.publish();
+        boundary.publish();
+        assert_eq!(boundary.in_flight(), 2);
+        assert!(!boundary.is_ahead());
+
+        boundary.set_frames_in_flight(FramesInFlight::Two);
+        assert!(boundary.is_ahead());
+        boundary.set_frames_in_flight(FramesInFlight::One);
+        assert!(boundary.is_ahead());
+
+        boundary.consume();
+        assert_eq!(boundary.in_flight(), 0);
+        assert!(!boundary.is_ahead());
+        assert_eq!(boundary.frames_in_flight(), FramesInFlight::One);
+    }
+
+    #[test]
+    fn wake_on_consume() {
+        let boundary = Boundary::new(());
+        boundary.set_frames_in_flight(FramesInFlight::One);
+        boundary.publish();
+        assert!(boundary.is_ahead());
+
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                boundary.consume();
+            });
+            boundary.wait_in_flight();
+        });
+        assert!(!boundary.is_ahead());
+        // woken by the cross, long before the timeout
+        assert!(start.elapsed() < MAX_IN_FLIGHT_WAIT);
+    }
+
+    #[test]
+    fn exclusive_between_crosses() {
+        let (producer, consumer) = create(vec![1u32]);
+        assert_eq!(consumer.try_exclusive(|storage| storage.push(2)), Some(()));
+
+        producer.cross(|_, storage| {
+            assert_eq!(*storage, [1, 2]);
+            // the producer is crossing
+            assert_eq!(consumer.try_exclusive(|storage| storage.push(3)), None);
+        });
+        assert_eq!(consumer.try_exclusive(|storage| storage.len()), Some(2));
+        assert_eq!(producer.boundary.published(), 1);
+    }
+}
@@ -1,6 +1,8 @@
 use std::borrow::{Borrow, BorrowMut};
 
-use crate::state::data::{Column, DirectIndex, IndirectIndex, SparseSlot};
+use crate::state::data::{
+    Column, DirectIndex, EntityColumn, IndirectIndex, MemoryFootprint, SparseSlot,
+};
 
 /// A wrapper for an entry of an [`IndexArrayColumn`] over the `T` type.
 ///
@@ -110,6 +112,28 @@ where
     }
 }
 
+/// Free the element of `entity` in `column` if it holds one, see
+/// [`EntityColumn::free_entity`].
+fn free_entity<T: Default, C: Column<T>>(column: &mut C, entity: IndirectIndex) -> bool {
+    match column.solve_indirect(entity) {
+        Some(index) if index.as_int() != 0 => {
+            column.free(entity);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The contiguous indices of the elements sorted by their `keys`, starting
+/// from index 1 to skip the degenerate element.
+///
+/// The sort is stable: elements with equal keys keep their relative order.
+fn sorted_order<K: Ord>(keys: impl Iterator<Item = K>) -> Vec<usize> {
+    let mut order = keys.zip(1..).collect::<Vec<_>>();
+    order.sort_by(|(a, _), (b, _)| a.cmp(b));
+    order.into_iter().map(|(_, i)| i).collect()
+}
+
 #[derive(Debug)]
 pub struct IndexArrayColumn<T: Default> {
     /// Collection of direct indices to the `contiguous` data of this Column.
@@ -135,6 +159,106 @@ impl<T: Default> IndexArrayColumn<T> {
         self.contiguous.resize_with(1, || Entry::default());
         self.free.clear();
     }
+
+    /// Retain only the elements for which `f` returns `true`, freeing all
+    /// others in a single pass.
+    ///
+    /// Unlike [`Column::free`], this preserves the relative order of the
+    /// retained elements.
+    pub fn retain<F: FnMut(IndirectIndex, &T) -> bool>(&mut self, f: F) {
+        let head = self.compact_where(f);
+        self.contiguous.truncate(head);
+    }
+
+    /// Free all elements for which `f` returns `true` in a single pass,
+    /// returning their owner index and value, like [`Vec::extract_if`].
+    ///
+    /// See [`IndexArrayColumn::retain`].
+    pub fn drain_where<F: FnMut(IndirectIndex, &T) -> bool>(
+        &mut self,
+        mut f: F,
+    ) -> Vec<(IndirectIndex, T)> {
+        let head = self.compact_where(|slot, value| !f(slot, value));
+        self.contiguous
+            .split_off(head)
+            .into_iter()
+            .map(|entry| (entry.owner, entry.inner))
+            .collect()
+    }
+
+    /// Move all elements to keep to the front, updating their indices and
+    /// freeing the slots of all others.
+    ///
+    /// # Returns
+    /// The length of the retained contiguous data.
+    fn compact_where<F: FnMut(IndirectIndex, &T) -> bool>(&mut self, mut keep: F) -> usize {
+        let mut head = 1;
+        for i in 1..self.contiguous.len() {
+            let owner = self.contiguous[i].owner;
+            if keep(owner, &self.contiguous[i].inner) {
+                if head != i {
+                    self.contiguous.swap(head, i);
+                }
+                self.indices[owner.as_index()] = DirectIndex::from_index(head, owner.generation);
+                head += 1;
+            } else {
+                self.indices[owner.as_index()] = self.indices[owner.as_index()].freed();
+                self.free.push(owner.next_generation());
+            }
+        }
+        head
+    }
+}
+
+impl<T: Default> IndexArrayColumn<T> {
+    /// Get an iterator over the owner slot and value of each element.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_slots(&self) -> impl Iterator<Item = (IndirectIndex, &T)> {
+        self.contiguous
+            .iter()
+            .skip(1)
+            .map(|entry| (entry.owner, &entry.inner))
+    }
+
+    /// Get an iterator over the owner slot and mutable value of each element.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_slots_mut(&mut self) -> impl Iterator<Item = (IndirectIndex, &mut T)> {
+        self.contiguous
+            .iter_mut()
+            .skip(1)
+            .map(|entry| (entry.owner, &mut entry.inner))
+    }
+
+    /// Reorder the contiguous data by `key`, e.g. by spatial cell, so that
+    /// iteration order follows the access patterns of the systems using the
+    /// column.
+    ///
+    /// Indices are rewritten consistently: indirect indices remain valid,
+    /// while direct indices obtained before the call are invalidated.
+    pub fn sort_by_key<K: Ord, F: FnMut(IndirectIndex, &T) -> K>(&mut self, mut key: F) {
+        let order = sorted_order(self.iter_slots().map(|(owner, value)| key(owner, value)));
+
+        let mut contiguous = Vec::with_capacity(self.contiguous.capacity());
+        contiguous.push(std::mem::take(&mut self.contiguous[0]));
+        for i in order {
+            let entry = std::mem::take(&mut self.contiguous[i]);
+            let owner = entry.owner;
+            self.indices[owner.as_index()] =
+                DirectIndex::from_index(contiguous.len(), owner.generation);
+            contiguous.push(entry);
+        }
+        self.contiguous = contiguous;
+    }
+
+    /// Reorder the contiguous data by owner slot, restoring the insertion
+    /// order scrambled by frees and slot reuse.
+    ///
+    /// See [`IndexArrayColumn::sort_by_key`].
+    pub fn sort_by_owner(&mut self) {
+        self.sort_by_key(|owner, _| owner.as_int());
+    }
 }
 
 impl<T: Default> Default for IndexArrayColumn<T> {
@@ -197,6 +321,27 @@ impl<T: Default> Column<T> for IndexArrayColumn<T> {
         self.contiguous.len()
     }
 
+    fn capacity(&self) -> usize {
+        self.contiguous.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.indices.reserve(additional);
+        self.contiguous.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.indices.shrink_to_fit();
+        self.contiguous.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    fn memory_footprint(&self) -> MemoryFootprint {
+        MemoryFootprint::of_vec(&self.indices)
+            + MemoryFootprint::of_vec(&self.contiguous)
+            + MemoryFootprint::of_vec(&self.free)
+    }
+
     fn size(&self) -> usize {
         self.indices.len()
     }
@@ -210,9 +355,12 @@ impl<T: Default> Column<T> for IndexArrayColumn<T> {
         if !contiguous_slot.related_to_indirect(&slot) || contiguous_slot.as_int() == 0 {
             return;
         }
-        self.indices[slot.as_index()] = contiguous_slot.next_generation();
+        self.indices[slot.as_index()] = contiguous_slot.freed();
 
-        if let Some(owner_last) = self.contiguous.last().map(Entry::owner) {
+        // do not reassign slot if we are freeing last
+        if let Some(owner_last) = self.contiguous.last().map(Entry::owner)
+            && owner_last.as_index() != slot.as_index()
+        {
             self.indices[owner_last.as_index()] = contiguous_slot;
         }
 
@@ -229,6 +377,12 @@ impl<T: Default> Column<T> for IndexArrayColumn<T> {
     }
 }
 
+impl<T: Default> EntityColumn for IndexArrayColumn<T> {
+    fn free_entity(&mut self, entity: IndirectIndex) -> bool {
+        free_entity(self, entity)
+    }
+}
+
 impl<'iter, T: Default + 'iter> IterColumn<'iter, T, Entry<T>> for IndexArrayColumn<T> {
     fn contiguous(&self) -> &[Entry<T>] {
         &self.contiguous
@@ -317,6 +471,27 @@ impl<T: Default> Column<T> for ArrayColumn<T> {
         self.contiguous.len()
     }
 
+    fn capacity(&self) -> usize {
+        self.contiguous.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.indices.reserve(additional);
+        self.contiguous.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.indices.shrink_to_fit();
+        self.contiguous.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    fn memory_footprint(&self) -> MemoryFootprint {
+        MemoryFootprint::of_vec(&self.indices)
+            + MemoryFootprint::of_vec(&self.contiguous)
+            + MemoryFootprint::of_vec(&self.free)
+    }
+
     fn size(&self) -> usize {
         self.indices.len()
     }
@@ -330,7 +505,7 @@ impl<T: Default> Column<T> for ArrayColumn<T> {
         if !contiguous_slot.related_to_indirect(&slot) || contiguous_slot.as_int() == 0 {
             return;
         }
-        self.indices[slot.as_index()] = contiguous_slot.next_generation();
+        self.indices[slot.as_index()] = contiguous_slot.freed();
 
         self.contiguous.swap_remove(contiguous_slot.as_index());
         self.free.push(slot.next_generation());
@@ -347,6 +522,12 @@ impl<T: Default> Column<T> for ArrayColumn<T> {
     }
 }
 
+impl<T: Default> EntityColumn for ArrayColumn<T> {
+    fn free_entity(&mut self, entity: IndirectIndex) -> bool {
+        free_entity(self, entity)
+    }
+}
+
 impl<'iter, T: Default + 'iter> IterColumn<'iter, T, T> for ArrayColumn<T> {
     fn contiguous(&self) -> &[T] {
         &self.contiguous
@@ -386,6 +567,57 @@ impl<T: Default> ParallelIndexArrayColumn<T> {
         self.contiguous.resize_with(1, || T::default());
         self.free.clear();
     }
+
+    /// Retain only the elements for which `f` returns `true`, freeing all
+    /// others in a single pass.
+    ///
+    /// Unlike [`Column::free`], this preserves the relative order of the
+    /// retained elements.
+    pub fn retain<F: FnMut(IndirectIndex, &T) -> bool>(&mut self, f: F) {
+        let head = self.compact_where(f);
+        self.contiguous.truncate(head);
+        self.owners.truncate(head);
+    }
+
+    /// Free all elements for which `f` returns `true` in a single pass,
+    /// returning their owner index and value, like [`Vec::extract_if`].
+    ///
+    /// See [`ParallelIndexArrayColumn::retain`].
+    pub fn drain_where<F: FnMut(IndirectIndex, &T) -> bool>(
+        &mut self,
+        mut f: F,
+    ) -> Vec<(IndirectIndex, T)> {
+        let head = self.compact_where(|slot, value| !f(slot, value));
+        let owners = self.owners.split_off(head);
+        owners
+            .into_iter()
+            .zip(self.contiguous.split_off(head))
+            .collect()
+    }
+
+    /// Move all elements to keep to the front, updating their indices and
+    /// freeing the slots of all others.
+    ///
+    /// # Returns
+    /// The length of the retained contiguous data.
+    fn compact_where<F: FnMut(IndirectIndex, &T) -> bool>(&mut self, mut keep: F) -> usize {
+        let mut head = 1;
+        for i in 1..self.contiguous.len() {
+            let owner = self.owners[i];
+            if keep(owner, &self.contiguous[i]) {
+                if head != i {
+                    self.contiguous.swap(head, i);
+                    self.owners.swap(head, i);
+                }
+                self.indices[owner.as_index()] = DirectIndex::from_index(head, owner.generation);
+                head += 1;
+            } else {
+                self.indices[owner.as_index()] = self.indices[owner.as_index()].freed();
+                self.free.push(owner.next_generation());
+            }
+        }
+        head
+    }
 }
 
 impl<T: Default> Default for ParallelIndexArrayColumn<T> {
@@ -435,6 +667,61 @@ impl<T: Default> ParallelIndexArrayColumn<T> {
     pub fn handles_mut(&mut self) -> &mut [IndirectIndex] {
         &mut self.owners
     }
+
+    /// Borrow the contiguous data mutably alongside its owner indices.
+    pub fn split_mut(&mut self) -> (&mut [T], &[IndirectIndex]) {
+        (&mut self.contiguous, &self.owners)
+    }
+
+    /// Get an iterator over the owner slot and value of each element.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_slots(&self) -> impl Iterator<Item = (IndirectIndex, &T)> {
+        self.owners.iter().copied().zip(&self.contiguous).skip(1)
+    }
+
+    /// Get an iterator over the owner slot and mutable value of each element.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_slots_mut(&mut self) -> impl Iterator<Item = (IndirectIndex, &mut T)> {
+        self.owners
+            .iter()
+            .copied()
+            .zip(&mut self.contiguous)
+            .skip(1)
+    }
+
+    /// Reorder the contiguous data by `key`, e.g. by spatial cell, so that
+    /// iteration order follows the access patterns of the systems using the
+    /// column.
+    ///
+    /// Indices are rewritten consistently: indirect indices remain valid,
+    /// while direct indices obtained before the call are invalidated.
+    pub fn sort_by_key<K: Ord, F: FnMut(IndirectIndex, &T) -> K>(&mut self, mut key: F) {
+        let order = sorted_order(self.iter_slots().map(|(owner, value)| key(owner, value)));
+
+        let mut contiguous = Vec::with_capacity(self.contiguous.capacity());
+        let mut owners = Vec::with_capacity(self.owners.capacity());
+        contiguous.push(std::mem::take(&mut self.contiguous[0]));
+        owners.push(self.owners[0]);
+        for i in order {
+            let owner = self.owners[i];
+            self.indices[owner.as_index()] =
+                DirectIndex::from_index(contiguous.len(), owner.generation);
+            contiguous.push(std::mem::take(&mut self.contiguous[i]));
+            owners.push(owner);
+        }
+        self.contiguous = contiguous;
+        self.owners = owners;
+    }
+
+    /// Reorder the contiguous data by owner slot, restoring the insertion
+    /// order scrambled by frees and slot reuse.
+    ///
+    /// See [`ParallelIndexArrayColumn::sort_by_key`].
+    pub fn sort_by_owner(&mut self) {
+        self.sort_by_key(|owner, _| owner.as_int());
+    }
 }
 
 impl<T: Default> SparseSlot for ParallelIndexArrayColumn<T> {
@@ -460,6 +747,30 @@ impl<T: Default> Column<T> for ParallelIndexArrayColumn<T> {
         self.contiguous.len()
     }
 
+    fn capacity(&self) -> usize {
+        self.contiguous.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.indices.reserve(additional);
+        self.contiguous.reserve(additional);
+        self.owners.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.indices.shrink_to_fit();
+        self.contiguous.shrink_to_fit();
+        self.owners.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    fn memory_footprint(&self) -> MemoryFootprint {
+        MemoryFootprint::of_vec(&self.indices)
+            + MemoryFootprint::of_vec(&self.contiguous)
+            + MemoryFootprint::of_vec(&self.owners)
+            + MemoryFootprint::of_vec(&self.free)
+    }
+
     fn size(&self) -> usize {
         self.indices.len()
     }
@@ -474,12 +785,15 @@ impl<T: Default> Column<T> for ParallelIndexArrayColumn<T> {
             return;
         }
 
-        self.indices[slot.as_index()] = contiguous_slot.next_generation();
+        self.indices[slot.as_index()] = contiguous_slot.freed();
         let last_owner = *self
             .owners
             .last()
             .expect("contiguous vectors are never empty");
-        self.indices[last_owner.as_index()] = contiguous_slot;
+        // do not reassign slot if we are freeing last
+        if last_owner.as_index() != slot.as_index() {
+            self.indices[last_owner.as_index()] = contiguous_slot;
+        }
 
         self.owners.swap_remove(contiguous_slot.as_index());
         self.contiguous.swap_remove(contiguous_slot.as_index());
@@ -496,6 +810,12 @@ impl<T: Default> Column<T> for ParallelIndexArrayColumn<T> {
     }
 }
 
+impl<T: Default> EntityColumn for ParallelIndexArrayColumn<T> {
+    fn free_entity(&mut self, entity: IndirectIndex) -> bool {
+        free_entity(self, entity)
+    }
+}
+
 impl<'iter, T: Default + 'iter> IterColumn<'iter, T, T> for ParallelIndexArrayColumn<T> {
     fn contiguous(&self) -> &[T] {
         &self.contiguous
@@ -506,6 +826,477 @@ impl<'iter, T: Default + 'iter> IterColumn<'iter, T, T> for ParallelIndexArrayCo
     }
 }
 
+/// The default amount of elements per chunk of a [`ChunkedColumn`].
+pub const DEFAULT_COLUMN_CHUNK: usize = 4096;
+
+#[derive(Debug)]
+struct ColumnChunk<T> {
+    values: Vec<T>,
+    owners: Vec<IndirectIndex>,
+}
+
+impl<T> ColumnChunk<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+            owners: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+/// A column storing its data in fixed-size blocks of `CHUNK` elements.
+///
+/// This is functionally equivalent to a [`ParallelIndexArrayColumn`], but
+/// growing the column only ever allocates a new chunk: existing elements are
+/// never moved to a new allocation, so huge columns do not suffer from the
+/// reallocation spikes of a single `Vec`, and references to the data of a
+/// chunk remain valid as long as the column is not mutated.
+///
+/// The data is only contiguous within a chunk, thus this does not implement
+/// [`IterColumn`]. Use [`ChunkedColumn::chunks`] to process the data in
+/// contiguous blocks, which also lends itself to parallel iteration.
+///
+/// Emptied chunks are kept allocated to be reused by later insertions.
+#[derive(Debug)]
+pub struct ChunkedColumn<T: Default, const CHUNK: usize = DEFAULT_COLUMN_CHUNK> {
+    /// Collection of direct indices to the chunked data of this Column.
+    ///
+    /// The indexing of this collection is guaranteed to be stable, assuming
+    /// the correct [`IndirectIndex`] is used when performing index operations.
+    indices: Vec<DirectIndex>,
+
+    /// The blocks of data, each with a fixed capacity of `CHUNK` elements.
+    ///
+    /// Only the last non-empty chunk may be partially filled.
+    chunks: Vec<ColumnChunk<T>>,
+
+    /// Keeps track of free slots of the indirect indices map.
+    free: Vec<IndirectIndex>,
+
+    /// The total amount of elements across all chunks.
+    len: usize,
+}
+
+impl<T: Default, const CHUNK: usize> Default for ChunkedColumn<T, CHUNK> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Default, const CHUNK: usize> ChunkedColumn<T, CHUNK> {
+    /// Create a blank new Column with a size of `1`.
+    ///
+    /// The only element present is the degenerate element at index `0`.
+    ///
+    /// # Panics
+    /// If `CHUNK` is `0`.
+    pub fn new() -> Self {
+        assert!(CHUNK > 0, "chunk size of a ChunkedColumn must not be zero");
+
+        let mut chunk = ColumnChunk::new(CHUNK);
+        chunk.values.push(T::default());
+        chunk.owners.push(IndirectIndex::default());
+
+        Self {
+            indices: vec![DirectIndex::default()],
+            chunks: vec![chunk],
+            free: Vec::new(),
+            len: 1,
+        }
+    }
+
+    /// Creata a blank new column with enough chunks to fit `capacity`
+    /// elements.
+    ///
+    /// # Panics
+    /// If `CHUNK` is `0`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut column = Self::new();
+        column.indices.reserve(capacity.saturating_sub(1));
+        let chunk_count = capacity.div_ceil(CHUNK).max(1);
+        (1..chunk_count).for_each(|_| column.chunks.push(ColumnChunk::new(CHUNK)));
+        column
+    }
+
+    pub fn clear(&mut self) {
+        self.indices.resize(1, DirectIndex::default());
+        self.chunks.iter_mut().skip(1).for_each(|chunk| {
+            chunk.values.clear();
+            chunk.owners.clear();
+        });
+        self.chunks[0].values.truncate(1);
+        self.chunks[0].owners.truncate(1);
+        self.free.clear();
+        self.len = 1;
+    }
+
+    /// Retain only the elements for which `f` returns `true`, freeing all
+    /// others in a single pass.
+    ///
+    /// Unlike [`Column::free`], this preserves the relative order of the
+    /// retained elements. Emptied chunks are kept allocated.
+    pub fn retain<F: FnMut(IndirectIndex, &T) -> bool>(&mut self, f: F) {
+        let head = self.compact_where(f);
+        self.truncate_chunks(head, |_, _| {});
+    }
+
+    /// Free all elements for which `f` returns `true` in a single pass,
+    /// returning their owner index and value, like [`Vec::extract_if`].
+    ///
+    /// See [`ChunkedColumn::retain`].
+    pub fn drain_where<F: FnMut(IndirectIndex, &T) -> bool>(
+        &mut self,
+        mut f: F,
+    ) -> Vec<(IndirectIndex, T)> {
+        let head = self.compact_where(|slot, value| !f(slot, value));
+        let mut drained = Vec::with_capacity(self.len - head);
+        self.truncate_chunks(head, |owners, values| {
+            drained.extend(owners.into_iter().zip(values));
+        });
+        drained
+    }
+
+    /// Move all elements to keep to the front, updating their indices and
+    /// freeing the slots of all others.
+    ///
+    /// # Returns
+    /// The length of the retained data.
+    fn compact_where<F: FnMut(IndirectIndex, &T) -> bool>(&mut self, mut keep: F) -> usize {
+        let mut head = 1;
+        for i in 1..self.len {
+            let (chunk, offset) = (i / CHUNK, i % CHUNK);
+            let owner = self.chunks[chunk].owners[offset];
+            if keep(owner, &self.chunks[chunk].values[offset]) {
+                if head != i {
+                    let value = std::mem::take(&mut self.chunks[chunk].values[offset]);
+                    let removed = std::mem::replace(
+                        &mut self.chunks[head / CHUNK].values[head % CHUNK],
+                        value,
+                    );
+                    self.chunks[chunk].values[offset] = removed;
+                    self.chunks[chunk].owners[offset] =
+                        self.chunks[head / CHUNK].owners[head % CHUNK];
+                    self.chunks[head / CHUNK].owners[head % CHUNK] = owner;
+                }
+                self.indices[owner.as_index()] = DirectIndex::from_index(head, owner.generation);
+                head += 1;
+            } else {
+                self.indices[owner.as_index()] = self.indices[owner.as_index()].freed();
+                self.free.push(owner.next_generation());
+            }
+        }
+        head
+    }
+
+    /// Truncate the data to `len` elements, passing the removed tail of each
+    /// chunk to `removed`, in order.
+    fn truncate_chunks<F: FnMut(Vec<IndirectIndex>, Vec<T>)>(
+        &mut self,
+        len: usize,
+        mut removed: F,
+    ) {
+        for (i, chunk) in self.chunks.iter_mut().enumerate() {
+            let keep = len.saturating_sub(i * CHUNK).min(CHUNK);
+            if keep < chunk.values.len() {
+                removed(chunk.owners.split_off(keep), chunk.values.split_off(keep));
+            }
+        }
+        self.len = len;
+    }
+
+    /// The amount of allocated chunks, including empty ones.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Get an iterator over the contiguous blocks of data.
+    ///
+    /// The first element of the first chunk is the degenerate element at
+    /// index 0. Empty chunks are skipped.
+    pub fn chunks(&self) -> impl Iterator<Item = &[T]> {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.values.as_slice())
+            .take_while(|values| !values.is_empty())
+    }
+
+    /// Get a mutable iterator over the contiguous blocks of data.
+    ///
+    /// The first element of the first chunk is the degenerate element at
+    /// index 0. Empty chunks are skipped.
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.chunks
+            .iter_mut()
+            .map(|chunk| chunk.values.as_mut_slice())
+            .take_while(|values| !values.is_empty())
+    }
+
+    /// Get an iterator over the owner indices of each block of data, parallel
+    /// to [`ChunkedColumn::chunks`].
+    pub fn handle_chunks(&self) -> impl Iterator<Item = &[IndirectIndex]> {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.owners.as_slice())
+            .take_while(|owners| !owners.is_empty())
+    }
+
+    /// Get an immutable iterator over all elements.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks().flatten().skip(1)
+    }
+
+    /// Get a mutable iterator over all elements.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks_mut().flatten().skip(1)
+    }
+
+    /// Get an iterator over the owner slot and value of each element.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_slots(&self) -> impl Iterator<Item = (IndirectIndex, &T)> {
+        self.chunks
+            .iter()
+            .take_while(|chunk| !chunk.values.is_empty())
+            .flat_map(|chunk| chunk.owners.iter().copied().zip(&chunk.values))
+            .skip(1)
+    }
+
+    /// Get an iterator over the owner slot and mutable value of each element.
+    ///
+    /// This skips the first degenerate element at index 0.
+    pub fn iter_slots_mut(&mut self) -> impl Iterator<Item = (IndirectIndex, &mut T)> {
+        self.chunks
+            .iter_mut()
+            .take_while(|chunk| !chunk.values.is_empty())
+            .flat_map(|chunk| chunk.owners.iter().copied().zip(&mut chunk.values))
+            .skip(1)
+    }
+
+    /// Reorder the data by `key`, e.g. by spatial cell, so that iteration
+    /// order follows the access patterns of the systems using the column.
+    ///
+    /// Indices are rewritten consistently: indirect indices remain valid,
+    /// while direct indices obtained before the call are invalidated. Chunks
+    /// are reused, not reallocated.
+    pub fn sort_by_key<K: Ord, F: FnMut(IndirectIndex, &T) -> K>(&mut self, mut key: F) {
+        let order = sorted_order(self.iter_slots().map(|(owner, value)| key(owner, value)));
+
+        let mut sorted = Vec::with_capacity(self.len);
+        for i in std::iter::once(0).chain(order) {
+            let chunk = &mut self.chunks[i / CHUNK];
+            let offset = i % CHUNK;
+            sorted.push((
+                chunk.owners[offset],
+                std::mem::take(&mut chunk.values[offset]),
+            ));
+        }
+
+        self.chunks.iter_mut().for_each(|chunk| {
+            chunk.values.clear();
+            chunk.owners.clear();
+        });
+        for (head, (owner, value)) in sorted.into_iter().enumerate() {
+            if head != 0 {
+                self.indices[owner.as_index()] = DirectIndex::from_index(head, owner.generation);
+            }
+            let chunk = &mut self.chunks[head / CHUNK];
+            chunk.values.push(value);
+            chunk.owners.push(owner);
+        }
+    }
+
+    /// Reorder the data by owner slot, restoring the insertion order
+    /// scrambled by frees and slot reuse.
+    ///
+    /// See [`ChunkedColumn::sort_by_key`].
+    pub fn sort_by_owner(&mut self) {
+        self.sort_by_key(|owner, _| owner.as_int());
+    }
+
+    /// Get the element at the given `direct` index.
+    ///
+    /// # Panics
+    /// If `direct` is out of bounds.
+    pub fn direct(&self, direct: DirectIndex) -> &T {
+        let index = direct.as_index();
+        &self.chunks[index / CHUNK].values[index % CHUNK]
+    }
+
+    /// Get the element at the given `direct` index mutably.
+    ///
+    /// # Panics
+    /// If `direct` is out of bounds.
+    pub fn direct_mut(&mut self, direct: DirectIndex) -> &mut T {
+        let index = direct.as_index();
+        &mut self.chunks[index / CHUNK].values[index % CHUNK]
+    }
+
+    /// Get the element pointed to by the indirect index `slot`.
+    ///
+    /// Returns `None` if `slot` is not valid or has been freed.
+    pub fn get(&self, slot: IndirectIndex) -> Option<&T> {
+        self.solve_indirect(slot)
+            .filter(|direct| direct.as_int() != 0)
+            .map(|direct| self.direct(direct))
+    }
+
+    /// Get the element pointed to by the indirect index `slot` mutably.
+    ///
+    /// Returns `None` if `slot` is not valid or has been freed.
+    pub fn get_mut(&mut self, slot: IndirectIndex) -> Option<&mut T> {
+        self.solve_indirect(slot)
+            .filter(|direct| direct.as_int() != 0)
+            .map(|direct| self.direct_mut(direct))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Default + Send + Sync, const CHUNK: usize> ChunkedColumn<T, CHUNK> {
+    /// Get a parallel iterator over the contiguous blocks of data.
+    ///
+    /// See [`ChunkedColumn::chunks`].
+    pub fn par_chunks(&self) -> impl rayon::iter::ParallelIterator<Item = &[T]> {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+        self.chunks
+            .par_iter()
+            .map(|chunk| chunk.values.as_slice())
+            .filter(|values| !values.is_empty())
+    }
+
+    /// Get a mutable parallel iterator over the contiguous blocks of data.
+    ///
+    /// See [`ChunkedColumn::chunks_mut`].
+    pub fn par_chunks_mut(&mut self) -> impl rayon::iter::ParallelIterator<Item = &mut [T]> {
+        use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+        self.chunks
+            .par_iter_mut()
+            .map(|chunk| chunk.values.as_mut_slice())
+            .filter(|values| !values.is_empty())
+    }
+}
+
+impl<T: Default, const CHUNK: usize> SparseSlot for ChunkedColumn<T, CHUNK> {
+    fn slots_map(&self) -> &Vec<DirectIndex> {
+        &self.indices
+    }
+
+    fn slots_map_mut(&mut self) -> &mut Vec<DirectIndex> {
+        &mut self.indices
+    }
+
+    fn free_list(&self) -> &Vec<IndirectIndex> {
+        &self.free
+    }
+
+    fn free_list_mut(&mut self) -> &mut Vec<IndirectIndex> {
+        &mut self.free
+    }
+}
+
+impl<T: Default, const CHUNK: usize> Column<T> for ChunkedColumn<T, CHUNK> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.chunks.len() * CHUNK
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.indices.reserve(additional);
+        let required = (self.len + additional).div_ceil(CHUNK);
+        while self.chunks.len() < required {
+            self.chunks.push(ColumnChunk::new(CHUNK));
+        }
+    }
+
+    /// Deallocate all empty chunks and shrink the sparse indices.
+    ///
+    /// Chunks in use always keep their full capacity.
+    fn shrink_to_fit(&mut self) {
+        let used = self.len.div_ceil(CHUNK).max(1);
+        self.chunks.truncate(used);
+        self.chunks.shrink_to_fit();
+        self.indices.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    fn memory_footprint(&self) -> MemoryFootprint {
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|chunk| {
+                MemoryFootprint::of_vec(&chunk.values) + MemoryFootprint::of_vec(&chunk.owners)
+            })
+            .sum::<MemoryFootprint>();
+        MemoryFootprint::of_vec(&self.indices)
+            + MemoryFootprint::of_vec(&self.chunks)
+            + chunks
+            + MemoryFootprint::of_vec(&self.free)
+    }
+
+    fn size(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn free(&mut self, slot: IndirectIndex) {
+        if slot.as_int() == 0 {
+            panic!("slot 0 is reserved for degenerate elements and must not be freed");
+        }
+
+        let contiguous_slot = self.indices[slot.as_index()];
+        if !contiguous_slot.related_to_indirect(&slot) || contiguous_slot.as_int() == 0 {
+            return;
+        }
+        self.indices[slot.as_index()] = contiguous_slot.freed();
+
+        // swap remove across chunks: the last element takes the place of the
+        // removed one
+        let last_chunk = &mut self.chunks[(self.len - 1) / CHUNK];
+        let last_value = last_chunk.values.pop().expect("chunks are never empty");
+        let last_owner = last_chunk.owners.pop().expect("chunks are never empty");
+        self.len -= 1;
+
+        let index = contiguous_slot.as_index();
+        if index < self.len {
+            let chunk = &mut self.chunks[index / CHUNK];
+            chunk.values[index % CHUNK] = last_value;
+            chunk.owners[index % CHUNK] = last_owner;
+            self.indices[last_owner.as_index()] = contiguous_slot;
+        }
+
+        self.free.push(slot.next_generation());
+    }
+
+    fn insert<V: Into<T>>(&mut self, value: V) -> IndirectIndex {
+        let index = self.next_slot_index();
+        let head = self.len;
+        self.indices[index.as_index()] = DirectIndex::from_index(head, index.generation);
+
+        if head / CHUNK == self.chunks.len() {
+            self.chunks.push(ColumnChunk::new(CHUNK));
+        }
+        let chunk = &mut self.chunks[head / CHUNK];
+        chunk.values.push(value.into());
+        chunk.owners.push(index);
+        self.len += 1;
+
+        index
+    }
+}
+
+impl<T: Default, const CHUNK: usize> EntityColumn for ChunkedColumn<T, CHUNK> {
+    fn free_entity(&mut self, entity: IndirectIndex) -> bool {
+        free_entity(self, entity)
+    }
+}
+
 impl<T: Default> IntoIterator for IndexArrayColumn<T> {
     type Item = Entry<T>;
 
@@ -564,4 +1355,205 @@ mod tests {
         // free last
         column.free(last);
     }
+
+    #[test]
+    fn freed_slot_keeps_index() {
+        let indirect = IndirectIndex::from_int(7, 2).next_generation();
+        assert_eq!((indirect.as_int(), indirect.generation()), (7, 3));
+        let direct = DirectIndex::from_int(7, 2).next_generation();
+        assert_eq!((direct.as_int(), direct.generation()), (7, 3));
+        assert_eq!(DirectIndex::from_int(7, 2).freed(), DirectIndex::null(3));
+
+        let mut column = IndexArrayColumn::<u32>::new();
+        let first = column.insert(1u32);
+        let second = column.insert(2u32);
+        column.free(first);
+        assert_eq!(column.solve_indirect(first), None);
+
+        // the freed slot is reused, and not the degenerate one
+        let reused = column.insert(3u32);
+        assert_eq!(reused.as_int(), first.as_int());
+        assert_eq!(reused.generation(), first.generation() + 1);
+        assert_eq!(column.solve_indirect(first), None);
+        let values = [reused, second].map(|slot| {
+            let direct = column.solve_indirect(slot).unwrap();
+            *column.contiguous()[direct.as_index()].inner_value()
+        });
+        assert_eq!(values, [3, 2]);
+    }
+
+    #[test]
+    fn retain_and_drain() {
+        let mut column = ParallelIndexArrayColumn::<u32>::new();
+        let slots = (0..100u32).map(|i| column.insert(i)).collect::<Vec<_>>();
+
+        column.retain(|_, value| value % 3 != 0);
+        assert_eq!(column.len(), 67);
+        for (i, &slot) in slots.iter().enumerate() {
+            let direct = column.solve_indirect(slot);
+            if i % 3 == 0 {
+                assert!(direct.is_none());
+            } else {
+                assert_eq!(column.contiguous()[direct.unwrap().as_index()], i as u32);
+            }
+        }
+
+        let drained = column.drain_where(|_, value| *value >= 50);
+        assert_eq!(drained.len(), 33);
+        assert!(
+            drained
+                .iter()
+                .all(|(slot, value)| *value >= 50 && *slot == slots[*value as usize])
+        );
+        assert!(column.iter().all(|value| *value < 50));
+        assert!(
+            column
+                .iter()
+                .zip(&column.handles()[1..])
+                .all(|(value, slot)| *slot == slots[*value as usize])
+        );
+
+        // freed slots are reused with a newer generation
+        let reused = column.insert(1000u32);
+        assert!(slots.iter().any(|slot| slot.as_int() == reused.as_int()));
+        assert_ne!(reused.generation(), 0);
+
+        let mut chunked = ChunkedColumn::<u32, 8>::new();
+        let slots = (0..50u32).map(|i| chunked.insert(i)).collect::<Vec<_>>();
+        let drained = chunked.drain_where(|_, value| value % 2 == 1);
+        // exactly the matching elements are drained
+        let mut values = drained.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, (1..50).step_by(2).collect::<Vec<_>>());
+        assert_eq!(chunked.len(), 26);
+        for (i, &slot) in slots.iter().enumerate() {
+            assert_eq!(chunked.get(slot).copied(), (i % 2 == 0).then_some(i as u32));
+        }
+        assert_eq!(
+            chunked.iter().copied().collect::<Vec<_>>(),
+            (0..50).step_by(2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn shrink_after_churn() {
+        let mut column = ParallelIndexArrayColumn::<u64>::with_capacity(16);
+        column.reserve(1000);
+        assert!(column.capacity() >= 1001);
+
+        let slots = (0..1000u64).map(|i| column.insert(i)).collect::<Vec<_>>();
+        slots.iter().skip(10).for_each(|&slot| column.free(slot));
+
+        let before = column.memory_footprint();
+        column.shrink_to_fit();
+        let after = column.memory_footprint();
+
+        assert_eq!(column.capacity(), 11);
+        assert_eq!(before.used, after.used);
+        assert!(after.reserved < before.reserved);
+
+        let mut chunked = ChunkedColumn::<u64, 64>::with_capacity(1024);
+        assert_eq!(chunked.capacity(), 1024);
+        chunked.insert(1u64);
+        chunked.shrink_to_fit();
+        assert_eq!(chunked.capacity(), 64);
+        chunked.reserve(200);
+        assert_eq!(chunked.capacity(), 256);
+    }
+
+    #[test]
+    fn chunked_column_free_across_chunks() {
+        let mut column = ChunkedColumn::<u32, 8>::new();
+
+        let slots = (0..50u32).map(|i| column.insert(i)).collect::<Vec<_>>();
+        assert_eq!(column.len(), 51);
+        assert_eq!(column.chunk_count(), 7);
+
+        for &i in &[37, 14, 0, 49, 8, 3, 21] {
+            column.free(slots[i]);
+            assert!(column.get(slots[i]).is_none());
+        }
+        assert_eq!(column.len(), 44);
+
+        for (i, &slot) in slots.iter().enumerate() {
+            if let Some(&value) = column.get(slot) {
+                assert_eq!(value, i as u32);
+            }
+        }
+
+        // owners stay parallel to the data
+        let values = column.chunks().flatten();
+        let owners = column.handle_chunks().flatten();
+        for (value, owner) in values.zip(owners).skip(1) {
+            assert_eq!(column.get(*owner), Some(value));
+        }
+
+        // freed slots are reused, chunks are not reallocated
+        let reused = column.insert(100u32);
+        assert_eq!(column.get(reused), Some(&100));
+        assert_eq!(column.iter().count(), 44);
+        assert_eq!(column.chunk_count(), 7);
+
+        for (slot, value) in column.iter_slots_mut() {
+            *value = slot.as_int();
+        }
+        assert_eq!(column.get(reused), Some(&reused.as_int()));
+    }
+
+    #[test]
+    fn iter_slots() {
+        let mut column = IndexArrayColumn::<u32>::new();
+        let slots = (0..4u32).map(|i| column.insert(i * 10)).collect::<Vec<_>>();
+        column.free(slots[1]);
+
+        for (slot, value) in column.iter_slots_mut() {
+            *value += 1;
+            assert_ne!(slot.as_int(), 0);
+        }
+        let mut live = column.iter_slots().collect::<Vec<_>>();
+        live.sort();
+        assert_eq!(live, [(slots[0], &1), (slots[2], &21), (slots[3], &31)]);
+
+        let mut column = ParallelIndexArrayColumn::<u32>::new();
+        let a = column.insert(5u32);
+        assert_eq!(column.iter_slots().collect::<Vec<_>>(), [(a, &5)]);
+    }
+
+    #[test]
+    fn sort_after_churn() {
+        let mut column = ParallelIndexArrayColumn::<u32>::new();
+        let mut chunked = ChunkedColumn::<u32, 4>::new();
+        let slots = (0..20u32).map(|i| column.insert(i)).collect::<Vec<_>>();
+        let chunked_slots = (0..20u32).map(|i| chunked.insert(i)).collect::<Vec<_>>();
+        for i in [3, 0, 11, 7, 19] {
+            column.free(slots[i]);
+            chunked.free(chunked_slots[i]);
+        }
+
+        column.sort_by_owner();
+        chunked.sort_by_owner();
+        let owners = column.handles()[1..].iter().map(|owner| owner.as_int());
+        assert!(owners.clone().is_sorted());
+        assert_eq!(owners.count(), 15);
+        let owners = chunked.handle_chunks().flatten().skip(1);
+        assert!(owners.map(|owner| owner.as_int()).is_sorted());
+
+        column.sort_by_key(|_, &value| std::cmp::Reverse(value));
+        assert!(
+            column
+                .iter()
+                .map(|&value| std::cmp::Reverse(value))
+                .is_sorted()
+        );
+
+        for (i, (&slot, &chunked_slot)) in slots.iter().zip(&chunked_slots).enumerate() {
+            let value = column
+                .solve_indirect(slot)
+                .map(|direct| column.contiguous()[direct.as_index()]);
+            assert_eq!(value, chunked.get(chunked_slot).copied());
+            if let Some(value) = value {
+                assert_eq!(value, i as u32);
+            }
+        }
+    }
 }
@@ -0,0 +1,242 @@
+//! Tracking of the elements of a column changed since each section of a
+//! triple buffer was last written.
+//!
+//! Blitting whole partitions every upload costs bandwidth proportional to the
+//! amount of entities, even when only a few of them moved. [`DirtyRanges`]
+//! records the ranges of a column changed by the simulation, so that only
+//! these are uploaded, through
+//! [`PartitionedTriBuffer::blit_part_at`] or [`TriBuffer::blit_section_at`].
+//!
+//! A section of a triple buffer is only written every third upload, so it
+//! must catch up on the changes of the uploads it missed: the ranges are
+//! tracked per section, and taken when the section is written.
+//!
+//! Ranges are merged when they overlap or touch, and the closest ones are
+//! merged when there are more than [`MAX_RANGES`] of them, so that a section
+//! is never uploaded in more than [`MAX_RANGES`] blits. Scattered changes
+//! thus upload the unchanged elements between them: sorting a column by
+//! locality (e.g. with [`sort_by_key`]) keeps the changes of a frame close
+//! together.
+//!
+//! # Example
+//! ```rust,ignore
+//! // in the fixed step
+//! for (i, position) in positions.iter_mut().enumerate().filter(moving) {
+//!     *position += velocity * delta;
+//!     dirty_positions.mark(i);
+//! }
+//!
+//! // in the upload
+//! unsafe {
+//!     dirty_positions.upload_part(storage, section, LayoutEntities::Positions as usize, &positions)
+//! };
+//! ```
+//!
+//! [`PartitionedTriBuffer::blit_part_at`]: crate::render::buffer::PartitionedTriBuffer::blit_part_at
+//! [`TriBuffer::blit_section_at`]: crate::render::buffer::TriBuffer::blit_section_at
+//! [`sort_by_key`]: super::IndexArrayColumn::sort_by_key
+
+use std::ops::Range;
+
+use crate::render::buffer::{PartitionedTriBuffer, TriBuffer};
+
+/// The maximum amount of ranges tracked per section.
+pub const MAX_RANGES: usize = 32;
+
+/// The ranges of a column changed since each section of a triple buffer was
+/// last written.
+///
+/// See the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct DirtyRanges {
+    /// Sorted ranges per section, neither overlapping nor touching.
+    sections: [Vec<Range<usize>>; 3],
+}
+
+impl Default for DirtyRanges {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirtyRanges {
+    /// All elements are dirty, so that each section is fully written by its
+    /// first upload.
+    pub fn new() -> Self {
+        let mut dirty = Self {
+            sections: Default::default(),
+        };
+        dirty.mark_all();
+        dirty
+    }
+
+    pub fn mark(&mut self, index: usize) {
+        self.mark_range(index..index + 1);
+    }
+
+    pub fn mark_range(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        for ranges in &mut self.sections {
+            insert(ranges, range.clone());
+        }
+    }
+
+    /// Mark every element, e.g. after the column was sorted or elements were
+    /// removed from its middle.
+    pub fn mark_all(&mut self) {
+        for ranges in &mut self.sections {
+            ranges.clear();
+            ranges.push(0..usize::MAX);
+        }
+    }
+
+    /// Whether no element changed since `section` was last written.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn is_clean(&self, section: usize) -> bool {
+        self.sections[section].is_empty()
+    }
+
+    /// The ranges changed since `section` was last written.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn ranges(&self, section: usize) -> &[Range<usize>] {
+        &self.sections[section]
+    }
+
+    /// Take the ranges changed since `section` was last written, clamped to
+    /// the `len` of the column, marking the section as clean.
+    ///
+    /// # Panics
+    /// If `section` is not a value within the range (0, 2).
+    pub fn take(&mut self, section: usize, len: usize) -> impl Iterator<Item = Range<usize>> {
+        self.sections[section]
+            .drain(..)
+            .map(move |range| range.start.min(len)..range.end.min(len))
+            .filter(|range| !range.is_empty())
+    }
+
+    /// Blit the ranges of `data` changed since `section` was last written to
+    /// a `partition` of `buffer`, and set the length of the partition to the
+    /// length of `data`.
+    ///
+    /// Elements exceeding the capacity of the partition are dropped, as with
+    /// [`PartitionedTriBuffer::blit_part`].
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    ///
+    /// # Safety
+    /// The caller must ensure that `T` is the actual type of the data in this
+    /// partition, as with [`PartitionedTriBuffer::blit_part`].
+    pub unsafe fn upload_part<T: Sized + Clone + Copy, const PARTS: usize>(
+        &mut self,
+        buffer: &PartitionedTriBuffer<PARTS>,
+        section: usize,
+        partition: usize,
+        data: &[T],
+    ) -> usize {
+        let capacity = buffer.layout().length_at(partition) / size_of::<T>();
+        let mut written = 0;
+        for range in self.take(section, data.len()) {
+            if range.start >= capacity {
+                break;
+            }
+            // SAFETY: the caller guarantees the type of the partition.
+            written +=
+                unsafe { buffer.blit_part_at(section, partition, range.start, &data[range]) };
+        }
+        let length = data.len().min(capacity);
+        buffer.set_length(section, partition, length as u32);
+        written
+    }
+
+    /// Blit the ranges of `data` changed since `section` was last written to
+    /// `buffer`, and set the length of the section to the length of `data`.
+    ///
+    /// Elements exceeding the capacity of the buffer are dropped, as with
+    /// [`TriBuffer::blit_section`].
+    ///
+    /// # Returns
+    /// The amount of elements written.
+    pub fn upload_section<T: Sized + Clone + Copy>(
+        &mut self,
+        buffer: &TriBuffer<T>,
+        section: usize,
+        data: &[T],
+    ) -> usize {
+        let capacity = buffer.capacity();
+        let mut written = 0;
+        for range in self.take(section, data.len()) {
+            if range.start >= capacity {
+                break;
+            }
+            written += buffer.blit_section_at(section, range.start, &data[range]);
+        }
+        buffer.set_length(section, data.len().min(capacity) as u32);
+        written
+    }
+}
+
+/// Insert `range` in the sorted `ranges`, merging it with the ranges it
+/// overlaps or touches, then merging the closest ranges if there are too many.
+fn insert(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    let first = ranges.partition_point(|r| r.end < range.start);
+    let last = ranges.partition_point(|r| r.start <= range.end);
+    if first == last {
+        ranges.insert(first, range);
+    } else {
+        let merged = ranges[first].start.min(range.start)..ranges[last - 1].end.max(range.end);
+        ranges.splice(first..last, [merged]);
+    }
+
+    if ranges.len() > MAX_RANGES {
+        let closest = (0..ranges.len() - 1)
+            .min_by_key(|&i| ranges[i + 1].start - ranges[i].end)
+            .unwrap_or_default();
+        ranges[closest].end = ranges[closest + 1].end;
+        ranges.remove(closest + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_ranges_per_section() {
+        let mut dirty = DirtyRanges::new();
+        assert!(dirty.take(0, 10).eq(std::iter::once(0..10)));
+        assert!(dirty.is_clean(0));
+        assert!(!dirty.is_clean(1));
+        dirty.take(1, 10).for_each(drop);
+        dirty.take(2, 10).for_each(drop);
+
+        dirty.mark(4);
+        dirty.mark(5);
+        dirty.mark_range(8..12);
+        dirty.mark(7);
+        dirty.mark(1);
+        assert_eq!(dirty.ranges(0), [1..2, 4..6, 7..12]);
+
+        // the section written this upload is clean, the others catch up
+        assert_eq!(dirty.take(0, 10).collect::<Vec<_>>(), [1..2, 4..6, 7..10]);
+        dirty.mark(0);
+        assert_eq!(
+            (dirty.ranges(0).len(), dirty.ranges(0).first()),
+            (1, Some(&(0..1)))
+        );
+        assert_eq!(dirty.ranges(1), [0..2, 4..6, 7..12]);
+
+        // the closest ranges are merged past the limit
+        let mut dirty = DirtyRanges::new();
+        dirty.take(0, 0).for_each(drop);
+        (0..=MAX_RANGES).for_each(|i| dirty.mark(i * 10 + (i == 3) as usize * 5));
+        assert_eq!(dirty.ranges(0).len(), MAX_RANGES);
+        assert_eq!(dirty.ranges(0)[2..4], [20..21, 35..41]);
+    }
+}
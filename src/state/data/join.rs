@@ -0,0 +1,156 @@
+//! Iteration over multiple [`ParallelIndexArrayColumn`]s joined by their
+//! owner slots.
+//!
+//! When the same indirect index is used to refer to an entity across multiple
+//! columns, a join yields the values of every column only where all of them
+//! hold a value for that slot.
+//!
+//! The first column of a join drives the iteration: it is iterated
+//! contiguously, while the others are only accessed through their sparse
+//! indices. For best performance, the first column should be the one with the
+//! least elements.
+
+use crate::state::data::{
+    Column, IndirectIndex, ParallelIndexArrayColumn, SparseSlot, column::IterColumn,
+};
+
+/// Solve `slot` to the contiguous index of a live element of `column`.
+///
+/// Unlike [`Column::solve_indirect`], a generation mismatch is not reported,
+/// as it is expected for columns that do not share all of their slots.
+#[inline(always)]
+fn solve_live<T: Default>(
+    column: &ParallelIndexArrayColumn<T>,
+    slot: IndirectIndex,
+) -> Option<usize> {
+    column
+        .slots_map()
+        .get(slot.as_index())
+        .filter(|direct| direct.generation() == slot.generation() && direct.as_int() != 0)
+        .map(|direct| direct.as_index())
+}
+
+/// Iterate the elements of `a` and `b` sharing the same owner slot.
+///
+/// # Returns
+/// An iterator over the owner slot and values of each element present in
+/// both columns, in the contiguous order of `a`.
+pub fn join<'col, A: Default, B: Default>(
+    a: &'col ParallelIndexArrayColumn<A>,
+    b: &'col ParallelIndexArrayColumn<B>,
+) -> impl Iterator<Item = (IndirectIndex, &'col A, &'col B)> {
+    let b_data = b.contiguous();
+    a.iter_slots().filter_map(move |(slot, value)| {
+        solve_live(b, slot).map(|direct| (slot, value, &b_data[direct]))
+    })
+}
+
+/// Iterate the elements of `a`, `b` and `c` sharing the same owner slot.
+///
+/// # Returns
+/// An iterator over the owner slot and values of each element present in all
+/// three columns, in the contiguous order of `a`.
+pub fn join3<'col, A: Default, B: Default, C: Default>(
+    a: &'col ParallelIndexArrayColumn<A>,
+    b: &'col ParallelIndexArrayColumn<B>,
+    c: &'col ParallelIndexArrayColumn<C>,
+) -> impl Iterator<Item = (IndirectIndex, &'col A, &'col B, &'col C)> {
+    let c_data = c.contiguous();
+    join(a, b).filter_map(move |(slot, a, b)| {
+        solve_live(c, slot).map(|direct| (slot, a, b, &c_data[direct]))
+    })
+}
+
+/// Iterate the elements of `a` and `b` sharing the same owner slot, with
+/// mutable access to the values of `a`.
+///
+/// # Returns
+/// An iterator over the owner slot and values of each element present in
+/// both columns, in the contiguous order of `a`.
+pub fn join_mut<'col, A: Default, B: Default>(
+    a: &'col mut ParallelIndexArrayColumn<A>,
+    b: &'col ParallelIndexArrayColumn<B>,
+) -> impl Iterator<Item = (IndirectIndex, &'col mut A, &'col B)> {
+    let b_data = b.contiguous();
+    a.iter_slots_mut().filter_map(move |(slot, value)| {
+        solve_live(b, slot).map(|direct| (slot, value, &b_data[direct]))
+    })
+}
+
+/// Iterate the elements of `a`, `b` and `c` sharing the same owner slot,
+/// with mutable access to the values of `a`.
+///
+/// # Returns
+/// An iterator over the owner slot and values of each element present in all
+/// three columns, in the contiguous order of `a`.
+pub fn join3_mut<'col, A: Default, B: Default, C: Default>(
+    a: &'col mut ParallelIndexArrayColumn<A>,
+    b: &'col ParallelIndexArrayColumn<B>,
+    c: &'col ParallelIndexArrayColumn<C>,
+) -> impl Iterator<Item = (IndirectIndex, &'col mut A, &'col B, &'col C)> {
+    let c_data = c.contiguous();
+    join_mut(a, b).filter_map(move |(slot, a, b)| {
+        solve_live(c, slot).map(|direct| (slot, a, b, &c_data[direct]))
+    })
+}
+
+/// Count the elements of `a` that also have a value in `b`.
+pub fn join_count<A: Default, B: Default>(
+    a: &ParallelIndexArrayColumn<A>,
+    b: &ParallelIndexArrayColumn<B>,
+) -> usize {
+    if a.len() > b.len() {
+        return join_count(b, a);
+    }
+    a.handles()[1..]
+        .iter()
+        .filter(|&&slot| solve_live(b, slot).is_some())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_shared_slots() {
+        let mut positions = ParallelIndexArrayColumn::<f32>::new();
+        let mut velocities = ParallelIndexArrayColumn::<f32>::new();
+        let mut masses = ParallelIndexArrayColumn::<f32>::new();
+
+        // all entities have a position, every other has a velocity
+        let slots = (0..10)
+            .map(|i| {
+                let slot = positions.insert(i as f32);
+                velocities.insert(if i % 2 == 0 { 1.0 } else { 0.0 });
+                masses.insert(2.0);
+                slot
+            })
+            .collect::<Vec<_>>();
+        slots
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .for_each(|&slot| velocities.free(slot));
+        masses.free(slots[4]);
+
+        assert_eq!(join(&positions, &velocities).count(), 5);
+        assert_eq!(join_count(&positions, &velocities), 5);
+        assert_eq!(join_count(&velocities, &positions), 5);
+
+        for (_, position, velocity) in join_mut(&mut positions, &velocities) {
+            *position += velocity;
+        }
+        for (i, &slot) in slots.iter().enumerate() {
+            let direct = positions.solve_indirect(slot).unwrap().as_index();
+            let expected = if i % 2 == 0 { i as f32 + 1.0 } else { i as f32 };
+            assert_eq!(positions.contiguous()[direct], expected);
+        }
+
+        let joined = join3(&positions, &velocities, &masses)
+            .map(|(slot, ..)| slot)
+            .collect::<Vec<_>>();
+        assert_eq!(joined.len(), 4);
+        assert!(!joined.contains(&slots[4]));
+    }
+}
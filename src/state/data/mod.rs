@@ -1,8 +1,17 @@
 pub mod column;
+pub mod dirty;
 pub mod hash;
+pub mod join;
+pub mod slot;
+pub mod staging;
+#[cfg(feature = "tables")]
 pub mod table;
 
-pub use column::{ArrayColumn, IndexArrayColumn, ParallelIndexArrayColumn};
+pub use column::{ArrayColumn, ChunkedColumn, IndexArrayColumn, ParallelIndexArrayColumn};
+pub use dirty::DirtyRanges;
+pub use slot::{Slot, TypedColumn};
+pub use staging::StagingColumn;
+#[cfg(feature = "tables")]
 pub use table::Table;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -39,9 +48,11 @@ impl IndirectIndex {
         }
     }
 
+    /// The same slot with the next generation, so that freed slots can be
+    /// reused by the free list.
     pub const fn next_generation(self) -> Self {
         Self {
-            index: 0,
+            index: self.index,
             generation: self.generation + 1,
         }
     }
@@ -89,13 +100,22 @@ impl DirectIndex {
         }
     }
 
+    /// The same index with the next generation.
     pub const fn next_generation(self) -> Self {
         Self {
-            index: 0,
+            index: self.index,
             generation: self.generation + 1,
         }
     }
 
+    /// The degenerate index with the next generation, marking a freed slot:
+    /// the handles of its previous generation no longer resolve, and the
+    /// handle it is reused with resolves to the degenerate element until it
+    /// is occupied again.
+    pub const fn freed(self) -> Self {
+        Self::null(self.generation + 1)
+    }
+
     pub const fn related_to_indirect(&self, indirect: &IndirectIndex) -> bool {
         self.generation == indirect.generation
     }
@@ -141,6 +161,66 @@ impl Into<usize> for DirectIndex {
     }
 }
 
+impl std::fmt::Display for IndirectIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.index, self.generation)
+    }
+}
+
+impl std::fmt::Display for DirectIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.index, self.generation)
+    }
+}
+
+/// The heap memory usage of a column or table, in bytes.
+///
+/// See [`Column::memory_footprint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MemoryFootprint {
+    /// The bytes occupied by initialised elements.
+    pub used: usize,
+    /// The bytes allocated, including unused capacity.
+    pub reserved: usize,
+}
+
+impl MemoryFootprint {
+    pub const fn of_vec<T>(vec: &Vec<T>) -> Self {
+        Self {
+            used: vec.len() * size_of::<T>(),
+            reserved: vec.capacity() * size_of::<T>(),
+        }
+    }
+
+    /// The bytes allocated but not in use.
+    pub const fn slack(&self) -> usize {
+        self.reserved - self.used
+    }
+}
+
+impl std::ops::Add for MemoryFootprint {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            used: self.used + rhs.used,
+            reserved: self.reserved + rhs.reserved,
+        }
+    }
+}
+
+impl std::ops::AddAssign for MemoryFootprint {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::iter::Sum for MemoryFootprint {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |acc, footprint| acc + footprint)
+    }
+}
+
 pub trait SparseSlot: Default {
     fn slots_map(&self) -> &Vec<DirectIndex>;
 
@@ -180,6 +260,23 @@ pub trait Column<T: Default>: SparseSlot + Default {
     /// The total length of the contiguous data (SoA's).
     fn len(&self) -> usize;
 
+    /// The amount of elements the contiguous data can hold without
+    /// reallocating.
+    fn capacity(&self) -> usize;
+
+    /// Reserve capacity for at least `additional` more elements.
+    fn reserve(&mut self, additional: usize);
+
+    /// Shrink all inner storage as much as possible, releasing the memory
+    /// left over after churn.
+    ///
+    /// This never invalidates indirect indices.
+    fn shrink_to_fit(&mut self);
+
+    /// The heap memory used and reserved by all inner storage of the column,
+    /// including the sparse indices and free list.
+    fn memory_footprint(&self) -> MemoryFootprint;
+
     /// Solve the given indirect index.
     ///
     /// The returned direct index is not a stable index and will change
@@ -250,3 +347,18 @@ pub trait Column<T: Default>: SparseSlot + Default {
     /// Returns the indirect index of the newly inserted element.
     fn insert<V: Into<T>>(&mut self, value: V) -> IndirectIndex;
 }
+
+/// Per-entity data freed without knowing the type of its elements, see
+/// [`StateHandler::entity_columns`](crate::StateHandler::entity_columns).
+///
+/// Implemented by all columns and by the tables of `table_spec!`.
+pub trait EntityColumn {
+    /// Free the element owned by `entity`, if any.
+    ///
+    /// Unlike [`Column::free`], handles which are out of bounds, stale or
+    /// degenerate are ignored, as not every column holds every entity.
+    ///
+    /// # Returns
+    /// Whether the column held the entity.
+    fn free_entity(&mut self, entity: IndirectIndex) -> bool;
+}
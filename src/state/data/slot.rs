@@ -0,0 +1,245 @@
+//! Typed slot handles.
+//!
+//! An [`IndirectIndex`] is only meaningful in the column that issued it, but
+//! nothing stops a position slot from being used on the rotation column,
+//! silently reading or freeing the wrong element. A [`Slot`] tags the index
+//! with a marker type, and a [`TypedColumn`] only accepts the slots of its own
+//! marker, turning cross-column misuse into a type error.
+//!
+//! Marker types and their slot aliases are declared with [`slot_types!`].
+//!
+//! ```compile_fail
+//! use ethel::{
+//!     slot_types,
+//!     state::data::{IndexArrayColumn, slot::TypedColumn},
+//! };
+//!
+//! slot_types! {
+//!     pub PositionSlot;
+//!     pub RotationSlot;
+//! }
+//!
+//! let mut positions = TypedColumn::<IndexArrayColumn<[f32; 3]>, PositionSlotMarker>::default();
+//! let rotations = TypedColumn::<IndexArrayColumn<[f32; 4]>, RotationSlotMarker>::default();
+//!
+//! let position: PositionSlot = positions.insert([0.0; 3]);
+//! rotations.solve(position); // expected `RotationSlot`, found `PositionSlot`
+//! ```
+//!
+//! [`slot_types!`]: crate::slot_types
+
+use std::marker::PhantomData;
+
+use crate::state::data::{Column, DirectIndex, IndirectIndex, column::IterColumn};
+
+/// An [`IndirectIndex`] into the columns tagged with the marker `M`.
+pub struct Slot<M> {
+    index: IndirectIndex,
+    _marker: PhantomData<fn() -> M>,
+}
+
+impl<M> Slot<M> {
+    /// Tag an untyped `index` with the marker `M`.
+    ///
+    /// This is the only unchecked conversion: `index` must have been issued
+    /// by a column of `M`.
+    pub const fn new(index: IndirectIndex) -> Self {
+        Self {
+            index,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn index(self) -> IndirectIndex {
+        self.index
+    }
+}
+
+impl<M> Clone for Slot<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for Slot<M> {}
+
+impl<M> Default for Slot<M> {
+    fn default() -> Self {
+        Self::new(IndirectIndex::default())
+    }
+}
+
+impl<M> PartialEq for Slot<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<M> Eq for Slot<M> {}
+
+impl<M> std::hash::Hash for Slot<M> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<M> std::fmt::Debug for Slot<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let marker = std::any::type_name::<M>();
+        let marker = marker.rsplit("::").next().unwrap_or(marker);
+        write!(f, "Slot<{marker}>({})", self.index)
+    }
+}
+
+impl<M> std::fmt::Display for Slot<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.index.fmt(f)
+    }
+}
+
+impl<M> From<Slot<M>> for IndirectIndex {
+    fn from(slot: Slot<M>) -> Self {
+        slot.index
+    }
+}
+
+/// A column `C` only accepting the [`Slot`]s of the marker `M`.
+///
+/// Dereferences to the inner column for iteration and other slot-agnostic
+/// operations.
+#[derive(Debug)]
+pub struct TypedColumn<C, M> {
+    column: C,
+    _marker: PhantomData<fn() -> M>,
+}
+
+impl<C: Default, M> Default for TypedColumn<C, M> {
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+impl<C, M> TypedColumn<C, M> {
+    pub fn new(column: C) -> Self {
+        Self {
+            column,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.column
+    }
+
+    /// Add an element `value` to the column.
+    ///
+    /// See [`Column::insert`].
+    pub fn insert<T: Default, V: Into<T>>(&mut self, value: V) -> Slot<M>
+    where
+        C: Column<T>,
+    {
+        Slot::new(self.column.insert(value))
+    }
+
+    /// Solve the given `slot` to its current direct index.
+    ///
+    /// See [`Column::solve_indirect`].
+    pub fn solve<T: Default>(&self, slot: Slot<M>) -> Option<DirectIndex>
+    where
+        C: Column<T>,
+    {
+        self.column.solve_indirect(slot.index)
+    }
+
+    /// Mark the given `slot` as free.
+    ///
+    /// See [`Column::free`].
+    pub fn free<T: Default>(&mut self, slot: Slot<M>)
+    where
+        C: Column<T>,
+    {
+        self.column.free(slot.index)
+    }
+
+    /// Get the element at `slot`, if still present.
+    pub fn get<'a, T, R>(&'a self, slot: Slot<M>) -> Option<&'a R>
+    where
+        T: Default,
+        R: Default + std::borrow::Borrow<T> + std::borrow::BorrowMut<T> + 'a,
+        C: Column<T> + IterColumn<'a, T, R>,
+    {
+        let direct = self.column.solve_indirect(slot.index)?;
+        self.column.contiguous().get(direct.as_index())
+    }
+}
+
+impl<C, M> std::ops::Deref for TypedColumn<C, M> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.column
+    }
+}
+
+impl<C, M> std::ops::DerefMut for TypedColumn<C, M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.column
+    }
+}
+
+/// Declare slot marker types and their [`Slot`] aliases.
+///
+/// Each `$vis $name;` declares an uninhabited `[<$name Marker>]` type and the
+/// alias `$name = Slot<[<$name Marker>]>`.
+///
+/// # Example
+/// ```rust,ignore
+/// slot_types! {
+///     pub PositionSlot;
+///     pub(crate) RotationSlot;
+/// }
+///
+/// struct Entity {
+///     position: PositionSlot,
+///     rotation: RotationSlot,
+/// }
+/// ```
+///
+/// [`Slot`]: crate::state::data::slot::Slot
+#[macro_export]
+macro_rules! slot_types {
+    ($($vis:vis $name:ident;)+) => {
+        paste::paste! {
+            $(
+                #[derive(Debug)]
+                $vis enum [< $name Marker >] {}
+
+                $vis type $name = $crate::state::data::slot::Slot<[< $name Marker >]>;
+            )+
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state::data::{IndexArrayColumn, column::Entry};
+
+    use super::*;
+
+    crate::slot_types! {
+        PositionSlot;
+    }
+
+    #[test]
+    fn typed_column() {
+        let mut positions = TypedColumn::<IndexArrayColumn<u32>, PositionSlotMarker>::default();
+        let a: PositionSlot = positions.insert(7u32);
+        let b = positions.insert(9u32);
+
+        assert_eq!(positions.get(b).map(Entry::inner_value), Some(&9));
+        positions.free(a);
+        assert!(positions.solve(a).is_none());
+        assert_eq!(positions.get(b).map(Entry::owner), Some(b.index()));
+        assert_eq!(format!("{b:?}"), "Slot<PositionSlotMarker>(2:0)");
+    }
+}
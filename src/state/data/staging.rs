@@ -0,0 +1,113 @@
+//! Conversion of CPU-layout column data to its GPU layout.
+//!
+//! The layout that is best for the simulation is often not the layout the
+//! GPU expects, e.g. `Vec3` positions must be padded to `vec4` in std430
+//! storage blocks. A [`StagingColumn`] keeps a GPU-layout copy of a column,
+//! converted through a configurable function, and ready to be blitted as is.
+//!
+//! Staging columns are synchronised in [`StateHandler::sync_staging`], which
+//! the [`State`] calls once per upload, right before
+//! [`StateHandler::upload_gpu`]. Each column converts its data at most once
+//! per upload, so it can be synchronised from multiple places without
+//! converting twice.
+//!
+//! [`StateHandler::sync_staging`]: crate::StateHandler::sync_staging
+//! [`StateHandler::upload_gpu`]: crate::StateHandler::upload_gpu
+//! [`State`]: crate::state::State
+
+use glam::{Vec3, Vec4};
+
+/// A GPU-layout copy of a column of `C`, converted to `G`.
+#[derive(Clone, Debug)]
+pub struct StagingColumn<C, G> {
+    convert: fn(&C) -> G,
+    staged: Vec<G>,
+    /// The upload the data was last converted for.
+    synced: Option<u64>,
+}
+
+impl<C, G> StagingColumn<C, G> {
+    pub fn new(convert: fn(&C) -> G) -> Self {
+        Self {
+            convert,
+            staged: Vec::new(),
+            synced: None,
+        }
+    }
+
+    pub fn with_capacity(convert: fn(&C) -> G, capacity: usize) -> Self {
+        Self {
+            staged: Vec::with_capacity(capacity),
+            ..Self::new(convert)
+        }
+    }
+
+    /// Convert `data` for the upload `frame`, unless it was already
+    /// converted for it.
+    ///
+    /// # Returns
+    /// Whether the data was converted.
+    pub fn sync(&mut self, frame: u64, data: &[C]) -> bool {
+        if self.synced == Some(frame) {
+            return false;
+        }
+        self.synced = Some(frame);
+        self.convert(data);
+        true
+    }
+
+    /// Convert `data` unconditionally.
+    pub fn convert(&mut self, data: &[C]) {
+        self.staged.clear();
+        self.staged.extend(data.iter().map(self.convert));
+    }
+
+    /// The converted data, in the same order as the source column.
+    pub fn staged(&self) -> &[G] {
+        &self.staged
+    }
+
+    /// The upload the data was last synchronised for.
+    pub fn synced_frame(&self) -> Option<u64> {
+        self.synced
+    }
+}
+
+impl StagingColumn<Vec3, Vec4> {
+    /// Pad `Vec3` data to `Vec4`, as required by std430 storage blocks.
+    ///
+    /// The `w` component is set to `1.0`, as positions are the common case.
+    pub fn vec3_to_vec4() -> Self {
+        Self::new(|v| v.extend(1.0))
+    }
+}
+
+impl<C: Copy> StagingColumn<C, C> {
+    /// A staging column which copies the data as is, e.g. to snapshot a
+    /// column that is mutated while the upload reads it.
+    pub fn identity() -> Self {
+        Self::new(|&v| v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_once_per_upload() {
+        let mut positions = StagingColumn::vec3_to_vec4();
+        let data = [Vec3::X, Vec3::Y];
+
+        assert!(positions.sync(1, &data));
+        assert!(!positions.sync(1, &[Vec3::Z]));
+        assert_eq!(
+            positions.staged(),
+            [Vec4::new(1.0, 0.0, 0.0, 1.0), Vec4::new(0.0, 1.0, 0.0, 1.0)]
+        );
+
+        let mut health = StagingColumn::new(|&h: &f32| (h * 100.0) as u32);
+        assert!(health.sync(2, &[0.5]));
+        assert_eq!(health.staged(), [50]);
+    }
+}
@@ -791,6 +791,80 @@ macro_rules! table_spec {
                 }
             }
 
+            impl [< $name TableDef >] {
+                /// Create a builder for a row, with all fields initialised to
+                /// their [`Default`] value.
+                pub fn builder() -> [< $name RowBuilder >] {
+                    [< $name RowBuilder >]::default()
+                }
+            }
+
+            /// A builder for a detached row.
+            ///
+            /// The built row can be inserted immediately, or stored to be
+            /// inserted at a later point, for example when spawning rows while
+            /// iterating the table.
+            #[derive(Clone, Debug, Default)]
+            pub struct [< $name RowBuilder >] {
+                $row_0: $rt_0,
+                $(
+                    $row: $rt,
+                )+
+            }
+
+            impl [< $name RowBuilder >] {
+                pub fn $row_0(mut self, value: $rt_0) -> Self {
+                    self.$row_0 = value;
+                    self
+                }
+
+                $(
+                    pub fn $row(mut self, value: $rt) -> Self {
+                        self.$row = value;
+                        self
+                    }
+                )+
+
+                pub fn build(self) -> [< $name TableDef >] {
+                    [< $name TableDef >]((self.$row_0, $(self.$row,)+))
+                }
+            }
+
+            impl From<[< $name RowBuilder >]> for [< $name TableDef >] {
+                fn from(value: [< $name RowBuilder >]) -> [< $name TableDef >] {
+                    value.build()
+                }
+            }
+
+            /// A builder for a row inserted in its table on
+            /// [`build`](Self::build).
+            ///
+            /// See the `spawn` function of the table.
+            #[derive(Debug)]
+            pub struct [< $name RowSpawner >]<'table> {
+                table: &'table mut [< $name RowTable >],
+                row: [< $name RowBuilder >],
+            }
+
+            impl<'table> [< $name RowSpawner >]<'table> {
+                pub fn $row_0(mut self, value: $rt_0) -> Self {
+                    self.row.$row_0 = value;
+                    self
+                }
+
+                $(
+                    pub fn $row(mut self, value: $rt) -> Self {
+                        self.row.$row = value;
+                        self
+                    }
+                )+
+
+                /// Insert the row in the table.
+                pub fn build(self) -> [< $name RowHandle >] {
+                    self.table.insert_row(self.row)
+                }
+            }
+
             #[derive(Debug, Clone, Copy)]
             pub struct [< $name RowTableView >]<'view> {
                 pub indirect_indices: &'view [$crate::state::data::DirectIndex],
@@ -921,6 +995,40 @@ macro_rules! table_spec {
                 }
             }
 
+            /// A typed handle to a row of its table.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+            pub struct [< $name RowHandle >](pub $crate::state::data::IndirectIndex);
+
+            impl From<$crate::state::data::IndirectIndex> for [< $name RowHandle >] {
+                fn from(value: $crate::state::data::IndirectIndex) -> Self {
+                    Self(value)
+                }
+            }
+
+            impl From<[< $name RowHandle >]> for $crate::state::data::IndirectIndex {
+                fn from(value: [< $name RowHandle >]) -> Self {
+                    value.0
+                }
+            }
+
+            /// References to each field of a single row.
+            #[derive(Debug, Clone, Copy)]
+            pub struct [< $name RowRef >]<'row> {
+                pub $row_0: &'row $rt_0,
+                $(
+                    pub $row: &'row $rt,
+                )+
+            }
+
+            /// Mutable references to each field of a single row.
+            #[derive(Debug)]
+            pub struct [< $name RowMut >]<'row> {
+                pub $row_0: &'row mut $rt_0,
+                $(
+                    pub $row: &'row mut $rt,
+                )+
+            }
+
             #[derive(Debug)]
             pub struct [< $name RowTable >] {
                 indices: Vec<$crate::state::data::DirectIndex>,
@@ -963,38 +1071,50 @@ macro_rules! table_spec {
                     self.$row_0.len()
                 }
 
-                fn size(&self) -> usize {
-                    self.indices.len()
+                fn capacity(&self) -> usize {
+                    self.$row_0.capacity()
+                    $(
+                        .min(self.$row.capacity())
+                    )+
                 }
 
-                fn free(&mut self, slot: $crate::state::data::IndirectIndex) {
-                    if slot.as_int() == 0 {
-                        panic!("slot 0 is reserved for degenerate elements and must not be freed");
-                    }
+                fn reserve(&mut self, additional: usize) {
+                    self.indices.reserve(additional);
+                    self.handles.reserve(additional);
+                    self.$row_0.reserve(additional);
+                    $(
+                        self.$row.reserve(additional);
+                    )+
+                }
 
-                    let contiguous_slot = self.indices[slot.as_index()];
-                    if !contiguous_slot.related_to_indirect(&slot) || contiguous_slot.as_int() == 0 {
-                        return;
-                    }
+                fn shrink_to_fit(&mut self) {
+                    self.indices.shrink_to_fit();
+                    self.handles.shrink_to_fit();
+                    self.free.shrink_to_fit();
+                    self.$row_0.shrink_to_fit();
+                    $(
+                        self.$row.shrink_to_fit();
+                    )+
+                }
 
-                    let last_owner = *self
-                        .handles
-                        .last()
-                        .expect("contiguous vectors are never empty");
+                fn memory_footprint(&self) -> $crate::state::data::MemoryFootprint {
+                    use $crate::state::data::MemoryFootprint;
 
-                    self.indices[slot.as_index()] = contiguous_slot.next_generation();
-                    // do not reassign slot if we are freeing last
-                    if last_owner.as_index() != slot.as_index() {
-                        self.indices[last_owner.as_index()] = contiguous_slot;
-                    }
+                    MemoryFootprint::of_vec(&self.indices)
+                        + MemoryFootprint::of_vec(&self.handles)
+                        + MemoryFootprint::of_vec(&self.free)
+                        + MemoryFootprint::of_vec(&self.$row_0)
+                        $(
+                            + MemoryFootprint::of_vec(&self.$row)
+                        )+
+                }
 
-                    let contiguous_index = contiguous_slot.as_index();
-                    self.handles.swap_remove(contiguous_index);
-                    self.$row_0.swap_remove(contiguous_index);
-                    $(
-                        self.$row.swap_remove(contiguous_index);
-                    )+
-                    self.free.push(slot.next_generation());
+                fn size(&self) -> usize {
+                    self.indices.len()
+                }
+
+                fn free(&mut self, slot: $crate::state::data::IndirectIndex) {
+                    self.remove(slot);
                 }
 
                 fn insert<V: Into<[< $name TableDef >]>>(&mut self, element: V) -> $crate::state::data::IndirectIndex {
@@ -1016,6 +1136,17 @@ macro_rules! table_spec {
                 }
             }
 
+            impl $crate::state::data::EntityColumn for [< $name RowTable >] {
+                fn free_entity(&mut self, entity: $crate::state::data::IndirectIndex) -> bool {
+                    use $crate::state::data::Column;
+
+                    match self.solve_indirect(entity) {
+                        Some(index) if index.as_int() != 0 => self.remove(entity).is_some(),
+                        _ => false,
+                    }
+                }
+            }
+
             impl [< $name RowTable >] {
                 pub fn new() -> Self {
                     Self {
@@ -1052,6 +1183,137 @@ macro_rules! table_spec {
                     }
                 }
 
+                /// Insert a `row`, returning its typed handle.
+                ///
+                /// See `Column::insert`.
+                pub fn insert_row<V: Into<[< $name TableDef >]>>(&mut self, row: V) -> [< $name RowHandle >] {
+                    use $crate::state::data::Column;
+
+                    [< $name RowHandle >](self.insert(row))
+                }
+
+                /// Start building a row that is inserted in this table once
+                /// built.
+                ///
+                /// All fields that are not set are initialised to their
+                /// [`Default`] value.
+                pub fn spawn(&mut self) -> [< $name RowSpawner >]<'_> {
+                    [< $name RowSpawner >] {
+                        table: self,
+                        row: Default::default(),
+                    }
+                }
+
+                /// Insert all `rows`, for example rows built and deferred
+                /// while iterating this table.
+                ///
+                /// # Returns
+                /// The handles of the inserted rows, in order.
+                pub fn insert_rows<V, I>(&mut self, rows: I) -> Vec<[< $name RowHandle >]>
+                where
+                    V: Into<[< $name TableDef >]>,
+                    I: IntoIterator<Item = V>,
+                {
+                    rows.into_iter().map(|row| self.insert_row(row)).collect()
+                }
+
+                /// Whether `handle` points to a live row.
+                pub fn contains<H: Into<$crate::state::data::IndirectIndex>>(&self, handle: H) -> bool {
+                    self.solve_live(handle.into()).is_some()
+                }
+
+                /// The contiguous index of the row pointed to by `handle` in
+                /// each column, e.g. to index data packed from the columns.
+                ///
+                /// Returns `None` if `handle` is not valid or the row has been
+                /// removed.
+                pub fn index_of<H: Into<$crate::state::data::IndirectIndex>>(&self, handle: H) -> Option<usize> {
+                    self.solve_live(handle.into())
+                }
+
+                /// Get references to each field of the row pointed to by
+                /// `handle`.
+                ///
+                /// Returns `None` if `handle` is not valid or the row has been
+                /// removed.
+                pub fn get<H: Into<$crate::state::data::IndirectIndex>>(&self, handle: H) -> Option<[< $name RowRef >]<'_>> {
+                    let direct = self.solve_live(handle.into())?;
+                    Some([< $name RowRef >] {
+                        $row_0: &self.$row_0[direct],
+                        $(
+                            $row: &self.$row[direct],
+                        )+
+                    })
+                }
+
+                /// Get mutable references to each field of the row pointed to
+                /// by `handle`.
+                ///
+                /// Returns `None` if `handle` is not valid or the row has been
+                /// removed.
+                pub fn get_mut<H: Into<$crate::state::data::IndirectIndex>>(&mut self, handle: H) -> Option<[< $name RowMut >]<'_>> {
+                    let direct = self.solve_live(handle.into())?;
+                    Some([< $name RowMut >] {
+                        $row_0: &mut self.$row_0[direct],
+                        $(
+                            $row: &mut self.$row[direct],
+                        )+
+                    })
+                }
+
+                /// Remove the row pointed to by `handle`, returning its values.
+                ///
+                /// Returns `None` if the row has already been removed, i.e.
+                /// `handle` is of an older generation of its slot.
+                ///
+                /// # Panics
+                /// * If `handle` is out of bounds in the sparse index array
+                /// * If `handle` points to slot `0`, since it is a reserved
+                ///   slot to mark degenerate elements
+                pub fn remove<H: Into<$crate::state::data::IndirectIndex>>(&mut self, handle: H) -> Option<[< $name TableDef >]> {
+                    let slot = handle.into();
+                    if slot.as_int() == 0 {
+                        panic!("slot 0 is reserved for degenerate elements and must not be freed");
+                    }
+
+                    let contiguous_slot = self.indices[slot.as_index()];
+                    if !contiguous_slot.related_to_indirect(&slot) || contiguous_slot.as_int() == 0 {
+                        return None;
+                    }
+
+                    let last_owner = *self
+                        .handles
+                        .last()
+                        .expect("contiguous vectors are never empty");
+
+                    self.indices[slot.as_index()] = contiguous_slot.freed();
+                    // do not reassign slot if we are freeing last
+                    if last_owner.as_index() != slot.as_index() {
+                        self.indices[last_owner.as_index()] = contiguous_slot;
+                    }
+
+                    let contiguous_index = contiguous_slot.as_index();
+                    self.handles.swap_remove(contiguous_index);
+                    let row = (
+                        self.$row_0.swap_remove(contiguous_index),
+                        $(
+                            self.$row.swap_remove(contiguous_index),
+                        )+
+                    );
+                    self.free.push(slot.next_generation());
+
+                    Some([< $name TableDef >](row))
+                }
+
+                /// Solve `slot` to the contiguous index of a live row.
+                fn solve_live(&self, slot: $crate::state::data::IndirectIndex) -> Option<usize> {
+                    use $crate::state::data::Column;
+
+                    self.solve_indirect(slot)
+                        .map(|direct| direct.as_index())
+                        .filter(|&direct| direct != 0)
+                }
+
                 pub fn clear(&mut self) {
                     self.indices.resize(1, $crate::state::data::DirectIndex::default());
                     self.handles.resize(1, $crate::state::data::IndirectIndex::default());
@@ -1064,6 +1326,80 @@ macro_rules! table_spec {
                     self.free.clear();
                 }
 
+                /// Retain only the rows for which `f` returns `true`, freeing
+                /// all others in a single pass.
+                ///
+                /// Unlike `Column::free`,
+                /// this preserves the relative order of the retained rows.
+                pub fn retain<F>(&mut self, f: F)
+                where
+                    F: FnMut($crate::state::data::IndirectIndex, (&$rt_0, $(&$rt,)+)) -> bool,
+                {
+                    let head = self.compact_where(f);
+                    self.handles.truncate(head);
+                    self.$row_0.truncate(head);
+                    $(
+                        self.$row.truncate(head);
+                    )+
+                }
+
+                /// Free all rows for which `f` returns `true` in a single
+                /// pass, returning their owner index and values, like
+                /// `Vec::extract_if`.
+                ///
+                /// See [`Self::retain`].
+                pub fn drain_where<F>(&mut self, mut f: F) -> Vec<($crate::state::data::IndirectIndex, [< $name TableDef >])>
+                where
+                    F: FnMut($crate::state::data::IndirectIndex, (&$rt_0, $(&$rt,)+)) -> bool,
+                {
+                    let head = self.compact_where(|slot, row| !f(slot, row));
+                    let mut $row_0 = self.$row_0.split_off(head).into_iter();
+                    $(
+                        let mut $row = self.$row.split_off(head).into_iter();
+                    )+
+                    self.handles
+                        .split_off(head)
+                        .into_iter()
+                        .map(|handle| {
+                            let row = (
+                                $row_0.next().expect("rows are parallel"),
+                                $($row.next().expect("rows are parallel"),)+
+                            );
+                            (handle, [< $name TableDef >](row))
+                        })
+                        .collect()
+                }
+
+                /// Move all rows to keep to the front, updating their indices
+                /// and freeing the slots of all others.
+                ///
+                /// Returns the length of the retained rows.
+                fn compact_where<F>(&mut self, mut keep: F) -> usize
+                where
+                    F: FnMut($crate::state::data::IndirectIndex, (&$rt_0, $(&$rt,)+)) -> bool,
+                {
+                    let mut head = 1;
+                    for i in 1..self.handles.len() {
+                        let owner = self.handles[i];
+                        if keep(owner, (&self.$row_0[i], $(&self.$row[i],)+)) {
+                            if head != i {
+                                self.handles.swap(head, i);
+                                self.$row_0.swap(head, i);
+                                $(
+                                    self.$row.swap(head, i);
+                                )+
+                            }
+                            self.indices[owner.as_index()] =
+                                $crate::state::data::DirectIndex::from_index(head, owner.generation());
+                            head += 1;
+                        } else {
+                            self.indices[owner.as_index()] = self.indices[owner.as_index()].freed();
+                            self.free.push(owner.next_generation());
+                        }
+                    }
+                    head
+                }
+
                 /// Returns the "reverse map" for the handle of each element.
                 ///
                 /// Each handle corresponds in parallel to an element in all
@@ -1281,6 +1617,126 @@ mod tests {
         let view = TestRowTableView::from(&tab);
     }
 
+    #[test]
+    fn typed_row_access() {
+        table_spec! {
+            struct Test {
+                name: String;
+                health: f32;
+            }
+        };
+
+        let mut table = TestRowTable::new();
+        let a = table.insert_row((String::from("a"), 10.0));
+        let b = table.insert_row((String::from("b"), 20.0));
+
+        assert_eq!(table.get(a).unwrap().name, "a");
+        *table.get_mut(b).unwrap().health -= 5.0;
+        assert_eq!(*table.get(b).unwrap().health, 15.0);
+
+        let TestTableDef((name, health)) = table.remove(a).unwrap();
+        assert_eq!((name.as_str(), health), ("a", 10.0));
+        assert!(!table.contains(a));
+        assert!(table.get(a).is_none());
+        assert!(table.remove(a).is_none());
+
+        // b was moved into the removed row, its handle is still valid
+        assert_eq!(table.get(b).unwrap().name, "b");
+
+        // the removed slot is reused with a newer generation
+        let c = table.insert_row((String::from("c"), 0.0));
+        assert_eq!(c.0.as_int(), a.0.as_int());
+        assert!(table.get(a).is_none());
+        assert_eq!(table.get(c).unwrap().name, "c");
+    }
+
+    #[test]
+    #[should_panic]
+    fn remove_out_of_bounds() {
+        table_spec! {
+            struct Test {
+                name: String;
+                health: f32;
+            }
+        };
+
+        let mut table = TestRowTable::new();
+        table.insert_row((String::from("a"), 10.0));
+        table.remove(crate::state::data::IndirectIndex::from_int(99, 0));
+    }
+
+    #[test]
+    fn build_rows() {
+        table_spec! {
+            struct Test {
+                position: (f32, f32);
+                tag: &'static str;
+                health: f32;
+            }
+        };
+
+        let mut table = TestRowTable::new();
+        let player = table.spawn().position((1.0, 2.0)).tag("player").build();
+
+        // rows spawned while iterating are deferred
+        let deferred = table
+            .tag_view()
+            .into_iter()
+            .map(|_| TestTableDef::builder().tag("enemy").health(10.0))
+            .collect::<Vec<_>>();
+        let enemies = table.insert_rows(deferred);
+        assert_eq!(enemies.len(), 1);
+
+        let row = table.get(player).unwrap();
+        assert_eq!(
+            (*row.position, *row.tag, *row.health),
+            ((1.0, 2.0), "player", 0.0)
+        );
+
+        let row = table.get(enemies[0]).unwrap();
+        assert_eq!(
+            (*row.position, *row.tag, *row.health),
+            ((0.0, 0.0), "enemy", 10.0)
+        );
+    }
+
+    #[test]
+    fn retain_and_drain_rows() {
+        use crate::state::data::Column;
+
+        table_spec! {
+            struct Test {
+                a: u32;
+                b: u32;
+            }
+        };
+
+        let mut table = TestRowTable::new();
+        let slots = (0..40u32)
+            .map(|i| table.insert((i, i * 10)))
+            .collect::<Vec<_>>();
+
+        table.retain(|_, (a, _)| a % 4 != 0);
+        assert_eq!(table.len(), 31);
+
+        let drained = table.drain_where(|_, (_, b)| *b >= 200);
+        assert_eq!(drained.len(), 15);
+        for (slot, TestTableDef((a, b))) in drained {
+            assert!(a >= 20);
+            assert_eq!(slot, slots[a as usize]);
+            assert_eq!(b, a * 10);
+            assert!(table.solve_indirect(slot).is_none());
+        }
+
+        for (i, &slot) in slots.iter().enumerate() {
+            if let Some(direct) = table.solve_indirect(slot) {
+                assert!(i % 4 != 0 && i < 20);
+                assert_eq!(table.a[direct.as_index()], i as u32);
+                assert_eq!(table.handles[direct.as_index()], slot);
+            }
+        }
+    }
+
     #[test]
     fn free_last_after_random_free() {
         use crate::state::data::{Column, IndirectIndex};
@@ -0,0 +1,139 @@
+//! Human-readable descriptions of entities for development logs.
+//!
+//! Entities are spread across columns, so there is no single value to
+//! `Debug` print: [`EntityDebug`] collects the resolved state of an entity
+//! from wherever it lives and displays it in one line, or one field per line
+//! with the alternate flag (`{:#}`).
+//!
+//! # Example
+//! ```rust,ignore
+//! let entity = EntityDebug::new(id)
+//!     .slot("transforms", transforms.direct_of(id))
+//!     .position(positions[id])
+//!     .rotation(rotations[id])
+//!     .mesh(mesh, "crate");
+//! tracing::debug!("{entity}");
+//! // entity 12:0 { slots: [transforms 4:0], position: (1.000, 0.000, -2.500),
+//! //   rotation: (yaw 90.0°, pitch 0.0°, roll 0.0°), mesh: 3 "crate" }
+//! ```
+
+use std::fmt::Write;
+
+use glam::{EulerRot, Quat, Vec3};
+
+use crate::{
+    mesh,
+    state::data::{DirectIndex, IndirectIndex},
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct EntityDebug<'a> {
+    id: IndirectIndex,
+    slots: Vec<(&'a str, DirectIndex)>,
+    position: Option<Vec3>,
+    rotation: Option<Quat>,
+    mesh: Option<(mesh::Id, &'a str)>,
+}
+
+impl<'a> EntityDebug<'a> {
+    pub fn new(id: IndirectIndex) -> Self {
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
+
+    /// Add the resolved `slot` of the entity in the column `name`.
+    pub fn slot(mut self, name: &'a str, slot: DirectIndex) -> Self {
+        self.slots.push((name, slot));
+        self
+    }
+
+    pub fn position(mut self, position: Vec3) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// The rotation of the entity, displayed as Euler angles in degrees.
+    pub fn rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    pub fn mesh(mut self, id: mesh::Id, name: &'a str) -> Self {
+        self.mesh = Some((id, name));
+        self
+    }
+}
+
+impl std::fmt::Display for EntityDebug<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = if f.alternate() { "\n    " } else { " " };
+        let mut fields = Vec::with_capacity(4);
+
+        if !self.slots.is_empty() {
+            let mut slots = String::from("slots: [");
+            for (i, (name, slot)) in self.slots.iter().enumerate() {
+                if i > 0 {
+                    slots.push_str(", ");
+                }
+                write!(slots, "{name} {slot}")?;
+            }
+            slots.push(']');
+            fields.push(slots);
+        }
+        if let Some(p) = self.position {
+            let p = p + 0.0;
+            fields.push(format!("position: ({:.3}, {:.3}, {:.3})", p.x, p.y, p.z));
+        }
+        if let Some(rotation) = self.rotation {
+            let (yaw, pitch, roll) = rotation.to_euler(EulerRot::YXZ);
+            fields.push(format!(
+                "rotation: (yaw {:.1}°, pitch {:.1}°, roll {:.1}°)",
+                degrees(yaw),
+                degrees(pitch),
+                degrees(roll)
+            ));
+        }
+        if let Some((id, name)) = self.mesh {
+            fields.push(format!("mesh: {id} {name:?}"));
+        }
+
+        write!(f, "entity {} {{", self.id)?;
+        for (i, field) in fields.iter().enumerate() {
+            let comma = if i + 1 < fields.len() { "," } else { "" };
+            write!(f, "{separator}{field}{comma}")?;
+        }
+        let end = if f.alternate() { "\n" } else { " " };
+        write!(f, "{end}}}")
+    }
+}
+
+/// Degrees of `radians`, without negative zeros.
+fn degrees(radians: f32) -> f32 {
+    radians.to_degrees() + 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_entity() {
+        let entity = EntityDebug::new(IndirectIndex::from_int(12, 0))
+            .slot("transforms", DirectIndex::from_int(4, 1))
+            .position(glam::vec3(1.0, 0.0, -2.5))
+            .rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2))
+            .mesh(unsafe { mesh::Id::from_value(3) }, "crate");
+
+        assert_eq!(
+            entity.to_string(),
+            "entity 12:0 { slots: [transforms 4:1], position: (1.000, 0.000, -2.500), \
+             rotation: (yaw 90.0°, pitch 0.0°, roll 0.0°), mesh: 3 \"crate\" }"
+        );
+        assert_eq!(
+            format!("{:#}", EntityDebug::new(Default::default())),
+            "entity 0:0 {\n}"
+        );
+    }
+}
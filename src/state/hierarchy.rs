@@ -0,0 +1,241 @@
+//! Parent-child relations between entities, and the composition of their
+//! world transforms.
+//!
+//! A [`Hierarchy`] only stores the relations: transforms stay in the columns
+//! of their table, as transforms local to the parent of each entity. Every
+//! update, local transforms are packed into matrices (see
+//! [`pack_trs_matrices`](crate::render::buffer::pack::pack_trs_matrices)),
+//! and [`Hierarchy::propagate`] composes them in place into world matrices,
+//! visiting parents before their children. The resolved matrices are then
+//! uploaded instead of the raw local columns.
+//!
+//! Relations are not removed with their entities: call [`Hierarchy::remove`]
+//! when destroying an entity, e.g. from
+//! [`StateHandler::destroy_entity`](crate::StateHandler::destroy_entity).
+//!
+//! # Example
+//! ```rust,ignore
+//! hierarchy.set_parent(turret, tank)?;
+//!
+//! // in StateHandler::upload_gpu
+//! let world = arena.alloc_slice_fill(props.position.len(), Mat4::IDENTITY);
+//! pack::pack_trs_matrices(&props.position, &props.rotation, &props.scale, world);
+//! hierarchy.propagate(world, |entity| props.index_of(entity));
+//! ```
+
+use glam::Mat4;
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::state::data::IndirectIndex;
+
+/// A parent-child relation which cannot be created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HierarchyError {
+    /// An entity cannot be its own parent.
+    SelfParent(IndirectIndex),
+    /// The parent is a descendant of the child.
+    Cycle {
+        child: IndirectIndex,
+        parent: IndirectIndex,
+    },
+}
+
+impl std::fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SelfParent(entity) => write!(f, "entity {entity:?} cannot be its own parent"),
+            Self::Cycle { child, parent } => write!(
+                f,
+                "cannot parent entity {child:?} to its descendant {parent:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+/// The parent of each parented entity, and the order in which their world
+/// transforms are resolved.
+#[derive(Clone, Debug, Default)]
+pub struct Hierarchy {
+    parents: HashMap<IndirectIndex, IndirectIndex>,
+    children: HashMap<IndirectIndex, Vec<IndirectIndex>>,
+    /// The parented entities, each after its parent.
+    order: Vec<IndirectIndex>,
+    dirty: bool,
+}
+
+impl Hierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parent `child` to `parent`, replacing its previous parent if any.
+    ///
+    /// The transform of `child` is then interpreted as local to `parent`.
+    ///
+    /// # Returns
+    /// The previous parent of `child`, or an error if `parent` is `child`
+    /// itself or one of its descendants.
+    pub fn set_parent(
+        &mut self,
+        child: IndirectIndex,
+        parent: IndirectIndex,
+    ) -> Result<Option<IndirectIndex>, HierarchyError> {
+        if child == parent {
+            return Err(HierarchyError::SelfParent(child));
+        }
+        if self.ancestors(parent).any(|ancestor| ancestor == child) {
+            return Err(HierarchyError::Cycle { child, parent });
+        }
+
+        let previous = self.unparent(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+        self.dirty = true;
+        Ok(previous)
+    }
+
+    /// Detach `child` from its parent, making it a root.
+    ///
+    /// # Returns
+    /// The previous parent of `child`, if any.
+    pub fn unparent(&mut self, child: IndirectIndex) -> Option<IndirectIndex> {
+        let parent = self.parents.remove(&child)?;
+        if let Some(siblings) = self.children.get_mut(&parent) {
+            siblings.retain(|&sibling| sibling != child);
+            if siblings.is_empty() {
+                self.children.remove(&parent);
+            }
+        }
+        self.dirty = true;
+        Some(parent)
+    }
+
+    /// Remove all relations of `entity`, e.g. when it is destroyed.
+    ///
+    /// Its children become roots.
+    ///
+    /// # Returns
+    /// The former children of `entity`.
+    pub fn remove(&mut self, entity: IndirectIndex) -> Vec<IndirectIndex> {
+        self.unparent(entity);
+        let children = self.children.remove(&entity).unwrap_or_default();
+        for child in &children {
+            self.parents.remove(child);
+        }
+        self.dirty |= !children.is_empty();
+        children
+    }
+
+    pub fn parent(&self, child: IndirectIndex) -> Option<IndirectIndex> {
+        self.parents.get(&child).copied()
+    }
+
+    pub fn children(&self, parent: IndirectIndex) -> &[IndirectIndex] {
+        self.children.get(&parent).map_or(&[], Vec::as_slice)
+    }
+
+    /// The parent of `entity`, its grandparent, and so on up to its root.
+    pub fn ancestors(&self, entity: IndirectIndex) -> impl Iterator<Item = IndirectIndex> + '_ {
+        std::iter::successors(self.parent(entity), |&ancestor| self.parent(ancestor))
+    }
+
+    /// The amount of parented entities.
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// The parented entities, each after its parent.
+    ///
+    /// The order is only recomputed after the relations changed.
+    pub fn order(&mut self) -> &[IndirectIndex] {
+        if self.dirty {
+            self.order.clear();
+            let roots = self
+                .children
+                .iter()
+                .filter(|(parent, _)| !self.parents.contains_key(parent));
+            self.order.extend(roots.flat_map(|(_, children)| children));
+
+            // breadth first: the children of each visited entity follow it
+            let mut next = 0;
+            while next < self.order.len() {
+                if let Some(children) = self.children.get(&self.order[next]) {
+                    self.order.extend(children);
+                }
+                next += 1;
+            }
+            self.dirty = false;
+        }
+        &self.order
+    }
+
+    /// Compose the world transforms of all parented entities in place.
+    ///
+    /// On entry, `transforms` holds the local transform of each entity, at
+    /// the index given by `index_of`; on return, parented entities hold their
+    /// world transform. Relations of entities without an index, e.g.
+    /// destroyed ones, are skipped.
+    ///
+    /// # Returns
+    /// The amount of composed transforms.
+    pub fn propagate(
+        &mut self,
+        transforms: &mut [Mat4],
+        index_of: impl Fn(IndirectIndex) -> Option<usize>,
+    ) -> usize {
+        self.order();
+
+        let mut composed = 0;
+        for &child in &self.order {
+            let parent = self.parents[&child];
+            let (Some(child), Some(parent)) = (index_of(child), index_of(parent)) else {
+                continue;
+            };
+            if child < transforms.len() && parent < transforms.len() {
+                transforms[child] = transforms[parent] * transforms[child];
+                composed += 1;
+            }
+        }
+        composed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn compose_world_transforms() {
+        let entity = |i: u32| IndirectIndex::from_int(i, 0);
+        let mut hierarchy = Hierarchy::new();
+
+        // 3 -> 2 -> 1, inserted out of order
+        hierarchy.set_parent(entity(3), entity(2)).unwrap();
+        hierarchy.set_parent(entity(2), entity(1)).unwrap();
+        assert_eq!(
+            hierarchy.set_parent(entity(1), entity(3)),
+            Err(HierarchyError::Cycle {
+                child: entity(1),
+                parent: entity(3)
+            })
+        );
+        assert_eq!(hierarchy.order(), &[entity(2), entity(3)]);
+
+        let offset = Mat4::from_translation(Vec3::X);
+        let mut transforms = [Mat4::IDENTITY, offset, offset, offset];
+        let composed = hierarchy.propagate(&mut transforms, |e| Some(e.as_index()));
+        assert_eq!(composed, 2);
+        assert_eq!(transforms[3].w_axis.x, 3.0);
+
+        assert_eq!(hierarchy.remove(entity(2)), vec![entity(3)]);
+        assert!(hierarchy.is_empty());
+    }
+}
@@ -1,26 +1,53 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use janus::sync;
 
 use crate::{
     StateHandler,
+    platform::{
+        self, ThreadHints,
+        config::{Config, Persist},
+    },
     render::{
-        ScreenSpace,
-        command::{DrawGroups, GpuCommandQueue},
+        ScreenSpace, buffer,
+        command::{DrawCmd, DrawGroups, GpuCommandQueue},
     },
     state::{
+        arena::StagingArena,
         camera::ViewPoint,
         cross::{Cross, Producer},
+        data::IndirectIndex,
+        stats::{FrameRecord, Recorder, StatsExporter, StatsSettings},
+        watchdog::Watchdog,
     },
 };
 
+pub mod arena;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod budget;
 pub mod camera;
+pub mod commands;
 pub mod cross;
 pub mod data;
+pub mod debug;
+pub mod hierarchy;
+pub mod stats;
+#[cfg(feature = "spatial-hash")]
+pub mod stream;
 pub mod time;
+pub mod watchdog;
 
 #[derive(Debug)]
-pub struct State<D: Sized, T: StateHandler<D, RG>, RG: DrawGroups> {
+pub struct State<
+    D: Sized,
+    T: StateHandler<D, RG, C>,
+    RG: DrawGroups,
+    C: DrawCmd = crate::DrawCommand,
+> {
     input: crate::InputSystem,
 
     screen: sync::Mirror<ScreenSpace>,
@@ -28,13 +55,64 @@ pub struct State<D: Sized, T: StateHandler<D, RG>, RG: DrawGroups> {
     handler: T,
 
     boundary: Cross<Producer, D>,
-    cmd_queue: GpuCommandQueue<crate::DrawCommand, RG>,
+    cmd_queue: GpuCommandQueue<C, RG>,
+    arena: StagingArena,
+    stats: UploadStats,
+    bandwidth: buffer::bandwidth::Bandwidth,
+    /// Collects the counters reported during uploads.
+    recorder: Recorder,
+    /// The CPU time of the fixed steps since the last upload.
+    tick_time: Duration,
+    /// The entities destroyed since the last upload.
+    destroyed: usize,
+    record: FrameRecord,
+    stats_exporter: Option<StatsExporter>,
+    /// Applied to the update thread on the next frame.
+    thread_hints: Option<ThreadHints>,
+    watchdog: Option<Watchdog>,
+    config: Config,
 }
 
-impl<D, T, RG> Default for State<D, T, RG>
+/// Statistics of the last [`State::upload`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    /// The amount of uploads performed so far.
+    pub frame: u64,
+
+    /// The amount of elements that did not fit in their GPU buffers and were
+    /// dropped during the last upload.
+    ///
+    /// A non-zero value means that the layout of some buffer is too small for
+    /// the current scene. See [`buffer::overflow`] for details.
+    pub dropped_elements: usize,
+
+    /// The total amount of elements dropped across all uploads.
+    pub total_dropped_elements: u64,
+
+    /// The amount of NaN, infinite or out of range elements found by
+    /// [`buffer::validate`] during the last upload.
+    ///
+    /// Always `0` in release builds.
+    pub invalid_elements: usize,
+
+    /// The amount of bytes blitted to GPU buffers during the last upload.
+    ///
+    /// See [`State::bandwidth`] for the amount per buffer and partition.
+    pub uploaded_bytes: usize,
+
+    /// The total amount of bytes blitted across all uploads.
+    pub total_uploaded_bytes: u64,
+
+    /// The amount of entities destroyed with [`State::destroy_entity`]
+    /// since the previous upload.
+    pub destroyed_entities: usize,
+}
+
+impl<D, T, RG, C> Default for State<D, T, RG, C>
 where
     D: Sized + Default,
-    T: StateHandler<D, RG> + Default,
+    T: StateHandler<D, RG, C> + Default,
+    C: DrawCmd,
     RG: DrawGroups,
 {
     fn default() -> Self {
@@ -45,17 +123,29 @@ where
             handler: Default::default(),
             boundary: Default::default(),
             cmd_queue: GpuCommandQueue::new(),
+            arena: StagingArena::new(),
+            stats: Default::default(),
+            bandwidth: Default::default(),
+            recorder: Default::default(),
+            tick_time: Duration::ZERO,
+            destroyed: 0,
+            record: Default::default(),
+            stats_exporter: None,
+            thread_hints: None,
+            watchdog: None,
+            config: Config::new(),
         }
     }
 }
 
 pub(crate) const DEFAULT_STEP: std::time::Duration = std::time::Duration::from_millis(8);
 
-impl<D, T, RG> State<D, T, RG>
+impl<D, T, RG, C> State<D, T, RG, C>
 where
     D: Sized,
-    T: StateHandler<D, RG>,
+    T: StateHandler<D, RG, C>,
     RG: DrawGroups,
+    C: DrawCmd,
 {
     pub fn handler_init_callback<F: FnOnce(&mut T)>(&mut self, callback: F) {
         callback(&mut self.handler)
@@ -69,15 +159,165 @@ where
         &mut self.boundary
     }
 
+    /// Upload the current state to the GPU through the
+    /// [`handler`](StateHandler::upload_gpu).
+    ///
+    /// CPU-layout data is converted first through
+    /// [`StateHandler::sync_staging`].
+    ///
+    /// Elements that do not fit in their GPU buffers are counted in
+    /// [`UploadStats::dropped_elements`], and invalid elements found by
+    /// [`buffer::validate`] in [`UploadStats::invalid_elements`].
+    ///
+    /// Handlers can upload only the elements of a column changed since a
+    /// section was last written by tracking them with [`DirtyRanges`], which
+    /// shows in [`UploadStats::uploaded_bytes`].
+    ///
+    /// [`DirtyRanges`]: data::DirtyRanges
     pub fn upload(&mut self) {
-        self.handler.upload_gpu(&self.boundary, &mut self.cmd_queue);
+        let _zone = crate::zone::zone("upload");
+        let start = Instant::now();
+        self.arena.reset();
+        self.stats.destroyed_entities = self.destroyed;
+        self.destroyed = 0;
+
+        self.stats.frame += 1;
+        self.recorder.record(|| {
+            self.handler.sync_staging(self.stats.frame);
+            self.handler
+                .upload_gpu(&self.boundary, &mut self.cmd_queue, &self.arena);
+        });
+
+        self.record = FrameRecord {
+            frame: self.stats.frame,
+            tick_time: std::mem::take(&mut self.tick_time),
+            upload_time: start.elapsed(),
+            gpu_time: self.boundary.gpu_time(),
+            ..Default::default()
+        };
+        self.recorder.take(&mut self.record, &mut self.bandwidth);
+
+        self.stats.dropped_elements = self.record.dropped_elements;
+        self.stats.total_dropped_elements += self.stats.dropped_elements as u64;
+        self.stats.invalid_elements = self.record.invalid_elements;
+        self.stats.uploaded_bytes = self.record.uploaded_bytes;
+        self.stats.total_uploaded_bytes += self.stats.uploaded_bytes as u64;
+        if let Some(exporter) = &mut self.stats_exporter
+            && let Err(err) = exporter.write(&self.record)
+        {
+            use tracing::Level;
+            tracing::event!(
+                name: "state.stats",
+                Level::WARN,
+                "frame stats export failed, disabling it: {err}"
+            );
+            self.stats_exporter = None;
+        }
+    }
+
+    /// Destroy `entity`, freeing its slots in all the
+    /// [entity columns](StateHandler::entity_columns) of the handler, then
+    /// removing it from any other storage through
+    /// [`StateHandler::destroy_entity`].
+    ///
+    /// The entity is no longer drawn from the next upload on.
+    ///
+    /// # Returns
+    /// Whether the entity existed.
+    pub fn destroy_entity(&mut self, entity: IndirectIndex) -> bool {
+        let mut destroyed = false;
+        for column in self.handler.entity_columns() {
+            destroyed |= column.free_entity(entity);
+        }
+        destroyed |= self.handler.destroy_entity(entity);
+        if destroyed {
+            self.destroyed += 1;
+        } else {
+            use tracing::Level;
+            tracing::event!(
+                name: "state.destroy_entity",
+                Level::DEBUG,
+                "attempted to destroy {entity:?}, which does not exist"
+            );
+        }
+        destroyed
+    }
+
+    pub fn upload_stats(&self) -> &UploadStats {
+        &self.stats
+    }
+
+    /// The timings and counters of the last upload.
+    ///
+    /// See [`stats`].
+    pub fn frame_record(&self) -> &FrameRecord {
+        &self.record
+    }
+
+    /// Append a [`FrameRecord`] to `exporter` after every upload, or stop
+    /// exporting with `None`.
+    ///
+    /// Nothing is exported by default, unless the [config](Self::set_config)
+    /// opts into it, see [`StatsSettings`].
+    pub fn set_stats_exporter(&mut self, exporter: Option<StatsExporter>) {
+        self.stats_exporter = exporter;
+    }
+
+    /// Apply the scheduling `hints` to the update thread at the start of the
+    /// next frame, see [`platform`].
+    pub fn set_thread_hints(&mut self, hints: Option<ThreadHints>) {
+        self.thread_hints = hints;
+    }
+
+    /// Keep `watchdog` running as long as the state, or stop the current one
+    /// with `None`.
+    ///
+    /// See [`watchdog`].
+    pub fn set_watchdog(&mut self, watchdog: Option<Watchdog>) {
+        self.watchdog = watchdog;
+    }
+
+    /// The persisted settings, see [`platform::config`].
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
     }
 
-    pub fn command_queue(&self) -> &GpuCommandQueue<crate::DrawCommand, RG> {
+    /// Replace the persisted settings.
+    ///
+    /// If the `config` sets `stats.export`, the frame stats are exported to
+    /// that file from now on, see [`StatsSettings`].
+    pub fn set_config(&mut self, config: Config) {
+        let mut stats = StatsSettings::default();
+        stats.restore(&config);
+        if let Some(exporter) = stats.exporter() {
+            self.stats_exporter = Some(exporter);
+        }
+        self.config = config;
+    }
+
+    /// The bytes blitted per buffer and partition during the last upload,
+    /// on the thread calling [`Self::upload`].
+    pub fn bandwidth(&self) -> &buffer::bandwidth::Bandwidth {
+        &self.bandwidth
+    }
+
+    pub fn staging_arena(&self) -> &StagingArena {
+        &self.arena
+    }
+
+    pub fn staging_arena_mut(&mut self) -> &mut StagingArena {
+        &mut self.arena
+    }
+
+    pub fn command_queue(&self) -> &GpuCommandQueue<C, RG> {
         &self.cmd_queue
     }
 
-    pub fn command_queue_mut(&mut self) -> &mut GpuCommandQueue<crate::DrawCommand, RG> {
+    pub fn command_queue_mut(&mut self) -> &mut GpuCommandQueue<C, RG> {
         &mut self.cmd_queue
     }
 
@@ -110,16 +350,20 @@ where
     }
 }
 
-impl<D, T, RG> janus::context::Update for State<D, T, RG>
+impl<D, T, RG, C> janus::context::Update for State<D, T, RG, C>
 where
     D: Sized,
-    T: StateHandler<D, RG>,
+    T: StateHandler<D, RG, C>,
     RG: DrawGroups,
+    C: DrawCmd,
 {
     #[inline]
     fn update(&mut self, delta: janus::context::DeltaTime) {
+        let _zone = crate::zone::zone("update");
+        let start = Instant::now();
         self.handler
             .fixed_step(&mut self.input, &mut self.screen, &self.view, delta);
+        self.tick_time += start.elapsed();
     }
 
     #[inline]
@@ -129,6 +373,7 @@ where
 
     #[inline]
     fn new_frame(&mut self, delta: janus::context::DeltaTime) {
+        platform::apply_once(&mut self.thread_hints, "update");
         self.input.sync();
         self.input.poll_key_events();
 
@@ -144,3 +389,100 @@ where
         self.upload();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::data::{
+        Column, EntityColumn, IndexArrayColumn, ParallelIndexArrayColumn, column::IterColumn,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct Groups;
+
+    impl std::fmt::Display for Groups {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.as_str())
+        }
+    }
+
+    impl DrawGroups for Groups {
+        fn as_str(&self) -> &'static str {
+            "groups"
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Handler {
+        healths: IndexArrayColumn<u32>,
+        positions: ParallelIndexArrayColumn<u32>,
+    }
+
+    impl StateHandler<(), Groups> for Handler {
+        fn upload_gpu(
+            &mut self,
+            _frame_boundary: &Cross<Producer, ()>,
+            _command_queue: &mut GpuCommandQueue<crate::DrawCommand, Groups>,
+            _arena: &StagingArena,
+        ) {
+        }
+
+        fn fixed_step(
+            &mut self,
+            _input: &mut crate::InputSystem,
+            _screen: &mut sync::Mirror<ScreenSpace>,
+            _view_point: &sync::TriCell<ViewPoint>,
+            _delta: janus::context::DeltaTime,
+        ) {
+        }
+
+        fn entity_columns(&mut self) -> Vec<&mut dyn EntityColumn> {
+            vec![&mut self.healths, &mut self.positions]
+        }
+    }
+
+    #[test]
+    fn destroy_entities() {
+        let mut state = State::<(), Handler, Groups>::default();
+        let mut entities = Vec::new();
+        let mut len = 0;
+        state.handler_init_callback(|handler| {
+            for value in 0..3u32 {
+                let entity = handler.healths.insert(value);
+                assert_eq!(handler.positions.insert(value), entity);
+                entities.push(entity);
+            }
+            len = handler.healths.len();
+        });
+
+        assert!(state.destroy_entity(entities[1]));
+        assert!(!state.destroy_entity(entities[1]));
+        state.handler_init_callback(|handler| {
+            assert_eq!(handler.healths.len(), len - 1);
+            assert_eq!(handler.positions.len(), len - 1);
+            assert!(handler.healths.solve_indirect(entities[1]).is_none());
+            assert!(handler.positions.solve_indirect(entities[1]).is_none());
+            assert!(handler.healths.solve_indirect(entities[2]).is_some());
+            assert_eq!(
+                handler.positions.iter().copied().collect::<Vec<_>>(),
+                [0, 2]
+            );
+        });
+
+        // the last element takes no other slot with it
+        assert!(state.destroy_entity(entities[2]));
+        assert!(!state.destroy_entity(entities[2]));
+        state.handler_init_callback(|handler| {
+            assert_eq!(handler.healths.len(), len - 2);
+            assert_eq!(handler.positions.len(), len - 2);
+            assert!(handler.healths.solve_indirect(entities[2]).is_none());
+            assert!(handler.positions.solve_indirect(entities[2]).is_none());
+            assert!(handler.positions.solve_indirect(entities[0]).is_some());
+        });
+
+        state.upload();
+        assert_eq!(state.upload_stats().destroyed_entities, 2);
+        state.upload();
+        assert_eq!(state.upload_stats().destroyed_entities, 0);
+    }
+}
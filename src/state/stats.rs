@@ -0,0 +1,368 @@
+//! Per-frame statistics, and their export for offline analysis.
+//!
+//! Every [`State::upload`](super::State::upload) produces a [`FrameRecord`]:
+//! the CPU time spent in fixed steps and in the upload itself, the latest GPU
+//! frame time measured by the renderer, and the counters reported during the
+//! upload. The library cannot know how many draws were issued or entities
+//! culled by the handler, which reports them with [`record_draws`] and
+//! [`record_culled`].
+//!
+//! Counters are reported to the [`Recorder`] recording on the current
+//! thread: every state records its uploads with its own recorder, so that
+//! states never take the counters of one another, and blits on the render
+//! thread are not mixed into the uploads. Counters reported on threads
+//! without a recorder are discarded.
+//!
+//! With a [`StatsExporter`] set through
+//! [`State::set_stats_exporter`](super::State::set_stats_exporter), records
+//! are appended to a file as CSV rows or JSON lines. Exporting can also be
+//! enabled without code changes by setting `stats.export` to the path of the
+//! file in the [`Config`](crate::platform::config::Config) of the state, see
+//! [`StatsSettings`]. Nothing is exported otherwise.
+//!
+//! # Example
+//! ```rust,ignore
+//! state.set_stats_exporter(Some(StatsExporter::create("stats.csv", StatsFormat::Csv)?));
+//!
+//! // in StateHandler::upload_gpu
+//! stats::record_draws(commands.len() as u32);
+//! stats::record_culled(culled as u32);
+//! ```
+
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::render::buffer::bandwidth::{Bandwidth, BlitCounters};
+
+thread_local! {
+    /// The recorder of the current thread, set for the duration of
+    /// [`Recorder::record`].
+    static RECORDER: Cell<*const Recorder> = const { Cell::new(std::ptr::null()) };
+}
+
+/// Collects the counters reported on the thread it records on.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct Recorder {
+    blitted: BlitCounters,
+    dropped: Cell<usize>,
+    invalid: Cell<usize>,
+    draws: Cell<u32>,
+    culled: Cell<u32>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `op`, reporting the counters of the current thread to this
+    /// recorder instead of the one recording before, if any.
+    pub fn record<R>(&self, op: impl FnOnce() -> R) -> R {
+        /// Restores the previous recorder, even if `op` panics.
+        struct Restore(*const Recorder);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                RECORDER.set(self.0);
+            }
+        }
+
+        let _restore = Restore(RECORDER.replace(self));
+        op()
+    }
+
+    /// The amount of elements dropped by blits since the counters were last
+    /// taken.
+    pub fn dropped_elements(&self) -> usize {
+        self.dropped.get()
+    }
+
+    /// Take the counters reported since the last call into `record`, and the
+    /// bytes blitted into `bandwidth`, resetting them.
+    pub fn take(&self, record: &mut FrameRecord, bandwidth: &mut Bandwidth) {
+        self.blitted.take(bandwidth);
+        record.uploaded_bytes = bandwidth.total();
+        record.dropped_elements = self.dropped.take();
+        record.invalid_elements = self.invalid.take();
+        record.draws = self.draws.take();
+        record.culled = self.culled.take();
+    }
+
+    pub(crate) fn blitted(&self) -> &BlitCounters {
+        &self.blitted
+    }
+}
+
+/// Run `op` with the recorder of the current thread.
+///
+/// # Returns
+/// `None` if no recorder is recording on the current thread.
+pub(crate) fn with_recorder<R>(op: impl FnOnce(&Recorder) -> R) -> Option<R> {
+    let recorder = RECORDER.get();
+    // SAFETY: the recorder is borrowed for as long as it is set, see
+    // `Recorder::record`.
+    (!recorder.is_null()).then(|| op(unsafe { &*recorder }))
+}
+
+/// Report `count` draws issued for the current upload.
+pub fn record_draws(count: u32) {
+    with_recorder(|recorder| recorder.draws.set(recorder.draws.get() + count));
+}
+
+/// Report `count` entities culled during the current upload.
+pub fn record_culled(count: u32) {
+    with_recorder(|recorder| recorder.culled.set(recorder.culled.get() + count));
+}
+
+/// Report `count` elements dropped by a blit, see
+/// [`overflow`](crate::render::buffer::overflow).
+pub(crate) fn record_dropped(count: usize) {
+    with_recorder(|recorder| recorder.dropped.set(recorder.dropped.get() + count));
+}
+
+/// Report `count` invalid elements, see
+/// [`validate`](crate::render::buffer::validate).
+pub(crate) fn record_invalid(count: usize) {
+    with_recorder(|recorder| recorder.invalid.set(recorder.invalid.get() + count));
+}
+
+/// The statistics of a single upload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameRecord {
+    pub frame: u64,
+    /// The CPU time of the fixed steps since the previous upload.
+    pub tick_time: Duration,
+    /// The CPU time of the upload.
+    pub upload_time: Duration,
+    /// The GPU time of the last frame completed by the renderer, if measured.
+    pub gpu_time: Option<Duration>,
+    pub draws: u32,
+    pub culled: u32,
+    pub uploaded_bytes: usize,
+    pub dropped_elements: usize,
+    pub invalid_elements: usize,
+}
+
+/// The settings of the frame stats export, persisted under the `stats` key
+/// prefix of a [`Config`](crate::platform::config::Config).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatsSettings {
+    /// The file the frame stats are exported to, in the format matching its
+    /// extension, see [`StatsFormat::from_path`]. Nothing is exported if
+    /// `None`.
+    pub export: Option<PathBuf>,
+}
+
+impl StatsSettings {
+    /// Create an exporter to the [`export`](Self::export) file.
+    ///
+    /// # Returns
+    /// `None` if no file is set, or it cannot be created.
+    pub fn exporter(&self) -> Option<StatsExporter> {
+        let path = self.export.as_ref()?;
+        match StatsExporter::create(path, StatsFormat::from_path(path)) {
+            Ok(exporter) => Some(exporter),
+            Err(err) => {
+                use tracing::Level;
+                tracing::event!(
+                    name: "state.stats",
+                    Level::WARN,
+                    "cannot export frame stats to {}: {err}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+}
+
+/// The file format of a [`StatsExporter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StatsFormat {
+    /// Comma separated values, with a header row.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl StatsFormat {
+    /// The format matching the extension of `path`: JSON lines for `.json`
+    /// and `.jsonl`, CSV otherwise.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("json" | "jsonl") => Self::JsonLines,
+            _ => Self::Csv,
+        }
+    }
+}
+
+const CSV_HEADER: &str =
+    "frame,tick_us,upload_us,gpu_us,draws,culled,uploaded_bytes,dropped_elements,invalid_elements";
+
+/// Appends [`FrameRecord`]s to a writer.
+pub struct StatsExporter {
+    writer: Box<dyn Write + Send>,
+    format: StatsFormat,
+    header_written: bool,
+}
+
+impl std::fmt::Debug for StatsExporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StatsExporter")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StatsExporter {
+    /// Export to `writer`.
+    pub fn new<W: Write + Send + 'static>(writer: W, format: StatsFormat) -> Self {
+        Self {
+            writer: Box::new(writer),
+            format,
+            header_written: false,
+        }
+    }
+
+    /// Export to the file at `path`, truncating it.
+    pub fn create(path: impl AsRef<Path>, format: StatsFormat) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file), format))
+    }
+
+    pub fn format(&self) -> StatsFormat {
+        self.format
+    }
+
+    /// Append `record`.
+    pub fn write(&mut self, record: &FrameRecord) -> std::io::Result<()> {
+        let gpu_us = record.gpu_time.map(|t| t.as_micros());
+        match self.format {
+            StatsFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.writer, "{CSV_HEADER}")?;
+                    self.header_written = true;
+                }
+                writeln!(
+                    self.writer,
+                    "{},{},{},{},{},{},{},{},{}",
+                    record.frame,
+                    record.tick_time.as_micros(),
+                    record.upload_time.as_micros(),
+                    gpu_us.map(|t| t.to_string()).unwrap_or_default(),
+                    record.draws,
+                    record.culled,
+                    record.uploaded_bytes,
+                    record.dropped_elements,
+                    record.invalid_elements,
+                )
+            }
+            StatsFormat::JsonLines => writeln!(
+                self.writer,
+                r#"{{"frame":{},"tick_us":{},"upload_us":{},"gpu_us":{},"draws":{},"culled":{},"uploaded_bytes":{},"dropped_elements":{},"invalid_elements":{}}}"#,
+                record.frame,
+                record.tick_time.as_micros(),
+                record.upload_time.as_micros(),
+                gpu_us.map(|t| t.to_string()).as_deref().unwrap_or("null"),
+                record.draws,
+                record.culled,
+                record.uploaded_bytes,
+                record.dropped_elements,
+                record.invalid_elements,
+            ),
+        }
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Drop for StatsExporter {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_per_recorder() {
+        let (ours, theirs) = (Recorder::new(), Recorder::new());
+        ours.record(|| {
+            record_draws(4);
+            theirs.record(|| record_draws(1));
+            record_culled(2);
+            record_dropped(3);
+            record_invalid(1);
+        });
+        // outside of a recording
+        record_draws(8);
+        // on another thread
+        ours.record(|| std::thread::scope(|scope| _ = scope.spawn(|| record_draws(16))));
+
+        let mut record = FrameRecord::default();
+        let mut bandwidth = Bandwidth::default();
+        ours.take(&mut record, &mut bandwidth);
+        assert_eq!((record.draws, record.culled), (4, 2));
+        assert_eq!((record.dropped_elements, record.invalid_elements), (3, 1));
+        theirs.take(&mut record, &mut bandwidth);
+        assert_eq!((record.draws, record.culled), (1, 0));
+        ours.take(&mut record, &mut bandwidth);
+        assert_eq!(record.draws, 0);
+    }
+
+    #[test]
+    fn export_records() {
+        let record = FrameRecord {
+            frame: 7,
+            tick_time: Duration::from_micros(1500),
+            draws: 6,
+            ..Default::default()
+        };
+
+        let csv = Shared::default();
+        let mut exporter = StatsExporter::new(csv.clone(), StatsFormat::Csv);
+        exporter.write(&record).unwrap();
+        exporter.write(&record).unwrap();
+        let csv = String::from_utf8(csv.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "7,1500,0,,6,0,0,0,0");
+
+        let json = Shared::default();
+        let mut exporter = StatsExporter::new(json.clone(), StatsFormat::JsonLines);
+        exporter.write(&record).unwrap();
+        let json = String::from_utf8(json.0.lock().unwrap().clone()).unwrap();
+        assert!(json.starts_with(r#"{"frame":7,"tick_us":1500,"#));
+        assert!(json.contains(r#""gpu_us":null"#));
+
+        assert_eq!(StatsFormat::from_path("a/b.jsonl"), StatsFormat::JsonLines);
+    }
+}
@@ -0,0 +1,256 @@
+//! World streaming over spatial chunks.
+//!
+//! Entities are grouped into chunks, the cells of a [`FxLsSpatialHash`] with
+//! a coarse [`SpatialResolution`]. As the focus point (usually the camera)
+//! moves, [`ChunkStream::update`] loads the populated chunks within the load
+//! radius and unloads the resident chunks past the unload radius, calling
+//! the respective callbacks.
+//!
+//! The callbacks are where the content of a chunk is streamed in and out,
+//! e.g. by uploading its static data to a
+//! [`ChunkBuffers`](crate::render::buffer::statics::ChunkBuffers) on the
+//! render thread, and command generation only iterates the entities of
+//! resident chunks through [`ChunkStream::resident_entities`].
+
+use rustc_hash::FxHashSet as HashSet;
+
+use crate::state::data::hash::{Cell, FxLsSpatialHash, SpatialResolution};
+
+type LoadCallback<T> = Box<dyn FnMut(Cell, &[T]) + Send>;
+type UnloadCallback = Box<dyn FnMut(Cell) + Send>;
+
+/// The chunks loaded and unloaded by a [`ChunkStream::update`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunkChanges {
+    pub loaded: usize,
+    pub unloaded: usize,
+}
+
+pub struct ChunkStream<T: Clone + Copy> {
+    chunks: FxLsSpatialHash<T>,
+    resident: HashSet<Cell>,
+
+    load_radius: u32,
+    unload_radius: u32,
+
+    on_load: Option<LoadCallback<T>>,
+    on_unload: Option<UnloadCallback>,
+}
+
+impl<T: Clone + Copy + std::fmt::Debug> std::fmt::Debug for ChunkStream<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkStream")
+            .field("chunks", &self.chunks)
+            .field("resident", &self.resident)
+            .field("load_radius", &self.load_radius)
+            .field("unload_radius", &self.unload_radius)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone + Copy> ChunkStream<T> {
+    /// Create a stream of chunks of the given world-space `chunk_size`,
+    /// loading the chunks within `load_radius` chunks of the focus point.
+    ///
+    /// Chunks are unloaded one chunk past the load radius, so that moving
+    /// back and forth across a chunk border does not stream it repeatedly.
+    pub fn new(chunk_size: f32, load_radius: u32) -> Self {
+        Self {
+            chunks: FxLsSpatialHash::new(SpatialResolution::new(chunk_size)),
+            resident: HashSet::default(),
+            load_radius,
+            unload_radius: load_radius + 1,
+            on_load: None,
+            on_unload: None,
+        }
+    }
+
+    /// Set the radius, in chunks, past which resident chunks are unloaded.
+    ///
+    /// # Panics
+    /// If `radius` is lower than the load radius.
+    pub fn unload_radius(mut self, radius: u32) -> Self {
+        assert!(
+            radius >= self.load_radius,
+            "the unload radius cannot be lower than the load radius {}",
+            self.load_radius
+        );
+        self.unload_radius = radius;
+        self
+    }
+
+    /// Call `callback` with the entities of each chunk when it is loaded.
+    pub fn on_load<F: FnMut(Cell, &[T]) + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_load = Some(Box::new(callback));
+        self
+    }
+
+    /// Call `callback` with each chunk when it is unloaded.
+    pub fn on_unload<F: FnMut(Cell) + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_unload = Some(Box::new(callback));
+        self
+    }
+
+    pub fn resolution(&self) -> SpatialResolution {
+        self.chunks.resolution()
+    }
+
+    /// The chunk containing `point`.
+    pub fn chunk_at(&self, point: glam::Vec3) -> Cell {
+        self.chunks.cell_at(point)
+    }
+
+    /// Add an `entity` to the chunk containing `position`.
+    ///
+    /// Entities added to a resident chunk are not streamed in until the chunk
+    /// is loaded again.
+    pub fn insert(&mut self, position: glam::Vec3, entity: T) {
+        let cell = self.chunks.cell_at(position);
+        self.chunks.put(cell, entity);
+    }
+
+    /// Regroup all entities from their `positions`.
+    ///
+    /// Resident chunks stay resident.
+    pub fn rebuild(&mut self, positions: &[glam::Vec3], entities: &[T]) {
+        self.chunks.clear();
+        self.chunks.dump_soa(positions, entities);
+    }
+
+    /// The entities of `chunk`.
+    pub fn entities(&self, chunk: Cell) -> &[T] {
+        self.chunks
+            .get(chunk)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn is_resident(&self, chunk: Cell) -> bool {
+        self.resident.contains(&chunk)
+    }
+
+    pub fn resident_chunks(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.resident.iter().copied()
+    }
+
+    /// The entities of all resident chunks, for command generation.
+    pub fn resident_entities(&self) -> impl Iterator<Item = &T> + '_ {
+        self.resident
+            .iter()
+            .flat_map(|&chunk| self.entities(chunk).iter())
+    }
+
+    /// Stream chunks in and out around the `focus` point.
+    pub fn update(&mut self, focus: glam::Vec3) -> ChunkChanges {
+        let center = self.chunks.cell_at(focus);
+        let mut changes = ChunkChanges::default();
+
+        let unload_radius = self.unload_radius;
+        let on_unload = &mut self.on_unload;
+        self.resident.retain(|&chunk| {
+            let keep = chunk_distance(center, chunk) <= unload_radius;
+            if !keep {
+                changes.unloaded += 1;
+                if let Some(callback) = on_unload {
+                    callback(chunk);
+                }
+            }
+            keep
+        });
+
+        let radius = self.load_radius as i32;
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    let chunk = center + Cell::new(x, y, z);
+                    let Some(entities) = self.chunks.get(chunk) else {
+                        continue;
+                    };
+                    if entities.is_empty() || !self.resident.insert(chunk) {
+                        continue;
+                    }
+                    changes.loaded += 1;
+                    if let Some(callback) = &mut self.on_load {
+                        callback(chunk, entities);
+                    }
+                }
+            }
+        }
+
+        if changes != ChunkChanges::default() {
+            use tracing::Level;
+            tracing::event!(
+                name: "state.stream.update",
+                Level::DEBUG,
+                "streamed {} chunks in and {} out around chunk {center:?}",
+                changes.loaded,
+                changes.unloaded
+            );
+        }
+        changes
+    }
+
+    /// Unload all resident chunks.
+    pub fn unload_all(&mut self) {
+        for chunk in self.resident.drain() {
+            if let Some(callback) = &mut self.on_unload {
+                callback(chunk);
+            }
+        }
+    }
+}
+
+/// The distance between two chunks, in chunks along the farthest axis.
+fn chunk_distance(a: Cell, b: Cell) -> u32 {
+    let d = (a - b).abs();
+    d.x.max(d.y).max(d.z) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn stream_around_focus() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (load_log, unload_log) = (log.clone(), log.clone());
+
+        let mut stream = ChunkStream::new(10.0, 1)
+            .on_load(move |chunk, entities: &[u32]| {
+                load_log.lock().unwrap().push((chunk, entities.len()))
+            })
+            .on_unload(move |chunk| unload_log.lock().unwrap().push((chunk, 0)));
+
+        stream.rebuild(
+            &[
+                glam::vec3(0.0, 0.0, 0.0),
+                glam::vec3(1.0, 0.0, 0.0),
+                glam::vec3(10.0, 0.0, 0.0),
+                glam::vec3(50.0, 0.0, 0.0),
+            ],
+            &[0, 1, 2, 3],
+        );
+
+        let changes = stream.update(glam::Vec3::ZERO);
+        assert_eq!(changes.loaded, 2);
+        assert_eq!(stream.resident_entities().count(), 3);
+        assert!(!stream.is_resident(stream.chunk_at(glam::vec3(50.0, 0.0, 0.0))));
+
+        // the hysteresis keeps the chunk at the origin resident
+        let changes = stream.update(glam::vec3(20.0, 0.0, 0.0));
+        assert_eq!(changes, ChunkChanges::default());
+
+        let changes = stream.update(glam::vec3(50.0, 0.0, 0.0));
+        assert_eq!(
+            changes,
+            ChunkChanges {
+                loaded: 1,
+                unloaded: 2
+            }
+        );
+        assert_eq!(stream.resident_entities().copied().collect::<Vec<_>>(), [3]);
+        assert_eq!(log.lock().unwrap().len(), 5);
+    }
+}
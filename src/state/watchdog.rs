@@ -0,0 +1,318 @@
+//! Detection of a stalled [`Boundary`](super::cross::Boundary).
+//!
+//! When a GPU fence never signals, or the update or render thread panics,
+//! one side of the boundary stops crossing and the other ends up spinning on
+//! a lock or rendering the same section forever: the application freezes
+//! without any diagnostic.
+//!
+//! Both sides record a [`Pulse`] every time they cross. A [`Watchdog`]
+//! checks it from its own thread, and logs a dump of the boundary (see
+//! [`BoundaryDiagnostics`]) as soon as either side has not crossed for longer
+//! than its timeout, and again once the boundary recovers.
+//!
+//! The watchdog is started with
+//! [`StartupHandler::with_watchdog`](crate::StartupHandler::with_watchdog),
+//! or manually from any [`Cross`](super::cross::Cross) operator:
+//!
+//! ```rust,ignore
+//! let watchdog = Watchdog::spawn(producer.pulse().clone(), Duration::from_secs(2))?;
+//! ```
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use tracing::{Level, event};
+
+use crate::render::{
+    buffer::StorageSection,
+    sync::{MAX_SYNC_LANES, SyncState},
+};
+
+const SECTIONS: [StorageSection; 3] = [
+    StorageSection::Front,
+    StorageSection::Back,
+    StorageSection::Spare,
+];
+
+/// The crosses of both sides of a boundary, shared with a [`Watchdog`].
+///
+/// Beats are stored as milliseconds since the creation of the pulse, offset
+/// by one so that `0` means that the side never crossed.
+#[derive(Debug)]
+pub struct Pulse {
+    epoch: Instant,
+    produced_at: AtomicU64,
+    consumed_at: AtomicU64,
+    published: AtomicU64,
+    consumed: AtomicU64,
+    section: AtomicU8,
+    /// The section the producer is waiting to be unlocked, `0` if none.
+    waiting: AtomicU8,
+    locks: AtomicU8,
+    lane_locks: [AtomicU8; MAX_SYNC_LANES],
+}
+
+impl Default for Pulse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pulse {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            produced_at: AtomicU64::new(0),
+            consumed_at: AtomicU64::new(0),
+            published: AtomicU64::new(0),
+            consumed: AtomicU64::new(0),
+            section: AtomicU8::new(StorageSection::Spare as u8),
+            waiting: AtomicU8::new(0),
+            locks: AtomicU8::new(0),
+            lane_locks: Default::default(),
+        }
+    }
+
+    fn beat(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64 + 1
+    }
+
+    fn since(&self, beat: &AtomicU64) -> Option<Duration> {
+        match beat.load(Ordering::Acquire) {
+            0 => None,
+            beat => Some(Duration::from_millis(self.beat().saturating_sub(beat))),
+        }
+    }
+
+    /// Record the [`Producer`](super::cross::Producer) publishing `section`,
+    /// the `published`-th one.
+    pub(crate) fn produced(&self, published: u64, section: StorageSection) {
+        self.published.store(published, Ordering::Relaxed);
+        self.section.store(section as u8, Ordering::Relaxed);
+        self.produced_at.store(self.beat(), Ordering::Release);
+    }
+
+    /// Record the [`Consumer`](super::cross::Consumer) crossing the section
+    /// stamped `stamp`, with the locks it left in `sync`.
+    pub(crate) fn consumed(&self, stamp: u64, sync: &SyncState) {
+        self.consumed.store(stamp, Ordering::Relaxed);
+        self.locks.store(sync.lock_mask(), Ordering::Relaxed);
+        for (lane, locks) in self.lane_locks.iter().enumerate() {
+            locks.store(sync.lane_lock_mask(lane), Ordering::Relaxed);
+        }
+        self.consumed_at.store(self.beat(), Ordering::Release);
+    }
+
+    /// Record the producer waiting for `section` to be unlocked, or no longer
+    /// waiting with `None`.
+    pub(crate) fn set_waiting(&self, section: Option<StorageSection>) {
+        let byte = section.map_or(0, |section| section as u8);
+        self.waiting.store(byte, Ordering::Relaxed);
+    }
+
+    /// A snapshot of the state of the boundary.
+    pub fn diagnostics(&self) -> BoundaryDiagnostics {
+        let waiting = self.waiting.load(Ordering::Relaxed);
+        BoundaryDiagnostics {
+            since_produced: self.since(&self.produced_at),
+            since_consumed: self.since(&self.consumed_at),
+            published: self.published.load(Ordering::Relaxed),
+            consumed: self.consumed.load(Ordering::Relaxed),
+            section: StorageSection::from_byte(self.section.load(Ordering::Relaxed)),
+            waiting: (waiting != 0).then(|| StorageSection::from_byte(waiting)),
+            locks: self.locks.load(Ordering::Relaxed),
+            lane_locks: std::array::from_fn(|lane| self.lane_locks[lane].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The side of a boundary which stopped crossing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StalledSide {
+    Producer,
+    Consumer,
+    Both,
+}
+
+/// A snapshot of a boundary, as dumped by the [`Watchdog`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoundaryDiagnostics {
+    /// The time since the last cross of the producer, `None` if it never
+    /// crossed.
+    pub since_produced: Option<Duration>,
+    /// The time since the last cross of the consumer, `None` if it never
+    /// crossed.
+    pub since_consumed: Option<Duration>,
+    /// The amount of sections published by the producer.
+    pub published: u64,
+    /// The stamp of the section last crossed by the consumer.
+    pub consumed: u64,
+    /// The section last published by the producer.
+    pub section: StorageSection,
+    /// The section the producer is waiting to be unlocked, if any.
+    pub waiting: Option<StorageSection>,
+    /// The sections whose fence had not signaled yet at the last cross of
+    /// the consumer.
+    pub locks: u8,
+    /// The locks of each lane, see [`LaneFences`](crate::render::sync::LaneFences).
+    pub lane_locks: [u8; MAX_SYNC_LANES],
+}
+
+impl BoundaryDiagnostics {
+    /// The side which has not crossed for longer than `timeout`, if any.
+    ///
+    /// A side which never crossed is not considered stalled, so that a slow
+    /// startup is not reported.
+    pub fn stalled(&self, timeout: Duration) -> Option<StalledSide> {
+        let stalled = |since: Option<Duration>| since.is_some_and(|since| since > timeout);
+        match (stalled(self.since_produced), stalled(self.since_consumed)) {
+            (true, true) => Some(StalledSide::Both),
+            (true, false) => Some(StalledSide::Producer),
+            (false, true) => Some(StalledSide::Consumer),
+            (false, false) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BoundaryDiagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let locked = |bits: u8| {
+            SECTIONS
+                .iter()
+                .filter(move |&&section| bits & section as u8 != 0)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        writeln!(f, "last produced: {:?} ago", self.since_produced)?;
+        writeln!(f, "last consumed: {:?} ago", self.since_consumed)?;
+        writeln!(
+            f,
+            "published: {}, consumed: {}, section: {:?}",
+            self.published, self.consumed, self.section
+        )?;
+        writeln!(f, "producer waiting on: {:?}", self.waiting)?;
+        write!(f, "pending fences: {:?}", locked(self.locks))?;
+        for (lane, &bits) in self.lane_locks.iter().enumerate() {
+            if bits != 0 {
+                write!(f, "\nlane {lane} pending fences: {:?}", locked(bits))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Logs a dump of a boundary whose producer or consumer stalled.
+///
+/// The watchdog thread stops when the watchdog is dropped.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct Watchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start watching `pulse`, reporting sides which have not crossed for
+    /// longer than `timeout`.
+    pub fn spawn(pulse: Arc<Pulse>, timeout: Duration) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("ethel-watchdog".into())
+            .spawn({
+                let stop = Arc::clone(&stop);
+                move || watch(&pulse, timeout, &stop)
+            })?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(pulse: &Pulse, timeout: Duration, stop: &AtomicBool) {
+    let mut stalled = None;
+    while !stop.load(Ordering::Acquire) {
+        std::thread::park_timeout(timeout / 4);
+
+        let diagnostics = pulse.diagnostics();
+        let side = diagnostics.stalled(timeout);
+        if side == stalled {
+            continue;
+        }
+        match side {
+            Some(side) => event!(
+                name: "state.watchdog",
+                Level::ERROR,
+                "{side:?} has not crossed the boundary for over {timeout:?}:\n{diagnostics}"
+            ),
+            None => event!(
+                name: "state.watchdog",
+                Level::INFO,
+                "boundary crosses resumed"
+            ),
+        }
+        stalled = side;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_stall_detection() {
+        let pulse = Pulse::new();
+        let timeout = Duration::from_millis(100);
+
+        // neither side crossed yet
+        let diagnostics = pulse.diagnostics();
+        assert_eq!(diagnostics.since_produced, None);
+        assert_eq!(diagnostics.stalled(timeout), None);
+
+        let sync = SyncState::new();
+        pulse.produced(1, StorageSection::Front);
+        pulse.consumed(1, &sync);
+        pulse.set_waiting(Some(StorageSection::Back));
+        let diagnostics = pulse.diagnostics();
+        assert_eq!(diagnostics.stalled(timeout), None);
+        assert_eq!(diagnostics.waiting, Some(StorageSection::Back));
+        assert_eq!((diagnostics.published, diagnostics.consumed), (1, 1));
+
+        let stale = Some(Duration::from_millis(150));
+        let fresh = Some(Duration::ZERO);
+        let cases = [
+            (stale, fresh, Some(StalledSide::Producer)),
+            (fresh, stale, Some(StalledSide::Consumer)),
+            (stale, stale, Some(StalledSide::Both)),
+            (stale, None, Some(StalledSide::Producer)),
+        ];
+        for (since_produced, since_consumed, side) in cases {
+            let mut stalled = diagnostics;
+            stalled.since_produced = since_produced;
+            stalled.since_consumed = since_consumed;
+            assert_eq!(stalled.stalled(timeout), side);
+        }
+
+        let mut locked = diagnostics;
+        locked.locks = StorageSection::Spare as u8;
+        assert!(locked.to_string().contains("pending fences: [Spare]"));
+    }
+}
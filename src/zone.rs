@@ -0,0 +1,275 @@
+//! Profiler zones around the phases of the update and render threads.
+//!
+//! With the `tracy` feature, the phases of a frame are recorded as zones of
+//! the [Tracy](https://github.com/wolfpld/tracy) profiler:
+//!
+//! * `update`, around each fixed step, and `upload`, around the upload of
+//!   the frame data, on the update thread;
+//! * `draw`, around each frame, and `cross`, around the consumption of the
+//!   frame data and the drawing of the scene, on the render thread;
+//! * the GPU time of the `frame` and of its `scene`, as GPU zones measured
+//!   with `GL_TIMESTAMP` queries, see [`GpuZones`].
+//!
+//! Handlers can add their own zones with [`zone`] and [`GpuZones`].
+//! Without the feature, zones compile to nothing and no query is issued.
+//!
+//! # Example
+//! ```rust,ignore
+//! fn fixed_step(&mut self, ...) {
+//!     let _zone = ethel::zone::zone("physics");
+//!     self.integrate(delta);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::render::NotSend;
+
+/// Whether zones are recorded, i.e. the crate is built with the `tracy`
+/// feature.
+pub const ENABLED: bool = api::ENABLED;
+
+/// A CPU zone, ending when dropped.
+#[must_use = "the zone ends when dropped"]
+pub struct Zone(#[allow(dead_code)] Option<api::Span>);
+
+impl std::fmt::Debug for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Zone").field(&self.0.is_some()).finish()
+    }
+}
+
+/// Start a CPU zone named `name` on the current thread.
+#[inline]
+pub fn zone(name: &'static str) -> Zone {
+    Zone(api::span(name))
+}
+
+/// Mark the end of a frame, separating the frames of the profiler.
+#[inline]
+pub fn frame_mark() {
+    api::frame_mark();
+}
+
+/// A GPU zone, between two timestamp queries.
+struct GpuZone {
+    queries: [u32; 2],
+    span: Option<api::GpuSpan>,
+    ended: bool,
+}
+
+/// GPU zones measured with `GL_TIMESTAMP` queries, on the render thread.
+///
+/// Zones may be nested. Their timestamps are only read once available, which
+/// takes a few frames, with [`collect`](Self::collect), so that measuring
+/// never stalls the render thread.
+pub struct GpuZones {
+    context: Option<api::GpuContext>,
+    /// The zones whose timestamps were not read yet, oldest first.
+    pending: VecDeque<GpuZone>,
+    /// Query pairs of collected zones, reused by the next zones.
+    free: Vec<[u32; 2]>,
+
+    _marker: NotSend,
+}
+
+impl Default for GpuZones {
+    fn default() -> Self {
+        Self {
+            context: None,
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl std::fmt::Debug for GpuZones {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuZones")
+            .field("pending", &self.pending.len())
+            .field("free", &self.free.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl GpuZones {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a GPU zone named `name`, timing the GL commands issued until
+    /// the matching [`end`](Self::end).
+    pub fn begin(&mut self, name: &'static str) {
+        if !ENABLED {
+            return;
+        }
+        if self.context.is_none() {
+            let mut timestamp = 0;
+            unsafe {
+                janus::gl::GetInteger64v(janus::gl::TIMESTAMP, &mut timestamp);
+            }
+            self.context = api::gpu_context(timestamp);
+        }
+        let Some(span) = self
+            .context
+            .as_ref()
+            .and_then(|ctx| api::gpu_span(ctx, name))
+        else {
+            return;
+        };
+
+        let queries = self.free.pop().unwrap_or_else(|| {
+            let mut queries = [0; 2];
+            unsafe {
+                janus::gl::CreateQueries(janus::gl::TIMESTAMP, 2, queries.as_mut_ptr());
+            }
+            queries
+        });
+        unsafe {
+            janus::gl::QueryCounter(queries[0], janus::gl::TIMESTAMP);
+        }
+        self.pending.push_back(GpuZone {
+            queries,
+            span: Some(span),
+            ended: false,
+        });
+    }
+
+    /// End the innermost GPU zone.
+    pub fn end(&mut self) {
+        let Some(zone) = self.pending.iter_mut().rev().find(|zone| !zone.ended) else {
+            return;
+        };
+        unsafe {
+            janus::gl::QueryCounter(zone.queries[1], janus::gl::TIMESTAMP);
+        }
+        if let Some(span) = &mut zone.span {
+            api::gpu_end(span);
+        }
+        zone.ended = true;
+    }
+
+    /// Send the timestamps of the oldest ended zones to the profiler, as long
+    /// as they are available.
+    pub fn collect(&mut self) {
+        while let Some(zone) = self.pending.front()
+            && zone.ended
+        {
+            let [start, end] = zone.queries;
+            let mut available = 0;
+            unsafe {
+                janus::gl::GetQueryObjectiv(end, janus::gl::QUERY_RESULT_AVAILABLE, &mut available);
+            }
+            if available == 0 {
+                return;
+            }
+
+            let (mut start_ns, mut end_ns) = (0, 0);
+            unsafe {
+                janus::gl::GetQueryObjecti64v(start, janus::gl::QUERY_RESULT, &mut start_ns);
+                janus::gl::GetQueryObjecti64v(end, janus::gl::QUERY_RESULT, &mut end_ns);
+            }
+            if let Some(mut zone) = self.pending.pop_front() {
+                if let Some(span) = zone.span.take() {
+                    api::gpu_upload(span, start_ns, end_ns);
+                }
+                self.free.push(zone.queries);
+            }
+        }
+    }
+}
+
+impl Drop for GpuZones {
+    fn drop(&mut self) {
+        let queries = self
+            .pending
+            .iter()
+            .map(|zone| zone.queries)
+            .chain(self.free.iter().copied())
+            .flatten()
+            .collect::<Vec<_>>();
+        if !queries.is_empty() {
+            unsafe {
+                janus::gl::DeleteQueries(queries.len() as i32, queries.as_ptr());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tracy")]
+mod api {
+    use tracy_client::{Client, GpuContextType};
+
+    pub(super) use tracy_client::{GpuContext, GpuSpan, Span};
+
+    pub(super) const ENABLED: bool = true;
+
+    #[inline]
+    pub(super) fn span(name: &'static str) -> Option<Span> {
+        Some(Client::start().span_alloc(Some(name), "", "", 0, 0))
+    }
+
+    #[inline]
+    pub(super) fn frame_mark() {
+        Client::start().frame_mark();
+    }
+
+    // timestamps are in nanoseconds, as are GL timestamps
+    pub(super) fn gpu_context(timestamp: i64) -> Option<GpuContext> {
+        match Client::start().new_gpu_context(Some("GL"), GpuContextType::OpenGL, timestamp, 1.0) {
+            Ok(context) => Some(context),
+            Err(err) => {
+                use tracing::Level;
+                tracing::event!(
+                    name: "zone.gpu",
+                    Level::WARN,
+                    "GPU zones are not available: {err}"
+                );
+                None
+            }
+        }
+    }
+
+    pub(super) fn gpu_span(context: &GpuContext, name: &'static str) -> Option<GpuSpan> {
+        context.span_alloc(name, "", "", 0).ok()
+    }
+
+    pub(super) fn gpu_end(span: &mut GpuSpan) {
+        span.end_zone();
+    }
+
+    pub(super) fn gpu_upload(span: GpuSpan, start: i64, end: i64) {
+        span.upload_timestamp_start(start);
+        span.upload_timestamp_end(end);
+    }
+}
+
+#[cfg(not(feature = "tracy"))]
+mod api {
+    pub(super) type Span = ();
+    pub(super) type GpuContext = ();
+    pub(super) type GpuSpan = ();
+
+    pub(super) const ENABLED: bool = false;
+
+    #[inline]
+    pub(super) fn span(_name: &'static str) -> Option<Span> {
+        None
+    }
+
+    #[inline]
+    pub(super) fn frame_mark() {}
+
+    pub(super) fn gpu_context(_timestamp: i64) -> Option<GpuContext> {
+        None
+    }
+
+    pub(super) fn gpu_span(_context: &GpuContext, _name: &'static str) -> Option<GpuSpan> {
+        None
+    }
+
+    pub(super) fn gpu_end(_span: &mut GpuSpan) {}
+
+    pub(super) fn gpu_upload(_span: GpuSpan, _start: i64, _end: i64) {}
+}